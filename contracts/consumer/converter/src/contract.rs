@@ -5,12 +5,13 @@ use cosmwasm_std::{
 use cw2::set_contract_version;
 use cw_storage_plus::Item;
 use cw_utils::{must_pay, nonpayable, parse_instantiate_response_data};
-use mesh_apis::ibc::ConsumerPacket;
+use mesh_apis::ibc::{convert, ConsumerPacket, UnstakeInfo};
 use sylvia::types::{ExecCtx, InstantiateCtx, QueryCtx, ReplyCtx};
 use sylvia::{contract, schemars};
 
 use mesh_apis::converter_api::{self, ConverterApi, RewardInfo};
 use mesh_apis::price_feed_api;
+use mesh_apis::reply::ReplyId;
 use mesh_apis::virtual_staking_api;
 
 use crate::error::ContractError;
@@ -23,13 +24,17 @@ use crate::state::Config;
 pub const CONTRACT_NAME: &str = env!("CARGO_PKG_NAME");
 pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
-const REPLY_ID_INSTANTIATE: u64 = 1;
-
 pub struct ConverterContract<'a> {
     pub config: Item<'a, Config>,
     pub virtual_stake: Item<'a, Addr>,
 }
 
+impl Default for ConverterContract<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg_attr(not(feature = "library"), sylvia::entry_points)]
 #[contract]
 #[error(ContractError)]
@@ -89,16 +94,16 @@ impl ConverterContract<'_> {
             funds: vec![],
             label: format!("Virtual Staking: {}", &config.remote_denom),
         };
-        let init_msg = SubMsg::reply_on_success(init_msg, REPLY_ID_INSTANTIATE);
+        let init_msg = SubMsg::reply_on_success(init_msg, ReplyId::Instantiate.into());
 
         Ok(Response::new().add_submessage(init_msg))
     }
 
     #[msg(reply)]
     fn reply(&self, ctx: ReplyCtx, reply: Reply) -> Result<Response, ContractError> {
-        match reply.id {
-            REPLY_ID_INSTANTIATE => self.reply_init_callback(ctx.deps, reply.result.unwrap()),
-            _ => Err(ContractError::InvalidReplyId(reply.id)),
+        match ReplyId::try_from(reply.id).map_err(ContractError::InvalidReplyId)? {
+            ReplyId::Instantiate => self.reply_init_callback(ctx.deps, reply.result.unwrap()),
+            id => Err(ContractError::InvalidReplyId(id.into())),
         }
     }
 
@@ -156,6 +161,27 @@ impl ConverterContract<'_> {
         }
     }
 
+    /// This is only used for tests.
+    /// Ideally we want conditional compilation of these whole methods and the enum variants
+    #[msg(exec)]
+    fn test_restake(
+        &self,
+        ctx: ExecCtx,
+        from: Vec<UnstakeInfo>,
+        to_validator: String,
+    ) -> Result<Response, ContractError> {
+        #[cfg(any(test, feature = "mt"))]
+        {
+            // This can only ever be called in tests
+            self.restake(ctx.deps, from, to_validator)
+        }
+        #[cfg(not(any(test, feature = "mt")))]
+        {
+            let _ = (ctx, from, to_validator);
+            Err(ContractError::Unauthorized)
+        }
+    }
+
     #[msg(query)]
     fn config(&self, ctx: QueryCtx) -> Result<ConfigResponse, ContractError> {
         let config = self.config.load(ctx.deps.storage)?;
@@ -215,6 +241,27 @@ impl ConverterContract<'_> {
         Ok(Response::new().add_message(msg).add_event(event))
     }
 
+    /// This is called by ibc_packet_receive.
+    /// It is pulled out into a method, so it can also be called by test_restake for testing
+    pub(crate) fn restake(
+        &self,
+        mut deps: DepsMut,
+        from: Vec<UnstakeInfo>,
+        to_validator: String,
+    ) -> Result<Response, ContractError> {
+        let mut res = Response::new();
+        for UnstakeInfo { validator, unstake } in from {
+            let unbond = self.unstake(deps.branch(), validator, unstake.clone())?;
+            let bond = self.stake(deps.branch(), to_validator.clone(), unstake)?;
+            res = res
+                .add_submessages(unbond.messages)
+                .add_events(unbond.events)
+                .add_submessages(bond.messages)
+                .add_events(bond.events);
+        }
+        Ok(res)
+    }
+
     fn normalize_price(&self, deps: Deps, amount: Coin) -> Result<Coin, ContractError> {
         let config = self.config.load(deps.storage)?;
         ensure_eq!(
@@ -231,12 +278,13 @@ impl ConverterContract<'_> {
         use price_feed_api::Querier;
         let remote = price_feed_api::Remote::new(config.price_feed);
         let price = remote.querier(&deps.querier).price()?.native_per_foreign;
-        let converted = (amount.amount * price) * config.price_adjustment;
+        let converted = convert(
+            &amount,
+            price * config.price_adjustment,
+            &config.local_denom,
+        )?;
 
-        Ok(Coin {
-            denom: config.local_denom,
-            amount: converted,
-        })
+        Ok(converted)
     }
 
     pub(crate) fn transfer_rewards(
@@ -1,6 +1,6 @@
 use cosmwasm_std::{StdError, Uint128};
 use cw_utils::{ParseReplyError, PaymentError};
-use mesh_apis::ibc::VersionError;
+use mesh_apis::ibc::{ConversionError, VersionError};
 use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq)]
@@ -17,6 +17,9 @@ pub enum ContractError {
     #[error("{0}")]
     ParseReply(#[from] ParseReplyError),
 
+    #[error("{0}")]
+    Conversion(#[from] ConversionError),
+
     #[error("Unauthorized")]
     Unauthorized,
 
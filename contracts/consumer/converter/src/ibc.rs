@@ -11,7 +11,8 @@ use cw_storage_plus::Item;
 
 use mesh_apis::ibc::{
     ack_success, validate_channel_order, AckWrapper, AddValidator, ConsumerPacket, ProtocolVersion,
-    ProviderPacket, RemoveValidator, StakeAck, TransferRewardsAck, UnstakeAck, PROTOCOL_NAME,
+    ProviderPacket, RemoveValidator, RequestValidatorsAck, RestakeAck, StakeAck,
+    TransferRewardsAck, UnstakeAck, UnstakeBatchAck, UnstakeInfo, PROTOCOL_NAME,
 };
 use sylvia::types::ExecCtx;
 
@@ -120,12 +121,8 @@ pub fn ibc_channel_connect(
     Ok(IbcBasicResponse::new().add_message(msg))
 }
 
-pub(crate) fn add_validators_msg(
-    env: &Env,
-    channel: &IbcChannel,
-    validators: &[Validator],
-) -> Result<IbcMsg, ContractError> {
-    let updates = validators
+fn add_validators(env: &Env, validators: &[Validator]) -> Vec<AddValidator> {
+    validators
         .iter()
         .map(|v| AddValidator {
             valoper: v.address.clone(),
@@ -136,8 +133,15 @@ pub(crate) fn add_validators_msg(
             start_height: env.block.height,
             start_time: env.block.time.seconds(),
         })
-        .collect();
-    let packet = ConsumerPacket::AddValidators(updates);
+        .collect()
+}
+
+pub(crate) fn add_validators_msg(
+    env: &Env,
+    channel: &IbcChannel,
+    validators: &[Validator],
+) -> Result<IbcMsg, ContractError> {
+    let packet = ConsumerPacket::AddValidators(add_validators(env, validators));
     let msg = IbcMsg::SendPacket {
         channel_id: channel.endpoint.channel_id.clone(),
         data: to_binary(&packet)?,
@@ -208,8 +212,8 @@ pub fn ibc_channel_close(
 /// We cannot return any meaningful response value as we do not know the response value
 /// of execution. We just return ok if we dispatched, error if we failed to dispatch
 pub fn ibc_packet_receive(
-    deps: DepsMut,
-    _env: Env,
+    mut deps: DepsMut,
+    env: Env,
     msg: IbcPacketReceiveMsg,
 ) -> Result<IbcReceiveResponse, ContractError> {
     let packet: ProviderPacket = from_slice(&msg.packet.data)?;
@@ -218,12 +222,13 @@ pub fn ibc_packet_receive(
         ProviderPacket::Stake {
             validator,
             stake,
-            tx_id: _,
+            tx_id,
         } => {
             let response = contract.stake(deps, validator, stake)?;
-            let ack = ack_success(&StakeAck {})?;
+            let ack = ack_success(&StakeAck { tx_id: Some(tx_id) })?;
             IbcReceiveResponse::new()
                 .set_ack(ack)
+                .add_attribute("tx_id", tx_id.to_string())
                 .add_submessages(response.messages)
                 .add_events(response.events)
                 .add_attributes(response.attributes)
@@ -231,16 +236,43 @@ pub fn ibc_packet_receive(
         ProviderPacket::Unstake {
             validator,
             unstake,
-            tx_id: _,
+            tx_id,
         } => {
             let response = contract.unstake(deps, validator, unstake)?;
-            let ack = ack_success(&UnstakeAck {})?;
+            let ack = ack_success(&UnstakeAck { tx_id: Some(tx_id) })?;
             IbcReceiveResponse::new()
                 .set_ack(ack)
+                .add_attribute("tx_id", tx_id.to_string())
                 .add_submessages(response.messages)
                 .add_events(response.events)
                 .add_attributes(response.attributes)
         }
+        ProviderPacket::UnstakeBatch { unstakes, tx_id } => {
+            let mut res = IbcReceiveResponse::new()
+                .set_ack(ack_success(&UnstakeBatchAck { tx_id: Some(tx_id) })?)
+                .add_attribute("tx_id", tx_id.to_string());
+            for UnstakeInfo { validator, unstake } in unstakes {
+                let response = contract.unstake(deps.branch(), validator, unstake)?;
+                res = res
+                    .add_submessages(response.messages)
+                    .add_events(response.events)
+                    .add_attributes(response.attributes);
+            }
+            res
+        }
+        ProviderPacket::Restake {
+            from,
+            to_validator,
+            tx_id,
+        } => {
+            let response = contract.restake(deps, from, to_validator)?;
+            let ack = ack_success(&RestakeAck { tx_id: Some(tx_id) })?;
+            IbcReceiveResponse::new()
+                .set_ack(ack)
+                .add_attribute("tx_id", tx_id.to_string())
+                .add_submessages(response.messages)
+                .add_events(response.events)
+        }
         ProviderPacket::TransferRewards {
             rewards, recipient, ..
         } => {
@@ -248,6 +280,13 @@ pub fn ibc_packet_receive(
             let ack = ack_success(&TransferRewardsAck {})?;
             IbcReceiveResponse::new().set_ack(ack).add_message(msg)
         }
+        ProviderPacket::RequestValidators {} => {
+            let validators = deps.querier.query_all_validators()?;
+            let ack = ack_success(&RequestValidatorsAck {
+                validators: add_validators(&env, &validators),
+            })?;
+            IbcReceiveResponse::new().set_ack(ack)
+        }
     };
     Ok(res)
 }
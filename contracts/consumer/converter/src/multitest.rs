@@ -3,6 +3,7 @@ mod virtual_staking_mock;
 use cosmwasm_std::{coin, coins, Addr, Decimal, StdError, Uint128, Validator};
 use cw_multi_test::App as MtApp;
 use mesh_apis::converter_api::RewardInfo;
+use mesh_apis::ibc::UnstakeInfo;
 use sylvia::multitest::App;
 
 use crate::contract;
@@ -200,6 +201,89 @@ fn ibc_stake_and_unstake() {
     );
 }
 
+#[test]
+fn ibc_restake() {
+    let app = App::default();
+
+    let owner = "sunny"; // Owner of the staking contract (i. e. the vault contract)
+    let admin = "theman";
+    let discount = Decimal::percent(40); // 1 OSMO worth of JUNO should give 0.6 OSMO of stake
+    let native_per_foreign = Decimal::percent(50); // 1 JUNO is worth 0.5 OSMO
+
+    let SetupResponse {
+        price_feed: _,
+        converter,
+        virtual_staking,
+    } = setup(
+        &app,
+        SetupArgs {
+            owner,
+            admin,
+            discount,
+            native_per_foreign,
+        },
+    );
+
+    let val1 = "Val Kilmer";
+    let val2 = "Valley Girl";
+    let val3 = "Valium";
+    converter
+        .test_stake(val1.to_string(), coin(1000, JUNO))
+        .call(owner)
+        .unwrap();
+    converter
+        .test_stake(val2.to_string(), coin(4000, JUNO))
+        .call(owner)
+        .unwrap();
+
+    // Each source stake is unbonded, and bonded on the target
+    converter
+        .test_restake(
+            vec![
+                UnstakeInfo {
+                    validator: val1.to_string(),
+                    unstake: coin(1000, JUNO),
+                },
+                UnstakeInfo {
+                    validator: val2.to_string(),
+                    unstake: coin(2000, JUNO),
+                },
+            ],
+            val3.to_string(),
+        )
+        .call(owner)
+        .unwrap();
+
+    // (1000 * 0.6 * 0.5 = 300) (2000 * 0.6 * 0.5 = 600)
+    assert_eq!(
+        virtual_staking.all_stake().unwrap().stakes,
+        vec![
+            (val1.to_string(), Uint128::zero()),
+            (val3.to_string(), Uint128::new(900)),
+            (val2.to_string(), Uint128::new(600)),
+        ]
+    );
+
+    // Amounts are converted in the same way
+    let err = converter
+        .test_restake(
+            vec![UnstakeInfo {
+                validator: val2.to_string(),
+                unstake: coin(100, "uatom"),
+            }],
+            val3.to_string(),
+        )
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::WrongDenom {
+            sent: "uatom".to_owned(),
+            expected: JUNO.to_owned()
+        }
+    );
+}
+
 #[test]
 fn valset_update_works() {
     let app = App::default();
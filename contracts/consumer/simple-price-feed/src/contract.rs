@@ -18,6 +18,12 @@ pub struct SimplePriceFeedContract<'a> {
     pub config: Item<'a, Config>,
 }
 
+impl Default for SimplePriceFeedContract<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg_attr(not(feature = "library"), sylvia::entry_points)]
 #[contract]
 #[error(ContractError)]
@@ -17,6 +17,7 @@ use mesh_bindings::{
 use sylvia::types::{ExecCtx, InstantiateCtx, QueryCtx, ReplyCtx};
 use sylvia::{contract, schemars};
 
+use mesh_apis::reply::ReplyId;
 use mesh_apis::virtual_staking_api::{self, SudoMsg, VirtualStakingApi};
 
 use crate::error::ContractError;
@@ -47,6 +48,12 @@ pub struct VirtualStakingContract<'a> {
     pub jailed: Item<'a, Vec<String>>,
 }
 
+impl Default for VirtualStakingContract<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg_attr(not(feature = "library"), sylvia::entry_points)]
 #[contract]
 #[error(ContractError)]
@@ -255,9 +262,10 @@ impl VirtualStakingContract<'_> {
 
     #[msg(reply)]
     fn reply(&self, ctx: ReplyCtx, reply: Reply) -> Result<Response, ContractError> {
-        match (reply.id, reply.result.into_result()) {
-            (REPLY_REWARDS_ID, Ok(_)) => self.reply_rewards(ctx.deps, ctx.env),
-            (REPLY_REWARDS_ID, Err(e)) => {
+        let id = ReplyId::try_from(reply.id).map_err(ContractError::InvalidReplyId)?;
+        match (id, reply.result.into_result()) {
+            (ReplyId::Rewards, Ok(_)) => self.reply_rewards(ctx.deps, ctx.env),
+            (ReplyId::Rewards, Err(e)) => {
                 // We need to pop the REWARD_TARGETS so it doesn't get out of sync
                 let (target, _) = pop_target(ctx.deps)?;
                 // Ignore errors, so the rest doesn't fail, but report them.
@@ -266,7 +274,7 @@ impl VirtualStakingContract<'_> {
                     .add_attribute("target", target);
                 Ok(Response::new().add_event(evt))
             }
-            (id, _) => Err(ContractError::InvalidReplyId(id)),
+            (id, _) => Err(ContractError::InvalidReplyId(id.into())),
         }
     }
 
@@ -385,7 +393,6 @@ fn calculate_rebalance(
 
 const REWARD_TARGETS: Item<Vec<String>> = Item::new("reward_targets");
 const VALIDATOR_REWARDS_BATCH: ValidatorRewardsBatch = ValidatorRewardsBatch::new();
-const REPLY_REWARDS_ID: u64 = 1;
 
 struct ValidatorRewardsBatch<'a> {
     rewards: Item<'a, Vec<RewardInfo>>,
@@ -452,7 +459,7 @@ fn withdraw_reward_msgs<T: CustomQuery>(
                 DistributionMsg::WithdrawDelegatorReward {
                     validator: validator.clone(),
                 },
-                REPLY_REWARDS_ID,
+                ReplyId::Rewards.into(),
             )
         })
         .collect()
@@ -1,31 +1,45 @@
 use cosmwasm_std::{
-    coin, ensure, ensure_eq, to_binary, Coin, Decimal, DepsMut, Env, Event, IbcMsg, Order,
-    Response, StdResult, Storage, Uint128, Uint256, WasmMsg,
+    coin, coins, ensure, ensure_eq, from_binary, to_binary, Addr, BankMsg, Binary, BlockInfo, Coin,
+    Decimal, Deps, DepsMut, Env, Event, IbcMsg, Order, Response, StdResult, Storage, Timestamp,
+    Uint128, Uint256, WasmMsg,
 };
 use cw2::set_contract_version;
-use cw_storage_plus::{Bounder, Item, Map};
-use cw_utils::{nonpayable, PaymentError};
+use cw_storage_plus::{Bound, Bounder, Deque, Item, Map, PrimaryKey};
+use cw_utils::{must_pay, nonpayable, PaymentError};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use std::cmp::min;
+use std::collections::{BTreeMap, BTreeSet};
 
 use mesh_apis::converter_api::RewardInfo;
 use sylvia::contract;
-use sylvia::types::{ExecCtx, InstantiateCtx, QueryCtx};
+use sylvia::types::{ExecCtx, InstantiateCtx, MigrateCtx, QueryCtx};
 
 use mesh_apis::cross_staking_api::{self};
-use mesh_apis::ibc::ProviderPacket;
-use mesh_apis::vault_api::{SlashInfo, VaultApiHelper};
-use mesh_sync::{Tx, ValueRange};
+use mesh_apis::ibc::{AddValidator, ProviderPacket, UnstakeInfo};
+use mesh_apis::vault_api::{SlashInfo, SlashPayout, VaultApiHelper};
+use mesh_sync::{Tx, TxHistory, TxStatus, ValueRange, DEFAULT_TX_HISTORY_LEN};
 
-use crate::crdt::CrdtState;
+use crate::crdt::{CrdtState, ValUpdate, ValidatorState};
 use crate::error::ContractError;
+use crate::evidence::DoubleSignEvidence;
 use crate::ibc::{packet_timeout, IBC_CHANNEL};
 use crate::msg::{
     AllPendingRewards, AllTxsResponse, AuthorizedEndpointResponse, ConfigResponse,
-    IbcChannelResponse, ListRemoteValidatorsResponse, PendingRewards, StakeInfo, StakesResponse,
-    TxResponse, ValidatorPendingRewards,
+    DenomRewardDebug, EstimatedRelease, EstimatedReleaseResponse, IbcChannelResponse,
+    LastValidatorSyncResponse, ListRemoteValidatorsResponse, LockedStake, LockedStakesResponse,
+    NetPositionResponse, PendingRewards, ReceiveVirtualStake, RewardDebug, ScheduledUnlock,
+    StakeInfo, StakesOrderBy, StakesResponse, StatsResponse, SyncStatusResponse,
+    TotalWithdrawnResponse, TxResponse, TxsHistoryResponse, UnbondListingsResponse,
+    UnlockScheduleResponse, ValidatorPendingRewards, ValidatorResponse, ValidatorSlash,
+    ValidatorSlashesResponse, ValidatorStatus, ValsetSyncResponse,
 };
 use crate::stakes::Stakes;
-use crate::state::{Config, Distribution, Stake};
+use crate::state::{
+    Config, Distribution, DustPolicy, PendingUnbond, Redelegation, RewardDenom, SlashRecord,
+    SlashRedistribution, Stake, UnbondListing, UserMeta,
+};
+use crate::txs::PendingTxs;
 
 pub const CONTRACT_NAME: &str = env!("CARGO_PKG_NAME");
 pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -33,24 +47,109 @@ pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 pub const DEFAULT_PAGE_LIMIT: u32 = 10;
 pub const MAX_PAGE_LIMIT: u32 = 30;
 
+/// Number of validators added to the valset per message while syncing it, when not specified
+pub const DEFAULT_VALSET_SYNC_LIMIT: u32 = 30;
+pub const MAX_VALSET_SYNC_LIMIT: u32 = 100;
+
+/// Number of validators credited per message while redistributing slashes, when not specified
+pub const DEFAULT_REDISTRIBUTION_LIMIT: u32 = 30;
+pub const MAX_REDISTRIBUTION_LIMIT: u32 = 100;
+
+/// Max number of tombstoned validators checked for pruning per valset update
+pub const PRUNE_VALIDATORS_LIMIT: u32 = 10;
+
+/// Max number of validators a user can stake on, on new contracts
+pub const DEFAULT_MAX_VALIDATORS_PER_USER: u32 = 50;
+
+/// Min number of blocks between two `request_validator_sync` calls
+pub const VALIDATOR_SYNC_INTERVAL: u64 = 100;
+
+/// Points per unit of rewards. Rewards are tracked in points so that amounts not divisible by
+/// the total stake are split as precisely as possible, whatever the decimals of their denom
 pub const DISTRIBUTION_POINTS_SCALE: Uint256 = Uint256::from_u128(1_000_000_000);
 
+/// Owner, unstakes per validator, and creation time of an unstake tx
+type UnstakeTx = (Addr, Vec<(String, Uint128)>, Timestamp);
+
 /// Aligns pagination limit
 fn clamp_page_limit(limit: Option<u32>) -> usize {
-    limit.unwrap_or(DEFAULT_PAGE_LIMIT).max(MAX_PAGE_LIMIT) as usize
+    limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT) as usize
+}
+
+fn join_coins(coins: &[Coin]) -> String {
+    coins
+        .iter()
+        .map(Coin::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Updates a maintained stats counter, a missing one counting as zero
+fn update_stat<T>(
+    storage: &mut dyn Storage,
+    stat: &Item<T>,
+    update: impl FnOnce(T) -> T,
+) -> StdResult<()>
+where
+    T: Default + Serialize + DeserializeOwned,
+{
+    let value = stat.may_load(storage)?.unwrap_or_default();
+    stat.save(storage, &update(value))
 }
 
 pub struct ExternalStakingContract<'a> {
     pub config: Item<'a, Config>,
     /// Stakes indexed by `(owner, validator)` pair
     pub stakes: Stakes<'a>,
-    /// Per-validator distribution information
-    pub distribution: Map<'a, &'a str, Distribution>,
+    /// Per-validator and rewards denom distribution information
+    pub distribution: Map<'a, (&'a str, &'a str), Distribution>,
     /// Pending txs information
     pub tx_count: Item<'a, u64>,
-    pub pending_txs: Map<'a, u64, Tx>,
+    pub pending_txs: PendingTxs<'a>,
+    /// Recently resolved txs
+    pub tx_history: TxHistory<'a>,
     /// Valset CRDT
     pub val_set: CrdtState<'a>,
+    /// Validators received from the consumer, not yet added to the valset
+    pub valset_backlog: Deque<'a, AddValidator>,
+    /// Whether the first `AddValidators` packet from the consumer was fully processed
+    pub valset_synced: Item<'a, bool>,
+    /// Height of the last `request_validator_sync` call
+    pub validator_sync_requested: Item<'a, u64>,
+    /// Height at which the consumer validator set was last received
+    pub last_validator_sync: Item<'a, u64>,
+    /// Pending unbonds for sale, indexed by `(owner, validator, release_at)`
+    pub unbond_listings: Map<'a, (&'a Addr, &'a str, u64), UnbondListing>,
+    /// Slashes of the validators, indexed by `(validator, height)`
+    pub slashes: Map<'a, (&'a str, u64), SlashRecord>,
+    /// Stake restaked off each validator, still slashable for it, indexed by
+    /// `(validator, user)`
+    pub redelegations: Map<'a, (&'a str, &'a Addr), Vec<Redelegation>>,
+    /// Activity timestamps of the users
+    pub user_meta: Map<'a, &'a Addr, UserMeta>,
+    /// Number of validators each user has a stake entry on
+    pub validators_count: Map<'a, &'a Addr, u32>,
+    /// Number of users with a stake entry on each validator
+    pub validator_stakers: Map<'a, &'a str, u32>,
+    /// Number of users with a stake entry
+    pub total_stakers: Item<'a, u64>,
+    /// Number of validators with a stake entry
+    pub total_validators: Item<'a, u64>,
+    /// Stake counted in the validator distributions, summed over all the stakes
+    pub total_stake: Item<'a, Uint128>,
+    /// Tokens waiting for their unbonding period, summed over all the stakes
+    pub total_pending_unbonds: Item<'a, Uint128>,
+    /// Rewards distributed to the stakers so far, per rewards denom
+    pub rewards_distributed: Item<'a, BTreeMap<String, Uint128>>,
+    /// Redistributed slashes credited to the stakers so far, in the collateral denom
+    pub slashes_redistributed: Item<'a, Uint128>,
+    /// Rewards withdrawn from the closed stakes of the users, per `(user, rewards denom)`
+    pub closed_withdrawn: Map<'a, (&'a Addr, &'a str), Uint128>,
+    /// Slashes being redistributed, oldest first
+    pub slash_redistributions: Deque<'a, SlashRedistribution>,
+    /// Collateral held for the funded slash redistributions, and the rewards credited out of them
+    /// not withdrawn yet
+    pub redistribution_reserve: Item<'a, Uint128>,
 }
 
 impl Default for ExternalStakingContract<'_> {
@@ -63,16 +162,36 @@ impl Default for ExternalStakingContract<'_> {
 #[contract]
 #[error(ContractError)]
 #[messages(cross_staking_api as CrossStakingApi)]
-#[messages(crate::test_methods as TestMethods)]
+#[cfg_attr(any(test, feature = "mt"), messages(crate::test_methods as TestMethods))]
 impl ExternalStakingContract<'_> {
     pub fn new() -> Self {
         Self {
             config: Item::new("config"),
-            stakes: Stakes::new("stakes", "vals"),
-            distribution: Map::new("distribution"),
-            pending_txs: Map::new("pending_txs"),
+            stakes: Stakes::new("stakes", "vals", "stakes__size"),
+            distribution: Map::new("distributions"),
+            pending_txs: PendingTxs::new("pending_txs", "pending_txs_by_stake"),
+            tx_history: TxHistory::new("tx_history", "tx_history_order", DEFAULT_TX_HISTORY_LEN),
             tx_count: Item::new("tx_count"),
             val_set: CrdtState::new(),
+            valset_backlog: Deque::new("valset_backlog"),
+            valset_synced: Item::new("valset_synced"),
+            validator_sync_requested: Item::new("validator_sync_requested"),
+            last_validator_sync: Item::new("last_validator_sync"),
+            unbond_listings: Map::new("unbond_listings"),
+            slashes: Map::new("slashes"),
+            redelegations: Map::new("redelegations"),
+            user_meta: Map::new("user_meta"),
+            validators_count: Map::new("validators_count"),
+            validator_stakers: Map::new("validator_stakers"),
+            total_stakers: Item::new("total_stakers"),
+            total_validators: Item::new("total_validators"),
+            total_stake: Item::new("total_stake"),
+            total_pending_unbonds: Item::new("total_pending_unbonds"),
+            rewards_distributed: Item::new("rewards_distributed"),
+            slashes_redistributed: Item::new("slashes_redistributed"),
+            closed_withdrawn: Map::new("closed_withdrawn"),
+            slash_redistributions: Deque::new("slash_redistributions"),
+            redistribution_reserve: Item::new("redistribution_reserve"),
         }
     }
 
@@ -90,7 +209,7 @@ impl ExternalStakingContract<'_> {
         &self,
         ctx: InstantiateCtx,
         denom: String,
-        rewards_denom: String,
+        rewards_denoms: Vec<RewardDenom>,
         vault: String,
         unbonding_period: u64,
         remote_contact: crate::msg::AuthorizedEndpoint,
@@ -103,12 +222,26 @@ impl ExternalStakingContract<'_> {
             return Err(ContractError::InvalidMaxSlashing);
         }
 
+        if rewards_denoms.is_empty() {
+            return Err(ContractError::NoRewardsDenoms);
+        }
+
         let config = Config {
             denom,
-            rewards_denom,
+            rewards_denoms,
+            retired_rewards_denoms: vec![],
+            swept_rewards_denoms: BTreeMap::new(),
             vault,
             unbonding_period,
             max_slashing,
+            min_remaining_stake: None,
+            dust_policy: DustPolicy::default(),
+            slash_redistribution: None,
+            evidence_bounty: None,
+            consumer_chain_id: None,
+            max_tracked_validators: None,
+            relay_latency: None,
+            max_validators_per_user: Some(DEFAULT_MAX_VALIDATORS_PER_USER),
         };
 
         self.config.save(ctx.deps.storage, &config)?;
@@ -132,395 +265,1839 @@ impl ExternalStakingContract<'_> {
         Ok(Response::new())
     }
 
-    /// In test code, this is called from `test_commit_stake`.
-    /// In non-test code, this is called from `ibc_packet_ack`
-    pub(crate) fn commit_stake(&self, deps: DepsMut, tx_id: u64) -> Result<WasmMsg, ContractError> {
-        // Load tx
-        let tx = self.pending_txs.load(deps.storage, tx_id)?;
-
-        // Verify tx is of the right type
-        ensure!(
-            matches!(tx, Tx::InFlightRemoteStaking { .. }),
-            ContractError::WrongTypeTx(tx_id, tx)
-        );
-
-        let (tx_amount, tx_user, tx_validator) = match tx {
-            Tx::InFlightRemoteStaking {
-                amount,
-                user,
-                validator,
-                ..
-            } => (amount, user, validator),
-            _ => unreachable!(),
-        };
-
-        // Load stake
-        let mut stake = self
-            .stakes
-            .stake
-            .load(deps.storage, (&tx_user, &tx_validator))?;
-
-        // Load distribution
-        let mut distribution = self
-            .distribution
-            .may_load(deps.storage, &tx_validator)?
-            .unwrap_or_default();
-
-        // Commit stake (saturating up if slashed)
-        stake.stake.commit_add_saturating(tx_amount);
+    /// Sets the minimum stake to be left on a position after an unstake, and how to handle
+    /// unstakes going below it. Only the contract admin can call it.
+    #[msg(exec)]
+    pub fn update_min_remaining_stake(
+        &self,
+        ctx: ExecCtx,
+        min_remaining_stake: Option<Uint128>,
+        dust_policy: DustPolicy,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.ensure_admin(&ctx)?;
 
-        // Distribution alignment
-        stake
-            .points_alignment
-            .stake_increased(tx_amount, distribution.points_per_stake);
-        distribution.total_stake += tx_amount;
+        let mut config = self.config.load(ctx.deps.storage)?;
+        config.min_remaining_stake = min_remaining_stake;
+        config.dust_policy = dust_policy;
+        self.config.save(ctx.deps.storage, &config)?;
 
-        // Save stake
-        self.stakes
-            .stake
-            .save(deps.storage, (&tx_user, &tx_validator), &stake)?;
+        let min_remaining_stake = min_remaining_stake
+            .map(|min| min.to_string())
+            .unwrap_or_else(|| "none".to_owned());
+        Ok(Response::new()
+            .add_attribute("action", "update_min_remaining_stake")
+            .add_attribute("min_remaining_stake", min_remaining_stake))
+    }
 
-        // Save distribution
-        self.distribution
-            .save(deps.storage, &tx_validator, &distribution)?;
+    /// Sets the part of the slashed stake redistributed to the stakers of the other validators,
+    /// instead of being burned. The collateral denom has to be a rewards denom for it to be
+    /// accounted. Only the contract admin can call it.
+    #[msg(exec)]
+    pub fn update_slash_redistribution(
+        &self,
+        ctx: ExecCtx,
+        slash_redistribution: Option<Decimal>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.ensure_admin(&ctx)?;
 
-        // Remove tx
-        self.pending_txs.remove(deps.storage, tx_id);
+        let mut config = self.config.load(ctx.deps.storage)?;
+        if let Some(ratio) = slash_redistribution {
+            ensure!(
+                ratio <= Decimal::one(),
+                ContractError::InvalidSlashRedistribution
+            );
+            ensure!(
+                config.is_rewards_denom(&config.denom),
+                ContractError::SlashRedistributionDenom(config.denom)
+            );
+        }
+        config.slash_redistribution = slash_redistribution;
+        self.config.save(ctx.deps.storage, &config)?;
 
-        // Call commit hook on vault
-        let cfg = self.config.load(deps.storage)?;
-        let msg = cfg.vault.commit_tx(tx_id)?;
-        Ok(msg)
+        let slash_redistribution = slash_redistribution
+            .map(|ratio| ratio.to_string())
+            .unwrap_or_else(|| "none".to_owned());
+        Ok(Response::new()
+            .add_attribute("action", "update_slash_redistribution")
+            .add_attribute("slash_redistribution", slash_redistribution))
     }
 
-    /// In test code, this is called from `test_rollback_stake`.
-    /// In non-test code, this is called from `ibc_packet_ack` or `ibc_packet_timeout`
-    pub(crate) fn rollback_stake(
+    /// Sets the part of the stake slashed on submitted evidence paid to the submitter. Only the
+    /// contract admin can call it.
+    #[msg(exec)]
+    pub fn update_evidence_bounty(
         &self,
-        deps: DepsMut,
-        tx_id: u64,
-    ) -> Result<WasmMsg, ContractError> {
-        // Load tx
-        let tx = self.pending_txs.load(deps.storage, tx_id)?;
-
-        // Verify tx is of the right type
-        ensure!(
-            matches!(tx, Tx::InFlightRemoteStaking { .. }),
-            ContractError::WrongTypeTx(tx_id, tx)
-        );
+        ctx: ExecCtx,
+        evidence_bounty: Option<Decimal>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.ensure_admin(&ctx)?;
 
-        let (tx_amount, tx_user, tx_validator) = match tx {
-            Tx::InFlightRemoteStaking {
-                amount,
-                user,
-                validator,
-                ..
-            } => (amount, user, validator),
-            _ => unreachable!(),
-        };
+        if let Some(ratio) = evidence_bounty {
+            ensure!(
+                ratio <= Decimal::one(),
+                ContractError::InvalidEvidenceBounty
+            );
+        }
 
-        // Load stake
-        let mut stake = self
-            .stakes
-            .stake
-            .load(deps.storage, (&tx_user, &tx_validator))?;
+        let mut config = self.config.load(ctx.deps.storage)?;
+        config.evidence_bounty = evidence_bounty;
+        self.config.save(ctx.deps.storage, &config)?;
 
-        // Rollback add amount (saturating up if slashed)
-        stake.stake.rollback_add_saturating(tx_amount);
+        let evidence_bounty = evidence_bounty
+            .map(|ratio| ratio.to_string())
+            .unwrap_or_else(|| "none".to_owned());
+        Ok(Response::new()
+            .add_attribute("action", "update_evidence_bounty")
+            .add_attribute("evidence_bounty", evidence_bounty))
+    }
 
-        // Save stake
-        self.stakes
-            .stake
-            .save(deps.storage, (&tx_user, &tx_validator), &stake)?;
+    /// Sets the chain id of the consumer, which the submitted evidence has to be for. Only the
+    /// contract admin can call it.
+    #[msg(exec)]
+    pub fn update_consumer_chain_id(
+        &self,
+        ctx: ExecCtx,
+        consumer_chain_id: Option<String>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.ensure_admin(&ctx)?;
 
-        // Remove tx
-        self.pending_txs.remove(deps.storage, tx_id);
+        let mut config = self.config.load(ctx.deps.storage)?;
+        config.consumer_chain_id = consumer_chain_id;
+        self.config.save(ctx.deps.storage, &config)?;
 
-        // Call rollback hook on vault
-        let cfg = self.config.load(deps.storage)?;
-        let msg = cfg.vault.rollback_tx(tx_id)?;
-        Ok(msg)
+        let consumer_chain_id = config
+            .consumer_chain_id
+            .unwrap_or_else(|| "none".to_owned());
+        Ok(Response::new()
+            .add_attribute("action", "update_consumer_chain_id")
+            .add_attribute("consumer_chain_id", consumer_chain_id))
     }
 
-    /// Schedules tokens for release, adding them to the pending unbonds. After the unbonding period
-    /// passes, funds are ready to be released through a `withdraw_unbonded` call by the user.
+    /// Sets the max number of validators tracked. Over it, the validators tombstoned the earliest
+    /// without any stake on them are not tracked anymore, starting with the next valset update.
+    /// Only the contract admin can call it.
     #[msg(exec)]
-    pub fn unstake(
+    pub fn update_max_tracked_validators(
         &self,
         ctx: ExecCtx,
-        validator: String,
-        amount: Coin,
+        max_tracked_validators: Option<u32>,
     ) -> Result<Response, ContractError> {
-        let ExecCtx { info, deps, env } = ctx;
-        nonpayable(&info)?;
+        nonpayable(&ctx.info)?;
+        self.ensure_admin(&ctx)?;
 
-        let config = self.config.load(deps.storage)?;
+        let mut config = self.config.load(ctx.deps.storage)?;
+        config.max_tracked_validators = max_tracked_validators;
+        self.config.save(ctx.deps.storage, &config)?;
 
-        ensure_eq!(
-            amount.denom,
-            config.denom,
-            ContractError::InvalidDenom(config.denom)
-        );
+        let max_tracked_validators = max_tracked_validators
+            .map(|max| max.to_string())
+            .unwrap_or_else(|| "none".to_owned());
+        Ok(Response::new()
+            .add_attribute("action", "update_max_tracked_validators")
+            .add_attribute("max_tracked_validators", max_tracked_validators))
+    }
 
-        let mut stake = self
-            .stakes
-            .stake
-            .may_load(deps.storage, (&info.sender, &validator))?
-            .unwrap_or_default();
+    /// Sets the max number of validators a user can stake on. Users already over it keep their
+    /// stakes, but can't stake on new validators. Only the contract admin can call it.
+    #[msg(exec)]
+    pub fn update_max_validators_per_user(
+        &self,
+        ctx: ExecCtx,
+        max_validators_per_user: Option<u32>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.ensure_admin(&ctx)?;
 
-        ensure!(
-            stake.stake.low() >= amount.amount,
-            ContractError::NotEnoughStake(stake.stake.low())
-        );
+        let mut config = self.config.load(ctx.deps.storage)?;
+        config.max_validators_per_user = max_validators_per_user;
+        self.config.save(ctx.deps.storage, &config)?;
 
-        stake.stake.prepare_sub(amount.amount, Uint128::zero())?;
+        let max_validators_per_user = max_validators_per_user
+            .map(|max| max.to_string())
+            .unwrap_or_else(|| "none".to_owned());
+        Ok(Response::new()
+            .add_attribute("action", "update_max_validators_per_user")
+            .add_attribute("max_validators_per_user", max_validators_per_user))
+    }
 
-        self.stakes
-            .stake
-            .save(deps.storage, (&info.sender, &validator), &stake)?;
+    /// Sets the typical IBC relay latency, in seconds, used to estimate when the unstakes not
+    /// committed yet are released. Only the contract admin can call it.
+    #[msg(exec)]
+    pub fn update_relay_latency(
+        &self,
+        ctx: ExecCtx,
+        relay_latency: Option<u64>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.ensure_admin(&ctx)?;
 
-        // Create new tx
-        let tx_id = self.next_tx_id(deps.storage)?;
+        let mut config = self.config.load(ctx.deps.storage)?;
+        config.relay_latency = relay_latency;
+        self.config.save(ctx.deps.storage, &config)?;
 
-        // Save tx
-        let new_tx = Tx::InFlightRemoteUnstaking {
-            id: tx_id,
-            amount: amount.amount,
-            user: info.sender.clone(),
-            validator: validator.clone(),
-        };
-        self.pending_txs.save(deps.storage, tx_id, &new_tx)?;
+        let relay_latency = relay_latency
+            .map(|latency| latency.to_string())
+            .unwrap_or_else(|| "none".to_owned());
+        Ok(Response::new()
+            .add_attribute("action", "update_relay_latency")
+            .add_attribute("relay_latency", relay_latency))
+    }
 
-        #[allow(unused_mut)]
-        let mut resp = Response::new()
-            .add_attribute("action", "unstake")
-            .add_attribute("amount", amount.amount.to_string())
-            .add_attribute("owner", info.sender);
+    /// Changes the IBC transfer trace the rewards in `base` denom arrive through. Only the
+    /// contract admin can call it.
+    ///
+    /// With `sweep`, the rewards held in the former denom are moved 1:1 to the new one, and
+    /// withdrawn in it from then on. They are still accounted in the denom they were distributed
+    /// in, so nothing is moved in storage. Otherwise they stay in the former denom, which is
+    /// retired: it isn't distributed anymore, but the stakers keep withdrawing it along with the
+    /// new one.
+    #[msg(exec)]
+    pub fn update_rewards_trace(
+        &self,
+        ctx: ExecCtx,
+        base: String,
+        trace: Option<String>,
+        sweep: bool,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.ensure_admin(&ctx)?;
 
-        let channel = IBC_CHANNEL.load(deps.storage)?;
-        let packet = ProviderPacket::Unstake {
-            validator,
-            unstake: amount,
-            tx_id,
-        };
-        let msg = IbcMsg::SendPacket {
-            channel_id: channel.endpoint.channel_id,
-            data: to_binary(&packet)?,
-            timeout: packet_timeout(&env),
+        let mut config = self.config.load(ctx.deps.storage)?;
+        let index = match config.rewards_denoms.iter().position(|d| d.base == base) {
+            Some(index) => index,
+            None => return Err(ContractError::UnknownRewardsBase(base)),
         };
-        // send packet if we are ibc enabled
-        // TODO: send in test code when we can handle it
-        #[cfg(not(any(test, feature = "mt")))]
-        {
-            resp = resp.add_message(msg);
-        }
-        #[cfg(any(test, feature = "mt"))]
-        {
-            let _ = msg;
+        let old_denom = config.rewards_denoms[index].denom();
+        let new_reward_denom = RewardDenom { base, trace };
+        let new_denom = new_reward_denom.denom();
+
+        if new_denom != old_denom {
+            // Swept, the former denom is not held anymore, and its accounting goes to the new one
+            let except = sweep.then_some(old_denom.as_str());
+            ensure!(
+                !config.is_rewards_denom_taken(&new_denom, except),
+                ContractError::RewardsDenomHeld(new_denom)
+            );
+            if sweep {
+                let accounting = config
+                    .swept_rewards_denoms
+                    .remove(&old_denom)
+                    .unwrap_or_else(|| old_denom.clone());
+                if accounting != new_denom {
+                    config
+                        .swept_rewards_denoms
+                        .insert(new_denom.clone(), accounting);
+                }
+            } else {
+                config.retired_rewards_denoms.push(old_denom.clone());
+            }
         }
+        config.rewards_denoms[index] = new_reward_denom;
+        self.config.save(ctx.deps.storage, &config)?;
 
-        Ok(resp)
+        Ok(Response::new()
+            .add_attribute("action", "update_rewards_trace")
+            .add_attribute("old_denom", old_denom)
+            .add_attribute("new_denom", new_denom)
+            .add_attribute("sweep", sweep.to_string()))
     }
 
-    /// In test code, this is called from `test_commit_unstake`.
-    /// In non-test code, this is called from `ibc_packet_ack`
-    pub(crate) fn commit_unstake(
+    /// Replaces the rewards denom with `base` by `new_denom`, when the consumer changes the token it
+    /// distributes rewards in. Only the contract admin can call it.
+    ///
+    /// The former denom is retired: it isn't distributed anymore, but the stakers keep
+    /// withdrawing the rewards already held in it along with the new one. The rewards in the new
+    /// denom accrue from zero.
+    #[msg(exec)]
+    pub fn replace_rewards_denom(
         &self,
-        deps: DepsMut,
-        env: Env,
-        tx_id: u64,
-    ) -> Result<(), ContractError> {
-        use crate::state::PendingUnbond;
-
-        // Load tx
-        let tx = self.pending_txs.load(deps.storage, tx_id)?;
+        ctx: ExecCtx,
+        base: String,
+        new_denom: String,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.ensure_admin(&ctx)?;
 
-        // Verify tx is of the right type
+        let mut config = self.config.load(ctx.deps.storage)?;
+        let new_reward_denom = RewardDenom::from_denom(&new_denom);
         ensure!(
-            matches!(tx, Tx::InFlightRemoteUnstaking { .. }),
-            ContractError::WrongTypeTx(tx_id, tx)
+            !config.is_rewards_denom_taken(&new_denom, None)
+                && !config
+                    .rewards_denoms
+                    .iter()
+                    .any(|d| d.base == new_reward_denom.base && d.base != base),
+            ContractError::RewardsDenomHeld(new_denom)
         );
-
-        let (tx_amount, tx_user, tx_validator) = match tx {
-            Tx::InFlightRemoteUnstaking {
-                amount,
-                user,
-                validator,
-                ..
-            } => (amount, user, validator),
-            _ => unreachable!(),
+        let reward_denom = match config.rewards_denoms.iter_mut().find(|d| d.base == base) {
+            Some(reward_denom) => reward_denom,
+            None => return Err(ContractError::UnknownRewardsBase(base)),
         };
+        let old_denom = reward_denom.denom();
+        *reward_denom = new_reward_denom;
 
-        let config = self.config.load(deps.storage)?;
+        config.retired_rewards_denoms.push(old_denom.clone());
+        self.config.save(ctx.deps.storage, &config)?;
 
-        // Load stake
+        Ok(Response::new()
+            .add_attribute("action", "replace_rewards_denom")
+            .add_attribute("old_denom", old_denom)
+            .add_attribute("new_denom", new_denom))
+    }
+
+    /// Clears the pending changes of a stake no pending tx references anymore, which would keep
+    /// it locked for good. Only the contract admin can call it.
+    ///
+    /// Whether the lost changes were stakes or unstakes is unknown, so the stake is set to the low
+    /// end of its range, the amount it is guaranteed to have. The part of it earning rewards is
+    /// decreased to match. The lost stakes and unstakes are reported in the `force_unlock` event,
+    /// to be reconciled with the consumer chain.
+    #[msg(exec)]
+    pub fn force_unlock(
+        &self,
+        ctx: ExecCtx,
+        user: String,
+        validator: String,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.ensure_admin(&ctx)?;
+
+        let user = ctx.deps.api.addr_validate(&user)?;
         let mut stake = self
             .stakes
             .stake
-            .load(deps.storage, (&tx_user, &tx_validator))?;
-
-        // Load distribution
-        let mut distribution = self
-            .distribution
-            .may_load(deps.storage, &tx_validator)?
+            .may_load(ctx.deps.storage, (&user, &validator))?
             .unwrap_or_default();
+        ensure!(
+            stake.stake.low() != stake.stake.high(),
+            ContractError::StakeNotLocked
+        );
+        if let Some(tx_id) = self.stake_pending_tx(ctx.deps.storage, &user, &validator)? {
+            return Err(ContractError::StakeLockInUse(tx_id));
+        }
 
-        // Commit sub amount, saturating if slashed
-        let amount = min(tx_amount, stake.stake.high());
-        stake.stake.commit_sub(amount);
-
-        // FIXME? Release period being computed after successful IBC tx
-        // (Note: this is good for now, but can be revisited in v1 design)
-        let release_at = env.block.time.plus_seconds(config.unbonding_period);
-        let unbond = PendingUnbond { amount, release_at };
-        stake.pending_unbonds.push(unbond);
-
-        // Distribution alignment
-        stake
-            .points_alignment
-            .stake_decreased(amount, distribution.points_per_stake);
-        distribution.total_stake -= amount;
-
-        // Save stake
+        // Pending stakes never earned rewards, but pending unstakes still do. Both are lost: the
+        // unstakes may have been committed on the consumer chain, without being unbonded here
+        let config = self.config.load(ctx.deps.storage)?;
+        let locked = stake.stake;
+        let lost_stake = locked.high().saturating_sub(stake.rewards_stake);
+        let lost_unstake = stake.rewards_stake.saturating_sub(locked.low());
+        if !lost_unstake.is_zero() {
+            self.stake_decreased(
+                ctx.deps.storage,
+                &config,
+                &validator,
+                &mut stake,
+                lost_unstake,
+            )?;
+        }
+        stake.stake = ValueRange::new_val(locked.low());
         self.stakes
             .stake
-            .save(deps.storage, (&tx_user, &tx_validator), &stake)?;
+            .save(ctx.deps.storage, (&user, &validator), &stake)?;
 
-        // Save distribution
-        self.distribution
-            .save(deps.storage, &tx_validator, &distribution)?;
+        let event = Event::new("force_unlock")
+            .add_attribute("user", &user)
+            .add_attribute("validator", &validator)
+            .add_attribute("locked", locked.to_string())
+            .add_attribute("stake", stake.stake.low().to_string())
+            .add_attribute("lost_stake", lost_stake.to_string())
+            .add_attribute("lost_unstake", lost_unstake.to_string());
+        Ok(Response::new()
+            .add_event(event)
+            .add_attribute("action", "force_unlock"))
+    }
+
+    /// Credits the rewards left unclaimable on `validator` to the position of `recipient` on it,
+    /// to be withdrawn with `withdraw_rewards`. Only the contract admin can call it.
+    ///
+    /// These are the whole tokens left undistributed on validators nobody stakes on anymore, and
+    /// which aren't tracked anymore (or never were, for rewards distributed to unknown validators
+    /// before they were rejected).
+    #[msg(exec)]
+    pub fn recover_unclaimable_rewards(
+        &self,
+        ctx: ExecCtx,
+        validator: String,
+        recipient: String,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.ensure_admin(&ctx)?;
+        let recipient = ctx.deps.api.addr_validate(&recipient)?;
+
+        ensure!(
+            self.val_set
+                .validator_state(ctx.deps.storage, &validator)?
+                .is_none(),
+            ContractError::ValidatorTracked(validator)
+        );
+
+        let config = self.config.load(ctx.deps.storage)?;
+        let existing = self
+            .stakes
+            .stake
+            .may_load(ctx.deps.storage, (&recipient, &validator))?;
+        let is_new = existing.is_none();
+        let mut stake = existing.unwrap_or_default();
+        let mut recovered = vec![];
+        for denom in config.held_rewards_denoms() {
+            let accounting = config.accounting_denom(&denom);
+            let mut distribution = match self
+                .distribution
+                .may_load(ctx.deps.storage, (&validator, accounting))?
+            {
+                Some(distribution) if distribution.total_stake.is_zero() => distribution,
+                _ => continue,
+            };
+            let amount = distribution.points_leftover / DISTRIBUTION_POINTS_SCALE;
+            if amount.is_zero() {
+                continue;
+            }
+            let points = amount * DISTRIBUTION_POINTS_SCALE;
+            distribution.points_leftover -= points;
+            self.distribution
+                .save(ctx.deps.storage, (&validator, accounting), &distribution)?;
+
+            let rewards = stake.rewards.entry(accounting.to_owned()).or_default();
+            rewards.checkpoint(stake.rewards_stake, distribution.points_per_stake);
+            rewards.points += points;
+            recovered.push(coin(Uint128::try_from(amount)?.u128(), denom));
+        }
+        ensure!(!recovered.is_empty(), ContractError::NoRewards);
+        self.stakes
+            .stake
+            .save(ctx.deps.storage, (&recipient, &validator), &stake)?;
+        if is_new {
+            let count = self.stake_entry_added(ctx.deps.storage, &recipient, &validator)?;
+            if let Some(max) = config.max_validators_per_user {
+                ensure!(count <= max, ContractError::TooManyValidators(max));
+            }
+        }
+
+        Ok(Response::new()
+            .add_attribute("action", "recover_unclaimable_rewards")
+            .add_attribute("validator", validator)
+            .add_attribute("recipient", recipient)
+            .add_attribute("rewards", join_coins(&recovered)))
+    }
+
+    /// Sends the balance of `denom` held by the contract to `recipient`, less the collateral kept
+    /// in the redistribution reserve. Only the contract admin can call it.
+    ///
+    /// Meant for tokens sent to the contract by mistake. The only funds it holds of its own are
+    /// the redistributed slashes not withdrawn yet, in the collateral denom: stakes and pending
+    /// unbonds are liens on the vault collateral, rewards are held on the consumer side, and
+    /// unbond sale payments go to the seller right away.
+    #[msg(exec)]
+    pub fn sweep(
+        &self,
+        ctx: ExecCtx,
+        denom: String,
+        recipient: String,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.ensure_admin(&ctx)?;
+        let recipient = ctx.deps.api.addr_validate(&recipient)?;
+
+        let mut balance = ctx
+            .deps
+            .querier
+            .query_balance(&ctx.env.contract.address, &denom)?;
+        if denom == self.config.load(ctx.deps.storage)?.denom {
+            let reserved = self
+                .redistribution_reserve
+                .may_load(ctx.deps.storage)?
+                .unwrap_or_default();
+            balance.amount = balance.amount.saturating_sub(reserved);
+        }
+        ensure!(
+            !balance.amount.is_zero(),
+            ContractError::NothingToSweep(denom)
+        );
+
+        Ok(Response::new()
+            .add_message(BankMsg::Send {
+                to_address: recipient.to_string(),
+                amount: vec![balance.clone()],
+            })
+            .add_attribute("action", "sweep")
+            .add_attribute("recipient", recipient)
+            .add_attribute("amount", balance.to_string()))
+    }
+
+    /// Rolls back a pending stake whose ack never arrived, unlocking the stake and rolling back
+    /// the vault tx too. Only the contract admin can call it.
+    ///
+    /// It's meant for packets known to be lost (eg. on a closed channel): an ack arriving after
+    /// the rollback would be rejected, as its tx is not pending anymore.
+    #[msg(exec)]
+    pub fn force_rollback_stake(
+        &self,
+        ctx: ExecCtx,
+        tx_id: u64,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.ensure_admin(&ctx)?;
+
+        let msg = self.rollback_stake(ctx.deps, ctx.env, tx_id)?;
+
+        Ok(Response::new()
+            .add_message(msg)
+            .add_attribute("action", "force_rollback_stake")
+            .add_attribute("tx_id", tx_id.to_string()))
+    }
+
+    /// Returns the pending tx changing the stake of `user` on `validator`, if any
+    fn stake_pending_tx(
+        &self,
+        storage: &dyn Storage,
+        user: &Addr,
+        validator: &str,
+    ) -> StdResult<Option<u64>> {
+        self.pending_txs.stake_changing_tx(storage, user, validator)
+    }
+
+    /// Loads the distribution on `validator` of the rewards accounted in `denom`. Denoms not
+    /// distributed on the validator yet start with its total stake, the one of any other held denom
+    fn load_distribution(
+        &self,
+        storage: &dyn Storage,
+        config: &Config,
+        validator: &str,
+        denom: &str,
+    ) -> StdResult<Distribution> {
+        if let Some(distribution) = self.distribution.may_load(storage, (validator, denom))? {
+            return Ok(distribution);
+        }
+        for held in config.held_rewards_denoms() {
+            let accounting = config.accounting_denom(&held);
+            if let Some(other) = self
+                .distribution
+                .may_load(storage, (validator, accounting))?
+            {
+                return Ok(Distribution {
+                    total_stake: other.total_stake,
+                    ..Default::default()
+                });
+            }
+        }
+        Ok(Distribution::default())
+    }
+
+    /// Slashes `validator` on evidence of it double-signing at `height` on the consumer chain,
+    /// the same way as if the consumer reported it. `signatures` is the JSON encoded
+    /// `DoubleSignEvidence`, with both prevotes or precommits on the consumer chain id, signed by
+    /// the validator pubkey active at `height`.
+    /// Validators jailed since are slashed as well.
+    ///
+    /// The sender gets the `evidence_bounty` part of the slashed stake.
+    #[msg(exec)]
+    pub fn submit_evidence(
+        &self,
+        ctx: ExecCtx,
+        validator: String,
+        height: u64,
+        signatures: Binary,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let config = self.config.load(ctx.deps.storage)?;
+        let chain_id = config
+            .consumer_chain_id
+            .as_deref()
+            .ok_or(ContractError::ConsumerChainIdNotSet)?;
+        let evidence: DoubleSignEvidence = from_binary(&signatures)?;
+        // Jailed validators can still be slashed for what they signed before being jailed
+        let signing =
+            self.val_set
+                .signing_validator_at_height(ctx.deps.storage, &validator, height)?;
+        let pub_key = match signing {
+            Some(signing) => Binary::from_base64(&signing.pub_key)?,
+            None => return Err(ContractError::ValidatorNotActiveAt(validator, height)),
+        };
+        evidence.verify(ctx.deps.api, &pub_key, chain_id, height)?;
+
+        // Tombstone and slash, as for a consumer reported double-sign
+        self.val_set
+            .remove_validator(ctx.deps.storage, &validator, height)?;
+        let (slash_infos, slashed, redistributed) =
+            self.slash_validator(&ctx.env, ctx.deps.storage, &validator)?;
+        let bounty = config
+            .evidence_bounty
+            .map(|ratio| (ctx.info.sender.to_string(), slashed * ratio));
+        let msg = self.slash_msg(&ctx.env, &config, slash_infos, redistributed, bounty)?;
+        let redistribution = self.redistribution_msg(&ctx.env, ctx.deps.storage)?;
+
+        Ok(Response::new()
+            .add_message(msg)
+            .add_messages(redistribution)
+            .add_attribute("action", "submit_evidence")
+            .add_attribute("validator", validator)
+            .add_attribute("height", height.to_string())
+            .add_attribute("submitter", ctx.info.sender))
+    }
+
+    /// Ensures the sender is the admin of this contract (the one able to migrate it)
+    fn ensure_admin(&self, ctx: &ExecCtx) -> Result<(), ContractError> {
+        let info = ctx
+            .deps
+            .querier
+            .query_wasm_contract_info(&ctx.env.contract.address)?;
+        ensure!(
+            info.admin.as_deref() == Some(ctx.info.sender.as_str()),
+            ContractError::Unauthorized
+        );
+        Ok(())
+    }
+
+    /// Returns the amount to actually unstake from a `staked` position, so no dust below the
+    /// configured minimum is left behind
+    fn avoid_dust(
+        config: &Config,
+        staked: Uint128,
+        amount: Uint128,
+    ) -> Result<Uint128, ContractError> {
+        let remaining = staked - amount;
+        match config.min_remaining_stake {
+            Some(min) if !remaining.is_zero() && remaining < min => match config.dust_policy {
+                DustPolicy::Reject => Err(ContractError::WouldLeaveDust(remaining, min)),
+                DustPolicy::FullUnstake => Ok(staked),
+            },
+            _ => Ok(amount),
+        }
+    }
+
+    /// Migrates the state of previous versions of the contract
+    #[msg(migrate)]
+    pub fn migrate(&self, ctx: MigrateCtx) -> Result<Response, ContractError> {
+        crate::migration::migrate_structured_rewards_denoms(ctx.deps.storage, self)?;
+        crate::migration::migrate_rewards_denoms(ctx.deps.storage, self)?;
+        crate::migration::migrate_rewards_checkpoints(ctx.deps.storage, self)?;
+        crate::migration::index_stakes_size(ctx.deps.storage, self)?;
+        crate::migration::count_user_validators(ctx.deps.storage, self)?;
+        crate::migration::init_stats(ctx.deps.storage, self)?;
+        self.val_set.reindex(ctx.deps.storage)?;
+        self.pending_txs.reindex(ctx.deps.storage)?;
+        set_contract_version(ctx.deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+        Ok(Response::new().add_attribute("action", "migrate"))
+    }
+
+    /// In test code, this is called from `test_commit_stake`.
+    /// In non-test code, this is called from `ibc_packet_ack`.
+    /// Returns the vault `commit_tx` message, to be sent in the same response so that both
+    /// contracts resolve the tx together
+    pub(crate) fn commit_stake(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        tx_id: u64,
+    ) -> Result<WasmMsg, ContractError> {
+        // Load tx
+        let tx = self.pending_txs.txs.load(deps.storage, tx_id)?;
+
+        // Verify tx is of the right type
+        ensure!(
+            matches!(tx, Tx::InFlightRemoteStaking { .. }),
+            ContractError::WrongTypeTx(tx_id, tx)
+        );
+
+        let (tx_amount, tx_user, tx_validator) = match tx {
+            Tx::InFlightRemoteStaking {
+                amount,
+                user,
+                validator,
+                ..
+            } => (amount, user, validator),
+            _ => unreachable!(),
+        };
+
+        let config = self.config.load(deps.storage)?;
+
+        // Load stake
+        let mut stake = self
+            .stakes
+            .stake
+            .load(deps.storage, (&tx_user, &tx_validator))?;
+
+        // Commit stake (saturating up if slashed)
+        stake.stake.commit_add_saturating(tx_amount);
+
+        // Rewards checkpoint
+        self.stake_increased(deps.storage, &config, &tx_validator, &mut stake, tx_amount)?;
+
+        // Save stake
+        self.stakes
+            .stake
+            .save(deps.storage, (&tx_user, &tx_validator), &stake)?;
+
+        // Remove tx
+        self.pending_txs.remove(deps.storage, tx_id)?;
+        self.tx_history
+            .record(deps.storage, tx_id, true, env.block.time)?;
+
+        // Call commit hook on vault
+        let msg = config.vault.commit_tx(tx_id)?;
+        Ok(msg)
+    }
+
+    /// In test code, this is called from `test_rollback_stake`.
+    /// In non-test code, this is called from `ibc_packet_ack` or `ibc_packet_timeout`.
+    /// Returns the vault `rollback_tx` message, to be sent in the same response so that both
+    /// contracts resolve the tx together
+    pub(crate) fn rollback_stake(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        tx_id: u64,
+    ) -> Result<WasmMsg, ContractError> {
+        self.revert_stake(deps.storage, &env, tx_id)?;
+
+        // Call rollback hook on vault
+        let cfg = self.config.load(deps.storage)?;
+        let msg = cfg.vault.rollback_tx(tx_id)?;
+        Ok(msg)
+    }
+
+    /// Rolls back the pending stake `tx_id` on this side only
+    fn revert_stake(
+        &self,
+        storage: &mut dyn Storage,
+        env: &Env,
+        tx_id: u64,
+    ) -> Result<(), ContractError> {
+        // Load tx
+        let tx = self.pending_txs.txs.load(storage, tx_id)?;
+
+        // Verify tx is of the right type
+        ensure!(
+            matches!(tx, Tx::InFlightRemoteStaking { .. }),
+            ContractError::WrongTypeTx(tx_id, tx)
+        );
+
+        let (tx_amount, tx_user, tx_validator) = match tx {
+            Tx::InFlightRemoteStaking {
+                amount,
+                user,
+                validator,
+                ..
+            } => (amount, user, validator),
+            _ => unreachable!(),
+        };
+
+        // Load stake
+        let mut stake = self.stakes.stake.load(storage, (&tx_user, &tx_validator))?;
+
+        // Rollback add amount (saturating up if slashed)
+        stake.stake.rollback_add_saturating(tx_amount);
+
+        // Remove tx
+        self.pending_txs.remove(storage, tx_id)?;
+        self.tx_history
+            .record(storage, tx_id, false, env.block.time)?;
+
+        // Save stake, or remove it if it was the first one
+        let cfg = self.config.load(storage)?;
+        self.save_or_remove_stake(storage, &cfg, &tx_user, &tx_validator, &stake)?;
+        Ok(())
+    }
+
+    /// Schedules tokens for release, adding them to the pending unbonds. After the unbonding period
+    /// passes, funds are ready to be released through a `withdraw_unbonded` call by the user.
+    #[msg(exec)]
+    pub fn unstake(
+        &self,
+        ctx: ExecCtx,
+        validator: String,
+        amount: Coin,
+    ) -> Result<Response, ContractError> {
+        let ExecCtx { info, deps, env } = ctx;
+        nonpayable(&info)?;
+
+        let config = self.config.load(deps.storage)?;
+
+        ensure_eq!(
+            amount.denom,
+            config.denom,
+            ContractError::InvalidDenom(config.denom)
+        );
+
+        self.send_unstake(
+            deps,
+            &env,
+            &config,
+            &info.sender,
+            validator,
+            amount.amount,
+            "unstake",
+        )
+    }
+
+    /// Schedules tokens for release from multiple validators at once, like `unstake`. A single tx
+    /// and IBC packet are created for all of them, to be committed or rolled back together.
+    #[msg(exec)]
+    pub fn unstake_batch(
+        &self,
+        ctx: ExecCtx,
+        unstakes: Vec<(String, Coin)>,
+    ) -> Result<Response, ContractError> {
+        let ExecCtx { info, deps, env } = ctx;
+        nonpayable(&info)?;
+        ensure!(!unstakes.is_empty(), ContractError::EmptyUnstakeBatch);
+
+        let config = self.config.load(deps.storage)?;
+
+        let unstakes = unstakes
+            .into_iter()
+            .map(|(validator, amount)| {
+                ensure_eq!(
+                    amount.denom,
+                    config.denom,
+                    ContractError::InvalidDenom(config.denom.clone())
+                );
+                Ok((validator, amount.amount))
+            })
+            .collect::<Result<_, ContractError>>()?;
+
+        self.send_unstake_batch(deps, &env, &config, &info.sender, unstakes, "unstake_batch")
+    }
+
+    /// Starts the unstaking of `amount` of the user stake on the validator, creating its tx and
+    /// IBC packet. Shared by `unstake` and `vault_unstake`
+    #[allow(clippy::too_many_arguments)]
+    fn send_unstake(
+        &self,
+        deps: DepsMut,
+        env: &Env,
+        config: &Config,
+        user: &Addr,
+        validator: String,
+        amount: Uint128,
+        action: &str,
+    ) -> Result<Response, ContractError> {
+        let amount = coin(
+            self.prepare_unstake(deps.storage, config, user, &validator, amount)?
+                .u128(),
+            &config.denom,
+        );
+
+        // Create new tx
+        let tx_id = self.next_tx_id(deps.storage)?;
+
+        // Save tx
+        let new_tx = Tx::InFlightRemoteUnstaking {
+            id: tx_id,
+            amount: amount.amount,
+            user: user.clone(),
+            validator: validator.clone(),
+            created_at: env.block.time,
+        };
+        self.pending_txs.save(deps.storage, tx_id, &new_tx)?;
+        self.record_activity(deps.storage, user, env.block.time, false)?;
+
+        #[allow(unused_mut)]
+        let mut resp = Response::new()
+            .add_attribute("action", action)
+            .add_attribute("amount", amount.amount.to_string())
+            .add_attribute("owner", user);
+
+        let channel = IBC_CHANNEL.load(deps.storage)?;
+        let packet = ProviderPacket::Unstake {
+            validator,
+            unstake: amount,
+            tx_id,
+        };
+        let msg = IbcMsg::SendPacket {
+            channel_id: channel.endpoint.channel_id,
+            data: to_binary(&packet)?,
+            timeout: packet_timeout(env),
+        };
+        // send packet if we are ibc enabled
+        // TODO: send in test code when we can handle it
+        #[cfg(not(any(test, feature = "mt")))]
+        {
+            resp = resp.add_message(msg);
+        }
+        #[cfg(any(test, feature = "mt"))]
+        {
+            let _ = msg;
+        }
+
+        Ok(resp)
+    }
+
+    /// Like `send_unstake`, for several validators at once. Shared by `unstake_batch` and
+    /// `vault_unstake`
+    fn send_unstake_batch(
+        &self,
+        deps: DepsMut,
+        env: &Env,
+        config: &Config,
+        user: &Addr,
+        unstakes: Vec<(String, Uint128)>,
+        action: &str,
+    ) -> Result<Response, ContractError> {
+        let mut tx_unstakes = vec![];
+        let mut packet_unstakes = vec![];
+        for (validator, amount) in unstakes {
+            let amount = self.prepare_unstake(deps.storage, config, user, &validator, amount)?;
+            tx_unstakes.push((validator.clone(), amount));
+            packet_unstakes.push(UnstakeInfo {
+                validator,
+                unstake: coin(amount.u128(), &config.denom),
+            });
+        }
+        let total: Uint128 = tx_unstakes.iter().map(|(_, amount)| amount).sum();
+
+        // Create new tx
+        let tx_id = self.next_tx_id(deps.storage)?;
+
+        // Save tx
+        let new_tx = Tx::InFlightRemoteUnstakingBatch {
+            id: tx_id,
+            user: user.clone(),
+            unstakes: tx_unstakes,
+            created_at: env.block.time,
+        };
+        self.pending_txs.save(deps.storage, tx_id, &new_tx)?;
+        self.record_activity(deps.storage, user, env.block.time, false)?;
+
+        #[allow(unused_mut)]
+        let mut resp = Response::new()
+            .add_attribute("action", action)
+            .add_attribute("amount", total.to_string())
+            .add_attribute("owner", user)
+            .add_attribute("tx_id", tx_id.to_string());
+
+        let channel = IBC_CHANNEL.load(deps.storage)?;
+        let packet = ProviderPacket::UnstakeBatch {
+            unstakes: packet_unstakes,
+            tx_id,
+        };
+        let msg = IbcMsg::SendPacket {
+            channel_id: channel.endpoint.channel_id,
+            data: to_binary(&packet)?,
+            timeout: packet_timeout(env),
+        };
+        // send packet if we are ibc enabled
+        // TODO: send in test code when we can handle it
+        #[cfg(not(any(test, feature = "mt")))]
+        {
+            resp = resp.add_message(msg);
+        }
+        #[cfg(any(test, feature = "mt"))]
+        {
+            let _ = msg;
+        }
+
+        Ok(resp)
+    }
+
+    /// Splits `amount` over the user stakes, taking from the largest stakes first
+    fn largest_stakes_first(
+        &self,
+        storage: &dyn Storage,
+        user: &Addr,
+        amount: Uint128,
+    ) -> Result<Vec<(String, Uint128)>, ContractError> {
+        let mut stakes = self
+            .stakes
+            .stake
+            .prefix(user)
+            .range(storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(validator, stake)| (validator, stake.stake.low())))
+            .collect::<StdResult<Vec<_>>>()?;
+        // Stable sort, so equal stakes stay ordered by validator
+        stakes.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+        let mut left = amount;
+        let mut unstakes = vec![];
+        for (validator, stake) in stakes {
+            let unstake = stake.min(left);
+            if unstake.is_zero() {
+                break;
+            }
+            unstakes.push((validator, unstake));
+            left -= unstake;
+        }
+        ensure!(left.is_zero(), ContractError::NotEnoughStake(amount - left));
+
+        Ok(unstakes)
+    }
+
+    /// Marks `amount` of the user stake on the validator as being unstaken. Returns the amount
+    /// to actually unstake, which may include the dust left otherwise
+    fn prepare_unstake(
+        &self,
+        storage: &mut dyn Storage,
+        config: &Config,
+        user: &Addr,
+        validator: &str,
+        amount: Uint128,
+    ) -> Result<Uint128, ContractError> {
+        let mut stake = self
+            .stakes
+            .stake
+            .may_load(storage, (user, validator))?
+            .unwrap_or_default();
+
+        ensure!(
+            stake.stake.low() >= amount,
+            ContractError::NotEnoughStake(stake.stake.low())
+        );
+
+        let amount = Self::avoid_dust(config, stake.stake.low(), amount)?;
+
+        stake.stake.prepare_sub(amount, Uint128::zero())?;
+
+        self.stakes.stake.save(storage, (user, validator), &stake)?;
+
+        Ok(amount)
+    }
+
+    /// Returns the owner, the unstakes and the creation time of a single or batch unstake tx
+    fn unstake_tx(tx_id: u64, tx: Tx) -> Result<UnstakeTx, ContractError> {
+        match tx {
+            Tx::InFlightRemoteUnstaking {
+                amount,
+                user,
+                validator,
+                created_at,
+                ..
+            } => Ok((user, vec![(validator, amount)], created_at)),
+            Tx::InFlightRemoteUnstakingBatch {
+                user,
+                unstakes,
+                created_at,
+                ..
+            } => Ok((user, unstakes, created_at)),
+            _ => Err(ContractError::WrongTypeTx(tx_id, tx)),
+        }
+    }
+
+    /// Commits both single and batch unstakes.
+    ///
+    /// In test code, this is called from `test_commit_unstake`.
+    /// In non-test code, this is called from `ibc_packet_ack`
+    pub(crate) fn commit_unstake(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        tx_id: u64,
+    ) -> Result<(), ContractError> {
+        // Load tx, verifying it is of the right type
+        let tx = self.pending_txs.txs.load(deps.storage, tx_id)?;
+        let (tx_user, tx_unstakes, created_at) = Self::unstake_tx(tx_id, tx)?;
+
+        let config = self.config.load(deps.storage)?;
+
+        for (tx_validator, tx_amount) in tx_unstakes {
+            // Load stake
+            let mut stake = self
+                .stakes
+                .stake
+                .load(deps.storage, (&tx_user, &tx_validator))?;
+
+            // Commit sub amount, saturating if slashed
+            let amount = min(tx_amount, stake.stake.high());
+            stake.stake.commit_sub(amount);
+
+            // FIXME? Release period being computed after successful IBC tx
+            // (Note: this is good for now, but can be revisited in v1 design)
+            let release_at = env.block.time.plus_seconds(config.unbonding_period);
+            let unbond = PendingUnbond {
+                amount,
+                requested_at: created_at,
+                release_at,
+            };
+            stake.add_pending_unbond(unbond);
+            update_stat(deps.storage, &self.total_pending_unbonds, |total| {
+                total + amount
+            })?;
+
+            // Rewards checkpoint
+            self.stake_decreased(deps.storage, &config, &tx_validator, &mut stake, amount)?;
+
+            // Save stake
+            self.stakes
+                .stake
+                .save(deps.storage, (&tx_user, &tx_validator), &stake)?;
+        }
+
+        // Remove tx
+        self.pending_txs.remove(deps.storage, tx_id)?;
+        self.tx_history
+            .record(deps.storage, tx_id, true, env.block.time)?;
+        Ok(())
+    }
+
+    /// Rolls back both single and batch unstakes.
+    ///
+    /// In test code, this is called from `test_rollback_unstake`.
+    /// In non-test code, this is called from `ibc_packet_ack` or `ibc_packet_timeout`
+    pub(crate) fn rollback_unstake(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        tx_id: u64,
+    ) -> Result<(), ContractError> {
+        // Load tx, verifying it is of the right type
+        let tx = self.pending_txs.txs.load(deps.storage, tx_id)?;
+        let (tx_user, tx_unstakes, _) = Self::unstake_tx(tx_id, tx)?;
+
+        for (tx_validator, tx_amount) in tx_unstakes {
+            // Load stake
+            let mut stake = self
+                .stakes
+                .stake
+                .load(deps.storage, (&tx_user, &tx_validator))?;
+
+            // Rollback sub amount
+            stake.stake.rollback_sub_saturating(tx_amount);
+
+            // Save stake
+            self.stakes
+                .stake
+                .save(deps.storage, (&tx_user, &tx_validator), &stake)?;
+        }
+
+        // Remove tx
+        self.pending_txs.remove(deps.storage, tx_id)?;
+        self.tx_history
+            .record(deps.storage, tx_id, false, env.block.time)?;
+        Ok(())
+    }
+
+    /// Commits a restake, moving the stake from the `from` validators to the `to` one.
+    ///
+    /// In test code, this is called from `test_commit_restake`.
+    /// In non-test code, this is called from `ibc_packet_ack`
+    pub(crate) fn commit_restake(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        tx_id: u64,
+    ) -> Result<(), ContractError> {
+        // Load tx, verifying it is of the right type
+        let tx = self.pending_txs.txs.load(deps.storage, tx_id)?;
+        let (tx_user, tx_from, tx_to) = match tx {
+            Tx::InFlightRemoteRestaking { user, from, to, .. } => (user, from, to),
+            _ => return Err(ContractError::WrongTypeTx(tx_id, tx)),
+        };
+
+        let config = self.config.load(deps.storage)?;
+
+        // Remove tx first, so that the emptied stakes can be closed
+        self.pending_txs.remove(deps.storage, tx_id)?;
+        self.tx_history
+            .record(deps.storage, tx_id, true, env.block.time)?;
+
+        let mut total = Uint128::zero();
+        let mut moved = Uint128::zero();
+        for (tx_validator, tx_amount) in tx_from {
+            let mut stake = self
+                .stakes
+                .stake
+                .load(deps.storage, (&tx_user, &tx_validator))?;
+
+            // Commit sub amount, saturating if slashed
+            let amount = min(tx_amount, stake.stake.high());
+            stake.stake.commit_sub(amount);
+
+            // Rewards checkpoint
+            self.stake_decreased(deps.storage, &config, &tx_validator, &mut stake, amount)?;
+
+            self.save_or_remove_stake(deps.storage, &config, &tx_user, &tx_validator, &stake)?;
+            total += tx_amount;
+            moved += amount;
+
+            // The moved stake stays slashable for the source validator
+            if !amount.is_zero() {
+                self.add_redelegation(
+                    deps.storage,
+                    &env,
+                    &tx_validator,
+                    &tx_user,
+                    Redelegation {
+                        to: tx_to.clone(),
+                        amount,
+                        release_at: env.block.time.plus_seconds(config.unbonding_period),
+                    },
+                )?;
+            }
+        }
+
+        let mut stake = self.stakes.stake.load(deps.storage, (&tx_user, &tx_to))?;
+
+        // Only the stake left after slashing is moved
+        stake.stake.rollback_add_saturating(total - moved);
+        stake.stake.commit_add_saturating(moved);
+
+        // Rewards checkpoint
+        self.stake_increased(deps.storage, &config, &tx_to, &mut stake, moved)?;
+
+        self.stakes
+            .stake
+            .save(deps.storage, (&tx_user, &tx_to), &stake)?;
+        Ok(())
+    }
+
+    /// Records a stake restaked off `validator`, dropping the ones out of their unbonding period
+    fn add_redelegation(
+        &self,
+        storage: &mut dyn Storage,
+        env: &Env,
+        validator: &str,
+        user: &Addr,
+        redelegation: Redelegation,
+    ) -> Result<(), ContractError> {
+        let mut redelegations = self
+            .redelegations
+            .may_load(storage, (validator, user))?
+            .unwrap_or_default();
+        redelegations.retain(|redelegation| redelegation.release_at > env.block.time);
+        redelegations.push(redelegation);
+        self.redelegations
+            .save(storage, (validator, user), &redelegations)?;
+        Ok(())
+    }
+
+    /// Rolls back a restake, leaving the stake on the `from` validators.
+    ///
+    /// In test code, this is called from `test_rollback_restake`.
+    /// In non-test code, this is called from `ibc_packet_ack` or `ibc_packet_timeout`
+    pub(crate) fn rollback_restake(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        tx_id: u64,
+    ) -> Result<(), ContractError> {
+        // Load tx, verifying it is of the right type
+        let tx = self.pending_txs.txs.load(deps.storage, tx_id)?;
+        let (tx_user, tx_from, tx_to) = match tx {
+            Tx::InFlightRemoteRestaking { user, from, to, .. } => (user, from, to),
+            _ => return Err(ContractError::WrongTypeTx(tx_id, tx)),
+        };
+
+        let mut total = Uint128::zero();
+        for (tx_validator, tx_amount) in tx_from {
+            let mut stake = self
+                .stakes
+                .stake
+                .load(deps.storage, (&tx_user, &tx_validator))?;
+
+            // Rollback sub amount
+            stake.stake.rollback_sub_saturating(tx_amount);
+
+            self.stakes
+                .stake
+                .save(deps.storage, (&tx_user, &tx_validator), &stake)?;
+            total += tx_amount;
+        }
+
+        let mut stake = self.stakes.stake.load(deps.storage, (&tx_user, &tx_to))?;
+
+        // Rollback add amount (saturating up if slashed)
+        stake.stake.rollback_add_saturating(total);
+
+        // Remove tx
+        self.pending_txs.remove(deps.storage, tx_id)?;
+        self.tx_history
+            .record(deps.storage, tx_id, false, env.block.time)?;
+
+        // Save stake, or remove it if it was new
+        let config = self.config.load(deps.storage)?;
+        self.save_or_remove_stake(deps.storage, &config, &tx_user, &tx_to, &stake)?;
+        Ok(())
+    }
+
+    /// Withdraws all of their released tokens to the calling user.
+    ///
+    /// Tokens to be claimed have to be unbond before by calling the `unbond` message, and
+    /// their unbonding period must have passed.
+    ///
+    /// If `recipient` is set, the released collateral is also unbonded from the user's vault
+    /// account and sent to the recipient instead.
+    #[msg(exec)]
+    pub fn withdraw_unbonded(
+        &self,
+        ctx: ExecCtx,
+        recipient: Option<String>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let recipient = recipient
+            .map(|recipient| ctx.deps.api.addr_validate(&recipient))
+            .transpose()?;
+
+        let config = self.config.load(ctx.deps.storage)?;
+        let released = self.release_matured(
+            ctx.deps.storage,
+            &ctx.env.block,
+            &config,
+            &ctx.info.sender,
+            None,
+        )?;
+
+        let mut resp = Response::new()
+            .add_attribute("action", "withdraw_unbonded")
+            .add_attribute("owner", ctx.info.sender.to_string())
+            .add_attribute("amount", released.to_string());
+
+        if let Some(recipient) = &recipient {
+            resp = resp.add_attribute("recipient", recipient);
+        }
+
+        if !released.is_zero() {
+            let release_msg = config.vault.release_matured_cross_stake(
+                ctx.info.sender.into_string(),
+                coin(released.u128(), &config.denom),
+                recipient.map(Addr::into_string),
+            )?;
+
+            resp = resp.add_message(release_msg);
+        }
+
+        Ok(resp)
+    }
+
+    /// Withdraws the matured unbonds of `user` to their vault account, as `withdraw_unbonded`
+    /// would. Anyone can call it, eg. a keeper bot, as the collateral is only ever released to
+    /// the user.
+    ///
+    /// Only the stakes on `validators` are processed if given, all the user's stakes otherwise.
+    /// The caller gets no incentive, as the vault release has no way to split off a fee.
+    #[msg(exec)]
+    pub fn withdraw_unbonded_for(
+        &self,
+        ctx: ExecCtx,
+        user: String,
+        validators: Option<Vec<String>>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let user = ctx.deps.api.addr_validate(&user)?;
+        let config = self.config.load(ctx.deps.storage)?;
+        let released =
+            self.release_matured(ctx.deps.storage, &ctx.env.block, &config, &user, validators)?;
+
+        let mut resp = Response::new()
+            .add_attribute("action", "withdraw_unbonded_for")
+            .add_attribute("sender", ctx.info.sender.into_string())
+            .add_attribute("owner", user.to_string())
+            .add_attribute("amount", released.to_string());
+
+        if !released.is_zero() {
+            let release_msg = config.vault.release_matured_cross_stake(
+                user.into_string(),
+                coin(released.u128(), &config.denom),
+                None,
+            )?;
+            resp = resp.add_message(release_msg);
+        }
+
+        Ok(resp)
+    }
+
+    /// Offers a pending unbond of the sender for sale, for at least `min_price` in the vault
+    /// denom. Listing the same pending unbond again updates its price.
+    ///
+    /// The buyer gets the pending unbond, and the collateral it covers in the vault, for
+    /// instant exit liquidity.
+    #[msg(exec)]
+    pub fn list_unbond_for_sale(
+        &self,
+        ctx: ExecCtx,
+        validator: String,
+        unbond_index: u32,
+        min_price: Uint128,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
 
-        // Remove tx
-        self.pending_txs.remove(deps.storage, tx_id);
-        Ok(())
+        let stake = self
+            .stakes
+            .stake
+            .may_load(ctx.deps.storage, (&ctx.info.sender, &validator))?
+            .unwrap_or_default();
+        let unbond = Self::pending_unbond(&stake, unbond_index, &ctx.env.block)?;
+
+        let listing = UnbondListing {
+            release_at: unbond.release_at,
+            amount: unbond.amount,
+            min_price,
+        };
+        self.unbond_listings.save(
+            ctx.deps.storage,
+            (&ctx.info.sender, &validator, unbond.release_at.nanos()),
+            &listing,
+        )?;
+
+        let resp = Response::new()
+            .add_attribute("action", "list_unbond_for_sale")
+            .add_attribute("owner", ctx.info.sender)
+            .add_attribute("validator", validator)
+            .add_attribute("amount", unbond.amount.to_string())
+            .add_attribute("min_price", min_price.to_string());
+
+        Ok(resp)
     }
 
-    /// In test code, this is called from `test_rollback_unstake`.
-    /// In non-test code, this is called from `ibc_packet_ack` or `ibc_packet_timeout`
-    pub(crate) fn rollback_unstake(&self, deps: DepsMut, tx_id: u64) -> Result<(), ContractError> {
-        // Load tx
-        let tx = self.pending_txs.load(deps.storage, tx_id)?;
+    /// Withdraws a pending unbond of the sender from sale
+    #[msg(exec)]
+    pub fn cancel_unbond_sale(
+        &self,
+        ctx: ExecCtx,
+        validator: String,
+        unbond_index: u32,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
 
-        // Verify tx is of the right type
+        let stake = self
+            .stakes
+            .stake
+            .may_load(ctx.deps.storage, (&ctx.info.sender, &validator))?
+            .unwrap_or_default();
+        let unbond = stake
+            .pending_unbonds
+            .get(unbond_index as usize)
+            .ok_or(ContractError::UnbondNotFound(unbond_index))?;
+
+        let key = (
+            &ctx.info.sender,
+            validator.as_str(),
+            unbond.release_at.nanos(),
+        );
         ensure!(
-            matches!(tx, Tx::InFlightRemoteUnstaking { .. }),
-            ContractError::WrongTypeTx(tx_id, tx)
+            self.unbond_listings.has(ctx.deps.storage, key),
+            ContractError::UnbondNotForSale(unbond_index)
         );
-        let (tx_amount, tx_user, tx_validator) = match tx {
-            Tx::InFlightRemoteUnstaking {
-                amount,
-                user,
-                validator,
-                ..
-            } => (amount, user, validator),
-            _ => unreachable!(),
-        };
+        self.unbond_listings.remove(ctx.deps.storage, key);
 
-        // Load stake
-        let mut stake = self
+        let resp = Response::new()
+            .add_attribute("action", "cancel_unbond_sale")
+            .add_attribute("owner", ctx.info.sender)
+            .add_attribute("validator", validator);
+
+        Ok(resp)
+    }
+
+    /// Buys a pending unbond offered for sale. The sent funds are paid to the seller right away.
+    ///
+    /// The pending unbond moves to the buyer's stake on the same validator, and the vault moves
+    /// the lien and collateral backing it to the buyer. Once released, the buyer gets the
+    /// collateral with `withdraw_unbonded`.
+    #[msg(exec)]
+    pub fn buy_unbond(
+        &self,
+        ctx: ExecCtx,
+        seller: String,
+        validator: String,
+        unbond_index: u32,
+    ) -> Result<Response, ContractError> {
+        let config = self.config.load(ctx.deps.storage)?;
+        let price = must_pay(&ctx.info, &config.denom)?;
+
+        let seller = ctx.deps.api.addr_validate(&seller)?;
+        ensure!(seller != ctx.info.sender, ContractError::CannotBuyOwnUnbond);
+
+        let mut seller_stake = self
             .stakes
             .stake
-            .load(deps.storage, (&tx_user, &tx_validator))?;
+            .may_load(ctx.deps.storage, (&seller, &validator))?
+            .unwrap_or_default();
+        let unbond = Self::pending_unbond(&seller_stake, unbond_index, &ctx.env.block)?.clone();
 
-        // Rollback sub amount
-        stake.stake.rollback_sub_saturating(tx_amount);
+        let key = (&seller, validator.as_str(), unbond.release_at.nanos());
+        let listing = self
+            .unbond_listings
+            .may_load(ctx.deps.storage, key)?
+            .ok_or(ContractError::UnbondNotForSale(unbond_index))?;
+        ensure!(
+            listing.amount == unbond.amount,
+            ContractError::UnbondListingOutdated(unbond_index)
+        );
+        ensure!(
+            price >= listing.min_price,
+            ContractError::PriceTooLow(listing.min_price)
+        );
+        self.unbond_listings.remove(ctx.deps.storage, key);
 
-        // Save stake
+        // Move the pending unbond
+        seller_stake.pending_unbonds.remove(unbond_index as usize);
         self.stakes
             .stake
-            .save(deps.storage, (&tx_user, &tx_validator), &stake)?;
+            .save(ctx.deps.storage, (&seller, &validator), &seller_stake)?;
 
-        // Remove tx
-        self.pending_txs.remove(deps.storage, tx_id);
-        Ok(())
+        let buyer_stake = self
+            .stakes
+            .stake
+            .may_load(ctx.deps.storage, (&ctx.info.sender, &validator))?;
+        let is_new = buyer_stake.is_none();
+        let mut buyer_stake = buyer_stake.unwrap_or_default();
+        buyer_stake.insert_pending_unbond(unbond.clone());
+        self.stakes.stake.save(
+            ctx.deps.storage,
+            (&ctx.info.sender, &validator),
+            &buyer_stake,
+        )?;
+        // Buying on a new validator is bounded by the max validators per user
+        if is_new {
+            let count = self.stake_entry_added(ctx.deps.storage, &ctx.info.sender, &validator)?;
+            if let Some(max) = config.max_validators_per_user {
+                ensure!(count <= max, ContractError::TooManyValidators(max));
+            }
+        }
+
+        let transfer_msg = config.vault.transfer_cross_stake(
+            seller.to_string(),
+            ctx.info.sender.to_string(),
+            coin(unbond.amount.u128(), &config.denom),
+        )?;
+        let pay_msg = BankMsg::Send {
+            to_address: seller.to_string(),
+            amount: coins(price.u128(), &config.denom),
+        };
+
+        let resp = Response::new()
+            .add_message(transfer_msg)
+            .add_message(pay_msg)
+            .add_attribute("action", "buy_unbond")
+            .add_attribute("buyer", ctx.info.sender)
+            .add_attribute("seller", seller)
+            .add_attribute("validator", validator)
+            .add_attribute("amount", unbond.amount.to_string())
+            .add_attribute("price", price.to_string());
+
+        Ok(resp)
     }
 
-    /// Withdraws all of their released tokens to the calling user.
+    /// Returns the pending unbond at `unbond_index`, failing if it is already released
+    fn pending_unbond<'s>(
+        stake: &'s Stake,
+        unbond_index: u32,
+        block: &BlockInfo,
+    ) -> Result<&'s PendingUnbond, ContractError> {
+        let unbond = stake
+            .pending_unbonds
+            .get(unbond_index as usize)
+            .ok_or(ContractError::UnbondNotFound(unbond_index))?;
+        ensure!(
+            unbond.release_at > block.time,
+            ContractError::UnbondReleased(unbond_index)
+        );
+        Ok(unbond)
+    }
+
+    /// Merges pending unbonds of the `(user, validator)` stake that are released at the same time.
     ///
-    /// Tokens to be claimed have to be unbond before by calling the `unbond` message, and
-    /// their unbonding period must have passed.
+    /// Newly committed unstakes are merged on the fly, this is meant to clean up entries created
+    /// before that. It doesn't change any amounts or release times, so anyone can call it.
     #[msg(exec)]
-    pub fn withdraw_unbonded(&self, ctx: ExecCtx) -> Result<Response, ContractError> {
+    pub fn compact_unbonds(
+        &self,
+        ctx: ExecCtx,
+        user: String,
+        validator: String,
+    ) -> Result<Response, ContractError> {
         nonpayable(&ctx.info)?;
 
-        let config = self.config.load(ctx.deps.storage)?;
-
-        let stakes: Vec<_> = self
+        let user = ctx.deps.api.addr_validate(&user)?;
+        let mut stake = self
             .stakes
             .stake
-            .prefix(&ctx.info.sender)
-            .range(ctx.deps.storage, None, None, Order::Ascending)
-            .collect::<Result<_, _>>()?;
+            .may_load(ctx.deps.storage, (&user, &validator))?
+            .unwrap_or_default();
 
-        let released: Uint128 = stakes
-            .into_iter()
-            .map(|(validator, mut stake)| -> Result<_, ContractError> {
-                let released = stake.release_pending(&ctx.env.block);
+        let merged = stake.compact_pending_unbonds();
+        if merged > 0 {
+            self.stakes
+                .stake
+                .save(ctx.deps.storage, (&user, &validator), &stake)?;
+        }
 
-                if !released.is_zero() {
-                    self.stakes.stake.save(
-                        ctx.deps.storage,
-                        (&ctx.info.sender, &validator),
-                        &stake,
-                    )?
-                }
+        let resp = Response::new()
+            .add_attribute("action", "compact_unbonds")
+            .add_attribute("owner", user)
+            .add_attribute("validator", validator)
+            .add_attribute("merged", merged.to_string());
 
-                Ok(released)
-            })
-            .fold(Ok(Uint128::zero()), |acc, released| {
-                let acc = acc?;
-                released.map(|released| released + acc)
-            })?;
+        Ok(resp)
+    }
 
-        let mut resp = Response::new()
-            .add_attribute("action", "withdraw_unbonded")
-            .add_attribute("owner", ctx.info.sender.to_string())
-            .add_attribute("amount", released.to_string());
+    /// Queues validators to be added to the valset, and adds up to `limit` of them right away.
+    /// The rest is added by subsequent `continue_valset_sync` calls.
+    /// In test code, this is called from `test_add_validators`.
+    /// In non-test code, this is called from `ibc_packet_receive`
+    pub(crate) fn add_validators(
+        &self,
+        storage: &mut dyn Storage,
+        validators: Vec<AddValidator>,
+        limit: u32,
+    ) -> Result<u32, ContractError> {
+        for validator in &validators {
+            self.valset_backlog.push_back(storage, validator)?;
+        }
+        let processed = self.sync_valset(storage, limit)?;
+        if self.valset_backlog.len(storage)? == 0 {
+            self.valset_synced.save(storage, &true)?;
+        }
+        Ok(processed)
+    }
 
-        if !released.is_zero() {
-            let release_msg = config.vault.release_cross_stake(
-                ctx.info.sender.into_string(),
-                coin(released.u128(), &config.denom),
-                vec![],
-            )?;
+    /// Adds up to `limit` validators from the backlog to the valset, returning how many were added
+    fn sync_valset(&self, storage: &mut dyn Storage, limit: u32) -> Result<u32, ContractError> {
+        let mut processed = 0;
+        while processed < limit {
+            let AddValidator {
+                valoper,
+                pub_key,
+                start_height,
+                start_time,
+            } = match self.valset_backlog.pop_front(storage)? {
+                Some(validator) => validator,
+                None => break,
+            };
+            let update = ValUpdate {
+                pub_key,
+                start_height,
+                start_time,
+            };
+            self.val_set.add_validator(storage, &valoper, update)?;
+            processed += 1;
+        }
+        self.prune_validators(storage)?;
+        Ok(processed)
+    }
 
-            resp = resp.add_message(release_msg);
+    /// Jails `valoper` at `height`. Validators still queued in the backlog are jailed as well, so
+    /// that they are not added as active once processed.
+    pub(crate) fn jail_validator(
+        &self,
+        storage: &mut dyn Storage,
+        valoper: &str,
+        height: u64,
+    ) -> Result<(), ContractError> {
+        if self.val_set.validator_state(storage, valoper)?.is_some() {
+            self.val_set.jail_validator(storage, valoper, height)?;
+            return Ok(());
+        }
+        for queued in self.valset_backlog.iter(storage)? {
+            if queued?.valoper == valoper {
+                self.val_set
+                    .jail_queued_validator(storage, valoper, height)?;
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Stops tracking the validators tombstoned the earliest over `max_tracked_validators`, if
+    /// set. Validators with stakes on them are kept, so they can still be slashed, unstaked from
+    /// and have their rewards withdrawn.
+    fn prune_validators(&self, storage: &mut dyn Storage) -> Result<Vec<String>, ContractError> {
+        let max = match self.config.load(storage)?.max_tracked_validators {
+            Some(max) => max,
+            None => return Ok(vec![]),
+        };
+        let pruned = self.val_set.prune_inactive(
+            storage,
+            max,
+            PRUNE_VALIDATORS_LIMIT,
+            |storage, valoper| {
+                let stakers = self.validator_stakers.may_load(storage, valoper)?;
+                Ok(stakers.unwrap_or_default() > 0)
+            },
+        )?;
+        Ok(pruned)
+    }
+
+    /// Adds the next `limit` validators received from the consumer to the valset.
+    ///
+    /// Big validator sets are added in chunks to stay within the block gas limit. New stakes are
+    /// rejected until the backlog is drained, so anyone can call this.
+    #[msg(exec)]
+    pub fn continue_valset_sync(
+        &self,
+        ctx: ExecCtx,
+        limit: Option<u32>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let limit = limit
+            .unwrap_or(DEFAULT_VALSET_SYNC_LIMIT)
+            .min(MAX_VALSET_SYNC_LIMIT);
+        let processed = self.sync_valset(ctx.deps.storage, limit)?;
+        let pending = self.valset_backlog.len(ctx.deps.storage)?;
+        if processed > 0 && pending == 0 {
+            self.valset_synced.save(ctx.deps.storage, &true)?;
+        }
+
+        let resp = Response::new()
+            .add_attribute("action", "continue_valset_sync")
+            .add_attribute("processed", processed.to_string())
+            .add_attribute("pending", pending.to_string());
+
+        Ok(resp)
+    }
+
+    /// Credits the slashes redistributed to the stakers of the other validators, over the next
+    /// `limit` validators.
+    ///
+    /// Redistributions are credited as they are paid back by the vault, in chunks to stay within
+    /// the block gas limit. Anyone can call this.
+    #[msg(exec)]
+    pub fn continue_slash_redistribution(
+        &self,
+        mut ctx: ExecCtx,
+        limit: Option<u32>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let limit = limit
+            .unwrap_or(DEFAULT_REDISTRIBUTION_LIMIT)
+            .min(MAX_REDISTRIBUTION_LIMIT);
+        let (processed, events) =
+            self.credit_redistributions(ctx.deps.branch(), &ctx.env, limit)?;
+        let pending = self.slash_redistributions.len(ctx.deps.storage)?;
+
+        let resp = Response::new()
+            .add_events(events)
+            .add_attribute("action", "continue_slash_redistribution")
+            .add_attribute("processed", processed.to_string())
+            .add_attribute("pending", pending.to_string());
+
+        Ok(resp)
+    }
+
+    /// Asks the consumer for its whole validator set, in case an `AddValidators` packet was lost.
+    ///
+    /// Anyone can call it, at most once every `VALIDATOR_SYNC_INTERVAL` blocks.
+    #[msg(exec)]
+    pub fn request_validator_sync(&self, ctx: ExecCtx) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let height = ctx.env.block.height;
+        if let Some(requested) = self.validator_sync_requested.may_load(ctx.deps.storage)? {
+            let next = requested + VALIDATOR_SYNC_INTERVAL;
+            ensure!(height >= next, ContractError::ValidatorSyncTooSoon(next));
+        }
+        self.validator_sync_requested
+            .save(ctx.deps.storage, &height)?;
+
+        #[allow(unused_mut)]
+        let mut resp = Response::new().add_attribute("action", "request_validator_sync");
+
+        let channel = IBC_CHANNEL.load(ctx.deps.storage)?;
+        let msg = IbcMsg::SendPacket {
+            channel_id: channel.endpoint.channel_id,
+            data: to_binary(&ProviderPacket::RequestValidators {})?,
+            timeout: packet_timeout(&ctx.env),
+        };
+        // send packet if we are ibc enabled
+        #[cfg(not(any(test, feature = "mt")))]
+        {
+            resp = resp.add_message(msg);
+        }
+        #[cfg(any(test, feature = "mt"))]
+        {
+            let _ = msg;
         }
 
         Ok(resp)
     }
 
+    /// Merges the consumer validator set received in response to `request_validator_sync`.
+    /// Validators already known are left untouched, so their start heights are kept.
+    /// In test code, this is called from `test_validator_sync`.
+    /// In non-test code, this is called from `ibc_packet_ack`
+    pub(crate) fn merge_validator_sync(
+        &self,
+        storage: &mut dyn Storage,
+        height: u64,
+        validators: Vec<AddValidator>,
+    ) -> Result<u32, ContractError> {
+        let mut unknown = vec![];
+        for validator in validators {
+            let known = self
+                .val_set
+                .validator_state(storage, &validator.valoper)?
+                .is_some();
+            // Pruned validators were tombstoned, and can't come back
+            if !known && !self.val_set.is_pruned(storage, &validator.valoper)? {
+                unknown.push(validator);
+            }
+        }
+        self.last_validator_sync.save(storage, &height)?;
+        self.add_validators(storage, unknown, DEFAULT_VALSET_SYNC_LIMIT)
+    }
+
     /// Distributes reward among users staking via particular validator. Distribution is performed
     /// proportionally to amount of tokens staked by user.
     /// In test code, this is called from `test_distribute_rewards`.
     /// In non-test code, this is called from `ibc_packet_receive`
     pub(crate) fn distribute_rewards(
         &self,
-        mut deps: DepsMut,
+        deps: DepsMut,
         validator: &str,
         rewards: Coin,
     ) -> Result<Event, ContractError> {
         // check we have the proper denom
         let config = self.config.load(deps.storage)?;
-        ensure_eq!(
-            rewards.denom,
-            config.rewards_denom,
-            PaymentError::MissingDenom(rewards.denom)
-        );
+        if !config.is_rewards_denom(&rewards.denom) {
+            return Err(PaymentError::MissingDenom(rewards.denom).into());
+        }
+
+        let event = self.distribute_rewards_unchecked(
+            deps.storage,
+            &config,
+            validator,
+            &rewards.denom,
+            rewards.amount,
+        )?;
+        self.record_rewards_distributed(deps.storage, &rewards.denom, rewards.amount)?;
+        Ok(event)
+    }
 
-        self.distribute_rewards_unchecked(&mut deps, validator, rewards.amount)
+    /// Adds `amount` of `denom` to the rewards distributed so far
+    fn record_rewards_distributed(
+        &self,
+        storage: &mut dyn Storage,
+        denom: &str,
+        amount: Uint128,
+    ) -> StdResult<()> {
+        update_stat(storage, &self.rewards_distributed, |mut distributed| {
+            *distributed.entry(denom.to_owned()).or_default() += amount;
+            distributed
+        })
     }
 
+    /// Distributes `amount` of `denom` between the stakers of `validator`, in proportion to their
+    /// stake.
+    ///
+    /// The points which can't be split evenly between all the staked tokens are kept in
+    /// `points_leftover`, and added to the next distribution. Nothing is lost on the validator
+    /// side: rounding down to whole units only happens when calculating each staker's rewards.
+    ///
+    /// Rewards of validators never announced by the consumer are rejected, so that they are
+    /// refunded through the error ack instead of being held with no one to withdraw them.
     fn distribute_rewards_unchecked(
         &self,
-        deps: &mut DepsMut,
+        storage: &mut dyn Storage,
+        config: &Config,
         validator: &str,
+        denom: &str,
         amount: Uint128,
     ) -> Result<Event, ContractError> {
-        let mut distribution = self
-            .distribution
-            .may_load(deps.storage, validator)?
-            .unwrap_or_default();
+        ensure!(
+            self.val_set.validator_state(storage, validator)?.is_some(),
+            ContractError::UnknownValidator(validator.to_owned())
+        );
+
+        let accounting = config.accounting_denom(denom);
+        let mut distribution = self.load_distribution(storage, config, validator, accounting)?;
 
         let total_stake = Uint256::from(distribution.total_stake);
         let points_distributed =
@@ -531,10 +2108,11 @@ impl ExternalStakingContract<'_> {
         distribution.points_per_stake += points_per_stake;
 
         self.distribution
-            .save(deps.storage, validator, &distribution)?;
+            .save(storage, (validator, accounting), &distribution)?;
 
         let event = Event::new("distribute_rewards")
             .add_attribute("validator", validator)
+            .add_attribute("denom", denom)
             .add_attribute("amount", amount.to_string());
 
         Ok(event)
@@ -542,99 +2120,272 @@ impl ExternalStakingContract<'_> {
 
     pub(crate) fn distribute_rewards_batch(
         &self,
-        mut deps: DepsMut,
+        deps: DepsMut,
         rewards: &[RewardInfo],
         denom: &str,
     ) -> Result<Vec<Event>, ContractError> {
         // check we have the proper denom
         let config = self.config.load(deps.storage)?;
-        ensure_eq!(
-            denom,
-            config.rewards_denom,
-            ContractError::InvalidDenom(config.rewards_denom)
+        ensure!(
+            config.is_rewards_denom(denom),
+            ContractError::InvalidDenom(
+                config
+                    .rewards_denoms
+                    .iter()
+                    .map(RewardDenom::denom)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
         );
 
-        rewards
-            .iter()
-            .map(|reward_info| {
-                self.distribute_rewards_unchecked(
-                    &mut deps,
-                    &reward_info.validator,
-                    reward_info.reward,
-                )
-            })
-            .collect()
+        rewards
+            .iter()
+            .map(|reward_info| {
+                let event = self.distribute_rewards_unchecked(
+                    deps.storage,
+                    &config,
+                    &reward_info.validator,
+                    denom,
+                    reward_info.reward,
+                )?;
+                self.record_rewards_distributed(deps.storage, denom, reward_info.reward)?;
+                Ok(event)
+            })
+            .collect()
+    }
+
+    /// Withdraw rewards from staking via given validator, in all the rewards denoms.
+    ///
+    /// The redistributed slashes, in the collateral denom, are paid to the staker on this side.
+    #[msg(exec)]
+    pub fn withdraw_rewards(
+        &self,
+        ctx: ExecCtx,
+        validator: String,
+        /// Address on the consumer side to receive the rewards
+        remote_recipient: String,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let config = self.config.load(ctx.deps.storage)?;
+        // Positions on validators pruned since they were opened can still be withdrawn from
+        let stake = match self
+            .stakes
+            .stake
+            .may_load(ctx.deps.storage, (&ctx.info.sender, &validator))?
+        {
+            Some(stake) => stake,
+            None if self
+                .val_set
+                .validator_state(ctx.deps.storage, &validator)?
+                .is_none() =>
+            {
+                return Err(ContractError::UnknownValidator(validator))
+            }
+            None => Stake::default(),
+        };
+
+        let rewards = self.calculate_rewards(ctx.deps.storage, &config, &validator, &stake)?;
+        let rewards: Vec<_> = rewards
+            .into_iter()
+            .filter(|c| !c.amount.is_zero())
+            .collect();
+
+        if rewards.is_empty() {
+            return Err(ContractError::NoRewards);
+        }
+        self.record_activity(
+            ctx.deps.storage,
+            &ctx.info.sender,
+            ctx.env.block.time,
+            false,
+        )?;
+
+        let resp = Response::new()
+            .add_attribute("action", "withdraw_rewards")
+            .add_attribute("owner", ctx.info.sender.to_string())
+            .add_attribute("validator", &validator)
+            .add_attribute("recipient", &remote_recipient)
+            .add_attribute("rewards", join_coins(&rewards));
+
+        self.transfer_rewards(
+            ctx.deps.storage,
+            &ctx.env,
+            &ctx.info.sender,
+            &validator,
+            &remote_recipient,
+            rewards,
+            resp,
+        )
     }
 
-    /// Withdraw rewards from staking via given validator
+    /// Withdraws the rewards of all the validators the caller is staking on, in a single call.
+    ///
+    /// Emits a `withdraw_rewards` event per validator with rewards to withdraw, along with their
+    /// total amount.
     #[msg(exec)]
-    pub fn withdraw_rewards(
+    pub fn withdraw_all_rewards(
         &self,
         ctx: ExecCtx,
-        validator: String,
         /// Address on the consumer side to receive the rewards
         remote_recipient: String,
     ) -> Result<Response, ContractError> {
         nonpayable(&ctx.info)?;
 
-        let stake = self
+        let config = self.config.load(ctx.deps.storage)?;
+        let stakes: Vec<_> = self
             .stakes
             .stake
-            .may_load(ctx.deps.storage, (&ctx.info.sender, &validator))?
-            .unwrap_or_default();
+            .prefix(&ctx.info.sender)
+            .range(ctx.deps.storage, None, None, Order::Ascending)
+            .collect::<Result<_, _>>()?;
 
-        let distribution = self
-            .distribution
-            .may_load(ctx.deps.storage, &validator)?
-            .unwrap_or_default();
+        let mut resp = Response::new();
+        let mut total: BTreeMap<String, Uint128> = BTreeMap::new();
+        for (validator, stake) in stakes {
+            let rewards = self.calculate_rewards(ctx.deps.storage, &config, &validator, &stake)?;
+            let rewards: Vec<_> = rewards
+                .into_iter()
+                .filter(|c| !c.amount.is_zero())
+                .collect();
+            if rewards.is_empty() {
+                continue;
+            }
 
-        let amount = Self::calculate_reward(&stake, &distribution)?;
+            for reward in &rewards {
+                *total.entry(reward.denom.clone()).or_default() += reward.amount;
+            }
+            resp = resp.add_event(
+                Event::new("withdraw_rewards")
+                    .add_attribute("validator", &validator)
+                    .add_attribute("amount", join_coins(&rewards)),
+            );
+            resp = self.transfer_rewards(
+                ctx.deps.storage,
+                &ctx.env,
+                &ctx.info.sender,
+                &validator,
+                &remote_recipient,
+                rewards,
+                resp,
+            )?;
+        }
 
-        if amount.is_zero() {
+        if total.is_empty() {
             return Err(ContractError::NoRewards);
         }
+        self.record_activity(
+            ctx.deps.storage,
+            &ctx.info.sender,
+            ctx.env.block.time,
+            false,
+        )?;
+        let total: Vec<_> = total
+            .into_iter()
+            .map(|(denom, amount)| Coin { denom, amount })
+            .collect();
 
-        #[allow(unused_mut)]
-        let mut resp = Response::new()
-            .add_attribute("action", "withdraw_rewards")
+        Ok(resp
+            .add_attribute("action", "withdraw_all_rewards")
             .add_attribute("owner", ctx.info.sender.to_string())
-            .add_attribute("validator", &validator)
             .add_attribute("recipient", &remote_recipient)
-            .add_attribute("amount", amount.to_string());
-
-        // prepare the pending tx
-        let tx_id = self.next_tx_id(ctx.deps.storage)?;
-        let new_tx = Tx::InFlightTransferFunds {
-            id: tx_id,
-            amount,
-            staker: ctx.info.sender,
-            validator,
-        };
-        self.pending_txs.save(ctx.deps.storage, tx_id, &new_tx)?;
+            .add_attribute("amount", join_coins(&total)))
+    }
 
-        // Crate the IBC packet
-        let config = self.config.load(ctx.deps.storage)?;
-        let rewards = coin(amount.u128(), config.rewards_denom);
-        let packet = ProviderPacket::TransferRewards {
-            rewards,
-            recipient: remote_recipient,
-            tx_id,
-        };
-        let channel_id = IBC_CHANNEL.load(ctx.deps.storage)?.endpoint.channel_id;
-        let send_msg = IbcMsg::SendPacket {
-            channel_id,
-            data: to_binary(&packet)?,
-            timeout: packet_timeout(&ctx.env),
-        };
+    /// Records an activity of `user` at `time`, which is also their first stake if `stake` is set
+    /// and none was recorded yet
+    fn record_activity(
+        &self,
+        storage: &mut dyn Storage,
+        user: &Addr,
+        time: Timestamp,
+        stake: bool,
+    ) -> StdResult<()> {
+        let mut meta = self.user_meta.may_load(storage, user)?.unwrap_or_default();
+        if stake && meta.first_stake_at.is_none() {
+            meta.first_stake_at = Some(time);
+        }
+        meta.last_activity_at = Some(time);
+        self.user_meta.save(storage, user, &meta)
+    }
 
-        // TODO: send in test code when we can handle it
-        #[cfg(not(any(test, feature = "mt")))]
-        {
-            resp = resp.add_message(send_msg);
+    /// Creates the pending txs and IBC packets transferring the `rewards` of `staker` on
+    /// `validator` to the consumer side. One transfer per denom, as they are sent as separate
+    /// packets. Rewards in the collateral denom are sent to `staker` on this side.
+    ///
+    /// The rewards are accounted as withdrawn right away, so they can't be withdrawn again while
+    /// the transfers are in flight. They are given back if the transfers are rolled back. The
+    /// pending txs record the denom the rewards are accounted in, which trace changes don't alter.
+    #[allow(clippy::too_many_arguments, unused_mut)]
+    fn transfer_rewards(
+        &self,
+        storage: &mut dyn Storage,
+        env: &Env,
+        staker: &Addr,
+        validator: &str,
+        remote_recipient: &str,
+        rewards: Vec<Coin>,
+        mut resp: Response,
+    ) -> Result<Response, ContractError> {
+        let config = self.config.load(storage)?;
+        let mut stake = self.stakes.stake.load(storage, (staker, validator))?;
+        for reward in &rewards {
+            stake
+                .rewards
+                .entry(config.accounting_denom(&reward.denom).to_owned())
+                .or_default()
+                .withdrawn_funds += reward.amount;
         }
-        #[cfg(any(test, feature = "mt"))]
-        {
-            let _ = send_msg;
+        self.stakes
+            .stake
+            .save(storage, (staker, validator), &stake)?;
+
+        let channel_id = IBC_CHANNEL.load(storage)?.endpoint.channel_id;
+        for rewards in rewards {
+            // The redistributed slashes are held by this contract, and paid right away
+            if rewards.denom == config.denom {
+                update_stat(storage, &self.redistribution_reserve, |reserve| {
+                    reserve.saturating_sub(rewards.amount)
+                })?;
+                resp = resp.add_message(BankMsg::Send {
+                    to_address: staker.to_string(),
+                    amount: vec![rewards],
+                });
+                continue;
+            }
+
+            // prepare the pending tx
+            let tx_id = self.next_tx_id(storage)?;
+            let new_tx = Tx::InFlightTransferFunds {
+                id: tx_id,
+                amount: rewards.amount,
+                denom: config.accounting_denom(&rewards.denom).to_owned(),
+                staker: staker.clone(),
+                validator: validator.to_owned(),
+            };
+            self.pending_txs.save(storage, tx_id, &new_tx)?;
+
+            // Crate the IBC packet
+            let packet = ProviderPacket::TransferRewards {
+                rewards,
+                recipient: remote_recipient.to_owned(),
+                tx_id,
+            };
+            let send_msg = IbcMsg::SendPacket {
+                channel_id: channel_id.clone(),
+                data: to_binary(&packet)?,
+                timeout: packet_timeout(env),
+            };
+
+            // TODO: send in test code when we can handle it
+            #[cfg(not(any(test, feature = "mt")))]
+            {
+                resp = resp.add_message(send_msg);
+            }
+            #[cfg(any(test, feature = "mt"))]
+            {
+                let _ = send_msg;
+            }
         }
 
         Ok(resp)
@@ -645,20 +2396,40 @@ impl ExternalStakingContract<'_> {
     pub(crate) fn rollback_withdraw_rewards(
         &self,
         deps: DepsMut,
+        env: Env,
         tx_id: u64,
     ) -> Result<(), ContractError> {
-        let tx = self.pending_txs.load(deps.storage, tx_id)?;
+        let tx = self.pending_txs.txs.load(deps.storage, tx_id)?;
 
-        // Verify tx is of the right type and remove it from the map
-        match tx {
-            Tx::InFlightTransferFunds { .. } => {
-                self.pending_txs.remove(deps.storage, tx_id);
-            }
+        // Verify tx is of the right type and get data
+        let (amount, denom, staker, validator) = match tx {
+            Tx::InFlightTransferFunds {
+                amount,
+                denom,
+                staker,
+                validator,
+                ..
+            } => (amount, denom, staker, validator),
             _ => {
                 return Err(ContractError::WrongTypeTx(tx_id, tx));
             }
         };
 
+        self.pending_txs.remove(deps.storage, tx_id)?;
+        self.tx_history
+            .record(deps.storage, tx_id, false, env.block.time)?;
+
+        // Give the rewards back, as they were not transferred
+        let mut stake = self
+            .stakes
+            .stake
+            .load(deps.storage, (&staker, &validator))?;
+        stake.rewards.entry(denom).or_default().withdrawn_funds -= amount;
+
+        self.stakes
+            .stake
+            .save(deps.storage, (&staker, &validator), &stake)?;
+
         Ok(())
     }
 
@@ -667,39 +2438,248 @@ impl ExternalStakingContract<'_> {
     pub(crate) fn commit_withdraw_rewards(
         &self,
         deps: DepsMut,
+        env: Env,
         tx_id: u64,
     ) -> Result<(), ContractError> {
         // Load tx
-        let tx = self.pending_txs.load(deps.storage, tx_id)?;
-        self.pending_txs.remove(deps.storage, tx_id);
+        let tx = self.pending_txs.txs.load(deps.storage, tx_id)?;
+        self.pending_txs.remove(deps.storage, tx_id)?;
+        self.tx_history
+            .record(deps.storage, tx_id, true, env.block.time)?;
 
-        // Verify tx is of the right type and get data
-        let (amount, staker, validator) = match tx {
+        // Verify tx is of the right type. The rewards are already accounted as withdrawn
+        let (staker, validator) = match tx {
             Tx::InFlightTransferFunds {
-                amount,
-                staker,
-                validator,
-                ..
-            } => (amount, staker, validator),
-            _ => {
-                return Err(ContractError::WrongTypeTx(tx_id, tx));
-            }
+                staker, validator, ..
+            } => (staker, validator),
+            _ => return Err(ContractError::WrongTypeTx(tx_id, tx)),
         };
 
-        // Update withdrawn_funds to hold this transfer
-        let mut stake = self
+        // The position may be closed now
+        let config = self.config.load(deps.storage)?;
+        let stake = self
             .stakes
             .stake
             .load(deps.storage, (&staker, &validator))?;
-        stake.withdrawn_funds += amount;
+        if self.is_stake_closed(deps.storage, &config, &staker, &validator, &stake)? {
+            self.remove_stake(deps.storage, &staker, &validator)?;
+        }
 
-        self.stakes
+        Ok(())
+    }
+
+    /// Releases the matured unbonds of `owner` on `validators`, or on all their validators if
+    /// `None`, and returns the released amount. Validators without a stake are skipped.
+    fn release_matured(
+        &self,
+        storage: &mut dyn Storage,
+        block: &BlockInfo,
+        config: &Config,
+        owner: &Addr,
+        validators: Option<Vec<String>>,
+    ) -> Result<Uint128, ContractError> {
+        let stakes: Vec<_> = match validators {
+            // Deduplicated, so the same unbonds are not released twice
+            Some(validators) => BTreeSet::from_iter(validators)
+                .into_iter()
+                .map(|validator| {
+                    let stake = self.stakes.stake.may_load(storage, (owner, &validator))?;
+                    Ok(stake.map(|stake| (validator, stake)))
+                })
+                .filter_map(StdResult::transpose)
+                .collect::<StdResult<_>>()?,
+            None => self
+                .stakes
+                .stake
+                .prefix(owner)
+                .range(storage, None, None, Order::Ascending)
+                .collect::<StdResult<_>>()?,
+        };
+
+        stakes
+            .into_iter()
+            .try_fold(Uint128::zero(), |acc, (validator, mut stake)| {
+                let released = stake.release_pending(block);
+
+                if !released.is_zero() {
+                    self.save_or_remove_stake(storage, config, owner, &validator, &stake)?;
+                    update_stat(storage, &self.total_pending_unbonds, |total| {
+                        total.saturating_sub(released)
+                    })?;
+                }
+
+                Ok(acc + released)
+            })
+    }
+
+    /// Saves the stake of `user` on `validator`, or removes it if the position is closed
+    fn save_or_remove_stake(
+        &self,
+        storage: &mut dyn Storage,
+        config: &Config,
+        user: &Addr,
+        validator: &str,
+        stake: &Stake,
+    ) -> Result<(), ContractError> {
+        if self.is_stake_closed(storage, config, user, validator, stake)? {
+            self.remove_stake(storage, user, validator)?;
+        } else {
+            self.stakes.stake.save(storage, (user, validator), stake)?;
+        }
+        Ok(())
+    }
+
+    /// Counts a new stake entry of `user` on `validator`, and returns their number of validators
+    fn stake_entry_added(
+        &self,
+        storage: &mut dyn Storage,
+        user: &Addr,
+        validator: &str,
+    ) -> StdResult<u32> {
+        let count = self.validators_count.may_load(storage, user)?.unwrap_or(0) + 1;
+        self.validators_count.save(storage, user, &count)?;
+        if count == 1 {
+            update_stat(storage, &self.total_stakers, |stakers| stakers + 1)?;
+        }
+        let stakers = self
+            .validator_stakers
+            .may_load(storage, validator)?
+            .unwrap_or(0)
+            + 1;
+        self.validator_stakers.save(storage, validator, &stakers)?;
+        if stakers == 1 {
+            update_stat(storage, &self.total_validators, |validators| validators + 1)?;
+        }
+        Ok(count)
+    }
+
+    /// Validates a `receive_virtual_stake` of `amount` for `owner` with `msg`, without changing
+    /// any state. Returns the owner, the parsed msg and the current stake on its validator, if any
+    fn check_virtual_stake(
+        &self,
+        deps: Deps,
+        config: &Config,
+        owner: &str,
+        amount: &Coin,
+        msg: &Binary,
+    ) -> Result<(Addr, ReceiveVirtualStake, Option<Stake>), ContractError> {
+        // sending proper denom
+        ensure_eq!(
+            amount.denom,
+            config.denom,
+            ContractError::InvalidDenom(config.denom.clone())
+        );
+
+        let owner = deps.api.addr_validate(owner)?;
+
+        // no new stakes until the valset is fully synced
+        let pending = self.valset_backlog.len(deps.storage)?;
+        ensure!(pending == 0, ContractError::ValsetSyncInProgress(pending));
+
+        // parse and validate message
+        let msg: ReceiveVirtualStake = from_binary(msg)?;
+        match self.val_set.validator_state(deps.storage, &msg.validator)? {
+            Some(state) if state.is_active() => {}
+            Some(_) => return Err(ContractError::ValidatorNotActive(msg.validator)),
+            None => return Err(ContractError::UnknownValidator(msg.validator)),
+        }
+        let stake = self
+            .stakes
             .stake
-            .save(deps.storage, (&staker, &validator), &stake)?;
+            .may_load(deps.storage, (&owner, &msg.validator))?;
+        // Staking on a new validator is bounded by the max validators per user
+        if let (None, Some(max)) = (&stake, config.max_validators_per_user) {
+            let count = self.validators_count.may_load(deps.storage, &owner)?;
+            ensure!(
+                count.unwrap_or(0) < max,
+                ContractError::TooManyValidators(max)
+            );
+        }
+
+        Ok((owner, msg, stake))
+    }
 
+    /// Removes the stake entry of `user` on `validator`, and uncounts it. The rewards withdrawn
+    /// from it are kept in the user's `closed_withdrawn`
+    fn remove_stake(
+        &self,
+        storage: &mut dyn Storage,
+        user: &Addr,
+        validator: &str,
+    ) -> StdResult<()> {
+        if let Some(stake) = self.stakes.stake.may_load(storage, (user, validator))? {
+            for (denom, rewards) in &stake.rewards {
+                if rewards.withdrawn_funds.is_zero() {
+                    continue;
+                }
+                self.closed_withdrawn.update(
+                    storage,
+                    (user, denom),
+                    |withdrawn| -> StdResult<_> {
+                        Ok(withdrawn.unwrap_or_default() + rewards.withdrawn_funds)
+                    },
+                )?;
+            }
+        }
+        self.stakes.stake.remove(storage, (user, validator))?;
+        match self.validators_count.may_load(storage, user)?.unwrap_or(0) {
+            0 => {}
+            1 => {
+                self.validators_count.remove(storage, user);
+                update_stat(storage, &self.total_stakers, |stakers| {
+                    stakers.saturating_sub(1)
+                })?;
+            }
+            count => self.validators_count.save(storage, user, &(count - 1))?,
+        }
+        match self
+            .validator_stakers
+            .may_load(storage, validator)?
+            .unwrap_or(0)
+        {
+            0 => {}
+            1 => {
+                self.validator_stakers.remove(storage, validator);
+                update_stat(storage, &self.total_validators, |validators| {
+                    validators.saturating_sub(1)
+                })?;
+            }
+            stakers => self
+                .validator_stakers
+                .save(storage, validator, &(stakers - 1))?,
+        }
         Ok(())
     }
 
+    /// Checks if a position is closed, so it can be removed: nothing is staked, there are no
+    /// pending unbonds nor rewards left to withdraw, and no pending tx is involving it
+    fn is_stake_closed(
+        &self,
+        storage: &dyn Storage,
+        config: &Config,
+        user: &Addr,
+        validator: &str,
+        stake: &Stake,
+    ) -> Result<bool, ContractError> {
+        if !stake.stake.high().is_zero() || !stake.pending_unbonds.is_empty() {
+            return Ok(false);
+        }
+
+        let rewards = self.calculate_rewards(storage, config, validator, stake)?;
+        if rewards.iter().any(|reward| !reward.amount.is_zero()) {
+            return Ok(false);
+        }
+
+        if self
+            .pending_txs
+            .references_stake(storage, user, validator)?
+        {
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+
     /// Slashes a validator.
     ///
     /// In test code, this is called from `test_handle_slashing`.
@@ -711,6 +2691,67 @@ impl ExternalStakingContract<'_> {
         storage: &mut dyn Storage,
         validator: &str,
     ) -> Result<WasmMsg, ContractError> {
+        let config = self.config.load(storage)?;
+        let (slash_infos, _, redistributed) = self.slash_validator(env, storage, validator)?;
+
+        // Route associated users to vault for slashing of their collateral
+        self.slash_msg(env, &config, slash_infos, redistributed, None)
+    }
+
+    /// Message to the vault slashing the collateral as per `slash_infos`, paying back the
+    /// `redistributed` part of it to this contract first, and then the `bounty` if any
+    fn slash_msg(
+        &self,
+        env: &Env,
+        config: &Config,
+        slash_infos: Vec<SlashInfo>,
+        redistributed: Uint128,
+        bounty: Option<(String, Uint128)>,
+    ) -> Result<WasmMsg, ContractError> {
+        let msg = if !redistributed.is_zero() {
+            let payouts = std::iter::once((env.contract.address.to_string(), redistributed))
+                .chain(bounty)
+                .map(|(recipient, amount)| SlashPayout { recipient, amount })
+                .collect();
+            config
+                .vault
+                .process_cross_slashing_with_payouts(slash_infos, payouts)?
+        } else if let Some((recipient, bounty)) = bounty {
+            config
+                .vault
+                .process_cross_slashing_with_bounty(slash_infos, recipient, bounty)?
+        } else {
+            config.vault.process_cross_slashing(slash_infos)?
+        };
+        Ok(msg)
+    }
+
+    /// Message crediting the slash redistributions, once the vault paid them back. To be sent after
+    /// the slashing messages, if any redistribution is pending
+    pub(crate) fn redistribution_msg(
+        &self,
+        env: &Env,
+        storage: &dyn Storage,
+    ) -> StdResult<Option<WasmMsg>> {
+        if self.slash_redistributions.len(storage)? == 0 {
+            return Ok(None);
+        }
+        let msg = WasmMsg::Execute {
+            contract_addr: env.contract.address.to_string(),
+            msg: to_binary(&ExecMsg::ContinueSlashRedistribution { limit: None })?,
+            funds: vec![],
+        };
+        Ok(Some(msg))
+    }
+
+    /// Slashes the stakes on `validator`, returning the slashes to process on the vault, the
+    /// slashed amount not redistributed, and the redistributed one
+    fn slash_validator(
+        &self,
+        env: &Env,
+        storage: &mut dyn Storage,
+        validator: &str,
+    ) -> Result<(Vec<SlashInfo>, Uint128, Uint128), ContractError> {
         let config = self.config.load(storage)?;
         // Get the list of users staking via this validator
         let users = self
@@ -728,6 +2769,7 @@ impl ExternalStakingContract<'_> {
 
         // Slash their stake in passing
         let mut slash_infos = vec![];
+        let mut total_slashed = Uint128::zero();
         for (user, ref mut stake) in users {
             let stake_low = stake.stake.low();
             let stake_high = stake.stake.high();
@@ -741,31 +2783,275 @@ impl ExternalStakingContract<'_> {
                 stake_high - stake_slash,
             );
 
-            // Distribution alignment
-            let mut distribution = self
-                .distribution
-                .may_load(storage, validator)?
-                .unwrap_or_default();
-            stake
-                .points_alignment
-                .stake_decreased(stake_slash, distribution.points_per_stake);
-            distribution.total_stake -= stake_slash;
-            self.distribution.save(storage, validator, &distribution)?;
+            // Rewards checkpoint
+            self.stake_decreased(storage, &config, validator, stake, stake_slash)?;
+
+            // Slash the unbondings
+            let pending_slashed = stake.slash_pending(&env.block, config.max_slashing);
+            update_stat(storage, &self.total_pending_unbonds, |total| {
+                total.saturating_sub(pending_slashed)
+            })?;
+
+            self.stakes.stake.save(storage, (&user, validator), stake)?;
+
+            total_slashed += stake_slash + pending_slashed;
+            slash_infos.push(SlashInfo {
+                user: user.to_string(),
+                slash: stake_slash + pending_slashed,
+            });
+        }
+
+        // Along with the stake restaked off it during its unbonding period
+        let redelegated = self
+            .redelegations
+            .prefix(validator)
+            .range(storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        for (user, mut redelegations) in redelegated {
+            redelegations.retain(|redelegation| redelegation.release_at > env.block.time);
+            let mut user_slashed = Uint128::zero();
+            for redelegation in redelegations.iter_mut() {
+                let slashed =
+                    self.slash_redelegation(env, storage, &config, &user, redelegation)?;
+                redelegation.amount -= slashed;
+                user_slashed += slashed;
+            }
+            if redelegations.is_empty() {
+                self.redelegations.remove(storage, (validator, &user));
+            } else {
+                self.redelegations
+                    .save(storage, (validator, &user), &redelegations)?;
+            }
+
+            if user_slashed.is_zero() {
+                continue;
+            }
+            total_slashed += user_slashed;
+            match slash_infos
+                .iter_mut()
+                .find(|info| info.user == user.as_str())
+            {
+                Some(info) => info.slash += user_slashed,
+                None => slash_infos.push(SlashInfo {
+                    user: user.to_string(),
+                    slash: user_slashed,
+                }),
+            }
+        }
+
+        // Slashes processed in the same block add up
+        let key = (validator, env.block.height);
+        let mut record = self
+            .slashes
+            .may_load(storage, key)?
+            .unwrap_or_else(|| SlashRecord {
+                ratio: config.max_slashing,
+                amount: Uint128::zero(),
+            });
+        record.amount += total_slashed;
+        self.slashes.save(storage, key, &record)?;
+
+        let redistributed = match config.slash_redistribution {
+            Some(ratio) => {
+                self.redistribute_slash(storage, &config, validator, total_slashed * ratio)?
+            }
+            None => Uint128::zero(),
+        };
+
+        Ok((slash_infos, total_slashed - redistributed, redistributed))
+    }
+
+    /// Slashes the stake restaked by `user` as per `redelegation`, on the validator it was moved
+    /// to. What was unstaked since is slashed off the pending unbonds. Returns the slashed amount
+    fn slash_redelegation(
+        &self,
+        env: &Env,
+        storage: &mut dyn Storage,
+        config: &Config,
+        user: &Addr,
+        redelegation: &Redelegation,
+    ) -> Result<Uint128, ContractError> {
+        let key = (user, redelegation.to.as_str());
+        let mut stake = match self.stakes.stake.may_load(storage, key)? {
+            Some(stake) => stake,
+            None => return Ok(Uint128::zero()),
+        };
+        let slash = redelegation.amount * config.max_slashing;
+
+        let stake_slash = min(slash, stake.stake.high());
+        stake.stake = ValueRange::new(
+            stake.stake.low().saturating_sub(stake_slash),
+            stake.stake.high() - stake_slash,
+        );
+        self.stake_decreased(storage, config, &redelegation.to, &mut stake, stake_slash)?;
+
+        let pending_slashed = stake.slash_pending_amount(&env.block, slash - stake_slash);
+        update_stat(storage, &self.total_pending_unbonds, |total| {
+            total.saturating_sub(pending_slashed)
+        })?;
+
+        self.stakes.stake.save(storage, key, &stake)?;
+        Ok(stake_slash + pending_slashed)
+    }
+
+    /// Schedules the redistribution of `amount` of the stake slashed on `validator` as rewards in
+    /// the collateral denom, between the other validators in proportion to their total stake.
+    /// Returns the amount to redistribute: nothing if there is no stake on the other validators
+    fn redistribute_slash(
+        &self,
+        storage: &mut dyn Storage,
+        config: &Config,
+        validator: &str,
+        amount: Uint128,
+    ) -> Result<Uint128, ContractError> {
+        let slashed_stake = self
+            .load_distribution(
+                storage,
+                config,
+                validator,
+                config.accounting_denom(&config.denom),
+            )?
+            .total_stake;
+        let total_stake = self
+            .total_stake
+            .may_load(storage)?
+            .unwrap_or_default()
+            .saturating_sub(slashed_stake);
+        if amount.is_zero() || total_stake.is_zero() {
+            return Ok(Uint128::zero());
+        }
+
+        self.slash_redistributions.push_back(
+            storage,
+            &SlashRedistribution {
+                validator: validator.to_owned(),
+                total_stake,
+                amount,
+                funded: false,
+                remaining: amount,
+                start_after: None,
+            },
+        )?;
+        Ok(amount)
+    }
 
-            // Slash the unbondings
-            let pending_slashed = stake.slash_pending(&env.block, config.max_slashing);
+    /// Credits the pending slash redistributions to the validators, at most `limit` of them.
+    ///
+    /// Each redistribution is first funded out of the collateral balance not held for the former
+    /// ones: whatever the vault couldn't pay back is not redistributed. Returns the number of
+    /// validators credited
+    fn credit_redistributions(
+        &self,
+        deps: DepsMut,
+        env: &Env,
+        limit: u32,
+    ) -> Result<(u32, Vec<Event>), ContractError> {
+        let config = self.config.load(deps.storage)?;
+        let mut processed = 0;
+        let mut events = vec![];
+        while processed < limit {
+            let mut redistribution = match self.slash_redistributions.pop_front(deps.storage)? {
+                Some(redistribution) => redistribution,
+                None => break,
+            };
 
-            self.stakes.stake.save(storage, (&user, validator), stake)?;
+            if !redistribution.funded {
+                let balance = deps
+                    .querier
+                    .query_balance(&env.contract.address, &config.denom)?
+                    .amount;
+                let reserved = self
+                    .redistribution_reserve
+                    .may_load(deps.storage)?
+                    .unwrap_or_default();
+                redistribution.amount =
+                    min(redistribution.amount, balance.saturating_sub(reserved));
+                redistribution.remaining = redistribution.amount;
+                redistribution.funded = true;
+                update_stat(deps.storage, &self.redistribution_reserve, |reserve| {
+                    reserve + redistribution.amount
+                })?;
+            }
 
-            slash_infos.push(SlashInfo {
-                user: user.to_string(),
-                slash: stake_slash + pending_slashed,
-            });
+            let batch = limit - processed;
+            let start = redistribution.start_after.as_deref().map(Bound::exclusive);
+            let mut validators = self
+                .validator_stakers
+                .keys(deps.storage, start, None, Order::Ascending)
+                .take(batch as usize + 1)
+                .collect::<StdResult<Vec<_>>>()?;
+            let exhausted = validators.len() <= batch as usize;
+            validators.truncate(batch as usize);
+            processed += validators.len() as u32;
+
+            for validator in &validators {
+                if *validator == redistribution.validator {
+                    continue;
+                }
+                let stake = self
+                    .load_distribution(
+                        deps.storage,
+                        &config,
+                        validator,
+                        config.accounting_denom(&config.denom),
+                    )?
+                    .total_stake;
+                // Stakes changed since the slash can't get more than what's left
+                let share = min(
+                    redistribution
+                        .amount
+                        .multiply_ratio(stake, redistribution.total_stake),
+                    redistribution.remaining,
+                );
+                if share.is_zero() {
+                    continue;
+                }
+                redistribution.remaining -= share;
+                events.push(self.distribute_rewards_unchecked(
+                    deps.storage,
+                    &config,
+                    validator,
+                    &config.denom,
+                    share,
+                )?);
+                update_stat(deps.storage, &self.slashes_redistributed, |total| {
+                    total + share
+                })?;
+            }
+
+            if exhausted {
+                // All validators credited, the rounding remainder is not held anymore
+                update_stat(deps.storage, &self.redistribution_reserve, |reserve| {
+                    reserve.saturating_sub(redistribution.remaining)
+                })?;
+            } else {
+                redistribution.start_after = validators.last().cloned();
+                self.slash_redistributions
+                    .push_front(deps.storage, &redistribution)?;
+            }
         }
 
-        // Route associated users to vault for slashing of their collateral
-        let msg = config.vault.process_cross_slashing(slash_infos)?;
-        Ok(msg)
+        Ok((processed, events))
+    }
+
+    /// Returns the pending unbonds of the user on the validator offered for sale. Listings of
+    /// pending unbonds already released, or changed since, are included.
+    #[msg(query)]
+    pub fn unbond_listings(
+        &self,
+        ctx: QueryCtx,
+        owner: String,
+        validator: String,
+    ) -> Result<UnbondListingsResponse, ContractError> {
+        let owner = ctx.deps.api.addr_validate(&owner)?;
+        let listings = self
+            .unbond_listings
+            .prefix((&owner, &validator))
+            .range(ctx.deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(_, listing)| listing))
+            .collect::<StdResult<_>>()?;
+
+        Ok(UnbondListingsResponse { listings })
     }
 
     /// Queries for contract configuration
@@ -792,7 +3078,26 @@ impl ExternalStakingContract<'_> {
         Ok(IbcChannelResponse { channel })
     }
 
-    /// Show all external validators that we know to be active (and can delegate to)
+    /// Queries the state of an external validator
+    #[msg(query)]
+    pub fn validator(
+        &self,
+        ctx: QueryCtx,
+        valoper: String,
+    ) -> Result<ValidatorResponse, ContractError> {
+        let status = self
+            .val_set
+            .validator_state(ctx.deps.storage, &valoper)?
+            .map(|state| match state {
+                ValidatorState::Active(_) => ValidatorStatus::Active {},
+                ValidatorState::Jailed { since, .. } => ValidatorStatus::Jailed { since },
+                ValidatorState::Tombstoned { height } => ValidatorStatus::Tombstoned { height },
+            });
+        Ok(ValidatorResponse { valoper, status })
+    }
+
+    /// Show all external validators that we know to be active (and can delegate to).
+    /// Jailed and tombstoned validators are not listed
     #[msg(query)]
     pub fn list_remote_validators(
         &self,
@@ -807,6 +3112,66 @@ impl ExternalStakingContract<'_> {
         Ok(ListRemoteValidatorsResponse { validators })
     }
 
+    /// Queries for the progress of the valset sync
+    #[msg(query)]
+    pub fn valset_sync(&self, ctx: QueryCtx) -> Result<ValsetSyncResponse, ContractError> {
+        let pending = self.valset_backlog.len(ctx.deps.storage)?;
+        Ok(ValsetSyncResponse { pending })
+    }
+
+    /// Queries whether the valset was synced with the consumer, that is whether
+    /// `list_remote_validators` is authoritative
+    #[msg(query)]
+    pub fn sync_status(&self, ctx: QueryCtx) -> Result<SyncStatusResponse, ContractError> {
+        let synced = self
+            .valset_synced
+            .may_load(ctx.deps.storage)?
+            .unwrap_or_default();
+        Ok(SyncStatusResponse { synced })
+    }
+
+    /// Queries the contract-wide statistics. They are maintained as the stakes change, so the
+    /// query is cheap whatever the number of stakes
+    #[msg(query)]
+    pub fn stats(&self, ctx: QueryCtx) -> Result<StatsResponse, ContractError> {
+        let storage = ctx.deps.storage;
+        let rewards_distributed = self
+            .rewards_distributed
+            .may_load(storage)?
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(denom, amount)| coin(amount.u128(), denom))
+            .collect();
+
+        Ok(StatsResponse {
+            total_stakers: self.total_stakers.may_load(storage)?.unwrap_or_default(),
+            total_validators: self.total_validators.may_load(storage)?.unwrap_or_default(),
+            total_stake: self.total_stake.may_load(storage)?.unwrap_or_default(),
+            total_pending_unbonds: self
+                .total_pending_unbonds
+                .may_load(storage)?
+                .unwrap_or_default(),
+            rewards_distributed,
+            slashes_redistributed: self
+                .slashes_redistributed
+                .may_load(storage)?
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Queries when the consumer validator set was last received, for monitoring to detect a
+    /// stale valset
+    #[msg(query)]
+    pub fn last_validator_sync(
+        &self,
+        ctx: QueryCtx,
+    ) -> Result<LastValidatorSyncResponse, ContractError> {
+        Ok(LastValidatorSyncResponse {
+            synced_at: self.last_validator_sync.may_load(ctx.deps.storage)?,
+            requested_at: self.validator_sync_requested.may_load(ctx.deps.storage)?,
+        })
+    }
+
     /// Queries for stake info
     ///
     /// If stake does not exist for (user, validator) pair, the zero-stake is returned
@@ -827,27 +3192,291 @@ impl ExternalStakingContract<'_> {
         Ok(stake)
     }
 
-    /// Paginated list of user stakes.
+    /// Value of the stake of `user` on `validator` left if it is slashed by the max slashing
+    /// ratio. Pending stakes are not included, as they may not be committed
+    #[msg(query)]
+    pub fn net_position(
+        &self,
+        ctx: QueryCtx,
+        user: String,
+        validator: String,
+    ) -> Result<NetPositionResponse, ContractError> {
+        let user = ctx.deps.api.addr_validate(&user)?;
+        let max_slash = self.config.load(ctx.deps.storage)?.max_slashing;
+        let stake = self
+            .stakes
+            .stake
+            .may_load(ctx.deps.storage, (&user, &validator))?
+            .unwrap_or_default()
+            .stake
+            .low();
+
+        Ok(NetPositionResponse {
+            stake,
+            max_slash,
+            worst_case: stake * (Decimal::one() - max_slash),
+        })
+    }
+
+    /// Activity timestamps of the user. Both are unset for unknown users
+    #[msg(query)]
+    pub fn user_meta(&self, ctx: QueryCtx, user: String) -> Result<UserMeta, ContractError> {
+        let user = ctx.deps.api.addr_validate(&user)?;
+        let meta = self
+            .user_meta
+            .may_load(ctx.deps.storage, &user)?
+            .unwrap_or_default();
+
+        Ok(meta)
+    }
+
+    /// Amount of the user's unbonded tokens which can be withdrawn now, over all validators
+    #[msg(query)]
+    pub fn withdrawable(&self, ctx: QueryCtx, user: String) -> Result<Uint128, ContractError> {
+        let user = ctx.deps.api.addr_validate(&user)?;
+        self.stakes
+            .stake
+            .prefix(&user)
+            .range(ctx.deps.storage, None, None, Order::Ascending)
+            .map(|item| {
+                let (_, stake) = item?;
+                Ok(stake.releasable(&ctx.env.block))
+            })
+            .sum()
+    }
+
+    /// Pending unbonds of `user` over all validators, sorted by release time. Only the first
+    /// `limit` ones are returned
+    #[msg(query)]
+    pub fn unlock_schedule(
+        &self,
+        ctx: QueryCtx,
+        user: String,
+        limit: Option<u32>,
+    ) -> Result<UnlockScheduleResponse, ContractError> {
+        let limit = clamp_page_limit(limit);
+        let user = ctx.deps.api.addr_validate(&user)?;
+
+        let mut unlocks = vec![];
+        for item in
+            self.stakes
+                .stake
+                .prefix(&user)
+                .range(ctx.deps.storage, None, None, Order::Ascending)
+        {
+            let (validator, stake) = item?;
+            unlocks.extend(stake.pending_unbonds.iter().map(|unbond| ScheduledUnlock {
+                validator: validator.clone(),
+                amount: unbond.amount,
+                release_at: unbond.release_at,
+            }));
+        }
+        // Stable sort, so unlocks released together stay ordered by validator
+        unlocks.sort_by_key(|unlock| unlock.release_at);
+        unlocks.truncate(limit);
+
+        Ok(UnlockScheduleResponse { unlocks })
+    }
+
+    /// Slashes of `validator`, sorted by the height they were processed at.
+    /// `start_after` is the last height included in previous page
+    #[msg(query)]
+    pub fn validator_slashes(
+        &self,
+        ctx: QueryCtx,
+        validator: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    ) -> Result<ValidatorSlashesResponse, ContractError> {
+        let limit = clamp_page_limit(limit);
+        let bound = start_after.map(Bound::exclusive);
+
+        let slashes = self
+            .slashes
+            .prefix(&validator)
+            .range(ctx.deps.storage, bound, None, Order::Ascending)
+            .map(|item| {
+                let (height, record) = item?;
+                Ok(ValidatorSlash {
+                    height,
+                    ratio: record.ratio,
+                    amount: record.amount,
+                })
+            })
+            .take(limit)
+            .collect::<StdResult<_>>()?;
+
+        Ok(ValidatorSlashesResponse { slashes })
+    }
+
+    /// Estimates when the unstakes of `user` from `validator` not committed yet are released. They
+    /// are expected to be committed once relayed, after the configured `relay_latency` from now,
+    /// and released an unbonding period later. Committed unstakes are in the stake
+    /// `pending_unbonds`, with their actual release time.
+    #[msg(query)]
+    pub fn estimated_release(
+        &self,
+        ctx: QueryCtx,
+        user: String,
+        validator: String,
+    ) -> Result<EstimatedReleaseResponse, ContractError> {
+        let user = ctx.deps.api.addr_validate(&user)?;
+        let config = self.config.load(ctx.deps.storage)?;
+        let release_at = ctx
+            .env
+            .block
+            .time
+            .plus_seconds(config.relay_latency.unwrap_or_default())
+            .plus_seconds(config.unbonding_period);
+
+        let mut unstakes = vec![];
+        for item in self
+            .pending_txs
+            .txs
+            .range(ctx.deps.storage, None, None, Order::Ascending)
+        {
+            let (tx_id, tx) = item?;
+            let (tx_user, tx_unstakes, created_at) = match Self::unstake_tx(tx_id, tx) {
+                Ok(unstake) => unstake,
+                Err(_) => continue,
+            };
+            if tx_user != user {
+                continue;
+            }
+            for (tx_validator, amount) in tx_unstakes {
+                if tx_validator == validator {
+                    unstakes.push(EstimatedRelease {
+                        tx_id,
+                        amount,
+                        requested_at: created_at,
+                        release_at,
+                    });
+                }
+            }
+        }
+
+        Ok(EstimatedReleaseResponse { unstakes })
+    }
+
+    /// Lists the stakes with pending changes, along with the pending tx changing them. Stakes no
+    /// pending tx references are locked for good, until `force_unlock` is called on them.
+    ///
+    /// `start_after` is the last `(owner, validator)` pair of the previous page
+    #[msg(query)]
+    pub fn locked_stakes(
+        &self,
+        ctx: QueryCtx,
+        start_after: Option<(String, String)>,
+        limit: Option<u32>,
+    ) -> Result<LockedStakesResponse, ContractError> {
+        let limit = clamp_page_limit(limit);
+        let bound = match start_after {
+            Some((owner, validator)) => {
+                let owner = ctx.deps.api.addr_validate(&owner)?;
+                Some(Bound::ExclusiveRaw(
+                    (&owner, validator.as_str()).joined_key(),
+                ))
+            }
+            None => None,
+        };
+
+        let locked = self
+            .stakes
+            .stake
+            .range(ctx.deps.storage, bound, None, Order::Ascending)
+            .filter(|item| match item {
+                Ok((_, stake)) => stake.stake.low() != stake.stake.high(),
+                Err(_) => true,
+            })
+            .take(limit)
+            .collect::<StdResult<Vec<_>>>()?;
+
+        let stakes = locked
+            .into_iter()
+            .map(|((owner, validator), stake)| {
+                let tx_id = self.stake_pending_tx(ctx.deps.storage, &owner, &validator)?;
+                Ok(LockedStake {
+                    owner: owner.into_string(),
+                    validator,
+                    stake: stake.stake,
+                    tx_id,
+                })
+            })
+            .collect::<StdResult<_>>()?;
+
+        Ok(LockedStakesResponse { stakes })
+    }
+
+    /// Paginated list of user stakes. Positions with nothing staked are skipped, unless
+    /// `include_zero` is set.
     ///
     /// `start_after` is the last validator of previous page
+    ///
+    /// `order_by` defaults to ordering by validator. With `StakeDesc`, the stakes are ordered by
+    /// decreasing amount (the `high` end of the range), then decreasing validator, and the page
+    /// starts after the `(start_after_stake, start_after)` cursor. `start_after_stake` defaults to
+    /// the current stake on `start_after`.
     #[msg(query)]
+    #[allow(clippy::too_many_arguments)]
     pub fn stakes(
         &self,
         ctx: QueryCtx,
         user: String,
         start_after: Option<String>,
         limit: Option<u32>,
+        include_zero: Option<bool>,
+        order_by: Option<StakesOrderBy>,
+        start_after_stake: Option<Uint128>,
     ) -> Result<StakesResponse, ContractError> {
+        let include_zero = include_zero.unwrap_or_default();
         let limit = clamp_page_limit(limit);
         let user = ctx.deps.api.addr_validate(&user)?;
 
-        let bound = start_after.as_deref().and_then(Bounder::exclusive_bound);
+        let stakes: Box<dyn Iterator<Item = StdResult<(String, Stake)>>> =
+            match order_by.unwrap_or_default() {
+                StakesOrderBy::Validator => {
+                    let bound = start_after.as_deref().and_then(Bounder::exclusive_bound);
+                    self.stakes.stake.prefix(&user).range(
+                        ctx.deps.storage,
+                        bound,
+                        None,
+                        Order::Ascending,
+                    )
+                }
+                StakesOrderBy::StakeDesc => {
+                    let bound = match start_after {
+                        Some(validator) => {
+                            let stake = match start_after_stake {
+                                Some(stake) => stake,
+                                None => self
+                                    .stakes
+                                    .stake
+                                    .may_load(ctx.deps.storage, (&user, &validator))?
+                                    .unwrap_or_default()
+                                    .stake
+                                    .high(),
+                            };
+                            Some(Bound::exclusive((stake.u128(), (user.clone(), validator))))
+                        }
+                        None => None,
+                    };
+                    Box::new(
+                        self.stakes
+                            .stake
+                            .idx
+                            .size
+                            .sub_prefix(user.clone())
+                            .range(ctx.deps.storage, None, bound, Order::Descending)
+                            .map(|item| item.map(|((_, validator), stake)| (validator, stake))),
+                    )
+                }
+            };
 
-        let stakes = self
-            .stakes
-            .stake
-            .prefix(&user)
-            .range(ctx.deps.storage, bound, None, Order::Ascending)
+        let stakes = stakes
+            .filter(|item| match item {
+                Ok((_, stake)) => include_zero || !stake.stake.high().is_zero(),
+                Err(_) => true,
+            })
             .map(|item| {
                 item.map(|(validator, stake)| {
                     Ok::<StakeInfo, ContractError>(StakeInfo {
@@ -860,16 +3489,46 @@ impl ExternalStakingContract<'_> {
             .take(limit)
             .collect::<Result<_, _>>()?;
 
-        let resp = StakesResponse { stakes };
+        let meta = self
+            .user_meta
+            .may_load(ctx.deps.storage, &user)?
+            .unwrap_or_default();
+        let resp = StakesResponse {
+            stakes,
+            first_stake_at: meta.first_stake_at,
+            last_activity_at: meta.last_activity_at,
+        };
 
         Ok(resp)
     }
 
-    /// Queries a pending tx.
+    /// Queries a pending tx, or its outcome if it was recently resolved.
     #[msg(query)]
     fn pending_tx(&self, ctx: QueryCtx, tx_id: u64) -> Result<TxResponse, ContractError> {
-        let resp = self.pending_txs.load(ctx.deps.storage, tx_id)?;
-        Ok(resp)
+        match self.tx_history.may_load(ctx.deps.storage, tx_id)? {
+            Some(result) => Ok(TxStatus::Resolved(result)),
+            None => {
+                let tx = self.pending_txs.txs.load(ctx.deps.storage, tx_id)?;
+                Ok(TxStatus::Pending(tx))
+            }
+        }
+    }
+
+    /// Queries the recently resolved txs.
+    /// Reports txs in descending order (newest first).
+    /// `start_after` is the last tx id included in previous page
+    #[msg(query)]
+    fn txs_history(
+        &self,
+        ctx: QueryCtx,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    ) -> Result<TxsHistoryResponse, ContractError> {
+        let limit = clamp_page_limit(limit);
+        let txs = self
+            .tx_history
+            .range_desc(ctx.deps.storage, start_after, limit)?;
+        Ok(TxsHistoryResponse { txs })
     }
 
     /// Queries for all pending txs.
@@ -887,10 +3546,11 @@ impl ExternalStakingContract<'_> {
 
         let txs = self
             .pending_txs
+            .txs
             .range(ctx.deps.storage, None, bound, Order::Descending)
             .map(|item| {
                 let (_id, tx) = item?;
-                Ok::<TxResponse, ContractError>(tx)
+                Ok::<Tx, ContractError>(tx)
             })
             .take(limit)
             .collect::<Result<_, _>>()?;
@@ -917,16 +3577,55 @@ impl ExternalStakingContract<'_> {
             .may_load(ctx.deps.storage, (&user, &validator))?
             .unwrap_or_default();
 
-        let distribution = self
-            .distribution
-            .may_load(ctx.deps.storage, &validator)?
+        let config = self.config.load(ctx.deps.storage)?;
+        let rewards = self.calculate_rewards(ctx.deps.storage, &config, &validator, &stake)?;
+
+        Ok(PendingRewards { rewards })
+    }
+
+    /// Returns the raw rewards accumulators of the user's stake on the validator, and of the
+    /// validator distributions, in all the rewards denoms. Meant for debugging the rewards math
+    #[msg(query)]
+    pub fn reward_debug(
+        &self,
+        ctx: QueryCtx,
+        user: String,
+        validator: String,
+    ) -> Result<RewardDebug, ContractError> {
+        let user = ctx.deps.api.addr_validate(&user)?;
+
+        let stake = self
+            .stakes
+            .stake
+            .may_load(ctx.deps.storage, (&user, &validator))?
             .unwrap_or_default();
 
-        let amount = Self::calculate_reward(&stake, &distribution)?;
         let config = self.config.load(ctx.deps.storage)?;
+        let denoms = config
+            .held_rewards_denoms()
+            .into_iter()
+            .map(|denom| {
+                let accounting = config.accounting_denom(&denom);
+                let distribution = self
+                    .distribution
+                    .may_load(ctx.deps.storage, (&validator, accounting))?
+                    .unwrap_or_default();
+                let rewards = stake.rewards.get(accounting).cloned().unwrap_or_default();
+                Ok(DenomRewardDebug {
+                    denom,
+                    points_per_stake: distribution.points_per_stake,
+                    points_leftover: distribution.points_leftover,
+                    reward_index: rewards.reward_index,
+                    points: rewards.points,
+                    withdrawn_funds: rewards.withdrawn_funds,
+                })
+            })
+            .collect::<StdResult<_>>()?;
 
-        Ok(PendingRewards {
-            rewards: coin(amount.u128(), config.rewards_denom),
+        Ok(RewardDebug {
+            stake: stake.stake,
+            rewards_stake: stake.rewards_stake,
+            denoms,
         })
     }
 
@@ -955,24 +3654,104 @@ impl ExternalStakingContract<'_> {
             .take(limit)
             .map(|item| {
                 let (validator, stake) = item?;
+                let rewards =
+                    self.calculate_rewards(ctx.deps.storage, &config, &validator, &stake)?;
+                Ok::<_, ContractError>(ValidatorPendingRewards {
+                    validator,
+                    rewards: PendingRewards { rewards },
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(AllPendingRewards { rewards })
+    }
+
+    /// Returns the rewards withdrawn by the user so far, per rewards denom, summed over the user
+    /// validators. Withdrawals in flight are included, and rolled back ones are not.
+    ///
+    /// Validators are summed by pages of up to `limit`, `start_after` being the cursor returned
+    /// with the previous page. The rewards withdrawn from the closed stakes are added to the first
+    /// page, so that the pages add up to the lifetime total.
+    #[msg(query)]
+    pub fn total_withdrawn(
+        &self,
+        ctx: QueryCtx,
+        user: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    ) -> Result<TotalWithdrawnResponse, ContractError> {
+        let limit: usize = clamp_page_limit(limit);
+        let user = ctx.deps.api.addr_validate(&user)?;
+
+        let bound = start_after.as_deref().and_then(Bounder::exclusive_bound);
+
+        let mut stakes = self
+            .stakes
+            .stake
+            .prefix(&user)
+            .range(ctx.deps.storage, bound, None, Order::Ascending)
+            .take(limit + 1)
+            .collect::<StdResult<Vec<_>>>()?;
+        let more = stakes.len() > limit;
+        stakes.truncate(limit);
+
+        let mut total: BTreeMap<String, Uint128> = BTreeMap::new();
+        if start_after.is_none() {
+            for item in self.closed_withdrawn.prefix(&user).range(
+                ctx.deps.storage,
+                None,
+                None,
+                Order::Ascending,
+            ) {
+                let (denom, withdrawn) = item?;
+                *total.entry(denom).or_default() += withdrawn;
+            }
+        }
+        for (_, stake) in &stakes {
+            for (denom, rewards) in &stake.rewards {
+                *total.entry(denom.clone()).or_default() += rewards.withdrawn_funds;
+            }
+        }
+        let withdrawn = total
+            .into_iter()
+            .filter(|(_, amount)| !amount.is_zero())
+            .map(|(denom, amount)| coin(amount.u128(), denom))
+            .collect();
+        let cursor = if more {
+            stakes.pop().map(|(validator, _)| validator)
+        } else {
+            None
+        };
+
+        Ok(TotalWithdrawnResponse { withdrawn, cursor })
+    }
+
+    /// Calculates rewards for the user basing on the `Stake` he want to withdraw rewards from, in
+    /// all the rewards denoms.
+    pub(crate) fn calculate_rewards(
+        &self,
+        storage: &dyn Storage,
+        config: &Config,
+        validator: &str,
+        stake: &Stake,
+    ) -> Result<Vec<Coin>, ContractError> {
+        config
+            .held_rewards_denoms()
+            .iter()
+            .map(|denom| {
+                let accounting = config.accounting_denom(denom);
                 let distribution = self
                     .distribution
-                    .may_load(ctx.deps.storage, &validator)?
+                    .may_load(storage, (validator, accounting))?
                     .unwrap_or_default();
-                let amount = Self::calculate_reward(&stake, &distribution)?;
-                Ok::<_, ContractError>(ValidatorPendingRewards::new(
-                    validator,
-                    amount.u128(),
-                    &config.rewards_denom,
-                ))
+                let amount = Self::calculate_reward(stake, &distribution, accounting)?;
+                Ok(coin(amount.u128(), denom))
             })
-            .collect::<Result<_, _>>()?;
-
-        Ok(AllPendingRewards { rewards })
+            .collect()
     }
 
     /// Calculates reward for the user basing on the `Stake` he want to withdraw rewards from, and
-    /// the corresponding validator `Distribution`.
+    /// the corresponding validator `Distribution` of the rewards accounted in `denom`.
     //
     // It is important to make sure the distribution passed matches the validator for stake. It
     // could be enforced by taking user and validator in arguments, then fetching data, but
@@ -981,24 +3760,138 @@ impl ExternalStakingContract<'_> {
     fn calculate_reward(
         stake: &Stake,
         distribution: &Distribution,
+        denom: &str,
     ) -> Result<Uint128, ContractError> {
-        // Calculating rewards with always the `low` value of the range goes against the user in some
-        // scenario (pending unstakes), but the possible errors are small and temporary.
-        let points = distribution.points_per_stake * Uint256::from(stake.stake.low());
+        let rewards = stake.rewards.get(denom).cloned().unwrap_or_default();
 
-        let points = stake.points_alignment.align(points);
+        let points = rewards.points(stake.rewards_stake, distribution.points_per_stake);
+        // Rounding down the total earned so far, not each distribution, so the remainders of
+        // successive distributions add up. At most one unit is held back from the staker
         let total = Uint128::try_from(points / DISTRIBUTION_POINTS_SCALE)?;
 
-        Ok(total - stake.withdrawn_funds)
+        Ok(total.saturating_sub(rewards.withdrawn_funds))
+    }
+
+    /// Asserts the rewards of `denom` all the stakers could withdraw add up to at most `balance`,
+    /// the amount distributed and not withdrawn yet. Panics otherwise.
+    #[cfg(any(test, feature = "strict-invariants"))]
+    pub fn debug_assert_rewards_solvent(
+        &self,
+        storage: &dyn Storage,
+        denom: &str,
+        balance: Uint128,
+    ) {
+        let mut total = Uint128::zero();
+        for item in self
+            .stakes
+            .stake
+            .range(storage, None, None, Order::Ascending)
+        {
+            let ((_, validator), stake) = item.expect("Stake not loaded");
+            let distribution = self
+                .distribution
+                .may_load(storage, (&validator, denom))
+                .expect("Distribution not loaded")
+                .unwrap_or_default();
+            total += Self::calculate_reward(&stake, &distribution, denom)
+                .expect("Rewards not calculated");
+        }
+
+        assert!(
+            total <= balance,
+            "Rewards insolvent: {total}{denom} withdrawable out of {balance}{denom}"
+        );
+    }
+
+    /// The distributions on `validator` of all the held rewards, along with the denom they are
+    /// accounted in
+    fn held_distributions(
+        &self,
+        storage: &dyn Storage,
+        config: &Config,
+        validator: &str,
+    ) -> StdResult<Vec<(String, Distribution)>> {
+        config
+            .held_rewards_denoms()
+            .iter()
+            .map(|denom| {
+                let accounting = config.accounting_denom(denom);
+                let distribution =
+                    self.load_distribution(storage, config, validator, accounting)?;
+                Ok((accounting.to_owned(), distribution))
+            })
+            .collect()
+    }
+
+    /// Checkpoints the stake rewards before its increase by `amount`, and updates the validator
+    /// distributions, for all the rewards denoms. The stake itself is not saved.
+    fn stake_increased(
+        &self,
+        storage: &mut dyn Storage,
+        config: &Config,
+        validator: &str,
+        stake: &mut Stake,
+        amount: Uint128,
+    ) -> StdResult<()> {
+        // All loaded before any is updated, as the distributions not saved yet start with the
+        // total stake of the others
+        for (denom, mut distribution) in self.held_distributions(storage, config, validator)? {
+            // Nothing earned before the first distribution
+            if !distribution.points_per_stake.is_zero() {
+                stake
+                    .rewards
+                    .entry(denom.clone())
+                    .or_default()
+                    .checkpoint(stake.rewards_stake, distribution.points_per_stake);
+            }
+            distribution.total_stake += amount;
+            self.distribution
+                .save(storage, (validator, &denom), &distribution)?;
+        }
+        stake.rewards_stake += amount;
+        update_stat(storage, &self.total_stake, |total| total + amount)?;
+        Ok(())
+    }
+
+    /// Checkpoints the stake rewards before its decrease by `amount`, and updates the validator
+    /// distributions, for all the rewards denoms. The stake itself is not saved.
+    fn stake_decreased(
+        &self,
+        storage: &mut dyn Storage,
+        config: &Config,
+        validator: &str,
+        stake: &mut Stake,
+        amount: Uint128,
+    ) -> StdResult<()> {
+        // All loaded before any is updated, as the distributions not saved yet start with the
+        // total stake of the others
+        for (denom, mut distribution) in self.held_distributions(storage, config, validator)? {
+            // Nothing earned before the first distribution
+            if !distribution.points_per_stake.is_zero() {
+                stake
+                    .rewards
+                    .entry(denom.clone())
+                    .or_default()
+                    .checkpoint(stake.rewards_stake, distribution.points_per_stake);
+            }
+            distribution.total_stake -= amount;
+            self.distribution
+                .save(storage, (validator, &denom), &distribution)?;
+        }
+        let decrease = min(amount, stake.rewards_stake);
+        stake.rewards_stake -= decrease;
+        update_stat(storage, &self.total_stake, |total| {
+            total.saturating_sub(decrease)
+        })?;
+        Ok(())
     }
 }
 
 pub mod cross_staking {
-    use crate::msg::ReceiveVirtualStake;
-
     use super::*;
-    use cosmwasm_std::{from_binary, Binary};
-    use mesh_apis::{cross_staking_api::CrossStakingApi, local_staking_api::MaxSlashResponse};
+    use cosmwasm_std::Binary;
+    use mesh_apis::cross_staking_api::{CanStakeResponse, CrossStakingApi};
+    use mesh_apis::local_staking_api::MaxSlashResponse;
 
     #[contract(module=crate::contract)]
     #[messages(mesh_apis::cross_staking_api as CrossStakingApi)]
@@ -1017,28 +3910,12 @@ pub mod cross_staking {
             let config = self.config.load(ctx.deps.storage)?;
             ensure_eq!(ctx.info.sender, config.vault.0, ContractError::Unauthorized);
 
-            // sending proper denom
-            ensure_eq!(
-                amount.denom,
-                config.denom,
-                ContractError::InvalidDenom(config.denom)
-            );
-
-            let owner = ctx.deps.api.addr_validate(&owner)?;
-
-            // parse and validate message
-            let msg: ReceiveVirtualStake = from_binary(&msg)?;
-            if !self
-                .val_set
-                .is_active_validator(ctx.deps.storage, &msg.validator)?
-            {
-                return Err(ContractError::ValidatorNotActive(msg.validator));
+            let (owner, msg, stake) =
+                self.check_virtual_stake(ctx.deps.as_ref(), &config, &owner, &amount, &msg)?;
+            if stake.is_none() {
+                self.stake_entry_added(ctx.deps.storage, &owner, &msg.validator)?;
             }
-            let mut stake = self
-                .stakes
-                .stake
-                .may_load(ctx.deps.storage, (&owner, &msg.validator))?
-                .unwrap_or_default();
+            let mut stake = stake.unwrap_or_default();
 
             // Prepare stake addition and save stake.
             // We don't check for max here, as this call can only come from the `vault` contract, which already
@@ -1056,6 +3933,7 @@ pub mod cross_staking {
                 validator: msg.validator.clone(),
             };
             self.pending_txs.save(ctx.deps.storage, tx_id, &new_tx)?;
+            self.record_activity(ctx.deps.storage, &owner, ctx.env.block.time, true)?;
 
             let mut resp = Response::new();
 
@@ -1089,6 +3967,190 @@ pub mod cross_staking {
             Ok(resp)
         }
 
+        #[msg(exec)]
+        fn vault_unstake(
+            &self,
+            ctx: ExecCtx,
+            owner: String,
+            validator: Option<String>,
+            amount: Coin,
+        ) -> Result<Response, Self::Error> {
+            let ExecCtx { info, deps, env } = ctx;
+            nonpayable(&info)?;
+
+            let config = self.config.load(deps.storage)?;
+            ensure_eq!(info.sender, config.vault.0, ContractError::Unauthorized);
+            ensure_eq!(
+                amount.denom,
+                config.denom,
+                ContractError::InvalidDenom(config.denom)
+            );
+
+            let owner = deps.api.addr_validate(&owner)?;
+            match validator {
+                Some(validator) => self.send_unstake(
+                    deps,
+                    &env,
+                    &config,
+                    &owner,
+                    validator,
+                    amount.amount,
+                    "vault_unstake",
+                ),
+                None => {
+                    let unstakes =
+                        self.largest_stakes_first(deps.storage, &owner, amount.amount)?;
+                    ensure!(!unstakes.is_empty(), ContractError::EmptyUnstakeBatch);
+                    self.send_unstake_batch(deps, &env, &config, &owner, unstakes, "vault_unstake")
+                }
+            }
+        }
+
+        #[msg(exec)]
+        fn expire_stake(&self, ctx: ExecCtx, tx_id: u64) -> Result<Response, Self::Error> {
+            nonpayable(&ctx.info)?;
+
+            let config = self.config.load(ctx.deps.storage)?;
+            ensure_eq!(ctx.info.sender, config.vault.0, ContractError::Unauthorized);
+
+            self.revert_stake(ctx.deps.storage, &ctx.env, tx_id)?;
+
+            Ok(Response::new()
+                .add_attribute("action", "expire_stake")
+                .add_attribute("tx_id", tx_id.to_string()))
+        }
+
+        #[msg(exec)]
+        fn restake(
+            &self,
+            ctx: ExecCtx,
+            owner: String,
+            from_validators: Vec<String>,
+            to_validator: String,
+        ) -> Result<Response, Self::Error> {
+            let ExecCtx { info, deps, env } = ctx;
+            nonpayable(&info)?;
+
+            let config = self.config.load(deps.storage)?;
+            ensure_eq!(info.sender, config.vault.0, ContractError::Unauthorized);
+
+            let owner = deps.api.addr_validate(&owner)?;
+
+            // no new stakes until the valset is fully synced
+            let pending = self.valset_backlog.len(deps.storage)?;
+            ensure!(pending == 0, ContractError::ValsetSyncInProgress(pending));
+
+            match self.val_set.validator_state(deps.storage, &to_validator)? {
+                Some(state) if state.is_active() => {}
+                Some(_) => return Err(ContractError::ValidatorNotActive(to_validator)),
+                None => return Err(ContractError::UnknownValidator(to_validator)),
+            }
+
+            // The whole stake on each validator is moved, once
+            let from_validators: BTreeSet<_> = from_validators
+                .into_iter()
+                .filter(|validator| *validator != to_validator)
+                .collect();
+            let mut from = vec![];
+            for validator in from_validators {
+                let mut stake = match self
+                    .stakes
+                    .stake
+                    .may_load(deps.storage, (&owner, &validator))?
+                {
+                    Some(stake) => stake,
+                    None => continue,
+                };
+                let amount = stake.stake.low();
+                if amount.is_zero() {
+                    continue;
+                }
+                // The stake on a jailed or tombstoned validator can't escape its slashing
+                ensure!(
+                    self.val_set.is_active_validator(deps.storage, &validator)?,
+                    ContractError::ValidatorNotActive(validator)
+                );
+                stake.stake.prepare_sub(amount, Uint128::zero())?;
+                self.stakes
+                    .stake
+                    .save(deps.storage, (&owner, &validator), &stake)?;
+                from.push((validator, amount));
+            }
+            ensure!(
+                !from.is_empty(),
+                ContractError::NothingToRestake(to_validator)
+            );
+            let total: Uint128 = from.iter().map(|(_, amount)| amount).sum();
+
+            let stake = self
+                .stakes
+                .stake
+                .may_load(deps.storage, (&owner, &to_validator))?;
+            // Moving to a new validator is bounded by the max validators per user
+            if stake.is_none() {
+                let count = self.stake_entry_added(deps.storage, &owner, &to_validator)?;
+                if let Some(max) = config.max_validators_per_user {
+                    ensure!(count <= max, ContractError::TooManyValidators(max));
+                }
+            }
+            let mut stake = stake.unwrap_or_default();
+            stake.stake.prepare_add(total, None)?;
+            self.stakes
+                .stake
+                .save(deps.storage, (&owner, &to_validator), &stake)?;
+
+            // Create new tx
+            let tx_id = self.next_tx_id(deps.storage)?;
+
+            // Save tx
+            let new_tx = Tx::InFlightRemoteRestaking {
+                id: tx_id,
+                user: owner.clone(),
+                from: from.clone(),
+                to: to_validator.clone(),
+                created_at: env.block.time,
+            };
+            self.pending_txs.save(deps.storage, tx_id, &new_tx)?;
+            self.record_activity(deps.storage, &owner, env.block.time, false)?;
+
+            #[allow(unused_mut)]
+            let mut resp = Response::new()
+                .add_attribute("action", "restake")
+                .add_attribute("owner", &owner)
+                .add_attribute("validator", &to_validator)
+                .add_attribute("amount", total.to_string())
+                .add_attribute("tx_id", tx_id.to_string());
+
+            let channel = IBC_CHANNEL.load(deps.storage)?;
+            let packet = ProviderPacket::Restake {
+                from: from
+                    .into_iter()
+                    .map(|(validator, amount)| UnstakeInfo {
+                        validator,
+                        unstake: coin(amount.u128(), &config.denom),
+                    })
+                    .collect(),
+                to_validator,
+                tx_id,
+            };
+            let msg = IbcMsg::SendPacket {
+                channel_id: channel.endpoint.channel_id,
+                data: to_binary(&packet)?,
+                timeout: packet_timeout(&env),
+            };
+            // add ibc packet if we are ibc enabled (skip in tests)
+            #[cfg(not(any(feature = "mt", test)))]
+            {
+                resp = resp.add_message(msg);
+            }
+            #[cfg(any(feature = "mt", test))]
+            {
+                let _ = msg;
+            }
+
+            Ok(resp)
+        }
+
         #[msg(query)]
         fn max_slash(&self, ctx: QueryCtx) -> Result<MaxSlashResponse, ContractError> {
             let Config { max_slashing, .. } = self.config.load(ctx.deps.storage)?;
@@ -1096,5 +4158,790 @@ pub mod cross_staking {
                 max_slash: max_slashing,
             })
         }
+
+        #[msg(query)]
+        fn can_stake(
+            &self,
+            ctx: QueryCtx,
+            owner: String,
+            amount: Coin,
+            msg: Binary,
+        ) -> Result<CanStakeResponse, ContractError> {
+            let config = self.config.load(ctx.deps.storage)?;
+            let resp = match self.check_virtual_stake(ctx.deps, &config, &owner, &amount, &msg) {
+                Ok(_) => CanStakeResponse::Accepted {},
+                Err(err) => CanStakeResponse::Rejected {
+                    reason: err.to_string(),
+                },
+            };
+            Ok(resp)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cosmwasm_std::testing::{
+        mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage,
+    };
+    use cosmwasm_std::{
+        ContractInfoResponse, ContractResult, CosmosMsg, OwnedDeps, SystemResult, WasmQuery,
+    };
+    use mesh_apis::cross_staking_api::CrossStakingApi;
+    use mesh_apis::vault_api;
+
+    use ed25519_zebra::{SigningKey, VerificationKey};
+
+    use crate::evidence::{CanonicalVote, SignedVote};
+    use crate::msg::{AuthorizedEndpoint, ReceiveVirtualStake};
+
+    /// Xorshift pseudo random numbers, for reproducible runs
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, max: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % max as u64) as usize
+        }
+    }
+
+    fn last_tx_id(deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>) -> u64 {
+        ExternalStakingContract::new()
+            .tx_count
+            .load(&deps.storage)
+            .unwrap()
+    }
+
+    #[test]
+    fn rewards_stay_solvent() {
+        let mut deps = mock_dependencies();
+        let contract = ExternalStakingContract::new();
+        let users = ["user1", "user2", "user3"];
+        let validators = ["validator1", "validator2"];
+
+        contract
+            .instantiate(
+                (deps.as_mut(), mock_env(), mock_info("owner", &[])).into(),
+                "osmo".to_owned(),
+                vec![RewardDenom::from_denom("star")],
+                "vault".to_owned(),
+                100,
+                AuthorizedEndpoint::new("connection-2", "wasm-osmo1foobarbaz"),
+                Decimal::percent(10),
+            )
+            .unwrap();
+
+        // Stake txs are identified by the vault tx id
+        let mut vault_tx_id = 0;
+        for validator in validators {
+            contract
+                .val_set
+                .add_validator(&mut deps.storage, validator, ValUpdate::new("pubkey", 1, 1))
+                .unwrap();
+            for (user, amount) in users.into_iter().zip([1000, 700, 333]) {
+                vault_tx_id += 1;
+                contract
+                    .receive_virtual_stake(
+                        (deps.as_mut(), mock_env(), mock_info("vault", &[])).into(),
+                        user.to_owned(),
+                        coin(amount, "osmo"),
+                        vault_tx_id,
+                        to_binary(&ReceiveVirtualStake {
+                            validator: validator.to_owned(),
+                        })
+                        .unwrap(),
+                    )
+                    .unwrap();
+                contract
+                    .commit_stake(deps.as_mut(), mock_env(), vault_tx_id)
+                    .unwrap();
+            }
+        }
+
+        let mut rng = Rng(0x2545f4914f6cdd1d);
+        let mut distributed = Uint128::zero();
+        // Points alignments of the stakes, as (added, removed) points, the way the rewards were
+        // computed before being checkpointed
+        let mut alignments: BTreeMap<(&str, &str), (Uint256, Uint256)> = BTreeMap::new();
+        let load = |storage: &dyn Storage| {
+            let mut stakes = vec![];
+            for user in users {
+                for validator in validators {
+                    let stake = contract
+                        .stakes
+                        .stake
+                        .load(storage, (&Addr::unchecked(user), validator))
+                        .unwrap();
+                    let distribution = contract
+                        .distribution
+                        .load(storage, (validator, "star"))
+                        .unwrap();
+                    stakes.push(((user, validator), stake, distribution));
+                }
+            }
+            stakes
+        };
+        let mut withdrawn = Uint128::zero();
+        // Withdrawals and unstakes in flight, by tx id
+        let mut withdrawals: Vec<(u64, Uint128)> = vec![];
+        let mut unstakes: Vec<u64> = vec![];
+
+        for _ in 0..100 {
+            let user = users[rng.below(users.len())];
+            let validator = validators[rng.below(validators.len())];
+            let before = load(&deps.storage);
+
+            match rng.below(8) {
+                0 | 1 => {
+                    let amount = Uint128::new(rng.below(100) as u128 + 1);
+                    contract
+                        .distribute_rewards(deps.as_mut(), validator, coin(amount.u128(), "star"))
+                        .unwrap();
+                    distributed += amount;
+                }
+                // Withdrawals are attempted again before being committed
+                2 | 3 => {
+                    let res = contract.withdraw_rewards(
+                        (deps.as_mut(), mock_env(), mock_info(user, &[])).into(),
+                        validator.to_owned(),
+                        "remote".to_owned(),
+                    );
+                    match res {
+                        Ok(_) => {
+                            let tx_id = last_tx_id(&deps);
+                            let amount = match contract.pending_txs.txs.load(&deps.storage, tx_id) {
+                                Ok(Tx::InFlightTransferFunds { amount, .. }) => amount,
+                                tx => panic!("Unexpected tx: {tx:?}"),
+                            };
+                            withdrawals.push((tx_id, amount));
+                            withdrawn += amount;
+                        }
+                        Err(err) => assert_eq!(err, ContractError::NoRewards),
+                    }
+                }
+                4 if !withdrawals.is_empty() => {
+                    let (tx_id, amount) = withdrawals.remove(rng.below(withdrawals.len()));
+                    if rng.below(3) == 0 {
+                        contract
+                            .rollback_withdraw_rewards(deps.as_mut(), mock_env(), tx_id)
+                            .unwrap();
+                        withdrawn -= amount;
+                    } else {
+                        contract
+                            .commit_withdraw_rewards(deps.as_mut(), mock_env(), tx_id)
+                            .unwrap();
+                    }
+                }
+                5 => {
+                    let amount = rng.below(20) as u128 + 1;
+                    contract
+                        .unstake(
+                            (deps.as_mut(), mock_env(), mock_info(user, &[])).into(),
+                            validator.to_owned(),
+                            coin(amount, "osmo"),
+                        )
+                        .unwrap();
+                    unstakes.push(last_tx_id(&deps));
+                }
+                6 if !unstakes.is_empty() => {
+                    let tx_id = unstakes.remove(rng.below(unstakes.len()));
+                    contract
+                        .commit_unstake(deps.as_mut(), mock_env(), tx_id)
+                        .unwrap();
+                }
+                // Slashing adjusts the stakes, and their checkpoints
+                7 => {
+                    contract
+                        .handle_slashing(&mock_env(), &mut deps.storage, validator)
+                        .unwrap();
+                }
+                _ => {}
+            }
+
+            contract.debug_assert_rewards_solvent(&deps.storage, "star", distributed - withdrawn);
+
+            // Rewards match the alignment math, except on pending unstakes, which it took out of
+            // the rewards until committed
+            for (before, (key, stake, distribution)) in before.iter().zip(load(&deps.storage)) {
+                let pps = distribution.points_per_stake;
+                let (added, removed) = alignments.entry(key).or_default();
+                if stake.rewards_stake > before.1.rewards_stake {
+                    *removed += Uint256::from(stake.rewards_stake - before.1.rewards_stake) * pps;
+                } else {
+                    *added += Uint256::from(before.1.rewards_stake - stake.rewards_stake) * pps;
+                }
+
+                if stake.stake.low() != stake.rewards_stake {
+                    continue;
+                }
+                let points = pps * Uint256::from(stake.stake.low()) + *added - *removed;
+                let total = Uint128::try_from(points / DISTRIBUTION_POINTS_SCALE).unwrap();
+                let withdrawn = stake
+                    .rewards
+                    .get("star")
+                    .map(|rewards| rewards.withdrawn_funds)
+                    .unwrap_or_default();
+                assert_eq!(
+                    ExternalStakingContract::calculate_reward(&stake, &distribution, "star")
+                        .unwrap(),
+                    total.saturating_sub(withdrawn)
+                );
+            }
+        }
+    }
+
+    fn signed_vote(key: &SigningKey, height: i64, block_id: &[u8]) -> SignedVote {
+        sign_vote(
+            key,
+            CanonicalVote {
+                vote_type: 2,
+                height,
+                round: 0,
+                block_id: block_id.to_vec(),
+                chain_id: "osmosis-1".to_owned(),
+            },
+        )
+    }
+
+    fn sign_vote(key: &SigningKey, vote: CanonicalVote) -> SignedVote {
+        let sign_bytes = vote.encode();
+        let signature = <[u8; 64]>::from(key.sign(&sign_bytes));
+        SignedVote {
+            sign_bytes: sign_bytes.into(),
+            signature: signature.to_vec().into(),
+        }
+    }
+
+    fn evidence(key: &SigningKey, height: i64, block_a: &[u8], block_b: &[u8]) -> Binary {
+        to_binary(&DoubleSignEvidence {
+            vote_a: signed_vote(key, height, block_a),
+            vote_b: signed_vote(key, height, block_b),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn slash_redistribution_paginated() {
+        let mut deps = mock_dependencies();
+        let contract = ExternalStakingContract::new();
+        let validators = ["validator1", "validator2", "validator3", "validator4"];
+
+        contract
+            .instantiate(
+                (deps.as_mut(), mock_env(), mock_info("owner", &[])).into(),
+                "osmo".to_owned(),
+                vec![
+                    RewardDenom::from_denom("star"),
+                    RewardDenom::from_denom("osmo"),
+                ],
+                "vault".to_owned(),
+                100,
+                AuthorizedEndpoint::new("connection-2", "wasm-osmo1foobarbaz"),
+                Decimal::percent(10),
+            )
+            .unwrap();
+        contract
+            .config
+            .update(&mut deps.storage, |mut config| -> StdResult<_> {
+                config.slash_redistribution = Some(Decimal::percent(50));
+                Ok(config)
+            })
+            .unwrap();
+        for (tx_id, validator) in (1..).zip(validators) {
+            contract
+                .val_set
+                .add_validator(&mut deps.storage, validator, ValUpdate::new("pubkey", 1, 1))
+                .unwrap();
+            contract
+                .receive_virtual_stake(
+                    (deps.as_mut(), mock_env(), mock_info("vault", &[])).into(),
+                    "user".to_owned(),
+                    coin(100, "osmo"),
+                    tx_id,
+                    to_binary(&ReceiveVirtualStake {
+                        validator: validator.to_owned(),
+                    })
+                    .unwrap(),
+                )
+                .unwrap();
+            contract
+                .commit_stake(deps.as_mut(), mock_env(), tx_id)
+                .unwrap();
+        }
+
+        // Half of the slash is asked back from the vault
+        let msg = contract
+            .handle_slashing(&mock_env(), &mut deps.storage, validators[0])
+            .unwrap();
+        match msg {
+            WasmMsg::Execute { msg, .. } => match from_binary(&msg).unwrap() {
+                vault_api::ExecMsg::CrossSlashWithPayouts { payouts, .. } => assert_eq!(
+                    payouts,
+                    [SlashPayout {
+                        recipient: mock_env().contract.address.to_string(),
+                        amount: Uint128::new(5),
+                    }]
+                ),
+                msg => panic!("Unexpected vault message: {msg:?}"),
+            },
+            msg => panic!("Unexpected message: {msg:?}"),
+        }
+
+        // Only what was paid back is redistributed, over a validator per call
+        deps.querier
+            .update_balance(mock_env().contract.address, coins(3, "osmo"));
+        let credit = |deps: &mut OwnedDeps<_, _, _>| {
+            contract
+                .credit_redistributions(deps.as_mut(), &mock_env(), 1)
+                .unwrap()
+                .0
+        };
+        let distributed = |deps: &OwnedDeps<_, _, _>| {
+            contract
+                .slashes_redistributed
+                .may_load(&deps.storage)
+                .unwrap()
+                .unwrap_or_default()
+                .u128()
+        };
+        // The slashed validator is skipped
+        assert_eq!(credit(&mut deps), 1);
+        assert_eq!(distributed(&deps), 0);
+        for credited in 1..=3 {
+            assert_eq!(credit(&mut deps), 1);
+            assert_eq!(distributed(&deps), credited);
+        }
+        assert_eq!(
+            contract.slash_redistributions.len(&deps.storage).unwrap(),
+            0
+        );
+        assert_eq!(credit(&mut deps), 0);
+        assert_eq!(
+            contract.redistribution_reserve.load(&deps.storage).unwrap(),
+            Uint128::new(3)
+        );
+    }
+
+    #[test]
+    fn submit_evidence() {
+        let mut deps = mock_dependencies();
+        let contract = ExternalStakingContract::new();
+        let key = SigningKey::from([1; 32]);
+        let other_key = SigningKey::from([2; 32]);
+        let pub_key = <[u8; 32]>::from(VerificationKey::from(&key));
+
+        contract
+            .instantiate(
+                (deps.as_mut(), mock_env(), mock_info("owner", &[])).into(),
+                "osmo".to_owned(),
+                vec![RewardDenom::from_denom("star")],
+                "vault".to_owned(),
+                100,
+                AuthorizedEndpoint::new("connection-2", "wasm-osmo1foobarbaz"),
+                Decimal::percent(10),
+            )
+            .unwrap();
+        contract
+            .val_set
+            .add_validator(
+                &mut deps.storage,
+                "validator",
+                ValUpdate::new(Binary::from(pub_key).to_base64(), 10, 1),
+            )
+            .unwrap();
+        contract
+            .receive_virtual_stake(
+                (deps.as_mut(), mock_env(), mock_info("vault", &[])).into(),
+                "user".to_owned(),
+                coin(1000, "osmo"),
+                1,
+                to_binary(&ReceiveVirtualStake {
+                    validator: "validator".to_owned(),
+                })
+                .unwrap(),
+            )
+            .unwrap();
+        contract.commit_stake(deps.as_mut(), mock_env(), 1).unwrap();
+        let submit = |deps: &mut OwnedDeps<_, _, _>, validator: &str, height, signatures| {
+            contract.submit_evidence(
+                (deps.as_mut(), mock_env(), mock_info("watcher", &[])).into(),
+                validator.to_owned(),
+                height,
+                signatures,
+            )
+        };
+
+        // Evidence is checked against the consumer chain id
+        let err = submit(&mut deps, "validator", 20, evidence(&key, 20, b"a", b"b")).unwrap_err();
+        assert_eq!(err, ContractError::ConsumerChainIdNotSet);
+        contract
+            .config
+            .update(&mut deps.storage, |mut config| -> StdResult<_> {
+                config.evidence_bounty = Some(Decimal::percent(50));
+                config.consumer_chain_id = Some("osmosis-1".to_owned());
+                Ok(config)
+            })
+            .unwrap();
+        let vote = |vote_type, chain_id: &str, block_id: &[u8]| {
+            sign_vote(
+                &key,
+                CanonicalVote {
+                    vote_type,
+                    height: 20,
+                    round: 0,
+                    block_id: block_id.to_vec(),
+                    chain_id: chain_id.to_owned(),
+                },
+            )
+        };
+        let conflicting = |vote_type, chain_id| {
+            to_binary(&DoubleSignEvidence {
+                vote_a: vote(vote_type, chain_id, b"a"),
+                vote_b: vote(vote_type, chain_id, b"b"),
+            })
+            .unwrap()
+        };
+        let err = submit(&mut deps, "validator", 20, conflicting(2, "juno-1")).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidEvidence(_)));
+        // Only prevotes and precommits count, not proposals
+        let err = submit(&mut deps, "validator", 20, conflicting(32, "osmosis-1")).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidEvidence(_)));
+
+        // Invalid evidence is rejected
+        let err = submit(
+            &mut deps,
+            "validator",
+            20,
+            evidence(&other_key, 20, b"a", b"b"),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidEvidence(_)));
+        let err = submit(&mut deps, "validator", 20, evidence(&key, 20, b"a", b"a")).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidEvidence(_)));
+        let err = submit(&mut deps, "validator", 21, evidence(&key, 20, b"a", b"b")).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidEvidence(_)));
+
+        // The validator must be active at the evidence height
+        let err = submit(&mut deps, "validator", 5, evidence(&key, 5, b"a", b"b")).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::ValidatorNotActiveAt("validator".to_owned(), 5)
+        );
+        let err = submit(&mut deps, "unknown", 20, evidence(&key, 20, b"a", b"b")).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::ValidatorNotActiveAt("unknown".to_owned(), 20)
+        );
+
+        // Jailed validators are accountable for what they signed before being jailed
+        contract
+            .val_set
+            .jail_validator(&mut deps.storage, "validator", 30)
+            .unwrap();
+        let err = submit(&mut deps, "validator", 30, evidence(&key, 30, b"a", b"b")).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::ValidatorNotActiveAt("validator".to_owned(), 30)
+        );
+
+        // Valid evidence tombstones and slashes the validator, with a bounty for the submitter
+        let res = submit(&mut deps, "validator", 20, evidence(&key, 20, b"a", b"b")).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        match &res.messages[0].msg {
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr, msg, ..
+            }) => {
+                assert_eq!(contract_addr, "vault");
+                match from_binary(msg).unwrap() {
+                    vault_api::ExecMsg::CrossSlashWithBounty {
+                        recipient, bounty, ..
+                    } => {
+                        assert_eq!(recipient, "watcher");
+                        assert_eq!(bounty.u128(), 50);
+                    }
+                    msg => panic!("Unexpected vault message: {msg:?}"),
+                }
+            }
+            msg => panic!("Unexpected message: {msg:?}"),
+        }
+
+        assert_eq!(
+            contract
+                .val_set
+                .validator_state(&deps.storage, "validator")
+                .unwrap(),
+            Some(ValidatorState::Tombstoned { height: 20 })
+        );
+        let stake = contract
+            .stakes
+            .stake
+            .load(&deps.storage, (&Addr::unchecked("user"), "validator"))
+            .unwrap();
+        assert_eq!(stake.stake.high().u128(), 900);
+
+        // The same evidence can't be submitted twice
+        let err = submit(&mut deps, "validator", 20, evidence(&key, 20, b"a", b"b")).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::ValidatorNotActiveAt("validator".to_owned(), 20)
+        );
+    }
+
+    #[test]
+    fn compact_unbonds_merges_same_release() {
+        let mut deps = mock_dependencies();
+        let contract = ExternalStakingContract::new();
+        let user = Addr::unchecked("user");
+        let release_at = mock_env().block.time.plus_seconds(100);
+
+        // Unbonds committed in the same block before they were merged on the fly
+        let unbond = |amount, requested_at| PendingUnbond {
+            amount: Uint128::new(amount),
+            requested_at: Timestamp::from_seconds(requested_at),
+            release_at,
+        };
+        let stake = Stake {
+            pending_unbonds: vec![
+                unbond(20, 2),
+                unbond(30, 1),
+                PendingUnbond {
+                    release_at: release_at.plus_seconds(10),
+                    ..unbond(10, 3)
+                },
+            ],
+            ..Stake::default()
+        };
+        contract
+            .stakes
+            .stake
+            .save(&mut deps.storage, (&user, "validator"), &stake)
+            .unwrap();
+
+        let resp = contract
+            .compact_unbonds(
+                (deps.as_mut(), mock_env(), mock_info("anyone", &[])).into(),
+                user.to_string(),
+                "validator".to_owned(),
+            )
+            .unwrap();
+        let merged = resp
+            .attributes
+            .iter()
+            .find(|attr| attr.key == "merged")
+            .unwrap();
+        assert_eq!(merged.value, "1");
+
+        let stake = contract
+            .stakes
+            .stake
+            .load(&deps.storage, (&user, "validator"))
+            .unwrap();
+        assert_eq!(
+            stake.pending_unbonds,
+            [
+                unbond(50, 1),
+                PendingUnbond {
+                    release_at: release_at.plus_seconds(10),
+                    ..unbond(10, 3)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn inactive_validators_are_pruned() {
+        let mut deps = mock_dependencies();
+        let contract = ExternalStakingContract::new();
+
+        contract
+            .instantiate(
+                (deps.as_mut(), mock_env(), mock_info("owner", &[])).into(),
+                "osmo".to_owned(),
+                vec![RewardDenom::from_denom("star")],
+                "vault".to_owned(),
+                100,
+                AuthorizedEndpoint::new("connection-2", "wasm-osmo1foobarbaz"),
+                Decimal::percent(10),
+            )
+            .unwrap();
+        contract
+            .config
+            .update(&mut deps.storage, |mut config| -> StdResult<_> {
+                config.max_tracked_validators = Some(3);
+                Ok(config)
+            })
+            .unwrap();
+        contract
+            .add_validators(
+                &mut deps.storage,
+                ["staked", "unstaked", "active"]
+                    .map(AddValidator::mock)
+                    .to_vec(),
+                DEFAULT_VALSET_SYNC_LIMIT,
+            )
+            .unwrap();
+
+        contract
+            .receive_virtual_stake(
+                (deps.as_mut(), mock_env(), mock_info("vault", &[])).into(),
+                "user".to_owned(),
+                coin(1000, "osmo"),
+                1,
+                to_binary(&ReceiveVirtualStake {
+                    validator: "staked".to_owned(),
+                })
+                .unwrap(),
+            )
+            .unwrap();
+        contract.commit_stake(deps.as_mut(), mock_env(), 1).unwrap();
+
+        // The staked validator is the one inactive for the longest
+        contract
+            .val_set
+            .remove_validator(&mut deps.storage, "staked", 10)
+            .unwrap();
+        contract
+            .val_set
+            .remove_validator(&mut deps.storage, "unstaked", 20)
+            .unwrap();
+
+        contract
+            .add_validators(
+                &mut deps.storage,
+                vec![AddValidator::mock("new")],
+                DEFAULT_VALSET_SYNC_LIMIT,
+            )
+            .unwrap();
+
+        assert_eq!(
+            contract
+                .val_set
+                .validator_state(&deps.storage, "unstaked")
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            contract
+                .val_set
+                .validator_state(&deps.storage, "staked")
+                .unwrap(),
+            Some(ValidatorState::Tombstoned { height: 10 })
+        );
+        assert_eq!(
+            contract
+                .val_set
+                .list_active_validators(&deps.storage, None, 10)
+                .unwrap(),
+            ["active", "new"]
+        );
+
+        // The pruned validator can't be added back, by a replayed packet or a validator sync
+        contract
+            .add_validators(
+                &mut deps.storage,
+                vec![AddValidator::mock("unstaked")],
+                DEFAULT_VALSET_SYNC_LIMIT,
+            )
+            .unwrap();
+        let added = contract
+            .merge_validator_sync(&mut deps.storage, 100, vec![AddValidator::mock("unstaked")])
+            .unwrap();
+        assert_eq!(added, 0);
+        assert_eq!(
+            contract
+                .val_set
+                .validator_state(&deps.storage, "unstaked")
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            contract
+                .val_set
+                .list_active_validators(&deps.storage, None, 10)
+                .unwrap(),
+            ["active", "new"]
+        );
+    }
+
+    #[test]
+    fn unclaimable_rewards_recovered() {
+        let mut deps = mock_dependencies();
+        let contract = ExternalStakingContract::new();
+
+        contract
+            .instantiate(
+                (deps.as_mut(), mock_env(), mock_info("owner", &[])).into(),
+                "osmo".to_owned(),
+                vec![RewardDenom::from_denom("star")],
+                "vault".to_owned(),
+                100,
+                AuthorizedEndpoint::new("connection-2", "wasm-osmo1foobarbaz"),
+                Decimal::percent(10),
+            )
+            .unwrap();
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::ContractInfo { .. } => {
+                let mut info = ContractInfoResponse::default();
+                info.admin = Some("owner".to_owned());
+                SystemResult::Ok(ContractResult::Ok(to_binary(&info).unwrap()))
+            }
+            _ => panic!("Unexpected query: {query:?}"),
+        });
+
+        // Rewards distributed to a validator without stake before unknown validators were
+        // rejected: 2.5 tokens, with no one to claim them
+        let leftover = Uint256::from(2_500_000_000u128);
+        contract
+            .distribution
+            .save(
+                &mut deps.storage,
+                ("gone", "star"),
+                &Distribution {
+                    points_leftover: leftover,
+                    ..Distribution::default()
+                },
+            )
+            .unwrap();
+
+        let res = contract
+            .recover_unclaimable_rewards(
+                (deps.as_mut(), mock_env(), mock_info("owner", &[])).into(),
+                "gone".to_owned(),
+                "treasury".to_owned(),
+            )
+            .unwrap();
+        assert!(res
+            .attributes
+            .iter()
+            .any(|attr| attr.key == "rewards" && attr.value == "2star"));
+
+        // Whole tokens are credited to the recipient, the dust stays
+        let distribution = contract
+            .distribution
+            .load(&deps.storage, ("gone", "star"))
+            .unwrap();
+        assert_eq!(distribution.points_leftover, Uint256::from(500_000_000u128));
+        let stake = contract
+            .stakes
+            .stake
+            .load(&deps.storage, (&Addr::unchecked("treasury"), "gone"))
+            .unwrap();
+        assert_eq!(
+            stake.rewards["star"].points(stake.rewards_stake, distribution.points_per_stake),
+            Uint256::from(2u128) * DISTRIBUTION_POINTS_SCALE
+        );
+
+        let err = contract
+            .recover_unclaimable_rewards(
+                (deps.as_mut(), mock_env(), mock_info("owner", &[])).into(),
+                "gone".to_owned(),
+                "treasury".to_owned(),
+            )
+            .unwrap_err();
+        assert_eq!(err, ContractError::NoRewards);
     }
 }
@@ -1,13 +1,23 @@
 use cosmwasm_schema::cw_serde;
 use cosmwasm_std::{Order, StdError, StdResult, Storage};
-use cw_storage_plus::{Bound, Map};
+use cw_storage_plus::{Bound, Item, Map};
 
 // Question: Do we need to add more info here if we want to keep historical info for slashing.
 // Would we ever need the pubkeys for a Tombstoned validator? Or do we consider it already slashed and therefore unslashable?
 #[cw_serde]
 pub enum ValidatorState {
     Active(ActiveState),
-    Tombstoned {},
+    /// Temporarily out of the active set since the `since` height. Its updates are kept, as it
+    /// becomes active again once unjailed
+    Jailed {
+        since: u64,
+        updates: ActiveState,
+    },
+    /// Tombstoned at `height`. Zero for validators tombstoned before it was tracked
+    Tombstoned {
+        #[serde(default)]
+        height: u64,
+    },
 }
 
 impl ValidatorState {
@@ -25,7 +35,7 @@ impl ActiveState {
     /// Add one more element to this list, maintaining the constraints
     pub fn insert_unique(&mut self, update: ValUpdate) {
         self.0.push(update);
-        self.0.sort_by(|a, b| b.start_height.cmp(&a.start_height));
+        self.0.sort_by_key(|v| std::cmp::Reverse(v.start_height));
         self.0.dedup();
     }
 
@@ -54,12 +64,31 @@ impl ValUpdate {
 /// This holds all CRDT related state and logic (related to validators)
 pub struct CrdtState<'a> {
     validators: Map<'a, &'a str, ValidatorState>,
+    /// Number of tracked validators
+    tracked: Item<'a, u32>,
+    /// Tombstoned validators, by tombstoning height. Only those can be pruned
+    tombstoned: Map<'a, (u64, &'a str), ()>,
+    /// Last tombstoned validator checked for pruning
+    prune_cursor: Item<'a, (u64, String)>,
+    /// Tombstoned validators not tracked anymore. Kept so that they are never added back, at the
+    /// cost of a key per pruned validator
+    pruned: Map<'a, &'a str, ()>,
+}
+
+impl Default for CrdtState<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<'a> CrdtState<'a> {
     pub const fn new() -> Self {
         CrdtState {
             validators: Map::new("crdt.validators"),
+            tracked: Item::new("crdt.tracked"),
+            tombstoned: Map::new("crdt.tombstoned"),
+            prune_cursor: Item::new("crdt.prune_cursor"),
+            pruned: Map::new("crdt.pruned"),
         }
     }
 
@@ -72,17 +101,27 @@ impl<'a> CrdtState<'a> {
         valoper: &str,
         update: ValUpdate,
     ) -> Result<(), StdError> {
-        let mut state = self
-            .validators
-            .may_load(storage, valoper)?
-            .unwrap_or_else(|| ValidatorState::Active(ActiveState(vec![])));
+        // Tombstoning is final, even once pruned
+        if self.is_pruned(storage, valoper)? {
+            return Ok(());
+        }
+        let mut state = match self.validators.may_load(storage, valoper)? {
+            Some(state) => state,
+            None => {
+                self.validator_tracked(storage)?;
+                ValidatorState::Active(ActiveState(vec![]))
+            }
+        };
 
         match &mut state {
-            ValidatorState::Active(active) => {
+            ValidatorState::Active(active)
+            | ValidatorState::Jailed {
+                updates: active, ..
+            } => {
                 // add to the set, ensuring there are no duplicates
                 active.insert_unique(update);
             }
-            ValidatorState::Tombstoned {} => {
+            ValidatorState::Tombstoned { .. } => {
                 // we just silently ignore it here
             }
         }
@@ -90,17 +129,102 @@ impl<'a> CrdtState<'a> {
         self.validators.save(storage, valoper, &state)
     }
 
-    /// Remove a validator.
+    /// Remove a validator, tombstoned at `height`.
     /// In non-test code, this is called from `ibc_packet_receive`
     pub fn remove_validator(
         &self,
         storage: &mut dyn Storage,
         valoper: &str,
+        height: u64,
+    ) -> Result<(), StdError> {
+        if self.is_pruned(storage, valoper)? {
+            return Ok(());
+        }
+        match self.validators.may_load(storage, valoper)? {
+            Some(ValidatorState::Tombstoned { height: previous }) => {
+                self.tombstoned.remove(storage, (previous, valoper))
+            }
+            Some(_) => {}
+            None => self.validator_tracked(storage)?,
+        }
+        self.tombstoned.save(storage, (height, valoper), &())?;
+        let state = ValidatorState::Tombstoned { height };
+        self.validators.save(storage, valoper, &state)
+    }
+
+    /// Counts a newly tracked validator
+    fn validator_tracked(&self, storage: &mut dyn Storage) -> StdResult<()> {
+        let tracked = self.tracked.may_load(storage)?.unwrap_or_default();
+        self.tracked.save(storage, &(tracked + 1))
+    }
+
+    /// Jail an active validator, at `height`. Other validators are left untouched.
+    /// In non-test code, this is called from `ibc_packet_receive`
+    pub fn jail_validator(
+        &self,
+        storage: &mut dyn Storage,
+        valoper: &str,
+        height: u64,
+    ) -> Result<(), StdError> {
+        if let Some(ValidatorState::Active(updates)) = self.validators.may_load(storage, valoper)? {
+            let state = ValidatorState::Jailed {
+                since: height,
+                updates,
+            };
+            self.validators.save(storage, valoper, &state)?;
+        }
+        Ok(())
+    }
+
+    /// Jail a validator whose addition is still queued, at `height`. It is tracked as jailed right
+    /// away, and its updates are added to the jailed state once processed. Does nothing if the
+    /// validator is already tracked.
+    pub fn jail_queued_validator(
+        &self,
+        storage: &mut dyn Storage,
+        valoper: &str,
+        height: u64,
     ) -> Result<(), StdError> {
-        let state = ValidatorState::Tombstoned {};
+        if self.validators.has(storage, valoper) || self.is_pruned(storage, valoper)? {
+            return Ok(());
+        }
+        self.validator_tracked(storage)?;
+        let state = ValidatorState::Jailed {
+            since: height,
+            updates: ActiveState(vec![]),
+        };
         self.validators.save(storage, valoper, &state)
     }
 
+    /// Unjail a jailed validator, making it active again. Other validators are left untouched.
+    /// In non-test code, this is called from `ibc_packet_receive`
+    pub fn unjail_validator(
+        &self,
+        storage: &mut dyn Storage,
+        valoper: &str,
+    ) -> Result<(), StdError> {
+        if let Some(ValidatorState::Jailed { updates, .. }) =
+            self.validators.may_load(storage, valoper)?
+        {
+            self.validators
+                .save(storage, valoper, &ValidatorState::Active(updates))?;
+        }
+        Ok(())
+    }
+
+    pub fn validator_state(
+        &self,
+        storage: &dyn Storage,
+        valoper: &str,
+    ) -> StdResult<Option<ValidatorState>> {
+        self.validators.may_load(storage, valoper)
+    }
+
+    /// Whether `valoper` was tombstoned and is not tracked anymore
+    pub fn is_pruned(&self, storage: &dyn Storage, valoper: &str) -> StdResult<bool> {
+        Ok(self.pruned.has(storage, valoper))
+    }
+
     pub fn is_active_validator(&self, storage: &dyn Storage, valoper: &str) -> StdResult<bool> {
         let active = self
             .validators
@@ -110,18 +234,100 @@ impl<'a> CrdtState<'a> {
         Ok(active)
     }
 
+    /// Whether `valoper` was active at `height`. Validators not tracked are not active
     pub fn is_active_validator_at_height(
         &self,
         storage: &dyn Storage,
         valoper: &str,
         height: u64,
     ) -> StdResult<bool> {
+        if !self.validators.has(storage, valoper) {
+            return Ok(false);
+        }
         let active = self
             .active_validator_at_height(storage, valoper, height)?
             .is_some();
         Ok(active)
     }
 
+    /// Stops tracking tombstoned validators, until at most `max` validators are tracked. The ones
+    /// tombstoned the earliest are removed first. Jailed validators are never removed, as they
+    /// can be unjailed. Validators for which `keep` returns true are not removed either.
+    ///
+    /// At most `limit` validators are checked per call, from the one after the last checked by
+    /// the previous call, so that the kept ones are eventually checked again.
+    ///
+    /// Removed validators are remembered as pruned, so they can't be added back.
+    ///
+    /// Returns the removed validators.
+    pub fn prune_inactive(
+        &self,
+        storage: &mut dyn Storage,
+        max: u32,
+        limit: u32,
+        mut keep: impl FnMut(&dyn Storage, &str) -> StdResult<bool>,
+    ) -> StdResult<Vec<String>> {
+        let mut tracked = self.tracked.may_load(storage)?.unwrap_or_default();
+        if tracked <= max {
+            return Ok(vec![]);
+        }
+
+        let cursor = self.prune_cursor.may_load(storage)?;
+        let start = cursor
+            .as_ref()
+            .map(|(height, valoper)| Bound::exclusive((*height, valoper.as_str())));
+        let candidates = self
+            .tombstoned
+            .keys(storage, start, None, Order::Ascending)
+            .take(limit as usize)
+            .collect::<StdResult<Vec<_>>>()?;
+        let exhausted = candidates.len() < limit as usize;
+
+        let mut pruned = vec![];
+        let mut unchecked = candidates.len();
+        let mut last_checked = None;
+        for (height, valoper) in candidates {
+            if tracked <= max {
+                break;
+            }
+            unchecked -= 1;
+            if !keep(storage, &valoper)? {
+                self.validators.remove(storage, &valoper);
+                self.tombstoned.remove(storage, (height, &valoper));
+                self.pruned.save(storage, &valoper, &())?;
+                tracked -= 1;
+                pruned.push(valoper.clone());
+            }
+            last_checked = Some((height, valoper));
+        }
+        self.tracked.save(storage, &tracked)?;
+
+        // Start over from the earliest tombstoned once all of them were checked
+        match last_checked {
+            _ if exhausted && unchecked == 0 => self.prune_cursor.remove(storage),
+            Some(last_checked) => self.prune_cursor.save(storage, &last_checked)?,
+            None => {}
+        }
+        Ok(pruned)
+    }
+
+    /// Rebuilds the count of tracked validators and the index of the tombstoned ones, for
+    /// validators tracked before they were maintained
+    pub fn reindex(&self, storage: &mut dyn Storage) -> StdResult<()> {
+        let validators = self
+            .validators
+            .range(storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        self.tombstoned.clear(storage);
+        self.prune_cursor.remove(storage);
+        for (valoper, state) in &validators {
+            if let ValidatorState::Tombstoned { height } = state {
+                self.tombstoned.save(storage, (*height, valoper), &())?;
+            }
+        }
+        self.tracked.save(storage, &(validators.len() as u32))
+    }
+
     /// This returns the valoper address of all active validators
     pub fn list_active_validators(
         &self,
@@ -134,7 +340,7 @@ impl<'a> CrdtState<'a> {
             .range(storage, start, None, Order::Ascending)
             .filter_map(|r| match r {
                 Ok((valoper, ValidatorState::Active(_))) => Some(Ok(valoper)),
-                Ok((_, ValidatorState::Tombstoned {})) => None,
+                Ok(_) => None,
                 Err(e) => Some(Err(e)),
             })
             .take(limit)
@@ -149,7 +355,7 @@ impl<'a> CrdtState<'a> {
         let state = self.validators.load(storage, valoper)?;
         match state {
             ValidatorState::Active(active) => Ok(active.0.first().cloned()),
-            ValidatorState::Tombstoned {} => Ok(None),
+            ValidatorState::Jailed { .. } | ValidatorState::Tombstoned { .. } => Ok(None),
         }
     }
 
@@ -162,7 +368,26 @@ impl<'a> CrdtState<'a> {
         let state = self.validators.load(storage, valoper)?;
         match state {
             ValidatorState::Active(active) => Ok(active.query_at_height(height).cloned()),
-            ValidatorState::Tombstoned {} => Ok(None),
+            ValidatorState::Jailed { .. } | ValidatorState::Tombstoned { .. } => Ok(None),
+        }
+    }
+
+    /// The validator update in effect at `height`, if the validator was signing blocks then.
+    /// Unlike `active_validator_at_height`, jailed validators are included up to their jailing,
+    /// as they are still accountable for what they signed before.
+    pub fn signing_validator_at_height(
+        &self,
+        storage: &dyn Storage,
+        valoper: &str,
+        height: u64,
+    ) -> StdResult<Option<ValUpdate>> {
+        let state = self.validators.may_load(storage, valoper)?;
+        match state {
+            Some(ValidatorState::Active(active)) => Ok(active.query_at_height(height).cloned()),
+            Some(ValidatorState::Jailed { since, updates }) if height < since => {
+                Ok(updates.query_at_height(height).cloned())
+            }
+            _ => Ok(None),
         }
     }
 }
@@ -197,7 +422,7 @@ mod tests {
             .unwrap();
         crdt.add_validator(&mut storage, "carl", mock_update(303))
             .unwrap();
-        crdt.remove_validator(&mut storage, "bob").unwrap();
+        crdt.remove_validator(&mut storage, "bob", 300).unwrap();
 
         assert!(crdt.is_active_validator(&storage, "alice").unwrap());
         assert!(!crdt.is_active_validator(&storage, "bob").unwrap());
@@ -213,7 +438,7 @@ mod tests {
         let mut storage = MemoryStorage::new();
         let crdt = CrdtState::new();
 
-        crdt.remove_validator(&mut storage, "bob").unwrap();
+        crdt.remove_validator(&mut storage, "bob", 300).unwrap();
         crdt.add_validator(&mut storage, "alice", mock_update(123))
             .unwrap();
         crdt.add_validator(&mut storage, "bob", mock_update(200))
@@ -243,7 +468,8 @@ mod tests {
         }
         // in reverse order, so remove doesn't shift the indexes we will later read
         for i in [19, 17, 12, 11, 7, 4, 3] {
-            crdt.remove_validator(&mut storage, &validators[i]).unwrap();
+            crdt.remove_validator(&mut storage, &validators[i], 300)
+                .unwrap();
             validators.remove(i);
         }
 
@@ -332,4 +558,87 @@ mod tests {
             })
         );
     }
+
+    #[test]
+    fn prune_inactive_works() {
+        let mut storage = MemoryStorage::new();
+        let crdt = CrdtState::new();
+
+        for (valoper, height) in [("alice", 1), ("bob", 2), ("carl", 3), ("dave", 4)] {
+            crdt.add_validator(&mut storage, valoper, mock_update(height))
+                .unwrap();
+        }
+        crdt.remove_validator(&mut storage, "carl", 20).unwrap();
+        crdt.jail_validator(&mut storage, "alice", 30).unwrap();
+        crdt.remove_validator(&mut storage, "bob", 40).unwrap();
+        crdt.remove_validator(&mut storage, "erin", 50).unwrap();
+
+        // Nothing is pruned under the limit
+        let pruned = crdt
+            .prune_inactive(&mut storage, 5, 10, |_, _| Ok(false))
+            .unwrap();
+        assert!(pruned.is_empty());
+
+        // The earliest tombstoned are pruned first, skipping the ones to keep, at most `limit`
+        // checked per call
+        let pruned = crdt
+            .prune_inactive(&mut storage, 2, 1, |_, valoper| Ok(valoper == "carl"))
+            .unwrap();
+        assert!(pruned.is_empty());
+        let pruned = crdt
+            .prune_inactive(&mut storage, 2, 1, |_, valoper| Ok(valoper == "carl"))
+            .unwrap();
+        assert_eq!(pruned, ["bob"]);
+        assert_eq!(crdt.validator_state(&storage, "bob").unwrap(), None);
+
+        // Once all were checked, the kept ones are checked again
+        let pruned = crdt
+            .prune_inactive(&mut storage, 2, 10, |_, _| Ok(false))
+            .unwrap();
+        assert_eq!(pruned, ["erin"]);
+        let pruned = crdt
+            .prune_inactive(&mut storage, 2, 10, |_, _| Ok(false))
+            .unwrap();
+        assert_eq!(pruned, ["carl"]);
+
+        // Pruned validators are tombstoned for good
+        crdt.add_validator(&mut storage, "bob", mock_update(60))
+            .unwrap();
+        crdt.remove_validator(&mut storage, "bob", 70).unwrap();
+        crdt.jail_queued_validator(&mut storage, "bob", 80).unwrap();
+        assert_eq!(crdt.validator_state(&storage, "bob").unwrap(), None);
+        assert!(crdt.is_pruned(&storage, "bob").unwrap());
+        assert_eq!(crdt.tracked.load(&storage).unwrap(), 2);
+
+        // Jailed and active validators are never pruned
+        let pruned = crdt
+            .prune_inactive(&mut storage, 0, 10, |_, _| Ok(false))
+            .unwrap();
+        assert!(pruned.is_empty());
+        assert!(crdt.validator_state(&storage, "alice").unwrap().is_some());
+        assert_eq!(
+            crdt.list_active_validators(&storage, None, 10).unwrap(),
+            ["dave"]
+        );
+    }
+
+    #[test]
+    fn reindex_works() {
+        let mut storage = MemoryStorage::new();
+        let crdt = CrdtState::new();
+
+        crdt.add_validator(&mut storage, "alice", mock_update(1))
+            .unwrap();
+        crdt.remove_validator(&mut storage, "bob", 20).unwrap();
+        // As if tracked before the index
+        crdt.tracked.remove(&mut storage);
+        crdt.tombstoned.clear(&mut storage);
+
+        crdt.reindex(&mut storage).unwrap();
+        assert_eq!(crdt.tracked.load(&storage).unwrap(), 2);
+        let pruned = crdt
+            .prune_inactive(&mut storage, 1, 10, |_, _| Ok(false))
+            .unwrap();
+        assert_eq!(pruned, ["bob"]);
+    }
 }
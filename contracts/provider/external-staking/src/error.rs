@@ -21,15 +21,72 @@ pub enum ContractError {
     #[error("Unauthorized")]
     Unauthorized,
 
+    #[error("Rejected malformed validators: {0}")]
+    InvalidValidators(String),
+
     #[error("Invalid denom, {0} expected")]
     InvalidDenom(String),
 
     #[error("You cannot use a max slashing rate over 1.0 (100%)")]
     InvalidMaxSlashing,
 
+    #[error("You cannot redistribute over 1.0 (100%) of the slashed stake")]
+    InvalidSlashRedistribution,
+
+    #[error("Slash redistribution requires the collateral denom {0} to be a rewards denom")]
+    SlashRedistributionDenom(String),
+
+    #[error("You cannot use an evidence bounty over 1.0 (100%)")]
+    InvalidEvidenceBounty,
+
+    #[error("Invalid evidence: {0}")]
+    InvalidEvidence(String),
+
+    #[error("The consumer chain id is not set")]
+    ConsumerChainIdNotSet,
+
+    #[error("Validator {0} was not active at height {1}")]
+    ValidatorNotActiveAt(String, u64),
+
+    #[error("At least one rewards denom is required")]
+    NoRewardsDenoms,
+
+    #[error("No rewards denom with base {0}")]
+    UnknownRewardsBase(String),
+
+    #[error("Rewards are already held in {0}")]
+    RewardsDenomHeld(String),
+
     #[error("Not enough tokens staked, up to {0} can be unbond")]
     NotEnoughStake(Uint128),
 
+    #[error("An unstake batch can't be empty")]
+    EmptyUnstakeBatch,
+
+    #[error("No stake to move to {0}")]
+    NothingToRestake(String),
+
+    #[error("Unstaking would leave {0} staked, below the minimum of {1}")]
+    WouldLeaveDust(Uint128, Uint128),
+
+    #[error("No pending unbond at index {0}")]
+    UnbondNotFound(u32),
+
+    #[error("Pending unbond at index {0} is already released")]
+    UnbondReleased(u32),
+
+    #[error("Pending unbond at index {0} is not for sale")]
+    UnbondNotForSale(u32),
+
+    #[error("Pending unbond at index {0} changed since it was listed")]
+    UnbondListingOutdated(u32),
+
+    #[error("Price too low, at least {0} expected")]
+    PriceTooLow(Uint128),
+
+    #[error("Cannot buy an own pending unbond")]
+    CannotBuyOwnUnbond,
+
     #[error("Not enough tokens released, up to {0} can be claimed")]
     NotEnoughRelease(Uint128),
 
@@ -39,6 +96,9 @@ pub enum ContractError {
     #[error("Cannot stake to {0}, not listed as an active validator on consumer")]
     ValidatorNotActive(String),
 
+    #[error("Cannot stake to {0}, never announced as a validator by the consumer")]
+    UnknownValidator(String),
+
     #[error("Contract already has an open IBC channel")]
     IbcChannelAlreadyOpen,
 
@@ -48,6 +108,12 @@ pub enum ContractError {
     #[error("Invalid authorized endpoint: {0}")]
     InvalidEndpoint(String),
 
+    #[error("Channel must be on connection {expected}, got {actual}")]
+    IbcUnauthorizedConnection { expected: String, actual: String },
+
+    #[error("Channel counterparty port must be {expected}, got {actual}")]
+    IbcUnauthorizedPort { expected: String, actual: String },
+
     #[error("The tx {0} exists but is of the wrong type: {1}")]
     WrongTypeTx(u64, Tx),
 
@@ -57,6 +123,27 @@ pub enum ContractError {
     #[error("Validator '{0}' already tombstoned / not found at height {1}")]
     AlreadyTombstoned(String, u64),
 
+    #[error("Validator set sync in progress, {0} validators pending")]
+    ValsetSyncInProgress(u32),
+
+    #[error("Validator sync recently requested, next request allowed at height {0}")]
+    ValidatorSyncTooSoon(u64),
+
+    #[error("Stake is not locked")]
+    StakeNotLocked,
+
+    #[error("Stake is locked by the pending tx {0}")]
+    StakeLockInUse(u64),
+
+    #[error("Validator {0} is still tracked")]
+    ValidatorTracked(String),
+
+    #[error("Staking on more than {0} validators per user is not allowed")]
+    TooManyValidators(u32),
+
+    #[error("No {0} to be swept")]
+    NothingToSweep(String),
+
     #[error("{0}")]
     Range(#[from] RangeError),
 }
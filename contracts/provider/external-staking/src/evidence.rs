@@ -0,0 +1,230 @@
+//! Double-sign evidence verification.
+//!
+//! Evidence is made of two votes signed by a validator, as their Tendermint sign bytes (the
+//! length-prefixed protobuf encoding of a `CanonicalVote`) and signatures. They are conflicting
+//! when they are of the same type, chain, height and round, but for different blocks.
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Api, Binary};
+
+use crate::error::ContractError;
+
+/// `SignedMsgType` of the votes which can conflict
+const PREVOTE: u64 = 1;
+const PRECOMMIT: u64 = 2;
+
+/// Evidence of a validator double-signing, submitted by `submit_evidence`
+#[cw_serde]
+pub struct DoubleSignEvidence {
+    pub vote_a: SignedVote,
+    pub vote_b: SignedVote,
+}
+
+#[cw_serde]
+pub struct SignedVote {
+    /// Length-prefixed protobuf encoded `CanonicalVote`, as signed by the validator
+    pub sign_bytes: Binary,
+    /// Ed25519 signature of the sign bytes
+    pub signature: Binary,
+}
+
+/// The fields of a Tendermint `CanonicalVote` needed to check for conflicts. The timestamp is
+/// ignored
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CanonicalVote {
+    pub vote_type: u64,
+    pub height: i64,
+    pub round: i64,
+    /// Raw encoded `CanonicalBlockID`
+    pub block_id: Vec<u8>,
+    pub chain_id: String,
+}
+
+impl DoubleSignEvidence {
+    /// Checks both votes are signed with `pub_key`, for `height` on `chain_id`, and conflicting
+    pub fn verify(
+        &self,
+        api: &dyn Api,
+        pub_key: &[u8],
+        chain_id: &str,
+        height: u64,
+    ) -> Result<(), ContractError> {
+        let vote_a = self.vote_a.verify(api, pub_key)?;
+        let vote_b = self.vote_b.verify(api, pub_key)?;
+
+        if vote_a.chain_id != chain_id || vote_b.chain_id != chain_id {
+            return Err(ContractError::InvalidEvidence(format!(
+                "votes not on chain {chain_id}"
+            )));
+        }
+        if ![PREVOTE, PRECOMMIT].contains(&vote_a.vote_type) {
+            return Err(ContractError::InvalidEvidence(
+                "votes neither prevotes nor precommits".to_owned(),
+            ));
+        }
+
+        if vote_a.height != height as i64 || vote_b.height != height as i64 {
+            return Err(ContractError::InvalidEvidence(format!(
+                "votes not at height {height}"
+            )));
+        }
+        if vote_a.vote_type != vote_b.vote_type
+            || vote_a.round != vote_b.round
+            || vote_a.chain_id != vote_b.chain_id
+        {
+            return Err(ContractError::InvalidEvidence(
+                "votes for different steps".to_owned(),
+            ));
+        }
+        if vote_a.block_id == vote_b.block_id {
+            return Err(ContractError::InvalidEvidence(
+                "votes for the same block".to_owned(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl SignedVote {
+    fn verify(&self, api: &dyn Api, pub_key: &[u8]) -> Result<CanonicalVote, ContractError> {
+        let valid = api
+            .ed25519_verify(&self.sign_bytes, &self.signature, pub_key)
+            .map_err(|err| ContractError::InvalidEvidence(err.to_string()))?;
+        if !valid {
+            return Err(ContractError::InvalidEvidence(
+                "invalid vote signature".to_owned(),
+            ));
+        }
+        CanonicalVote::decode(&self.sign_bytes)
+    }
+}
+
+impl CanonicalVote {
+    /// Decodes length-prefixed sign bytes
+    pub fn decode(sign_bytes: &[u8]) -> Result<Self, ContractError> {
+        let mut reader = Reader(sign_bytes);
+        let len = reader.varint()? as usize;
+        let mut reader = Reader(reader.bytes(len)?);
+
+        let mut vote = CanonicalVote::default();
+        while !reader.0.is_empty() {
+            let key = reader.varint()?;
+            match (key >> 3, key & 7) {
+                (1, 0) => vote.vote_type = reader.varint()?,
+                (2, 1) => vote.height = reader.fixed64()? as i64,
+                (3, 1) => vote.round = reader.fixed64()? as i64,
+                (4, 2) => vote.block_id = reader.len_delimited()?.to_vec(),
+                (6, 2) => {
+                    vote.chain_id = String::from_utf8(reader.len_delimited()?.to_vec())
+                        .map_err(|_| malformed())?
+                }
+                // Unused fields
+                (_, 0) => {
+                    reader.varint()?;
+                }
+                (_, 1) => {
+                    reader.fixed64()?;
+                }
+                (_, 2) => {
+                    reader.len_delimited()?;
+                }
+                _ => return Err(malformed()),
+            }
+        }
+
+        Ok(vote)
+    }
+
+    /// Encodes as length-prefixed sign bytes
+    #[cfg(test)]
+    pub fn encode(&self) -> Vec<u8> {
+        fn varint(buf: &mut Vec<u8>, mut value: u64) {
+            while value >= 0x80 {
+                buf.push(value as u8 | 0x80);
+                value >>= 7;
+            }
+            buf.push(value as u8);
+        }
+
+        let mut msg = vec![];
+        varint(&mut msg, 1 << 3);
+        varint(&mut msg, self.vote_type);
+        msg.push(2 << 3 | 1);
+        msg.extend(self.height.to_le_bytes());
+        msg.push(3 << 3 | 1);
+        msg.extend(self.round.to_le_bytes());
+        msg.push(4 << 3 | 2);
+        varint(&mut msg, self.block_id.len() as u64);
+        msg.extend(&self.block_id);
+        msg.push(6 << 3 | 2);
+        varint(&mut msg, self.chain_id.len() as u64);
+        msg.extend(self.chain_id.as_bytes());
+
+        let mut sign_bytes = vec![];
+        varint(&mut sign_bytes, msg.len() as u64);
+        sign_bytes.extend(msg);
+        sign_bytes
+    }
+}
+
+fn malformed() -> ContractError {
+    ContractError::InvalidEvidence("malformed vote".to_owned())
+}
+
+/// Minimal protobuf wire format reader
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], ContractError> {
+        if self.0.len() < len {
+            return Err(malformed());
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn varint(&mut self) -> Result<u64, ContractError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.bytes(1)?[0];
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(malformed())
+    }
+
+    fn fixed64(&mut self) -> Result<u64, ContractError> {
+        let bytes = self.bytes(8)?;
+        Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn len_delimited(&mut self) -> Result<&'a [u8], ContractError> {
+        let len = self.varint()? as usize;
+        self.bytes(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_sign_bytes() {
+        let vote = CanonicalVote {
+            vote_type: 2,
+            height: 300,
+            round: 1,
+            block_id: vec![7; 40],
+            chain_id: "osmosis-1".to_owned(),
+        };
+        assert_eq!(CanonicalVote::decode(&vote.encode()).unwrap(), vote);
+
+        // Truncated sign bytes
+        let sign_bytes = vote.encode();
+        let err = CanonicalVote::decode(&sign_bytes[..sign_bytes.len() - 1]).unwrap_err();
+        assert_eq!(err, malformed());
+    }
+}
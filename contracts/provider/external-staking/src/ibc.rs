@@ -2,19 +2,20 @@
 use cosmwasm_std::entry_point;
 
 use cosmwasm_std::{
-    from_slice, DepsMut, Env, Ibc3ChannelOpenResponse, IbcBasicResponse, IbcChannel,
-    IbcChannelCloseMsg, IbcChannelConnectMsg, IbcChannelOpenMsg, IbcChannelOpenResponse,
-    IbcPacketAckMsg, IbcPacketReceiveMsg, IbcPacketTimeoutMsg, IbcReceiveResponse, IbcTimeout,
+    ensure, from_binary, from_slice, Api, Attribute, Deps, DepsMut, Env, Ibc3ChannelOpenResponse,
+    IbcBasicResponse, IbcChannel, IbcChannelCloseMsg, IbcChannelConnectMsg, IbcChannelOpenMsg,
+    IbcChannelOpenResponse, IbcPacketAckMsg, IbcPacketReceiveMsg, IbcPacketTimeoutMsg,
+    IbcReceiveResponse, IbcTimeout,
 };
 use cw_storage_plus::Item;
 use mesh_apis::ibc::{
-    ack_success, validate_channel_order, AckWrapper, AddValidator, AddValidatorsAck,
+    ack_fail, ack_success, validate_channel_order, AckWrapper, AddValidator, AddValidatorsAck,
     ConsumerPacket, DistributeAck, JailValidatorsAck, ProtocolVersion, ProviderPacket,
-    RemoveValidator, RemoveValidatorsAck,
+    RemoveValidator, RemoveValidatorsAck, RequestValidatorsAck, RestakeAck, StakeAck,
+    UnjailValidatorsAck, UnstakeAck, UnstakeBatchAck,
 };
 
-use crate::contract::ExternalStakingContract;
-use crate::crdt::ValUpdate;
+use crate::contract::{ExternalStakingContract, DEFAULT_VALSET_SYNC_LIMIT};
 use crate::error::ContractError;
 use crate::msg::AuthorizedEndpoint;
 
@@ -31,6 +32,20 @@ pub const IBC_CHANNEL: Item<IbcChannel> = Item::new("ibc_channel");
 // This is long enough to allow some clock drift between chains
 const DEFAULT_TIMEOUT: u64 = 10 * 60;
 
+/// Logs the ack of a packet sent for `tx_id` echoing another tx id. The ack still resolves the tx
+/// of the packet, which was set by this contract. Returns the attribute reporting the mismatch
+fn echoed_tx_mismatch(api: &dyn Api, tx_id: u64, echoed: Option<u64>) -> Option<Attribute> {
+    match echoed {
+        Some(echoed) if echoed != tx_id => {
+            api.debug(&format!(
+                "Ack of the tx {tx_id} packet echoes the tx {echoed}"
+            ));
+            Some(Attribute::new("echoed_tx_id", echoed.to_string()))
+        }
+        _ => None,
+    }
+}
+
 pub fn packet_timeout(env: &Env) -> IbcTimeout {
     // No idea about their blocktime, but 24 hours ahead of our view of the clock
     // should be decently in the future.
@@ -38,6 +53,42 @@ pub fn packet_timeout(env: &Env) -> IbcTimeout {
     IbcTimeout::with_timestamp(timeout)
 }
 
+/// Checks the channel ordering, and that it connects to the authorized endpoint
+fn validate_channel(deps: Deps, channel: &IbcChannel) -> Result<(), ContractError> {
+    validate_channel_order(&channel.order)?;
+
+    let authorized = AUTH_ENDPOINT.load(deps.storage)?;
+    ensure!(
+        authorized.connection_id == channel.connection_id,
+        ContractError::IbcUnauthorizedConnection {
+            expected: authorized.connection_id,
+            actual: channel.connection_id.clone(),
+        }
+    );
+    ensure!(
+        authorized.port_id == channel.counterparty_endpoint.port_id,
+        ContractError::IbcUnauthorizedPort {
+            expected: authorized.port_id,
+            actual: channel.counterparty_endpoint.port_id.clone(),
+        }
+    );
+    Ok(())
+}
+
+/// Splits validators received from the consumer into the valid ones and the errors of the
+/// malformed ones
+fn validate_validators(validators: Vec<AddValidator>) -> (Vec<AddValidator>, Vec<String>) {
+    let mut valid = vec![];
+    let mut errors = vec![];
+    for validator in validators {
+        match validator.validate() {
+            Ok(()) => valid.push(validator),
+            Err(err) => errors.push(err.to_string()),
+        }
+    }
+    (valid, errors)
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 /// enforces ordering and versioning constraints
 pub fn ibc_channel_open(
@@ -58,17 +109,7 @@ pub fn ibc_channel_open(
         } => (channel, counterparty_version),
     };
 
-    // verify the ordering is correct
-    validate_channel_order(&channel.order)?;
-
-    // assert expected endpoint
-    let authorized = AUTH_ENDPOINT.load(deps.storage)?;
-    if authorized.connection_id != channel.connection_id
-        || authorized.port_id != channel.counterparty_endpoint.port_id
-    {
-        // FIXME: do we need a better error here?
-        return Err(ContractError::Unauthorized);
-    }
+    validate_channel(deps.as_ref(), &channel)?;
 
     // we handshake with the counterparty version, it must not be empty
     let v: ProtocolVersion = from_slice(counterparty_version.as_bytes())?;
@@ -98,7 +139,11 @@ pub fn ibc_channel_connect(
         IbcChannelConnectMsg::OpenAck { .. } => return Err(ContractError::IbcOpenInitDisallowed),
     };
 
-    // Version negotiation over, we can only store the channel
+    // Check again, along with the negotiated version, before storing the channel
+    validate_channel(deps.as_ref(), &channel)?;
+    let version: ProtocolVersion = from_slice(channel.version.as_bytes())?;
+    version.verify_compatibility(SUPPORTED_IBC_PROTOCOL_VERSION, MIN_IBC_PROTOCOL_VERSION)?;
+
     IBC_CHANNEL.save(deps.storage, &channel)?;
 
     Ok(IbcBasicResponse::default())
@@ -126,23 +171,15 @@ pub fn ibc_packet_receive(
     let packet: ConsumerPacket = from_slice(&msg.packet.data)?;
     let resp = match packet {
         ConsumerPacket::AddValidators(to_add) => {
-            for AddValidator {
-                valoper,
-                pub_key,
-                start_height,
-                start_time,
-            } in to_add
-            {
-                let update = ValUpdate {
-                    pub_key,
-                    start_height,
-                    start_time,
-                };
-                contract
-                    .val_set
-                    .add_validator(deps.storage, &valoper, update)?;
-            }
-            let ack = ack_success(&AddValidatorsAck {})?;
+            // Malformed validators are not stored, and reported in the ack
+            let (valid, errors) = validate_validators(to_add);
+            // Big sets are only partially added here, the rest is added via `continue_valset_sync`
+            contract.add_validators(deps.storage, valid, DEFAULT_VALSET_SYNC_LIMIT)?;
+            let ack = if errors.is_empty() {
+                ack_success(&AddValidatorsAck {})?
+            } else {
+                ack_fail(ContractError::InvalidValidators(errors.join("; ")))?
+            };
             IbcReceiveResponse::new().set_ack(ack)
         }
         ConsumerPacket::TombstoneValidators(to_remove) => {
@@ -159,7 +196,9 @@ pub fn ibc_packet_receive(
                     &valoper,
                     end_height,
                 )?;
-                contract.val_set.remove_validator(deps.storage, &valoper)?;
+                contract
+                    .val_set
+                    .remove_validator(deps.storage, &valoper, end_height)?;
                 if active {
                     // slash the validator
                     // TODO: Error handling / capturing
@@ -167,6 +206,8 @@ pub fn ibc_packet_receive(
                     msgs.push(msg);
                 }
             }
+            // Credit the redistributed slashes once paid back
+            msgs.extend(contract.redistribution_msg(&env, deps.storage)?);
             let ack = ack_success(&RemoveValidatorsAck {})?;
             IbcReceiveResponse::new().set_ack(ack).add_messages(msgs)
         }
@@ -184,8 +225,8 @@ pub fn ibc_packet_receive(
                     &valoper,
                     end_height,
                 )?;
-                // We don't change the validator's state here, as that's currently not supported
-                // (only Active and Tombstoned)
+                // Jailed validators don't accept new stakes until unjailed
+                contract.jail_validator(deps.storage, &valoper, end_height)?;
                 if active {
                     // slash the validator
                     // TODO: Slash with a different slash ratio! (downtime / offline slash ratio)
@@ -193,9 +234,18 @@ pub fn ibc_packet_receive(
                     msgs.push(msg);
                 }
             }
+            // Credit the redistributed slashes once paid back
+            msgs.extend(contract.redistribution_msg(&env, deps.storage)?);
             let ack = ack_success(&JailValidatorsAck {})?;
             IbcReceiveResponse::new().set_ack(ack).add_messages(msgs)
         }
+        ConsumerPacket::UnjailValidators(to_unjail) => {
+            for RemoveValidator { valoper, .. } in to_unjail {
+                contract.val_set.unjail_validator(deps.storage, &valoper)?;
+            }
+            let ack = ack_success(&UnjailValidatorsAck {})?;
+            IbcReceiveResponse::new().set_ack(ack)
+        }
         ConsumerPacket::Distribute { validator, rewards } => {
             let contract = ExternalStakingContract::new();
             let evt = contract.distribute_rewards(deps, &validator, rewards)?;
@@ -227,38 +277,88 @@ pub fn ibc_packet_ack(
     let mut resp = IbcBasicResponse::new();
 
     match (packet, ack) {
-        (ProviderPacket::Stake { tx_id, .. }, AckWrapper::Result(_)) => {
-            let msg = contract.commit_stake(deps, tx_id)?;
+        (ProviderPacket::Stake { tx_id, .. }, AckWrapper::Result(data)) => {
+            let StakeAck { tx_id: echoed } = from_binary(&data)?;
+            resp = resp.add_attributes(echoed_tx_mismatch(deps.api, tx_id, echoed));
+            let msg = contract.commit_stake(deps, env.clone(), tx_id)?;
             resp = resp
                 .add_message(msg)
                 .add_attribute("success", "true")
                 .add_attribute("tx_id", tx_id.to_string());
         }
         (ProviderPacket::Stake { tx_id, .. }, AckWrapper::Error(e)) => {
-            let msg = contract.rollback_stake(deps, tx_id)?;
+            let msg = contract.rollback_stake(deps, env.clone(), tx_id)?;
             resp = resp
                 .add_message(msg)
                 .add_attribute("error", e)
                 .add_attribute("tx_id", tx_id.to_string());
         }
-        (ProviderPacket::Unstake { tx_id, .. }, AckWrapper::Result(_)) => {
+        (ProviderPacket::Unstake { tx_id, .. }, AckWrapper::Result(data)) => {
+            let UnstakeAck { tx_id: echoed } = from_binary(&data)?;
+            resp = resp.add_attributes(echoed_tx_mismatch(deps.api, tx_id, echoed));
             contract.commit_unstake(deps, env, tx_id)?;
             resp = resp
                 .add_attribute("success", "true")
                 .add_attribute("tx_id", tx_id.to_string());
         }
         (ProviderPacket::Unstake { tx_id, .. }, AckWrapper::Error(e)) => {
-            contract.rollback_unstake(deps, tx_id)?;
+            contract.rollback_unstake(deps, env.clone(), tx_id)?;
+            resp = resp
+                .add_attribute("error", e)
+                .add_attribute("tx_id", tx_id.to_string());
+        }
+        (ProviderPacket::UnstakeBatch { tx_id, .. }, AckWrapper::Result(data)) => {
+            let UnstakeBatchAck { tx_id: echoed } = from_binary(&data)?;
+            resp = resp.add_attributes(echoed_tx_mismatch(deps.api, tx_id, echoed));
+            contract.commit_unstake(deps, env, tx_id)?;
+            resp = resp
+                .add_attribute("success", "true")
+                .add_attribute("tx_id", tx_id.to_string());
+        }
+        (ProviderPacket::UnstakeBatch { tx_id, .. }, AckWrapper::Error(e)) => {
+            contract.rollback_unstake(deps, env.clone(), tx_id)?;
+            resp = resp
+                .add_attribute("error", e)
+                .add_attribute("tx_id", tx_id.to_string());
+        }
+        (ProviderPacket::Restake { tx_id, .. }, AckWrapper::Result(data)) => {
+            let RestakeAck { tx_id: echoed } = from_binary(&data)?;
+            resp = resp.add_attributes(echoed_tx_mismatch(deps.api, tx_id, echoed));
+            contract.commit_restake(deps, env, tx_id)?;
+            resp = resp
+                .add_attribute("success", "true")
+                .add_attribute("tx_id", tx_id.to_string());
+        }
+        (ProviderPacket::Restake { tx_id, .. }, AckWrapper::Error(e)) => {
+            contract.rollback_restake(deps, env.clone(), tx_id)?;
             resp = resp
                 .add_attribute("error", e)
                 .add_attribute("tx_id", tx_id.to_string());
         }
         (ProviderPacket::TransferRewards { tx_id, .. }, AckWrapper::Result(_)) => {
             // TODO: Any events to add?
-            contract.commit_withdraw_rewards(deps, tx_id)?;
+            contract.commit_withdraw_rewards(deps, env.clone(), tx_id)?;
         }
         (ProviderPacket::TransferRewards { tx_id, .. }, AckWrapper::Error(e)) => {
-            contract.rollback_withdraw_rewards(deps, tx_id)?;
+            contract.rollback_withdraw_rewards(deps, env.clone(), tx_id)?;
+            resp = resp
+                .add_attribute("error", e)
+                .add_attribute("packet", msg.original_packet.sequence.to_string());
+        }
+        (ProviderPacket::RequestValidators {}, AckWrapper::Result(data)) => {
+            let RequestValidatorsAck { validators } = from_binary(&data)?;
+            // Malformed validators are skipped, a sync can be requested again later
+            let (valid, errors) = validate_validators(validators);
+            contract.merge_validator_sync(deps.storage, env.block.height, valid)?;
+            resp = resp.add_attribute("success", "true");
+            if !errors.is_empty() {
+                resp = resp.add_attribute(
+                    "error",
+                    ContractError::InvalidValidators(errors.join("; ")).to_string(),
+                );
+            }
+        }
+        (ProviderPacket::RequestValidators {}, AckWrapper::Error(e)) => {
             resp = resp
                 .add_attribute("error", e)
                 .add_attribute("packet", msg.original_packet.sequence.to_string());
@@ -271,7 +371,7 @@ pub fn ibc_packet_ack(
 /// This should trigger a rollback of staking/unstaking
 pub fn ibc_packet_timeout(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     msg: IbcPacketTimeoutMsg,
 ) -> Result<IbcBasicResponse, ContractError> {
     let packet: ProviderPacket = from_slice(&msg.packet.data)?;
@@ -279,19 +379,650 @@ pub fn ibc_packet_timeout(
     let mut resp = IbcBasicResponse::new().add_attribute("action", "ibc_packet_timeout");
     match packet {
         ProviderPacket::Stake { tx_id, .. } => {
-            let msg = contract.rollback_stake(deps, tx_id)?;
+            let msg = contract.rollback_stake(deps, env.clone(), tx_id)?;
             resp = resp
                 .add_message(msg)
                 .add_attribute("tx_id", tx_id.to_string());
         }
-        ProviderPacket::Unstake { tx_id, .. } => {
-            contract.rollback_unstake(deps, tx_id)?;
+        ProviderPacket::Unstake { tx_id, .. } | ProviderPacket::UnstakeBatch { tx_id, .. } => {
+            contract.rollback_unstake(deps, env.clone(), tx_id)?;
+            resp = resp.add_attribute("tx_id", tx_id.to_string());
+        }
+        ProviderPacket::Restake { tx_id, .. } => {
+            contract.rollback_restake(deps, env.clone(), tx_id)?;
             resp = resp.add_attribute("tx_id", tx_id.to_string());
         }
         ProviderPacket::TransferRewards { tx_id, .. } => {
-            contract.rollback_withdraw_rewards(deps, tx_id)?;
+            contract.rollback_withdraw_rewards(deps, env.clone(), tx_id)?;
             resp = resp.add_attribute("tx_id", tx_id.to_string());
         }
+        // Nothing to roll back, a sync can be requested again
+        ProviderPacket::RequestValidators {} => {}
     };
     Ok(resp)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bech32::{ToBase32, Variant};
+    use cosmwasm_std::testing::{
+        mock_dependencies, mock_env, mock_ibc_channel, mock_ibc_packet_ack, mock_ibc_packet_recv,
+        mock_ibc_packet_timeout, mock_info, MockApi, MockQuerier, MockStorage,
+    };
+    use cosmwasm_std::{
+        coin, to_binary, Decimal, IbcAcknowledgement, IbcOrder, OwnedDeps, StdError, Uint128,
+    };
+    use mesh_apis::cross_staking_api::CrossStakingApi;
+    use mesh_apis::ibc::{AddValidator, UnstakeInfo, VersionError, PROTOCOL_NAME};
+    use mesh_sync::ValueRange;
+
+    use crate::contract::DEFAULT_VALSET_SYNC_LIMIT;
+    use crate::msg::{ReceiveVirtualStake, ValidatorStatus};
+    use crate::state::RewardDenom;
+
+    fn instantiate() -> OwnedDeps<MockStorage, MockApi, MockQuerier> {
+        let mut deps = mock_dependencies();
+        ExternalStakingContract::new()
+            .instantiate(
+                (deps.as_mut(), mock_env(), mock_info("owner", &[])).into(),
+                "osmo".to_owned(),
+                vec![RewardDenom::from_denom("star")],
+                "vault".to_owned(),
+                100,
+                AuthorizedEndpoint::new("connection-2", "wasm-osmo1foobarbaz"),
+                Decimal::percent(10),
+            )
+            .unwrap();
+        deps
+    }
+
+    fn valoper(name: &str) -> String {
+        bech32::encode("osmovaloper", name.as_bytes().to_base32(), Variant::Bech32).unwrap()
+    }
+
+    fn synced(deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>) -> bool {
+        ExternalStakingContract::new()
+            .sync_status((deps.as_ref(), mock_env()).into())
+            .unwrap()
+            .synced
+    }
+
+    fn channel(order: IbcOrder, port: &str, version: &str) -> IbcChannel {
+        let mut channel = mock_ibc_channel("channel-12", order, version);
+        channel.counterparty_endpoint.port_id = port.to_owned();
+        channel
+    }
+
+    fn protocol_version(version: &str) -> String {
+        ProtocolVersion::new(PROTOCOL_NAME, version)
+            .to_string()
+            .unwrap()
+    }
+
+    #[test]
+    fn channel_handshake() {
+        let mut deps = instantiate();
+        // Test code opens a channel on instantiation
+        IBC_CHANNEL.remove(&mut deps.storage);
+        let version = protocol_version(SUPPORTED_IBC_PROTOCOL_VERSION);
+        let open = |deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>, channel| {
+            ibc_channel_open(
+                deps.as_mut(),
+                mock_env(),
+                IbcChannelOpenMsg::new_try(channel, version.clone()),
+            )
+        };
+
+        // Wrong port
+        let err = open(
+            &mut deps,
+            channel(IbcOrder::Unordered, "wasm-osmo1other", &version),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::IbcUnauthorizedPort {
+                expected: "wasm-osmo1foobarbaz".to_owned(),
+                actual: "wasm-osmo1other".to_owned(),
+            }
+        );
+
+        // Wrong connection
+        let mut other_connection = channel(IbcOrder::Unordered, "wasm-osmo1foobarbaz", &version);
+        other_connection.connection_id = "connection-3".to_owned();
+        let err = open(&mut deps, other_connection).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::IbcUnauthorizedConnection {
+                expected: "connection-2".to_owned(),
+                actual: "connection-3".to_owned(),
+            }
+        );
+
+        // Wrong ordering
+        let err = open(
+            &mut deps,
+            channel(IbcOrder::Ordered, "wasm-osmo1foobarbaz", &version),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::IbcVersion(VersionError::InvalidChannelOrder)
+        );
+
+        // Wrong version
+        let err = ibc_channel_open(
+            deps.as_mut(),
+            mock_env(),
+            IbcChannelOpenMsg::new_try(
+                channel(IbcOrder::Unordered, "wasm-osmo1foobarbaz", &version),
+                protocol_version("0.1.0"),
+            ),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::IbcVersion(VersionError::VersionTooOld { .. })
+        ));
+
+        // The channel is checked again on connect, along with the negotiated version
+        let valid = channel(IbcOrder::Unordered, "wasm-osmo1foobarbaz", &version);
+        open(&mut deps, valid.clone()).unwrap();
+        let err = ibc_channel_connect(
+            deps.as_mut(),
+            mock_env(),
+            IbcChannelConnectMsg::new_confirm(channel(
+                IbcOrder::Unordered,
+                "wasm-osmo1other",
+                &version,
+            )),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::IbcUnauthorizedPort { .. }));
+
+        let err = ibc_channel_connect(
+            deps.as_mut(),
+            mock_env(),
+            IbcChannelConnectMsg::new_confirm(channel(
+                IbcOrder::Unordered,
+                "wasm-osmo1foobarbaz",
+                &protocol_version("99.0.0"),
+            )),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::IbcVersion(VersionError::VersionTooNew { .. })
+        ));
+
+        ibc_channel_connect(
+            deps.as_mut(),
+            mock_env(),
+            IbcChannelConnectMsg::new_confirm(valid.clone()),
+        )
+        .unwrap();
+        assert_eq!(IBC_CHANNEL.load(&deps.storage).unwrap(), valid);
+    }
+
+    #[test]
+    fn first_validators_packet_syncs_valset() {
+        let mut deps = instantiate();
+        assert!(!synced(&deps));
+
+        let packet = ConsumerPacket::AddValidators(vec![
+            AddValidator::mock(&valoper("alice")),
+            AddValidator::mock(&valoper("bob")),
+        ]);
+        let msg = mock_ibc_packet_recv("channel-172", &packet).unwrap();
+        ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+
+        assert!(synced(&deps));
+    }
+
+    #[test]
+    fn big_validators_packet_syncs_valset_once_processed() {
+        let mut deps = instantiate();
+        let contract = ExternalStakingContract::new();
+
+        let validators = (0..DEFAULT_VALSET_SYNC_LIMIT + 1)
+            .map(|i| AddValidator::mock(&valoper(&format!("validator-{:02}", i))))
+            .collect();
+        let packet = ConsumerPacket::AddValidators(validators);
+        let msg = mock_ibc_packet_recv("channel-172", &packet).unwrap();
+        ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert!(!synced(&deps));
+
+        contract
+            .continue_valset_sync(
+                (deps.as_mut(), mock_env(), mock_info("anyone", &[])).into(),
+                None,
+            )
+            .unwrap();
+        assert!(synced(&deps));
+    }
+
+    #[test]
+    fn queued_validator_is_jailed() {
+        let mut deps = instantiate();
+        let contract = ExternalStakingContract::new();
+
+        let validators: Vec<_> = (0..DEFAULT_VALSET_SYNC_LIMIT + 1)
+            .map(|i| valoper(&format!("validator-{:02}", i)))
+            .collect();
+        let queued = validators.last().unwrap().clone();
+        let packet = ConsumerPacket::AddValidators(
+            validators.iter().map(|v| AddValidator::mock(v)).collect(),
+        );
+        let msg = mock_ibc_packet_recv("channel-172", &packet).unwrap();
+        ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+
+        let status = |deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>| {
+            contract
+                .validator((deps.as_ref(), mock_env()).into(), queued.clone())
+                .unwrap()
+                .status
+        };
+        assert_eq!(status(&deps), None);
+
+        // Jailed while still in the backlog
+        let packet = ConsumerPacket::JailValidators(vec![RemoveValidator {
+            valoper: queued.clone(),
+            height: 200,
+            time: 1687339542,
+        }]);
+        let msg = mock_ibc_packet_recv("channel-172", &packet).unwrap();
+        ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(status(&deps), Some(ValidatorStatus::Jailed { since: 200 }));
+
+        // It stays jailed once added
+        contract
+            .continue_valset_sync(
+                (deps.as_mut(), mock_env(), mock_info("anyone", &[])).into(),
+                None,
+            )
+            .unwrap();
+        assert!(synced(&deps));
+        assert_eq!(status(&deps), Some(ValidatorStatus::Jailed { since: 200 }));
+        let active = contract
+            .val_set
+            .list_active_validators(&deps.storage, None, 100)
+            .unwrap();
+        assert_eq!(active.len(), validators.len() - 1);
+        assert!(!active.contains(&queued));
+
+        // And active with its updates once unjailed
+        let packet = ConsumerPacket::UnjailValidators(vec![RemoveValidator {
+            valoper: queued.clone(),
+            height: 300,
+            time: 1687339542,
+        }]);
+        let msg = mock_ibc_packet_recv("channel-172", &packet).unwrap();
+        ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(status(&deps), Some(ValidatorStatus::Active {}));
+        assert!(contract
+            .val_set
+            .active_validator(&deps.storage, &queued)
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn malformed_validators_are_rejected() {
+        let mut deps = instantiate();
+        let contract = ExternalStakingContract::new();
+
+        let valid = AddValidator::mock(&valoper("alice"));
+        // Consumers can't fill the pubkey yet
+        let no_pubkey = AddValidator {
+            pub_key: "TODO".to_owned(),
+            ..AddValidator::mock(&valoper("bob"))
+        };
+        let bad_valoper = AddValidator::mock("carl");
+        let bad_checksum =
+            AddValidator::mock("cosmosvaloper1sjllsnramtg3ewxqwwrwjxfgc4n4ef9u2lcnj1");
+        let packet =
+            ConsumerPacket::AddValidators(vec![valid, no_pubkey, bad_valoper, bad_checksum]);
+        let msg = mock_ibc_packet_recv("channel-172", &packet).unwrap();
+        let resp = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+
+        let ack: AckWrapper = from_slice(&resp.acknowledgement).unwrap();
+        assert_eq!(
+            ack,
+            AckWrapper::Error(
+                "Rejected malformed validators: Invalid valoper address: carl; \
+                 Invalid valoper address: cosmosvaloper1sjllsnramtg3ewxqwwrwjxfgc4n4ef9u2lcnj1"
+                    .to_owned()
+            )
+        );
+
+        // Only the valid validators are stored
+        let active = contract
+            .val_set
+            .list_active_validators(&deps.storage, None, 10)
+            .unwrap();
+        assert_eq!(active, [valoper("alice"), valoper("bob")]);
+    }
+
+    #[test]
+    fn validator_jail_unjail_tombstone() {
+        let mut deps = instantiate();
+        let contract = ExternalStakingContract::new();
+        let alice = valoper("alice");
+
+        let receive = |deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+                       packet: ConsumerPacket| {
+            let msg = mock_ibc_packet_recv("channel-172", &packet).unwrap();
+            ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        };
+        let status = |deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>| {
+            contract
+                .validator((deps.as_ref(), mock_env()).into(), alice.clone())
+                .unwrap()
+                .status
+        };
+        let stake = |deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>| {
+            contract.receive_virtual_stake(
+                (deps.as_mut(), mock_env(), mock_info("vault", &[])).into(),
+                "owner".to_owned(),
+                coin(100, "osmo"),
+                1,
+                to_binary(&ReceiveVirtualStake {
+                    validator: alice.clone(),
+                })
+                .unwrap(),
+            )
+        };
+        let removal = |height| RemoveValidator {
+            valoper: alice.clone(),
+            height,
+            time: 1687339542,
+        };
+
+        assert_eq!(status(&deps), None);
+        receive(
+            &mut deps,
+            ConsumerPacket::AddValidators(vec![AddValidator::mock(&alice)]),
+        );
+        assert_eq!(status(&deps), Some(ValidatorStatus::Active {}));
+
+        // Jailed validators don't accept new stakes
+        receive(
+            &mut deps,
+            ConsumerPacket::JailValidators(vec![removal(200)]),
+        );
+        assert_eq!(status(&deps), Some(ValidatorStatus::Jailed { since: 200 }));
+        let err = stake(&mut deps).unwrap_err();
+        assert_eq!(err, ContractError::ValidatorNotActive(alice.clone()));
+        let active = contract
+            .val_set
+            .list_active_validators(&deps.storage, None, 10)
+            .unwrap();
+        assert!(active.is_empty());
+
+        // Until unjailed
+        receive(
+            &mut deps,
+            ConsumerPacket::UnjailValidators(vec![removal(300)]),
+        );
+        assert_eq!(status(&deps), Some(ValidatorStatus::Active {}));
+        stake(&mut deps).unwrap();
+
+        // Tombstoning is for good
+        receive(
+            &mut deps,
+            ConsumerPacket::TombstoneValidators(vec![removal(400)]),
+        );
+        assert_eq!(
+            status(&deps),
+            Some(ValidatorStatus::Tombstoned { height: 400 })
+        );
+        receive(
+            &mut deps,
+            ConsumerPacket::UnjailValidators(vec![removal(500)]),
+        );
+        assert_eq!(
+            status(&deps),
+            Some(ValidatorStatus::Tombstoned { height: 400 })
+        );
+        let err = stake(&mut deps).unwrap_err();
+        assert_eq!(err, ContractError::ValidatorNotActive(alice));
+    }
+
+    #[test]
+    fn stake_ack_echoes_tx_id() {
+        let mut deps = instantiate();
+        let contract = ExternalStakingContract::new();
+        let alice = valoper("alice");
+
+        let packet = ConsumerPacket::AddValidators(vec![AddValidator::mock(&alice)]);
+        let msg = mock_ibc_packet_recv("channel-172", &packet).unwrap();
+        ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+
+        let ack = |deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+                   tx_id: u64,
+                   echoed: Option<u64>| {
+            contract
+                .receive_virtual_stake(
+                    (deps.as_mut(), mock_env(), mock_info("vault", &[])).into(),
+                    "owner".to_owned(),
+                    coin(100, "osmo"),
+                    tx_id,
+                    to_binary(&ReceiveVirtualStake {
+                        validator: alice.clone(),
+                    })
+                    .unwrap(),
+                )
+                .unwrap();
+            let packet = ProviderPacket::Stake {
+                validator: alice.clone(),
+                stake: coin(100, "osmo"),
+                tx_id,
+            };
+            let ack = ack_success(&StakeAck { tx_id: echoed }).unwrap();
+            let msg =
+                mock_ibc_packet_ack("channel-172", &packet, IbcAcknowledgement::new(ack)).unwrap();
+            ibc_packet_ack(deps.as_mut(), mock_env(), msg)
+        };
+
+        // A mismatched echoed tx id is reported, and the tx of the packet committed
+        let resp = ack(&mut deps, 1, Some(2)).unwrap();
+        assert_eq!(resp.messages.len(), 1);
+        assert!(resp
+            .attributes
+            .contains(&Attribute::new("echoed_tx_id", "2")));
+        assert!(resp.attributes.contains(&Attribute::new("tx_id", "1")));
+
+        let resp = ack(&mut deps, 2, Some(2)).unwrap();
+        assert_eq!(resp.messages.len(), 1);
+        assert!(!resp.attributes.iter().any(|a| a.key == "echoed_tx_id"));
+
+        // Consumers not echoing it are still supported
+        let resp = ack(&mut deps, 3, None).unwrap();
+        assert_eq!(resp.messages.len(), 1);
+
+        let stake = contract
+            .stake(
+                (deps.as_ref(), mock_env()).into(),
+                "owner".to_owned(),
+                alice,
+            )
+            .unwrap();
+        // All the acks committed the stake of their packet
+        assert_eq!(stake.stake.low().u128(), 300);
+        assert_eq!(stake.stake.high().u128(), 300);
+    }
+
+    #[test]
+    fn restake_ack_and_timeout() {
+        let mut deps = instantiate();
+        let contract = ExternalStakingContract::new();
+        let [alice, bob, carl] = ["alice", "bob", "carl"].map(valoper);
+
+        let receive = |deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+                       packet: ConsumerPacket| {
+            let msg = mock_ibc_packet_recv("channel-172", &packet).unwrap();
+            ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        };
+        receive(
+            &mut deps,
+            ConsumerPacket::AddValidators(vec![
+                AddValidator::mock(&alice),
+                AddValidator::mock(&bob),
+                AddValidator::mock(&carl),
+            ]),
+        );
+        for (tx_id, validator) in [(1, &alice), (2, &bob)] {
+            contract
+                .receive_virtual_stake(
+                    (deps.as_mut(), mock_env(), mock_info("vault", &[])).into(),
+                    "owner".to_owned(),
+                    coin(100, "osmo"),
+                    tx_id,
+                    to_binary(&ReceiveVirtualStake {
+                        validator: validator.clone(),
+                    })
+                    .unwrap(),
+                )
+                .unwrap();
+            contract
+                .commit_stake(deps.as_mut(), mock_env(), tx_id)
+                .unwrap();
+        }
+
+        let restake = |deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+                       from: &str|
+         -> Result<ProviderPacket, ContractError> {
+            let resp = contract.restake(
+                (deps.as_mut(), mock_env(), mock_info("vault", &[])).into(),
+                "owner".to_owned(),
+                vec![from.to_owned()],
+                carl.clone(),
+            )?;
+            let tx_id = resp
+                .attributes
+                .iter()
+                .find(|attr| attr.key == "tx_id")
+                .unwrap()
+                .value
+                .parse()
+                .unwrap();
+            Ok(ProviderPacket::Restake {
+                from: vec![UnstakeInfo {
+                    validator: from.to_owned(),
+                    unstake: coin(100, "osmo"),
+                }],
+                to_validator: carl.clone(),
+                tx_id,
+            })
+        };
+        let stake = |deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>, validator: &str| {
+            contract
+                .stake(
+                    (deps.as_ref(), mock_env()).into(),
+                    "owner".to_owned(),
+                    validator.to_owned(),
+                )
+                .unwrap()
+                .stake
+        };
+
+        // An error ack rolls the restake back
+        let packet = restake(&mut deps, &alice).unwrap();
+        let ack = IbcAcknowledgement::new(ack_fail(StdError::generic_err("failed")).unwrap());
+        let msg = mock_ibc_packet_ack("channel-172", &packet, ack).unwrap();
+        ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(stake(&deps, &alice), ValueRange::new_val(Uint128::new(100)));
+        assert_eq!(stake(&deps, &carl), ValueRange::new_val(Uint128::zero()));
+
+        // And so does a timeout
+        let packet = restake(&mut deps, &alice).unwrap();
+        let msg = mock_ibc_packet_timeout("channel-172", &packet).unwrap();
+        ibc_packet_timeout(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(stake(&deps, &alice), ValueRange::new_val(Uint128::new(100)));
+        assert_eq!(stake(&deps, &carl), ValueRange::new_val(Uint128::zero()));
+
+        // A successful ack commits it
+        let packet = restake(&mut deps, &alice).unwrap();
+        let ProviderPacket::Restake { tx_id, .. } = packet else {
+            unreachable!()
+        };
+        let ack = ack_success(&RestakeAck { tx_id: Some(tx_id) }).unwrap();
+        let msg =
+            mock_ibc_packet_ack("channel-172", &packet, IbcAcknowledgement::new(ack)).unwrap();
+        ibc_packet_ack(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(stake(&deps, &alice), ValueRange::new_val(Uint128::zero()));
+        assert_eq!(stake(&deps, &carl), ValueRange::new_val(Uint128::new(100)));
+
+        // The stake on a jailed validator can't be moved
+        receive(
+            &mut deps,
+            ConsumerPacket::JailValidators(vec![RemoveValidator {
+                valoper: bob.clone(),
+                height: 200,
+                time: 1687339542,
+            }]),
+        );
+        let err = restake(&mut deps, &bob).unwrap_err();
+        assert_eq!(err, ContractError::ValidatorNotActive(bob));
+    }
+
+    #[test]
+    fn validator_sync_response_is_merged() {
+        let mut deps = instantiate();
+        let contract = ExternalStakingContract::new();
+        let alice = valoper("alice");
+        let bob = valoper("bob");
+
+        let packet = ConsumerPacket::AddValidators(vec![AddValidator::mock(&alice)]);
+        let msg = mock_ibc_packet_recv("channel-172", &packet).unwrap();
+        ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+
+        // The sync response has the current height as start height of all validators
+        let env = mock_env();
+        let sync = |valoper: &str| AddValidator {
+            start_height: env.block.height,
+            ..AddValidator::mock(valoper)
+        };
+        let ack = ack_success(&RequestValidatorsAck {
+            validators: vec![sync(&alice), sync(&bob), sync("carl")],
+        })
+        .unwrap();
+        let msg = mock_ibc_packet_ack(
+            "channel-172",
+            &ProviderPacket::RequestValidators {},
+            IbcAcknowledgement::new(ack),
+        )
+        .unwrap();
+        let resp = ibc_packet_ack(deps.as_mut(), env.clone(), msg).unwrap();
+        assert_eq!(
+            resp.attributes[1].value,
+            "Rejected malformed validators: Invalid valoper address: carl"
+        );
+
+        // Missing validators are added, known ones are left untouched
+        let active = contract
+            .val_set
+            .list_active_validators(&deps.storage, None, 10)
+            .unwrap();
+        assert_eq!(active, [alice.clone(), bob.clone()]);
+        let update = contract
+            .val_set
+            .active_validator(&deps.storage, &alice)
+            .unwrap()
+            .unwrap();
+        assert_eq!(update.start_height, AddValidator::mock(&alice).start_height);
+        let update = contract
+            .val_set
+            .active_validator(&deps.storage, &bob)
+            .unwrap()
+            .unwrap();
+        assert_eq!(update.start_height, env.block.height);
+
+        let sync = contract
+            .last_validator_sync((deps.as_ref(), env.clone()).into())
+            .unwrap();
+        assert_eq!(sync.synced_at, Some(env.block.height));
+    }
+}
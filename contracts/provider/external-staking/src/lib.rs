@@ -1,12 +1,16 @@
 pub mod contract;
 pub mod crdt;
 pub mod error;
+pub mod evidence;
 pub mod ibc;
+mod migration;
 pub mod msg;
 #[cfg(test)]
 mod multitest;
-mod points_alignment;
 mod stakes;
 pub mod state;
+#[cfg(any(test, feature = "mt"))]
 pub mod test_methods;
+#[cfg(any(test, feature = "mt"))]
 pub mod test_methods_impl;
+mod txs;
@@ -0,0 +1,704 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Addr, Decimal, Order, StdResult, Storage, Uint128, Uint256};
+use cw_storage_plus::{Item, Map};
+use mesh_apis::vault_api::VaultApiHelper;
+use mesh_sync::{Tx, ValueRange};
+use std::cmp::min;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::contract::ExternalStakingContract;
+use crate::state::{
+    Config, Distribution, DustPolicy, PendingUnbond, RewardDenom, Stake, StakeRewards,
+};
+
+/// Config before multiple rewards denoms were supported
+#[cw_serde]
+struct ConfigV1 {
+    denom: String,
+    rewards_denom: String,
+    vault: VaultApiHelper,
+    unbonding_period: u64,
+    max_slashing: Decimal,
+}
+
+/// Stake before multiple rewards denoms were supported
+#[cw_serde]
+struct StakeV1 {
+    stake: ValueRange<Uint128>,
+    pending_unbonds: Vec<PendingUnbond>,
+    points_alignment: Uint256,
+    withdrawn_funds: Uint128,
+}
+
+/// Stake before the rewards were checkpointed
+#[cw_serde]
+struct StakeV2 {
+    stake: ValueRange<Uint128>,
+    pending_unbonds: Vec<PendingUnbond>,
+    rewards: BTreeMap<String, StakeRewardsV2>,
+}
+
+/// Points alignment of a stake, before the rewards were checkpointed. Shifted by
+/// `Uint256::MAX / 2` to be signed
+#[cw_serde]
+struct StakeRewardsV2 {
+    points_alignment: Uint256,
+    withdrawn_funds: Uint128,
+}
+
+/// Config before rewards denoms were split in base and trace
+#[cw_serde]
+struct ConfigV2 {
+    denom: String,
+    rewards_denoms: Vec<String>,
+    vault: VaultApiHelper,
+    unbonding_period: u64,
+    max_slashing: Decimal,
+    #[serde(default)]
+    min_remaining_stake: Option<Uint128>,
+    #[serde(default)]
+    dust_policy: DustPolicy,
+    #[serde(default)]
+    slash_redistribution: Option<Decimal>,
+    #[serde(default)]
+    evidence_bounty: Option<Decimal>,
+}
+
+const CONFIG_V1: Item<ConfigV1> = Item::new("config");
+const CONFIG_V2: Item<ConfigV2> = Item::new("config");
+const DISTRIBUTION_V1: Map<&str, Distribution> = Map::new("distribution");
+// Same namespace as the `stakes` indexed map. The validator index only depends on the keys, so it
+// doesn't need to be updated. The size index is rebuilt by `index_stakes_size`.
+const STAKES_V1: Map<(&Addr, &str), StakeV1> = Map::new("stakes");
+const STAKES_V2: Map<(&Addr, &str), StakeV2> = Map::new("stakes");
+const STAKES_V3: Map<(&Addr, &str), Stake> = Map::new("stakes");
+
+/// Moves the single rewards denom state to the multiple rewards denoms layout. Does nothing if
+/// the state is already migrated.
+pub(crate) fn migrate_rewards_denoms(
+    storage: &mut dyn Storage,
+    contract: &ExternalStakingContract,
+) -> StdResult<()> {
+    if contract.config.load(storage).is_ok() {
+        return Ok(());
+    }
+
+    let ConfigV1 {
+        denom,
+        rewards_denom,
+        vault,
+        unbonding_period,
+        max_slashing,
+    } = CONFIG_V1.load(storage)?;
+    let config = Config {
+        denom,
+        rewards_denoms: vec![RewardDenom::from_denom(&rewards_denom)],
+        retired_rewards_denoms: vec![],
+        swept_rewards_denoms: BTreeMap::new(),
+        vault,
+        unbonding_period,
+        max_slashing,
+        min_remaining_stake: None,
+        dust_policy: Default::default(),
+        slash_redistribution: None,
+        evidence_bounty: None,
+        consumer_chain_id: None,
+        max_tracked_validators: None,
+        relay_latency: None,
+        max_validators_per_user: None,
+    };
+    contract.config.save(storage, &config)?;
+
+    let distributions = DISTRIBUTION_V1
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for (validator, distribution) in distributions {
+        contract
+            .distribution
+            .save(storage, (&validator, &rewards_denom), &distribution)?;
+        DISTRIBUTION_V1.remove(storage, &validator);
+    }
+
+    let stakes = STAKES_V1
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for ((user, validator), stake) in stakes {
+        let rewards = StakeRewardsV2 {
+            points_alignment: stake.points_alignment,
+            withdrawn_funds: stake.withdrawn_funds,
+        };
+        let stake = StakeV2 {
+            stake: stake.stake,
+            pending_unbonds: stake.pending_unbonds,
+            rewards: BTreeMap::from([(rewards_denom.clone(), rewards)]),
+        };
+        STAKES_V2.save(storage, (&user, &validator), &stake)?;
+    }
+
+    let txs = contract
+        .pending_txs
+        .txs
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for (id, mut tx) in txs {
+        if let Tx::InFlightTransferFunds { denom, .. } = &mut tx {
+            *denom = rewards_denom.clone();
+            contract.pending_txs.save(storage, id, &tx)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Replaces the points alignments of the stakes with rewards checkpoints, keeping the rewards
+/// earned so far. Stakes already checkpointed don't parse in the previous layout, and are left
+/// unchanged.
+pub(crate) fn migrate_rewards_checkpoints(
+    storage: &mut dyn Storage,
+    contract: &ExternalStakingContract,
+) -> StdResult<()> {
+    let stakes: Vec<_> = STAKES_V2
+        .range(storage, None, None, Order::Ascending)
+        .filter_map(Result::ok)
+        .collect();
+    if stakes.is_empty() {
+        return Ok(());
+    }
+
+    // Pending unstakes are still counted in the validators distributions
+    let mut unstaking: BTreeMap<(Addr, String), Uint128> = BTreeMap::new();
+    for item in contract
+        .pending_txs
+        .txs
+        .range(storage, None, None, Order::Ascending)
+    {
+        let (_, tx) = item?;
+        match tx {
+            Tx::InFlightRemoteUnstaking {
+                user,
+                validator,
+                amount,
+                ..
+            } => *unstaking.entry((user, validator)).or_default() += amount,
+            Tx::InFlightRemoteUnstakingBatch { user, unstakes, .. } => {
+                for (validator, amount) in unstakes {
+                    *unstaking.entry((user.clone(), validator)).or_default() += amount;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let zero_alignment = Uint256::MAX >> 1;
+    for ((user, validator), stake) in stakes {
+        let pending = unstaking
+            .get(&(user.clone(), validator.clone()))
+            .copied()
+            .unwrap_or_default();
+        let rewards_stake = min(stake.stake.low() + pending, stake.stake.high());
+
+        let mut rewards = BTreeMap::new();
+        for (denom, old) in stake.rewards {
+            let points_per_stake = contract
+                .distribution
+                .may_load(storage, (&validator, &denom))?
+                .unwrap_or_default()
+                .points_per_stake;
+            let points = points_per_stake * Uint256::from(rewards_stake);
+            let points = if old.points_alignment >= zero_alignment {
+                points + (old.points_alignment - zero_alignment)
+            } else {
+                points.saturating_sub(zero_alignment - old.points_alignment)
+            };
+            let checkpoint = StakeRewards {
+                reward_index: points_per_stake,
+                points,
+                withdrawn_funds: old.withdrawn_funds,
+            };
+            rewards.insert(denom, checkpoint);
+        }
+
+        let stake = Stake {
+            stake: stake.stake,
+            pending_unbonds: stake.pending_unbonds,
+            rewards_stake,
+            rewards,
+        };
+        STAKES_V3.save(storage, (&user, &validator), &stake)?;
+    }
+
+    Ok(())
+}
+
+/// Splits the rewards denoms of the config in base and trace. The full denoms are unchanged, so
+/// the rewards accounting doesn't need to be migrated. Does nothing if the config is not in the
+/// flat rewards denoms layout.
+pub(crate) fn migrate_structured_rewards_denoms(
+    storage: &mut dyn Storage,
+    contract: &ExternalStakingContract,
+) -> StdResult<()> {
+    let config = match CONFIG_V2.load(storage) {
+        Ok(config) => config,
+        Err(_) => return Ok(()),
+    };
+
+    let config = Config {
+        denom: config.denom,
+        rewards_denoms: config
+            .rewards_denoms
+            .iter()
+            .map(|denom| RewardDenom::from_denom(denom))
+            .collect(),
+        retired_rewards_denoms: vec![],
+        swept_rewards_denoms: BTreeMap::new(),
+        vault: config.vault,
+        unbonding_period: config.unbonding_period,
+        max_slashing: config.max_slashing,
+        min_remaining_stake: config.min_remaining_stake,
+        dust_policy: config.dust_policy,
+        slash_redistribution: config.slash_redistribution,
+        evidence_bounty: config.evidence_bounty,
+        consumer_chain_id: None,
+        max_tracked_validators: None,
+        relay_latency: None,
+        max_validators_per_user: None,
+    };
+    contract.config.save(storage, &config)
+}
+
+/// Indexes the stakes by size, re-saving them. Safe to call again once indexed.
+pub(crate) fn index_stakes_size(
+    storage: &mut dyn Storage,
+    contract: &ExternalStakingContract,
+) -> StdResult<()> {
+    let stakes = contract
+        .stakes
+        .stake
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for ((user, validator), stake) in stakes {
+        contract
+            .stakes
+            .stake
+            .save(storage, (&user, &validator), &stake)?;
+    }
+    Ok(())
+}
+
+/// Counts the validators each user has a stake entry on. Safe to call again, the counts are
+/// recalculated from the stakes.
+pub(crate) fn count_user_validators(
+    storage: &mut dyn Storage,
+    contract: &ExternalStakingContract,
+) -> StdResult<()> {
+    let mut counts: BTreeMap<Addr, u32> = BTreeMap::new();
+    for key in contract
+        .stakes
+        .stake
+        .keys(storage, None, None, Order::Ascending)
+    {
+        let (user, _) = key?;
+        *counts.entry(user).or_default() += 1;
+    }
+    for (user, count) in counts {
+        contract.validators_count.save(storage, &user, &count)?;
+    }
+    Ok(())
+}
+
+/// Initializes the stats maintained along the stakes. Safe to call again, they are recalculated
+/// from the stakes. Rewards distributed before can't be recalculated, so they are only counted
+/// from the first migration on.
+pub(crate) fn init_stats(
+    storage: &mut dyn Storage,
+    contract: &ExternalStakingContract,
+) -> StdResult<()> {
+    let mut stakers: BTreeSet<Addr> = BTreeSet::new();
+    let mut validator_stakers: BTreeMap<String, u32> = BTreeMap::new();
+    let mut total_stake = Uint128::zero();
+    let mut total_pending_unbonds = Uint128::zero();
+    for item in contract
+        .stakes
+        .stake
+        .range(storage, None, None, Order::Ascending)
+    {
+        let ((user, validator), stake) = item?;
+        stakers.insert(user);
+        *validator_stakers.entry(validator).or_default() += 1;
+        total_stake += stake.rewards_stake;
+        total_pending_unbonds += stake
+            .pending_unbonds
+            .iter()
+            .map(|unbond| unbond.amount)
+            .sum::<Uint128>();
+    }
+
+    for (validator, count) in &validator_stakers {
+        contract.validator_stakers.save(storage, validator, count)?;
+    }
+    contract
+        .total_stakers
+        .save(storage, &(stakers.len() as u64))?;
+    contract
+        .total_validators
+        .save(storage, &(validator_stakers.len() as u64))?;
+    contract.total_stake.save(storage, &total_stake)?;
+    contract
+        .total_pending_unbonds
+        .save(storage, &total_pending_unbonds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cosmwasm_std::testing::MockStorage;
+    use cosmwasm_std::{coin, Timestamp};
+
+    use crate::contract::DISTRIBUTION_POINTS_SCALE;
+
+    #[test]
+    fn single_rewards_denom_state_is_migrated() {
+        let mut storage = MockStorage::new();
+        let contract = ExternalStakingContract::new();
+        let user = Addr::unchecked("user");
+
+        CONFIG_V1
+            .save(
+                &mut storage,
+                &ConfigV1 {
+                    denom: "osmo".to_owned(),
+                    rewards_denom: "star".to_owned(),
+                    vault: VaultApiHelper(Addr::unchecked("vault")),
+                    unbonding_period: 100,
+                    max_slashing: Decimal::percent(10),
+                },
+            )
+            .unwrap();
+        let distribution = Distribution {
+            total_stake: Uint128::new(100),
+            points_per_stake: Uint256::from(3u128) * DISTRIBUTION_POINTS_SCALE,
+            points_leftover: Uint256::zero(),
+        };
+        DISTRIBUTION_V1
+            .save(&mut storage, "alice", &distribution)
+            .unwrap();
+        STAKES_V1
+            .save(
+                &mut storage,
+                (&user, "alice"),
+                &StakeV1 {
+                    stake: ValueRange::new_val(Uint128::new(100)),
+                    pending_unbonds: vec![],
+                    points_alignment: Uint256::MAX >> 1,
+                    withdrawn_funds: Uint128::new(120),
+                },
+            )
+            .unwrap();
+        // Pending withdrawal created before the migration
+        let tx: Tx = cosmwasm_std::from_slice(
+            br#"{"in_flight_transfer_funds":{"id":1,"amount":"50","staker":"user","validator":"alice"}}"#,
+        )
+        .unwrap();
+        contract.pending_txs.save(&mut storage, 1, &tx).unwrap();
+
+        migrate_rewards_denoms(&mut storage, &contract).unwrap();
+        migrate_rewards_checkpoints(&mut storage, &contract).unwrap();
+
+        let config = contract.config.load(&storage).unwrap();
+        assert_eq!(config.rewards_denoms, [RewardDenom::from_denom("star")]);
+        assert_eq!(
+            contract
+                .distribution
+                .load(&storage, ("alice", "star"))
+                .unwrap(),
+            distribution
+        );
+        assert!(DISTRIBUTION_V1
+            .may_load(&storage, "alice")
+            .unwrap()
+            .is_none());
+
+        let stake = contract
+            .stakes
+            .stake
+            .load(&storage, (&user, "alice"))
+            .unwrap();
+        assert_eq!(stake.rewards["star"].withdrawn_funds, Uint128::new(120));
+        // 300 earned, 120 withdrawn
+        let rewards = contract
+            .calculate_rewards(&storage, &config, "alice", &stake)
+            .unwrap();
+        assert_eq!(rewards, [coin(180, "star")]);
+
+        match contract.pending_txs.txs.load(&storage, 1).unwrap() {
+            Tx::InFlightTransferFunds { denom, .. } => assert_eq!(denom, "star"),
+            tx => panic!("unexpected tx {}", tx),
+        }
+
+        // Migrating again is a no-op
+        migrate_rewards_denoms(&mut storage, &contract).unwrap();
+        assert_eq!(contract.config.load(&storage).unwrap(), config);
+    }
+
+    #[test]
+    fn alignments_are_checkpointed() {
+        let mut storage = MockStorage::new();
+        let contract = ExternalStakingContract::new();
+        let user1 = Addr::unchecked("user1");
+        let user2 = Addr::unchecked("user2");
+
+        let config = Config {
+            denom: "osmo".to_owned(),
+            rewards_denoms: vec![RewardDenom::from_denom("star")],
+            retired_rewards_denoms: vec![],
+            swept_rewards_denoms: BTreeMap::new(),
+            vault: VaultApiHelper(Addr::unchecked("vault")),
+            unbonding_period: 100,
+            max_slashing: Decimal::percent(10),
+            min_remaining_stake: None,
+            dust_policy: Default::default(),
+            slash_redistribution: None,
+            evidence_bounty: None,
+            consumer_chain_id: None,
+            max_tracked_validators: None,
+            relay_latency: None,
+            max_validators_per_user: None,
+        };
+        contract.config.save(&mut storage, &config).unwrap();
+        let distribution = Distribution {
+            total_stake: Uint128::new(150),
+            points_per_stake: Uint256::from(3u128) * DISTRIBUTION_POINTS_SCALE,
+            points_leftover: Uint256::zero(),
+        };
+        contract
+            .distribution
+            .save(&mut storage, ("alice", "star"), &distribution)
+            .unwrap();
+
+        // Staked 100 at 1 point per stake, so earning 200 since
+        let alignment = (Uint256::MAX >> 1) - Uint256::from(100u128) * DISTRIBUTION_POINTS_SCALE;
+        let stake1 = StakeV2 {
+            stake: ValueRange::new_val(Uint128::new(100)),
+            pending_unbonds: vec![],
+            rewards: BTreeMap::from([(
+                "star".to_owned(),
+                StakeRewardsV2 {
+                    points_alignment: alignment,
+                    withdrawn_funds: Uint128::new(50),
+                },
+            )]),
+        };
+        STAKES_V2
+            .save(&mut storage, (&user1, "alice"), &stake1)
+            .unwrap();
+        // Staked 50 before the first distribution, unstaking 20
+        let stake2 = StakeV2 {
+            stake: ValueRange::new(Uint128::new(30), Uint128::new(50)),
+            pending_unbonds: vec![],
+            rewards: BTreeMap::new(),
+        };
+        STAKES_V2
+            .save(&mut storage, (&user2, "alice"), &stake2)
+            .unwrap();
+        let tx = Tx::InFlightRemoteUnstaking {
+            id: 1,
+            amount: Uint128::new(20),
+            user: user2.clone(),
+            validator: "alice".to_owned(),
+            created_at: Default::default(),
+        };
+        contract.pending_txs.save(&mut storage, 1, &tx).unwrap();
+
+        migrate_rewards_checkpoints(&mut storage, &contract).unwrap();
+        // Migrating again is a no-op
+        migrate_rewards_checkpoints(&mut storage, &contract).unwrap();
+
+        let stake = contract
+            .stakes
+            .stake
+            .load(&storage, (&user1, "alice"))
+            .unwrap();
+        assert_eq!(stake.rewards_stake, Uint128::new(100));
+        assert_eq!(
+            stake.rewards["star"],
+            StakeRewards {
+                reward_index: distribution.points_per_stake,
+                points: Uint256::from(200u128) * DISTRIBUTION_POINTS_SCALE,
+                withdrawn_funds: Uint128::new(50),
+            }
+        );
+        let rewards = contract
+            .calculate_rewards(&storage, &config, "alice", &stake)
+            .unwrap();
+        assert_eq!(rewards, [coin(150, "star")]);
+
+        // The pending unstake still earns rewards, as it is counted in the distribution
+        let stake = contract
+            .stakes
+            .stake
+            .load(&storage, (&user2, "alice"))
+            .unwrap();
+        assert_eq!(stake.rewards_stake, Uint128::new(50));
+        let rewards = contract
+            .calculate_rewards(&storage, &config, "alice", &stake)
+            .unwrap();
+        assert_eq!(rewards, [coin(150, "star")]);
+    }
+
+    #[test]
+    fn flat_rewards_denoms_are_split() {
+        let mut storage = MockStorage::new();
+        let contract = ExternalStakingContract::new();
+
+        CONFIG_V2
+            .save(
+                &mut storage,
+                &ConfigV2 {
+                    denom: "osmo".to_owned(),
+                    rewards_denoms: vec!["star".to_owned(), "transfer/channel-3/uatom".to_owned()],
+                    vault: VaultApiHelper(Addr::unchecked("vault")),
+                    unbonding_period: 100,
+                    max_slashing: Decimal::percent(10),
+                    min_remaining_stake: None,
+                    dust_policy: DustPolicy::FullUnstake,
+                    slash_redistribution: None,
+                    evidence_bounty: Some(Decimal::percent(5)),
+                },
+            )
+            .unwrap();
+
+        migrate_structured_rewards_denoms(&mut storage, &contract).unwrap();
+
+        let config = contract.config.load(&storage).unwrap();
+        assert_eq!(
+            config.rewards_denoms,
+            [
+                RewardDenom {
+                    base: "star".to_owned(),
+                    trace: None,
+                },
+                RewardDenom {
+                    base: "uatom".to_owned(),
+                    trace: Some("transfer/channel-3".to_owned()),
+                },
+            ]
+        );
+        assert_eq!(
+            config.held_rewards_denoms(),
+            ["star", "transfer/channel-3/uatom"]
+        );
+        assert_eq!(config.dust_policy, DustPolicy::FullUnstake);
+        assert_eq!(config.evidence_bounty, Some(Decimal::percent(5)));
+
+        // Migrating again is a no-op
+        migrate_structured_rewards_denoms(&mut storage, &contract).unwrap();
+        assert_eq!(contract.config.load(&storage).unwrap(), config);
+    }
+
+    #[test]
+    fn stakes_size_is_indexed() {
+        let mut storage = MockStorage::new();
+        let contract = ExternalStakingContract::new();
+        let user = Addr::unchecked("user");
+
+        // Stakes stored before the size index
+        for (validator, amount) in [("alice", 100), ("bob", 300)] {
+            STAKES_V3
+                .save(
+                    &mut storage,
+                    (&user, validator),
+                    &Stake::from_amount(Uint128::new(amount)),
+                )
+                .unwrap();
+        }
+
+        index_stakes_size(&mut storage, &contract).unwrap();
+        // Indexing again doesn't duplicate the entries
+        index_stakes_size(&mut storage, &contract).unwrap();
+
+        let indexed: Vec<_> = contract
+            .stakes
+            .stake
+            .idx
+            .size
+            .sub_prefix(user.clone())
+            .keys(&storage, None, None, Order::Descending)
+            .collect::<StdResult<_>>()
+            .unwrap();
+        assert_eq!(
+            indexed,
+            [(user.clone(), "bob".to_owned()), (user, "alice".to_owned())]
+        );
+    }
+
+    #[test]
+    fn user_validators_are_counted() {
+        let mut storage = MockStorage::new();
+        let contract = ExternalStakingContract::new();
+        let alice = Addr::unchecked("alice");
+        let bob = Addr::unchecked("bob");
+
+        // Stakes stored before the counts
+        for (user, validator) in [(&alice, "val1"), (&alice, "val2"), (&bob, "val1")] {
+            STAKES_V3
+                .save(
+                    &mut storage,
+                    (user, validator),
+                    &Stake::from_amount(Uint128::new(100)),
+                )
+                .unwrap();
+        }
+
+        count_user_validators(&mut storage, &contract).unwrap();
+        // Counting again doesn't count the stakes twice
+        count_user_validators(&mut storage, &contract).unwrap();
+
+        assert_eq!(contract.validators_count.load(&storage, &alice).unwrap(), 2);
+        assert_eq!(contract.validators_count.load(&storage, &bob).unwrap(), 1);
+    }
+
+    #[test]
+    fn stats_are_initialized() {
+        let mut storage = MockStorage::new();
+        let contract = ExternalStakingContract::new();
+        let alice = Addr::unchecked("alice");
+        let bob = Addr::unchecked("bob");
+
+        // Stakes stored before the stats
+        let mut unbonding = Stake::from_amount(Uint128::new(50));
+        unbonding.add_pending_unbond(PendingUnbond {
+            amount: Uint128::new(30),
+            requested_at: Timestamp::from_seconds(1),
+            release_at: Timestamp::from_seconds(2),
+        });
+        for (user, validator, stake) in [
+            (&alice, "val1", Stake::from_amount(Uint128::new(100))),
+            (&alice, "val2", unbonding),
+            (&bob, "val1", Stake::from_amount(Uint128::new(20))),
+        ] {
+            STAKES_V3
+                .save(&mut storage, (user, validator), &stake)
+                .unwrap();
+        }
+
+        init_stats(&mut storage, &contract).unwrap();
+        // Initializing again doesn't count the stakes twice
+        init_stats(&mut storage, &contract).unwrap();
+
+        assert_eq!(contract.total_stakers.load(&storage).unwrap(), 2);
+        assert_eq!(contract.total_validators.load(&storage).unwrap(), 2);
+        assert_eq!(
+            contract.validator_stakers.load(&storage, "val1").unwrap(),
+            2
+        );
+        assert_eq!(
+            contract.total_stake.load(&storage).unwrap(),
+            Uint128::new(170)
+        );
+        assert_eq!(
+            contract.total_pending_unbonds.load(&storage).unwrap(),
+            Uint128::new(30)
+        );
+    }
+}
@@ -1,7 +1,9 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{coin, Coin, IbcChannel};
+use cosmwasm_std::{coin, Coin, Decimal, IbcChannel, Timestamp, Uint128, Uint256};
+use mesh_sync::ValueRange;
+use std::collections::BTreeMap;
 
-use crate::state::Stake;
+use crate::state::{DustPolicy, RewardDenom, Stake, UnbondListing};
 use crate::{error::ContractError, state::Config};
 
 #[cw_serde]
@@ -34,26 +36,107 @@ pub struct IbcChannelResponse {
     pub channel: IbcChannel,
 }
 
+/// State of a remote validator
+#[cw_serde]
+pub enum ValidatorStatus {
+    /// Accepting new stakes
+    Active {},
+    /// Not accepting new stakes until unjailed. Jailed at the `since` height
+    Jailed { since: u64 },
+    /// Removed for good at `height`
+    Tombstoned { height: u64 },
+}
+
+#[cw_serde]
+pub struct ValidatorResponse {
+    pub valoper: String,
+    /// `None` if the validator is unknown
+    pub status: Option<ValidatorStatus>,
+}
+
 #[cw_serde]
 pub struct ListRemoteValidatorsResponse {
     pub validators: Vec<String>,
 }
 
+/// Progress of the valset sync
+#[cw_serde]
+pub struct ValsetSyncResponse {
+    /// Validators received from the consumer, not yet added to the valset
+    pub pending: u32,
+}
+
+/// Whether the valset was synced with the consumer
+#[cw_serde]
+pub struct SyncStatusResponse {
+    pub synced: bool,
+}
+
+/// Contract-wide statistics
+#[cw_serde]
+pub struct StatsResponse {
+    /// Number of users with a stake entry
+    pub total_stakers: u64,
+    /// Number of validators with a stake entry
+    pub total_validators: u64,
+    /// Virtual stake of all the users, including the unstakes until they are committed
+    pub total_stake: Uint128,
+    /// Tokens waiting for their unbonding period
+    pub total_pending_unbonds: Uint128,
+    /// Rewards distributed to the stakers so far, per rewards denom
+    pub rewards_distributed: Vec<Coin>,
+    /// Redistributed slashes credited to the stakers so far, in the collateral denom
+    pub slashes_redistributed: Uint128,
+}
+
+/// Heights of the last validator sync with the consumer, and of the last request for one
+#[cw_serde]
+pub struct LastValidatorSyncResponse {
+    /// Height at which the last consumer validator set was received, if any
+    pub synced_at: Option<u64>,
+    /// Height of the last `request_validator_sync` call, if any
+    pub requested_at: Option<u64>,
+}
+
 /// Config information returned with query
 #[cw_serde]
 pub struct ConfigResponse {
     pub denom: String,
+    pub rewards_denoms: Vec<RewardDenom>,
+    pub retired_rewards_denoms: Vec<String>,
+    /// Rewards denoms swept from a former trace, to the denom they are still accounted in
+    pub swept_rewards_denoms: BTreeMap<String, String>,
     pub vault: String,
     /// In seconds
     pub unbonding_period: u64,
+    pub min_remaining_stake: Option<Uint128>,
+    pub dust_policy: DustPolicy,
+    pub slash_redistribution: Option<Decimal>,
+    pub evidence_bounty: Option<Decimal>,
+    pub consumer_chain_id: Option<String>,
+    pub max_tracked_validators: Option<u32>,
+    /// In seconds
+    pub relay_latency: Option<u64>,
+    pub max_validators_per_user: Option<u32>,
 }
 
 impl From<Config> for ConfigResponse {
     fn from(value: Config) -> Self {
         Self {
             denom: value.denom,
+            rewards_denoms: value.rewards_denoms,
+            retired_rewards_denoms: value.retired_rewards_denoms,
+            swept_rewards_denoms: value.swept_rewards_denoms,
             vault: value.vault.0.into(),
             unbonding_period: value.unbonding_period,
+            min_remaining_stake: value.min_remaining_stake,
+            dust_policy: value.dust_policy,
+            slash_redistribution: value.slash_redistribution,
+            evidence_bounty: value.evidence_bounty,
+            consumer_chain_id: value.consumer_chain_id,
+            max_tracked_validators: value.max_tracked_validators,
+            relay_latency: value.relay_latency,
+            max_validators_per_user: value.max_validators_per_user,
         }
     }
 }
@@ -76,10 +159,97 @@ impl StakeInfo {
     }
 }
 
+/// Ordering of the `stakes` query
+#[cw_serde]
+#[derive(Default)]
+pub enum StakesOrderBy {
+    #[default]
+    Validator,
+    /// By decreasing stake, then decreasing validator
+    StakeDesc,
+}
+
 /// Aggregated multiple stakes response
 #[cw_serde]
 pub struct StakesResponse {
     pub stakes: Vec<StakeInfo>,
+    /// Time of the user's first stake, if tracked
+    pub first_stake_at: Option<Timestamp>,
+    /// Time of the user's last stake, unstake or rewards withdrawal, if tracked
+    pub last_activity_at: Option<Timestamp>,
+}
+
+/// Unstake not committed yet, with its estimated release time
+#[cw_serde]
+pub struct EstimatedRelease {
+    pub tx_id: u64,
+    pub amount: Uint128,
+    /// Time the unstake was submitted at
+    pub requested_at: Timestamp,
+    /// Estimated time the tokens are released at, if the unstake is committed after the typical
+    /// relay latency from now
+    pub release_at: Timestamp,
+}
+
+#[cw_serde]
+pub struct EstimatedReleaseResponse {
+    pub unstakes: Vec<EstimatedRelease>,
+}
+
+/// Worst case value of a stake, if slashed by the max slashing ratio
+#[cw_serde]
+pub struct NetPositionResponse {
+    /// Stake, excluding the pending stakes
+    pub stake: Uint128,
+    pub max_slash: Decimal,
+    /// `stake * (1 - max_slash)`, rounded down
+    pub worst_case: Uint128,
+}
+
+/// Pending unbond of a user, along with the validator it is unbonded from
+#[cw_serde]
+pub struct ScheduledUnlock {
+    pub validator: String,
+    pub amount: Uint128,
+    /// Time when tokens are released
+    pub release_at: Timestamp,
+}
+
+#[cw_serde]
+pub struct UnlockScheduleResponse {
+    /// Sorted by release time
+    pub unlocks: Vec<ScheduledUnlock>,
+}
+
+/// Slash of a validator, at the height it was processed at
+#[cw_serde]
+pub struct ValidatorSlash {
+    pub height: u64,
+    /// Part of the stakes slashed
+    pub ratio: Decimal,
+    /// Slashed amount, including the slashed pending unbonds
+    pub amount: Uint128,
+}
+
+#[cw_serde]
+pub struct ValidatorSlashesResponse {
+    /// Sorted by height
+    pub slashes: Vec<ValidatorSlash>,
+}
+
+/// Stake with pending changes, and the pending tx changing it if any
+#[cw_serde]
+pub struct LockedStake {
+    pub owner: String,
+    pub validator: String,
+    pub stake: ValueRange<Uint128>,
+    /// None if no pending tx references the stake anymore, so it is locked for good
+    pub tx_id: Option<u64>,
+}
+
+#[cw_serde]
+pub struct LockedStakesResponse {
+    pub stakes: Vec<LockedStake>,
 }
 
 /// Message to be sent as `msg` field on `receive_virtual_staking`
@@ -103,7 +273,8 @@ pub struct UsersResponse {
 /// Response for pending rewards query on one validator
 #[cw_serde]
 pub struct PendingRewards {
-    pub rewards: Coin,
+    /// Pending rewards in each of the rewards denoms
+    pub rewards: Vec<Coin>,
 }
 
 /// Response for pending rewards query on all validator
@@ -112,6 +283,16 @@ pub struct AllPendingRewards {
     pub rewards: Vec<ValidatorPendingRewards>,
 }
 
+/// Response for the rewards withdrawn by a user, summed over a page of validators
+#[cw_serde]
+pub struct TotalWithdrawnResponse {
+    /// Rewards withdrawn on the page validators, per rewards denom
+    pub withdrawn: Vec<Coin>,
+    /// Last validator of the page, to be passed as `start_after` to sum the next page. None once
+    /// all the validators are summed
+    pub cursor: Option<String>,
+}
+
 #[cw_serde]
 pub struct ValidatorPendingRewards {
     pub validator: String,
@@ -123,15 +304,50 @@ impl ValidatorPendingRewards {
         Self {
             validator: validator.into(),
             rewards: PendingRewards {
-                rewards: coin(amount, denom),
+                rewards: vec![coin(amount, denom)],
             },
         }
     }
 }
 
-pub type TxResponse = mesh_sync::Tx;
+pub type TxResponse = mesh_sync::TxStatus;
 
 #[cw_serde]
 pub struct AllTxsResponse {
-    pub txs: Vec<TxResponse>,
+    pub txs: Vec<mesh_sync::Tx>,
+}
+
+#[cw_serde]
+pub struct TxsHistoryResponse {
+    pub txs: Vec<mesh_sync::TxResult>,
+}
+
+/// Pending unbonds of an user offered for sale
+#[cw_serde]
+pub struct UnbondListingsResponse {
+    pub listings: Vec<UnbondListing>,
+}
+
+/// Raw rewards accumulators of a stake, for debugging the rewards math
+#[cw_serde]
+pub struct RewardDebug {
+    pub stake: ValueRange<Uint128>,
+    /// Stake counted in the validator distributions, including the pending unstakes
+    pub rewards_stake: Uint128,
+    /// Accumulators per rewards denom
+    pub denoms: Vec<DenomRewardDebug>,
+}
+
+#[cw_serde]
+pub struct DenomRewardDebug {
+    pub denom: String,
+    /// Validator distribution points per staked token
+    pub points_per_stake: Uint256,
+    /// Validator distribution points not distributed yet
+    pub points_leftover: Uint256,
+    /// Validator points per staked token at the stake checkpoint
+    pub reward_index: Uint256,
+    /// Points earned by the stake up to its checkpoint
+    pub points: Uint256,
+    pub withdrawn_funds: Uint128,
 }
@@ -2,23 +2,33 @@ mod utils;
 
 use anyhow::Result as AnyResult;
 
-use cosmwasm_std::{coin, coins, to_binary, Decimal, Uint128};
+use cosmwasm_std::{coin, coins, to_binary, Addr, Decimal, Uint128, Uint256};
+use cw_utils::PaymentError;
 use mesh_native_staking::contract::multitest_utils::CodeId as NativeStakingCodeId;
 use mesh_native_staking::contract::InstantiateMsg as NativeStakingInstantiateMsg;
 use mesh_native_staking_proxy::contract::multitest_utils::CodeId as NativeStakingProxyCodeId;
 use mesh_vault::contract::multitest_utils::{CodeId as VaultCodeId, VaultContractProxy};
 use mesh_vault::msg::StakingInitInfo;
+use mesh_vault::state::CollateralType;
 
-use mesh_sync::ValueRange;
+use mesh_apis::cross_staking_api::CanStakeResponse;
+use mesh_apis::ibc::AddValidator;
+use mesh_sync::{Tx, TxResult, TxStatus, ValueRange};
+use std::collections::BTreeMap;
 
-use cw_multi_test::App as MtApp;
+use cw_multi_test::{App as MtApp, Executor};
 use sylvia::multitest::App;
 
 use crate::contract::cross_staking::test_utils::CrossStakingApi;
 use crate::contract::multitest_utils::{CodeId, ExternalStakingContractProxy};
+use crate::contract::{DEFAULT_MAX_VALIDATORS_PER_USER, VALIDATOR_SYNC_INTERVAL};
 use crate::error::ContractError;
-use crate::msg::{AuthorizedEndpoint, ReceiveVirtualStake, StakeInfo, ValidatorPendingRewards};
-use crate::state::Stake;
+use crate::msg::{
+    AuthorizedEndpoint, EstimatedRelease, LockedStake, NetPositionResponse, PendingRewards,
+    ReceiveVirtualStake, ScheduledUnlock, StakeInfo, StakesOrderBy, StatsResponse,
+    ValidatorPendingRewards, ValidatorSlash,
+};
+use crate::state::{DustPolicy, PendingUnbond, RewardDenom, Stake, UserMeta};
 use crate::test_methods_impl::test_utils::TestMethods;
 use utils::{
     assert_rewards, get_last_external_staking_pending_tx_id, AppExt as _, ContractExt as _,
@@ -27,6 +37,7 @@ use utils::{
 
 const OSMO: &str = "osmo";
 const STAR: &str = "star";
+const ATOM: &str = "atom";
 
 /// 10% slashing on the remote chain
 const SLASHING_PERCENTAGE: u64 = 10;
@@ -43,6 +54,18 @@ fn setup<'app>(
 ) -> AnyResult<(
     VaultContractProxy<'app, MtApp>,
     ExternalStakingContractProxy<'app, MtApp>,
+)> {
+    setup_with_rewards_denoms(app, owner, unbond_period, &[STAR])
+}
+
+fn setup_with_rewards_denoms<'app>(
+    app: &'app App<MtApp>,
+    owner: &str,
+    unbond_period: u64,
+    rewards_denoms: &[&str],
+) -> AnyResult<(
+    VaultContractProxy<'app, MtApp>,
+    ExternalStakingContractProxy<'app, MtApp>,
 )> {
     let native_staking_proxy_code = NativeStakingProxyCodeId::store_code(app);
     let native_staking_code = NativeStakingCodeId::store_code(app);
@@ -53,6 +76,7 @@ fn setup<'app>(
         denom: OSMO.to_owned(),
         proxy_code_id: native_staking_proxy_code.code_id(),
         max_slashing: Decimal::percent(LOCAL_SLASHING_PERCENTAGE),
+        default_validator: None,
     };
 
     let staking_init = StakingInitInfo {
@@ -63,7 +87,7 @@ fn setup<'app>(
     };
 
     let vault = vault_code
-        .instantiate(OSMO.to_owned(), staking_init)
+        .instantiate(CollateralType::Native(OSMO.to_owned()), staking_init, None)
         .call(owner)?;
 
     let remote_contact = AuthorizedEndpoint::new("connection-2", "wasm-osmo1foobarbaz");
@@ -71,12 +95,16 @@ fn setup<'app>(
     let contract = contract_code
         .instantiate(
             OSMO.to_owned(),
-            STAR.to_owned(),
+            rewards_denoms
+                .iter()
+                .map(|d| RewardDenom::from_denom(d))
+                .collect(),
             vault.contract_addr.to_string(),
             unbond_period,
             remote_contact,
             Decimal::percent(SLASHING_PERCENTAGE),
         )
+        .with_admin(owner)
         .call(owner)?;
 
     Ok((vault, contract))
@@ -91,7 +119,9 @@ fn instantiate() {
 
     let (_, contract) = setup(&app, owner, 100).unwrap();
 
-    let stakes = contract.stakes(users[0].to_owned(), None, None).unwrap();
+    let stakes = contract
+        .stakes(users[0].to_owned(), None, None, None, None, None)
+        .unwrap();
     assert_eq!(stakes.stakes, []);
 
     let max_slash = contract.cross_staking_api_proxy().max_slash().unwrap();
@@ -123,23 +153,6 @@ fn staking() {
         .call(users[1])
         .unwrap();
 
-    /*
-    // Fail to stake on non-registered validator
-    let msg = to_binary(&ReceiveVirtualStake {
-        validator: "unknown".to_string(),
-    })
-    .unwrap();
-    println!("START");
-    // FIXME: Sylvia panics here, with this line in ExecProxy::call
-    //             .map_err(|err| err.downcast().unwrap())
-    // Note that the error didn't happen in vault, but in a SubMsg, so this should be some StdError not ContractError...
-    let res = vault
-        .stake_remote(contract.contract_addr.to_string(), coin(100, OSMO), msg)
-        .call(users[0]);
-    println!("GOT: {:?}", res);
-    assert!(res.is_err());
-    */
-
     vault.stake(&contract, users[0], validators[0], coin(100, OSMO));
     vault.stake(&contract, users[0], validators[1], coin(100, OSMO));
     vault.stake(&contract, users[0], validators[0], coin(100, OSMO));
@@ -186,7 +199,9 @@ fn staking() {
     assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(200)));
 
     // Querying fo all the stakes
-    let stakes = contract.stakes(users[0].to_owned(), None, None).unwrap();
+    let stakes = contract
+        .stakes(users[0].to_owned(), None, None, None, None, None)
+        .unwrap();
     assert_eq!(
         stakes.stakes,
         [
@@ -195,7 +210,9 @@ fn staking() {
         ]
     );
 
-    let stakes = contract.stakes(users[1].to_owned(), None, None).unwrap();
+    let stakes = contract
+        .stakes(users[1].to_owned(), None, None, None, None, None)
+        .unwrap();
     assert_eq!(
         stakes.stakes,
         [
@@ -205,6 +222,235 @@ fn staking() {
     );
 }
 
+#[test]
+fn staking_unknown_validator() {
+    let user = "user1";
+    let owner = "owner";
+
+    let app = App::new_with_balances(&[(user, &coins(300, OSMO))]);
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+    contract.activate_validators(["validator1"]);
+
+    vault
+        .bond()
+        .with_funds(&coins(300, OSMO))
+        .call(user)
+        .unwrap();
+
+    // Staking to a validator never announced by the consumer fails, along with the whole vault
+    // tx. The proxy can't downcast errors of a sub-message, so the message is executed directly
+    let msg = mesh_vault::contract::ExecMsg::StakeRemote {
+        contract: contract.contract_addr.to_string(),
+        amount: coin(100, OSMO),
+        msg: to_binary(&ReceiveVirtualStake {
+            validator: "unknown".to_owned(),
+        })
+        .unwrap(),
+        idempotency_key: None,
+    };
+    let err = app
+        .app_mut()
+        .execute_contract(
+            Addr::unchecked(user),
+            vault.contract_addr.clone(),
+            &msg,
+            &[],
+        )
+        .unwrap_err();
+    assert_eq!(
+        err.root_cause().downcast_ref::<ContractError>(),
+        Some(&ContractError::UnknownValidator("unknown".to_owned()))
+    );
+
+    // No pending tx nor lien is left behind
+    let txs = vault.all_pending_txs_desc(None, None).unwrap().txs;
+    assert_eq!(txs, []);
+    let account = vault.account(user.to_owned()).unwrap();
+    assert_eq!(account.free, ValueRange::new_val(Uint128::new(300)));
+    let stakes = contract
+        .stakes(user.to_owned(), None, None, None, None, None)
+        .unwrap();
+    assert_eq!(stakes.stakes, []);
+}
+
+#[test]
+fn can_stake_dry_run() {
+    let user = "user1";
+    let owner = "owner";
+
+    let app = App::new_with_balances(&[(user, &coins(300, OSMO))]);
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+    contract.activate_validators(["validator1"]);
+
+    // The verdict of the dry run matches the outcome of the actual stake
+    let assert_verdict = |validator: &str, expected: Option<ContractError>| {
+        let msg = to_binary(&ReceiveVirtualStake {
+            validator: validator.to_owned(),
+        })
+        .unwrap();
+        let verdict = contract
+            .cross_staking_api_proxy()
+            .can_stake(user.to_owned(), coin(100, OSMO), msg.clone())
+            .unwrap();
+        let outcome = contract
+            .cross_staking_api_proxy()
+            .receive_virtual_stake(user.to_owned(), coin(100, OSMO), 1, msg)
+            .call(vault.contract_addr.as_str());
+        match expected {
+            Some(err) => {
+                assert_eq!(
+                    verdict,
+                    CanStakeResponse::Rejected {
+                        reason: err.to_string()
+                    }
+                );
+                assert_eq!(outcome.unwrap_err(), err);
+            }
+            None => {
+                assert_eq!(verdict, CanStakeResponse::Accepted {});
+                outcome.unwrap();
+            }
+        }
+    };
+
+    assert_verdict(
+        "unknown",
+        Some(ContractError::UnknownValidator("unknown".to_owned())),
+    );
+
+    // New stakes are paused while the valset is synced
+    let validators: Vec<_> = (0..100)
+        .map(|i| AddValidator::mock(&format!("validator-{:03}", i)))
+        .collect();
+    contract
+        .test_methods_proxy()
+        .test_add_validators(validators)
+        .call("test")
+        .unwrap();
+    assert_verdict("validator1", Some(ContractError::ValsetSyncInProgress(70)));
+
+    contract.continue_valset_sync(Some(100)).call(user).unwrap();
+    assert_verdict("validator1", None);
+}
+
+#[test]
+fn valset_sync_in_chunks() {
+    let user = "user1";
+    let owner = "owner";
+
+    let app = App::new_with_balances(&[(user, &coins(300, OSMO))]);
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    vault
+        .bond()
+        .with_funds(&coins(300, OSMO))
+        .call(user)
+        .unwrap();
+
+    // Big initial valset, only the first chunk is added right away
+    let validators: Vec<_> = (0..100)
+        .map(|i| AddValidator::mock(&format!("validator-{:03}", i)))
+        .collect();
+    contract
+        .test_methods_proxy()
+        .test_add_validators(validators)
+        .call("test")
+        .unwrap();
+
+    let sync = contract.valset_sync().unwrap();
+    assert_eq!(sync.pending, 70);
+    let active = contract.list_remote_validators(None, Some(200)).unwrap();
+    assert_eq!(active.validators.len(), 30);
+    assert_eq!(active.validators[29], "validator-029");
+
+    // No new stakes until the sync is complete
+    let err = contract
+        .cross_staking_api_proxy()
+        .receive_virtual_stake(
+            user.to_owned(),
+            coin(100, OSMO),
+            1,
+            to_binary(&ReceiveVirtualStake {
+                validator: "validator-000".to_owned(),
+            })
+            .unwrap(),
+        )
+        .call(vault.contract_addr.as_str())
+        .unwrap_err();
+    assert_eq!(err, ContractError::ValsetSyncInProgress(70));
+
+    // Anyone can continue the sync
+    contract.continue_valset_sync(Some(30)).call(user).unwrap();
+    assert_eq!(contract.valset_sync().unwrap().pending, 40);
+    contract.continue_valset_sync(None).call(user).unwrap();
+    assert_eq!(contract.valset_sync().unwrap().pending, 10);
+    contract.continue_valset_sync(None).call(user).unwrap();
+    assert_eq!(contract.valset_sync().unwrap().pending, 0);
+
+    let active = contract.list_remote_validators(None, Some(200)).unwrap();
+    assert_eq!(active.validators.len(), 100);
+
+    // Continuing a finished sync is a no-op
+    contract.continue_valset_sync(None).call(user).unwrap();
+    assert_eq!(contract.valset_sync().unwrap().pending, 0);
+
+    // Staking works again
+    vault.stake(&contract, user, "validator-099", coin(100, OSMO));
+    let stake = contract
+        .stake(user.to_owned(), "validator-099".to_owned())
+        .unwrap();
+    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(100)));
+}
+
+#[test]
+fn validator_sync_request() {
+    let user = "user1";
+    let owner = "owner";
+
+    let app = App::new_with_balances(&[(user, &coins(300, OSMO))]);
+
+    let (_vault, contract) = setup(&app, owner, 100).unwrap();
+    contract.activate_validators(["validator1"]);
+
+    let sync = contract.last_validator_sync().unwrap();
+    assert_eq!(sync.synced_at, None);
+    assert_eq!(sync.requested_at, None);
+
+    // Anyone can request a sync, but not too often
+    let height = app.block_info().height;
+    contract.request_validator_sync().call(user).unwrap();
+    let err = contract.request_validator_sync().call(owner).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::ValidatorSyncTooSoon(height + VALIDATOR_SYNC_INTERVAL)
+    );
+
+    // The response adds the validators missed so far
+    let validators = ["validator1", "validator2"]
+        .into_iter()
+        .map(AddValidator::mock)
+        .collect();
+    contract
+        .test_methods_proxy()
+        .test_validator_sync(validators)
+        .call("test")
+        .unwrap();
+    let active = contract.list_remote_validators(None, None).unwrap();
+    assert_eq!(active.validators, ["validator1", "validator2"]);
+
+    let sync = contract.last_validator_sync().unwrap();
+    assert_eq!(sync.synced_at, Some(height));
+    assert_eq!(sync.requested_at, Some(height));
+
+    app.update_block(|block| block.height += VALIDATOR_SYNC_INTERVAL);
+    contract.request_validator_sync().call(owner).unwrap();
+    let sync = contract.last_validator_sync().unwrap();
+    assert_eq!(sync.requested_at, Some(height + VALIDATOR_SYNC_INTERVAL));
+}
+
 #[test]
 fn unstaking() {
     let users = ["user1", "user2"];
@@ -319,8 +565,8 @@ fn unstaking() {
     assert_eq!(claim.amount.val().unwrap().u128(), 300);
 
     // Immediately withdrawing liens
-    contract.withdraw_unbonded().call(users[0]).unwrap();
-    contract.withdraw_unbonded().call(users[1]).unwrap();
+    contract.withdraw_unbonded(None).call(users[0]).unwrap();
+    contract.withdraw_unbonded(None).call(users[1]).unwrap();
 
     // Claims still not changed on the vault side
     let claim = vault
@@ -340,8 +586,8 @@ fn unstaking() {
     });
 
     // Withdrawing liens
-    contract.withdraw_unbonded().call(users[0]).unwrap();
-    contract.withdraw_unbonded().call(users[1]).unwrap();
+    contract.withdraw_unbonded(None).call(users[0]).unwrap();
+    contract.withdraw_unbonded(None).call(users[1]).unwrap();
 
     // Claims still not changed on the vault side - withdrawal to early
     let claim = vault
@@ -406,8 +652,8 @@ fn unstaking() {
     });
 
     // Withdrawing liens
-    contract.withdraw_unbonded().call(users[0]).unwrap();
-    contract.withdraw_unbonded().call(users[1]).unwrap();
+    contract.withdraw_unbonded(None).call(users[0]).unwrap();
+    contract.withdraw_unbonded(None).call(users[1]).unwrap();
 
     // Now claims on vault got reduced, but only for first batch amount
     let claim = vault
@@ -438,8 +684,8 @@ fn unstaking() {
     assert_eq!(claim.amount.val().unwrap().u128(), 240);
 
     // Withdrawing liens
-    contract.withdraw_unbonded().call(users[0]).unwrap();
-    contract.withdraw_unbonded().call(users[1]).unwrap();
+    contract.withdraw_unbonded(None).call(users[0]).unwrap();
+    contract.withdraw_unbonded(None).call(users[1]).unwrap();
 
     // Now everything is released
     let claim = vault
@@ -454,147 +700,2175 @@ fn unstaking() {
 }
 
 #[test]
-fn distribution() {
+fn orphaned_lock_recovery() {
+    let user = "user1";
     let owner = "owner";
-    let users = ["user1", "user2"];
-    let remote = ["remote1", "remote2"];
 
-    let app = App::new_with_balances(&[
-        (users[0], &coins(600, OSMO)),
-        (users[1], &coins(600, OSMO)),
-        (owner, &[coin(1000, STAR), coin(1000, OSMO)]),
-    ]);
+    let app = App::new_with_balances(&[(user, &coins(300, OSMO))]);
 
     let (vault, contract) = setup(&app, owner, 100).unwrap();
 
-    let validators = contract.activate_validators(["validator1", "validator2"]);
+    let validator = contract.activate_validators(["validator1"])[0];
 
-    // Weights proportion:
-    // 2/5 of validators[0] to users[0]
-    // 3/5 of validators[0] to users[1]
-    // all of validators[1] to users[1]
     vault
         .bond()
-        .with_funds(&coins(600, OSMO))
-        .call(users[0])
+        .with_funds(&coins(300, OSMO))
+        .call(user)
         .unwrap();
+    vault.stake(&contract, user, validator, coin(100, OSMO));
 
-    vault
-        .bond()
-        .with_funds(&coins(600, OSMO))
-        .call(users[1])
+    contract
+        .unstake(validator.to_string(), coin(40, OSMO))
+        .call(user)
         .unwrap();
+    let tx_id = get_last_external_staking_pending_tx_id(&contract).unwrap();
 
-    vault.stake(&contract, users[0], validators[0], coin(200, OSMO));
-    vault.stake(&contract, users[0], validators[1], coin(100, OSMO));
-    vault.stake(&contract, users[1], validators[0], coin(300, OSMO));
+    let locked = contract.locked_stakes(None, None).unwrap().stakes;
+    assert_eq!(
+        locked,
+        [LockedStake {
+            owner: user.to_owned(),
+            validator: validator.to_owned(),
+            stake: ValueRange::new(Uint128::new(60), Uint128::new(100)),
+            tx_id: Some(tx_id),
+        }]
+    );
 
-    // Start with equal distribution:
-    // 20 tokens for users[0]
-    // 30 tokens for users[1]
+    // The lock of a pending tx can't be cleared
+    let err = contract
+        .force_unlock(user.to_owned(), validator.to_owned())
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(err, ContractError::StakeLockInUse(tx_id));
+
+    // The tx is lost, leaving the stake locked
     contract
         .test_methods_proxy()
-        .test_distribute_rewards(validators[0].to_owned(), coin(50, STAR))
+        .test_drop_pending_tx(tx_id)
+        .call("test")
+        .unwrap();
+    let locked = contract.locked_stakes(None, None).unwrap().stakes;
+    assert_eq!(locked.len(), 1);
+    assert_eq!(locked[0].tx_id, None);
+
+    // Only the admin can clear it
+    let err = contract
+        .force_unlock(user.to_owned(), validator.to_owned())
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized);
+
+    let res = contract
+        .force_unlock(user.to_owned(), validator.to_owned())
         .call(owner)
         .unwrap();
+    let event = res
+        .events
+        .iter()
+        .find(|event| event.ty == "wasm-force_unlock")
+        .unwrap();
+    let attr = |key: &str| {
+        event
+            .attributes
+            .iter()
+            .find(|attr| attr.key == key)
+            .map(|attr| attr.value.as_str())
+    };
+    assert_eq!(attr("lost_stake"), Some("0"));
+    assert_eq!(attr("lost_unstake"), Some("40"));
 
-    // Only users[0] stakes on validators[1]
-    // 30 tokens for users[0]
+    let stake = contract
+        .stake(user.to_owned(), validator.to_owned())
+        .unwrap();
+    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(60)));
+    assert_eq!(stake.rewards_stake, Uint128::new(60));
+    assert_eq!(contract.locked_stakes(None, None).unwrap().stakes, []);
+
+    let err = contract
+        .force_unlock(user.to_owned(), validator.to_owned())
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(err, ContractError::StakeNotLocked);
+
+    // The stake can be changed again
+    contract
+        .unstake(validator.to_string(), coin(10, OSMO))
+        .call(user)
+        .unwrap();
     contract
         .test_methods_proxy()
-        .test_distribute_rewards(validators[1].to_owned(), coin(30, STAR))
-        .call(owner)
+        .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
+    let stake = contract
+        .stake(user.to_owned(), validator.to_owned())
         .unwrap();
+    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(50)));
+}
 
-    // Check how much rewards are pending for withdrawal
-    let rewards = contract
-        .pending_rewards(users[0].to_owned(), validators[0].to_owned())
-        .unwrap()
-        .rewards;
-    assert_eq!(rewards.amount.u128(), 20);
+#[test]
+fn stuck_stake_force_rollback() {
+    let user = "user1";
+    let owner = "owner";
 
-    let rewards = contract
-        .pending_rewards(users[1].to_owned(), validators[0].to_owned())
-        .unwrap()
-        .rewards;
-    assert_eq!(rewards.amount.u128(), 30);
+    let app = App::new_with_balances(&[(user, &coins(300, OSMO))]);
 
-    let rewards = contract
-        .pending_rewards(users[0].to_owned(), validators[1].to_owned())
-        .unwrap()
-        .rewards;
-    assert_eq!(rewards.amount.u128(), 30);
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
 
-    let rewards = contract
-        .pending_rewards(users[1].to_owned(), validators[1].to_owned())
-        .unwrap()
-        .rewards;
-    assert_eq!(rewards.amount.u128(), 0);
+    let validator = contract.activate_validators(["validator1"])[0];
 
-    // Show all rewards skips validators that were never staked on
-    let all_rewards = contract
-        .all_pending_rewards(users[0].to_owned(), None, None)
+    vault
+        .bond()
+        .with_funds(&coins(300, OSMO))
+        .call(user)
         .unwrap();
-    let expected = vec![
-        ValidatorPendingRewards::new(validators[0], 20, STAR),
-        ValidatorPendingRewards::new(validators[1], 30, STAR),
-    ];
-    assert_eq!(all_rewards.rewards, expected);
+    vault.stake(&contract, user, validator, coin(100, OSMO));
 
-    let all_rewards = contract
-        .all_pending_rewards(users[1].to_owned(), None, None)
+    // The ack of the next stake never arrives
+    vault
+        .stake_remote(
+            contract.contract_addr.to_string(),
+            coin(50, OSMO),
+            to_binary(&ReceiveVirtualStake {
+                validator: validator.to_owned(),
+            })
+            .unwrap(),
+            None,
+        )
+        .call(user)
         .unwrap();
-    let expected = vec![ValidatorPendingRewards::new(validators[0], 30, STAR)];
-    assert_eq!(all_rewards.rewards, expected);
+    let tx_id = get_last_external_staking_pending_tx_id(&contract).unwrap();
 
-    // Some more distribution, this time not divisible by total staken tokens
-    // 28 tokens for users[0]
-    // 42 tokens for users[1]
-    // 1 token is not distributed
+    let locked = contract.locked_stakes(None, None).unwrap().stakes;
+    assert_eq!(
+        locked,
+        [LockedStake {
+            owner: user.to_owned(),
+            validator: validator.to_owned(),
+            stake: ValueRange::new(Uint128::new(100), Uint128::new(150)),
+            tx_id: Some(tx_id),
+        }]
+    );
+    assert_eq!(vault.all_pending_txs_desc(None, None).unwrap().txs.len(), 1);
+
+    // Only the admin can roll it back
+    let err = contract.force_rollback_stake(tx_id).call(user).unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized);
+
+    contract.force_rollback_stake(tx_id).call(owner).unwrap();
+
+    // Both contracts are unlocked
+    assert_eq!(contract.locked_stakes(None, None).unwrap().stakes, []);
+    assert_eq!(contract.all_pending_txs_desc(None, None).unwrap().txs, []);
+    let stake = contract
+        .stake(user.to_owned(), validator.to_owned())
+        .unwrap();
+    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(100)));
+    assert_eq!(vault.all_pending_txs_desc(None, None).unwrap().txs, []);
+    let account = vault.account(user.to_owned()).unwrap();
+    assert_eq!(account.free, ValueRange::new_val(Uint128::new(200)));
+
+    // It can't be rolled back twice
     contract
-        .test_methods_proxy()
-        .test_distribute_rewards(validators[0].to_owned(), coin(71, STAR))
+        .force_rollback_stake(tx_id)
         .call(owner)
-        .unwrap();
+        .unwrap_err();
 
-    // Distribution in invalid coin should fail
+    // Only stakes can be force rolled back
     contract
-        .test_methods_proxy()
-        .test_distribute_rewards(validators[1].to_owned(), coin(100, OSMO))
+        .unstake(validator.to_string(), coin(10, OSMO))
+        .call(user)
+        .unwrap();
+    let tx_id = get_last_external_staking_pending_tx_id(&contract).unwrap();
+    let err = contract
+        .force_rollback_stake(tx_id)
         .call(owner)
         .unwrap_err();
+    assert!(matches!(err, ContractError::WrongTypeTx(id, _) if id == tx_id));
+}
 
-    // Check how much rewards are pending for withdrawal
-    let rewards = contract
-        .pending_rewards(users[0].to_owned(), validators[0].to_owned())
-        .unwrap()
+#[test]
+fn unstaking_dust_rejected() {
+    let user = "user1";
+
+    let app = App::new_with_balances(&[(user, &coins(300, OSMO))]);
+
+    let owner = "owner";
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validator = contract.activate_validators(["validator1"])[0];
+
+    vault
+        .bond()
+        .with_funds(&coins(300, OSMO))
+        .call(user)
+        .unwrap();
+    vault.stake(&contract, user, validator, coin(100, OSMO));
+
+    // Only the admin can set the minimum
+    let err = contract
+        .update_min_remaining_stake(Some(Uint128::new(10)), DustPolicy::Reject)
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized);
+
+    contract
+        .update_min_remaining_stake(Some(Uint128::new(10)), DustPolicy::Reject)
+        .call(owner)
+        .unwrap();
+    let config = contract.config().unwrap();
+    assert_eq!(config.min_remaining_stake, Some(Uint128::new(10)));
+    assert_eq!(config.dust_policy, DustPolicy::Reject);
+
+    // Leaving less than the minimum fails
+    let err = contract
+        .unstake(validator.to_string(), coin(95, OSMO))
+        .call(user)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::WouldLeaveDust(Uint128::new(5), Uint128::new(10))
+    );
+
+    // Leaving exactly the minimum, or nothing, is fine
+    contract
+        .unstake(validator.to_string(), coin(90, OSMO))
+        .call(user)
+        .unwrap();
+    contract
+        .test_methods_proxy()
+        .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
+
+    contract
+        .unstake(validator.to_string(), coin(10, OSMO))
+        .call(user)
+        .unwrap();
+    contract
+        .test_methods_proxy()
+        .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
+
+    let stake = contract
+        .stake(user.to_string(), validator.to_string())
+        .unwrap();
+    assert_eq!(stake.stake, ValueRange::new_val(Uint128::zero()));
+}
+
+#[test]
+fn unstaking_dust_fully_unstaked() {
+    let user = "user1";
+
+    let app = App::new_with_balances(&[(user, &coins(300, OSMO))]);
+
+    let owner = "owner";
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validator = contract.activate_validators(["validator1"])[0];
+
+    vault
+        .bond()
+        .with_funds(&coins(300, OSMO))
+        .call(user)
+        .unwrap();
+    vault.stake(&contract, user, validator, coin(100, OSMO));
+
+    contract
+        .update_min_remaining_stake(Some(Uint128::new(10)), DustPolicy::FullUnstake)
+        .call(owner)
+        .unwrap();
+
+    // Leaving more than the minimum is untouched
+    contract
+        .unstake(validator.to_string(), coin(50, OSMO))
+        .call(user)
+        .unwrap();
+    contract
+        .test_methods_proxy()
+        .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
+
+    let stake = contract
+        .stake(user.to_string(), validator.to_string())
+        .unwrap();
+    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(50)));
+
+    // Leaving less than the minimum unstakes everything
+    contract
+        .unstake(validator.to_string(), coin(45, OSMO))
+        .call(user)
+        .unwrap();
+    let tx_id = get_last_external_staking_pending_tx_id(&contract).unwrap();
+    match contract.pending_tx(tx_id).unwrap() {
+        TxStatus::Pending(Tx::InFlightRemoteUnstaking { amount, .. }) => {
+            assert_eq!(amount.u128(), 50)
+        }
+        tx => panic!("unexpected tx {:?}", tx),
+    }
+    contract
+        .test_methods_proxy()
+        .test_commit_unstake(tx_id)
+        .call("test")
+        .unwrap();
+
+    let stake = contract
+        .stake(user.to_string(), validator.to_string())
+        .unwrap();
+    assert_eq!(stake.stake, ValueRange::new_val(Uint128::zero()));
+    let unbonding: u128 = stake.pending_unbonds.iter().map(|u| u.amount.u128()).sum();
+    assert_eq!(unbonding, 100);
+}
+
+#[test]
+fn unstaking_batch() {
+    let user = "user1";
+
+    let app = App::new_with_balances(&[(user, &coins(300, OSMO))]);
+
+    let owner = "owner";
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validators = contract.activate_validators(["validator1", "validator2", "validator3"]);
+
+    vault
+        .bond()
+        .with_funds(&coins(300, OSMO))
+        .call(user)
+        .unwrap();
+    for validator in validators {
+        vault.stake(&contract, user, validator, coin(100, OSMO));
+    }
+
+    // Empty batches are rejected
+    let err = contract.unstake_batch(vec![]).call(user).unwrap_err();
+    assert_eq!(err, ContractError::EmptyUnstakeBatch);
+
+    // A single failing entry fails the whole batch
+    let err = contract
+        .unstake_batch(vec![
+            (validators[0].to_string(), coin(10, OSMO)),
+            (validators[1].to_string(), coin(110, OSMO)),
+        ])
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::NotEnoughStake(Uint128::new(100)));
+
+    // Unstaking from all validators creates a single tx
+    contract
+        .unstake_batch(vec![
+            (validators[0].to_string(), coin(10, OSMO)),
+            (validators[1].to_string(), coin(20, OSMO)),
+            (validators[2].to_string(), coin(30, OSMO)),
+        ])
+        .call(user)
+        .unwrap();
+    let txs = contract.all_pending_txs_desc(None, None).unwrap().txs;
+    assert_eq!(txs.len(), 1);
+    let tx_id = txs[0].id();
+    match contract.pending_tx(tx_id).unwrap() {
+        TxStatus::Pending(Tx::InFlightRemoteUnstakingBatch { unstakes, .. }) => {
+            assert_eq!(unstakes.len(), 3)
+        }
+        tx => panic!("unexpected tx {:?}", tx),
+    }
+
+    contract
+        .test_methods_proxy()
+        .test_commit_unstake(tx_id)
+        .call("test")
+        .unwrap();
+
+    for (validator, unstaked) in validators.iter().zip([10, 20, 30]) {
+        let stake = contract
+            .stake(user.to_string(), validator.to_string())
+            .unwrap();
+        assert_eq!(
+            stake.stake,
+            ValueRange::new_val(Uint128::new(100 - unstaked))
+        );
+        let unbonding: u128 = stake.pending_unbonds.iter().map(|u| u.amount.u128()).sum();
+        assert_eq!(unbonding, unstaked);
+    }
+
+    // Rolling back a batch restores all the stakes
+    contract
+        .unstake_batch(vec![
+            (validators[0].to_string(), coin(10, OSMO)),
+            (validators[2].to_string(), coin(10, OSMO)),
+        ])
+        .call(user)
+        .unwrap();
+    contract
+        .test_methods_proxy()
+        .test_rollback_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
+
+    for (validator, unstaked) in validators.iter().zip([10, 20, 30]) {
+        let stake = contract
+            .stake(user.to_string(), validator.to_string())
+            .unwrap();
+        assert_eq!(
+            stake.stake,
+            ValueRange::new_val(Uint128::new(100 - unstaked))
+        );
+    }
+}
+
+#[test]
+fn net_position() {
+    let user = "user1";
+
+    let app = App::new_with_balances(&[(user, &coins(300, OSMO))]);
+
+    let owner = "owner";
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validators = contract.activate_validators(["validator1", "validator2"]);
+
+    vault
+        .bond()
+        .with_funds(&coins(300, OSMO))
+        .call(user)
+        .unwrap();
+    vault.stake(&contract, user, validators[0], coin(100, OSMO));
+    vault.stake(&contract, user, validators[1], coin(35, OSMO));
+
+    // 10% max slash
+    let position = contract
+        .net_position(user.to_owned(), validators[0].to_owned())
+        .unwrap();
+    assert_eq!(
+        position,
+        NetPositionResponse {
+            stake: Uint128::new(100),
+            max_slash: Decimal::percent(SLASHING_PERCENTAGE),
+            worst_case: Uint128::new(90),
+        }
+    );
+    // Rounded down
+    let position = contract
+        .net_position(user.to_owned(), validators[1].to_owned())
+        .unwrap();
+    assert_eq!(position.worst_case, Uint128::new(31));
+
+    // Pending stakes are not included
+    vault
+        .stake_remote(
+            contract.contract_addr.to_string(),
+            coin(50, OSMO),
+            to_binary(&ReceiveVirtualStake {
+                validator: validators[0].to_owned(),
+            })
+            .unwrap(),
+            None,
+        )
+        .call(user)
+        .unwrap();
+    let position = contract
+        .net_position(user.to_owned(), validators[0].to_owned())
+        .unwrap();
+    assert_eq!(position.stake, Uint128::new(100));
+    assert_eq!(position.worst_case, Uint128::new(90));
+
+    // Nothing staked
+    let position = contract
+        .net_position("user2".to_owned(), validators[0].to_owned())
+        .unwrap();
+    assert_eq!(position.worst_case, Uint128::zero());
+}
+
+#[test]
+fn closed_positions() {
+    let user = "user1";
+
+    let app = App::new_with_balances(&[(user, &coins(300, OSMO))]);
+
+    let owner = "owner";
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validators = contract.activate_validators(["validator1", "validator2", "validator3"]);
+
+    vault
+        .bond()
+        .with_funds(&coins(300, OSMO))
+        .call(user)
+        .unwrap();
+    for validator in validators {
+        vault.stake(&contract, user, validator, coin(100, OSMO));
+    }
+
+    contract
+        .test_methods_proxy()
+        .test_distribute_rewards(validators[1].to_owned(), coin(10, STAR))
+        .call(owner)
+        .unwrap();
+
+    // Fully unstake from the first two validators
+    for validator in &validators[..2] {
+        contract
+            .unstake(validator.to_string(), coin(100, OSMO))
+            .call(user)
+            .unwrap();
+        contract
+            .test_methods_proxy()
+            .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+            .call("test")
+            .unwrap();
+    }
+
+    // Zeroed positions are only listed on demand
+    let stakes = contract
+        .stakes(user.to_owned(), None, None, None, None, None)
+        .unwrap()
+        .stakes;
+    assert_eq!(stakes.len(), 1);
+    assert_eq!(stakes[0].validator, validators[2]);
+
+    let stakes = contract
+        .stakes(user.to_owned(), None, None, Some(true), None, None)
+        .unwrap()
+        .stakes;
+    let listed: Vec<_> = stakes.iter().map(|s| s.validator.as_str()).collect();
+    assert_eq!(listed, validators);
+
+    // Once released, the position without rewards left is removed
+    app.app_mut().update_block(|block| {
+        block.height += 1;
+        block.time = block.time.plus_seconds(100);
+    });
+    contract.withdraw_unbonded(None).call(user).unwrap();
+
+    let stakes = contract
+        .stakes(user.to_owned(), None, None, Some(true), None, None)
+        .unwrap()
+        .stakes;
+    let listed: Vec<_> = stakes.iter().map(|s| s.validator.as_str()).collect();
+    assert_eq!(listed, validators[1..]);
+
+    // The other one is removed after its rewards are withdrawn
+    contract
+        .withdraw_rewards(validators[1].to_owned(), "remote".to_owned())
+        .call(user)
+        .unwrap();
+    contract
+        .test_methods_proxy()
+        .test_commit_withdraw_rewards(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
+
+    let stakes = contract
+        .stakes(user.to_owned(), None, None, Some(true), None, None)
+        .unwrap()
+        .stakes;
+    let listed: Vec<_> = stakes.iter().map(|s| s.validator.as_str()).collect();
+    assert_eq!(listed, validators[2..]);
+
+    // Staking again on a removed position starts from scratch
+    vault.stake(&contract, user, validators[0], coin(50, OSMO));
+    let stake = contract
+        .stake(user.to_owned(), validators[0].to_owned())
+        .unwrap();
+    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(50)));
+    assert_eq!(
+        contract
+            .pending_rewards(user.to_owned(), validators[0].to_owned())
+            .unwrap()
+            .rewards,
+        [coin(0, STAR)]
+    );
+}
+
+#[test]
+fn stats() {
+    let user1 = "user1";
+    let user2 = "user2";
+    let owner = "owner";
+
+    let app = App::new_with_balances(&[(user1, &coins(300, OSMO)), (user2, &coins(300, OSMO))]);
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validators = contract.activate_validators(["validator1", "validator2"]);
+
+    assert_eq!(
+        contract.stats().unwrap(),
+        StatsResponse {
+            total_stakers: 0,
+            total_validators: 0,
+            total_stake: Uint128::zero(),
+            total_pending_unbonds: Uint128::zero(),
+            rewards_distributed: vec![],
+            slashes_redistributed: Uint128::zero(),
+        }
+    );
+
+    for user in [user1, user2] {
+        vault
+            .bond()
+            .with_funds(&coins(300, OSMO))
+            .call(user)
+            .unwrap();
+    }
+    vault.stake(&contract, user1, validators[0], coin(100, OSMO));
+    vault.stake(&contract, user1, validators[1], coin(50, OSMO));
+    vault.stake(&contract, user2, validators[0], coin(30, OSMO));
+
+    for (validator, amount) in [(validators[0], 10), (validators[1], 20)] {
+        contract
+            .test_methods_proxy()
+            .test_distribute_rewards(validator.to_owned(), coin(amount, STAR))
+            .call(owner)
+            .unwrap();
+    }
+
+    assert_eq!(
+        contract.stats().unwrap(),
+        StatsResponse {
+            total_stakers: 2,
+            total_validators: 2,
+            total_stake: Uint128::new(180),
+            total_pending_unbonds: Uint128::zero(),
+            rewards_distributed: vec![coin(30, STAR)],
+            slashes_redistributed: Uint128::zero(),
+        }
+    );
+
+    // Unstaked tokens move to the pending unbonds
+    contract
+        .unstake(validators[1].to_owned(), coin(50, OSMO))
+        .call(user1)
+        .unwrap();
+    contract
+        .test_methods_proxy()
+        .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
+
+    let stats = contract.stats().unwrap();
+    assert_eq!(stats.total_stake, Uint128::new(130));
+    assert_eq!(stats.total_pending_unbonds, Uint128::new(50));
+    assert_eq!(stats.total_validators, 2);
+
+    // Once released and its rewards withdrawn, the position is closed
+    app.app_mut().update_block(|block| {
+        block.height += 1;
+        block.time = block.time.plus_seconds(100);
+    });
+    contract.withdraw_unbonded(None).call(user1).unwrap();
+    contract
+        .withdraw_rewards(validators[1].to_owned(), "remote".to_owned())
+        .call(user1)
+        .unwrap();
+    contract
+        .test_methods_proxy()
+        .test_commit_withdraw_rewards(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
+
+    assert_eq!(
+        contract.stats().unwrap(),
+        StatsResponse {
+            total_stakers: 2,
+            total_validators: 1,
+            total_stake: Uint128::new(130),
+            total_pending_unbonds: Uint128::zero(),
+            rewards_distributed: vec![coin(30, STAR)],
+            slashes_redistributed: Uint128::zero(),
+        }
+    );
+}
+
+#[test]
+fn max_validators_per_user() {
+    let user = "user1";
+
+    let app = App::new_with_balances(&[(user, &coins(300, OSMO))]);
+
+    let owner = "owner";
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validators = contract.activate_validators(["validator1", "validator2", "validator3"]);
+
+    assert_eq!(
+        contract.config().unwrap().max_validators_per_user,
+        Some(DEFAULT_MAX_VALIDATORS_PER_USER)
+    );
+    // Only the admin can change the max
+    let err = contract
+        .update_max_validators_per_user(Some(2))
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized);
+    contract
+        .update_max_validators_per_user(Some(2))
+        .call(owner)
+        .unwrap();
+
+    vault
+        .bond()
+        .with_funds(&coins(300, OSMO))
+        .call(user)
+        .unwrap();
+    vault.stake(&contract, user, validators[0], coin(100, OSMO));
+    vault.stake(&contract, user, validators[1], coin(100, OSMO));
+
+    let stake_on = |validator: &str, tx_id| {
+        contract
+            .cross_staking_api_proxy()
+            .receive_virtual_stake(
+                user.to_owned(),
+                coin(50, OSMO),
+                tx_id,
+                to_binary(&ReceiveVirtualStake {
+                    validator: validator.to_owned(),
+                })
+                .unwrap(),
+            )
+            .call(vault.contract_addr.as_str())
+    };
+
+    // Over the max on a new validator, but not on the ones staked on already
+    let err = stake_on(validators[2], 100).unwrap_err();
+    assert_eq!(err, ContractError::TooManyValidators(2));
+    vault.stake(&contract, user, validators[1], coin(50, OSMO));
+
+    // A full exit from a validator frees up a slot
+    contract
+        .unstake(validators[0].to_string(), coin(100, OSMO))
+        .call(user)
+        .unwrap();
+    contract
+        .test_methods_proxy()
+        .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
+    // Not before the position is removed
+    let err = stake_on(validators[2], 101).unwrap_err();
+    assert_eq!(err, ContractError::TooManyValidators(2));
+
+    app.app_mut().update_block(|block| {
+        block.height += 1;
+        block.time = block.time.plus_seconds(100);
+    });
+    contract.withdraw_unbonded(None).call(user).unwrap();
+
+    vault.stake(&contract, user, validators[2], coin(50, OSMO));
+    let stakes = contract
+        .stakes(user.to_owned(), None, None, Some(true), None, None)
+        .unwrap()
+        .stakes;
+    let listed: Vec<_> = stakes.iter().map(|s| s.validator.as_str()).collect();
+    assert_eq!(listed, validators[1..]);
+}
+
+#[test]
+fn stakes_by_size() {
+    let owner = "owner";
+    let user = "user1";
+
+    let app = App::new_with_balances(&[(user, &coins(600, OSMO))]);
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validators = contract.activate_validators(["validator1", "validator2", "validator3"]);
+
+    vault
+        .bond()
+        .with_funds(&coins(600, OSMO))
+        .call(user)
+        .unwrap();
+    vault.stake(&contract, user, validators[0], coin(100, OSMO));
+    vault.stake(&contract, user, validators[1], coin(300, OSMO));
+    vault.stake(&contract, user, validators[2], coin(200, OSMO));
+
+    let stakes = |start_after: Option<&str>| {
+        contract
+            .stakes(
+                user.to_owned(),
+                start_after.map(str::to_owned),
+                None,
+                None,
+                Some(StakesOrderBy::StakeDesc),
+                None,
+            )
+            .unwrap()
+            .stakes
+            .into_iter()
+            .map(|stake| (stake.validator, stake.stake.stake.high().u128()))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        stakes(None),
+        [
+            (validators[1].to_owned(), 300),
+            (validators[2].to_owned(), 200),
+            (validators[0].to_owned(), 100),
+        ]
+    );
+
+    // Next page
+    assert_eq!(
+        stakes(Some(validators[2])),
+        [(validators[0].to_owned(), 100)]
+    );
+
+    // The index follows the stake changes
+    contract
+        .unstake(validators[1].to_owned(), coin(250, OSMO))
+        .call(user)
+        .unwrap();
+    contract
+        .test_methods_proxy()
+        .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
+    assert_eq!(
+        stakes(None),
+        [
+            (validators[2].to_owned(), 200),
+            (validators[0].to_owned(), 100),
+            (validators[1].to_owned(), 50),
+        ]
+    );
+}
+
+#[test]
+fn resolved_txs_history() {
+    let user = "user";
+
+    let app = App::new_with_balances(&[(user, &coins(300, OSMO))]);
+
+    let owner = "owner";
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validator = contract.activate_validators(["validator1"])[0];
+
+    vault
+        .bond()
+        .with_funds(&coins(300, OSMO))
+        .call(user)
+        .unwrap();
+    vault.stake(&contract, user, validator, coin(200, OSMO));
+
+    // Committed stake
+    let history = contract.txs_history(None, None).unwrap().txs;
+    assert_eq!(history.len(), 1);
+    let stake_tx = history[0].clone();
+    assert!(stake_tx.committed);
+    assert_eq!(
+        contract.pending_tx(stake_tx.id).unwrap(),
+        TxStatus::Resolved(stake_tx.clone())
+    );
+
+    // Rolled back unstake
+    contract
+        .unstake(validator.to_string(), coin(50, OSMO))
+        .call(user)
+        .unwrap();
+    let unstake_tx = get_last_external_staking_pending_tx_id(&contract).unwrap();
+    assert!(matches!(
+        contract.pending_tx(unstake_tx).unwrap(),
+        TxStatus::Pending(Tx::InFlightRemoteUnstaking { .. })
+    ));
+    contract
+        .test_methods_proxy()
+        .test_rollback_unstake(unstake_tx)
+        .call("test")
+        .unwrap();
+
+    let resolved_at = app.block_info().time;
+    assert_eq!(
+        contract.pending_tx(unstake_tx).unwrap(),
+        TxStatus::Resolved(TxResult {
+            id: unstake_tx,
+            committed: false,
+            resolved_at,
+        })
+    );
+
+    // Newest first, paginated
+    let history = contract.txs_history(None, None).unwrap().txs;
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].id, unstake_tx);
+    let history = contract.txs_history(Some(unstake_tx), None).unwrap().txs;
+    assert_eq!(history, [stake_tx]);
+
+    // Unknown txs are still not found
+    contract.pending_tx(unstake_tx + 1).unwrap_err();
+}
+
+#[test]
+fn unbond_sale() {
+    let seller = "seller";
+    let buyer = "buyer";
+
+    let app = App::new_with_balances(&[(seller, &coins(400, OSMO)), (buyer, &coins(100, OSMO))]);
+
+    let owner = "owner";
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validator = contract.activate_validators(["validator1"])[0];
+
+    vault
+        .bond()
+        .with_funds(&coins(300, OSMO))
+        .call(seller)
+        .unwrap();
+    vault.stake(&contract, seller, validator, coin(200, OSMO));
+
+    contract
+        .unstake(validator.to_string(), coin(100, OSMO))
+        .call(seller)
+        .unwrap();
+    contract
+        .test_methods_proxy()
+        .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
+
+    // Listing the pending unbond
+    contract
+        .list_unbond_for_sale(validator.to_string(), 0, Uint128::new(80))
+        .call(seller)
+        .unwrap();
+
+    let listings = contract
+        .unbond_listings(seller.to_owned(), validator.to_string())
+        .unwrap()
+        .listings;
+    assert_eq!(listings.len(), 1);
+    assert_eq!(listings[0].amount.u128(), 100);
+    assert_eq!(listings[0].min_price.u128(), 80);
+
+    // Paying less than the price, or buying an own unbond fails
+    let err = contract
+        .buy_unbond(seller.to_owned(), validator.to_string(), 0)
+        .with_funds(&coins(70, OSMO))
+        .call(buyer)
+        .unwrap_err();
+    assert_eq!(err, ContractError::PriceTooLow(Uint128::new(80)));
+
+    let err = contract
+        .buy_unbond(seller.to_owned(), validator.to_string(), 0)
+        .with_funds(&coins(80, OSMO))
+        .call(seller)
+        .unwrap_err();
+    assert_eq!(err, ContractError::CannotBuyOwnUnbond);
+
+    // Buying it pays the seller right away
+    contract
+        .buy_unbond(seller.to_owned(), validator.to_string(), 0)
+        .with_funds(&coins(90, OSMO))
+        .call(buyer)
+        .unwrap();
+
+    let balance = app.app().wrap().query_balance(seller, OSMO).unwrap();
+    assert_eq!(balance.amount.u128(), 190);
+    let balance = app.app().wrap().query_balance(buyer, OSMO).unwrap();
+    assert_eq!(balance.amount.u128(), 10);
+
+    let listings = contract
+        .unbond_listings(seller.to_owned(), validator.to_string())
+        .unwrap()
+        .listings;
+    assert_eq!(listings, []);
+
+    // The pending unbond moved to the buyer
+    let stake = contract
+        .stake(seller.to_string(), validator.to_string())
+        .unwrap();
+    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(100)));
+    assert_eq!(stake.pending_unbonds, []);
+
+    let stake = contract
+        .stake(buyer.to_string(), validator.to_string())
+        .unwrap();
+    assert_eq!(stake.stake, ValueRange::new_val(Uint128::zero()));
+    assert_eq!(stake.pending_unbonds.len(), 1);
+    assert_eq!(stake.pending_unbonds[0].amount.u128(), 100);
+
+    // Along with the lien and collateral
+    let claim = vault
+        .claim(seller.to_owned(), contract.contract_addr.to_string())
+        .unwrap();
+    assert_eq!(claim.amount.val().unwrap().u128(), 100);
+    assert_eq!(vault.account(seller.to_owned()).unwrap().bonded.u128(), 200);
+
+    let claim = vault
+        .claim(buyer.to_owned(), contract.contract_addr.to_string())
+        .unwrap();
+    assert_eq!(claim.amount.val().unwrap().u128(), 100);
+    let account = vault.account(buyer.to_owned()).unwrap();
+    assert_eq!(account.bonded.u128(), 100);
+    assert_eq!(account.free, ValueRange::new_val(Uint128::zero()));
+
+    // Once released, the buyer withdraws it
+    app.app_mut().update_block(|block| {
+        block.height += 1;
+        block.time = block.time.plus_seconds(100);
+    });
+
+    contract.withdraw_unbonded(None).call(seller).unwrap();
+    let claim = vault
+        .claim(seller.to_owned(), contract.contract_addr.to_string())
+        .unwrap();
+    assert_eq!(claim.amount.val().unwrap().u128(), 100);
+
+    contract.withdraw_unbonded(None).call(buyer).unwrap();
+    let claim = vault
+        .claim(buyer.to_owned(), contract.contract_addr.to_string())
+        .unwrap();
+    assert_eq!(claim.amount.val().unwrap().u128(), 0);
+
+    vault.unbond(coin(100, OSMO)).call(buyer).unwrap();
+    let balance = app.app().wrap().query_balance(buyer, OSMO).unwrap();
+    assert_eq!(balance.amount.u128(), 110);
+}
+
+#[test]
+fn unbond_sale_caps() {
+    let seller = "seller";
+    let buyer = "buyer";
+
+    let app = App::new_with_balances(&[(seller, &coins(300, OSMO)), (buyer, &coins(200, OSMO))]);
+
+    let owner = "owner";
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validators = contract.activate_validators(["validator1", "validator2"]);
+
+    vault
+        .bond()
+        .with_funds(&coins(300, OSMO))
+        .call(seller)
+        .unwrap();
+    vault.stake(&contract, seller, validators[0], coin(200, OSMO));
+    contract
+        .unstake(validators[0].to_string(), coin(100, OSMO))
+        .call(seller)
+        .unwrap();
+    contract
+        .test_methods_proxy()
+        .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
+    contract
+        .list_unbond_for_sale(validators[0].to_string(), 0, Uint128::new(80))
+        .call(seller)
+        .unwrap();
+
+    vault
+        .bond()
+        .with_funds(&coins(100, OSMO))
+        .call(buyer)
+        .unwrap();
+    vault.stake(&contract, buyer, validators[1], coin(50, OSMO));
+
+    // Buying on a new validator is bounded by the max validators per user
+    contract
+        .update_max_validators_per_user(Some(1))
+        .call(owner)
+        .unwrap();
+    let err = contract
+        .buy_unbond(seller.to_owned(), validators[0].to_string(), 0)
+        .with_funds(&coins(80, OSMO))
+        .call(buyer)
+        .unwrap_err();
+    assert_eq!(err, ContractError::TooManyValidators(1));
+    contract
+        .update_max_validators_per_user(None)
+        .call(owner)
+        .unwrap();
+
+    // And the buyer's leverage. The error comes from the vault, so the message is executed
+    // directly
+    vault
+        .set_max_leverage(Some(Decimal::percent(50)))
+        .call(buyer)
+        .unwrap();
+    let msg = crate::contract::ExecMsg::BuyUnbond {
+        seller: seller.to_owned(),
+        validator: validators[0].to_string(),
+        unbond_index: 0,
+    };
+    let err = app
+        .app_mut()
+        .execute_contract(
+            Addr::unchecked(buyer),
+            contract.contract_addr.clone(),
+            &msg,
+            &coins(80, OSMO),
+        )
+        .unwrap_err();
+    assert_eq!(
+        err.root_cause()
+            .downcast_ref::<mesh_vault::error::ContractError>(),
+        Some(&mesh_vault::error::ContractError::LeverageLimitExceeded(
+            Decimal::percent(50)
+        ))
+    );
+
+    // Within the limit, the sale goes through
+    vault.set_max_leverage(None).call(buyer).unwrap();
+    contract
+        .buy_unbond(seller.to_owned(), validators[0].to_string(), 0)
+        .with_funds(&coins(80, OSMO))
+        .call(buyer)
+        .unwrap();
+    let stake = contract
+        .stake(buyer.to_string(), validators[0].to_string())
+        .unwrap();
+    assert_eq!(stake.pending_unbonds.len(), 1);
+}
+
+#[test]
+fn unbond_sale_cancel_and_outdated() {
+    let seller = "seller";
+    let buyer = "buyer";
+
+    let app = App::new_with_balances(&[(seller, &coins(300, OSMO)), (buyer, &coins(100, OSMO))]);
+
+    let owner = "owner";
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validator = contract.activate_validators(["validator1"])[0];
+
+    vault
+        .bond()
+        .with_funds(&coins(300, OSMO))
+        .call(seller)
+        .unwrap();
+    vault.stake(&contract, seller, validator, coin(200, OSMO));
+
+    let unstake = |amount| {
+        contract
+            .unstake(validator.to_string(), coin(amount, OSMO))
+            .call(seller)
+            .unwrap();
+        contract
+            .test_methods_proxy()
+            .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+            .call("test")
+            .unwrap();
+    };
+    unstake(50);
+
+    // Only existing pending unbonds can be listed
+    let err = contract
+        .list_unbond_for_sale(validator.to_string(), 1, Uint128::new(40))
+        .call(seller)
+        .unwrap_err();
+    assert_eq!(err, ContractError::UnbondNotFound(1));
+
+    // Cancelled listings can't be bought
+    contract
+        .list_unbond_for_sale(validator.to_string(), 0, Uint128::new(40))
+        .call(seller)
+        .unwrap();
+    contract
+        .cancel_unbond_sale(validator.to_string(), 0)
+        .call(seller)
+        .unwrap();
+
+    let err = contract
+        .buy_unbond(seller.to_owned(), validator.to_string(), 0)
+        .with_funds(&coins(40, OSMO))
+        .call(buyer)
+        .unwrap_err();
+    assert_eq!(err, ContractError::UnbondNotForSale(0));
+
+    let err = contract
+        .cancel_unbond_sale(validator.to_string(), 0)
+        .call(seller)
+        .unwrap_err();
+    assert_eq!(err, ContractError::UnbondNotForSale(0));
+
+    // Unstaking more in the same block changes the listed pending unbond
+    contract
+        .list_unbond_for_sale(validator.to_string(), 0, Uint128::new(40))
+        .call(seller)
+        .unwrap();
+    unstake(10);
+
+    let err = contract
+        .buy_unbond(seller.to_owned(), validator.to_string(), 0)
+        .with_funds(&coins(40, OSMO))
+        .call(buyer)
+        .unwrap_err();
+    assert_eq!(err, ContractError::UnbondListingOutdated(0));
+
+    // Released pending unbonds can't be sold anymore
+    app.app_mut().update_block(|block| {
+        block.height += 1;
+        block.time = block.time.plus_seconds(100);
+    });
+
+    let err = contract
+        .buy_unbond(seller.to_owned(), validator.to_string(), 0)
+        .with_funds(&coins(40, OSMO))
+        .call(buyer)
+        .unwrap_err();
+    assert_eq!(err, ContractError::UnbondReleased(0));
+
+    let err = contract
+        .list_unbond_for_sale(validator.to_string(), 0, Uint128::new(40))
+        .call(seller)
+        .unwrap_err();
+    assert_eq!(err, ContractError::UnbondReleased(0));
+
+    // Nothing moved
+    let balance = app.app().wrap().query_balance(buyer, OSMO).unwrap();
+    assert_eq!(balance.amount.u128(), 100);
+    let stake = contract
+        .stake(seller.to_string(), validator.to_string())
+        .unwrap();
+    assert_eq!(stake.pending_unbonds.len(), 1);
+    assert_eq!(stake.pending_unbonds[0].amount.u128(), 60);
+}
+
+#[test]
+fn withdrawable() {
+    let user = "user1";
+
+    let app = App::new_with_balances(&[(user, &coins(300, OSMO))]);
+
+    let owner = "owner";
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validators = contract.activate_validators(["validator1", "validator2"]);
+
+    vault
+        .bond()
+        .with_funds(&coins(300, OSMO))
+        .call(user)
+        .unwrap();
+    vault.stake(&contract, user, validators[0], coin(200, OSMO));
+    vault.stake(&contract, user, validators[1], coin(100, OSMO));
+
+    let unstake = |validator: &str, amount| {
+        contract
+            .unstake(validator.to_string(), coin(amount, OSMO))
+            .call(user)
+            .unwrap();
+        contract
+            .test_methods_proxy()
+            .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+            .call("test")
+            .unwrap();
+    };
+    let skip_time = |seconds| {
+        app.app_mut().update_block(|block| {
+            block.height += 1;
+            block.time = block.time.plus_seconds(seconds);
+        })
+    };
+
+    unstake(validators[0], 20);
+    unstake(validators[1], 30);
+    assert_eq!(contract.withdrawable(user.to_owned()).unwrap().u128(), 0);
+
+    skip_time(50);
+    unstake(validators[0], 40);
+
+    // Only the first unbonds are released
+    skip_time(50);
+    assert_eq!(contract.withdrawable(user.to_owned()).unwrap().u128(), 50);
+    // Querying doesn't release anything
+    assert_eq!(contract.withdrawable(user.to_owned()).unwrap().u128(), 50);
+
+    contract.withdraw_unbonded(None).call(user).unwrap();
+    assert_eq!(contract.withdrawable(user.to_owned()).unwrap().u128(), 0);
+
+    skip_time(50);
+    assert_eq!(contract.withdrawable(user.to_owned()).unwrap().u128(), 40);
+    assert_eq!(contract.withdrawable("other".to_owned()).unwrap().u128(), 0);
+}
+
+#[test]
+fn withdraw_unbonded_for() {
+    let user = "user1";
+    let keeper = "keeper";
+
+    let app = App::new_with_balances(&[(user, &coins(300, OSMO))]);
+
+    let owner = "owner";
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validators = contract.activate_validators(["validator1", "validator2"]);
+
+    vault
+        .bond()
+        .with_funds(&coins(300, OSMO))
+        .call(user)
+        .unwrap();
+    vault.stake(&contract, user, validators[0], coin(200, OSMO));
+    vault.stake(&contract, user, validators[1], coin(100, OSMO));
+
+    for (validator, amount) in [(validators[0], 20), (validators[1], 30)] {
+        contract
+            .unstake(validator.to_string(), coin(amount, OSMO))
+            .call(user)
+            .unwrap();
+        contract
+            .test_methods_proxy()
+            .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+            .call("test")
+            .unwrap();
+    }
+    let free = |vault: &VaultContractProxy<MtApp>| {
+        vault
+            .account(user.to_owned())
+            .unwrap()
+            .free
+            .val()
+            .unwrap()
+            .u128()
+    };
+    assert_eq!(free(&vault), 0);
+
+    // Nothing matured yet
+    contract
+        .withdraw_unbonded_for(user.to_owned(), None)
+        .call(keeper)
+        .unwrap();
+    assert_eq!(free(&vault), 0);
+
+    app.app_mut().update_block(|block| {
+        block.height += 1;
+        block.time = block.time.plus_seconds(100);
+    });
+
+    // A third party cranks the matured unbonds of a single validator, duplicates are ignored
+    contract
+        .withdraw_unbonded_for(
+            user.to_owned(),
+            Some(vec![
+                validators[1].to_owned(),
+                validators[1].to_owned(),
+                "unknown".to_owned(),
+            ]),
+        )
+        .call(keeper)
+        .unwrap();
+    assert_eq!(free(&vault), 30);
+    assert_eq!(contract.withdrawable(user.to_owned()).unwrap().u128(), 20);
+
+    // Then the remaining ones
+    contract
+        .withdraw_unbonded_for(user.to_owned(), None)
+        .call(keeper)
+        .unwrap();
+    assert_eq!(free(&vault), 50);
+    assert_eq!(contract.withdrawable(user.to_owned()).unwrap().u128(), 0);
+
+    // The keeper doesn't get anything
+    assert_eq!(app.app().wrap().query_all_balances(keeper).unwrap(), vec![]);
+}
+
+#[test]
+fn user_meta() {
+    let owner = "owner";
+    let user = "user1";
+    let remote = "remote1";
+
+    let app = App::new_with_balances(&[(user, &coins(300, OSMO)), (owner, &coins(1000, STAR))]);
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validators = contract.activate_validators(["validator1", "validator2"]);
+
+    let skip_time = |seconds| {
+        app.app_mut().update_block(|block| {
+            block.height += 1;
+            block.time = block.time.plus_seconds(seconds);
+        })
+    };
+    let now = || app.app().block_info().time;
+
+    // Unknown user
+    let meta = contract.user_meta(user.to_owned()).unwrap();
+    assert_eq!(meta, UserMeta::default());
+
+    vault
+        .bond()
+        .with_funds(&coins(300, OSMO))
+        .call(user)
+        .unwrap();
+    vault.stake(&contract, user, validators[0], coin(200, OSMO));
+    let first_stake_at = now();
+    let meta = contract.user_meta(user.to_owned()).unwrap();
+    assert_eq!(meta.first_stake_at, Some(first_stake_at));
+    assert_eq!(meta.last_activity_at, Some(first_stake_at));
+
+    // Further stakes only update the last activity
+    skip_time(50);
+    vault.stake(&contract, user, validators[1], coin(100, OSMO));
+    let meta = contract.user_meta(user.to_owned()).unwrap();
+    assert_eq!(meta.first_stake_at, Some(first_stake_at));
+    assert_eq!(meta.last_activity_at, Some(now()));
+
+    skip_time(50);
+    contract
+        .unstake(validators[0].to_string(), coin(20, OSMO))
+        .call(user)
+        .unwrap();
+    let meta = contract.user_meta(user.to_owned()).unwrap();
+    assert_eq!(meta.first_stake_at, Some(first_stake_at));
+    assert_eq!(meta.last_activity_at, Some(now()));
+
+    skip_time(50);
+    contract
+        .test_methods_proxy()
+        .test_distribute_rewards(validators[0].to_owned(), coin(10, STAR))
+        .call(owner)
+        .unwrap();
+    contract
+        .withdraw_rewards(validators[0].to_owned(), remote.to_owned())
+        .call(user)
+        .unwrap();
+    let meta = contract.user_meta(user.to_owned()).unwrap();
+    assert_eq!(meta.first_stake_at, Some(first_stake_at));
+    assert_eq!(meta.last_activity_at, Some(now()));
+
+    // Also in the stakes response
+    let stakes = contract
+        .stakes(user.to_owned(), None, None, None, None, None)
+        .unwrap();
+    assert_eq!(stakes.first_stake_at, meta.first_stake_at);
+    assert_eq!(stakes.last_activity_at, meta.last_activity_at);
+    let stakes = contract
+        .stakes("other".to_owned(), None, None, None, None, None)
+        .unwrap();
+    assert_eq!(stakes.first_stake_at, None);
+    assert_eq!(stakes.last_activity_at, None);
+}
+
+#[test]
+fn unstaking_same_block_merges_unbonds() {
+    let user = "user1";
+
+    let app = App::new_with_balances(&[(user, &coins(300, OSMO))]);
+
+    let owner = "owner";
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validator = contract.activate_validators(["validator1"])[0];
+
+    vault
+        .bond()
+        .with_funds(&coins(300, OSMO))
+        .call(user)
+        .unwrap();
+
+    vault.stake(&contract, user, validator, coin(200, OSMO));
+
+    // Two unstakes committed in the same block
+    for amount in [20, 30] {
+        contract
+            .unstake(validator.to_string(), coin(amount, OSMO))
+            .call(user)
+            .unwrap();
+        contract
+            .test_methods_proxy()
+            .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+            .call("test")
+            .unwrap();
+    }
+
+    // A single merged pending unbond is created
+    let stake = contract
+        .stake(user.to_string(), validator.to_string())
+        .unwrap();
+    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(150)));
+    assert_eq!(stake.pending_unbonds.len(), 1);
+    assert_eq!(stake.pending_unbonds[0].amount, Uint128::new(50));
+
+    // Unstake in a later block is kept apart
+    app.app_mut().update_block(|block| {
+        block.height += 1;
+        block.time = block.time.plus_seconds(10);
+    });
+    contract
+        .unstake(validator.to_string(), coin(10, OSMO))
+        .call(user)
+        .unwrap();
+    contract
+        .test_methods_proxy()
+        .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
+
+    let stake = contract
+        .stake(user.to_string(), validator.to_string())
+        .unwrap();
+    assert_eq!(stake.pending_unbonds.len(), 2);
+    assert_eq!(stake.pending_unbonds[1].amount, Uint128::new(10));
+
+    // Compacting is a no-op, as there are no duplicates left
+    contract
+        .compact_unbonds(user.to_string(), validator.to_string())
+        .call(owner)
+        .unwrap();
+    let compacted = contract
+        .stake(user.to_string(), validator.to_string())
+        .unwrap();
+    assert_eq!(compacted, stake);
+}
+
+#[test]
+fn unbond_requested_and_released() {
+    let user = "user1";
+
+    let app = App::new_with_balances(&[(user, &coins(300, OSMO))]);
+
+    let owner = "owner";
+    let unbonding_period = 100;
+
+    let (vault, contract) = setup(&app, owner, unbonding_period).unwrap();
+
+    let validator = contract.activate_validators(["validator1"])[0];
+
+    vault
+        .bond()
+        .with_funds(&coins(300, OSMO))
+        .call(user)
+        .unwrap();
+    vault.stake(&contract, user, validator, coin(200, OSMO));
+
+    let relay_latency = 30;
+    contract
+        .update_relay_latency(Some(relay_latency))
+        .call(user)
+        .unwrap_err();
+    contract
+        .update_relay_latency(Some(relay_latency))
+        .call(owner)
+        .unwrap();
+    assert_eq!(
+        contract.config().unwrap().relay_latency,
+        Some(relay_latency)
+    );
+
+    let requested_at = app.app().block_info().time;
+    contract
+        .unstake(validator.to_string(), coin(50, OSMO))
+        .call(user)
+        .unwrap();
+    let single_tx = get_last_external_staking_pending_tx_id(&contract).unwrap();
+    contract
+        .unstake_batch(vec![(validator.to_string(), coin(20, OSMO))])
+        .call(user)
+        .unwrap();
+    let batch_tx = get_last_external_staking_pending_tx_id(&contract).unwrap();
+
+    let estimated = contract
+        .estimated_release(user.to_owned(), validator.to_owned())
+        .unwrap();
+    let release_at = requested_at.plus_seconds(relay_latency + unbonding_period);
+    assert_eq!(
+        estimated.unstakes,
+        [
+            EstimatedRelease {
+                tx_id: single_tx,
+                amount: Uint128::new(50),
+                requested_at,
+                release_at,
+            },
+            EstimatedRelease {
+                tx_id: batch_tx,
+                amount: Uint128::new(20),
+                requested_at,
+                release_at,
+            },
+        ]
+    );
+
+    // The unstakes are relayed later than estimated
+    app.app_mut().update_block(|block| {
+        block.height += 8;
+        block.time = block.time.plus_seconds(40);
+    });
+    let committed_at = app.app().block_info().time;
+    let estimated = contract
+        .estimated_release(user.to_owned(), validator.to_owned())
+        .unwrap();
+    assert_eq!(
+        estimated.unstakes[0].release_at,
+        committed_at.plus_seconds(relay_latency + unbonding_period)
+    );
+
+    for tx_id in [single_tx, batch_tx] {
+        contract
+            .test_methods_proxy()
+            .test_commit_unstake(tx_id)
+            .call("test")
+            .unwrap();
+    }
+
+    // The unbonding period starts on commit, the request time is kept
+    let stake = contract
+        .stake(user.to_string(), validator.to_string())
+        .unwrap();
+    assert_eq!(
+        stake.pending_unbonds,
+        [PendingUnbond {
+            amount: Uint128::new(70),
+            requested_at,
+            release_at: committed_at.plus_seconds(unbonding_period),
+        }]
+    );
+    let estimated = contract
+        .estimated_release(user.to_owned(), validator.to_owned())
+        .unwrap();
+    assert_eq!(estimated.unstakes, []);
+}
+
+#[test]
+fn unlock_schedule() {
+    let user = "user1";
+
+    let app = App::new_with_balances(&[(user, &coins(300, OSMO))]);
+
+    let owner = "owner";
+    let unbonding_period = 100;
+
+    let (vault, contract) = setup(&app, owner, unbonding_period).unwrap();
+
+    let validators = contract.activate_validators(["validator1", "validator2"]);
+
+    vault
+        .bond()
+        .with_funds(&coins(300, OSMO))
+        .call(user)
+        .unwrap();
+    vault.stake(&contract, user, validators[0], coin(100, OSMO));
+    vault.stake(&contract, user, validators[1], coin(100, OSMO));
+
+    let unstake = |validator: &str, amount: u128| {
+        contract
+            .unstake(validator.to_string(), coin(amount, OSMO))
+            .call(user)
+            .unwrap();
+        contract
+            .test_methods_proxy()
+            .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+            .call("test")
+            .unwrap();
+        app.app_mut().update_block(|block| {
+            block.height += 2;
+            block.time = block.time.plus_seconds(10);
+        });
+    };
+
+    // Unbonds from both validators are interleaved in time
+    let start = app.app().block_info().time;
+    unstake(validators[1], 10);
+    unstake(validators[0], 20);
+    unstake(validators[1], 30);
+
+    let schedule = contract.unlock_schedule(user.to_owned(), None).unwrap();
+    assert_eq!(
+        schedule.unlocks,
+        [
+            ScheduledUnlock {
+                validator: validators[1].to_owned(),
+                amount: Uint128::new(10),
+                release_at: start.plus_seconds(unbonding_period),
+            },
+            ScheduledUnlock {
+                validator: validators[0].to_owned(),
+                amount: Uint128::new(20),
+                release_at: start.plus_seconds(unbonding_period + 10),
+            },
+            ScheduledUnlock {
+                validator: validators[1].to_owned(),
+                amount: Uint128::new(30),
+                release_at: start.plus_seconds(unbonding_period + 20),
+            },
+        ]
+    );
+
+    // Other users have nothing to unlock
+    let schedule = contract.unlock_schedule("user2".to_owned(), None).unwrap();
+    assert_eq!(schedule.unlocks, []);
+}
+
+#[test]
+fn distribution() {
+    let owner = "owner";
+    let users = ["user1", "user2"];
+    let remote = ["remote1", "remote2"];
+
+    let app = App::new_with_balances(&[
+        (users[0], &coins(600, OSMO)),
+        (users[1], &coins(600, OSMO)),
+        (owner, &[coin(1000, STAR), coin(1000, OSMO)]),
+    ]);
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validators = contract.activate_validators(["validator1", "validator2"]);
+
+    // Weights proportion:
+    // 2/5 of validators[0] to users[0]
+    // 3/5 of validators[0] to users[1]
+    // all of validators[1] to users[1]
+    vault
+        .bond()
+        .with_funds(&coins(600, OSMO))
+        .call(users[0])
+        .unwrap();
+
+    vault
+        .bond()
+        .with_funds(&coins(600, OSMO))
+        .call(users[1])
+        .unwrap();
+
+    vault.stake(&contract, users[0], validators[0], coin(200, OSMO));
+    vault.stake(&contract, users[0], validators[1], coin(100, OSMO));
+    vault.stake(&contract, users[1], validators[0], coin(300, OSMO));
+
+    // Start with equal distribution:
+    // 20 tokens for users[0]
+    // 30 tokens for users[1]
+    contract
+        .test_methods_proxy()
+        .test_distribute_rewards(validators[0].to_owned(), coin(50, STAR))
+        .call(owner)
+        .unwrap();
+
+    // Only users[0] stakes on validators[1]
+    // 30 tokens for users[0]
+    contract
+        .test_methods_proxy()
+        .test_distribute_rewards(validators[1].to_owned(), coin(30, STAR))
+        .call(owner)
+        .unwrap();
+
+    // Check how much rewards are pending for withdrawal
+    let rewards = contract
+        .pending_rewards(users[0].to_owned(), validators[0].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards, [coin(20, STAR)]);
+
+    let rewards = contract
+        .pending_rewards(users[1].to_owned(), validators[0].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards, [coin(30, STAR)]);
+
+    let rewards = contract
+        .pending_rewards(users[0].to_owned(), validators[1].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards, [coin(30, STAR)]);
+
+    let rewards = contract
+        .pending_rewards(users[1].to_owned(), validators[1].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards, [coin(0, STAR)]);
+
+    // Show all rewards skips validators that were never staked on
+    let all_rewards = contract
+        .all_pending_rewards(users[0].to_owned(), None, None)
+        .unwrap();
+    let expected = vec![
+        ValidatorPendingRewards::new(validators[0], 20, STAR),
+        ValidatorPendingRewards::new(validators[1], 30, STAR),
+    ];
+    assert_eq!(all_rewards.rewards, expected);
+
+    let all_rewards = contract
+        .all_pending_rewards(users[1].to_owned(), None, None)
+        .unwrap();
+    let expected = vec![ValidatorPendingRewards::new(validators[0], 30, STAR)];
+    assert_eq!(all_rewards.rewards, expected);
+
+    // Some more distribution, this time not divisible by total staken tokens
+    // 28 tokens for users[0]
+    // 42 tokens for users[1]
+    // 1 token is not distributed
+    contract
+        .test_methods_proxy()
+        .test_distribute_rewards(validators[0].to_owned(), coin(71, STAR))
+        .call(owner)
+        .unwrap();
+
+    // Distribution in invalid coin should fail
+    contract
+        .test_methods_proxy()
+        .test_distribute_rewards(validators[1].to_owned(), coin(100, OSMO))
+        .call(owner)
+        .unwrap_err();
+
+    // Check how much rewards are pending for withdrawal
+    let rewards = contract
+        .pending_rewards(users[0].to_owned(), validators[0].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards, [coin(48, STAR)]);
+
+    let rewards = contract
+        .pending_rewards(users[1].to_owned(), validators[0].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards, [coin(72, STAR)]);
+
+    let rewards = contract
+        .pending_rewards(users[0].to_owned(), validators[1].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards, [coin(30, STAR)]);
+
+    let rewards = contract
+        .pending_rewards(users[1].to_owned(), validators[1].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards, [coin(0, STAR)]);
+
+    // Withdraw rewards
+    contract
+        .withdraw_rewards(validators[0].to_owned(), remote[0].to_owned())
+        .call(users[0])
+        .unwrap();
+
+    let tx_id = get_last_external_staking_pending_tx_id(&contract).unwrap();
+    contract
+        .test_methods_proxy()
+        .test_commit_withdraw_rewards(tx_id)
+        .call(users[0])
+        .unwrap();
+
+    contract
+        .withdraw_rewards(validators[1].to_owned(), remote[0].to_owned())
+        .call(users[0])
+        .unwrap();
+    let tx_id = get_last_external_staking_pending_tx_id(&contract).unwrap();
+    contract
+        .test_methods_proxy()
+        .test_commit_withdraw_rewards(tx_id)
+        .call(users[0])
+        .unwrap();
+
+    contract
+        .withdraw_rewards(validators[0].to_owned(), remote[1].to_owned())
+        .call(users[1])
+        .unwrap();
+    let tx_id = get_last_external_staking_pending_tx_id(&contract).unwrap();
+    contract
+        .test_methods_proxy()
+        .test_commit_withdraw_rewards(tx_id)
+        .call(users[1])
+        .unwrap();
+
+    // Rewards withrawal should not affect the stake
+    let stake = contract
+        .stake(users[0].to_owned(), validators[0].to_owned())
+        .unwrap();
+    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(200)));
+
+    let stake = contract
+        .stake(users[0].to_owned(), validators[1].to_owned())
+        .unwrap();
+    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(100)));
+
+    let stake = contract
+        .stake(users[1].to_owned(), validators[0].to_owned())
+        .unwrap();
+    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(300)));
+
+    // error if 0 rewards available
+    let err = contract
+        .withdraw_rewards(validators[1].to_owned(), remote[1].to_owned())
+        .call(users[1])
+        .unwrap_err();
+    assert_eq!(err, ContractError::NoRewards);
+    let tx_id = get_last_external_staking_pending_tx_id(&contract);
+    assert_eq!(tx_id, None);
+
+    // Stake remains unaffected after rewards withdrawal failure
+    let stake = contract
+        .stake(users[1].to_owned(), validators[1].to_owned())
+        .unwrap();
+    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(0)));
+
+    // Rewards should not be withdrawable anymore
+    let rewards = contract
+        .pending_rewards(users[0].to_owned(), validators[0].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards, [coin(0, STAR)]);
+
+    let rewards = contract
+        .pending_rewards(users[1].to_owned(), validators[0].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards, [coin(0, STAR)]);
+
+    let rewards = contract
+        .pending_rewards(users[0].to_owned(), validators[1].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards, [coin(0, STAR)]);
+
+    let rewards = contract
+        .pending_rewards(users[1].to_owned(), validators[1].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards, [coin(0, STAR)]);
+
+    // Another distribution - making it equal
+    // 4 on users[0]
+    // 6 on users[1]
+    //
+    // The additional 1 token is leftover after previous allocation
+    contract
+        .test_methods_proxy()
+        .test_distribute_rewards(validators[0].to_owned(), coin(9, STAR))
+        .call(owner)
+        .unwrap();
+
+    let rewards = contract
+        .pending_rewards(users[0].to_owned(), validators[0].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards, [coin(4, STAR)]);
+
+    let rewards = contract
+        .pending_rewards(users[1].to_owned(), validators[0].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards, [coin(6, STAR)]);
+
+    // Now yet another unequal distribution to play around keeping all correct when weights are
+    // changing
+    //
+    // 4 on users[0] (+ ~0.4)
+    // 6 on users[1] (+ ~0.6)
+    contract
+        .test_methods_proxy()
+        .test_distribute_rewards(validators[0].to_owned(), coin(11, STAR))
+        .call(owner)
+        .unwrap();
+
+    // Also leaving some rewards on validator[1]
+    //
+    // 11 on users[0]
+    contract
+        .test_methods_proxy()
+        .test_distribute_rewards(validators[1].to_owned(), coin(11, STAR))
+        .call(owner)
+        .unwrap();
+
+    // Unstaking some funds from validator should change weights - now users split validators[0]
+    // 50/50
+    //
+    // 200 tokens staken by user[0]
+    // 200 tokens staken by user[1]
+    contract
+        .unstake(validators[0].to_owned(), coin(100, OSMO))
+        .call(users[1])
+        .unwrap();
+    contract
+        .test_methods_proxy()
+        .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
+
+    // Staking also changes weights - now validators[1] also splits rewards:
+    // 1/4 for users[0]
+    // 3/4 for users[1]
+    //
+    // 100 tokens staken by user[0]
+    // 300 tokens staken by user[1]
+    vault
+        .stake_remote(
+            contract.contract_addr.to_string(),
+            coin(300, OSMO),
+            to_binary(&ReceiveVirtualStake {
+                validator: validators[1].to_string(),
+            })
+            .unwrap(),
+            None,
+        )
+        .call(users[1])
+        .unwrap();
+    contract
+        .test_methods_proxy()
+        .test_commit_stake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
+
+    // Check if messing up with weights didn't affect withdrawable
+    let rewards = contract
+        .pending_rewards(users[0].to_owned(), validators[0].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards, [coin(8, STAR)]);
+
+    let rewards = contract
+        .pending_rewards(users[1].to_owned(), validators[0].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards, [coin(12, STAR)]);
+
+    let rewards = contract
+        .pending_rewards(users[0].to_owned(), validators[1].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards, [coin(11, STAR)]);
+
+    let rewards = contract
+        .pending_rewards(users[1].to_owned(), validators[1].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards, [coin(0, STAR)]);
+
+    // Now distribute some nice values
+    // 10 on users[0] (~0.4 still not distributed)
+    // 10 on users[1] (~0.6 still not distributed)
+    contract
+        .test_methods_proxy()
+        .test_distribute_rewards(validators[0].to_owned(), coin(20, STAR))
+        .call(owner)
+        .unwrap();
+
+    // Also for validator[1]
+    // 10 on users[1]
+    // 30 on users[2]
+    contract
+        .test_methods_proxy()
+        .test_distribute_rewards(validators[1].to_owned(), coin(40, STAR))
+        .call(owner)
+        .unwrap();
+
+    let rewards = contract
+        .pending_rewards(users[0].to_owned(), validators[0].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards, [coin(18, STAR)]);
+
+    let rewards = contract
+        .pending_rewards(users[1].to_owned(), validators[0].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards, [coin(22, STAR)]);
+
+    let rewards = contract
+        .pending_rewards(users[0].to_owned(), validators[1].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards, [coin(21, STAR)]);
+
+    let rewards = contract
+        .pending_rewards(users[1].to_owned(), validators[1].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards, [coin(30, STAR)]);
+
+    // And some more distribution fun - we are 50/50 on validators[1], so distributing odd number of
+    // coins
+    // 2 for users[0] (+ ~0.5 from this distribution + ~0.4 accumulated -> ~0.9 tokens should be
+    //   here)
+    // 3 for users[1] (+ ~0.5 from this distribution + ~0.6 accumulated -> ~1.1 tokens, we give one
+    //   back leaving ~0.1 accumulated)
+    contract
+        .test_methods_proxy()
+        .test_distribute_rewards(validators[0].to_owned(), coin(5, STAR))
+        .call(owner)
+        .unwrap();
+
+    let rewards = contract
+        .pending_rewards(users[0].to_owned(), validators[0].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards, [coin(20, STAR)]);
+
+    let rewards = contract
+        .pending_rewards(users[1].to_owned(), validators[0].to_owned())
+        .unwrap()
         .rewards;
-    assert_eq!(rewards.amount.u128(), 48);
+    assert_eq!(rewards, [coin(25, STAR)]);
+
+    // More unstaking - to make it both ways by both stakers on at least one validator, for sake of
+    // funny error accumulation issues. After two following unstakes, staking on validators[0] is as
+    // follows:
+    //
+    // 150 tokens staken by users[0]
+    // 100 tokens staken by users[1]
+    //
+    // Rewards distribution:
+    //
+    // 3/5 rewards to users[0]
+    // 2/5 rewards to users[1]
+    contract
+        .unstake(validators[0].to_owned(), coin(50, OSMO))
+        .call(users[0])
+        .unwrap();
+    contract
+        .test_methods_proxy()
+        .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
 
-    let rewards = contract
-        .pending_rewards(users[1].to_owned(), validators[0].to_owned())
-        .unwrap()
-        .rewards;
-    assert_eq!(rewards.amount.u128(), 72);
+    contract
+        .unstake(validators[0].to_owned(), coin(100, OSMO))
+        .call(users[1])
+        .unwrap();
+    contract
+        .test_methods_proxy()
+        .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
+
+    // Distribute 12 tokens to validator[0]:
+    //
+    // 7 + 1 = 8 to users[0] (~0.9 accumulated + ~0.2 = ~1.1 leftover, 1.0 payed back, ~0.1 accumulated)
+    // 4 to users[0] (~0.1 accumulated + ~0.8 -> leaving at ~0.9)
+    contract
+        .test_methods_proxy()
+        .test_distribute_rewards(validators[0].to_owned(), coin(12, STAR))
+        .call(owner)
+        .unwrap();
 
     let rewards = contract
-        .pending_rewards(users[0].to_owned(), validators[1].to_owned())
+        .pending_rewards(users[0].to_owned(), validators[0].to_owned())
         .unwrap()
         .rewards;
-    assert_eq!(rewards.amount.u128(), 30);
+    assert_eq!(rewards, [coin(28, STAR)]);
 
     let rewards = contract
-        .pending_rewards(users[1].to_owned(), validators[1].to_owned())
+        .pending_rewards(users[1].to_owned(), validators[0].to_owned())
         .unwrap()
         .rewards;
-    assert_eq!(rewards.amount.u128(), 0);
+    assert_eq!(rewards, [coin(29, STAR)]);
 
-    // Withdraw rewards
+    // Withdraw only by users[0]
     contract
         .withdraw_rewards(validators[0].to_owned(), remote[0].to_owned())
         .call(users[0])
         .unwrap();
-
     let tx_id = get_last_external_staking_pending_tx_id(&contract).unwrap();
     contract
         .test_methods_proxy()
@@ -613,14 +2887,15 @@ fn distribution() {
         .call(users[0])
         .unwrap();
 
+    // Rollback on users[1]
     contract
-        .withdraw_rewards(validators[0].to_owned(), remote[1].to_owned())
+        .withdraw_rewards(validators[0].to_owned(), "bad_value".to_owned())
         .call(users[1])
         .unwrap();
     let tx_id = get_last_external_staking_pending_tx_id(&contract).unwrap();
     contract
         .test_methods_proxy()
-        .test_commit_withdraw_rewards(tx_id)
+        .test_rollback_withdraw_rewards(tx_id)
         .call(users[1])
         .unwrap();
 
@@ -628,7 +2903,7 @@ fn distribution() {
     let stake = contract
         .stake(users[0].to_owned(), validators[0].to_owned())
         .unwrap();
-    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(200)));
+    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(150)));
 
     let stake = contract
         .stake(users[0].to_owned(), validators[1].to_owned())
@@ -638,458 +2913,927 @@ fn distribution() {
     let stake = contract
         .stake(users[1].to_owned(), validators[0].to_owned())
         .unwrap();
-    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(300)));
-
-    // error if 0 rewards available
-    let err = contract
-        .withdraw_rewards(validators[1].to_owned(), remote[1].to_owned())
-        .call(users[1])
-        .unwrap_err();
-    assert_eq!(err, ContractError::NoRewards);
-    let tx_id = get_last_external_staking_pending_tx_id(&contract);
-    assert_eq!(tx_id, None);
+    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(100)));
 
-    // Stake remains unaffected after rewards withdrawal failure
     let stake = contract
         .stake(users[1].to_owned(), validators[1].to_owned())
         .unwrap();
-    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(0)));
+    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(300)));
 
-    // Rewards should not be withdrawable anymore
+    // Check withdrawals and accounts
+    let rewards = contract
+        .pending_rewards(users[0].to_owned(), validators[0].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards, [coin(0, STAR)]);
+
+    let rewards = contract
+        .pending_rewards(users[1].to_owned(), validators[0].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards, [coin(29, STAR)]);
+
+    let rewards = contract
+        .pending_rewards(users[0].to_owned(), validators[1].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards, [coin(0, STAR)]);
+
+    let rewards = contract
+        .pending_rewards(users[1].to_owned(), validators[1].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards, [coin(30, STAR)]);
+
+    // Final distribution - 10 tokens to both validators
+    // 6 tokens to users[0] via validators[0] (leftover as it was)
+    // 4 tokens to users[1] via validators[0] (leftover as it was)
+    // 2 tokens to users[0] via validators[1] (~0.5 leftover)
+    // 7 tokens to users[1] via validators[1] (~0.5 lefover)
+    contract
+        .test_methods_proxy()
+        .test_distribute_rewards(validators[0].to_owned(), coin(10, STAR))
+        .call(owner)
+        .unwrap();
+
+    contract
+        .test_methods_proxy()
+        .test_distribute_rewards(validators[1].to_owned(), coin(10, STAR))
+        .call(owner)
+        .unwrap();
+
+    // Check withdrawals and accounts
     let rewards = contract
         .pending_rewards(users[0].to_owned(), validators[0].to_owned())
         .unwrap()
         .rewards;
-    assert_eq!(rewards.amount.u128(), 0);
+    assert_eq!(rewards, [coin(6, STAR)]);
 
     let rewards = contract
         .pending_rewards(users[1].to_owned(), validators[0].to_owned())
         .unwrap()
         .rewards;
-    assert_eq!(rewards.amount.u128(), 0);
+    assert_eq!(rewards, [coin(33, STAR)]);
 
     let rewards = contract
         .pending_rewards(users[0].to_owned(), validators[1].to_owned())
         .unwrap()
         .rewards;
-    assert_eq!(rewards.amount.u128(), 0);
+    assert_eq!(rewards, [coin(2, STAR)]);
 
     let rewards = contract
         .pending_rewards(users[1].to_owned(), validators[1].to_owned())
         .unwrap()
         .rewards;
-    assert_eq!(rewards.amount.u128(), 0);
+    assert_eq!(rewards, [coin(37, STAR)]);
+
+    let all_rewards = contract
+        .all_pending_rewards(users[0].to_owned(), None, None)
+        .unwrap();
+    let expected = vec![
+        ValidatorPendingRewards::new(validators[0], 6, STAR),
+        ValidatorPendingRewards::new(validators[1], 2, STAR),
+    ];
+    assert_eq!(all_rewards.rewards, expected);
+
+    let all_rewards = contract
+        .all_pending_rewards(users[1].to_owned(), None, None)
+        .unwrap();
+    let expected = vec![
+        ValidatorPendingRewards::new(validators[0], 33, STAR),
+        ValidatorPendingRewards::new(validators[1], 37, STAR),
+    ];
+    assert_eq!(all_rewards.rewards, expected);
+
+    // And try to withdraw all, previous balances:
+    contract
+        .withdraw_rewards(validators[0].to_string(), remote[0].to_owned())
+        .call(users[0])
+        .unwrap();
+    let tx_id = get_last_external_staking_pending_tx_id(&contract).unwrap();
+    contract
+        .test_methods_proxy()
+        .test_commit_withdraw_rewards(tx_id)
+        .call(users[0])
+        .unwrap();
+
+    contract
+        .withdraw_rewards(validators[1].to_string(), remote[0].to_owned())
+        .call(users[0])
+        .unwrap();
+    let tx_id = get_last_external_staking_pending_tx_id(&contract).unwrap();
+    contract
+        .test_methods_proxy()
+        .test_commit_withdraw_rewards(tx_id)
+        .call(users[0])
+        .unwrap();
+
+    contract
+        .withdraw_rewards(validators[0].to_string(), remote[1].to_owned())
+        .call(users[1])
+        .unwrap();
+    let tx_id = get_last_external_staking_pending_tx_id(&contract).unwrap();
+    contract
+        .test_methods_proxy()
+        .test_commit_withdraw_rewards(tx_id)
+        .call(users[0])
+        .unwrap();
+
+    contract
+        .withdraw_rewards(validators[1].to_string(), remote[1].to_owned())
+        .call(users[1])
+        .unwrap();
+    let tx_id = get_last_external_staking_pending_tx_id(&contract).unwrap();
+    contract
+        .test_methods_proxy()
+        .test_commit_withdraw_rewards(tx_id)
+        .call(users[0])
+        .unwrap();
+}
+
+#[test]
+fn withdraw_all_rewards() {
+    let owner = "owner";
+    let user = "user1";
+    let remote = "remote1";
+
+    let app = App::new_with_balances(&[
+        (user, &coins(600, OSMO)),
+        (owner, &[coin(1000, STAR), coin(1000, OSMO)]),
+    ]);
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validators = contract.activate_validators(["validator1", "validator2", "validator3"]);
+
+    vault
+        .bond()
+        .with_funds(&coins(600, OSMO))
+        .call(user)
+        .unwrap();
+
+    for validator in validators {
+        vault.stake(&contract, user, validator, coin(100, OSMO));
+    }
+
+    // Nothing distributed yet
+    let err = contract
+        .withdraw_all_rewards(remote.to_owned())
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::NoRewards);
+
+    for (validator, amount) in validators.into_iter().zip([10, 20, 30]) {
+        contract
+            .test_methods_proxy()
+            .test_distribute_rewards(validator.to_owned(), coin(amount, STAR))
+            .call(owner)
+            .unwrap();
+    }
+
+    let resp = contract
+        .withdraw_all_rewards(remote.to_owned())
+        .call(user)
+        .unwrap();
+
+    // One event per validator, with its rewards
+    let withdrawn: Vec<_> = resp
+        .events
+        .iter()
+        .filter(|event| event.ty == "wasm-withdraw_rewards")
+        .map(|event| {
+            let attr = |key: &str| {
+                event
+                    .attributes
+                    .iter()
+                    .find(|attr| attr.key == key)
+                    .unwrap()
+                    .value
+                    .clone()
+            };
+            (attr("validator"), attr("amount"))
+        })
+        .collect();
+    let expected: Vec<_> = validators
+        .iter()
+        .zip(["10star", "20star", "30star"])
+        .map(|(validator, amount)| (validator.to_string(), amount.to_owned()))
+        .collect();
+    assert_eq!(withdrawn, expected);
+
+    // Along with the total
+    let total = resp
+        .events
+        .iter()
+        .filter(|event| event.ty == "wasm")
+        .flat_map(|event| &event.attributes)
+        .find(|attr| attr.key == "amount")
+        .unwrap();
+    assert_eq!(total.value, "60star");
+
+    // One transfer per validator
+    let txs = contract.all_pending_txs_desc(None, None).unwrap().txs;
+    assert_eq!(txs.len(), 3);
+    for tx in txs {
+        contract
+            .test_methods_proxy()
+            .test_commit_withdraw_rewards(tx.id())
+            .call(user)
+            .unwrap();
+    }
+
+    for validator in validators {
+        let rewards = contract
+            .pending_rewards(user.to_owned(), validator.to_owned())
+            .unwrap()
+            .rewards;
+        assert_eq!(rewards, [coin(0, STAR)]);
+    }
+
+    let err = contract
+        .withdraw_all_rewards(remote.to_owned())
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::NoRewards);
+}
+
+#[test]
+fn total_withdrawn() {
+    let owner = "owner";
+    let user = "user1";
+    let remote = "remote1";
+
+    let app = App::new_with_balances(&[
+        (user, &coins(600, OSMO)),
+        (owner, &[coin(1000, STAR), coin(1000, OSMO)]),
+    ]);
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validators = contract.activate_validators(["validator1", "validator2", "validator3"]);
+
+    vault
+        .bond()
+        .with_funds(&coins(600, OSMO))
+        .call(user)
+        .unwrap();
+
+    for validator in validators {
+        vault.stake(&contract, user, validator, coin(100, OSMO));
+    }
+
+    // Nothing withdrawn yet
+    let total = contract
+        .total_withdrawn(user.to_owned(), None, None)
+        .unwrap();
+    assert_eq!(total.withdrawn, []);
+    assert_eq!(total.cursor, None);
+
+    for (validator, amount) in validators.into_iter().zip([10, 20, 30]) {
+        contract
+            .test_methods_proxy()
+            .test_distribute_rewards(validator.to_owned(), coin(amount, STAR))
+            .call(owner)
+            .unwrap();
+    }
+
+    // Withdraw from the first two validators only
+    for validator in &validators[..2] {
+        contract
+            .withdraw_rewards(validator.to_string(), remote.to_owned())
+            .call(user)
+            .unwrap();
+        contract
+            .test_methods_proxy()
+            .test_commit_withdraw_rewards(
+                get_last_external_staking_pending_tx_id(&contract).unwrap(),
+            )
+            .call("test")
+            .unwrap();
+    }
+
+    let total = contract
+        .total_withdrawn(user.to_owned(), None, None)
+        .unwrap();
+    assert_eq!(total.withdrawn, [coin(30, STAR)]);
+    assert_eq!(total.cursor, None);
+
+    // Summed by pages
+    let first = contract
+        .total_withdrawn(user.to_owned(), None, Some(1))
+        .unwrap();
+    assert_eq!(first.withdrawn, [coin(10, STAR)]);
+    assert_eq!(first.cursor.as_deref(), Some(validators[0]));
+    let second = contract
+        .total_withdrawn(user.to_owned(), first.cursor, Some(2))
+        .unwrap();
+    assert_eq!(second.withdrawn, [coin(20, STAR)]);
+    assert_eq!(second.cursor, None);
+
+    // Closing a stake keeps its withdrawn rewards in the total
+    contract
+        .unstake(validators[0].to_owned(), coin(100, OSMO))
+        .call(user)
+        .unwrap();
+    contract
+        .test_methods_proxy()
+        .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
+    app.app_mut().update_block(|block| {
+        block.height += 1;
+        block.time = block.time.plus_seconds(100);
+    });
+    contract.withdraw_unbonded(None).call(user).unwrap();
+    let stakes = contract
+        .stakes(user.to_owned(), None, None, Some(true), None, None)
+        .unwrap();
+    assert_eq!(stakes.stakes.len(), 2);
+
+    let total = contract
+        .total_withdrawn(user.to_owned(), None, None)
+        .unwrap();
+    assert_eq!(total.withdrawn, [coin(30, STAR)]);
+}
+
+#[test]
+fn distribution_remainders_are_not_lost() {
+    let owner = "owner";
+    let users = ["user1", "user2", "user3"];
+    let remote = "remote";
+
+    let app = App::new_with_balances(&[
+        (users[0], &coins(100, OSMO)),
+        (users[1], &coins(200, OSMO)),
+        (users[2], &coins(400, OSMO)),
+        (owner, &[coin(1000, STAR), coin(1000, OSMO)]),
+    ]);
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validator = contract.activate_validators(["validator1"])[0];
+
+    for (user, amount) in users.into_iter().zip([100, 200, 400]) {
+        vault
+            .bond()
+            .with_funds(&coins(amount, OSMO))
+            .call(user)
+            .unwrap();
+        vault.stake(&contract, user, validator, coin(amount, OSMO));
+    }
+
+    // None of the amounts divides evenly between the 700 staked tokens
+    let distributions = [10, 33, 7, 101, 1, 58];
+    let mut withdrawn = [0u128; 3];
+    for amount in distributions {
+        contract
+            .test_methods_proxy()
+            .test_distribute_rewards(validator.to_owned(), coin(amount, STAR))
+            .call(owner)
+            .unwrap();
+
+        for (user, withdrawn) in users.iter().zip(&mut withdrawn) {
+            let rewards = contract
+                .pending_rewards(user.to_string(), validator.to_owned())
+                .unwrap()
+                .rewards;
+            let amount = rewards[0].amount.u128();
+            if amount == 0 {
+                continue;
+            }
+
+            contract
+                .withdraw_rewards(validator.to_owned(), remote.to_owned())
+                .call(user)
+                .unwrap();
+            let tx_id = get_last_external_staking_pending_tx_id(&contract).unwrap();
+            contract
+                .test_methods_proxy()
+                .test_commit_withdraw_rewards(tx_id)
+                .call(user)
+                .unwrap();
+            *withdrawn += amount;
+        }
+    }
+
+    // Each staker only misses the sub-unit remainder of their share of the total distributed
+    let distributed: u128 = distributions.iter().sum();
+    for (stake, withdrawn) in [100, 200, 400].into_iter().zip(withdrawn) {
+        assert_eq!(withdrawn, distributed * stake / 700);
+    }
+    let total_withdrawn: u128 = withdrawn.iter().sum();
+    assert!(distributed - total_withdrawn < users.len() as u128);
+}
+
+#[test]
+fn reward_debug() {
+    let owner = "owner";
+    let users = ["user1", "user2"];
+
+    let app = App::new_with_balances(&[
+        (users[0], &coins(300, OSMO)),
+        (users[1], &coins(100, OSMO)),
+        (owner, &coins(1000, STAR)),
+    ]);
 
-    // Another distribution - making it equal
-    // 4 on users[0]
-    // 6 on users[1]
-    //
-    // The additional 1 token is leftover after previous allocation
-    contract
-        .test_methods_proxy()
-        .test_distribute_rewards(validators[0].to_owned(), coin(9, STAR))
-        .call(owner)
-        .unwrap();
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
 
-    let rewards = contract
-        .pending_rewards(users[0].to_owned(), validators[0].to_owned())
-        .unwrap()
-        .rewards;
-    assert_eq!(rewards.amount.u128(), 4);
+    let validator = contract.activate_validators(["validator1"])[0];
 
-    let rewards = contract
-        .pending_rewards(users[1].to_owned(), validators[0].to_owned())
-        .unwrap()
-        .rewards;
-    assert_eq!(rewards.amount.u128(), 6);
+    // Nothing staked yet
+    let debug = contract
+        .reward_debug(users[0].to_owned(), validator.to_owned())
+        .unwrap();
+    assert_eq!(debug.stake, ValueRange::new_val(Uint128::zero()));
+    assert_eq!(debug.denoms.len(), 1);
+    assert_eq!(debug.denoms[0].denom, STAR);
+    assert_eq!(debug.denoms[0].points_per_stake, Uint256::zero());
 
-    // Now yet another unequal distribution to play around keeping all correct when weights are
-    // changing
-    //
-    // 4 on users[0] (+ ~0.4)
-    // 6 on users[1] (+ ~0.6)
-    contract
-        .test_methods_proxy()
-        .test_distribute_rewards(validators[0].to_owned(), coin(11, STAR))
-        .call(owner)
+    vault
+        .bond()
+        .with_funds(&coins(300, OSMO))
+        .call(users[0])
         .unwrap();
+    vault.stake(&contract, users[0], validator, coin(300, OSMO));
 
-    // Also leaving some rewards on validator[1]
-    //
-    // 11 on users[0]
+    // 10 * 10^9 points don't divide evenly between the 300 staked tokens
     contract
         .test_methods_proxy()
-        .test_distribute_rewards(validators[1].to_owned(), coin(11, STAR))
+        .test_distribute_rewards(validator.to_owned(), coin(10, STAR))
         .call(owner)
         .unwrap();
 
-    // Unstaking some funds from validator should change weights - now users split validators[0]
-    // 50/50
-    //
-    // 200 tokens staken by user[0]
-    // 200 tokens staken by user[1]
-    contract
-        .unstake(validators[0].to_owned(), coin(100, OSMO))
-        .call(users[1])
-        .unwrap();
-    contract
-        .test_methods_proxy()
-        .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
-        .call("test")
+    let points_per_stake = Uint256::from(33_333_333u128);
+
+    let debug = contract
+        .reward_debug(users[0].to_owned(), validator.to_owned())
         .unwrap();
+    assert_eq!(debug.stake, ValueRange::new_val(Uint128::new(300)));
+    let denom = &debug.denoms[0];
+    assert_eq!(denom.points_per_stake, points_per_stake);
+    assert_eq!(denom.points_leftover, Uint256::from(100u128));
+    assert_eq!(denom.reward_index, Uint256::zero());
+    assert_eq!(denom.points, Uint256::zero());
+    assert_eq!(denom.withdrawn_funds, Uint128::zero());
 
-    // Staking also changes weights - now validators[1] also splits rewards:
-    // 1/4 for users[0]
-    // 3/4 for users[1]
-    //
-    // 100 tokens staken by user[0]
-    // 300 tokens staken by user[1]
+    // Staking after the distribution is checkpointed so that no points are earned yet
     vault
-        .stake_remote(
-            contract.contract_addr.to_string(),
-            coin(300, OSMO),
-            to_binary(&ReceiveVirtualStake {
-                validator: validators[1].to_string(),
-            })
-            .unwrap(),
-        )
+        .bond()
+        .with_funds(&coins(100, OSMO))
         .call(users[1])
         .unwrap();
-    contract
-        .test_methods_proxy()
-        .test_commit_stake(get_last_external_staking_pending_tx_id(&contract).unwrap())
-        .call("test")
+    vault.stake(&contract, users[1], validator, coin(100, OSMO));
+
+    let debug = contract
+        .reward_debug(users[1].to_owned(), validator.to_owned())
         .unwrap();
+    assert_eq!(debug.stake, ValueRange::new_val(Uint128::new(100)));
+    assert_eq!(debug.rewards_stake, Uint128::new(100));
+    let denom = &debug.denoms[0];
+    assert_eq!(denom.points_per_stake, points_per_stake);
+    assert_eq!(denom.reward_index, points_per_stake);
+    assert_eq!(denom.points, Uint256::zero());
+}
 
-    // Check if messing up with weights didn't affect withdrawable
-    let rewards = contract
-        .pending_rewards(users[0].to_owned(), validators[0].to_owned())
-        .unwrap()
-        .rewards;
-    assert_eq!(rewards.amount.u128(), 8);
+#[test]
+fn distribution_multiple_denoms() {
+    let owner = "owner";
+    let users = ["user1", "user2"];
+    let remote = "remote1";
 
-    let rewards = contract
-        .pending_rewards(users[1].to_owned(), validators[0].to_owned())
-        .unwrap()
-        .rewards;
-    assert_eq!(rewards.amount.u128(), 12);
+    let app =
+        App::new_with_balances(&[(users[0], &coins(600, OSMO)), (users[1], &coins(600, OSMO))]);
 
-    let rewards = contract
-        .pending_rewards(users[0].to_owned(), validators[1].to_owned())
-        .unwrap()
-        .rewards;
-    assert_eq!(rewards.amount.u128(), 11);
+    let (vault, contract) = setup_with_rewards_denoms(&app, owner, 100, &[STAR, ATOM]).unwrap();
 
-    let rewards = contract
-        .pending_rewards(users[1].to_owned(), validators[1].to_owned())
-        .unwrap()
-        .rewards;
-    assert_eq!(rewards.amount.u128(), 0);
+    let validator = contract.activate_validators(["validator1"])[0];
+
+    for user in users {
+        vault
+            .bond()
+            .with_funds(&coins(600, OSMO))
+            .call(user)
+            .unwrap();
+    }
+
+    // 1/4 of validator to users[0], 3/4 to users[1]
+    vault.stake(&contract, users[0], validator, coin(100, OSMO));
+    vault.stake(&contract, users[1], validator, coin(300, OSMO));
 
-    // Now distribute some nice values
-    // 10 on users[0] (~0.4 still not distributed)
-    // 10 on users[1] (~0.6 still not distributed)
     contract
         .test_methods_proxy()
-        .test_distribute_rewards(validators[0].to_owned(), coin(20, STAR))
+        .test_distribute_rewards(validator.to_owned(), coin(40, STAR))
         .call(owner)
         .unwrap();
-
-    // Also for validator[1]
-    // 10 on users[1]
-    // 30 on users[2]
     contract
+        .distribute_batch(owner, ATOM, &[(validator, 80)])
+        .unwrap();
+
+    // Only the configured denoms are accepted
+    let err = contract
         .test_methods_proxy()
-        .test_distribute_rewards(validators[1].to_owned(), coin(40, STAR))
+        .test_distribute_rewards(validator.to_owned(), coin(40, OSMO))
         .call(owner)
-        .unwrap();
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::Payment(PaymentError::MissingDenom(OSMO.to_owned()))
+    );
 
     let rewards = contract
-        .pending_rewards(users[0].to_owned(), validators[0].to_owned())
+        .pending_rewards(users[0].to_owned(), validator.to_owned())
         .unwrap()
         .rewards;
-    assert_eq!(rewards.amount.u128(), 18);
-
+    assert_eq!(rewards, [coin(10, STAR), coin(20, ATOM)]);
     let rewards = contract
-        .pending_rewards(users[1].to_owned(), validators[0].to_owned())
+        .all_pending_rewards(users[1].to_owned(), None, None)
         .unwrap()
         .rewards;
-    assert_eq!(rewards.amount.u128(), 22);
+    assert_eq!(
+        rewards,
+        [ValidatorPendingRewards {
+            validator: validator.to_owned(),
+            rewards: PendingRewards {
+                rewards: vec![coin(30, STAR), coin(60, ATOM)],
+            },
+        }]
+    );
 
-    let rewards = contract
-        .pending_rewards(users[0].to_owned(), validators[1].to_owned())
-        .unwrap()
-        .rewards;
-    assert_eq!(rewards.amount.u128(), 21);
+    // Withdrawal sends both denoms, in separate transfers
+    contract
+        .withdraw_rewards(validator.to_owned(), remote.to_owned())
+        .call(users[0])
+        .unwrap();
+    let txs = contract.all_pending_txs_desc(None, None).unwrap().txs;
+    assert_eq!(txs.len(), 2);
+    for tx in txs {
+        contract
+            .test_methods_proxy()
+            .test_commit_withdraw_rewards(tx.id())
+            .call(users[0])
+            .unwrap();
+    }
 
     let rewards = contract
-        .pending_rewards(users[1].to_owned(), validators[1].to_owned())
+        .pending_rewards(users[0].to_owned(), validator.to_owned())
         .unwrap()
         .rewards;
-    assert_eq!(rewards.amount.u128(), 30);
+    assert_eq!(rewards, [coin(0, STAR), coin(0, ATOM)]);
 
-    // And some more distribution fun - we are 50/50 on validators[1], so distributing odd number of
-    // coins
-    // 2 for users[0] (+ ~0.5 from this distribution + ~0.4 accumulated -> ~0.9 tokens should be
-    //   here)
-    // 3 for users[1] (+ ~0.5 from this distribution + ~0.6 accumulated -> ~1.1 tokens, we give one
-    //   back leaving ~0.1 accumulated)
+    // New rewards in a single denom are withdrawn alone
     contract
         .test_methods_proxy()
-        .test_distribute_rewards(validators[0].to_owned(), coin(5, STAR))
+        .test_distribute_rewards(validator.to_owned(), coin(8, ATOM))
         .call(owner)
         .unwrap();
-
-    let rewards = contract
-        .pending_rewards(users[0].to_owned(), validators[0].to_owned())
-        .unwrap()
-        .rewards;
-    assert_eq!(rewards.amount.u128(), 20);
-
     let rewards = contract
-        .pending_rewards(users[1].to_owned(), validators[0].to_owned())
+        .pending_rewards(users[0].to_owned(), validator.to_owned())
         .unwrap()
         .rewards;
-    assert_eq!(rewards.amount.u128(), 25);
+    assert_eq!(rewards, [coin(0, STAR), coin(2, ATOM)]);
 
-    // More unstaking - to make it both ways by both stakers on at least one validator, for sake of
-    // funny error accumulation issues. After two following unstakes, staking on validators[0] is as
-    // follows:
-    //
-    // 150 tokens staken by users[0]
-    // 100 tokens staken by users[1]
-    //
-    // Rewards distribution:
-    //
-    // 3/5 rewards to users[0]
-    // 2/5 rewards to users[1]
     contract
-        .unstake(validators[0].to_owned(), coin(50, OSMO))
+        .withdraw_rewards(validator.to_owned(), remote.to_owned())
         .call(users[0])
         .unwrap();
-    contract
-        .test_methods_proxy()
-        .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
-        .call("test")
-        .unwrap();
+    let txs = contract.all_pending_txs_desc(None, None).unwrap().txs;
+    assert_eq!(txs.len(), 1);
+    assert!(matches!(
+        &txs[0],
+        Tx::InFlightTransferFunds { amount, denom, .. }
+            if *amount == Uint128::new(2) && denom == ATOM
+    ));
+}
 
-    contract
-        .unstake(validators[0].to_owned(), coin(100, OSMO))
-        .call(users[1])
-        .unwrap();
-    contract
-        .test_methods_proxy()
-        .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
-        .call("test")
-        .unwrap();
+#[test]
+fn rewards_trace_change() {
+    let owner = "owner";
+    let users = ["user1", "user2"];
+    let remote = "remote1";
+    let atom_ch0 = "transfer/channel-0/uatom";
+    let atom_ch1 = "transfer/channel-1/uatom";
+    let atom_ch2 = "transfer/channel-2/uatom";
+
+    let app =
+        App::new_with_balances(&[(users[0], &coins(600, OSMO)), (users[1], &coins(600, OSMO))]);
+
+    let (vault, contract) = setup_with_rewards_denoms(&app, owner, 100, &[atom_ch0]).unwrap();
+
+    let validator = contract.activate_validators(["validator1"])[0];
+
+    for user in users {
+        vault
+            .bond()
+            .with_funds(&coins(600, OSMO))
+            .call(user)
+            .unwrap();
+    }
+
+    // 1/4 of validator to users[0], 3/4 to users[1]
+    vault.stake(&contract, users[0], validator, coin(100, OSMO));
+    vault.stake(&contract, users[1], validator, coin(300, OSMO));
 
-    // Distribute 12 tokens to validator[0]:
-    //
-    // 7 + 1 = 8 to users[0] (~0.9 accumulated + ~0.2 = ~1.1 leftover, 1.0 payed back, ~0.1 accumulated)
-    // 4 to users[0] (~0.1 accumulated + ~0.8 -> leaving at ~0.9)
     contract
         .test_methods_proxy()
-        .test_distribute_rewards(validators[0].to_owned(), coin(12, STAR))
+        .test_distribute_rewards(validator.to_owned(), coin(40, atom_ch0))
         .call(owner)
         .unwrap();
 
-    let rewards = contract
-        .pending_rewards(users[0].to_owned(), validators[0].to_owned())
-        .unwrap()
-        .rewards;
-    assert_eq!(rewards.amount.u128(), 28);
+    // Only the admin can change the trace
+    let err = contract
+        .update_rewards_trace(
+            "uatom".to_owned(),
+            Some("transfer/channel-1".to_owned()),
+            false,
+        )
+        .call(users[0])
+        .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized);
 
-    let rewards = contract
-        .pending_rewards(users[1].to_owned(), validators[0].to_owned())
-        .unwrap()
-        .rewards;
-    assert_eq!(rewards.amount.u128(), 29);
+    let err = contract
+        .update_rewards_trace("uosmo".to_owned(), None, false)
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(err, ContractError::UnknownRewardsBase("uosmo".to_owned()));
 
-    // Withdraw only by users[0]
-    contract
-        .withdraw_rewards(validators[0].to_owned(), remote[0].to_owned())
-        .call(users[0])
-        .unwrap();
-    let tx_id = get_last_external_staking_pending_tx_id(&contract).unwrap();
+    // Without sweep, the rewards already distributed stay in the former denom
     contract
-        .test_methods_proxy()
-        .test_commit_withdraw_rewards(tx_id)
-        .call(users[0])
+        .update_rewards_trace(
+            "uatom".to_owned(),
+            Some("transfer/channel-1".to_owned()),
+            false,
+        )
+        .call(owner)
         .unwrap();
 
-    contract
-        .withdraw_rewards(validators[1].to_owned(), remote[0].to_owned())
-        .call(users[0])
-        .unwrap();
-    let tx_id = get_last_external_staking_pending_tx_id(&contract).unwrap();
-    contract
+    let err = contract
         .test_methods_proxy()
-        .test_commit_withdraw_rewards(tx_id)
-        .call(users[0])
-        .unwrap();
+        .test_distribute_rewards(validator.to_owned(), coin(40, atom_ch0))
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::Payment(PaymentError::MissingDenom(atom_ch0.to_owned()))
+    );
 
-    // Rollback on users[1]
-    contract
-        .withdraw_rewards(validators[0].to_owned(), "bad_value".to_owned())
-        .call(users[1])
-        .unwrap();
-    let tx_id = get_last_external_staking_pending_tx_id(&contract).unwrap();
+    // Stake changes don't affect the rewards held in the former denom
+    vault.stake(&contract, users[1], validator, coin(100, OSMO));
     contract
         .test_methods_proxy()
-        .test_rollback_withdraw_rewards(tx_id)
-        .call(users[1])
-        .unwrap();
-
-    // Rewards withrawal should not affect the stake
-    let stake = contract
-        .stake(users[0].to_owned(), validators[0].to_owned())
-        .unwrap();
-    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(150)));
-
-    let stake = contract
-        .stake(users[0].to_owned(), validators[1].to_owned())
-        .unwrap();
-    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(100)));
-
-    let stake = contract
-        .stake(users[1].to_owned(), validators[0].to_owned())
-        .unwrap();
-    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(100)));
-
-    let stake = contract
-        .stake(users[1].to_owned(), validators[1].to_owned())
+        .test_distribute_rewards(validator.to_owned(), coin(100, atom_ch1))
+        .call(owner)
         .unwrap();
-    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(300)));
 
-    // Check withdrawals and accounts
     let rewards = contract
-        .pending_rewards(users[0].to_owned(), validators[0].to_owned())
+        .pending_rewards(users[0].to_owned(), validator.to_owned())
         .unwrap()
         .rewards;
-    assert_eq!(rewards.amount.u128(), 0);
-
+    assert_eq!(rewards, [coin(20, atom_ch1), coin(10, atom_ch0)]);
     let rewards = contract
-        .pending_rewards(users[1].to_owned(), validators[0].to_owned())
+        .pending_rewards(users[1].to_owned(), validator.to_owned())
         .unwrap()
         .rewards;
-    assert_eq!(rewards.amount.u128(), 29);
+    assert_eq!(rewards, [coin(80, atom_ch1), coin(30, atom_ch0)]);
 
-    let rewards = contract
-        .pending_rewards(users[0].to_owned(), validators[1].to_owned())
+    // Withdrawal pays out both denoms
+    contract
+        .withdraw_rewards(validator.to_owned(), remote.to_owned())
+        .call(users[0])
+        .unwrap();
+    let txs = contract.all_pending_txs_desc(None, None).unwrap().txs;
+    assert_eq!(txs.len(), 2);
+    let ch1_tx = txs
+        .iter()
+        .find(|tx| matches!(tx, Tx::InFlightTransferFunds { denom, .. } if denom == atom_ch1))
         .unwrap()
-        .rewards;
-    assert_eq!(rewards.amount.u128(), 0);
+        .id();
+
+    // A denom already held can't be reused
+    let err = contract
+        .update_rewards_trace(
+            "uatom".to_owned(),
+            Some("transfer/channel-0".to_owned()),
+            true,
+        )
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(err, ContractError::RewardsDenomHeld(atom_ch0.to_owned()));
+
+    // With sweep, the rewards held in the former denom move to the new one
+    contract
+        .update_rewards_trace(
+            "uatom".to_owned(),
+            Some("transfer/channel-2".to_owned()),
+            true,
+        )
+        .call(owner)
+        .unwrap();
 
     let rewards = contract
-        .pending_rewards(users[1].to_owned(), validators[1].to_owned())
+        .pending_rewards(users[1].to_owned(), validator.to_owned())
         .unwrap()
         .rewards;
-    assert_eq!(rewards.amount.u128(), 30);
+    assert_eq!(rewards, [coin(80, atom_ch2), coin(30, atom_ch0)]);
 
-    // Final distribution - 10 tokens to both validators
-    // 6 tokens to users[0] via validators[0] (leftover as it was)
-    // 4 tokens to users[1] via validators[0] (leftover as it was)
-    // 2 tokens to users[0] via validators[1] (~0.5 leftover)
-    // 7 tokens to users[1] via validators[1] (~0.5 lefover)
+    // In-flight withdrawals are swept as well
     contract
         .test_methods_proxy()
-        .test_distribute_rewards(validators[0].to_owned(), coin(10, STAR))
+        .test_rollback_withdraw_rewards(ch1_tx)
         .call(owner)
         .unwrap();
+    let rewards = contract
+        .pending_rewards(users[0].to_owned(), validator.to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards, [coin(20, atom_ch2), coin(0, atom_ch0)]);
 
     contract
         .test_methods_proxy()
-        .test_distribute_rewards(validators[1].to_owned(), coin(10, STAR))
+        .test_distribute_rewards(validator.to_owned(), coin(100, atom_ch2))
         .call(owner)
         .unwrap();
-
-    // Check withdrawals and accounts
     let rewards = contract
-        .pending_rewards(users[0].to_owned(), validators[0].to_owned())
+        .pending_rewards(users[0].to_owned(), validator.to_owned())
         .unwrap()
         .rewards;
-    assert_eq!(rewards.amount.u128(), 6);
+    assert_eq!(rewards, [coin(40, atom_ch2), coin(0, atom_ch0)]);
 
-    let rewards = contract
-        .pending_rewards(users[1].to_owned(), validators[0].to_owned())
-        .unwrap()
-        .rewards;
-    assert_eq!(rewards.amount.u128(), 33);
+    let config = contract.config().unwrap();
+    assert_eq!(config.rewards_denoms, [RewardDenom::from_denom(atom_ch2)]);
+    assert_eq!(config.retired_rewards_denoms, [atom_ch0]);
+    // The swept rewards are still accounted in the former denom
+    assert_eq!(
+        config.swept_rewards_denoms,
+        BTreeMap::from([(atom_ch2.to_owned(), atom_ch1.to_owned())])
+    );
 
+    // The denom they are accounted in can only be swept back to
+    let err = contract
+        .update_rewards_trace(
+            "uatom".to_owned(),
+            Some("transfer/channel-1".to_owned()),
+            false,
+        )
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(err, ContractError::RewardsDenomHeld(atom_ch1.to_owned()));
+    contract
+        .update_rewards_trace(
+            "uatom".to_owned(),
+            Some("transfer/channel-1".to_owned()),
+            true,
+        )
+        .call(owner)
+        .unwrap();
     let rewards = contract
-        .pending_rewards(users[0].to_owned(), validators[1].to_owned())
+        .pending_rewards(users[0].to_owned(), validator.to_owned())
         .unwrap()
         .rewards;
-    assert_eq!(rewards.amount.u128(), 2);
+    assert_eq!(rewards, [coin(40, atom_ch1), coin(0, atom_ch0)]);
+    assert_eq!(
+        contract.config().unwrap().swept_rewards_denoms,
+        BTreeMap::new()
+    );
+}
 
-    let rewards = contract
-        .pending_rewards(users[1].to_owned(), validators[1].to_owned())
-        .unwrap()
-        .rewards;
-    assert_eq!(rewards.amount.u128(), 37);
+#[test]
+fn rewards_denom_replacement() {
+    let owner = "owner";
+    let users = ["user1", "user2"];
+    let remote = "remote1";
 
-    let all_rewards = contract
-        .all_pending_rewards(users[0].to_owned(), None, None)
-        .unwrap();
-    let expected = vec![
-        ValidatorPendingRewards::new(validators[0], 6, STAR),
-        ValidatorPendingRewards::new(validators[1], 2, STAR),
-    ];
-    assert_eq!(all_rewards.rewards, expected);
+    let app =
+        App::new_with_balances(&[(users[0], &coins(600, OSMO)), (users[1], &coins(600, OSMO))]);
 
-    let all_rewards = contract
-        .all_pending_rewards(users[1].to_owned(), None, None)
-        .unwrap();
-    let expected = vec![
-        ValidatorPendingRewards::new(validators[0], 33, STAR),
-        ValidatorPendingRewards::new(validators[1], 37, STAR),
-    ];
-    assert_eq!(all_rewards.rewards, expected);
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validator = contract.activate_validators(["validator1"])[0];
+
+    for user in users {
+        vault
+            .bond()
+            .with_funds(&coins(600, OSMO))
+            .call(user)
+            .unwrap();
+    }
+
+    // 1/4 of validator to users[0], 3/4 to users[1]
+    vault.stake(&contract, users[0], validator, coin(100, OSMO));
+    vault.stake(&contract, users[1], validator, coin(300, OSMO));
 
-    // And try to withdraw all, previous balances:
-    contract
-        .withdraw_rewards(validators[0].to_string(), remote[0].to_owned())
-        .call(users[0])
-        .unwrap();
-    let tx_id = get_last_external_staking_pending_tx_id(&contract).unwrap();
     contract
         .test_methods_proxy()
-        .test_commit_withdraw_rewards(tx_id)
-        .call(users[0])
+        .test_distribute_rewards(validator.to_owned(), coin(40, STAR))
+        .call(owner)
         .unwrap();
 
-    contract
-        .withdraw_rewards(validators[1].to_string(), remote[0].to_owned())
+    // Only the admin can replace the denom, by a denom not held yet
+    let err = contract
+        .replace_rewards_denom(STAR.to_owned(), ATOM.to_owned())
         .call(users[0])
-        .unwrap();
-    let tx_id = get_last_external_staking_pending_tx_id(&contract).unwrap();
+        .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized);
+    let err = contract
+        .replace_rewards_denom(ATOM.to_owned(), OSMO.to_owned())
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(err, ContractError::UnknownRewardsBase(ATOM.to_owned()));
+    let err = contract
+        .replace_rewards_denom(STAR.to_owned(), STAR.to_owned())
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(err, ContractError::RewardsDenomHeld(STAR.to_owned()));
+
     contract
-        .test_methods_proxy()
-        .test_commit_withdraw_rewards(tx_id)
-        .call(users[0])
+        .replace_rewards_denom(STAR.to_owned(), ATOM.to_owned())
+        .call(owner)
         .unwrap();
+    let config = contract.config().unwrap();
+    assert_eq!(config.rewards_denoms, [RewardDenom::from_denom(ATOM)]);
+    assert_eq!(config.retired_rewards_denoms, [STAR]);
 
+    // Rewards are only distributed in the new denom
+    let err = contract
+        .test_methods_proxy()
+        .test_distribute_rewards(validator.to_owned(), coin(40, STAR))
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::Payment(PaymentError::MissingDenom(STAR.to_owned()))
+    );
     contract
-        .withdraw_rewards(validators[0].to_string(), remote[1].to_owned())
-        .call(users[1])
+        .test_methods_proxy()
+        .test_distribute_rewards(validator.to_owned(), coin(100, ATOM))
+        .call(owner)
         .unwrap();
-    let tx_id = get_last_external_staking_pending_tx_id(&contract).unwrap();
+
+    let rewards = contract
+        .pending_rewards(users[0].to_owned(), validator.to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards, [coin(25, ATOM), coin(10, STAR)]);
+    let rewards = contract
+        .pending_rewards(users[1].to_owned(), validator.to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards, [coin(75, ATOM), coin(30, STAR)]);
+
+    // Both denoms are withdrawn
     contract
-        .test_methods_proxy()
-        .test_commit_withdraw_rewards(tx_id)
+        .withdraw_rewards(validator.to_owned(), remote.to_owned())
         .call(users[0])
         .unwrap();
+    let txs = contract.all_pending_txs_desc(None, None).unwrap().txs;
+    let mut withdrawn: Vec<_> = txs
+        .iter()
+        .map(|tx| match tx {
+            Tx::InFlightTransferFunds { amount, denom, .. } => coin(amount.u128(), denom),
+            _ => panic!("unexpected tx {tx}"),
+        })
+        .collect();
+    withdrawn.sort_by(|a, b| a.denom.cmp(&b.denom));
+    assert_eq!(withdrawn, [coin(25, ATOM), coin(10, STAR)]);
+}
+
+#[test]
+fn replaced_rewards_denom_follows_stake() {
+    let owner = "owner";
+    let user = "user1";
+    let usdc = "uusdc";
+
+    let app = App::new_with_balances(&[(user, &coins(600, OSMO))]);
+
+    let (vault, contract) = setup_with_rewards_denoms(&app, owner, 100, &[STAR, ATOM]).unwrap();
+
+    let validator = contract.activate_validators(["validator1"])[0];
+
+    vault
+        .bond()
+        .with_funds(&coins(600, OSMO))
+        .call(user)
+        .unwrap();
+    vault.stake(&contract, user, validator, coin(100, OSMO));
 
     contract
-        .withdraw_rewards(validators[1].to_string(), remote[1].to_owned())
-        .call(users[1])
+        .replace_rewards_denom(ATOM.to_owned(), usdc.to_owned())
+        .call(owner)
         .unwrap();
-    let tx_id = get_last_external_staking_pending_tx_id(&contract).unwrap();
+
+    // The distribution of the new denom starts with the stake on the validator, before the
+    // distributions of the other denoms are updated
+    vault.stake(&contract, user, validator, coin(100, OSMO));
     contract
         .test_methods_proxy()
-        .test_commit_withdraw_rewards(tx_id)
-        .call(users[0])
+        .test_distribute_rewards(validator.to_owned(), coin(50, usdc))
+        .call(owner)
         .unwrap();
+
+    let rewards = contract
+        .pending_rewards(user.to_owned(), validator.to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards, [coin(0, STAR), coin(50, usdc), coin(0, ATOM)]);
 }
 
 #[test]
@@ -1154,13 +3898,143 @@ fn batch_distribution_invalid_token() {
         .with_funds(&coins(600, OSMO))
         .call(user)
         .unwrap();
-
-    vault.stake(&contract, user, validator, coin(200, OSMO));
-
-    let err = contract
-        .distribute_batch(owner, "supertoken", &[(validator, 50)])
-        .unwrap_err();
-    assert_eq!(err, ContractError::InvalidDenom(STAR.to_string()));
+
+    vault.stake(&contract, user, validator, coin(200, OSMO));
+
+    let err = contract
+        .distribute_batch(owner, "supertoken", &[(validator, 50)])
+        .unwrap_err();
+    assert_eq!(err, ContractError::InvalidDenom(STAR.to_string()));
+}
+
+#[test]
+fn rewards_of_unknown_validator() {
+    let owner = "owner";
+    let user = "user1";
+
+    let app = App::new_with_balances(&[(user, &coins(600, OSMO))]);
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validator = contract.activate_validators(["validator1"])[0];
+
+    vault
+        .bond()
+        .with_funds(&coins(600, OSMO))
+        .call(user)
+        .unwrap();
+
+    vault.stake(&contract, user, validator, coin(200, OSMO));
+
+    // Distributions to validators not in the set are refunded, instead of being left unclaimable
+    let err = contract
+        .test_methods_proxy()
+        .test_distribute_rewards("unknown".to_owned(), coin(50, STAR))
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(err, ContractError::UnknownValidator("unknown".to_owned()));
+    let err = contract
+        .distribute_batch(owner, STAR, &[(validator, 50), ("unknown", 30)])
+        .unwrap_err();
+    assert_eq!(err, ContractError::UnknownValidator("unknown".to_owned()));
+    assert_rewards!(contract, user, validator, 0);
+
+    let err = contract
+        .withdraw_rewards("unknown".to_owned(), "remote".to_owned())
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::UnknownValidator("unknown".to_owned()));
+
+    // Only rewards left on validators out of the set can be recovered
+    let err = contract
+        .recover_unclaimable_rewards(validator.to_owned(), user.to_owned())
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(err, ContractError::ValidatorTracked(validator.to_owned()));
+    let err = contract
+        .recover_unclaimable_rewards("unknown".to_owned(), user.to_owned())
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized);
+    let err = contract
+        .recover_unclaimable_rewards("unknown".to_owned(), user.to_owned())
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(err, ContractError::NoRewards);
+}
+
+#[test]
+fn sweep() {
+    let owner = "owner";
+    let user = "user1";
+    let recipient = "recipient";
+
+    let app = App::new_with_balances(&[(user, &[coin(600, OSMO), coin(30, STAR)])]);
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validator = contract.activate_validators(["validator1"])[0];
+
+    vault
+        .bond()
+        .with_funds(&coins(500, OSMO))
+        .call(user)
+        .unwrap();
+    vault.stake(&contract, user, validator, coin(200, OSMO));
+
+    let err = contract
+        .sweep(OSMO.to_owned(), recipient.to_owned())
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(err, ContractError::NothingToSweep(OSMO.to_owned()));
+
+    // Tokens sent to the contract by mistake
+    app.app_mut()
+        .send_tokens(
+            Addr::unchecked(user),
+            contract.contract_addr.clone(),
+            &[coin(100, OSMO), coin(30, STAR)],
+        )
+        .unwrap();
+
+    let err = contract
+        .sweep(OSMO.to_owned(), recipient.to_owned())
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized);
+
+    contract
+        .sweep(OSMO.to_owned(), recipient.to_owned())
+        .call(owner)
+        .unwrap();
+    contract
+        .sweep(STAR.to_owned(), recipient.to_owned())
+        .call(owner)
+        .unwrap();
+    assert_eq!(
+        app.app().wrap().query_all_balances(recipient).unwrap(),
+        [coin(100, OSMO), coin(30, STAR)]
+    );
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_all_balances(&contract.contract_addr)
+            .unwrap(),
+        []
+    );
+
+    // The staked collateral stays in the vault
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(&vault.contract_addr, OSMO)
+            .unwrap(),
+        coin(500, OSMO)
+    );
+    let stake = contract
+        .stake(user.to_owned(), validator.to_owned())
+        .unwrap();
+    assert_eq!(stake.stake.low(), Uint128::new(200));
 }
 
 #[test]
@@ -1220,7 +4094,7 @@ fn slashing() {
     });
 
     // Withdrawing liens
-    contract.withdraw_unbonded().call(user).unwrap();
+    contract.withdraw_unbonded(None).call(user).unwrap();
 
     // Claims still not changed on the vault side - withdrawal to early
     let claim = vault
@@ -1282,7 +4156,7 @@ fn slashing() {
     assert_eq!(claim.amount.val().unwrap().u128(), 285);
 
     // Withdrawing liens
-    contract.withdraw_unbonded().call(user).unwrap();
+    contract.withdraw_unbonded(None).call(user).unwrap();
 
     // Now claims on vault got reduced, but only for first batch amount (not slashed)
     let claim = vault
@@ -1303,7 +4177,7 @@ fn slashing() {
     assert_eq!(claim.amount.val().unwrap().u128(), 235);
 
     // Withdrawing liens
-    contract.withdraw_unbonded().call(user).unwrap();
+    contract.withdraw_unbonded(None).call(user).unwrap();
 
     // Now everything is released (235 - 90 - 70 + 7 = 82)
     let claim = vault
@@ -1312,6 +4186,102 @@ fn slashing() {
     assert_eq!(claim.amount.val().unwrap().u128(), 82);
 }
 
+#[test]
+fn validator_slashes() {
+    let user = "user1";
+    let owner = "owner";
+
+    let app = App::new_with_balances(&[(user, &coins(300, OSMO))]);
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validators = contract.activate_validators(["validator1", "validator2"]);
+
+    vault
+        .bond()
+        .with_funds(&coins(300, OSMO))
+        .call(user)
+        .unwrap();
+    vault.stake(&contract, user, validators[0], coin(200, OSMO));
+    vault.stake(&contract, user, validators[1], coin(100, OSMO));
+
+    let slashes = contract
+        .validator_slashes(validators[0].to_owned(), None, None)
+        .unwrap()
+        .slashes;
+    assert_eq!(slashes, []);
+
+    let first_height = app.block_info().height;
+    contract
+        .test_methods_proxy()
+        .test_handle_slashing(validators[0].to_string())
+        .call("test")
+        .unwrap();
+    app.app_mut().update_block(|block| {
+        block.height += 5;
+        block.time = block.time.plus_seconds(30);
+    });
+    let second_height = app.block_info().height;
+    contract
+        .test_methods_proxy()
+        .test_handle_slashing(validators[0].to_string())
+        .call("test")
+        .unwrap();
+
+    let slashes = contract
+        .validator_slashes(validators[0].to_owned(), None, None)
+        .unwrap()
+        .slashes;
+    assert_eq!(
+        slashes,
+        [
+            ValidatorSlash {
+                height: first_height,
+                ratio: Decimal::percent(SLASHING_PERCENTAGE),
+                amount: Uint128::new(20),
+            },
+            ValidatorSlash {
+                height: second_height,
+                ratio: Decimal::percent(SLASHING_PERCENTAGE),
+                amount: Uint128::new(18),
+            },
+        ]
+    );
+
+    // Paginated by height
+    let slashes = contract
+        .validator_slashes(validators[0].to_owned(), None, Some(1))
+        .unwrap()
+        .slashes;
+    assert_eq!(
+        slashes,
+        [ValidatorSlash {
+            height: first_height,
+            ratio: Decimal::percent(SLASHING_PERCENTAGE),
+            amount: Uint128::new(20),
+        }]
+    );
+    let slashes = contract
+        .validator_slashes(validators[0].to_owned(), Some(first_height), Some(1))
+        .unwrap()
+        .slashes;
+    assert_eq!(
+        slashes,
+        [ValidatorSlash {
+            height: second_height,
+            ratio: Decimal::percent(SLASHING_PERCENTAGE),
+            amount: Uint128::new(18),
+        }]
+    );
+
+    // Other validators are not affected
+    let slashes = contract
+        .validator_slashes(validators[1].to_owned(), None, None)
+        .unwrap()
+        .slashes;
+    assert_eq!(slashes, []);
+}
+
 #[test]
 fn slashing_pending_tx_partial_unbond() {
     let user = "user1";
@@ -1392,7 +4362,7 @@ fn slashing_pending_tx_partial_unbond() {
     assert_eq!(claim.amount.val().unwrap().u128(), 280);
 
     // Withdrawing liens
-    contract.withdraw_unbonded().call(user).unwrap();
+    contract.withdraw_unbonded(None).call(user).unwrap();
 
     // Now claims on vault got reduced by the (full) unbonded amount
     let claim = vault
@@ -1475,7 +4445,7 @@ fn slashing_pending_tx_full_unbond() {
     assert_eq!(claim.amount.val().unwrap().u128(), 180);
 
     // Withdrawing liens
-    contract.withdraw_unbonded().call(user).unwrap();
+    contract.withdraw_unbonded(None).call(user).unwrap();
 
     // Now claims on vault got reduced by the (full) unbonded amount
     let claim = vault
@@ -1558,7 +4528,7 @@ fn slashing_pending_tx_full_unbond_rolled_back() {
     assert_eq!(claim.amount.val().unwrap().u128(), 180);
 
     // Withdrawing liens
-    contract.withdraw_unbonded().call(user).unwrap();
+    contract.withdraw_unbonded(None).call(user).unwrap();
 
     // Claims on vault are still unchanged
     let claim = vault
@@ -1597,6 +4567,7 @@ fn slashing_pending_tx_bond() {
                 validator: validators[0].into(),
             })
             .unwrap(),
+            None,
         )
         .call(user)
         .unwrap();
@@ -1684,6 +4655,7 @@ fn slashing_pending_tx_bond_rolled_back() {
                 validator: validators[0].into(),
             })
             .unwrap(),
+            None,
         )
         .call(user)
         .unwrap();
@@ -1740,3 +4712,360 @@ fn slashing_pending_tx_bond_rolled_back() {
         .unwrap();
     assert_eq!(claim.amount.val().unwrap().u128(), 225);
 }
+
+#[test]
+fn slashing_redistribution() {
+    let users = ["user1", "user2"];
+
+    let app =
+        App::new_with_balances(&[(users[0], &coins(200, OSMO)), (users[1], &coins(200, OSMO))]);
+
+    let owner = "owner";
+
+    // The collateral denom has to be a rewards denom for redistribution
+    let (_, contract) = setup(&app, owner, 100).unwrap();
+    let err = contract
+        .update_slash_redistribution(Some(Decimal::percent(50)))
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::SlashRedistributionDenom(OSMO.to_owned())
+    );
+
+    let (vault, contract) = setup_with_rewards_denoms(&app, owner, 100, &[STAR, OSMO]).unwrap();
+
+    let err = contract
+        .update_slash_redistribution(Some(Decimal::percent(50)))
+        .call(users[0])
+        .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized);
+
+    let err = contract
+        .update_slash_redistribution(Some(Decimal::percent(150)))
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(err, ContractError::InvalidSlashRedistribution);
+
+    contract
+        .update_slash_redistribution(Some(Decimal::percent(50)))
+        .call(owner)
+        .unwrap();
+    let config = contract.config().unwrap();
+    assert_eq!(config.slash_redistribution, Some(Decimal::percent(50)));
+
+    let validators = contract.activate_validators(["validator1", "validator2", "validator3"]);
+
+    for user in users {
+        vault
+            .bond()
+            .with_funds(&coins(200, OSMO))
+            .call(user)
+            .unwrap();
+    }
+    vault.stake(&contract, users[0], validators[0], coin(200, OSMO));
+    vault.stake(&contract, users[1], validators[1], coin(100, OSMO));
+    vault.stake(&contract, users[1], validators[2], coin(100, OSMO));
+
+    // validators[0] gets slashed by 20, half of it is redistributed to the other validators
+    contract
+        .test_methods_proxy()
+        .test_handle_slashing(validators[0].to_string())
+        .call("test")
+        .unwrap();
+
+    let claim = vault
+        .claim(users[0].to_owned(), contract.contract_addr.to_string())
+        .unwrap();
+    assert_eq!(claim.amount.val().unwrap().u128(), 180);
+
+    for validator in &validators[1..] {
+        let rewards = contract
+            .pending_rewards(users[1].to_owned(), validator.to_string())
+            .unwrap();
+        assert_eq!(rewards.rewards, [coin(0, STAR), coin(5, OSMO)]);
+    }
+
+    // The slashed validator stakers get nothing
+    let rewards = contract
+        .pending_rewards(users[0].to_owned(), validators[0].to_owned())
+        .unwrap();
+    assert_eq!(rewards.rewards, [coin(0, STAR), coin(0, OSMO)]);
+
+    // The vault paid the redistributed collateral back, and it's all credited
+    let balance = |addr: &str| app.app().wrap().query_balance(addr, OSMO).unwrap().amount;
+    assert_eq!(balance(contract.contract_addr.as_str()).u128(), 10);
+    assert_eq!(balance(vault.contract_addr.as_str()).u128(), 390);
+    let res = contract
+        .continue_slash_redistribution(None)
+        .call(users[0])
+        .unwrap();
+    let processed = res
+        .events
+        .iter()
+        .filter(|event| event.ty == "wasm")
+        .flat_map(|event| &event.attributes)
+        .find(|attr| attr.key == "processed")
+        .unwrap();
+    assert_eq!(processed.value, "0");
+
+    // The redistributed slashes are counted apart from the rewards
+    let stats = contract.stats().unwrap();
+    assert_eq!(stats.slashes_redistributed, Uint128::new(10));
+    assert_eq!(stats.rewards_distributed, vec![]);
+
+    // The redistributed rewards are paid on this side
+    contract
+        .withdraw_rewards(validators[1].to_owned(), "remote".to_owned())
+        .call(users[1])
+        .unwrap();
+    assert_eq!(balance(users[1]).u128(), 5);
+    assert_eq!(balance(contract.contract_addr.as_str()).u128(), 5);
+    assert_eq!(get_last_external_staking_pending_tx_id(&contract), None);
+    let rewards = contract
+        .pending_rewards(users[1].to_owned(), validators[1].to_string())
+        .unwrap();
+    assert_eq!(rewards.rewards, [coin(0, STAR), coin(0, OSMO)]);
+}
+
+#[test]
+fn sweep_keeps_redistribution_reserve() {
+    let users = ["user1", "user2"];
+    let owner = "owner";
+    let recipient = "recipient";
+
+    let app =
+        App::new_with_balances(&[(users[0], &coins(207, OSMO)), (users[1], &coins(200, OSMO))]);
+
+    let (vault, contract) = setup_with_rewards_denoms(&app, owner, 100, &[STAR, OSMO]).unwrap();
+    contract
+        .update_slash_redistribution(Some(Decimal::percent(50)))
+        .call(owner)
+        .unwrap();
+
+    let validators = contract.activate_validators(["validator1", "validator2", "validator3"]);
+
+    for user in users {
+        vault
+            .bond()
+            .with_funds(&coins(200, OSMO))
+            .call(user)
+            .unwrap();
+    }
+    vault.stake(&contract, users[0], validators[0], coin(200, OSMO));
+    vault.stake(&contract, users[1], validators[1], coin(100, OSMO));
+    vault.stake(&contract, users[1], validators[2], coin(100, OSMO));
+
+    // 10 of the slash is redistributed, and held until withdrawn
+    contract
+        .test_methods_proxy()
+        .test_handle_slashing(validators[0].to_string())
+        .call("test")
+        .unwrap();
+
+    let balance = |addr: &str| app.app().wrap().query_balance(addr, OSMO).unwrap().amount;
+    let err = contract
+        .sweep(OSMO.to_owned(), recipient.to_owned())
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(err, ContractError::NothingToSweep(OSMO.to_owned()));
+
+    // Only the tokens sent by mistake are swept
+    app.app_mut()
+        .send_tokens(
+            Addr::unchecked(users[0]),
+            contract.contract_addr.clone(),
+            &coins(7, OSMO),
+        )
+        .unwrap();
+    contract
+        .sweep(OSMO.to_owned(), recipient.to_owned())
+        .call(owner)
+        .unwrap();
+    assert_eq!(balance(recipient).u128(), 7);
+    assert_eq!(balance(contract.contract_addr.as_str()).u128(), 10);
+
+    // The stakers can still withdraw the redistributed slashes
+    for validator in &validators[1..] {
+        contract
+            .withdraw_rewards(validator.to_string(), "remote".to_owned())
+            .call(users[1])
+            .unwrap();
+    }
+    assert_eq!(balance(users[1]).u128(), 10);
+    assert_eq!(balance(contract.contract_addr.as_str()).u128(), 0);
+}
+
+#[test]
+fn restake() {
+    let user = "user1";
+    let owner = "owner";
+
+    let app = App::new_with_balances(&[(user, &coins(300, OSMO))]);
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validators = contract.activate_validators(["validator1", "validator2", "validator3"]);
+
+    vault
+        .bond()
+        .with_funds(&coins(300, OSMO))
+        .call(user)
+        .unwrap();
+    vault.stake(&contract, user, validators[0], coin(100, OSMO));
+    vault.stake(&contract, user, validators[1], coin(100, OSMO));
+
+    let restake = |from: &[&str], to: &str| {
+        contract
+            .cross_staking_api_proxy()
+            .restake(
+                user.to_owned(),
+                from.iter().map(|val| val.to_string()).collect(),
+                to.to_owned(),
+            )
+            .call(vault.contract_addr.as_str())
+    };
+    let stake = |validator: &str| {
+        contract
+            .stake(user.to_owned(), validator.to_owned())
+            .unwrap()
+            .stake
+    };
+
+    // Moving to a new validator is bounded by the max validators per user
+    contract
+        .update_max_validators_per_user(Some(2))
+        .call(owner)
+        .unwrap();
+    let err = restake(&[validators[0]], validators[2]).unwrap_err();
+    assert_eq!(err, ContractError::TooManyValidators(2));
+    contract
+        .update_max_validators_per_user(None)
+        .call(owner)
+        .unwrap();
+
+    // A rolled back restake leaves the stake in place
+    restake(&[validators[0]], validators[2]).unwrap();
+    assert_eq!(
+        stake(validators[0]),
+        ValueRange::new(Uint128::zero(), Uint128::new(100))
+    );
+    assert_eq!(
+        stake(validators[2]),
+        ValueRange::new(Uint128::zero(), Uint128::new(100))
+    );
+    contract
+        .test_methods_proxy()
+        .test_rollback_restake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
+    assert_eq!(stake(validators[0]), ValueRange::new_val(Uint128::new(100)));
+    let stakes = contract
+        .stakes(user.to_owned(), None, None, Some(true), None, None)
+        .unwrap()
+        .stakes;
+    assert_eq!(stakes.len(), 2);
+
+    // Slashing a source validator in flight leaves less to move
+    restake(&[validators[0], validators[1]], validators[2]).unwrap();
+    let tx_id = get_last_external_staking_pending_tx_id(&contract).unwrap();
+    contract
+        .test_methods_proxy()
+        .test_handle_slashing(validators[0].to_string())
+        .call("test")
+        .unwrap();
+    contract
+        .test_methods_proxy()
+        .test_commit_restake(tx_id)
+        .call("test")
+        .unwrap();
+    assert_eq!(stake(validators[0]), ValueRange::new_val(Uint128::zero()));
+    assert_eq!(stake(validators[1]), ValueRange::new_val(Uint128::zero()));
+    assert_eq!(stake(validators[2]), ValueRange::new_val(Uint128::new(190)));
+
+    // The moved stake is still slashed for the source validator
+    contract
+        .test_methods_proxy()
+        .test_handle_slashing(validators[1].to_string())
+        .call("test")
+        .unwrap();
+    assert_eq!(stake(validators[2]), ValueRange::new_val(Uint128::new(180)));
+    let claim = vault
+        .claim(user.to_owned(), contract.contract_addr.to_string())
+        .unwrap();
+    assert_eq!(claim.amount.val().unwrap().u128(), 180);
+
+    // But not after its unbonding period
+    app.app_mut().update_block(|block| {
+        block.height += 1;
+        block.time = block.time.plus_seconds(100);
+    });
+    vault.stake(&contract, user, validators[1], coin(10, OSMO));
+    contract
+        .test_methods_proxy()
+        .test_handle_slashing(validators[1].to_string())
+        .call("test")
+        .unwrap();
+    assert_eq!(stake(validators[1]), ValueRange::new_val(Uint128::new(9)));
+    assert_eq!(stake(validators[2]), ValueRange::new_val(Uint128::new(180)));
+}
+
+#[test]
+fn restake_slashes_unstaked_redelegation() {
+    let user = "user1";
+    let owner = "owner";
+
+    let app = App::new_with_balances(&[(user, &coins(300, OSMO))]);
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validators = contract.activate_validators(["validator1", "validator2"]);
+
+    vault
+        .bond()
+        .with_funds(&coins(300, OSMO))
+        .call(user)
+        .unwrap();
+    vault.stake(&contract, user, validators[0], coin(100, OSMO));
+
+    contract
+        .cross_staking_api_proxy()
+        .restake(
+            user.to_owned(),
+            vec![validators[0].to_owned()],
+            validators[1].to_owned(),
+        )
+        .call(vault.contract_addr.as_str())
+        .unwrap();
+    contract
+        .test_methods_proxy()
+        .test_commit_restake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
+
+    // Unstaking the moved stake doesn't escape the slashing of the source validator
+    contract
+        .unstake(validators[1].to_string(), coin(95, OSMO))
+        .call(user)
+        .unwrap();
+    contract
+        .test_methods_proxy()
+        .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
+    contract
+        .test_methods_proxy()
+        .test_handle_slashing(validators[0].to_string())
+        .call("test")
+        .unwrap();
+
+    let stake = contract
+        .stake(user.to_owned(), validators[1].to_owned())
+        .unwrap();
+    assert_eq!(stake.stake, ValueRange::new_val(Uint128::zero()));
+    assert_eq!(stake.pending_unbonds[0].amount.u128(), 90);
+    let claim = vault
+        .claim(user.to_owned(), contract.contract_addr.to_string())
+        .unwrap();
+    assert_eq!(claim.amount.val().unwrap().u128(), 90);
+}
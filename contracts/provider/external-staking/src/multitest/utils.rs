@@ -17,7 +17,7 @@ macro_rules! assert_rewards {
             .unwrap()
             .rewards;
         let expected = $expected;
-        let actual = rewards.amount.u128();
+        let actual = rewards[0].amount.u128();
         assert_eq!(
             actual, expected,
             "expected {} reward tokens, found: {}",
@@ -121,6 +121,7 @@ impl VaultExt for Vault<'_> {
                 validator: validator.into(),
             })
             .unwrap(),
+            None,
         )
         .call(user)
         .unwrap();
@@ -5,11 +5,13 @@ use cw_storage_plus::{Index, IndexList, IndexedMap, KeyDeserialize, MultiIndex};
 pub struct StakeIndexes<'a> {
     // Last type param defines the pk deserialization type
     pub rev: MultiIndex<'a, (String, Addr), Stake, (Addr, String)>,
+    /// Stakes of a user by their amount (the `high` end of the range)
+    pub size: MultiIndex<'a, (Addr, u128), Stake, (Addr, String)>,
 }
 
 impl<'a> IndexList<Stake> for StakeIndexes<'a> {
     fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Stake>> + '_> {
-        let v: Vec<&dyn Index<Stake>> = vec![&self.rev];
+        let v: Vec<&dyn Index<Stake>> = vec![&self.rev, &self.size];
         Box::new(v.into_iter())
     }
 }
@@ -23,7 +25,7 @@ impl<'a> Stakes<'a> {
         <(Addr, String)>::from_slice(pk).unwrap() // mustn't fail
     }
 
-    pub fn new(storage_key: &'a str, validator_subkey: &'a str) -> Self {
+    pub fn new(storage_key: &'a str, validator_subkey: &'a str, size_subkey: &'a str) -> Self {
         let indexes = StakeIndexes {
             rev: MultiIndex::new(
                 |pk, _| {
@@ -33,6 +35,14 @@ impl<'a> Stakes<'a> {
                 storage_key,
                 validator_subkey,
             ),
+            size: MultiIndex::new(
+                |pk, stake| {
+                    let (user, _) = Self::deserialize_pk(pk);
+                    (user, stake.stake.high().u128())
+                },
+                storage_key,
+                size_subkey,
+            ),
         };
         let stakes = IndexedMap::new(storage_key, indexes);
 
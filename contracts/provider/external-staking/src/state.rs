@@ -2,26 +2,140 @@ use cosmwasm_schema::cw_serde;
 use cosmwasm_std::{BlockInfo, Decimal, Timestamp, Uint128, Uint256};
 use mesh_apis::vault_api::VaultApiHelper;
 use mesh_sync::ValueRange;
-
-use crate::points_alignment::PointsAlignment;
+use std::collections::BTreeMap;
 
 /// Contract configuration
 #[cw_serde]
 pub struct Config {
     /// Local native token this contracts operate on
     pub denom: String,
-    /// Rewards token for this contract (remote IBC token)
-    pub rewards_denom: String,
+    /// Rewards tokens accepted by this contract (remote IBC tokens)
+    pub rewards_denoms: Vec<RewardDenom>,
+    /// Former rewards denoms, not distributed anymore, but still held by the stakers until they
+    /// withdraw them. Left behind by trace changes which didn't sweep them
+    #[serde(default)]
+    pub retired_rewards_denoms: Vec<String>,
+    /// Rewards denoms swept from a former trace, to the denom their rewards are still accounted
+    /// in. The other rewards denoms are accounted in their own denom
+    #[serde(default)]
+    pub swept_rewards_denoms: BTreeMap<String, String>,
     /// Vault contract address
     pub vault: VaultApiHelper,
     /// Unbonding period for claims in seconds
     pub unbonding_period: u64,
     /// Max slash percentage (from InstantiateMsg, maybe later from the chain)
     pub max_slashing: Decimal,
+    /// Minimum stake left on a position after an unstake. Unstakes leaving a nonzero remainder
+    /// below it are handled according to `dust_policy`
+    #[serde(default)]
+    pub min_remaining_stake: Option<Uint128>,
+    /// How to handle unstakes leaving less than `min_remaining_stake` staked
+    #[serde(default)]
+    pub dust_policy: DustPolicy,
+    /// Part of the slashed stake given to the stakers of the other validators as rewards in the
+    /// collateral denom, instead of being burned with the rest of it. The vault pays it back out of
+    /// the slashed collateral, and the stakers withdraw it from this contract
+    #[serde(default)]
+    pub slash_redistribution: Option<Decimal>,
+    /// Part of the stake slashed on submitted evidence, paid to the submitter
+    #[serde(default)]
+    pub evidence_bounty: Option<Decimal>,
+    /// Chain id of the consumer, which the submitted evidence has to be for. Evidence is rejected
+    /// until it is set
+    #[serde(default)]
+    pub consumer_chain_id: Option<String>,
+    /// Max number of validators tracked. Over it, the validators inactive for the longest without
+    /// any stake on them are not tracked anymore
+    #[serde(default)]
+    pub max_tracked_validators: Option<u32>,
+    /// Typical time for an IBC packet to be relayed and acknowledged, in seconds. Used to estimate
+    /// when the unstakes not committed yet are released
+    #[serde(default)]
+    pub relay_latency: Option<u64>,
+    /// Max number of validators a user can have a stake entry on. Bounds the cost of iterating
+    /// over the stakes of a user
+    #[serde(default)]
+    pub max_validators_per_user: Option<u32>,
+}
+
+/// Handling of unstakes which would leave a dust position behind
+#[cw_serde]
+#[derive(Default)]
+pub enum DustPolicy {
+    /// The unstake fails
+    #[default]
+    Reject,
+    /// The whole position is unstaked instead
+    FullUnstake,
+}
+
+/// Rewards denom, split in its base denom and the IBC transfer trace it arrives through
+#[cw_serde]
+pub struct RewardDenom {
+    /// Denom on its origin chain
+    pub base: String,
+    /// Transfer path prefixing the base denom (`transfer/channel-0`), if any
+    pub trace: Option<String>,
+}
+
+impl RewardDenom {
+    /// Splits a full denom on its last `/`, the part before it being the trace
+    pub fn from_denom(denom: &str) -> Self {
+        match denom.rsplit_once('/') {
+            Some((trace, base)) => Self {
+                base: base.to_owned(),
+                trace: Some(trace.to_owned()),
+            },
+            None => Self {
+                base: denom.to_owned(),
+                trace: None,
+            },
+        }
+    }
+
+    /// Full denom the rewards are distributed and accounted in
+    pub fn denom(&self) -> String {
+        match &self.trace {
+            Some(trace) => format!("{}/{}", trace, self.base),
+            None => self.base.clone(),
+        }
+    }
+}
+
+impl Config {
+    pub fn is_rewards_denom(&self, denom: &str) -> bool {
+        self.rewards_denoms.iter().any(|d| d.denom() == denom)
+    }
+
+    /// All the denoms rewards are held in: the current rewards denoms, then the retired ones
+    pub fn held_rewards_denoms(&self) -> Vec<String> {
+        self.rewards_denoms
+            .iter()
+            .map(RewardDenom::denom)
+            .chain(self.retired_rewards_denoms.iter().cloned())
+            .collect()
+    }
+
+    /// Denom the rewards held in `denom` are accounted in: the validators distributions and the
+    /// stakers checkpoints are keyed by it
+    pub fn accounting_denom<'a>(&'a self, denom: &'a str) -> &'a str {
+        self.swept_rewards_denoms
+            .get(denom)
+            .map(String::as_str)
+            .unwrap_or(denom)
+    }
+
+    /// Whether rewards are held in `denom`, or accounted in it, leaving the held denom `except` out
+    pub fn is_rewards_denom_taken(&self, denom: &str, except: Option<&str>) -> bool {
+        self.held_rewards_denoms()
+            .iter()
+            .filter(|held| Some(held.as_str()) != except)
+            .any(|held| held == denom || self.accounting_denom(held) == denom)
+    }
 }
 
 /// All single stake related information - entry per `(user, validator)` pair, including
-/// distribution alignment
+/// rewards checkpoints
 #[cw_serde]
 #[derive(Default)]
 pub struct Stake {
@@ -34,18 +148,83 @@ pub struct Stake {
     /// `unbonding_period` after current time - this way this is guaranteed to be
     /// always sorted (as time is guaranteed to be monotonic).
     pub pending_unbonds: Vec<PendingUnbond>,
-    /// Points alignment is how much points should be added/subtracted from points calculated per
-    /// user due to stake changes.
-    pub points_alignment: PointsAlignment,
+    /// Tokens counted in the validator distributions, earning rewards. Unlike `stake`, it
+    /// includes the pending unstakes until they are committed
+    #[serde(default)]
+    pub rewards_stake: Uint128,
+    /// Rewards checkpoints, per rewards denom
+    pub rewards: BTreeMap<String, StakeRewards>,
+}
+
+/// Slash applied to the stakes on a validator
+#[cw_serde]
+pub struct SlashRecord {
+    /// Part of the stakes slashed, the configured `max_slashing`
+    pub ratio: Decimal,
+    /// Slashed amount, including the slashed pending unbonds
+    pub amount: Uint128,
+}
+
+/// Part of a slash redistributed to the stakers of the other validators, credited a few
+/// validators at a time
+#[cw_serde]
+pub struct SlashRedistribution {
+    /// Slashed validator, whose stakers get nothing
+    pub validator: String,
+    /// Stake on the other validators when it was slashed, which the amount is shared by
+    pub total_stake: Uint128,
+    /// Amount redistributed. Capped to what the vault paid back once funded
+    pub amount: Uint128,
+    /// Whether the amount was set aside out of the contract balance
+    pub funded: bool,
+    /// Amount not credited yet
+    pub remaining: Uint128,
+    /// Last validator credited
+    pub start_after: Option<String>,
+}
+
+/// Activity timestamps of a user, for analytics
+#[cw_serde]
+#[derive(Default)]
+pub struct UserMeta {
+    /// Time of the first stake. Not set for users who only staked before it was tracked
+    pub first_stake_at: Option<Timestamp>,
+    /// Time of the last stake, unstake or rewards withdrawal
+    pub last_activity_at: Option<Timestamp>,
+}
+
+/// Rewards checkpoint of a stake for a single rewards denom
+#[cw_serde]
+#[derive(Default)]
+pub struct StakeRewards {
+    /// Validator distribution `points_per_stake` at the last change of the stake
+    pub reward_index: Uint256,
+    /// Points earned up to the last change of the stake
+    pub points: Uint256,
     /// Tokens already withdrawn by this user
     pub withdrawn_funds: Uint128,
 }
 
+impl StakeRewards {
+    /// Points earned so far by `rewards_stake` tokens staked since the checkpoint, with the
+    /// validator distribution now at `points_per_stake`
+    pub fn points(&self, rewards_stake: Uint128, points_per_stake: Uint256) -> Uint256 {
+        self.points + Uint256::from(rewards_stake) * (points_per_stake - self.reward_index)
+    }
+
+    /// Moves the checkpoint to `points_per_stake`. To be called before the stake changes
+    pub fn checkpoint(&mut self, rewards_stake: Uint128, points_per_stake: Uint256) {
+        self.points = self.points(rewards_stake, points_per_stake);
+        self.reward_index = points_per_stake;
+    }
+}
+
 impl Stake {
     /// Create simplified stake (mostly for tests)
     pub fn from_amount(amount: Uint128) -> Self {
         Self {
             stake: ValueRange::new_val(amount),
+            rewards_stake: amount,
             ..Default::default()
         }
     }
@@ -56,11 +235,70 @@ impl Stake {
 pub struct PendingUnbond {
     /// Tokens scheduled for unbonding
     pub amount: Uint128,
+    /// Time the unstake was submitted at. The unbonding period only starts once it is committed,
+    /// after the IBC relay. Zero for unbonds committed before it was tracked
+    #[serde(default)]
+    pub requested_at: Timestamp,
     /// Time when tokens are released
     pub release_at: Timestamp,
 }
 
+impl PendingUnbond {
+    /// Merges `other`, released at the same time, into this unbond. The earliest request is kept
+    fn merge(&mut self, other: &PendingUnbond) {
+        self.amount += other.amount;
+        self.requested_at = std::cmp::min(self.requested_at, other.requested_at);
+    }
+}
+
 impl Stake {
+    /// Schedules tokens for release, merging them into the last pending unbond if it is released
+    /// at exactly the same time (multiple unstakes committed in the same block).
+    pub fn add_pending_unbond(&mut self, unbond: PendingUnbond) {
+        match self.pending_unbonds.last_mut() {
+            Some(last) if last.release_at == unbond.release_at => last.merge(&unbond),
+            _ => self.pending_unbonds.push(unbond),
+        }
+    }
+
+    /// Schedules tokens for release at any time, keeping `pending_unbonds` sorted. They are merged
+    /// into the pending unbond released at exactly the same time, if any.
+    pub fn insert_pending_unbond(&mut self, unbond: PendingUnbond) {
+        let idx = self
+            .pending_unbonds
+            .partition_point(|pending| pending.release_at < unbond.release_at);
+        match self.pending_unbonds.get_mut(idx) {
+            Some(pending) if pending.release_at == unbond.release_at => pending.merge(&unbond),
+            _ => self.pending_unbonds.insert(idx, unbond),
+        }
+    }
+
+    /// Merges all the entries in `pending_unbonds` sharing the same `release_at`, returning the
+    /// number of entries removed.
+    ///
+    /// As `pending_unbonds` is always sorted, duplicates are guaranteed to be adjacent.
+    pub fn compact_pending_unbonds(&mut self) -> usize {
+        let before = self.pending_unbonds.len();
+        self.pending_unbonds.dedup_by(|next, prev| {
+            if next.release_at == prev.release_at {
+                prev.merge(next);
+                true
+            } else {
+                false
+            }
+        });
+        before - self.pending_unbonds.len()
+    }
+
+    /// Amount of tokens `release_pending` would release, without removing the entries
+    pub fn releasable(&self, info: &BlockInfo) -> Uint128 {
+        self.pending_unbonds
+            .iter()
+            .filter(|pending| pending.release_at <= info.time)
+            .map(|pending| pending.amount)
+            .sum()
+    }
+
     /// Removes expired entries from `pending_unbonds`, returning amount of tokens released.
     pub fn release_pending(&mut self, info: &BlockInfo) -> Uint128 {
         // The fact that `pending unbonds are always added to the end, so they are always ordered
@@ -99,9 +337,47 @@ impl Stake {
             })
             .sum()
     }
+
+    /// Slashes up to `amount` off the entries in `pending_unbonds` not released yet, the latest
+    /// first. Returns the slashed amount
+    pub fn slash_pending_amount(&mut self, info: &BlockInfo, amount: Uint128) -> Uint128 {
+        let mut remaining = amount;
+        for pending in self.pending_unbonds.iter_mut().rev() {
+            if remaining.is_zero() || pending.release_at <= info.time {
+                break;
+            }
+            let slash = std::cmp::min(pending.amount, remaining);
+            pending.amount -= slash;
+            remaining -= slash;
+        }
+        amount - remaining
+    }
+}
+
+/// Stake moved off a validator by a restake. It is still slashed for the misbehaviours of that
+/// validator until the end of its unbonding period
+#[cw_serde]
+pub struct Redelegation {
+    /// Validator the stake was moved to
+    pub to: String,
+    /// Amount moved, less what was slashed since
+    pub amount: Uint128,
+    /// End of the unbonding period, from the commit of the restake
+    pub release_at: Timestamp,
+}
+
+/// Pending unbond offered for sale by its owner
+#[cw_serde]
+pub struct UnbondListing {
+    /// Release time of the pending unbond, identifying it among the owner's pending unbonds
+    pub release_at: Timestamp,
+    /// Amount of the pending unbond when listed. The listing is outdated if it changed since
+    pub amount: Uint128,
+    /// Minimum price to pay to the owner, in the vault denom
+    pub min_price: Uint128,
 }
 
-/// Per validator distribution information
+/// Per validator and rewards denom distribution information
 #[cw_serde]
 #[derive(Default)]
 pub struct Distribution {
@@ -109,6 +385,7 @@ pub struct Distribution {
     pub total_stake: Uint128,
     /// Points user is eligible to by single token staken
     pub points_per_stake: Uint256,
-    /// Points which were not distributed previously
+    /// Points which were not distributed previously, as they couldn't be split evenly between
+    /// all the staked tokens. They are added to the next distribution, so no rewards are lost
     pub points_leftover: Uint256,
 }
@@ -26,6 +26,22 @@ pub trait TestMethods {
         validator: AddValidator,
     ) -> Result<Response, Self::Error>;
 
+    /// Adds validators to the valset, as if received in an `AddValidators` packet.
+    #[msg(exec)]
+    fn test_add_validators(
+        &self,
+        ctx: ExecCtx,
+        validators: Vec<AddValidator>,
+    ) -> Result<Response, Self::Error>;
+
+    /// Merges validators, as if received in response to a `request_validator_sync`.
+    #[msg(exec)]
+    fn test_validator_sync(
+        &self,
+        ctx: ExecCtx,
+        validators: Vec<AddValidator>,
+    ) -> Result<Response, Self::Error>;
+
     /// Commits a pending unstake.
     #[msg(exec)]
     fn test_commit_unstake(&self, ctx: ExecCtx, tx_id: u64) -> Result<Response, Self::Error>;
@@ -34,6 +50,14 @@ pub trait TestMethods {
     #[msg(exec)]
     fn test_rollback_unstake(&self, ctx: ExecCtx, tx_id: u64) -> Result<Response, Self::Error>;
 
+    /// Commits a pending restake.
+    #[msg(exec)]
+    fn test_commit_restake(&self, ctx: ExecCtx, tx_id: u64) -> Result<Response, Self::Error>;
+
+    /// Rollbacks a pending restake.
+    #[msg(exec)]
+    fn test_rollback_restake(&self, ctx: ExecCtx, tx_id: u64) -> Result<Response, Self::Error>;
+
     /// Distribute rewards.
     #[msg(exec)]
     fn test_distribute_rewards(
@@ -77,4 +101,8 @@ pub trait TestMethods {
         ctx: ExecCtx,
         validator: String,
     ) -> Result<Response, Self::Error>;
+
+    /// Drops a pending tx without resolving it, leaving what it locked behind.
+    #[msg(exec)]
+    fn test_drop_pending_tx(&self, ctx: ExecCtx, tx_id: u64) -> Result<Response, Self::Error>;
 }
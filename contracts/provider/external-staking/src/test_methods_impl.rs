@@ -8,7 +8,7 @@ use mesh_apis::ibc::AddValidator;
 use sylvia::contract;
 use sylvia::types::ExecCtx;
 
-/// These methods are for test usage only
+/// These methods are for test usage only, and only compiled in tests and with the `mt` feature
 #[contract(module=crate::contract)]
 #[messages(crate::test_methods as TestMethods)]
 impl TestMethods for ExternalStakingContract<'_> {
@@ -17,31 +17,15 @@ impl TestMethods for ExternalStakingContract<'_> {
     /// Commits a pending stake.
     #[msg(exec)]
     fn test_commit_stake(&self, ctx: ExecCtx, tx_id: u64) -> Result<Response, ContractError> {
-        #[cfg(any(feature = "mt", test))]
-        {
-            let msg = self.commit_stake(ctx.deps, tx_id)?;
-            Ok(Response::new().add_message(msg))
-        }
-        #[cfg(not(any(feature = "mt", test)))]
-        {
-            let _ = (ctx, tx_id);
-            Err(ContractError::Unauthorized {})
-        }
+        let msg = self.commit_stake(ctx.deps, ctx.env, tx_id)?;
+        Ok(Response::new().add_message(msg))
     }
 
     /// Rollbacks a pending stake.
     #[msg(exec)]
     fn test_rollback_stake(&self, ctx: ExecCtx, tx_id: u64) -> Result<Response, ContractError> {
-        #[cfg(any(test, feature = "mt"))]
-        {
-            let msg = self.rollback_stake(ctx.deps, tx_id)?;
-            Ok(Response::new().add_message(msg))
-        }
-        #[cfg(not(any(test, feature = "mt")))]
-        {
-            let _ = (ctx, tx_id);
-            Err(ContractError::Unauthorized {})
-        }
+        let msg = self.rollback_stake(ctx.deps, ctx.env, tx_id)?;
+        Ok(Response::new().add_message(msg))
     }
 
     /// Updates the active validator set.
@@ -51,58 +35,74 @@ impl TestMethods for ExternalStakingContract<'_> {
         ctx: ExecCtx,
         validator: AddValidator,
     ) -> Result<Response, ContractError> {
-        #[cfg(any(feature = "mt", test))]
-        {
-            let AddValidator {
-                valoper,
-                pub_key,
-                start_height,
-                start_time,
-            } = validator;
-            let update = crate::crdt::ValUpdate {
-                pub_key,
-                start_height,
-                start_time,
-            };
-            self.val_set
-                .add_validator(ctx.deps.storage, &valoper, update)?;
-            Ok(Response::new())
-        }
-        #[cfg(not(any(feature = "mt", test)))]
-        {
-            let _ = (ctx, validator);
-            Err(ContractError::Unauthorized {})
-        }
+        let AddValidator {
+            valoper,
+            pub_key,
+            start_height,
+            start_time,
+        } = validator;
+        let update = crate::crdt::ValUpdate {
+            pub_key,
+            start_height,
+            start_time,
+        };
+        self.val_set
+            .add_validator(ctx.deps.storage, &valoper, update)?;
+        Ok(Response::new())
+    }
+
+    /// Adds validators to the valset, as if received in an `AddValidators` packet.
+    #[msg(exec)]
+    fn test_add_validators(
+        &self,
+        ctx: ExecCtx,
+        validators: Vec<AddValidator>,
+    ) -> Result<Response, ContractError> {
+        self.add_validators(
+            ctx.deps.storage,
+            validators,
+            crate::contract::DEFAULT_VALSET_SYNC_LIMIT,
+        )?;
+        Ok(Response::new())
+    }
+
+    /// Merges validators, as if received in response to a `request_validator_sync`.
+    #[msg(exec)]
+    fn test_validator_sync(
+        &self,
+        ctx: ExecCtx,
+        validators: Vec<AddValidator>,
+    ) -> Result<Response, ContractError> {
+        self.merge_validator_sync(ctx.deps.storage, ctx.env.block.height, validators)?;
+        Ok(Response::new())
     }
 
     /// Commits a pending unstake.
     #[msg(exec)]
     fn test_commit_unstake(&self, ctx: ExecCtx, tx_id: u64) -> Result<Response, ContractError> {
-        #[cfg(any(test, feature = "mt"))]
-        {
-            self.commit_unstake(ctx.deps, ctx.env, tx_id)?;
-            Ok(Response::new())
-        }
-        #[cfg(not(any(test, feature = "mt")))]
-        {
-            let _ = (ctx, tx_id);
-            Err(ContractError::Unauthorized {})
-        }
+        self.commit_unstake(ctx.deps, ctx.env, tx_id)?;
+        Ok(Response::new())
     }
 
     /// Rollbacks a pending unstake.
     #[msg(exec)]
     fn test_rollback_unstake(&self, ctx: ExecCtx, tx_id: u64) -> Result<Response, ContractError> {
-        #[cfg(any(test, feature = "mt"))]
-        {
-            self.rollback_unstake(ctx.deps, tx_id)?;
-            Ok(Response::new())
-        }
-        #[cfg(not(any(test, feature = "mt")))]
-        {
-            let _ = (ctx, tx_id);
-            Err(ContractError::Unauthorized {})
-        }
+        self.rollback_unstake(ctx.deps, ctx.env, tx_id)?;
+        Ok(Response::new())
+    }
+
+    /// Commits a pending restake.
+    #[msg(exec)]
+    fn test_commit_restake(&self, ctx: ExecCtx, tx_id: u64) -> Result<Response, ContractError> {
+        self.commit_restake(ctx.deps, ctx.env, tx_id)?;
+        Ok(Response::new())
+    }
+
+    /// Rollbacks a pending restake.
+    #[msg(exec)]
+    fn test_rollback_restake(&self, ctx: ExecCtx, tx_id: u64) -> Result<Response, ContractError> {
+        self.rollback_restake(ctx.deps, ctx.env, tx_id)?;
+        Ok(Response::new())
     }
 
     /// Distribute rewards.
@@ -113,16 +113,8 @@ impl TestMethods for ExternalStakingContract<'_> {
         validator: String,
         rewards: Coin,
     ) -> Result<Response, ContractError> {
-        #[cfg(any(test, feature = "mt"))]
-        {
-            let event = self.distribute_rewards(ctx.deps, &validator, rewards)?;
-            Ok(Response::new().add_event(event))
-        }
-        #[cfg(not(any(test, feature = "mt")))]
-        {
-            let _ = (ctx, validator, rewards);
-            Err(ContractError::Unauthorized)
-        }
+        let event = self.distribute_rewards(ctx.deps, &validator, rewards)?;
+        Ok(Response::new().add_event(event))
     }
 
     /// Batch distribute rewards.
@@ -133,16 +125,8 @@ impl TestMethods for ExternalStakingContract<'_> {
         denom: String,
         rewards: Vec<RewardInfo>,
     ) -> Result<Response, Self::Error> {
-        #[cfg(any(test, feature = "mt"))]
-        {
-            let events = self.distribute_rewards_batch(ctx.deps, &rewards, &denom)?;
-            Ok(Response::new().add_events(events))
-        }
-        #[cfg(not(any(test, feature = "mt")))]
-        {
-            let _ = (ctx, denom, rewards);
-            Err(ContractError::Unauthorized)
-        }
+        let events = self.distribute_rewards_batch(ctx.deps, &rewards, &denom)?;
+        Ok(Response::new().add_events(events))
     }
 
     /// Commits a withdraw rewards transaction.
@@ -152,16 +136,8 @@ impl TestMethods for ExternalStakingContract<'_> {
         ctx: ExecCtx,
         tx_id: u64,
     ) -> Result<Response, ContractError> {
-        #[cfg(any(test, feature = "mt"))]
-        {
-            self.commit_withdraw_rewards(ctx.deps, tx_id)?;
-            Ok(Response::new())
-        }
-        #[cfg(not(any(test, feature = "mt")))]
-        {
-            let _ = (ctx, tx_id);
-            Err(ContractError::Unauthorized {})
-        }
+        self.commit_withdraw_rewards(ctx.deps, ctx.env, tx_id)?;
+        Ok(Response::new())
     }
 
     /// Rollbacks a withdraw rewards transaction.
@@ -171,16 +147,8 @@ impl TestMethods for ExternalStakingContract<'_> {
         ctx: ExecCtx,
         tx_id: u64,
     ) -> Result<Response, ContractError> {
-        #[cfg(any(test, feature = "mt"))]
-        {
-            self.rollback_withdraw_rewards(ctx.deps, tx_id)?;
-            Ok(Response::new())
-        }
-        #[cfg(not(any(test, feature = "mt")))]
-        {
-            let _ = (ctx, tx_id);
-            Err(ContractError::Unauthorized {})
-        }
+        self.rollback_withdraw_rewards(ctx.deps, ctx.env, tx_id)?;
+        Ok(Response::new())
     }
 
     /// Slashes a validator
@@ -190,15 +158,17 @@ impl TestMethods for ExternalStakingContract<'_> {
         ctx: ExecCtx,
         validator: String,
     ) -> Result<Response, ContractError> {
-        #[cfg(any(test, feature = "mt"))]
-        {
-            let msg = self.handle_slashing(&ctx.env, ctx.deps.storage, &validator)?;
-            Ok(Response::new().add_message(msg))
-        }
-        #[cfg(not(any(test, feature = "mt")))]
-        {
-            let _ = (ctx, validator);
-            Err(ContractError::Unauthorized {})
-        }
+        let msg = self.handle_slashing(&ctx.env, ctx.deps.storage, &validator)?;
+        let redistribution = self.redistribution_msg(&ctx.env, ctx.deps.storage)?;
+        Ok(Response::new()
+            .add_message(msg)
+            .add_messages(redistribution))
+    }
+
+    /// Drops a pending tx without resolving it
+    #[msg(exec)]
+    fn test_drop_pending_tx(&self, ctx: ExecCtx, tx_id: u64) -> Result<Response, ContractError> {
+        self.pending_txs.remove(ctx.deps.storage, tx_id)?;
+        Ok(Response::new())
     }
 }
@@ -0,0 +1,209 @@
+use cosmwasm_std::{Addr, Order, StdResult, Storage};
+use cw_storage_plus::Map;
+use mesh_sync::Tx;
+
+pub struct PendingTxs<'a> {
+    pub txs: Map<'a, u64, Tx>,
+    /// Txs by the `(user, validator)` stakes they reference, with whether they change the stake
+    /// itself (as opposed to its rewards only)
+    stakes: Map<'a, (&'a Addr, &'a str, u64), bool>,
+}
+
+impl<'a> PendingTxs<'a> {
+    pub const fn new(storage_key: &'a str, stakes_key: &'a str) -> Self {
+        Self {
+            txs: Map::new(storage_key),
+            stakes: Map::new(stakes_key),
+        }
+    }
+
+    /// The `(user, validator)` stakes referenced by `tx`, with whether it changes them
+    fn stakes_of(tx: &Tx) -> Vec<(&Addr, &str, bool)> {
+        match tx {
+            Tx::InFlightRemoteStaking {
+                user, validator, ..
+            }
+            | Tx::InFlightRemoteUnstaking {
+                user, validator, ..
+            } => vec![(user, validator.as_str(), true)],
+            Tx::InFlightRemoteUnstakingBatch { user, unstakes, .. } => unstakes
+                .iter()
+                .map(|(validator, _)| (user, validator.as_str(), true))
+                .collect(),
+            Tx::InFlightRemoteRestaking { user, from, to, .. } => from
+                .iter()
+                .map(|(validator, _)| validator)
+                .chain([to])
+                .map(|validator| (user, validator.as_str(), true))
+                .collect(),
+            Tx::InFlightTransferFunds {
+                staker, validator, ..
+            } => vec![(staker, validator.as_str(), false)],
+            Tx::InFlightStaking { .. } => vec![],
+        }
+    }
+
+    /// Saves a tx, indexed by the stakes it references
+    pub fn save(&self, storage: &mut dyn Storage, id: u64, tx: &Tx) -> StdResult<()> {
+        if let Some(previous) = self.txs.may_load(storage, id)? {
+            self.unindex(storage, id, &previous);
+        }
+        for (user, validator, changes_stake) in Self::stakes_of(tx) {
+            self.stakes
+                .save(storage, (user, validator, id), &changes_stake)?;
+        }
+        self.txs.save(storage, id, tx)
+    }
+
+    /// Removes a resolved tx
+    pub fn remove(&self, storage: &mut dyn Storage, id: u64) -> StdResult<()> {
+        if let Some(tx) = self.txs.may_load(storage, id)? {
+            self.unindex(storage, id, &tx);
+        }
+        self.txs.remove(storage, id);
+        Ok(())
+    }
+
+    fn unindex(&self, storage: &mut dyn Storage, id: u64, tx: &Tx) {
+        for (user, validator, _) in Self::stakes_of(tx) {
+            self.stakes.remove(storage, (user, validator, id));
+        }
+    }
+
+    /// The first pending tx changing the stake of `user` on `validator`, if any
+    pub fn stake_changing_tx(
+        &self,
+        storage: &dyn Storage,
+        user: &Addr,
+        validator: &str,
+    ) -> StdResult<Option<u64>> {
+        for item in
+            self.stakes
+                .prefix((user, validator))
+                .range(storage, None, None, Order::Ascending)
+        {
+            let (id, changes_stake) = item?;
+            if changes_stake {
+                return Ok(Some(id));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Whether any pending tx references the stake of `user` on `validator`
+    pub fn references_stake(
+        &self,
+        storage: &dyn Storage,
+        user: &Addr,
+        validator: &str,
+    ) -> StdResult<bool> {
+        let first = self
+            .stakes
+            .prefix((user, validator))
+            .keys(storage, None, None, Order::Ascending)
+            .next();
+        Ok(first.transpose()?.is_some())
+    }
+
+    /// Rebuilds the index of the stakes referenced, for txs created before it was maintained
+    pub fn reindex(&self, storage: &mut dyn Storage) -> StdResult<()> {
+        self.stakes.clear(storage);
+        let txs = self
+            .txs
+            .range(storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        for (id, tx) in &txs {
+            for (user, validator, changes_stake) in Self::stakes_of(tx) {
+                self.stakes
+                    .save(storage, (user, validator, *id), &changes_stake)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cosmwasm_std::testing::MockStorage;
+    use cosmwasm_std::{Timestamp, Uint128};
+
+    #[test]
+    fn txs_indexed_by_stake() {
+        let mut storage = MockStorage::new();
+        let txs = PendingTxs::new("txs", "txs_by_stake");
+        let user = Addr::unchecked("user");
+
+        txs.save(
+            &mut storage,
+            1,
+            &Tx::InFlightTransferFunds {
+                id: 1,
+                amount: Uint128::new(10),
+                denom: "star".to_owned(),
+                staker: user.clone(),
+                validator: "alice".to_owned(),
+            },
+        )
+        .unwrap();
+        txs.save(
+            &mut storage,
+            2,
+            &Tx::InFlightRemoteUnstakingBatch {
+                id: 2,
+                user: user.clone(),
+                unstakes: vec![
+                    ("alice".to_owned(), Uint128::new(10)),
+                    ("bob".to_owned(), Uint128::new(20)),
+                ],
+                created_at: Timestamp::from_seconds(1),
+            },
+        )
+        .unwrap();
+
+        // Rewards withdrawals don't change the stake, but still reference it
+        assert!(txs.references_stake(&storage, &user, "alice").unwrap());
+        assert!(!txs.references_stake(&storage, &user, "carl").unwrap());
+        assert_eq!(
+            txs.stake_changing_tx(&storage, &user, "alice").unwrap(),
+            Some(2)
+        );
+        assert_eq!(
+            txs.stake_changing_tx(&storage, &user, "bob").unwrap(),
+            Some(2)
+        );
+        assert_eq!(
+            txs.stake_changing_tx(&storage, &user, "carl").unwrap(),
+            None
+        );
+
+        txs.remove(&mut storage, 2).unwrap();
+        assert_eq!(
+            txs.stake_changing_tx(&storage, &user, "alice").unwrap(),
+            None
+        );
+        assert!(txs.references_stake(&storage, &user, "alice").unwrap());
+        assert!(!txs.references_stake(&storage, &user, "bob").unwrap());
+        assert_eq!(txs.stake_changing_tx(&storage, &user, "bob").unwrap(), None);
+
+        // Txs saved before the index are indexed again
+        txs.txs
+            .save(
+                &mut storage,
+                3,
+                &Tx::InFlightRemoteStaking {
+                    id: 3,
+                    amount: Uint128::new(10),
+                    user: user.clone(),
+                    validator: "carl".to_owned(),
+                },
+            )
+            .unwrap();
+        txs.reindex(&mut storage).unwrap();
+        assert_eq!(
+            txs.stake_changing_tx(&storage, &user, "carl").unwrap(),
+            Some(3)
+        );
+    }
+}
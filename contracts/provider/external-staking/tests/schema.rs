@@ -0,0 +1,27 @@
+//! The test methods must not be part of the released contract API.
+//!
+//! Only checked when the `mt` feature is off, as with `cargo test -p mesh-external-staking`,
+//! which CI runs as a separate step. Workspace builds enable it for the vault multitests, so the
+//! check is reported as ignored there.
+
+use cosmwasm_schema::generate_api;
+use mesh_external_staking::contract::{ContractExecMsg, ContractQueryMsg, InstantiateMsg};
+
+#[test]
+#[cfg_attr(
+    feature = "mt",
+    ignore = "the mt feature adds the test methods, run with -p mesh-external-staking"
+)]
+fn schema_has_no_test_methods() {
+    let api = generate_api! {
+        instantiate: InstantiateMsg,
+        execute: ContractExecMsg,
+        query: ContractQueryMsg,
+    }
+    .render()
+    .to_string()
+    .unwrap();
+
+    assert!(api.contains("\"unstake\""));
+    assert!(!api.contains("\"test_"), "Test methods in the schema");
+}
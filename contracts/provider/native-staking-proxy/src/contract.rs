@@ -1,7 +1,7 @@
 use cosmwasm_std::WasmMsg::Execute;
 use cosmwasm_std::{
-    coin, ensure_eq, to_binary, Coin, DistributionMsg, GovMsg, Response, StakingMsg, VoteOption,
-    WeightedVoteOption,
+    coin, ensure, ensure_eq, to_binary, BankMsg, Coin, CosmosMsg, DistributionMsg, GovMsg,
+    Response, StakingMsg, Uint128, VoteOption, WeightedVoteOption,
 };
 use cw2::set_contract_version;
 use cw_storage_plus::Item;
@@ -18,10 +18,39 @@ use crate::state::Config;
 pub const CONTRACT_NAME: &str = env!("CARGO_PKG_NAME");
 pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// Splits `amount` proportionally to the `delegations` amounts. The rounding remainder goes to
+/// the last delegation
+fn split_proportionally(
+    amount: Uint128,
+    delegations: &[(String, Uint128)],
+) -> Vec<(String, Uint128)> {
+    let total: Uint128 = delegations.iter().map(|(_, delegated)| delegated).sum();
+    let mut remaining = amount;
+    let mut shares: Vec<_> = delegations
+        .iter()
+        .map(|(validator, delegated)| {
+            let share = amount.multiply_ratio(*delegated, total);
+            remaining -= share;
+            (validator.clone(), share)
+        })
+        .collect();
+    if let Some((_, share)) = shares.last_mut() {
+        *share += remaining;
+    }
+    shares.retain(|(_, share)| !share.is_zero());
+    shares
+}
+
 pub struct NativeStakingProxyContract<'a> {
     config: Item<'a, Config>,
 }
 
+impl Default for NativeStakingProxyContract<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg_attr(not(feature = "library"), sylvia::entry_points)]
 #[contract]
 #[error(ContractError)]
@@ -46,6 +75,7 @@ impl NativeStakingProxyContract<'_> {
             denom,
             parent: ctx.info.sender.clone(),
             owner: ctx.deps.api.addr_validate(&owner)?,
+            compound_validator: None,
         };
         self.config.save(ctx.deps.storage, &config)?;
         set_contract_version(ctx.deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
@@ -173,6 +203,123 @@ impl NativeStakingProxyContract<'_> {
         Ok(res)
     }
 
+    /// Withdraws the rewards of all the delegations, and delegates them back, proportionally to
+    /// the current delegations or to the compound validator if set. Anyone can call it, so that a
+    /// keeper can compound on behalf of the owner.
+    /// Only the withdrawn rewards are delegated, never the unbonded tokens held by the proxy. They
+    /// are reported to the parent, so that they are added to the owner's claim on the vault.
+    /// Rewards in other denoms are sent to the owner, as with `withdraw_rewards`
+    #[msg(exec)]
+    fn compound(&self, ctx: ExecCtx) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let cfg = self.config.load(ctx.deps.storage)?;
+        let proxy = ctx.env.contract.address;
+
+        let mut delegations = vec![];
+        let mut rewards = Uint128::zero();
+        let mut other_rewards: Vec<Coin> = vec![];
+        for delegation in ctx.deps.querier.query_all_delegations(&proxy)? {
+            let full = match ctx
+                .deps
+                .querier
+                .query_delegation(&proxy, &delegation.validator)?
+            {
+                Some(full) => full,
+                None => continue,
+            };
+            for reward in full.accumulated_rewards {
+                if reward.denom == cfg.denom {
+                    rewards += reward.amount;
+                } else {
+                    match other_rewards.iter_mut().find(|c| c.denom == reward.denom) {
+                        Some(other) => other.amount += reward.amount,
+                        None => other_rewards.push(reward),
+                    }
+                }
+            }
+            delegations.push((full.validator, full.amount.amount));
+        }
+        ensure!(!rewards.is_zero(), ContractError::NoRewardsToCompound);
+
+        // Rewards are withdrawn to the proxy for the time of the compounding
+        let mut msgs: Vec<CosmosMsg> = vec![DistributionMsg::SetWithdrawAddress {
+            address: proxy.to_string(),
+        }
+        .into()];
+        msgs.extend(delegations.iter().map(|(validator, _)| {
+            CosmosMsg::from(DistributionMsg::WithdrawDelegatorReward {
+                validator: validator.clone(),
+            })
+        }));
+        let delegates = match cfg.compound_validator {
+            Some(validator) => vec![(validator, rewards)],
+            None => split_proportionally(rewards, &delegations),
+        };
+        let validators = delegates
+            .iter()
+            .map(|(validator, _)| validator.clone())
+            .collect();
+        msgs.extend(delegates.into_iter().map(|(validator, amount)| {
+            CosmosMsg::from(StakingMsg::Delegate {
+                validator,
+                amount: coin(amount.u128(), &cfg.denom),
+            })
+        }));
+        // The staked rewards are added to the owner's claim, so that they can be released
+        let msg = to_binary(&native_staking_callback::ExecMsg::AddProxyStake {
+            amount: rewards,
+            validators,
+        })?;
+        msgs.push(
+            Execute {
+                contract_addr: cfg.parent.to_string(),
+                msg,
+                funds: vec![],
+            }
+            .into(),
+        );
+        if !other_rewards.is_empty() {
+            msgs.push(
+                BankMsg::Send {
+                    to_address: cfg.owner.to_string(),
+                    amount: other_rewards,
+                }
+                .into(),
+            );
+        }
+        msgs.push(
+            DistributionMsg::SetWithdrawAddress {
+                address: cfg.owner.into_string(),
+            }
+            .into(),
+        );
+
+        Ok(Response::new()
+            .add_messages(msgs)
+            .add_attribute("action", "compound")
+            .add_attribute("amount", rewards.to_string()))
+    }
+
+    /// Sets the validator the compounded rewards are delegated to. If `None`, they are spread
+    /// over the current delegations.
+    /// Can only be called by the owner
+    #[msg(exec)]
+    fn set_compound_validator(
+        &self,
+        ctx: ExecCtx,
+        validator: Option<String>,
+    ) -> Result<Response, ContractError> {
+        let mut cfg = self.config.load(ctx.deps.storage)?;
+        ensure_eq!(cfg.owner, ctx.info.sender, ContractError::Unauthorized {});
+
+        nonpayable(&ctx.info)?;
+
+        cfg.compound_validator = validator;
+        self.config.save(ctx.deps.storage, &cfg)?;
+        Ok(Response::new())
+    }
+
     /// Unstakes the given amount from the given validator on behalf of the calling user.
     /// Returns an error if the user doesn't have such stake.
     /// After the unbonding period, it will allow the user to claim the tokens (returning to vault)
@@ -198,8 +345,31 @@ impl NativeStakingProxyContract<'_> {
         Ok(Response::new().add_message(msg))
     }
 
+    /// Unstakes all the delegations. Can only be called by the parent contract, in emergencies.
+    /// After the unbonding period, `release_unbonded` returns the tokens as usual
+    #[msg(exec)]
+    fn emergency_unstake(&self, ctx: ExecCtx) -> Result<Response, ContractError> {
+        let cfg = self.config.load(ctx.deps.storage)?;
+        ensure_eq!(cfg.parent, ctx.info.sender, ContractError::Unauthorized {});
+
+        nonpayable(&ctx.info)?;
+
+        let msgs: Vec<_> = ctx
+            .deps
+            .querier
+            .query_all_delegations(ctx.env.contract.address)?
+            .into_iter()
+            .map(|delegation| StakingMsg::Undelegate {
+                validator: delegation.validator,
+                amount: delegation.amount,
+            })
+            .collect();
+        Ok(Response::new().add_messages(msgs))
+    }
+
     /// Releases any tokens that have fully unbonded from a previous unstake.
     /// This will go back to the parent via `release_proxy_stake`.
+    /// The unbondings matured on all the validators are released together, in a single callback.
     /// Errors if the proxy doesn't have any liquid tokens
     #[msg(exec)]
     fn release_unbonded(&self, ctx: ExecCtx) -> Result<Response, ContractError> {
@@ -213,6 +383,7 @@ impl NativeStakingProxyContract<'_> {
             .deps
             .querier
             .query_balance(ctx.env.contract.address, cfg.denom)?;
+        ensure!(!balance.amount.is_zero(), ContractError::NothingToRelease);
 
         // Send them to the parent contract via `release_proxy_stake`
         let msg = to_binary(&native_staking_callback::ExecMsg::ReleaseProxyStake {})?;
@@ -220,9 +391,12 @@ impl NativeStakingProxyContract<'_> {
         let wasm_msg = Execute {
             contract_addr: cfg.parent.to_string(),
             msg,
-            funds: vec![balance],
+            funds: vec![balance.clone()],
         };
-        Ok(Response::new().add_message(wasm_msg))
+        Ok(Response::new()
+            .add_message(wasm_msg)
+            .add_attribute("action", "release_unbonded")
+            .add_attribute("amount", balance.to_string()))
     }
 
     #[msg(query)]
@@ -397,4 +571,32 @@ mod tests {
         let res = contract.vote_weighted(ctx, proposal_id, vote);
         assert!(matches!(res.unwrap_err(), ContractError::Unauthorized {}));
     }
+
+    #[test]
+    fn compound_split() {
+        let delegations = [
+            ("alice".to_owned(), Uint128::new(100)),
+            ("bob".to_owned(), Uint128::new(50)),
+            ("carl".to_owned(), Uint128::new(1)),
+        ];
+        let split = split_proportionally(Uint128::new(14), &delegations);
+        assert_eq!(
+            split,
+            [
+                ("alice".to_owned(), Uint128::new(9)),
+                ("bob".to_owned(), Uint128::new(4)),
+                ("carl".to_owned(), Uint128::new(1)),
+            ]
+        );
+
+        // Empty shares are skipped
+        let split = split_proportionally(Uint128::new(2), &delegations);
+        assert_eq!(
+            split,
+            [
+                ("alice".to_owned(), Uint128::new(1)),
+                ("carl".to_owned(), Uint128::new(1))
+            ]
+        );
+    }
 }
@@ -18,4 +18,10 @@ pub enum ContractError {
 
     #[error("Validator {0} has not enough delegated funds: {1}")]
     InsufficientDelegation(String, Uint128),
+
+    #[error("No staking rewards to be compounded")]
+    NoRewardsToCompound,
+
+    #[error("No unbonded tokens to be released")]
+    NothingToRelease,
 }
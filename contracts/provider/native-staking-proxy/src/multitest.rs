@@ -1,15 +1,17 @@
 use anyhow::Result as AnyResult;
 
 use cosmwasm_std::testing::mock_env;
-use cosmwasm_std::{coin, coins, to_binary, Addr, Decimal, Validator};
+use cosmwasm_std::{coin, coins, to_binary, Addr, Decimal, Uint128, Validator};
 
-use cw_multi_test::{App as MtApp, StakingInfo, StakingSudo, SudoMsg};
+use cw_multi_test::{App as MtApp, Executor, StakingInfo, StakingSudo, SudoMsg};
 
 use sylvia::multitest::App;
 
+use mesh_native_staking::msg::{OwnerPositionResponse, ValidatorDelegation};
 use mesh_vault::contract::multitest_utils::VaultContractProxy;
 
 use crate::contract;
+use crate::error::ContractError;
 use crate::msg::ConfigResponse;
 
 const OSMO: &str = "uosmo";
@@ -69,6 +71,7 @@ fn setup<'app>(
             denom: OSMO.to_owned(),
             proxy_code_id: staking_proxy_code.code_id(),
             max_slashing: Decimal::percent(5),
+            default_validator: None,
         })
         .unwrap(),
         label: None,
@@ -76,7 +79,11 @@ fn setup<'app>(
 
     // Instantiates vault and staking
     let vault = vault_code
-        .instantiate(OSMO.to_owned(), staking_init_info)
+        .instantiate(
+            mesh_vault::state::CollateralType::Native(OSMO.to_owned()),
+            staking_init_info,
+            None,
+        )
         .with_label("Vault")
         .call(owner)
         .unwrap();
@@ -93,7 +100,7 @@ fn setup<'app>(
         .stake_local(
             coin(100, OSMO),
             to_binary(&mesh_native_staking::msg::StakeMsg {
-                validator: validator.to_owned(),
+                validator: Some(validator.to_owned()),
             })
             .unwrap(),
         )
@@ -130,6 +137,7 @@ fn instantiation() {
             denom: OSMO.to_owned(),
             parent: Addr::unchecked(staking_addr), // parent is the staking contract
             owner: Addr::unchecked(user),          // owner is the user
+            compound_validator: None,
         }
     );
 
@@ -173,7 +181,7 @@ fn staking() {
         .stake_local(
             coin(20, OSMO),
             to_binary(&mesh_native_staking::msg::StakeMsg {
-                validator: validator.to_owned(),
+                validator: Some(validator.to_owned()),
             })
             .unwrap(),
         )
@@ -197,6 +205,226 @@ fn staking() {
     assert_eq!(delegation.amount, coin(120, OSMO));
 }
 
+#[test]
+fn owner_position() {
+    let owner = "vault_admin";
+
+    let staking_addr = "contract1"; // Second contract (instantiated by vault)
+    let proxy_addr = "contract2"; // Third contract (instantiated by staking contract on stake)
+
+    let user = "user1"; // One who wants to local stake (uses the proxy)
+    let validator = "validator1";
+    let validator2 = "validator2";
+
+    let app = init_app(user, &[validator, validator2]); // Fund user, create validators
+    let vault = setup(&app, owner, user, validator).unwrap();
+
+    // Stake on a second validator
+    vault
+        .stake_local(
+            coin(50, OSMO),
+            to_binary(&mesh_native_staking::msg::StakeMsg {
+                validator: Some(validator2.to_owned()),
+            })
+            .unwrap(),
+        )
+        .call(user)
+        .unwrap();
+
+    let staking = mesh_native_staking::contract::multitest_utils::NativeStakingContractProxy::new(
+        Addr::unchecked(staking_addr),
+        &app,
+    );
+    let position = staking.owner_position(user.to_owned()).unwrap();
+    assert_eq!(
+        position,
+        OwnerPositionResponse {
+            proxy: proxy_addr.to_owned(),
+            delegations: vec![
+                ValidatorDelegation {
+                    validator: validator.to_owned(),
+                    amount: Uint128::new(100),
+                },
+                ValidatorDelegation {
+                    validator: validator2.to_owned(),
+                    amount: Uint128::new(50),
+                },
+            ],
+        }
+    );
+
+    // Unknown owners have no position
+    staking.owner_position("user2".to_owned()).unwrap_err();
+}
+
+#[test]
+fn compounding() {
+    let owner = "vault_admin";
+
+    let proxy_addr = "contract2"; // Third contract (instantiated by staking contract on stake)
+
+    let user = "user1"; // One who wants to local stake (uses the proxy)
+    let keeper = "keeper"; // Anyone can compound
+    let validator = "validator1";
+    let validator2 = "validator2";
+
+    let app = init_app(user, &[validator, validator2]); // Fund user, create validators
+    let vault = setup(&app, owner, user, validator).unwrap();
+    vault
+        .stake_local(
+            coin(50, OSMO),
+            to_binary(&mesh_native_staking::msg::StakeMsg {
+                validator: Some(validator2.to_owned()),
+            })
+            .unwrap(),
+        )
+        .call(user)
+        .unwrap();
+
+    let staking_proxy = contract::multitest_utils::NativeStakingProxyContractProxy::new(
+        Addr::unchecked(proxy_addr),
+        &app,
+    );
+    let delegated = |validator: &str| {
+        app.app()
+            .wrap()
+            .query_delegation(staking_proxy.contract_addr.clone(), validator.to_owned())
+            .unwrap()
+            .unwrap()
+    };
+
+    // Nothing to compound yet
+    let err = staking_proxy.compound().call(keeper).unwrap_err();
+    assert_eq!(err, ContractError::NoRewardsToCompound);
+
+    // Unbonded tokens held by the proxy must not be compounded
+    app.app_mut()
+        .send_tokens(
+            Addr::unchecked(user),
+            staking_proxy.contract_addr.clone(),
+            &coins(10, OSMO),
+        )
+        .unwrap();
+
+    // Rewards accumulate for ten years
+    app.update_block(|block| block.time = block.time.plus_seconds(10 * 365 * 24 * 60 * 60));
+    let rewards: u128 = [validator, validator2]
+        .into_iter()
+        .map(|validator| delegated(validator).accumulated_rewards[0].amount.u128())
+        .sum();
+    assert_eq!(rewards, 13);
+    let user_balance = app.app().wrap().query_balance(user, OSMO).unwrap();
+
+    staking_proxy.compound().call(keeper).unwrap();
+
+    // Rewards are delegated proportionally to the delegations
+    assert_eq!(delegated(validator).amount, coin(108, OSMO));
+    assert_eq!(delegated(validator2).amount, coin(55, OSMO));
+    assert_eq!(delegated(validator).accumulated_rewards, []);
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(staking_proxy.contract_addr.clone(), OSMO)
+            .unwrap(),
+        coin(10, OSMO)
+    );
+    assert_eq!(
+        app.app().wrap().query_balance(user, OSMO).unwrap(),
+        user_balance
+    );
+
+    // Or to the compound validator, only settable by the owner
+    let err = staking_proxy
+        .set_compound_validator(Some(validator2.to_owned()))
+        .call(keeper)
+        .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    staking_proxy
+        .set_compound_validator(Some(validator2.to_owned()))
+        .call(user)
+        .unwrap();
+
+    app.update_block(|block| block.time = block.time.plus_seconds(10 * 365 * 24 * 60 * 60));
+    let rewards: u128 = [validator, validator2]
+        .into_iter()
+        .map(|validator| delegated(validator).accumulated_rewards[0].amount.u128())
+        .sum();
+    staking_proxy.compound().call(keeper).unwrap();
+    assert_eq!(delegated(validator).amount, coin(108, OSMO));
+    assert_eq!(delegated(validator2).amount, coin(55 + rewards, OSMO));
+
+    // Later rewards are withdrawn to the owner again
+    app.update_block(|block| block.time = block.time.plus_seconds(10 * 365 * 24 * 60 * 60));
+    staking_proxy.withdraw_rewards().call(user).unwrap();
+    assert!(app.app().wrap().query_balance(user, OSMO).unwrap().amount > user_balance.amount);
+}
+
+#[test]
+fn releasing_compounded() {
+    let owner = "vault_admin";
+
+    let staking_addr = "contract1"; // Second contract (instantiated by vault)
+    let proxy_addr = "contract2"; // Third contract (instantiated by staking contract on stake)
+
+    let user = "user1"; // One who wants to local stake (uses the proxy)
+    let validator = "validator1"; // Where to stake / unstake
+
+    let app = init_app(user, &[validator]); // Fund user, create validator
+    let vault = setup(&app, owner, user, validator).unwrap();
+
+    let staking_proxy = contract::multitest_utils::NativeStakingProxyContractProxy::new(
+        Addr::unchecked(proxy_addr),
+        &app,
+    );
+
+    // Rewards accumulate for ten years, and are compounded
+    app.update_block(|block| block.time = block.time.plus_seconds(10 * 365 * 24 * 60 * 60));
+    staking_proxy.compound().call(user).unwrap();
+    let staked = app
+        .app()
+        .wrap()
+        .query_delegation(staking_proxy.contract_addr.clone(), validator.to_owned())
+        .unwrap()
+        .unwrap()
+        .amount
+        .amount;
+    assert_eq!(staked.u128(), 109);
+
+    // The compounded rewards are added to the user collateral, and to the claim
+    assert_eq!(vault.account(user.to_owned()).unwrap().bonded.u128(), 209);
+    let claim = vault
+        .claim(user.to_owned(), staking_addr.to_owned())
+        .unwrap();
+    assert_eq!(claim.amount.val().unwrap().u128(), 109);
+
+    // So that all of the stake can be released
+    staking_proxy
+        .unstake(validator.to_owned(), coin(109, OSMO))
+        .call(user)
+        .unwrap();
+    app.update_block(|block| {
+        block.height += 12345;
+        block.time = block.time.plus_seconds(UNBONDING_PERIOD + 1);
+    });
+    app.app_mut()
+        .sudo(SudoMsg::Staking(StakingSudo::ProcessQueue {}))
+        .unwrap();
+    staking_proxy.release_unbonded().call(user).unwrap();
+
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(vault.contract_addr.clone(), OSMO)
+            .unwrap(),
+        coin(209, OSMO)
+    );
+    let claim = vault
+        .claim(user.to_owned(), staking_addr.to_owned())
+        .unwrap();
+    assert_eq!(claim.amount.val().unwrap().u128(), 0);
+    vault.unbond(coin(209, OSMO)).call(user).unwrap();
+}
+
 #[test]
 fn restaking() {
     let owner = "vault_admin";
@@ -357,6 +585,80 @@ fn releasing_unbonded() {
     );
 }
 
+#[test]
+fn releasing_unbonded_from_many_validators() {
+    let owner = "vault_admin";
+
+    let proxy_addr = "contract2"; // Third contract (instantiated by staking contract on stake)
+
+    let user = "user1";
+    let validators = ["validator1", "validator2"];
+
+    let app = init_app(user, &validators);
+    let vault = setup(&app, owner, user, validators[0]).unwrap();
+
+    // Stake on the second validator too, through the same proxy
+    vault
+        .stake_local(
+            coin(50, OSMO),
+            to_binary(&mesh_native_staking::msg::StakeMsg {
+                validator: Some(validators[1].to_owned()),
+            })
+            .unwrap(),
+        )
+        .call(user)
+        .unwrap();
+
+    let staking_proxy = contract::multitest_utils::NativeStakingProxyContractProxy::new(
+        Addr::unchecked(proxy_addr),
+        &app,
+    );
+
+    // Nothing unbonded yet
+    let err = staking_proxy.release_unbonded().call(user).unwrap_err();
+    assert_eq!(err, ContractError::NothingToRelease);
+
+    staking_proxy
+        .unstake(validators[0].to_owned(), coin(100, OSMO))
+        .call(user)
+        .unwrap();
+    staking_proxy
+        .unstake(validators[1].to_owned(), coin(50, OSMO))
+        .call(user)
+        .unwrap();
+
+    app.update_block(|block| {
+        block.height += 12345;
+        block.time = block.time.plus_seconds(UNBONDING_PERIOD + 1);
+    });
+    // Manually cause queue to get processed. TODO: Handle automatically in sylvia mt or cw-mt
+    app.app_mut()
+        .sudo(SudoMsg::Staking(StakingSudo::ProcessQueue {}))
+        .unwrap();
+
+    // Both unbondings are released to the vault at once
+    let resp = staking_proxy.release_unbonded().call(user).unwrap();
+    let vault_calls = resp
+        .events
+        .iter()
+        .filter(|event| {
+            event.ty == "execute"
+                && event.attributes.iter().any(|attr| {
+                    attr.key == "_contract_addr" && attr.value == vault.contract_addr.as_str()
+                })
+        })
+        .count();
+    assert_eq!(vault_calls, 1);
+
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(vault.contract_addr, OSMO)
+            .unwrap(),
+        coin(200, OSMO)
+    );
+}
+
 #[test]
 fn withdrawing_rewards() {
     let owner = "vault_admin";
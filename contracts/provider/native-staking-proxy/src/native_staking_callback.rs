@@ -1,4 +1,4 @@
-use cosmwasm_std::{Response, StdError};
+use cosmwasm_std::{Response, StdError, Uint128};
 use sylvia::types::ExecCtx;
 use sylvia::{interface, schemars};
 
@@ -12,4 +12,15 @@ pub trait NativeStakingCallback {
     /// The native-staking contract will then send those tokens back to vault and release the claim.
     #[msg(exec)]
     fn release_proxy_stake(&self, _ctx: ExecCtx) -> Result<Response, Self::Error>;
+
+    /// This reports the rewards the proxy staked back on the validators (compounding).
+    /// The native-staking contract adds them to the owner's claim on the vault, so that they
+    /// can be released along with the rest of the stake.
+    #[msg(exec)]
+    fn add_proxy_stake(
+        &self,
+        _ctx: ExecCtx,
+        amount: Uint128,
+        validators: Vec<String>,
+    ) -> Result<Response, Self::Error>;
 }
@@ -11,4 +11,9 @@ pub struct Config {
 
     /// The address of the parent contract (where we get and return stake)
     pub parent: Addr,
+
+    /// Validator the compounded rewards are delegated to. If not set, they are spread over the
+    /// current delegations
+    #[serde(default)]
+    pub compound_validator: Option<String>,
 }
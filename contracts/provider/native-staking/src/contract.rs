@@ -1,22 +1,29 @@
-use cosmwasm_std::{from_slice, Addr, Decimal, DepsMut, Reply, Response, SubMsgResponse};
+use cosmwasm_std::{
+    from_slice, Addr, Decimal, DepsMut, Order, Reply, Response, StdResult, SubMsgResponse, Uint128,
+};
 use cw2::set_contract_version;
-use cw_storage_plus::{Item, Map};
+use cw_storage_plus::{Bound, Item, Map};
 use cw_utils::parse_instantiate_response_data;
-use sylvia::types::{InstantiateCtx, QueryCtx, ReplyCtx};
+use sylvia::types::{InstantiateCtx, MigrateCtx, QueryCtx, ReplyCtx};
 use sylvia::{contract, schemars};
 
 use mesh_apis::local_staking_api;
+use mesh_apis::reply::ReplyId;
 use mesh_native_staking_proxy::msg::OwnerMsg;
 use mesh_native_staking_proxy::native_staking_callback;
 
 use crate::error::ContractError;
-use crate::msg::{ConfigResponse, OwnerByProxyResponse, ProxyByOwnerResponse};
+use crate::msg::{
+    ConfigResponse, OwnerByProxyResponse, OwnerDelegation, OwnerPositionResponse,
+    OwnersByValidatorResponse, ProxyByOwnerResponse, ValidatorDelegation,
+};
 use crate::state::Config;
 
 pub const CONTRACT_NAME: &str = env!("CARGO_PKG_NAME");
 pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
-pub const REPLY_ID_INSTANTIATE: u64 = 2;
+pub const DEFAULT_PAGE_LIMIT: u32 = 10;
+pub const MAX_PAGE_LIMIT: u32 = 30;
 
 pub struct NativeStakingContract<'a> {
     pub config: Item<'a, Config>,
@@ -24,6 +31,14 @@ pub struct NativeStakingContract<'a> {
     pub proxy_by_owner: Map<'a, &'a Addr, Addr>,
     /// Reverse map of owner address by proxy contract address
     pub owner_by_proxy: Map<'a, &'a Addr, Addr>,
+    /// Owners that staked on a validator, indexed by (validator, owner)
+    pub owners_by_validator: Map<'a, (&'a str, &'a Addr), ()>,
+}
+
+impl Default for NativeStakingContract<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[cfg_attr(not(feature = "library"), sylvia::entry_points)]
@@ -37,6 +52,7 @@ impl NativeStakingContract<'_> {
             config: Item::new("config"),
             proxy_by_owner: Map::new("proxies"),
             owner_by_proxy: Map::new("owners"),
+            owners_by_validator: Map::new("owners_by_validator"),
         }
     }
 
@@ -48,6 +64,7 @@ impl NativeStakingContract<'_> {
         denom: String,
         proxy_code_id: u64,
         max_slashing: Decimal,
+        default_validator: Option<String>,
     ) -> Result<Response, ContractError> {
         if max_slashing > Decimal::one() {
             return Err(ContractError::InvalidMaxSlashing);
@@ -58,12 +75,41 @@ impl NativeStakingContract<'_> {
             proxy_code_id,
             vault: ctx.info.sender,
             max_slashing,
+            default_validator,
         };
         self.config.save(ctx.deps.storage, &config)?;
         set_contract_version(ctx.deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
         Ok(Response::new())
     }
 
+    /// Migrates the state of previous versions of the contract
+    #[msg(migrate)]
+    fn migrate(&self, mut ctx: MigrateCtx) -> Result<Response, ContractError> {
+        self.index_owners_by_validator(ctx.deps.branch())?;
+        set_contract_version(ctx.deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+        Ok(Response::new().add_attribute("action", "migrate"))
+    }
+
+    /// Indexes the owners by the validators their proxies delegate to, for the stakes made before
+    /// the index was maintained
+    fn index_owners_by_validator(&self, deps: DepsMut) -> StdResult<()> {
+        let proxies = self
+            .owner_by_proxy
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        for (proxy, owner) in proxies {
+            for delegation in deps.querier.query_all_delegations(&proxy)? {
+                self.owners_by_validator.save(
+                    deps.storage,
+                    (&delegation.validator, &owner),
+                    &(),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
     #[msg(query)]
     fn config(&self, ctx: QueryCtx) -> Result<ConfigResponse, ContractError> {
         self.config.load(ctx.deps.storage).map_err(Into::into)
@@ -71,9 +117,9 @@ impl NativeStakingContract<'_> {
 
     #[msg(reply)]
     fn reply(&self, ctx: ReplyCtx, reply: Reply) -> Result<Response, ContractError> {
-        match reply.id {
-            REPLY_ID_INSTANTIATE => self.reply_init_callback(ctx.deps, reply.result.unwrap()),
-            _ => Err(ContractError::InvalidReplyId(reply.id)),
+        match ReplyId::try_from(reply.id).map_err(ContractError::InvalidReplyId)? {
+            ReplyId::Instantiate => self.reply_init_callback(ctx.deps, reply.result.unwrap()),
+            id => Err(ContractError::InvalidReplyId(id.into())),
         }
     }
 
@@ -110,6 +156,32 @@ impl NativeStakingContract<'_> {
         })
     }
 
+    /// Returns the owner's proxy along with its delegations, so a local staking position can be
+    /// shown with a single query
+    #[msg(query)]
+    fn owner_position(
+        &self,
+        ctx: QueryCtx,
+        owner: String,
+    ) -> Result<OwnerPositionResponse, ContractError> {
+        let owner_addr = ctx.deps.api.addr_validate(&owner)?;
+        let proxy_addr = self.proxy_by_owner.load(ctx.deps.storage, &owner_addr)?;
+        let delegations = ctx
+            .deps
+            .querier
+            .query_all_delegations(&proxy_addr)?
+            .into_iter()
+            .map(|delegation| ValidatorDelegation {
+                validator: delegation.validator,
+                amount: delegation.amount.amount,
+            })
+            .collect();
+        Ok(OwnerPositionResponse {
+            proxy: proxy_addr.into_string(),
+            delegations,
+        })
+    }
+
     #[msg(query)]
     fn owner_by_proxy(
         &self,
@@ -122,4 +194,105 @@ impl NativeStakingContract<'_> {
             owner: owner_addr.to_string(),
         })
     }
+
+    /// Paginates over the owners that staked on the validator, with their current delegation to
+    /// it. Owners that unstaked since are still listed, with a zero amount.
+    ///
+    /// `start_after` is the last owner included in previous page
+    #[msg(query)]
+    fn owners_by_validator(
+        &self,
+        ctx: QueryCtx,
+        validator: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    ) -> Result<OwnersByValidatorResponse, ContractError> {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT) as usize;
+        let start_after = start_after
+            .map(|owner| ctx.deps.api.addr_validate(&owner))
+            .transpose()?;
+        let bound = start_after.as_ref().map(Bound::exclusive);
+
+        let owners = self
+            .owners_by_validator
+            .prefix(&validator)
+            .keys(ctx.deps.storage, bound, None, Order::Ascending)
+            .take(limit)
+            .map(|owner| {
+                let owner = owner?;
+                let proxy = self.proxy_by_owner.load(ctx.deps.storage, &owner)?;
+                let amount = ctx
+                    .deps
+                    .querier
+                    .query_delegation(&proxy, &validator)?
+                    .map(|delegation| delegation.amount.amount)
+                    .unwrap_or_else(Uint128::zero);
+                Ok(OwnerDelegation {
+                    owner: owner.into_string(),
+                    amount,
+                })
+            })
+            .collect::<StdResult<_>>()?;
+
+        Ok(OwnersByValidatorResponse { owners })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use cosmwasm_std::{coin, FullDelegation, Validator};
+
+    use super::*;
+
+    #[test]
+    fn migration_indexes_owners_by_validator() {
+        let mut deps = mock_dependencies();
+        let contract = NativeStakingContract::new();
+        let (owner, proxy) = (Addr::unchecked("owner"), Addr::unchecked("proxy"));
+        contract
+            .owner_by_proxy
+            .save(&mut deps.storage, &proxy, &owner)
+            .unwrap();
+        contract
+            .proxy_by_owner
+            .save(&mut deps.storage, &owner, &proxy)
+            .unwrap();
+
+        let validator = |address: &str| Validator {
+            address: address.to_owned(),
+            commission: Decimal::zero(),
+            max_commission: Decimal::one(),
+            max_change_rate: Decimal::one(),
+        };
+        let delegation = |address: &str, amount| FullDelegation {
+            delegator: proxy.clone(),
+            validator: address.to_owned(),
+            amount: coin(amount, "uosmo"),
+            can_redelegate: coin(amount, "uosmo"),
+            accumulated_rewards: vec![],
+        };
+        deps.querier.update_staking(
+            "uosmo",
+            &[validator("val1"), validator("val2"), validator("val3")],
+            &[delegation("val1", 100), delegation("val2", 50)],
+        );
+
+        contract
+            .migrate((deps.as_mut(), mock_env()).into())
+            .unwrap();
+
+        let indexed = contract
+            .owners_by_validator
+            .keys(&deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()
+            .unwrap();
+        assert_eq!(
+            indexed,
+            [
+                ("val1".to_owned(), owner.clone()),
+                ("val2".to_owned(), owner)
+            ]
+        );
+    }
 }
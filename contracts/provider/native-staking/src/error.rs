@@ -24,4 +24,7 @@ pub enum ContractError {
 
     #[error("You cannot use a max slashing rate over 1.0 (100%)")]
     InvalidMaxSlashing,
+
+    #[error("No validator given, and no default validator configured")]
+    NoValidatorSpecified,
 }
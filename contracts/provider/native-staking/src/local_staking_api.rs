@@ -1,12 +1,13 @@
 use cosmwasm_std::{ensure_eq, from_slice, to_binary, Binary, Response, SubMsg, WasmMsg};
-use cw_utils::must_pay;
+use cw_utils::{must_pay, nonpayable};
 use sylvia::types::QueryCtx;
 use sylvia::{contract, types::ExecCtx};
 
 #[allow(unused_imports)]
 use mesh_apis::local_staking_api::{self, LocalStakingApi, MaxSlashResponse};
+use mesh_apis::reply::ReplyId;
 
-use crate::contract::{NativeStakingContract, REPLY_ID_INSTANTIATE};
+use crate::contract::NativeStakingContract;
 use crate::error::ContractError;
 use crate::msg::StakeMsg;
 
@@ -36,10 +37,15 @@ impl LocalStakingApi for NativeStakingContract<'_> {
         // Assert funds are passed in
         let _paid = must_pay(&ctx.info, &cfg.denom)?;
 
-        // Parse message to find validator to stake on
+        // Parse message to find validator to stake on, falling back to the default one
         let StakeMsg { validator } = from_slice(&msg)?;
+        let validator = validator
+            .or(cfg.default_validator)
+            .ok_or(ContractError::NoValidatorSpecified)?;
 
         let owner_addr = ctx.deps.api.addr_validate(&owner)?;
+        self.owners_by_validator
+            .save(ctx.deps.storage, (&validator, &owner_addr), &())?;
 
         // Look up if there is a proxy to match. Instantiate or call stake on existing
         match self
@@ -60,7 +66,7 @@ impl LocalStakingApi for NativeStakingContract<'_> {
                     funds: ctx.info.funds,
                     label: format!("LSP for {owner}"),
                 };
-                let sub_msg = SubMsg::reply_on_success(wasm_msg, REPLY_ID_INSTANTIATE);
+                let sub_msg = SubMsg::reply_on_success(wasm_msg, ReplyId::Instantiate.into());
                 Ok(Response::new().add_submessage(sub_msg))
             }
             Some(proxy_addr) => {
@@ -77,6 +83,37 @@ impl LocalStakingApi for NativeStakingContract<'_> {
         }
     }
 
+    /// Unstakes all of the owner's delegations, through their proxy. Can only be called by the
+    /// vault, in emergencies
+    #[msg(exec)]
+    fn emergency_unstake(&self, ctx: ExecCtx, owner: String) -> Result<Response, Self::Error> {
+        // Can only be called by the vault
+        let cfg = self.config.load(ctx.deps.storage)?;
+        ensure_eq!(cfg.vault, ctx.info.sender, ContractError::Unauthorized {});
+
+        nonpayable(&ctx.info)?;
+
+        let owner_addr = ctx.deps.api.addr_validate(&owner)?;
+
+        // Nothing to unstake if the owner never staked
+        match self
+            .proxy_by_owner
+            .may_load(ctx.deps.storage, &owner_addr)?
+        {
+            None => Ok(Response::new()),
+            Some(proxy_addr) => {
+                let msg =
+                    to_binary(&mesh_native_staking_proxy::contract::ExecMsg::EmergencyUnstake {})?;
+                let wasm_msg = WasmMsg::Execute {
+                    contract_addr: proxy_addr.into(),
+                    msg,
+                    funds: vec![],
+                };
+                Ok(Response::new().add_message(wasm_msg))
+            }
+        }
+    }
+
     /// Returns the maximum percentage that can be slashed
     #[msg(query)]
     fn max_slash(&self, ctx: QueryCtx) -> Result<MaxSlashResponse, Self::Error> {
@@ -1,5 +1,6 @@
 use crate::state::Config;
 use cosmwasm_schema::cw_serde;
+use cosmwasm_std::Uint128;
 
 pub type ConfigResponse = Config;
 
@@ -13,8 +14,30 @@ pub struct OwnerByProxyResponse {
     pub owner: String,
 }
 
-/// The message that is binary encoded in `receive_stake(..msg)`
 #[cw_serde]
-pub struct StakeMsg {
+pub struct OwnersByValidatorResponse {
+    pub owners: Vec<OwnerDelegation>,
+}
+
+#[cw_serde]
+pub struct OwnerDelegation {
+    pub owner: String,
+    /// Amount currently delegated to the validator, through the owner's proxy
+    pub amount: Uint128,
+}
+
+#[cw_serde]
+pub struct OwnerPositionResponse {
+    pub proxy: String,
+    /// Current delegations of the owner's proxy
+    pub delegations: Vec<ValidatorDelegation>,
+}
+
+#[cw_serde]
+pub struct ValidatorDelegation {
     pub validator: String,
+    pub amount: Uint128,
 }
+
+/// The message that is binary encoded in `receive_stake(..msg)`
+pub use mesh_apis::local_staking_api::StakeMsg;
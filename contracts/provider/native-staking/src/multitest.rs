@@ -1,4 +1,5 @@
-use cosmwasm_std::{coin, coins, to_binary, Addr, Decimal, StdError, Uint128};
+use cosmwasm_std::testing::mock_env;
+use cosmwasm_std::{coin, coins, to_binary, Addr, Binary, Decimal, StdError, Uint128, Validator};
 
 use cw_multi_test::App as MtApp;
 use sylvia::multitest::App;
@@ -13,7 +14,7 @@ mod local_staking_proxy;
 use crate::contract;
 use crate::error::ContractError;
 use crate::msg;
-use crate::msg::{OwnerByProxyResponse, ProxyByOwnerResponse};
+use crate::msg::{OwnerByProxyResponse, OwnerDelegation, ProxyByOwnerResponse};
 
 const OSMO: &str = "OSMO";
 
@@ -37,6 +38,7 @@ fn instantiation() {
             OSMO.to_owned(),
             staking_proxy_code.code_id(),
             slashing_rate(),
+            None,
         )
         .with_label("Staking")
         .call(owner)
@@ -58,12 +60,26 @@ fn receiving_stake() {
 
     let validator = "validator1"; // Validator to stake on
 
-    // Fund the vault
-    let app = MtApp::new(|router, _api, storage| {
+    // Fund the vault, and add the validator
+    let app = MtApp::new(|router, api, storage| {
         router
             .bank
             .init_balance(storage, &Addr::unchecked(owner), coins(300, OSMO))
             .unwrap();
+        router
+            .staking
+            .add_validator(
+                api,
+                storage,
+                &mock_env().block,
+                Validator {
+                    address: validator.to_owned(),
+                    commission: Decimal::zero(),
+                    max_commission: Decimal::zero(),
+                    max_change_rate: Decimal::zero(),
+                },
+            )
+            .unwrap();
     });
     let app = App::new(app);
 
@@ -76,6 +92,7 @@ fn receiving_stake() {
             OSMO.to_owned(),
             staking_proxy_code.code_id(),
             slashing_rate(),
+            None,
         )
         .with_label("Staking")
         .call(owner)
@@ -90,7 +107,7 @@ fn receiving_stake() {
 
     // Receive some stake on behalf of user1 for validator
     let stake_msg = to_binary(&msg::StakeMsg {
-        validator: validator.to_owned(),
+        validator: Some(validator.to_owned()),
     })
     .unwrap();
     staking
@@ -120,7 +137,7 @@ fn receiving_stake() {
 
     // Stake some more
     let stake_msg = to_binary(&msg::StakeMsg {
-        validator: validator.to_owned(),
+        validator: Some(validator.to_owned()),
     })
     .unwrap();
     staking
@@ -154,7 +171,7 @@ fn receiving_stake() {
 
     // Receive some stake on behalf of user2 for validator
     let stake_msg = to_binary(&msg::StakeMsg {
-        validator: validator.to_owned(),
+        validator: Some(validator.to_owned()),
     })
     .unwrap();
     staking
@@ -178,6 +195,137 @@ fn receiving_stake() {
         app.app().wrap().query_balance(proxy2, OSMO).unwrap(),
         coin(10, OSMO)
     );
+
+    // Both owners are indexed by validator. The mock proxy doesn't delegate, so amounts are zero
+    let owners = staking
+        .owners_by_validator(validator.to_owned(), None, None)
+        .unwrap()
+        .owners;
+    assert_eq!(
+        owners,
+        [
+            OwnerDelegation {
+                owner: user1.to_owned(),
+                amount: Uint128::zero(),
+            },
+            OwnerDelegation {
+                owner: user2.to_owned(),
+                amount: Uint128::zero(),
+            }
+        ]
+    );
+    let owners = staking
+        .owners_by_validator(validator.to_owned(), Some(user1.to_owned()), None)
+        .unwrap()
+        .owners;
+    assert_eq!(owners.len(), 1);
+    assert_eq!(owners[0].owner, user2);
+}
+
+#[test]
+fn default_validator() {
+    let owner = "vault"; // Owner of the staking contract (i. e. the vault contract)
+
+    let user = "user1";
+
+    let default_validator = "validator1";
+    let validator = "validator2";
+
+    let app = MtApp::new(|router, api, storage| {
+        router
+            .bank
+            .init_balance(storage, &Addr::unchecked(owner), coins(300, OSMO))
+            .unwrap();
+        for address in [default_validator, validator] {
+            router
+                .staking
+                .add_validator(
+                    api,
+                    storage,
+                    &mock_env().block,
+                    Validator {
+                        address: address.to_owned(),
+                        commission: Decimal::zero(),
+                        max_commission: Decimal::zero(),
+                        max_change_rate: Decimal::zero(),
+                    },
+                )
+                .unwrap();
+        }
+    });
+    let app = App::new(app);
+
+    let staking_proxy_code = local_staking_proxy::multitest_utils::CodeId::store_code(&app);
+    let staking_code = contract::multitest_utils::CodeId::store_code(&app);
+
+    // Without a default validator, the stake msg has to name one
+    let staking = staking_code
+        .instantiate(
+            OSMO.to_owned(),
+            staking_proxy_code.code_id(),
+            slashing_rate(),
+            None,
+        )
+        .with_label("Staking")
+        .call(owner)
+        .unwrap();
+
+    let err = staking
+        .local_staking_api_proxy()
+        .receive_stake(
+            user.to_owned(),
+            to_binary(&msg::StakeMsg { validator: None }).unwrap(),
+        )
+        .with_funds(&coins(100, OSMO))
+        .call(owner)
+        .unwrap_err();
+    assert!(matches!(err, ContractError::NoValidatorSpecified));
+
+    let staking = staking_code
+        .instantiate(
+            OSMO.to_owned(),
+            staking_proxy_code.code_id(),
+            slashing_rate(),
+            Some(default_validator.to_owned()),
+        )
+        .with_label("Staking")
+        .call(owner)
+        .unwrap();
+    assert_eq!(
+        staking.config().unwrap().default_validator.as_deref(),
+        Some(default_validator)
+    );
+
+    // An explicit validator is used as is
+    staking
+        .local_staking_api_proxy()
+        .receive_stake(
+            user.to_owned(),
+            to_binary(&msg::StakeMsg {
+                validator: Some(validator.to_owned()),
+            })
+            .unwrap(),
+        )
+        .with_funds(&coins(100, OSMO))
+        .call(owner)
+        .unwrap();
+
+    // An omitted one falls back to the default validator
+    staking
+        .local_staking_api_proxy()
+        .receive_stake(user.to_owned(), Binary::from(b"{}".as_slice()))
+        .with_funds(&coins(100, OSMO))
+        .call(owner)
+        .unwrap();
+
+    for val in [validator, default_validator] {
+        let owners = staking
+            .owners_by_validator(val.to_owned(), None, None)
+            .unwrap()
+            .owners;
+        assert_eq!(owners.len(), 1, "{val}");
+        assert_eq!(owners[0].owner, user);
+    }
 }
 
 #[test]
@@ -213,6 +361,7 @@ fn releasing_proxy_stake() {
             denom: OSMO.to_owned(),
             proxy_code_id: staking_proxy_code.code_id(),
             max_slashing: slashing_rate(),
+            default_validator: None,
         })
         .unwrap(),
         label: None,
@@ -220,7 +369,11 @@ fn releasing_proxy_stake() {
 
     // Instantiates vault and staking contracts
     let vault = vault_code
-        .instantiate(OSMO.to_owned(), staking_init_info)
+        .instantiate(
+            mesh_vault::state::CollateralType::Native(OSMO.to_owned()),
+            staking_init_info,
+            None,
+        )
         .with_label("Vault")
         .call(owner)
         .unwrap();
@@ -256,7 +409,7 @@ fn releasing_proxy_stake() {
         .stake_local(
             coin(100, OSMO),
             to_binary(&msg::StakeMsg {
-                validator: validator.to_owned(),
+                validator: Some(validator.to_owned()),
             })
             .unwrap(),
         )
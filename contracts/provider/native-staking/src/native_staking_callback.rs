@@ -1,5 +1,5 @@
-use cosmwasm_std::Response;
-use cw_utils::must_pay;
+use cosmwasm_std::{coin, Response, Uint128};
+use cw_utils::{must_pay, nonpayable};
 use sylvia::contract;
 use sylvia::types::ExecCtx;
 
@@ -40,4 +40,35 @@ impl NativeStakingCallback for NativeStakingContract<'_> {
 
         Ok(Response::new().add_message(msg))
     }
+
+    /// This reports the rewards the proxy staked back on the validators (compounding).
+    /// The native-staking contract adds them to the owner's claim on the vault, so that they
+    /// can be released along with the rest of the stake.
+    #[msg(exec)]
+    fn add_proxy_stake(
+        &self,
+        ctx: ExecCtx,
+        amount: Uint128,
+        validators: Vec<String>,
+    ) -> Result<Response, Self::Error> {
+        nonpayable(&ctx.info)?;
+        let cfg = self.config.load(ctx.deps.storage)?;
+
+        // Look up account owner by proxy address (info.sender). This asserts the caller is a valid
+        // proxy
+        let owner_addr = self
+            .owner_by_proxy
+            .load(ctx.deps.storage, &ctx.info.sender)?;
+
+        // The rewards may be staked on a validator the owner didn't stake on yet
+        for validator in &validators {
+            self.owners_by_validator
+                .save(ctx.deps.storage, (validator, &owner_addr), &())?;
+        }
+
+        let msg = VaultApiHelper(cfg.vault)
+            .add_local_stake(owner_addr.to_string(), coin(amount.u128(), cfg.denom))?;
+
+        Ok(Response::new().add_message(msg))
+    }
 }
@@ -14,4 +14,8 @@ pub struct Config {
 
     /// Max slash percentage (from InstantiateMsg, maybe later from the chain)
     pub max_slashing: Decimal,
+
+    /// Validator to stake on when the `StakeMsg` doesn't name one
+    #[serde(default)]
+    pub default_validator: Option<String>,
 }
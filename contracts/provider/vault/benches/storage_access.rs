@@ -0,0 +1,334 @@
+//! Storage accesses per message of the `stake_cross_txs` and `multiple_stakes` multitest
+//! scenarios.
+//!
+//! Run with `cargo test -p mesh-vault --features profiling --bench storage_access`. Only the
+//! vault storage accesses are counted, including the ones of the vault messages and queries
+//! triggered by the message. Each message is checked against a generous baseline, so regressions
+//! fail loudly.
+use cosmwasm_std::testing::mock_env;
+use cosmwasm_std::{coin, coins, to_binary, Addr, Decimal, Validator};
+use cw_multi_test::{App as MtApp, Executor, StakingInfo};
+use mesh_apis::ibc::AddValidator;
+use mesh_external_staking::contract::multitest_utils::{
+    CodeId as ExternalStakingCodeId, ExternalStakingContractProxy,
+};
+use mesh_external_staking::msg::{AuthorizedEndpoint, ReceiveVirtualStake};
+use mesh_external_staking::state::RewardDenom;
+use mesh_external_staking::test_methods_impl::test_utils::TestMethods;
+use mesh_sync::Tx;
+use mesh_vault::contract::multitest_utils::VaultContractProxy;
+use mesh_vault::contract::{InstantiateMsg, VaultContract};
+use mesh_vault::msg::StakingInitInfo;
+use mesh_vault::profiling::{AccessCounter, AccessCounts, ProfiledContract};
+use mesh_vault::state::CollateralType;
+use sylvia::multitest::App;
+
+const OSMO: &str = "OSMO";
+const STAR: &str = "star";
+
+/// Upper bounds of the storage reads (including ranges) and writes (including removes) per
+/// message. About twice the measured counts, so only significant regressions are caught
+const BASELINE: &[(&str, u64, u64)] = &[
+    ("bond", 10, 6),
+    ("stake_local", 16, 12),
+    ("stake_remote", 12, 12),
+    ("commit_stake", 16, 16),
+    ("rollback_stake", 16, 16),
+    ("withdraw_unbonded", 10, 6),
+    ("unbond", 10, 6),
+];
+
+/// Storage accesses of the messages run so far
+struct Profile {
+    counter: AccessCounter,
+    scenario: &'static str,
+    measures: Vec<(&'static str, AccessCounts)>,
+}
+
+impl Profile {
+    fn measure<T>(&mut self, entry_point: &'static str, f: impl FnOnce() -> T) -> T {
+        let (res, counts) = self.counter.measure(f);
+        println!("{}/{entry_point}: {counts}", self.scenario);
+        self.measures.push((entry_point, counts));
+        res
+    }
+}
+
+fn init_app(users: &[&str], amounts: &[u128]) -> App<MtApp> {
+    let app = MtApp::new(|router, api, storage| {
+        for (&user, amount) in std::iter::zip(users, amounts) {
+            router
+                .bank
+                .init_balance(storage, &Addr::unchecked(user), coins(*amount, OSMO))
+                .unwrap();
+        }
+        router
+            .staking
+            .setup(
+                storage,
+                StakingInfo {
+                    bonded_denom: OSMO.to_string(),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        router
+            .staking
+            .add_validator(
+                api,
+                storage,
+                &mock_env().block,
+                Validator {
+                    address: "local".to_string(),
+                    commission: Decimal::zero(),
+                    max_commission: Decimal::zero(),
+                    max_change_rate: Decimal::zero(),
+                },
+            )
+            .unwrap();
+    });
+
+    App::new(app)
+}
+
+/// Instantiates the vault, counting its storage accesses
+fn setup_vault<'app>(
+    app: &'app App<MtApp>,
+    owner: &str,
+) -> (VaultContractProxy<'app, MtApp>, AccessCounter) {
+    let native_staking_code =
+        mesh_native_staking::contract::multitest_utils::CodeId::store_code(app);
+    let native_staking_proxy_code =
+        mesh_native_staking_proxy::contract::multitest_utils::CodeId::store_code(app);
+    let vault_contract = ProfiledContract::new(VaultContract::new());
+    let counter = vault_contract.counter();
+    let vault_code = app.app_mut().store_code(Box::new(vault_contract));
+
+    let native_staking_inst_msg = mesh_native_staking::contract::InstantiateMsg {
+        denom: OSMO.to_string(),
+        max_slashing: Decimal::percent(10),
+        proxy_code_id: native_staking_proxy_code.code_id(),
+        default_validator: None,
+    };
+    let staking_init_info = StakingInitInfo {
+        admin: None,
+        code_id: native_staking_code.code_id(),
+        msg: to_binary(&native_staking_inst_msg).unwrap(),
+        label: None,
+    };
+
+    let msg = InstantiateMsg {
+        collateral: CollateralType::Native(OSMO.to_owned()),
+        local_staking: staking_init_info,
+        tx_timeout: None,
+    };
+    let vault_addr = app
+        .app_mut()
+        .instantiate_contract(
+            vault_code,
+            Addr::unchecked(owner),
+            &msg,
+            &[],
+            "Vault",
+            Some(owner.to_owned()),
+        )
+        .unwrap();
+
+    (VaultContractProxy::new(vault_addr, app), counter)
+}
+
+fn setup_cross_stake<'app>(
+    app: &'app App<MtApp>,
+    owner: &str,
+    vault: &VaultContractProxy<'app, MtApp>,
+    validator: &str,
+) -> ExternalStakingContractProxy<'app, MtApp> {
+    let cross_staking = ExternalStakingCodeId::store_code(app)
+        .instantiate(
+            OSMO.to_owned(),
+            vec![RewardDenom::from_denom(STAR)],
+            vault.contract_addr.to_string(),
+            100,
+            AuthorizedEndpoint::new("connection-2", "wasm-osmo1foobarbaz"),
+            Decimal::percent(10),
+        )
+        .call(owner)
+        .unwrap();
+
+    cross_staking
+        .test_methods_proxy()
+        .test_set_active_validator(AddValidator::mock(validator))
+        .call("test")
+        .unwrap();
+
+    cross_staking
+}
+
+fn last_pending_tx_id(contract: &ExternalStakingContractProxy<MtApp>) -> u64 {
+    let txs = contract.all_pending_txs_desc(None, None).unwrap().txs;
+    txs.first().map(Tx::id).unwrap()
+}
+
+fn stake_remote(
+    profile: &mut Profile,
+    vault: &VaultContractProxy<MtApp>,
+    cross_staking: &ExternalStakingContractProxy<MtApp>,
+    user: &str,
+    validator: &str,
+    amount: u128,
+) -> u64 {
+    let msg = to_binary(&ReceiveVirtualStake {
+        validator: validator.to_string(),
+    })
+    .unwrap();
+    profile.measure("stake_remote", || {
+        vault
+            .stake_remote(
+                cross_staking.contract_addr.to_string(),
+                coin(amount, OSMO),
+                msg,
+                None,
+            )
+            .call(user)
+            .unwrap()
+    });
+    last_pending_tx_id(cross_staking)
+}
+
+fn stake_cross_txs(profile: &mut Profile) {
+    let owner = "owner";
+    let users = ["user1", "user2"];
+    let validator = "validator";
+
+    let app = init_app(&users, &[300, 500]);
+    profile.scenario = "stake_cross_txs";
+
+    let (vault, counter) = setup_vault(&app, owner);
+    profile.counter = counter;
+    let cross_staking = setup_cross_stake(&app, owner, &vault, validator);
+
+    for (user, amount) in users.into_iter().zip([300, 500]) {
+        profile.measure("bond", || {
+            vault
+                .bond()
+                .with_funds(&coins(amount, OSMO))
+                .call(user)
+                .unwrap()
+        });
+    }
+
+    let first_tx = stake_remote(profile, &vault, &cross_staking, users[0], validator, 100);
+    let second_tx = stake_remote(profile, &vault, &cross_staking, users[0], validator, 50);
+    let third_tx = stake_remote(profile, &vault, &cross_staking, users[1], validator, 100);
+
+    let test_methods = cross_staking.test_methods_proxy();
+    profile.measure("commit_stake", || {
+        test_methods
+            .test_commit_stake(first_tx)
+            .call("test")
+            .unwrap()
+    });
+    profile.measure("rollback_stake", || {
+        test_methods
+            .test_rollback_stake(second_tx)
+            .call("test")
+            .unwrap()
+    });
+    profile.measure("commit_stake", || {
+        test_methods
+            .test_commit_stake(third_tx)
+            .call("test")
+            .unwrap()
+    });
+}
+
+fn multiple_stakes(profile: &mut Profile) {
+    let owner = "owner";
+    let user = "user1";
+    let validator = "validator";
+
+    let app = init_app(&[user], &[1000]);
+    profile.scenario = "multiple_stakes";
+
+    let (vault, counter) = setup_vault(&app, owner);
+    profile.counter = counter;
+    let cross_staking1 = setup_cross_stake(&app, owner, &vault, validator);
+    let cross_staking2 = setup_cross_stake(&app, owner, &vault, validator);
+
+    profile.measure("bond", || {
+        vault
+            .bond()
+            .with_funds(&coins(1000, OSMO))
+            .call(user)
+            .unwrap()
+    });
+
+    let msg = to_binary(&mesh_native_staking::msg::StakeMsg {
+        validator: Some("local".to_string()),
+    })
+    .unwrap();
+    profile.measure("stake_local", || {
+        vault.stake_local(coin(300, OSMO), msg).call(user).unwrap()
+    });
+
+    for (cross_staking, amount) in [
+        (&cross_staking1, 200),
+        (&cross_staking2, 100),
+        (&cross_staking1, 200),
+    ] {
+        let tx_id = stake_remote(profile, &vault, cross_staking, user, validator, amount);
+        profile.measure("commit_stake", || {
+            cross_staking
+                .test_methods_proxy()
+                .test_commit_stake(tx_id)
+                .call("test")
+                .unwrap()
+        });
+    }
+
+    // The vault is only involved once the unbonding period is over
+    cross_staking1
+        .unstake(validator.to_string(), coin(300, OSMO))
+        .call(user)
+        .unwrap();
+    cross_staking1
+        .test_methods_proxy()
+        .test_commit_unstake(last_pending_tx_id(&cross_staking1))
+        .call("test")
+        .unwrap();
+    app.update_block(|block| {
+        block.height += 1;
+        block.time = block.time.plus_seconds(101);
+    });
+
+    profile.measure("withdraw_unbonded", || {
+        cross_staking1.withdraw_unbonded(None).call(user).unwrap()
+    });
+    profile.measure("unbond", || {
+        vault.unbond(coin(100, OSMO)).call(user).unwrap()
+    });
+}
+
+fn main() {
+    let mut profile = Profile {
+        counter: ProfiledContract::new(()).counter(),
+        scenario: "",
+        measures: vec![],
+    };
+
+    stake_cross_txs(&mut profile);
+    multiple_stakes(&mut profile);
+
+    for (entry_point, counts) in profile.measures {
+        let (_, max_reads, max_writes) = BASELINE
+            .iter()
+            .find(|(name, _, _)| *name == entry_point)
+            .unwrap_or_else(|| panic!("No baseline for {entry_point}"));
+        let reads = counts.reads + counts.ranges;
+        let writes = counts.writes + counts.removes;
+        assert!(
+            reads <= *max_reads && writes <= *max_writes,
+            "{entry_point} storage accesses over the baseline: {counts}"
+        );
+    }
+}
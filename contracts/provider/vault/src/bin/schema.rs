@@ -1,6 +1,7 @@
 use cosmwasm_schema::write_api;
 
 use mesh_vault::contract::{ContractExecMsg, ContractQueryMsg, InstantiateMsg};
+use mesh_vault::error::ERROR_CODES;
 
 #[cfg(not(tarpaulin_include))]
 fn main() {
@@ -9,4 +10,22 @@ fn main() {
         execute: ContractExecMsg,
         query: ContractQueryMsg,
     }
+
+    // Published along with the schema, for frontends to map error codes
+    let codes = cosmwasm_std::to_vec(ERROR_CODES).unwrap();
+    std::fs::write("schema/mesh-vault-error-codes.json", codes).unwrap();
+
+    // Samples of the messages and responses, checked by the `schema_compat` test, for indexers to
+    // check their compatibility against
+    let samples = [
+        ("execute", include_str!("../../tests/golden/execute.json")),
+        ("query", include_str!("../../tests/golden/query.json")),
+        (
+            "responses",
+            include_str!("../../tests/golden/responses.json"),
+        ),
+    ];
+    for (name, sample) in samples {
+        std::fs::write(format!("schema/mesh-vault-samples-{name}.json"), sample).unwrap();
+    }
 }
@@ -1,42 +1,90 @@
 use cosmwasm_std::{
-    coin, ensure, Addr, BankMsg, Binary, Coin, Decimal, DepsMut, Fraction, Order, Reply, Response,
-    StdResult, Storage, SubMsg, SubMsgResponse, Uint128, WasmMsg,
+    coin, ensure, from_binary, to_binary, Addr, Api, BankMsg, Binary, Coin, CosmosMsg, Decimal,
+    Deps, DepsMut, Env, Event, Fraction, Order, Reply, Response, StdResult, Storage, SubMsg,
+    SubMsgResponse, Uint128, WasmMsg,
 };
 use cw2::set_contract_version;
-use cw_storage_plus::{Bounder, Item, Map};
+use cw20::Cw20ExecuteMsg;
+use cw_storage_plus::{Bound, Bounder, Deque, IndexedMap, Item, Map};
 use cw_utils::{must_pay, nonpayable, parse_instantiate_response_data};
-use std::cmp::min;
+use std::cmp::{max, min};
 
-use mesh_apis::cross_staking_api::CrossStakingApiHelper;
+use mesh_apis::cross_staking_api::{CanStakeResponse, CrossStakingApiHelper};
 use mesh_apis::local_staking_api::{
-    LocalStakingApiHelper, LocalStakingApiQueryMsg, MaxSlashResponse,
+    LocalStakingApiHelper, LocalStakingApiQueryMsg, MaxSlashResponse, StakeMsg,
 };
-use mesh_apis::vault_api::{self, SlashInfo, VaultApi};
+use mesh_apis::reply::ReplyId;
+use mesh_apis::vault_api::{self, SlashInfo, VaultApi, VaultCw20HookMsg};
 use mesh_sync::Tx::InFlightStaking;
-use mesh_sync::{max_range, ValueRange};
-use sylvia::types::{ExecCtx, InstantiateCtx, QueryCtx, ReplyCtx};
+use mesh_sync::{max_range, Tx, TxHistory, TxStatus, ValueRange, DEFAULT_TX_HISTORY_LEN};
+use sylvia::types::{ExecCtx, InstantiateCtx, MigrateCtx, QueryCtx, ReplyCtx};
 use sylvia::{contract, schemars};
 
 use crate::error::ContractError;
 use crate::msg::{
-    AccountClaimsResponse, AccountDetailsResponse, AccountResponse, AllAccountsResponse,
-    AllAccountsResponseItem, AllTxsResponse, AllTxsResponseItem, ConfigResponse, LienResponse,
-    StakingInitInfo, TxResponse,
+    AccountClaimsResponse, AccountDetailsResponse, AccountResponse,
+    AccountsByFreeCollateralResponse, AccountsOrderBy, AllAccountsResponse,
+    AllAccountsResponseItem, AllLiensResponse, AllLiensResponseItem, AllTxsResponse,
+    AllTxsResponseItem, ConfigResponse, EmergencyUnstakeResponse, FeeStatsResponse,
+    InvariantViolation, InvariantsReport, InvariantsResponse, LienResponse, NativeStakingQueryMsg,
+    OwnersByValidatorResponse, ProxyByOwnerResponse, SnapshotAccountResponse,
+    SnapshotAccountsResponse, SnapshotAccountsResponseItem, StakingInitInfo, SudoMsg, TxResponse,
+    TxsHistoryResponse, VaultOp, VotingPowerReportResponse,
+};
+use crate::snapshots::Snapshots;
+use crate::state::{
+    CollateralType, Config, FeeStats, IdempotentStake, Lien, LienKind, LocalSlash, LocalStaking,
+    UserInfo,
 };
-use crate::state::{Config, Lien, LocalStaking, UserInfo};
 use crate::txs::Txs;
+use crate::users::UserIndexes;
 
 pub const CONTRACT_NAME: &str = env!("CARGO_PKG_NAME");
 pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
-pub const REPLY_ID_INSTANTIATE: u64 = 1;
-
 pub const DEFAULT_PAGE_LIMIT: u32 = 10;
 pub const MAX_PAGE_LIMIT: u32 = 30;
 
+/// Owners slashed on a local slash notification, the others are slashed by `continue_local_slash`
+pub const LOCAL_SLASH_LIMIT: u32 = 90;
+pub const MAX_LOCAL_SLASH_LIMIT: u32 = 300;
+
+/// Max number of operations in a `batch` call
+pub const MAX_BATCH_LEN: usize = 10;
+
+/// Max bond and unbond fees, in percents
+pub const MAX_FEE_PERCENT: u64 = 5;
+
+/// Time during which a `stake_remote` idempotency key is remembered, in seconds
+pub const IDEMPOTENCY_KEY_TTL: u64 = 24 * 60 * 60;
+pub const MAX_IDEMPOTENCY_KEY_LEN: usize = 64;
+
 /// Aligns pagination limit
 fn clamp_page_limit(limit: Option<u32>) -> usize {
-    limit.unwrap_or(DEFAULT_PAGE_LIMIT).max(MAX_PAGE_LIMIT) as usize
+    limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT) as usize
+}
+
+/// Validates an address and brings it to its normal form, as the lienholders may pass the owners
+/// in any form the chain accepts (eg. upper-cased bech32)
+fn normalize_addr(api: &dyn Api, addr: &str) -> StdResult<Addr> {
+    api.addr_humanize(&api.addr_canonicalize(addr)?)
+}
+
+/// Formats tx ids for an attribute
+fn join_tx_ids(tx_ids: &[u64]) -> String {
+    tx_ids
+        .iter()
+        .map(u64::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn join_slashed_users(slashes: &[SlashInfo]) -> String {
+    slashes
+        .iter()
+        .map(|s| s.user.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
 /// Default falseness for serde
@@ -44,6 +92,22 @@ fn def_false() -> bool {
     false
 }
 
+/// Ensures `amount` can be added to the range without overflowing
+fn ensure_addable(range: ValueRange<Uint128>, amount: Uint128) -> Result<(), ContractError> {
+    range
+        .high()
+        .checked_add(amount)
+        .map_err(|_| ContractError::Overflow)?;
+    Ok(())
+}
+
+/// Slashable part of `amount`
+fn slashable_amount(amount: Uint128, slashable: Decimal) -> Result<Uint128, ContractError> {
+    amount
+        .checked_multiply_ratio(slashable.atomics(), Decimal::one().atomics())
+        .map_err(|_| ContractError::Overflow)
+}
+
 pub struct VaultContract<'a> {
     /// General contract configuration
     pub config: Item<'a, Config>,
@@ -53,11 +117,30 @@ pub struct VaultContract<'a> {
     ///
     /// Liens are indexed with (user, lien_holder), as this pair has to be unique
     pub liens: Map<'a, (&'a Addr, &'a Addr), Lien>,
-    /// Per-user information
-    pub users: Map<'a, &'a Addr, UserInfo>,
+    /// Per-user information, indexed by collateral
+    pub users: IndexedMap<'a, &'a Addr, UserInfo, UserIndexes<'a>>,
+    /// Sum of all the users collateral
+    pub total_collateral: Item<'a, Uint128>,
+    /// Whether `total_collateral` covers all the users. Set on instantiation, and on migration once
+    /// the total is recomputed
+    pub total_collateral_tracked: Item<'a, bool>,
+    /// Collateral tokens sent to the local staking contract, less the ones sent back or slashed
+    pub local_outstanding: Item<'a, Uint128>,
+    /// Users collateral snapshots
+    pub snapshots: Snapshots<'a>,
     /// Pending txs information
     pub tx_count: Item<'a, u64>,
     pub pending: Txs<'a>,
+    /// Recently resolved txs
+    pub tx_history: TxHistory<'a>,
+    /// Next user to be processed by `emergency_unstake_all_local`
+    pub emergency_unstake_cursor: Item<'a, Addr>,
+    /// Remote stakes by user and idempotency key
+    pub idempotency_keys: Map<'a, (&'a Addr, &'a str), IdempotentStake>,
+    /// Bond and unbond fees collected
+    pub fee_stats: Item<'a, FeeStats>,
+    /// Local slashes not applied to all the owners yet, oldest first
+    pub local_slashes: Deque<'a, LocalSlash>,
 }
 
 #[cfg_attr(not(feature = "library"), sylvia::entry_points)]
@@ -70,9 +153,25 @@ impl VaultContract<'_> {
             config: Item::new("config"),
             local_staking: Item::new("local_staking"),
             liens: Map::new("liens"),
-            users: Map::new("users"),
-            pending: Txs::new("pending_txs", "users"),
+            users: IndexedMap::new(
+                "users",
+                UserIndexes::new("users", "users__collateral", "free_collateral"),
+            ),
+            total_collateral: Item::new("total_collateral"),
+            total_collateral_tracked: Item::new("total_collateral_tracked"),
+            local_outstanding: Item::new("local_outstanding"),
+            snapshots: Snapshots::new("snapshot_id", "snapshots", "snapshot_collateral"),
+            pending: Txs::new(
+                "pending_txs",
+                "pending_txs__users",
+                "pending_txs__lienholders",
+            ),
             tx_count: Item::new("tx_count"),
+            tx_history: TxHistory::new("tx_history", "tx_history_order", DEFAULT_TX_HISTORY_LEN),
+            emergency_unstake_cursor: Item::new("emergency_unstake_cursor"),
+            idempotency_keys: Map::new("idempotency_keys"),
+            fee_stats: Item::new("fee_stats"),
+            local_slashes: Deque::new("local_slashes"),
         }
     }
 
@@ -82,17 +181,198 @@ impl VaultContract<'_> {
         Ok(id)
     }
 
+    /// Ensures the sender is the admin of this contract (the one able to migrate it)
+    fn ensure_admin(&self, ctx: &ExecCtx) -> Result<(), ContractError> {
+        let info = ctx
+            .deps
+            .querier
+            .query_wasm_contract_info(&ctx.env.contract.address)?;
+        ensure!(
+            info.admin.as_deref() == Some(ctx.info.sender.as_str()),
+            ContractError::Unauthorized {}
+        );
+        Ok(())
+    }
+
+    /// Updates the user's collateral, keeping the total collateral and snapshots in sync.
+    fn set_collateral(
+        &self,
+        storage: &mut dyn Storage,
+        user: &Addr,
+        user_info: &mut UserInfo,
+        collateral: Uint128,
+    ) -> Result<(), ContractError> {
+        self.snapshots
+            .checkpoint(storage, user, user_info.collateral)?;
+
+        let total = self.total_collateral.may_load(storage)?.unwrap_or_default();
+        let total = total
+            .checked_sub(user_info.collateral)
+            .map_err(|_| ContractError::Underflow)?
+            .checked_add(collateral)
+            .map_err(|_| ContractError::Overflow)?;
+        self.total_collateral.save(storage, &total)?;
+
+        user_info.collateral = collateral;
+        Ok(())
+    }
+
+    /// Adds `sent` tokens to the ones outstanding on the local staking contract, and removes the
+    /// `returned` ones.
+    ///
+    /// Returned tokens are capped to the outstanding ones, in case the local staking contract
+    /// returns more than it was sent
+    fn update_local_outstanding(
+        &self,
+        storage: &mut dyn Storage,
+        sent: Uint128,
+        returned: Uint128,
+    ) -> Result<(), ContractError> {
+        let outstanding = self
+            .local_outstanding
+            .may_load(storage)?
+            .unwrap_or_default();
+        let outstanding = outstanding
+            .checked_add(sent)
+            .map_err(|_| ContractError::Overflow)?
+            .saturating_sub(returned);
+        self.local_outstanding.save(storage, &outstanding)?;
+        Ok(())
+    }
+
+    /// Returns the collateral denom, failing if the collateral is not a native token
+    fn native_denom(&self, storage: &dyn Storage) -> Result<String, ContractError> {
+        match self.config.load(storage)?.collateral {
+            CollateralType::Native(denom) => Ok(denom),
+            CollateralType::Cw20(_) => Err(ContractError::Cw20Collateral),
+        }
+    }
+
+    /// Adds `amount` to the user's collateral, net of the bond fee
+    fn bond_collateral(
+        &self,
+        storage: &mut dyn Storage,
+        sender: Addr,
+        amount: Uint128,
+    ) -> Result<Response, ContractError> {
+        let config = self.config.load(storage)?;
+        let (net, fee_msg) = Self::take_fee(&config, amount, config.bond_fee)?;
+        if let Some(max) = config.max_total_collateral {
+            let total = self.total_collateral.may_load(storage)?.unwrap_or_default();
+            ensure!(
+                total.checked_add(net).is_ok_and(|total| total <= max),
+                ContractError::CollateralCapReached(max)
+            );
+        }
+
+        let mut user = self.load_user(storage, &sender)?;
+        let collateral = user
+            .collateral
+            .checked_add(net)
+            .map_err(|_| ContractError::Overflow)?;
+        self.set_collateral(storage, &sender, &mut user, collateral)?;
+        self.users.save(storage, &sender, &user)?;
+        self.assert_invariants(storage, &sender)?;
+
+        let mut resp = Response::new()
+            .add_attribute("action", "bond")
+            .add_attribute("sender", sender)
+            .add_attribute("amount", amount.to_string());
+        if let Some(fee_msg) = fee_msg {
+            let fee = amount - net;
+            let mut stats = self.fee_stats.may_load(storage)?.unwrap_or_default();
+            stats.bond_fees = stats
+                .bond_fees
+                .checked_add(fee)
+                .map_err(|_| ContractError::Overflow)?;
+            self.fee_stats.save(storage, &stats)?;
+            resp = resp
+                .add_message(fee_msg)
+                .add_attribute("fee", fee.to_string());
+        }
+
+        Ok(resp)
+    }
+
+    /// Splits the fee off `amount`. Returns the net amount, and the message sending the fee to
+    /// the fee recipient if there is any fee.
+    ///
+    /// The fee is rounded down, in favor of the user: amounts too small to pay a whole unit of fee
+    /// are free of fees
+    fn take_fee(
+        config: &Config,
+        amount: Uint128,
+        fee: Decimal,
+    ) -> StdResult<(Uint128, Option<CosmosMsg>)> {
+        let fee = amount * fee;
+        match &config.fee_recipient {
+            Some(recipient) if !fee.is_zero() => {
+                let msg = Self::send_collateral_msg(&config.collateral, recipient, fee)?;
+                Ok((amount - fee, Some(msg)))
+            }
+            _ => Ok((amount, None)),
+        }
+    }
+
+    /// Releases `amount` of the owner's local stake, sent back by the local staking contract
+    fn release_local_stake_amount(
+        &self,
+        mut ctx: ExecCtx,
+        owner: String,
+        amount: Uint128,
+    ) -> Result<Response, ContractError> {
+        let denom = self.config.load(ctx.deps.storage)?.collateral.denom();
+        let owner = normalize_addr(ctx.deps.api, &owner)?;
+
+        self.unstake(
+            &mut ctx,
+            &owner,
+            coin(amount.u128(), denom),
+            LienKind::Local,
+            false,
+        )?;
+        self.update_local_outstanding(ctx.deps.storage, Uint128::zero(), amount)?;
+
+        let resp = Response::new()
+            .add_attribute("action", "release_cross_stake")
+            .add_attribute("sender", ctx.info.sender)
+            .add_attribute("owner", owner)
+            .add_attribute("amount", amount.to_string());
+
+        Ok(resp)
+    }
+
     #[msg(instantiate)]
     pub fn instantiate(
         &self,
         ctx: InstantiateCtx,
-        denom: String,
+        collateral: CollateralType,
         local_staking: StakingInitInfo,
+        // Seconds after which a pending tx can be expired by the admin. Pending txs never expire if
+        // not set
+        tx_timeout: Option<u64>,
     ) -> Result<Response, ContractError> {
         nonpayable(&ctx.info)?;
 
-        let config = Config { denom };
+        let collateral = match collateral {
+            CollateralType::Cw20(addr) => {
+                CollateralType::Cw20(ctx.deps.api.addr_validate(addr.as_str())?)
+            }
+            native => native,
+        };
+        let config = Config {
+            collateral,
+            tx_timeout,
+            bond_fee: Decimal::zero(),
+            unbond_fee: Decimal::zero(),
+            fee_recipient: None,
+            max_total_collateral: None,
+            min_release: None,
+            check_remote_stake: false,
+        };
         self.config.save(ctx.deps.storage, &config)?;
+        self.total_collateral_tracked
+            .save(ctx.deps.storage, &true)?;
         set_contract_version(ctx.deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
         // instantiate local_staking and handle reply
@@ -105,66 +385,307 @@ impl VaultContract<'_> {
                 .label
                 .unwrap_or_else(|| "Mesh Security Local Staking".to_string()),
         };
-        let sub_msg = SubMsg::reply_on_success(msg, REPLY_ID_INSTANTIATE);
+        let sub_msg = SubMsg::reply_on_success(msg, ReplyId::Instantiate.into());
         Ok(Response::new().add_submessage(sub_msg))
     }
 
+    /// Migrates the state of previous versions of the contract
+    #[msg(migrate)]
+    fn migrate(&self, ctx: MigrateCtx) -> Result<Response, ContractError> {
+        crate::migration::migrate_collateral(ctx.deps.storage, self)?;
+        crate::migration::migrate_lien_kinds(ctx.deps.storage, self)?;
+        crate::migration::move_pending_txs_index(ctx.deps.storage, self)?;
+        crate::migration::index_pending_txs_lienholders(ctx.deps.storage, self)?;
+        crate::migration::index_users_collateral(ctx.deps.storage, self)?;
+        crate::migration::init_local_outstanding(ctx.deps.storage, self)?;
+        crate::migration::init_total_collateral(ctx.deps.storage, self)?;
+        set_contract_version(ctx.deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+        Ok(Response::new().add_attribute("action", "migrate"))
+    }
+
+    /// Bonds native collateral. Cw20 collateral is bonded by sending it with a
+    /// `VaultCw20HookMsg::Bond` message
     #[msg(exec)]
     fn bond(&self, ctx: ExecCtx) -> Result<Response, ContractError> {
-        let denom = self.config.load(ctx.deps.storage)?.denom;
+        let denom = self.native_denom(ctx.deps.storage)?;
         let amount = must_pay(&ctx.info, &denom)?;
 
-        let mut user = self
-            .users
-            .may_load(ctx.deps.storage, &ctx.info.sender)?
-            .unwrap_or_default();
-        user.collateral += amount;
-        self.users.save(ctx.deps.storage, &ctx.info.sender, &user)?;
+        self.bond_collateral(ctx.deps.storage, ctx.info.sender, amount)
+    }
 
-        let resp = Response::new()
-            .add_attribute("action", "bond")
-            .add_attribute("sender", ctx.info.sender)
-            .add_attribute("amount", amount.to_string());
+    /// Cw20 receive hook, for cw20 collateral. `msg` is a `VaultCw20HookMsg`
+    #[msg(exec)]
+    fn receive(
+        &self,
+        mut ctx: ExecCtx,
+        // address which sent the tokens
+        sender: String,
+        amount: Uint128,
+        msg: Binary,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
 
-        Ok(resp)
+        let collateral = self.config.load(ctx.deps.storage)?.collateral;
+        ensure!(
+            collateral == CollateralType::Cw20(ctx.info.sender.clone()),
+            ContractError::UnexpectedDenom(collateral.denom())
+        );
+        let sender = ctx.deps.api.addr_validate(&sender)?;
+
+        match from_binary(&msg)? {
+            VaultCw20HookMsg::Bond {} => self.bond_collateral(ctx.deps.storage, sender, amount),
+            VaultCw20HookMsg::ReleaseLocalStake { owner } => {
+                let local_staking = self.local_staking.load(ctx.deps.storage)?;
+                ensure!(
+                    sender == local_staking.contract.0,
+                    ContractError::Unauthorized {}
+                );
+                // The local staking contract is the lienholder, not the token contract
+                ctx.info.sender = sender;
+                self.release_local_stake_amount(ctx, owner, amount)
+            }
+        }
     }
 
     #[msg(exec)]
-    fn unbond(&self, ctx: ExecCtx, amount: Coin) -> Result<Response, ContractError> {
+    fn unbond(&self, mut ctx: ExecCtx, amount: Coin) -> Result<Response, ContractError> {
         nonpayable(&ctx.info)?;
 
-        let denom = self.config.load(ctx.deps.storage)?.denom;
+        self.unbond_collateral(&mut ctx, amount)
+    }
 
-        ensure!(denom == amount.denom, ContractError::UnexpectedDenom(denom));
+    /// Reserves `amount` of the sender's collateral as a buffer against slashing. The reserve can
+    /// be neither staked nor unbonded, and must be free even if the pending stakes are committed.
+    ///
+    /// It replaces any previous reserve, zero clears it.
+    #[msg(exec)]
+    fn set_reserve(&self, ctx: ExecCtx, amount: Uint128) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let mut user = self.load_user(ctx.deps.storage, &ctx.info.sender)?;
+        user.reserved = amount;
+        ensure!(user.covers_reserve(), ContractError::InsufficientBalance);
+        self.users.save(ctx.deps.storage, &ctx.info.sender, &user)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "set_reserve")
+            .add_attribute("owner", ctx.info.sender)
+            .add_attribute("amount", amount.to_string()))
+    }
+
+    /// Caps the leverage (max lien over collateral) of the sender. Stakes leaving it over
+    /// `max_leverage` are rejected. Positions already over it are kept.
+    ///
+    /// None removes the limit. Only accounts with a position can set it.
+    #[msg(exec)]
+    fn set_max_leverage(
+        &self,
+        ctx: ExecCtx,
+        max_leverage: Option<Decimal>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
 
         let mut user = self
             .users
             .may_load(ctx.deps.storage, &ctx.info.sender)?
-            .unwrap_or_default();
-
-        let free_collateral = user.free_collateral();
-        ensure!(
-            free_collateral.low() >= amount.amount,
-            ContractError::ClaimsLocked(free_collateral)
-        );
-
-        user.collateral -= amount.amount;
+            .ok_or(ContractError::UnknownAccount)?;
+        user.max_leverage = max_leverage;
         self.users.save(ctx.deps.storage, &ctx.info.sender, &user)?;
 
-        let msg = BankMsg::Send {
-            to_address: ctx.info.sender.to_string(),
-            amount: vec![amount.clone()],
-        };
+        let max_leverage = max_leverage
+            .map(|ratio| ratio.to_string())
+            .unwrap_or_else(|| "none".to_owned());
+        Ok(Response::new()
+            .add_attribute("action", "set_max_leverage")
+            .add_attribute("owner", ctx.info.sender)
+            .add_attribute("max_leverage", max_leverage))
+    }
 
-        let resp = Response::new()
-            .add_message(msg)
+    /// Unbonds `amount` of the sender's free collateral, sending it back to them net of the
+    /// unbond fee
+    fn unbond_collateral(
+        &self,
+        ctx: &mut ExecCtx,
+        amount: Coin,
+    ) -> Result<Response, ContractError> {
+        let unbond_fee = self.config.load(ctx.deps.storage)?.unbond_fee;
+        let (msgs, fee) = self.withdraw_collateral(
+            ctx.deps.storage,
+            &ctx.info.sender,
+            &ctx.info.sender,
+            &amount,
+            unbond_fee,
+        )?;
+
+        let mut resp = Response::new()
+            .add_messages(msgs)
             .add_attribute("action", "unbond")
-            .add_attribute("sender", ctx.info.sender)
+            .add_attribute("sender", &ctx.info.sender)
             .add_attribute("amount", amount.to_string());
+        if !fee.is_zero() {
+            let mut stats = self
+                .fee_stats
+                .may_load(ctx.deps.storage)?
+                .unwrap_or_default();
+            stats.unbond_fees = stats
+                .unbond_fees
+                .checked_add(fee)
+                .map_err(|_| ContractError::Overflow)?;
+            self.fee_stats.save(ctx.deps.storage, &stats)?;
+            resp = resp.add_attribute("fee", fee.to_string());
+        }
 
         Ok(resp)
     }
 
+    /// Removes `amount` of the owner's free collateral, returning the messages sending it to the
+    /// recipient, minus the `fee` part sent to the fee recipient. The fee amount is returned along
+    fn withdraw_collateral(
+        &self,
+        storage: &mut dyn Storage,
+        owner: &Addr,
+        recipient: &Addr,
+        amount: &Coin,
+        fee: Decimal,
+    ) -> Result<(Vec<CosmosMsg>, Uint128), ContractError> {
+        let config = self.config.load(storage)?;
+        let denom = config.collateral.denom();
+
+        ensure!(denom == amount.denom, ContractError::UnexpectedDenom(denom));
+
+        let mut user = self.load_user(storage, owner)?;
+
+        // Only collateral free whatever the outcome of the pending txs, and not reserved, can be
+        // withdrawn
+        let free_collateral = user.unreserved_collateral();
+        if !free_collateral.guaranteed_ge(amount.amount) {
+            return Err(self.claims_locked(storage, owner, free_collateral)?);
+        }
+
+        let collateral = user
+            .collateral
+            .checked_sub(amount.amount)
+            .map_err(|_| ContractError::Underflow)?;
+        self.set_collateral(storage, owner, &mut user, collateral)?;
+        self.users.save(storage, owner, &user)?;
+        self.assert_invariants(storage, owner)?;
+
+        let (net, fee_msg) = Self::take_fee(&config, amount.amount, fee)?;
+        let msg = Self::send_collateral_msg(&config.collateral, recipient, net)?;
+        Ok((
+            std::iter::once(msg).chain(fee_msg).collect(),
+            amount.amount - net,
+        ))
+    }
+
+    /// Error for an unbond over the `free` collateral of `owner`, telling what blocks it: the
+    /// lien using the most collateral and the pending txs
+    fn claims_locked(
+        &self,
+        storage: &dyn Storage,
+        owner: &Addr,
+        free: ValueRange<Uint128>,
+    ) -> Result<ContractError, ContractError> {
+        let liens: Vec<_> = self
+            .liens
+            .prefix(owner)
+            .range(storage, None, None, Order::Ascending)
+            .collect::<StdResult<_>>()?;
+        let max_lienholder = liens
+            .into_iter()
+            .max_by_key(|(_, lien)| lien.amount.high())
+            .map(|(lienholder, _)| lienholder);
+        let pending_txs = self.pending.txs_by_user(storage, owner)?.len() as u32;
+
+        Ok(ContractError::ClaimsLocked {
+            free,
+            max_lienholder,
+            pending_txs,
+        })
+    }
+
+    /// Message sending `amount` of the collateral from this contract to `recipient`
+    fn send_collateral_msg(
+        collateral: &CollateralType,
+        recipient: &Addr,
+        amount: Uint128,
+    ) -> StdResult<CosmosMsg> {
+        let msg = match collateral {
+            CollateralType::Native(denom) => BankMsg::Send {
+                to_address: recipient.to_string(),
+                amount: vec![coin(amount.u128(), denom)],
+            }
+            .into(),
+            CollateralType::Cw20(cw20) => WasmMsg::Execute {
+                contract_addr: cw20.to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: recipient.to_string(),
+                    amount,
+                })?,
+                funds: vec![],
+            }
+            .into(),
+        };
+        Ok(msg)
+    }
+
+    /// Collateral balance of this contract
+    fn collateral_balance(
+        deps: Deps,
+        env: &Env,
+        collateral: &CollateralType,
+    ) -> StdResult<Uint128> {
+        let balance = match collateral {
+            CollateralType::Native(denom) => {
+                deps.querier
+                    .query_balance(&env.contract.address, denom)?
+                    .amount
+            }
+            CollateralType::Cw20(cw20) => {
+                let resp: cw20::BalanceResponse = deps.querier.query_wasm_smart(
+                    cw20,
+                    &cw20::Cw20QueryMsg::Balance {
+                        address: env.contract.address.to_string(),
+                    },
+                )?;
+                resp.balance
+            }
+        };
+        Ok(balance)
+    }
+
+    /// Makes the `payouts` in order out of the collateral slashed by `slashes`, as far as it and
+    /// the collateral balance allow. Returns the amounts actually paid, with the transfers
+    fn slash_payouts(
+        &self,
+        deps: Deps,
+        env: &Env,
+        slashes: &[SlashInfo],
+        payouts: Vec<(Addr, Uint128)>,
+    ) -> StdResult<(Vec<Uint128>, Vec<CosmosMsg>)> {
+        let config = self.config.load(deps.storage)?;
+        let slashed: Uint128 = slashes.iter().map(|s| s.slash).sum();
+        let balance = Self::collateral_balance(deps, env, &config.collateral)?;
+        let mut available = min(slashed, balance);
+
+        let mut paid = vec![];
+        let mut msgs = vec![];
+        for (recipient, amount) in payouts {
+            let amount = min(amount, available);
+            available -= amount;
+            if !amount.is_zero() {
+                msgs.push(Self::send_collateral_msg(
+                    &config.collateral,
+                    &recipient,
+                    amount,
+                )?);
+            }
+            paid.push(amount);
+        }
+        Ok((paid, msgs))
+    }
+
     /// This assigns a claim of amount tokens to the remote contract, which can take some action with it
     #[msg(exec)]
     fn stake_remote(
@@ -176,16 +697,72 @@ impl VaultContract<'_> {
         amount: Coin,
         // action to take with that stake
         msg: Binary,
+        // retrying with the same key returns the original tx id instead of staking again
+        idempotency_key: Option<String>,
     ) -> Result<Response, ContractError> {
         nonpayable(&ctx.info)?;
 
+        self.stake_remote_collateral(&mut ctx, contract, amount, msg, idempotency_key)
+    }
+
+    /// Assigns a claim of `amount` of the sender's collateral to the remote `contract`. The
+    /// created tx id is set as response data.
+    fn stake_remote_collateral(
+        &self,
+        ctx: &mut ExecCtx,
+        contract: String,
+        amount: Coin,
+        msg: Binary,
+        idempotency_key: Option<String>,
+    ) -> Result<Response, ContractError> {
+        if let Some(key) = &idempotency_key {
+            ensure!(
+                key.len() <= MAX_IDEMPOTENCY_KEY_LEN,
+                ContractError::IdempotencyKeyTooLong(MAX_IDEMPOTENCY_KEY_LEN)
+            );
+            let seen = self
+                .idempotency_keys
+                .may_load(ctx.deps.storage, (&ctx.info.sender, key))?;
+            if let Some(seen) = seen.filter(|seen| seen.expires_at > ctx.env.block.time) {
+                let resp = Response::new()
+                    .set_data(to_binary(&seen.tx_id)?)
+                    .add_attribute("action", "stake_remote")
+                    .add_attribute("sender", &ctx.info.sender)
+                    .add_attribute("tx_id", seen.tx_id.to_string())
+                    .add_attribute("idempotency_key", key);
+                return Ok(resp);
+            }
+        }
+
         let config = self.config.load(ctx.deps.storage)?;
         let contract = ctx.deps.api.addr_validate(&contract)?;
         let contract = CrossStakingApiHelper(contract);
-        let slashable = contract.max_slash(ctx.deps.as_ref())?;
+        let slashable = contract.max_slash(ctx.deps.as_ref()).map_err(|source| {
+            ContractError::NotACrossStakingContract {
+                addr: contract.0.to_string(),
+                source,
+            }
+        })?;
+        // The slash ratio is trusted to compute the slashable collateral
+        ensure!(
+            slashable.max_slash <= Decimal::one(),
+            ContractError::InvalidSlashRatio(slashable.max_slash)
+        );
+        // Fail right away rather than with a rollback, if the stake would be rejected
+        if config.check_remote_stake {
+            let verdict = contract.can_stake(
+                ctx.deps.as_ref(),
+                ctx.info.sender.to_string(),
+                amount.clone(),
+                msg.clone(),
+            )?;
+            if let CanStakeResponse::Rejected { reason } = verdict {
+                return Err(ContractError::RemoteStakeRejected(reason));
+            }
+        }
 
         let tx_id = self.stake(
-            &mut ctx,
+            ctx,
             &config,
             &contract.0,
             slashable.max_slash,
@@ -201,16 +778,54 @@ impl VaultContract<'_> {
             vec![],
         )?;
 
-        let resp = Response::new()
+        let mut resp = Response::new()
+            .set_data(to_binary(&tx_id)?)
             .add_message(stake_msg)
             .add_attribute("action", "stake_remote")
-            .add_attribute("sender", ctx.info.sender)
+            .add_attribute("sender", &ctx.info.sender)
             .add_attribute("amount", amount.amount.to_string())
             .add_attribute("tx_id", tx_id.to_string());
 
+        if let Some(key) = idempotency_key {
+            self.save_idempotency_key(ctx, &key, tx_id)?;
+            resp = resp.add_attribute("idempotency_key", key);
+        }
+
         Ok(resp)
     }
 
+    /// Remembers the sender's `key` for `tx_id`, forgetting its expired keys
+    fn save_idempotency_key(
+        &self,
+        ctx: &mut ExecCtx,
+        key: &str,
+        tx_id: u64,
+    ) -> Result<(), ContractError> {
+        let now = ctx.env.block.time;
+        let expired = self
+            .idempotency_keys
+            .prefix(&ctx.info.sender)
+            .range(ctx.deps.storage, None, None, Order::Ascending)
+            .filter(|item| {
+                item.as_ref()
+                    .map_or(true, |(_, seen)| seen.expires_at <= now)
+            })
+            .map(|item| item.map(|(key, _)| key))
+            .collect::<StdResult<Vec<_>>>()?;
+        for expired_key in expired {
+            self.idempotency_keys
+                .remove(ctx.deps.storage, (&ctx.info.sender, &expired_key));
+        }
+
+        let seen = IdempotentStake {
+            tx_id,
+            expires_at: now.plus_seconds(IDEMPOTENCY_KEY_TTL),
+        };
+        self.idempotency_keys
+            .save(ctx.deps.storage, (&ctx.info.sender, key), &seen)?;
+        Ok(())
+    }
+
     /// This sends actual tokens to the local staking contract
     #[msg(exec)]
     fn stake_local(
@@ -223,42 +838,129 @@ impl VaultContract<'_> {
     ) -> Result<Response, ContractError> {
         nonpayable(&ctx.info)?;
 
+        self.stake_local_collateral(&mut ctx, amount, msg)
+    }
+
+    /// Like `stake_local`, delegating `amount` to `validator`. The `StakeMsg` is encoded here, for
+    /// local staking contracts delegating to validators, as the native staking one
+    #[msg(exec)]
+    fn stake_local_on(
+        &self,
+        mut ctx: ExecCtx,
+        validator: String,
+        amount: Coin,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let msg = to_binary(&StakeMsg {
+            validator: Some(validator.clone()),
+        })?;
+        let resp = self.stake_local_collateral(&mut ctx, amount, msg)?;
+        Ok(resp.add_attribute("validator", validator))
+    }
+
+    /// Stakes `amount` of the sender's collateral on the local staking contract
+    fn stake_local_collateral(
+        &self,
+        ctx: &mut ExecCtx,
+        amount: Coin,
+        msg: Binary,
+    ) -> Result<Response, ContractError> {
         let config = self.config.load(ctx.deps.storage)?;
         let local_staking = self.local_staking.load(ctx.deps.storage)?;
 
         self.stake(
-            &mut ctx,
+            ctx,
             &config,
             &local_staking.contract.0,
             local_staking.max_slash,
             amount.clone(),
             false,
         )?;
-
-        let stake_msg = local_staking.contract.receive_stake(
-            ctx.info.sender.to_string(),
-            msg,
-            vec![amount.clone()],
-        )?;
+        self.update_local_outstanding(ctx.deps.storage, amount.amount, Uint128::zero())?;
+
+        let stake_msg = match &config.collateral {
+            CollateralType::Native(_) => local_staking.contract.receive_stake(
+                ctx.info.sender.to_string(),
+                msg,
+                vec![amount.clone()],
+            )?,
+            CollateralType::Cw20(cw20) => local_staking.contract.receive_cw20_stake(
+                cw20,
+                ctx.info.sender.to_string(),
+                msg,
+                amount.amount,
+            )?,
+        };
 
         let resp = Response::new()
             .add_message(stake_msg)
             .add_attribute("action", "stake_local")
-            .add_attribute("sender", ctx.info.sender)
+            .add_attribute("sender", &ctx.info.sender)
             .add_attribute("amount", amount.amount.to_string());
 
         Ok(resp)
     }
 
+    /// Executes up to `MAX_BATCH_LEN` operations atomically, in order. The first failing one
+    /// reverts the whole batch.
+    ///
+    /// The attached funds are bonded by the `Bond` operation, which can only be included once.
+    /// Each operation reports its attributes in a `batch_op` event, along with its index
+    #[msg(exec)]
+    fn batch(&self, mut ctx: ExecCtx, ops: Vec<VaultOp>) -> Result<Response, ContractError> {
+        ensure!(
+            !ops.is_empty() && ops.len() <= MAX_BATCH_LEN,
+            ContractError::InvalidBatchLength(MAX_BATCH_LEN)
+        );
+        let bonds = ops
+            .iter()
+            .filter(|op| matches!(op, VaultOp::Bond {}))
+            .count();
+        ensure!(bonds <= 1, ContractError::MultipleBatchBonds);
+        if bonds == 0 {
+            nonpayable(&ctx.info)?;
+        }
+
+        let mut resp = Response::new()
+            .add_attribute("action", "batch")
+            .add_attribute("sender", &ctx.info.sender)
+            .add_attribute("ops", ops.len().to_string());
+
+        for (index, op) in ops.into_iter().enumerate() {
+            let op_resp = match op {
+                VaultOp::Bond {} => {
+                    let denom = self.native_denom(ctx.deps.storage)?;
+                    let amount = must_pay(&ctx.info, &denom)?;
+                    self.bond_collateral(ctx.deps.storage, ctx.info.sender.clone(), amount)?
+                }
+                VaultOp::StakeLocal { amount, msg } => {
+                    self.stake_local_collateral(&mut ctx, amount, msg)?
+                }
+                VaultOp::StakeRemote {
+                    contract,
+                    amount,
+                    msg,
+                } => self.stake_remote_collateral(&mut ctx, contract, amount, msg, None)?,
+                VaultOp::Unbond { amount } => self.unbond_collateral(&mut ctx, amount)?,
+            };
+
+            resp = resp.add_submessages(op_resp.messages).add_event(
+                Event::new("batch_op")
+                    .add_attribute("index", index.to_string())
+                    .add_attributes(op_resp.attributes),
+            );
+        }
+
+        Ok(resp)
+    }
+
     #[msg(query)]
     fn account(&self, ctx: QueryCtx, account: String) -> Result<AccountResponse, ContractError> {
-        let denom = self.config.load(ctx.deps.storage)?.denom;
+        let denom = self.config.load(ctx.deps.storage)?.collateral.denom();
         let account = ctx.deps.api.addr_validate(&account)?;
 
-        let user = self
-            .users
-            .may_load(ctx.deps.storage, &account)?
-            .unwrap_or_default();
+        let user = self.load_user(ctx.deps.storage, &account)?;
         Ok(AccountResponse {
             denom,
             bonded: user.collateral,
@@ -266,25 +968,205 @@ impl VaultContract<'_> {
         })
     }
 
+    /// Whether `account` has any active position: some collateral or any lien with an amount,
+    /// including pending ones. Cheaper than fetching the whole account
+    ///
+    /// Released liens are kept with no amount, so they don't count.
     #[msg(query)]
-    fn account_details(
-        &self,
-        ctx: QueryCtx,
-        account: String,
-    ) -> Result<AccountDetailsResponse, ContractError> {
-        let denom = self.config.load(ctx.deps.storage)?.denom;
+    fn is_participant(&self, ctx: QueryCtx, account: String) -> Result<bool, ContractError> {
         let account = ctx.deps.api.addr_validate(&account)?;
 
-        let user = self
-            .users
-            .may_load(ctx.deps.storage, &account)?
-            .unwrap_or_default();
+        let user = self.load_user(ctx.deps.storage, &account)?;
+        if !user.collateral.is_zero() {
+            return Ok(true);
+        }
+
+        for item in
+            self.liens
+                .prefix(&account)
+                .range(ctx.deps.storage, None, None, Order::Ascending)
+        {
+            let (_, lien) = item?;
+            if !lien.amount.high().is_zero() {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Total slashable collateral of `account`: the sum of its liens weighted by their slashable
+    /// ratios, as a range over the pending txs
+    #[msg(query)]
+    fn slashable_collateral(
+        &self,
+        ctx: QueryCtx,
+        account: String,
+    ) -> Result<ValueRange<Uint128>, ContractError> {
+        let account = ctx.deps.api.addr_validate(&account)?;
+
+        let user = self.load_user(ctx.deps.storage, &account)?;
+        Ok(user.total_slashable)
+    }
+
+    #[msg(query)]
+    fn account_details(
+        &self,
+        ctx: QueryCtx,
+        account: String,
+    ) -> Result<AccountDetailsResponse, ContractError> {
+        let denom = self.config.load(ctx.deps.storage)?.collateral.denom();
+        let account = ctx.deps.api.addr_validate(&account)?;
+
+        let user = self.load_user(ctx.deps.storage, &account)?;
         Ok(AccountDetailsResponse {
             denom,
             bonded: user.collateral,
             free: user.free_collateral(),
             max_lien: user.max_lien,
             total_slashable: user.total_slashable,
+            reserved: user.reserved,
+            max_leverage: user.max_leverage,
+        })
+    }
+
+    /// Checks the account accounting against its liens. Broken invariants are listed in the report
+    /// `violations`, instead of failing the query
+    #[msg(query)]
+    fn check_invariants(
+        &self,
+        ctx: QueryCtx,
+        account: String,
+    ) -> Result<InvariantsReport, ContractError> {
+        let account = ctx.deps.api.addr_validate(&account)?;
+        self.invariants_report(ctx.deps.storage, &account)
+    }
+
+    /// Checks the accounting of `account`, or of a page of the accounts if not set. Only the broken
+    /// invariants are returned, see `check_invariants` for the checked ones.
+    ///
+    /// The first page of all the accounts also checks the vault collateral balance covers the
+    /// collateral not staked locally.
+    ///
+    /// `start_after` is the `cursor` returned by the previous page
+    #[msg(query)]
+    fn invariants(
+        &self,
+        ctx: QueryCtx,
+        account: Option<String>,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    ) -> Result<InvariantsResponse, ContractError> {
+        let mut violations = vec![];
+        let (accounts, cursor) = match account {
+            Some(account) => (vec![ctx.deps.api.addr_validate(&account)?], None),
+            None => {
+                if start_after.is_none() && !self.balance_covers_collateral(&ctx)? {
+                    violations.push(InvariantViolation {
+                        account: None,
+                        invariant: "collateral_balance_below_unstaked".to_owned(),
+                    });
+                }
+
+                let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT) as usize;
+                let start_after = start_after.map(Addr::unchecked);
+                let bound = start_after.as_ref().and_then(Bounder::exclusive_bound);
+                let mut accounts = self
+                    .users
+                    .keys(ctx.deps.storage, bound, None, Order::Ascending)
+                    .take(limit + 1)
+                    .collect::<StdResult<Vec<_>>>()?;
+                let cursor = if accounts.len() > limit {
+                    accounts.pop();
+                    accounts.last().map(Addr::to_string)
+                } else {
+                    None
+                };
+                (accounts, cursor)
+            }
+        };
+
+        for account in accounts {
+            let report = self.invariants_report(ctx.deps.storage, &account)?;
+            violations.extend(
+                report
+                    .violations
+                    .into_iter()
+                    .map(|invariant| InvariantViolation {
+                        account: Some(account.to_string()),
+                        invariant,
+                    }),
+            );
+        }
+
+        Ok(InvariantsResponse { violations, cursor })
+    }
+
+    /// Breaks the user's tokens down by native chain voting power. Locally staked tokens are
+    /// delegated by the user's native staking proxy, so they vote through it, while unstaked and
+    /// remotely staked collateral sits in the vault's balance without any voting power.
+    ///
+    /// Liens are counted with their pending stakes. Collateral is shared between lienholders, so
+    /// `unstaked` saturates at zero if remote stakes also use locally staked collateral.
+    #[msg(query)]
+    fn voting_power_report(
+        &self,
+        ctx: QueryCtx,
+        account: String,
+    ) -> Result<VotingPowerReportResponse, ContractError> {
+        let denom = self.config.load(ctx.deps.storage)?.collateral.denom();
+        let account = ctx.deps.api.addr_validate(&account)?;
+        let local_staking = self.local_staking.load(ctx.deps.storage)?;
+
+        let collateral = self.load_user(ctx.deps.storage, &account)?.collateral;
+
+        let mut local_lien = None;
+        let mut remote_staked = Uint128::zero();
+        for item in
+            self.liens
+                .prefix(&account)
+                .range(ctx.deps.storage, None, None, Order::Ascending)
+        {
+            let (lienholder, lien) = item?;
+            if lienholder == local_staking.contract.0 {
+                local_lien = Some(lien.amount.high());
+            } else {
+                remote_staked = remote_staked.max(lien.amount.high());
+            }
+        }
+
+        // The proxy is created on the first local stake
+        let (proxy, local_staked) = match local_lien {
+            Some(_) => {
+                let query = NativeStakingQueryMsg::ProxyByOwner {
+                    owner: account.to_string(),
+                };
+                let ProxyByOwnerResponse { proxy } = ctx
+                    .deps
+                    .querier
+                    .query_wasm_smart(&local_staking.contract.0, &query)?;
+                let local_staked = ctx
+                    .deps
+                    .querier
+                    .query_all_delegations(&proxy)?
+                    .into_iter()
+                    .filter(|delegation| delegation.amount.denom == denom)
+                    .map(|delegation| delegation.amount.amount)
+                    .sum();
+                (Some(proxy), local_staked)
+            }
+            None => (None, Uint128::zero()),
+        };
+
+        let unstaked = collateral
+            .saturating_sub(local_lien.unwrap_or_default())
+            .saturating_sub(remote_staked);
+
+        Ok(VotingPowerReportResponse {
+            denom,
+            unstaked,
+            local_staked,
+            remote_staked,
+            proxy,
         })
     }
 
@@ -294,116 +1176,881 @@ impl VaultContract<'_> {
         let local_staking = self.local_staking.load(ctx.deps.storage)?;
 
         let resp = ConfigResponse {
-            denom: config.denom,
+            denom: config.collateral.denom(),
+            collateral: config.collateral,
             local_staking: local_staking.contract.0.into(),
+            local_staking_max_slash: local_staking.max_slash,
+            local_staking_checksum: local_staking.checksum,
+            tx_timeout: config.tx_timeout,
+            bond_fee: config.bond_fee,
+            unbond_fee: config.unbond_fee,
+            fee_recipient: config.fee_recipient.map(Addr::into_string),
+            max_total_collateral: config.max_total_collateral,
+            min_release: config.min_release,
+            check_remote_stake: config.check_remote_stake,
+            local_outstanding: self
+                .local_outstanding
+                .may_load(ctx.deps.storage)?
+                .unwrap_or_default(),
         };
 
         Ok(resp)
     }
 
-    /// Returns a single claim between the user and lienholder
+    /// Returns the collateral denom. For cw20 collateral, it's the token contract address.
+    ///
+    /// Cheaper than `config` for callers only needing the denom
+    #[msg(query)]
+    fn denom(&self, ctx: QueryCtx) -> Result<String, ContractError> {
+        let denom = self.config.load(ctx.deps.storage)?.collateral.denom();
+        Ok(denom)
+    }
+
+    /// Returns the bond and unbond fees collected so far
+    #[msg(query)]
+    fn fee_stats(&self, ctx: QueryCtx) -> Result<FeeStatsResponse, ContractError> {
+        let FeeStats {
+            bond_fees,
+            unbond_fees,
+        } = self
+            .fee_stats
+            .may_load(ctx.deps.storage)?
+            .unwrap_or_default();
+        Ok(FeeStatsResponse {
+            bond_fees,
+            unbond_fees,
+        })
+    }
+
+    /// Returns the free collateral of all the users together. Its low end is the collateral
+    /// free even if all the pending stakes are committed
+    #[msg(query)]
+    fn total_free_collateral(&self, ctx: QueryCtx) -> Result<ValueRange<Uint128>, ContractError> {
+        Ok(self.users.idx.free_collateral.load(ctx.deps.storage)?)
+    }
+
+    /// Returns a single claim between the user and lienholder
+    #[msg(query)]
+    fn claim(
+        &self,
+        ctx: QueryCtx,
+        account: String,
+        lienholder: String,
+    ) -> Result<Lien, ContractError> {
+        let account = ctx.deps.api.addr_validate(&account)?;
+        let lienholder = ctx.deps.api.addr_validate(&lienholder)?;
+
+        Ok(self.liens.load(ctx.deps.storage, (&account, &lienholder))?)
+    }
+
+    /// Returns whether `lienholder` could release `amount` of its cross stake lien on `owner`
+    /// with `release_cross_stake` right now, without releasing anything
+    #[msg(query)]
+    fn can_release(
+        &self,
+        ctx: QueryCtx,
+        owner: String,
+        lienholder: String,
+        amount: Coin,
+    ) -> Result<bool, ContractError> {
+        let owner = ctx.deps.api.addr_validate(&owner)?;
+        let lienholder = ctx.deps.api.addr_validate(&lienholder)?;
+
+        let (mut lien, amount) = match self.checked_lien(
+            ctx.deps.storage,
+            &owner,
+            &lienholder,
+            amount,
+            LienKind::Cross,
+        ) {
+            Ok(lien) => lien,
+            Err(_) => return Ok(false),
+        };
+        let mut user = self.users.load(ctx.deps.storage, &owner)?;
+
+        let config = self.config.load(ctx.deps.storage)?;
+        let can_release = lien.amount.sub(amount, Uint128::zero()).is_ok()
+            && Self::meets_min_release(&config, amount, &lien)
+            && user
+                .total_slashable
+                .sub(slashable_amount(amount, lien.slashable)?, Uint128::zero())
+                .is_ok();
+        Ok(can_release)
+    }
+
+    /// Returns paginated claims list for an user
+    ///
+    /// `start_after` is a last lienholder of the previous page, and it will not be included
+    #[msg(query)]
+    fn account_claims(
+        &self,
+        ctx: QueryCtx,
+        account: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    ) -> Result<AccountClaimsResponse, ContractError> {
+        let limit = clamp_page_limit(limit);
+        let start_after = start_after.map(Addr::unchecked);
+        let bound = start_after.as_ref().and_then(Bounder::exclusive_bound);
+
+        let account = Addr::unchecked(account);
+        let claims = self
+            .liens
+            .prefix(&account)
+            .range(ctx.deps.storage, bound, None, Order::Ascending)
+            .map(|item| {
+                let (lienholder, lien) = item?;
+                Ok::<_, ContractError>(LienResponse {
+                    lienholder: lienholder.to_string(),
+                    amount: lien.amount,
+                })
+            })
+            .take(limit)
+            .collect::<Result<_, _>>()?;
+
+        let resp = AccountClaimsResponse { claims };
+
+        Ok(resp)
+    }
+
+    /// Queries for all liens in the system, ordered by user and then lienholder, for full state
+    /// exports.
+    ///
+    /// `start_after` is the last `(user, lienholder)` pair of the previous page, and it will not
+    /// be included
+    #[msg(query)]
+    fn all_liens(
+        &self,
+        ctx: QueryCtx,
+        start_after: Option<(String, String)>,
+        limit: Option<u32>,
+    ) -> Result<AllLiensResponse, ContractError> {
+        let limit = clamp_page_limit(limit);
+        let start_after = start_after
+            .map(|(user, lienholder)| (Addr::unchecked(user), Addr::unchecked(lienholder)));
+        let bound = start_after
+            .as_ref()
+            .map(|(user, lienholder)| Bound::exclusive((user, lienholder)));
+
+        let liens = self
+            .liens
+            .range(ctx.deps.storage, bound, None, Order::Ascending)
+            .map(|item| {
+                let ((user, lienholder), lien) = item?;
+                Ok::<_, ContractError>(AllLiensResponseItem {
+                    user: user.into_string(),
+                    lienholder: lienholder.into_string(),
+                    amount: lien.amount,
+                    slashable: lien.slashable,
+                })
+            })
+            .take(limit)
+            .collect::<Result<_, _>>()?;
+
+        Ok(AllLiensResponse { liens })
+    }
+
+    /// Queries for all users ever performing action in the system, paginating over
+    /// them.
+    ///
+    /// `start_after` is the last account included in previous page
+    ///
+    /// `with_collateral` flag filters out users with no collateral, defaulted to false
+    ///
+    /// `order_by` defaults to ordering by address. With `BondedDesc`, the accounts are ordered by
+    /// decreasing collateral, and the page starts after the `(start_after_bonded, start_after)`
+    /// cursor. `start_after_bonded` defaults to the current collateral of `start_after`.
+    #[msg(query)]
+    fn all_accounts(
+        &self,
+        ctx: QueryCtx,
+        #[serde(default = "def_false")] with_collateral: bool,
+        start_after: Option<String>,
+        limit: Option<u32>,
+        order_by: Option<AccountsOrderBy>,
+        start_after_bonded: Option<Uint128>,
+    ) -> Result<AllAccountsResponse, ContractError> {
+        let limit = clamp_page_limit(limit);
+        let start_after = start_after.map(Addr::unchecked);
+
+        let denom = self.config.load(ctx.deps.storage)?.collateral.denom();
+
+        let users: Box<dyn Iterator<Item = StdResult<(Addr, UserInfo)>>> = match order_by
+            .unwrap_or_default()
+        {
+            AccountsOrderBy::Address => {
+                let bound = start_after.as_ref().and_then(Bounder::exclusive_bound);
+                self.users
+                    .range(ctx.deps.storage, bound, None, Order::Ascending)
+            }
+            AccountsOrderBy::BondedDesc => {
+                let bound = match start_after {
+                    Some(addr) => {
+                        let bonded = match start_after_bonded {
+                            Some(bonded) => bonded,
+                            None => self.load_user(ctx.deps.storage, &addr)?.collateral,
+                        };
+                        Some(Bound::exclusive((bonded.u128(), addr)))
+                    }
+                    None => None,
+                };
+                self.users
+                    .idx
+                    .collateral
+                    .range(ctx.deps.storage, None, bound, Order::Descending)
+            }
+        };
+
+        let accounts: Vec<_> = users
+            .filter(|account| {
+                account
+                    .as_ref()
+                    .map(|(_, account)| {
+                        !with_collateral || !account.collateral.is_zero() // Skip zero collateral
+                    })
+                    .unwrap_or(false) // Skip other errors
+            })
+            .map(|account| {
+                account.map(|(addr, account)| AllAccountsResponseItem {
+                    user: addr.to_string(),
+                    account: AccountResponse {
+                        denom: denom.clone(),
+                        bonded: account.collateral,
+                        free: account.free_collateral(),
+                    },
+                })
+            })
+            .take(limit)
+            .collect::<StdResult<_>>()?;
+
+        let resp = AllAccountsResponse { accounts };
+
+        Ok(resp)
+    }
+
+    /// Queries for the accounts with some collateral, of which the free collateral may be at most
+    /// `max_free` - the low end of their free collateral range is compared, so pending txs are
+    /// accounted for in the worst case.
+    ///
+    /// Accounts are not indexed by free collateral, so only up to `limit` accounts are scanned by
+    /// call, and the matching ones among them are returned. Pages may be partial or even empty,
+    /// scanning is done once the returned `cursor` is none.
+    ///
+    /// `start_after` is the `cursor` returned by the previous page
+    #[msg(query)]
+    fn accounts_by_free_collateral(
+        &self,
+        ctx: QueryCtx,
+        max_free: Uint128,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    ) -> Result<AccountsByFreeCollateralResponse, ContractError> {
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT) as usize;
+        let start_after = start_after.map(Addr::unchecked);
+        let bound = start_after.as_ref().and_then(Bounder::exclusive_bound);
+
+        let denom = self.config.load(ctx.deps.storage)?.collateral.denom();
+
+        let mut users = self
+            .users
+            .range(ctx.deps.storage, bound, None, Order::Ascending)
+            .take(limit + 1)
+            .collect::<StdResult<Vec<_>>>()?;
+        let cursor = if users.len() > limit {
+            users.pop();
+            users.last().map(|(addr, _)| addr.to_string())
+        } else {
+            None
+        };
+
+        let accounts = users
+            .into_iter()
+            .filter(|(_, account)| {
+                !account.collateral.is_zero() && account.free_collateral().low() <= max_free
+            })
+            .map(|(addr, account)| AllAccountsResponseItem {
+                user: addr.into_string(),
+                account: AccountResponse {
+                    denom: denom.clone(),
+                    bonded: account.collateral,
+                    free: account.free_collateral(),
+                },
+            })
+            .collect();
+
+        Ok(AccountsByFreeCollateralResponse { accounts, cursor })
+    }
+
+    /// Takes a snapshot of all the accounts collateral, for consistent exports over multiple
+    /// blocks. Only the contract admin can take snapshots.
+    ///
+    /// Only the last `MAX_LIVE_SNAPSHOTS` snapshots can be queried.
+    #[msg(exec)]
+    fn create_snapshot(&self, ctx: ExecCtx) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.ensure_admin(&ctx)?;
+
+        let total_collateral = self
+            .total_collateral
+            .may_load(ctx.deps.storage)?
+            .unwrap_or_default();
+        let snapshot_id =
+            self.snapshots
+                .create(ctx.deps.storage, &ctx.env.block, total_collateral)?;
+
+        let resp = Response::new()
+            .add_attribute("action", "create_snapshot")
+            .add_attribute("sender", ctx.info.sender)
+            .add_attribute("snapshot_id", snapshot_id.to_string())
+            .add_attribute("total_collateral", total_collateral.to_string());
+
+        Ok(resp)
+    }
+
+    /// Sets the bond and unbond fees, sent to `fee_recipient`. Fees are capped to
+    /// `MAX_FEE_PERCENT`. Only callable by the admin
+    #[msg(exec)]
+    fn update_fees(
+        &self,
+        ctx: ExecCtx,
+        bond_fee: Decimal,
+        unbond_fee: Decimal,
+        fee_recipient: Option<String>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.ensure_admin(&ctx)?;
+
+        let max_fee = Decimal::percent(MAX_FEE_PERCENT);
+        ensure!(
+            bond_fee <= max_fee && unbond_fee <= max_fee,
+            ContractError::FeeTooHigh(max_fee)
+        );
+        let fee_recipient = fee_recipient
+            .map(|addr| ctx.deps.api.addr_validate(&addr))
+            .transpose()?;
+        ensure!(
+            fee_recipient.is_some() || (bond_fee.is_zero() && unbond_fee.is_zero()),
+            ContractError::MissingFeeRecipient
+        );
+
+        let mut config = self.config.load(ctx.deps.storage)?;
+        config.bond_fee = bond_fee;
+        config.unbond_fee = unbond_fee;
+        config.fee_recipient = fee_recipient.clone();
+        self.config.save(ctx.deps.storage, &config)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "update_fees")
+            .add_attribute("bond_fee", bond_fee.to_string())
+            .add_attribute("unbond_fee", unbond_fee.to_string())
+            .add_attribute(
+                "fee_recipient",
+                fee_recipient.map_or_else(|| "none".to_owned(), Addr::into_string),
+            ))
+    }
+
+    /// Sets the max total collateral the vault accepts. No cap if not set. Only callable by the
+    /// admin
+    #[msg(exec)]
+    fn update_max_total_collateral(
+        &self,
+        ctx: ExecCtx,
+        max_total_collateral: Option<Uint128>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.ensure_admin(&ctx)?;
+
+        self.config
+            .update(ctx.deps.storage, |mut config| -> StdResult<_> {
+                config.max_total_collateral = max_total_collateral;
+                Ok(config)
+            })?;
+
+        Ok(Response::new()
+            .add_attribute("action", "update_max_total_collateral")
+            .add_attribute(
+                "max_total_collateral",
+                max_total_collateral.map_or_else(|| "none".to_owned(), |max| max.to_string()),
+            ))
+    }
+
+    /// Sets the min amount of a cross stake release, so that lienholders batch their releases.
+    /// The final release of a lien is always allowed. No min if not set. Only callable by the
+    /// admin
+    #[msg(exec)]
+    fn update_min_release(
+        &self,
+        ctx: ExecCtx,
+        min_release: Option<Uint128>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.ensure_admin(&ctx)?;
+
+        self.config
+            .update(ctx.deps.storage, |mut config| -> StdResult<_> {
+                config.min_release = min_release;
+                Ok(config)
+            })?;
+
+        Ok(Response::new()
+            .add_attribute("action", "update_min_release")
+            .add_attribute(
+                "min_release",
+                min_release.map_or_else(|| "none".to_owned(), |min| min.to_string()),
+            ))
+    }
+
+    /// Sets whether `stake_remote` checks with the cross staking contract that it would accept
+    /// the stake, before reserving the collateral. Only callable by the admin
+    #[msg(exec)]
+    fn update_check_remote_stake(
+        &self,
+        ctx: ExecCtx,
+        enabled: bool,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.ensure_admin(&ctx)?;
+
+        self.config
+            .update(ctx.deps.storage, |mut config| -> StdResult<_> {
+                config.check_remote_stake = enabled;
+                Ok(config)
+            })?;
+
+        Ok(Response::new()
+            .add_attribute("action", "update_check_remote_stake")
+            .add_attribute("enabled", enabled.to_string()))
+    }
+
+    /// Sends the collateral tokens held by the vault but not accounted as anyone's collateral (eg.
+    /// sent to the vault by mistake) to `recipient`. Only the contract admin can call it.
+    ///
+    /// The locally staked collateral is held by the local staking contract, see `held_collateral`.
+    #[msg(exec)]
+    fn sweep_unaccounted(
+        &self,
+        ctx: ExecCtx,
+        recipient: String,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.ensure_admin(&ctx)?;
+        let recipient = ctx.deps.api.addr_validate(&recipient)?;
+
+        let held = self.held_collateral(ctx.deps.storage)?;
+        let config = self.config.load(ctx.deps.storage)?;
+        let balance = Self::collateral_balance(ctx.deps.as_ref(), &ctx.env, &config.collateral)?;
+        let unaccounted = balance
+            .checked_sub(held)
+            .map_err(|_| ContractError::BalanceBelowCollateral(balance, held))?;
+        ensure!(!unaccounted.is_zero(), ContractError::ZeroAmount);
+
+        let msg = Self::send_collateral_msg(&config.collateral, &recipient, unaccounted)?;
+        let resp = Response::new()
+            .add_message(msg)
+            .add_attribute("action", "sweep_unaccounted")
+            .add_attribute("recipient", recipient)
+            .add_attribute("amount", unaccounted.to_string());
+        Ok(resp)
+    }
+
+    /// Sends the whole balance of `denom` held by the vault to `recipient`, but for the collateral
+    /// accounted to users, which is never swept. Only the contract admin can call it.
+    ///
+    /// Meant for tokens sent to the vault by mistake. Stray collateral tokens can be swept as
+    /// well, the same way as with `sweep_unaccounted`.
+    #[msg(exec)]
+    fn sweep(
+        &self,
+        ctx: ExecCtx,
+        denom: String,
+        recipient: String,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.ensure_admin(&ctx)?;
+        let recipient = ctx.deps.api.addr_validate(&recipient)?;
+
+        let config = self.config.load(ctx.deps.storage)?;
+        let held = match &config.collateral {
+            CollateralType::Native(collateral) if *collateral == denom => {
+                self.held_collateral(ctx.deps.storage)?
+            }
+            _ => Uint128::zero(),
+        };
+        let balance = ctx
+            .deps
+            .querier
+            .query_balance(&ctx.env.contract.address, &denom)?
+            .amount;
+        let stray = balance
+            .checked_sub(held)
+            .map_err(|_| ContractError::BalanceBelowCollateral(balance, held))?;
+        ensure!(!stray.is_zero(), ContractError::ZeroAmount);
+
+        let resp = Response::new()
+            .add_message(BankMsg::Send {
+                to_address: recipient.to_string(),
+                amount: vec![coin(stray.u128(), &denom)],
+            })
+            .add_attribute("action", "sweep")
+            .add_attribute("recipient", recipient)
+            .add_attribute("amount", coin(stray.u128(), denom).to_string());
+        Ok(resp)
+    }
+
+    /// Collateral tokens that should be held by the vault itself: the total collateral, less the
+    /// tokens outstanding on the local staking contract
+    fn held_collateral(&self, storage: &dyn Storage) -> Result<Uint128, ContractError> {
+        // Users bonded before the total was tracked are missing from it until migrated
+        ensure!(
+            self.total_collateral_tracked
+                .may_load(storage)?
+                .unwrap_or_default(),
+            ContractError::CollateralTotalUntracked
+        );
+        let total_collateral = self.total_collateral.may_load(storage)?.unwrap_or_default();
+        let outstanding = self
+            .local_outstanding
+            .may_load(storage)?
+            .unwrap_or_default();
+        Ok(total_collateral.saturating_sub(outstanding))
+    }
+
+    /// Rolls back a pending stake which wasn't resolved within the configured `tx_timeout`,
+    /// freeing its collateral. The lienholder rolls it back on its side in the same transaction,
+    /// through `expire_stake`. Anyone can call it, so keepers can unstick abandoned stakes.
+    ///
+    /// Txs created before their creation time was tracked can be expired right away.
+    #[msg(exec)]
+    fn expire_tx(&self, mut ctx: ExecCtx, tx_id: u64) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let tx_timeout = self
+            .config
+            .load(ctx.deps.storage)?
+            .tx_timeout
+            .ok_or(ContractError::TxTimeoutDisabled)?;
+        let tx = self.pending.txs.load(ctx.deps.storage, tx_id)?;
+        let (expires_at, lienholder) = match &tx {
+            InFlightStaking {
+                created_at,
+                lienholder,
+                ..
+            } => (created_at.plus_seconds(tx_timeout), lienholder.clone()),
+            _ => return Err(ContractError::WrongTypeTx(tx_id, tx)),
+        };
+        ensure!(
+            ctx.env.block.time > expires_at,
+            ContractError::TxNotExpired(tx_id, expires_at)
+        );
+
+        self.revert_stake(&mut ctx, tx_id, tx)?;
+        let msg = CrossStakingApiHelper(lienholder).expire_stake(tx_id)?;
+
+        let resp = Response::new()
+            .add_message(msg)
+            .add_attribute("action", "expire_tx")
+            .add_attribute("sender", ctx.info.sender)
+            .add_attribute("tx_id", tx_id.to_string());
+        Ok(resp)
+    }
+
+    /// Unstakes all the local stakes, in emergencies. Only the contract admin can call it.
+    ///
+    /// Users are processed by pages of up to `limit`, each call continuing where the previous one
+    /// stopped. The next user to be processed is returned as the cursor, none once all the users
+    /// are processed. Tokens are returned through `release_local_stake` after the local unbonding
+    /// period, releasing the liens. Cross stakes are not touched.
+    #[msg(exec)]
+    fn emergency_unstake_all_local(
+        &self,
+        ctx: ExecCtx,
+        limit: Option<u32>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.ensure_admin(&ctx)?;
+
+        let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT) as usize;
+        let local_staking = self.local_staking.load(ctx.deps.storage)?;
+        let lienholder = local_staking.contract.0.clone();
+
+        let cursor = self.emergency_unstake_cursor.may_load(ctx.deps.storage)?;
+        let bound = cursor.as_ref().and_then(Bounder::inclusive_bound);
+        let mut users = self
+            .users
+            .keys(ctx.deps.storage, bound, None, Order::Ascending)
+            .take(limit + 1)
+            .collect::<StdResult<Vec<_>>>()?;
+        let next = if users.len() > limit {
+            users.pop()
+        } else {
+            None
+        };
+
+        let mut msgs = vec![];
+        for user in users {
+            let staked = self
+                .liens
+                .may_load(ctx.deps.storage, (&user, &lienholder))?
+                .map(|lien| !lien.amount.high().is_zero())
+                .unwrap_or_default();
+            if staked {
+                msgs.push(
+                    local_staking
+                        .contract
+                        .emergency_unstake(user.into_string())?,
+                );
+            }
+        }
+
+        match &next {
+            Some(next) => self.emergency_unstake_cursor.save(ctx.deps.storage, next)?,
+            None => self.emergency_unstake_cursor.remove(ctx.deps.storage),
+        }
+
+        let cursor = next.map(Addr::into_string);
+        let mut resp = Response::new()
+            .add_messages(msgs)
+            .set_data(to_binary(&EmergencyUnstakeResponse {
+                cursor: cursor.clone(),
+            })?)
+            .add_attribute("action", "emergency_unstake_all_local")
+            .add_attribute("sender", ctx.info.sender);
+        if let Some(cursor) = cursor {
+            resp = resp.add_attribute("cursor", cursor);
+        }
+
+        Ok(resp)
+    }
+
+    /// Slashes the owners left by the local slashes notified by the chain, at most `limit` of them.
+    /// Anyone can call it.
+    #[msg(exec)]
+    fn continue_local_slash(
+        &self,
+        mut ctx: ExecCtx,
+        limit: Option<u32>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let limit = limit
+            .unwrap_or(LOCAL_SLASH_LIMIT)
+            .min(MAX_LOCAL_SLASH_LIMIT);
+        let mut slashes = vec![];
+        let mut processed = 0;
+        while processed < limit {
+            let mut slash = match self.local_slashes.pop_front(ctx.deps.storage)? {
+                Some(slash) => slash,
+                None => break,
+            };
+            let (slashed, owners, done) =
+                self.slash_owners(ctx.deps.branch(), &mut slash, true, limit - processed)?;
+            processed += owners;
+            slashes.extend(slashed);
+            if !done {
+                self.local_slashes.push_front(ctx.deps.storage, &slash)?;
+            }
+        }
+
+        let resp = Response::new()
+            .add_attribute("action", "continue_local_slash")
+            .add_attribute("users", join_slashed_users(&slashes))
+            .add_attribute(
+                "pending",
+                self.local_slashes.len(ctx.deps.storage)?.to_string(),
+            );
+
+        Ok(resp)
+    }
+
+    /// Unstakes `amount` of the account's cross stake on `contract`, eg. to force an exit from a
+    /// consumer chain. Only the contract admin can call it.
+    ///
+    /// Without a validator, the cross staking contract takes the stake from the largest stakes
+    /// first. Tokens are returned through `release_cross_stake` after the unbonding period,
+    /// releasing the lien.
+    #[msg(exec)]
+    fn force_unstake_remote(
+        &self,
+        ctx: ExecCtx,
+        account: String,
+        contract: String,
+        validator: Option<String>,
+        amount: Coin,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.ensure_admin(&ctx)?;
+
+        let account = ctx.deps.api.addr_validate(&account)?;
+        let contract = ctx.deps.api.addr_validate(&contract)?;
+        ensure!(
+            self.liens.has(ctx.deps.storage, (&account, &contract)),
+            ContractError::UnknownLienholder
+        );
+
+        let msg = CrossStakingApiHelper(contract.clone()).vault_unstake(
+            account.to_string(),
+            validator,
+            amount.clone(),
+        )?;
+
+        let resp = Response::new()
+            .add_message(msg)
+            .add_attribute("action", "force_unstake_remote")
+            .add_attribute("sender", ctx.info.sender)
+            .add_attribute("account", account)
+            .add_attribute("contract", contract)
+            .add_attribute("amount", amount.amount.to_string());
+        Ok(resp)
+    }
+
+    /// Moves the sender's whole cross stake on `from_validators` to `to_validator`, on the
+    /// `contract` lienholder.
+    ///
+    /// Liens are kept per lienholder, so the lien itself is unchanged: only the stake is
+    /// consolidated on the remote chain.
+    #[msg(exec)]
+    fn consolidate_stake(
+        &self,
+        ctx: ExecCtx,
+        contract: String,
+        from_validators: Vec<String>,
+        to_validator: String,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let contract = ctx.deps.api.addr_validate(&contract)?;
+        ensure!(
+            self.liens
+                .has(ctx.deps.storage, (&ctx.info.sender, &contract)),
+            ContractError::UnknownLienholder
+        );
+
+        let msg = CrossStakingApiHelper(contract.clone()).restake(
+            ctx.info.sender.to_string(),
+            from_validators,
+            to_validator.clone(),
+        )?;
+
+        let resp = Response::new()
+            .add_message(msg)
+            .add_attribute("action", "consolidate_stake")
+            .add_attribute("sender", ctx.info.sender)
+            .add_attribute("contract", contract)
+            .add_attribute("to_validator", to_validator);
+        Ok(resp)
+    }
+
+    /// Returns the collateral of an account at the given snapshot
     #[msg(query)]
-    fn claim(
+    fn snapshot_account(
         &self,
         ctx: QueryCtx,
+        snapshot_id: u64,
         account: String,
-        lienholder: String,
-    ) -> Result<Lien, ContractError> {
+    ) -> Result<SnapshotAccountResponse, ContractError> {
+        let denom = self.config.load(ctx.deps.storage)?.collateral.denom();
         let account = ctx.deps.api.addr_validate(&account)?;
-        let lienholder = ctx.deps.api.addr_validate(&lienholder)?;
 
-        Ok(self.liens.load(ctx.deps.storage, (&account, &lienholder))?)
+        if !self.snapshots.snapshots.has(ctx.deps.storage, snapshot_id) {
+            return Err(ContractError::SnapshotNotFound(snapshot_id));
+        }
+
+        let current = self.load_user(ctx.deps.storage, &account)?.collateral;
+        let bonded =
+            self.snapshots
+                .collateral_at(ctx.deps.storage, snapshot_id, &account, current)?;
+
+        Ok(SnapshotAccountResponse {
+            snapshot_id,
+            denom,
+            bonded,
+        })
     }
 
-    /// Returns paginated claims list for an user
+    /// Paginates over the accounts collateral at the given snapshot. Accounts with no collateral
+    /// at snapshot time are skipped.
     ///
-    /// `start_after` is a last lienholder of the previous page, and it will not be included
+    /// `start_after` is the last account included in previous page
     #[msg(query)]
-    fn account_claims(
+    fn snapshot_accounts(
         &self,
         ctx: QueryCtx,
-        account: String,
+        snapshot_id: u64,
         start_after: Option<String>,
         limit: Option<u32>,
-    ) -> Result<AccountClaimsResponse, ContractError> {
+    ) -> Result<SnapshotAccountsResponse, ContractError> {
         let limit = clamp_page_limit(limit);
         let start_after = start_after.map(Addr::unchecked);
         let bound = start_after.as_ref().and_then(Bounder::exclusive_bound);
 
-        let account = Addr::unchecked(account);
-        let claims = self
-            .liens
-            .prefix(&account)
+        let denom = self.config.load(ctx.deps.storage)?.collateral.denom();
+        let snapshot = self
+            .snapshots
+            .snapshots
+            .may_load(ctx.deps.storage, snapshot_id)?
+            .ok_or(ContractError::SnapshotNotFound(snapshot_id))?;
+
+        let accounts = self
+            .users
             .range(ctx.deps.storage, bound, None, Order::Ascending)
             .map(|item| {
-                let (lienholder, lien) = item?;
-                Ok::<_, ContractError>(LienResponse {
-                    lienholder: lienholder.to_string(),
-                    amount: lien.amount,
+                let (user, info) = item?;
+                let bonded = self.snapshots.collateral_at(
+                    ctx.deps.storage,
+                    snapshot_id,
+                    &user,
+                    info.collateral,
+                )?;
+                Ok::<_, ContractError>(SnapshotAccountsResponseItem {
+                    user: user.into_string(),
+                    bonded,
                 })
             })
+            .filter(|item| {
+                item.as_ref()
+                    .map(|item| !item.bonded.is_zero())
+                    .unwrap_or(true) // Keep errors
+            })
             .take(limit)
             .collect::<Result<_, _>>()?;
 
-        let resp = AccountClaimsResponse { claims };
+        Ok(SnapshotAccountsResponse {
+            snapshot_id,
+            height: snapshot.height,
+            time: snapshot.time,
+            denom,
+            total_collateral: snapshot.total_collateral,
+            accounts,
+        })
+    }
 
-        Ok(resp)
+    /// Queries a pending tx, or its outcome if it was recently resolved.
+    #[msg(query)]
+    fn pending_tx(&self, ctx: QueryCtx, tx_id: u64) -> Result<TxResponse, ContractError> {
+        match self.tx_history.may_load(ctx.deps.storage, tx_id)? {
+            Some(result) => Ok(TxStatus::Resolved(result)),
+            None => {
+                let tx = self.pending.txs.load(ctx.deps.storage, tx_id)?;
+                Ok(TxStatus::Pending(tx))
+            }
+        }
     }
 
-    /// Queries for all users ever performing action in the system, paginating over
-    /// them.
-    ///
-    /// `start_after` is the last account included in previous page
-    ///
-    /// `with_collateral` flag filters out users with no collateral, defaulted to false
+    /// Queries the recently resolved txs.
+    /// Reports txs in descending order (newest first).
+    /// `start_after` is the last tx id included in previous page
     #[msg(query)]
-    fn all_accounts(
+    fn txs_history(
         &self,
         ctx: QueryCtx,
-        #[serde(default = "def_false")] with_collateral: bool,
-        start_after: Option<String>,
+        start_after: Option<u64>,
         limit: Option<u32>,
-    ) -> Result<AllAccountsResponse, ContractError> {
+    ) -> Result<TxsHistoryResponse, ContractError> {
         let limit = clamp_page_limit(limit);
-        let start_after = start_after.map(Addr::unchecked);
-        let bound = start_after.as_ref().and_then(Bounder::exclusive_bound);
-
-        let denom = self.config.load(ctx.deps.storage)?.denom;
-
-        let accounts: Vec<_> = self
-            .users
-            .range(ctx.deps.storage, bound, None, Order::Ascending)
-            .filter(|account| {
-                account
-                    .as_ref()
-                    .map(|(_, account)| {
-                        !with_collateral || !account.collateral.is_zero() // Skip zero collateral
-                    })
-                    .unwrap_or(false) // Skip other errors
-            })
-            .map(|account| {
-                account.map(|(addr, account)| AllAccountsResponseItem {
-                    user: addr.to_string(),
-                    account: AccountResponse {
-                        denom: denom.clone(),
-                        bonded: account.collateral,
-                        free: account.free_collateral(),
-                    },
-                })
-            })
-            .take(limit)
-            .collect::<StdResult<_>>()?;
-
-        let resp = AllAccountsResponse { accounts };
-
-        Ok(resp)
-    }
-
-    /// Queries a pending tx.
-    #[msg(query)]
-    fn pending_tx(&self, ctx: QueryCtx, tx_id: u64) -> Result<TxResponse, ContractError> {
-        let resp = self.pending.txs.load(ctx.deps.storage, tx_id)?;
-        Ok(resp)
+        let txs = self
+            .tx_history
+            .range_desc(ctx.deps.storage, start_after, limit)?;
+        Ok(TxsHistoryResponse { txs })
     }
 
     /// Queries for all pending txs.
@@ -435,11 +2082,23 @@ impl VaultContract<'_> {
         Ok(resp)
     }
 
+    /// Queries for all the pending txs of an user, oldest first.
+    #[msg(query)]
+    fn user_pending_txs(
+        &self,
+        ctx: QueryCtx,
+        user: String,
+    ) -> Result<AllTxsResponse, ContractError> {
+        let user = ctx.deps.api.addr_validate(&user)?;
+        let txs = self.pending.txs_by_user(ctx.deps.storage, &user)?;
+        Ok(AllTxsResponse { txs })
+    }
+
     #[msg(reply)]
     fn reply(&self, ctx: ReplyCtx, reply: Reply) -> Result<Response, ContractError> {
-        match reply.id {
-            REPLY_ID_INSTANTIATE => self.reply_init_callback(ctx.deps, reply.result.unwrap()),
-            _ => Err(ContractError::InvalidReplyId(reply.id)),
+        match ReplyId::try_from(reply.id).map_err(ContractError::InvalidReplyId)? {
+            ReplyId::Instantiate => self.reply_init_callback(ctx.deps, reply.result.unwrap()),
+            id => Err(ContractError::InvalidReplyId(id.into())),
         }
     }
 
@@ -454,12 +2113,28 @@ impl VaultContract<'_> {
         // As we control the local staking contract it might be better to just raw-query it
         // on demand instead of duplicating the data.
         let query = LocalStakingApiQueryMsg::MaxSlash {};
-        let MaxSlashResponse { max_slash } =
-            deps.querier.query_wasm_smart(&local_staking, &query)?;
+        let MaxSlashResponse { max_slash } = deps
+            .querier
+            .query_wasm_smart(&local_staking, &query)
+            .map_err(|err| {
+                ContractError::LocalStakingNotCompatible(local_staking.to_string(), err)
+            })?;
+
+        // Code info queries are not supported everywhere, the checksum is then unknown
+        let code_id = deps
+            .querier
+            .query_wasm_contract_info(&local_staking)?
+            .code_id;
+        let checksum = deps
+            .querier
+            .query_wasm_code_info(code_id)
+            .ok()
+            .map(|info| info.checksum);
 
         let local_staking = LocalStaking {
             contract: LocalStakingApiHelper(local_staking),
             max_slash,
+            checksum,
         };
 
         self.local_staking.save(deps.storage, &local_staking)?;
@@ -486,49 +2161,61 @@ impl VaultContract<'_> {
         amount: Coin,
         remote: bool,
     ) -> Result<u64, ContractError> {
-        ensure!(
-            amount.denom == config.denom,
-            ContractError::UnexpectedDenom(config.denom.clone())
-        );
+        let denom = config.collateral.denom();
+        ensure!(amount.denom == denom, ContractError::UnexpectedDenom(denom));
 
         let amount = amount.amount;
+        let kind = if remote {
+            LienKind::Cross
+        } else {
+            LienKind::Local
+        };
         let mut lien = self
             .liens
             .may_load(ctx.deps.storage, (&ctx.info.sender, lienholder))?
             .unwrap_or_else(|| Lien {
                 amount: ValueRange::new_val(Uint128::zero()),
                 slashable,
+                kind,
             });
-        let mut user = self
-            .users
-            .may_load(ctx.deps.storage, &ctx.info.sender)?
-            .unwrap_or_default();
+        ensure!(lien.kind == kind, ContractError::WrongLienKind(kind));
+        let mut user = self.load_user(ctx.deps.storage, &ctx.info.sender)?;
+        let slashable_amount = slashable_amount(amount, lien.slashable)?;
+        ensure_addable(lien.amount, amount)?;
+        ensure_addable(user.total_slashable, slashable_amount)?;
         if remote {
             lien.amount
                 .prepare_add(amount, user.collateral)
-                .map_err(|_| ContractError::InsufficentBalance)?;
+                .map_err(|_| ContractError::InsufficientBalance)?;
             // Tentative value
             user.max_lien = max_range(user.max_lien, lien.amount);
             user.total_slashable
-                .prepare_add(amount * lien.slashable, user.collateral)
-                .map_err(|_| ContractError::InsufficentBalance)?;
+                .prepare_add(slashable_amount, user.collateral)
+                .map_err(|_| ContractError::InsufficientBalance)?;
         } else {
             // Update lien immediately
             lien.amount
                 .add(amount, user.collateral)
-                .map_err(|_| ContractError::InsufficentBalance)?;
+                .map_err(|_| ContractError::InsufficientBalance)?;
             // Update max lien and total slashable immediately
             user.max_lien = max_range(user.max_lien, lien.amount);
             user.total_slashable
-                .add(amount * lien.slashable, user.collateral)
-                .map_err(|_| ContractError::InsufficentBalance)?;
+                .add(slashable_amount, user.collateral)
+                .map_err(|_| ContractError::InsufficientBalance)?;
         }
 
-        ensure!(user.verify_collateral(), ContractError::InsufficentBalance);
+        ensure!(user.covers_reserve(), ContractError::InsufficientBalance);
+        if let Some(max_leverage) = user.max_leverage {
+            ensure!(
+                user.within_leverage(),
+                ContractError::LeverageLimitExceeded(max_leverage)
+            );
+        }
 
         self.liens
             .save(ctx.deps.storage, (&ctx.info.sender, lienholder), &lien)?;
         self.users.save(ctx.deps.storage, &ctx.info.sender, &user)?;
+        self.assert_invariants(ctx.deps.storage, &ctx.info.sender)?;
         let tx_id = if remote {
             // Create new tx
             let tx_id = self.next_tx_id(ctx.deps.storage)?;
@@ -539,8 +2226,9 @@ impl VaultContract<'_> {
                 slashable,
                 user: ctx.info.sender.clone(),
                 lienholder: lienholder.clone(),
+                created_at: ctx.env.block.time,
             };
-            self.pending.txs.save(ctx.deps.storage, tx_id, &new_tx)?;
+            self.pending.save(ctx.deps.storage, &new_tx)?;
             tx_id
         } else {
             0
@@ -548,12 +2236,17 @@ impl VaultContract<'_> {
         Ok(tx_id)
     }
 
-    /// Commits a pending stake
-    fn commit_stake(&self, ctx: &mut ExecCtx, tx_id: u64) -> Result<(), ContractError> {
-        // Load tx
+    /// Loads a pending stake to be resolved by the sender, verifying it was created for it
+    fn load_sender_tx(&self, ctx: &ExecCtx, tx_id: u64) -> Result<Tx, ContractError> {
         let tx = self.pending.txs.load(ctx.deps.storage, tx_id)?;
 
-        // Verify tx comes from the right contract, and is of the right type
+        // Verify tx comes from the right contract, both by its key and its content, and is of
+        // the right type
+        ensure!(
+            self.pending
+                .is_lienholder(ctx.deps.storage, tx_id, &ctx.info.sender),
+            ContractError::WrongContractTx(tx_id, ctx.info.sender.clone())
+        );
         ensure!(
             match tx.clone() {
                 InFlightStaking { lienholder, .. } => {
@@ -568,7 +2261,14 @@ impl VaultContract<'_> {
             ContractError::WrongTypeTx(tx_id, tx)
         );
 
-        let (tx_amount, tx_user, tx_lienholder) = match tx {
+        Ok(tx)
+    }
+
+    /// Commits a pending stake
+    fn commit_stake(&self, ctx: &mut ExecCtx, tx_id: u64) -> Result<(), ContractError> {
+        let tx = self.load_sender_tx(ctx, tx_id)?;
+
+        let (tx_amount, tx_user, tx_lienholder) = match tx.clone() {
             InFlightStaking {
                 amount,
                 user,
@@ -592,37 +2292,29 @@ impl VaultContract<'_> {
         // Update max lien definitive value (it depends on the lien's value range)
         user.max_lien = max_range(user.max_lien, lien.amount);
         // Commit total slashable
-        user.total_slashable.commit_add(tx_amount * lien.slashable);
+        user.total_slashable
+            .commit_add(slashable_amount(tx_amount, lien.slashable)?);
         // Save it
         self.users.save(ctx.deps.storage, &tx_user, &user)?;
+        self.assert_invariants(ctx.deps.storage, &tx_user)?;
 
         // Remove tx
-        self.pending.txs.remove(ctx.deps.storage, tx_id)?;
+        self.pending.remove(ctx.deps.storage, &tx)?;
+        self.tx_history
+            .record(ctx.deps.storage, tx_id, true, ctx.env.block.time)?;
 
         Ok(())
     }
 
     /// Rollbacks a pending tx
     fn rollback_stake(&self, ctx: &mut ExecCtx, tx_id: u64) -> Result<(), ContractError> {
-        // Load tx
-        let tx = self.pending.txs.load(ctx.deps.storage, tx_id)?;
-
-        // Verify tx comes from the right contract, and is of the right type
-        ensure!(
-            match tx.clone() {
-                InFlightStaking { lienholder, .. } => {
-                    ensure!(
-                        lienholder == ctx.info.sender,
-                        ContractError::WrongContractTx(tx_id, ctx.info.sender.clone())
-                    );
-                    true
-                }
-                _ => false,
-            },
-            ContractError::WrongTypeTx(tx_id, tx)
-        );
+        let tx = self.load_sender_tx(ctx, tx_id)?;
+        self.revert_stake(ctx, tx_id, tx)
+    }
 
-        let (tx_amount, tx_slashable, tx_user, tx_lienholder) = match tx {
+    /// Rolls back the lien and collateral of a pending stake, removing its tx
+    fn revert_stake(&self, ctx: &mut ExecCtx, tx_id: u64, tx: Tx) -> Result<(), ContractError> {
+        let (tx_amount, tx_slashable, tx_user, tx_lienholder) = match tx.clone() {
             InFlightStaking {
                 amount,
                 slashable,
@@ -651,11 +2343,15 @@ impl VaultContract<'_> {
         // is already written to storage
         self.recalculate_max_lien(ctx.deps.storage, &tx_user, &mut user)?;
 
-        user.total_slashable.rollback_add(tx_amount * tx_slashable);
+        user.total_slashable
+            .rollback_add(slashable_amount(tx_amount, tx_slashable)?);
         self.users.save(ctx.deps.storage, &tx_user, &user)?;
+        self.assert_invariants(ctx.deps.storage, &tx_user)?;
 
         // Remove tx
-        self.pending.txs.remove(ctx.deps.storage, tx_id)?;
+        self.pending.remove(ctx.deps.storage, &tx)?;
+        self.tx_history
+            .record(ctx.deps.storage, tx_id, false, ctx.env.block.time)?;
         Ok(())
     }
 
@@ -677,42 +2373,305 @@ impl VaultContract<'_> {
         Ok(())
     }
 
+    /// Checks the user accounting against their liens:
+    /// * `max_lien` is the max of the liens
+    /// * `total_slashable` is the slashable amount of the liens, up to one unit of rounding per
+    ///   lien
+    /// * the collateral covers the max lien, and the total slashable amount of the liens
+    /// * the collateral covers the used collateral
+    fn invariants_report(
+        &self,
+        storage: &dyn Storage,
+        user: &Addr,
+    ) -> Result<InvariantsReport, ContractError> {
+        let user_info = self.load_user(storage, user)?;
+
+        let mut liens_max = ValueRange::new_val(Uint128::zero());
+        let mut liens_slashable = ValueRange::new_val(Uint128::zero());
+        let mut liens_count = Uint128::zero();
+        for item in self
+            .liens
+            .prefix(user)
+            .range(storage, None, None, Order::Ascending)
+        {
+            let (_, lien) = item?;
+            liens_count += Uint128::one();
+            liens_max = max_range(liens_max, lien.amount);
+            liens_slashable = ValueRange::new(
+                liens_slashable.low() + lien.amount.low() * lien.slashable,
+                liens_slashable.high() + lien.amount.high() * lien.slashable,
+            );
+        }
+
+        let collateral = user_info.collateral;
+        let slashable_drift = max(
+            user_info
+                .total_slashable
+                .low()
+                .abs_diff(liens_slashable.low()),
+            user_info
+                .total_slashable
+                .high()
+                .abs_diff(liens_slashable.high()),
+        );
+        let violations = [
+            ("max_lien", user_info.max_lien != liens_max),
+            ("total_slashable", slashable_drift > liens_count),
+            ("liens_max_over_collateral", liens_max.high() > collateral),
+            (
+                "liens_slashable_over_collateral",
+                liens_slashable.high() > collateral,
+            ),
+            (
+                "used_collateral_over_collateral",
+                !user_info.verify_collateral(),
+            ),
+        ]
+        .into_iter()
+        .filter(|(_, violated)| *violated)
+        .map(|(name, _)| name.to_owned())
+        .collect();
+
+        Ok(InvariantsReport {
+            collateral,
+            max_lien: user_info.max_lien,
+            liens_max,
+            total_slashable: user_info.total_slashable,
+            liens_slashable,
+            violations,
+        })
+    }
+
+    /// Checks the vault balance, plus the tokens outstanding on the local staking contract, cover
+    /// the collateral of all the users.
+    ///
+    /// They are equal, but for the cross slashed collateral and the tokens sent to the vault by
+    /// mistake, which are kept in the vault
+    fn balance_covers_collateral(&self, ctx: &QueryCtx) -> Result<bool, ContractError> {
+        let config = self.config.load(ctx.deps.storage)?;
+        let balance = Self::collateral_balance(ctx.deps, &ctx.env, &config.collateral)?;
+        let total_collateral = self
+            .total_collateral
+            .may_load(ctx.deps.storage)?
+            .unwrap_or_default();
+        let outstanding = self
+            .local_outstanding
+            .may_load(ctx.deps.storage)?
+            .unwrap_or_default();
+
+        Ok(balance + outstanding >= total_collateral)
+    }
+
+    /// Panics if the user accounting is broken, when the `invariants` feature is enabled. To be
+    /// called after every change to the user or their liens
+    fn assert_invariants(&self, storage: &dyn Storage, user: &Addr) -> Result<(), ContractError> {
+        #[cfg(feature = "invariants")]
+        {
+            let report = self.invariants_report(storage, user)?;
+            assert!(
+                report.violations.is_empty(),
+                "Broken invariants of {user}: {report:?}"
+            );
+        }
+        #[cfg(not(feature = "invariants"))]
+        {
+            let _ = (storage, user);
+        }
+        Ok(())
+    }
+
     /// Updates the local stake for unstaking from any contract
     ///
     /// The unstake (both local and remote) is always called by the staking contract
-    /// (aka lien_holder), so the `sender` address is used for that.
-    fn unstake(&self, ctx: &mut ExecCtx, owner: String, amount: Coin) -> Result<(), ContractError> {
-        let denom = self.config.load(ctx.deps.storage)?.denom;
-        ensure!(amount.denom == denom, ContractError::UnexpectedDenom(denom));
-        let amount = amount.amount;
+    /// (aka lien_holder), so the `sender` address is used for that. The lien has to be of the
+    /// `kind` of the release entry point.
+    ///
+    /// The configured min release applies to the cross liens, unless `matured` (the released
+    /// tokens are done unbonding).
+    fn unstake(
+        &self,
+        ctx: &mut ExecCtx,
+        owner: &Addr,
+        amount: Coin,
+        kind: LienKind,
+        matured: bool,
+    ) -> Result<(), ContractError> {
+        let (mut lien, amount) =
+            self.checked_lien(ctx.deps.storage, owner, &ctx.info.sender, amount, kind)?;
+        let mut user = self.users.load(ctx.deps.storage, owner)?;
 
-        let owner = Addr::unchecked(owner);
-        let mut lien = self
-            .liens
-            .may_load(ctx.deps.storage, (&owner, &ctx.info.sender))?
-            .ok_or(ContractError::UnknownLienholder)?;
+        // Releasing a lien can only lower the max lien if it was the max one
+        let was_max =
+            lien.amount.low() == user.max_lien.low() || lien.amount.high() == user.max_lien.high();
 
         let slashable = lien.slashable;
         lien.amount
             .sub(amount, Uint128::zero())
             .map_err(|_| ContractError::InsufficientLien)?;
 
-        self.liens
-            .save(ctx.deps.storage, (&owner, &ctx.info.sender), &lien)?;
+        if kind == LienKind::Cross && !matured {
+            let config = self.config.load(ctx.deps.storage)?;
+            ensure!(
+                Self::meets_min_release(&config, amount, &lien),
+                ContractError::ReleaseTooSmall(config.min_release.unwrap_or_default())
+            );
+        }
 
-        let mut user = self.users.load(ctx.deps.storage, &owner)?;
+        self.liens
+            .save(ctx.deps.storage, (owner, &ctx.info.sender), &lien)?;
 
-        // Max lien has to be recalculated from scratch; the just saved lien
-        // is already written to storage
-        self.recalculate_max_lien(ctx.deps.storage, &owner, &mut user)?;
+        if was_max {
+            // Max lien has to be recalculated from scratch; the just saved lien
+            // is already written to storage
+            self.recalculate_max_lien(ctx.deps.storage, owner, &mut user)?;
+        }
 
         user.total_slashable
-            .sub(amount * slashable, Uint128::zero())?;
-        self.users.save(ctx.deps.storage, &owner, &user)?;
+            .sub(slashable_amount(amount, slashable)?, Uint128::zero())
+            .map_err(|_| ContractError::Underflow)?;
+        self.users.save(ctx.deps.storage, owner, &user)?;
+        self.assert_invariants(ctx.deps.storage, owner)?;
+
+        Ok(())
+    }
+
+    /// Loads the lien of `lienholder` on `owner` to release or add `amount` to, after checking
+    /// the amount and the lien kind. Returns the lien along with the amount.
+    fn checked_lien(
+        &self,
+        storage: &dyn Storage,
+        owner: &Addr,
+        lienholder: &Addr,
+        amount: Coin,
+        kind: LienKind,
+    ) -> Result<(Lien, Uint128), ContractError> {
+        let denom = self.config.load(storage)?.collateral.denom();
+        ensure!(amount.denom == denom, ContractError::UnexpectedDenom(denom));
+        let amount = amount.amount;
+        ensure!(!amount.is_zero(), ContractError::ZeroAmount);
+
+        let lien = self.load_lien(storage, owner, lienholder)?;
+        ensure!(lien.kind == kind, ContractError::WrongLienKind(kind));
+        Ok((lien, amount))
+    }
+
+    /// Loads the info of `user`, empty if they never bonded
+    fn load_user(&self, storage: &dyn Storage, user: &Addr) -> StdResult<UserInfo> {
+        Ok(self.users.may_load(storage, user)?.unwrap_or_default())
+    }
+
+    /// Loads the lien of `lienholder` on the `owner` collateral
+    fn load_lien(
+        &self,
+        storage: &dyn Storage,
+        owner: &Addr,
+        lienholder: &Addr,
+    ) -> Result<Lien, ContractError> {
+        self.liens
+            .may_load(storage, (owner, lienholder))?
+            .ok_or(ContractError::UnknownLienholder)
+    }
+
+    /// Whether a cross stake release of `amount`, leaving `lien`, meets the configured min
+    /// release. The final release of a lien is always allowed
+    fn meets_min_release(config: &Config, amount: Uint128, lien: &Lien) -> bool {
+        match config.min_release {
+            Some(min) => amount >= min || lien.amount.high().is_zero(),
+            None => true,
+        }
+    }
+
+    /// Moves `amount` of the owner's lien to the recipient, along with the collateral it covers.
+    ///
+    /// Like `unstake`, it is called by the lienholder. The owner's remaining collateral must
+    /// still cover all of their other liens.
+    fn transfer_lien(
+        &self,
+        ctx: &mut ExecCtx,
+        owner: String,
+        recipient: String,
+        amount: Coin,
+    ) -> Result<(), ContractError> {
+        let owner = normalize_addr(ctx.deps.api, &owner)?;
+        let recipient = ctx.deps.api.addr_validate(&recipient)?;
+        let lienholder = ctx.info.sender.clone();
+        let slashable = self
+            .load_lien(ctx.deps.storage, &owner, &lienholder)?
+            .slashable;
+
+        // Release the owner's lien, and take the collateral it covered
+        self.unstake(ctx, &owner, amount.clone(), LienKind::Cross, false)?;
+        let amount = amount.amount;
+
+        let mut owner_info = self.users.load(ctx.deps.storage, &owner)?;
+        let collateral = owner_info
+            .collateral
+            .checked_sub(amount)
+            .map_err(|_| ContractError::InsufficientBalance)?;
+        self.set_collateral(ctx.deps.storage, &owner, &mut owner_info, collateral)?;
+        ensure!(
+            owner_info.covers_reserve(),
+            ContractError::InsufficientBalance
+        );
+        self.users.save(ctx.deps.storage, &owner, &owner_info)?;
+        self.assert_invariants(ctx.deps.storage, &owner)?;
+
+        // Add both to the recipient
+        let lien = self
+            .liens
+            .may_load(ctx.deps.storage, (&recipient, &lienholder))?
+            .unwrap_or_else(|| Lien {
+                amount: ValueRange::new_val(Uint128::zero()),
+                slashable,
+                kind: LienKind::Cross,
+            });
+        let user =
+            self.add_covered_lien(ctx.deps.storage, &recipient, &lienholder, lien, amount)?;
+        ensure!(user.verify_collateral(), ContractError::InsufficientBalance);
+        if let Some(max_leverage) = user.max_leverage {
+            ensure!(
+                user.within_leverage(),
+                ContractError::LeverageLimitExceeded(max_leverage)
+            );
+        }
 
         Ok(())
     }
 
+    /// Adds `amount` to the lien of `lienholder` on `owner`, along with the collateral covering
+    /// it, and saves both. Returns the updated account
+    fn add_covered_lien(
+        &self,
+        storage: &mut dyn Storage,
+        owner: &Addr,
+        lienholder: &Addr,
+        mut lien: Lien,
+        amount: Uint128,
+    ) -> Result<UserInfo, ContractError> {
+        let mut user = self.load_user(storage, owner)?;
+        let collateral = user
+            .collateral
+            .checked_add(amount)
+            .map_err(|_| ContractError::Overflow)?;
+        self.set_collateral(storage, owner, &mut user, collateral)?;
+
+        let slashable_amount = slashable_amount(amount, lien.slashable)?;
+        ensure_addable(lien.amount, amount)?;
+        ensure_addable(user.total_slashable, slashable_amount)?;
+        lien.amount
+            .add(amount, user.collateral)
+            .map_err(|_| ContractError::InsufficientBalance)?;
+        user.max_lien = max_range(user.max_lien, lien.amount);
+        user.total_slashable
+            .add(slashable_amount, user.collateral)
+            .map_err(|_| ContractError::InsufficientBalance)?;
+
+        self.liens.save(storage, (owner, lienholder), &lien)?;
+        self.users.save(storage, owner, &user)?;
+        self.assert_invariants(storage, owner)?;
+        Ok(user)
+    }
+
     /// Processes a (remote or local) slashing event.
     ///
     /// This slashes the users that have funds delegated to the validator involved in the
@@ -723,49 +2682,154 @@ impl VaultContract<'_> {
     ///
     /// It also checks that the mesh security invariants are not violated after slashing,
     /// i.e. performs slashing propagation across lien holders, for all of the slashed users.
-    fn slash(&self, ctx: &mut ExecCtx, slashes: &[SlashInfo]) -> Result<(), ContractError> {
+    fn slash(
+        &self,
+        storage: &mut dyn Storage,
+        lien_holder: &Addr,
+        slashes: &[SlashInfo],
+    ) -> Result<(), ContractError> {
         // Process users that belong to lien_holder
-        let lien_holder = ctx.info.sender.clone();
         for slash in slashes {
             let slash_user = Addr::unchecked(slash.user.clone());
             // User must have a lien with this lien holder
-            let mut lien = self
-                .liens
-                .load(ctx.deps.storage, (&slash_user, &lien_holder))?;
+            let mut lien = self.liens.load(storage, (&slash_user, lien_holder))?;
             let slash_amount = slash.slash;
-            let mut user_info = self.users.load(ctx.deps.storage, &slash_user)?;
+            let mut user_info = self.users.load(storage, &slash_user)?;
             let new_collateral = user_info.collateral - slash_amount;
 
             // Slash user
             lien.amount.sub(slash_amount, Uint128::zero())?;
             // Save lien
             self.liens
-                .save(ctx.deps.storage, (&slash_user, &lien_holder), &lien)?;
+                .save(storage, (&slash_user, lien_holder), &lien)?;
             // Adjust total slashable and max lien
             user_info
                 .total_slashable
                 .sub(slash_amount * lien.slashable, Uint128::zero())?;
-            self.recalculate_max_lien(ctx.deps.storage, &slash_user, &mut user_info)?;
+            self.recalculate_max_lien(storage, &slash_user, &mut user_info)?;
             // Get free collateral before adjusting collateral, but after slashing
             let free_collateral = user_info.free_collateral().low(); // For simplicity
             if free_collateral < slash_amount {
                 // Check / adjust mesh security invariants according to the new collateral
                 self.propagate_slash(
-                    ctx.deps.storage,
+                    storage,
                     &slash_user,
                     &mut user_info,
                     new_collateral,
                     slash_amount - free_collateral,
                 )?;
             }
-            // Adjust collateral
-            user_info.collateral = new_collateral;
-            // Recompute max lien
-            self.recalculate_max_lien(ctx.deps.storage, &slash_user, &mut user_info)?;
-            // Save user info
-            self.users.save(ctx.deps.storage, &slash_user, &user_info)?;
+            // Adjust collateral
+            self.set_collateral(storage, &slash_user, &mut user_info, new_collateral)?;
+            // Recompute max lien
+            self.recalculate_max_lien(storage, &slash_user, &mut user_info)?;
+            // Save user info
+            self.users.save(storage, &slash_user, &user_info)?;
+            self.assert_invariants(storage, &slash_user)?;
+        }
+        Ok(())
+    }
+
+    /// Processes a local slashing event, notified by the chain.
+    ///
+    /// Slashes `slash_ratio` of the current delegations to `validator`, from the local liens of
+    /// their owners. Has to be notified before the slash is applied on chain. Only the first
+    /// `limit` owners are slashed, the others are left to `continue_local_slash`.
+    fn local_slash(
+        &self,
+        mut deps: DepsMut,
+        validator: String,
+        slash_ratio: Decimal,
+        infraction_height: u64,
+        limit: u32,
+    ) -> Result<Response, ContractError> {
+        let mut slash = LocalSlash {
+            validator,
+            slash_ratio,
+            infraction_height,
+            start_after: None,
+        };
+        let (slashes, _, done) = self.slash_owners(deps.branch(), &mut slash, false, limit)?;
+        if !done {
+            self.local_slashes.push_back(deps.storage, &slash)?;
+        }
+
+        let resp = Response::new()
+            .add_attribute("action", "process_local_slashing")
+            .add_attribute("validator", slash.validator)
+            .add_attribute("slash_ratio", slash_ratio.to_string())
+            .add_attribute("infraction_height", infraction_height.to_string())
+            .add_attribute("users", join_slashed_users(&slashes))
+            .add_attribute("pending", self.local_slashes.len(deps.storage)?.to_string());
+
+        Ok(resp)
+    }
+
+    /// Slashes the local liens of the owners delegated to the validator after `slash.start_after`,
+    /// at most `limit` of them, moving `start_after` along. Once the slash is applied on chain,
+    /// the delegations are slashed already: the owners are slashed on their delegation before it.
+    ///
+    /// Returns the slashes, the number of owners processed, and whether all of them are
+    fn slash_owners(
+        &self,
+        deps: DepsMut,
+        slash: &mut LocalSlash,
+        slashed_on_chain: bool,
+        limit: u32,
+    ) -> Result<(Vec<SlashInfo>, u32, bool), ContractError> {
+        let lien_holder = self.local_staking.load(deps.storage)?.contract.0;
+
+        let mut slashes = vec![];
+        let mut processed = 0;
+        let mut done = false;
+        while processed < limit && !done {
+            let page_limit = min(limit - processed, MAX_PAGE_LIMIT);
+            let page: OwnersByValidatorResponse = deps.querier.query_wasm_smart(
+                &lien_holder,
+                &NativeStakingQueryMsg::OwnersByValidator {
+                    validator: slash.validator.clone(),
+                    start_after: slash.start_after.clone(),
+                    limit: Some(page_limit),
+                },
+            )?;
+            processed += page.owners.len() as u32;
+            done = page.owners.len() < page_limit as usize;
+
+            for owner in page.owners {
+                slash.start_after = Some(owner.owner.clone());
+                let user = Addr::unchecked(&owner.owner);
+                let lien = match self.liens.may_load(deps.storage, (&user, &lien_holder))? {
+                    Some(lien) => lien,
+                    None => continue,
+                };
+                let kept = Decimal::one()
+                    .checked_sub(slash.slash_ratio)
+                    .unwrap_or_default();
+                let slash_amount = match slashed_on_chain {
+                    false => owner.amount * slash.slash_ratio,
+                    // The delegation was fully slashed, its owner loses the whole local lien
+                    true if kept.is_zero() => lien.amount.low(),
+                    true => owner
+                        .amount
+                        .multiply_ratio(slash.slash_ratio.numerator(), kept.numerator()),
+                };
+                // Never slash over the local lien
+                let slash_amount = min(slash_amount, lien.amount.low());
+                if !slash_amount.is_zero() {
+                    slashes.push(SlashInfo {
+                        user: owner.owner,
+                        slash: slash_amount,
+                    });
+                }
+            }
         }
-        Ok(())
+
+        self.slash(deps.storage, &lien_holder, &slashes)?;
+        // The slashed tokens are burnt on the local staking side
+        let slashed: Uint128 = slashes.iter().map(|s| s.slash).sum();
+        self.update_local_outstanding(deps.storage, Uint128::zero(), slashed)?;
+
+        Ok((slashes, processed, done))
     }
 
     fn propagate_slash(
@@ -865,7 +2929,8 @@ impl VaultApi for VaultContract<'_> {
     ) -> Result<Response, ContractError> {
         nonpayable(&ctx.info)?;
 
-        self.unstake(&mut ctx, owner.clone(), amount.clone())?;
+        let owner = normalize_addr(ctx.deps.api, &owner)?;
+        self.unstake(&mut ctx, &owner, amount.clone(), LienKind::Cross, false)?;
 
         let resp = Response::new()
             .add_attribute("action", "release_cross_stake")
@@ -876,22 +2941,161 @@ impl VaultApi for VaultContract<'_> {
         Ok(resp)
     }
 
+    /// This must be called by the remote staking contract to release this claim, sending the
+    /// released collateral to the recipient
+    #[msg(exec)]
+    fn release_cross_stake_to(
+        &self,
+        mut ctx: ExecCtx,
+        // address of the user who originally called stake_remote
+        owner: String,
+        // address of the user getting the released tokens
+        recipient: String,
+        // amount to unstake on that contract
+        amount: Coin,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let owner = normalize_addr(ctx.deps.api, &owner)?;
+        let recipient = ctx.deps.api.addr_validate(&recipient)?;
+
+        self.unstake(&mut ctx, &owner, amount.clone(), LienKind::Cross, false)?;
+
+        let (msgs, _) = self.withdraw_collateral(
+            ctx.deps.storage,
+            &owner,
+            &recipient,
+            &amount,
+            Decimal::zero(),
+        )?;
+
+        let resp = Response::new()
+            .add_messages(msgs)
+            .add_attribute("action", "release_cross_stake_to")
+            .add_attribute("sender", ctx.info.sender)
+            .add_attribute("owner", owner)
+            .add_attribute("recipient", recipient)
+            .add_attribute("amount", amount.amount.to_string());
+
+        Ok(resp)
+    }
+
+    /// Like `release_cross_stake`, or `release_cross_stake_to` with a recipient, for matured
+    /// unbonds, which are not subject to the min release
+    #[msg(exec)]
+    fn release_matured_cross_stake(
+        &self,
+        mut ctx: ExecCtx,
+        // address of the user who originally called stake_remote
+        owner: String,
+        // amount to unstake on that contract
+        amount: Coin,
+        // address of the user getting the released tokens, if they are to be unbonded
+        recipient: Option<String>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let owner = normalize_addr(ctx.deps.api, &owner)?;
+        let recipient = recipient
+            .map(|recipient| ctx.deps.api.addr_validate(&recipient))
+            .transpose()?;
+
+        self.unstake(&mut ctx, &owner, amount.clone(), LienKind::Cross, true)?;
+
+        let mut resp = Response::new()
+            .add_attribute("action", "release_matured_cross_stake")
+            .add_attribute("sender", ctx.info.sender)
+            .add_attribute("owner", &owner)
+            .add_attribute("amount", amount.amount.to_string());
+        if let Some(recipient) = recipient {
+            let (msgs, _) = self.withdraw_collateral(
+                ctx.deps.storage,
+                &owner,
+                &recipient,
+                &amount,
+                Decimal::zero(),
+            )?;
+            resp = resp
+                .add_messages(msgs)
+                .add_attribute("recipient", recipient);
+        }
+
+        Ok(resp)
+    }
+
+    /// This must be called by the remote staking contract to move part of the owner's claim to
+    /// the recipient, along with the collateral it covers
+    #[msg(exec)]
+    fn transfer_cross_stake(
+        &self,
+        mut ctx: ExecCtx,
+        // address of the user who originally called stake_remote
+        owner: String,
+        // address of the user getting the claim
+        recipient: String,
+        // amount of the claim to move
+        amount: Coin,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        self.transfer_lien(&mut ctx, owner.clone(), recipient.clone(), amount.clone())?;
+
+        let resp = Response::new()
+            .add_attribute("action", "transfer_cross_stake")
+            .add_attribute("sender", ctx.info.sender)
+            .add_attribute("owner", owner)
+            .add_attribute("recipient", recipient)
+            .add_attribute("amount", amount.amount.to_string());
+
+        Ok(resp)
+    }
+
     /// This must be called by the local staking contract to release this claim
     /// Amount of tokens unstaked are those included in ctx.info.funds
     #[msg(exec)]
     fn release_local_stake(
         &self,
-        mut ctx: ExecCtx,
+        ctx: ExecCtx,
         // address of the user who originally called stake_remote
         owner: String,
     ) -> Result<Response, ContractError> {
-        let denom = self.config.load(ctx.deps.storage)?.denom;
+        let denom = self.native_denom(ctx.deps.storage)?;
         let amount = must_pay(&ctx.info, &denom)?;
 
-        self.unstake(&mut ctx, owner.clone(), coin(amount.u128(), denom))?;
+        self.release_local_stake_amount(ctx, owner, amount)
+    }
+
+    /// The tokens are already staked by the local staking contract, so they are counted as
+    /// outstanding there, and the lien covers them in full
+    #[msg(exec)]
+    fn add_local_stake(
+        &self,
+        ctx: ExecCtx,
+        // address of the user who originally called stake_local
+        owner: String,
+        // amount staked back
+        amount: Coin,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let local_staking = self.local_staking.load(ctx.deps.storage)?;
+        ensure!(
+            ctx.info.sender == local_staking.contract.0,
+            ContractError::Unauthorized {}
+        );
+        let owner = normalize_addr(ctx.deps.api, &owner)?;
+        let (lien, amount) = self.checked_lien(
+            ctx.deps.storage,
+            &owner,
+            &ctx.info.sender,
+            amount,
+            LienKind::Local,
+        )?;
+        self.add_covered_lien(ctx.deps.storage, &owner, &ctx.info.sender, lien, amount)?;
+        self.update_local_outstanding(ctx.deps.storage, amount, Uint128::zero())?;
 
         let resp = Response::new()
-            .add_attribute("action", "release_cross_stake")
+            .add_attribute("action", "add_local_stake")
             .add_attribute("sender", ctx.info.sender)
             .add_attribute("owner", owner)
             .add_attribute("amount", amount.to_string());
@@ -901,16 +3105,93 @@ impl VaultApi for VaultContract<'_> {
 
     /// This must be called by the external staking contract to process a misbehaviour
     #[msg(exec)]
-    fn cross_slash(
+    fn cross_slash(&self, ctx: ExecCtx, slashes: Vec<SlashInfo>) -> Result<Response, Self::Error> {
+        nonpayable(&ctx.info)?;
+
+        self.slash(ctx.deps.storage, &ctx.info.sender, &slashes)?;
+
+        let resp = Response::new()
+            .add_attribute("action", "process_cross_slashing")
+            .add_attribute("lien_holder", ctx.info.sender)
+            .add_attribute(
+                "users",
+                slashes
+                    .iter()
+                    .map(|s| s.user.clone())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            );
+
+        Ok(resp)
+    }
+
+    #[msg(exec)]
+    fn cross_slash_with_bounty(
         &self,
-        mut ctx: ExecCtx,
+        ctx: ExecCtx,
+        slashes: Vec<SlashInfo>,
+        recipient: String,
+        bounty: Uint128,
+    ) -> Result<Response, Self::Error> {
+        nonpayable(&ctx.info)?;
+        let recipient = ctx.deps.api.addr_validate(&recipient)?;
+
+        self.slash(ctx.deps.storage, &ctx.info.sender, &slashes)?;
+
+        // The bounty is paid out of the slashed collateral, as far as it is available
+        let (paid, msgs) = self.slash_payouts(
+            ctx.deps.as_ref(),
+            &ctx.env,
+            &slashes,
+            vec![(recipient.clone(), bounty)],
+        )?;
+
+        let resp = Response::new()
+            .add_messages(msgs)
+            .add_attribute("action", "process_cross_slashing")
+            .add_attribute("lien_holder", ctx.info.sender)
+            .add_attribute(
+                "users",
+                slashes
+                    .iter()
+                    .map(|s| s.user.clone())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            )
+            .add_attribute("bounty", paid[0].to_string())
+            .add_attribute("recipient", &recipient);
+
+        Ok(resp)
+    }
+
+    #[msg(exec)]
+    fn cross_slash_with_payouts(
+        &self,
+        ctx: ExecCtx,
         slashes: Vec<SlashInfo>,
+        payouts: Vec<vault_api::SlashPayout>,
     ) -> Result<Response, Self::Error> {
         nonpayable(&ctx.info)?;
+        let payouts = payouts
+            .into_iter()
+            .map(|payout| {
+                Ok((
+                    ctx.deps.api.addr_validate(&payout.recipient)?,
+                    payout.amount,
+                ))
+            })
+            .collect::<StdResult<Vec<_>>>()?;
+
+        self.slash(ctx.deps.storage, &ctx.info.sender, &slashes)?;
 
-        self.slash(&mut ctx, &slashes)?;
+        let recipients: Vec<_> = payouts
+            .iter()
+            .map(|(recipient, _)| recipient.to_string())
+            .collect();
+        let (paid, msgs) = self.slash_payouts(ctx.deps.as_ref(), &ctx.env, &slashes, payouts)?;
 
         let resp = Response::new()
+            .add_messages(msgs)
             .add_attribute("action", "process_cross_slashing")
             .add_attribute("lien_holder", ctx.info.sender)
             .add_attribute(
@@ -920,6 +3201,14 @@ impl VaultApi for VaultContract<'_> {
                     .map(|s| s.user.clone())
                     .collect::<Vec<_>>()
                     .join(", "),
+            )
+            .add_attribute("recipients", recipients.join(", "))
+            .add_attribute(
+                "payouts",
+                paid.iter()
+                    .map(Uint128::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", "),
             );
 
         Ok(resp)
@@ -947,4 +3236,695 @@ impl VaultApi for VaultContract<'_> {
             .add_attribute("tx_id", tx_id.to_string());
         Ok(resp)
     }
+
+    #[msg(exec)]
+    fn commit_txs(&self, mut ctx: ExecCtx, tx_ids: Vec<u64>) -> Result<Response, ContractError> {
+        // Any failure reverts the whole batch
+        for &tx_id in &tx_ids {
+            self.commit_stake(&mut ctx, tx_id)?;
+        }
+
+        let resp = Response::new()
+            .add_attribute("action", "commit_txs")
+            .add_attribute("sender", ctx.info.sender)
+            .add_attribute("tx_ids", join_tx_ids(&tx_ids));
+
+        Ok(resp)
+    }
+
+    #[msg(exec)]
+    fn rollback_txs(&self, mut ctx: ExecCtx, tx_ids: Vec<u64>) -> Result<Response, ContractError> {
+        // Any failure reverts the whole batch
+        for &tx_id in &tx_ids {
+            self.rollback_stake(&mut ctx, tx_id)?;
+        }
+
+        let resp = Response::new()
+            .add_attribute("action", "rollback_txs")
+            .add_attribute("sender", ctx.info.sender)
+            .add_attribute("tx_ids", join_tx_ids(&tx_ids));
+
+        Ok(resp)
+    }
+}
+
+/// Sudo entry point. Only the chain can call it
+#[cfg_attr(not(feature = "library"), cosmwasm_std::entry_point)]
+pub fn sudo(deps: DepsMut, _env: Env, msg: SudoMsg) -> Result<Response, ContractError> {
+    match msg {
+        SudoMsg::LocalSlash {
+            validator,
+            slash_ratio,
+            infraction_height,
+        } => VaultContract::new().local_slash(
+            deps,
+            validator,
+            slash_ratio,
+            infraction_height,
+            LOCAL_SLASH_LIMIT,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{
+        coins, from_slice, CodeInfoResponse, ContractInfoResponse, ContractResult, HexBinary,
+        OwnedDeps, SystemResult, WasmQuery,
+    };
+    use mesh_apis::local_staking_api::LocalStakingApiHelper;
+
+    use crate::msg::OwnerDelegation;
+
+    use super::*;
+
+    const NATIVE_STAKING: &str = "native_staking";
+
+    /// Stores an user with a single local lien
+    fn local_stake(
+        contract: &VaultContract,
+        storage: &mut dyn Storage,
+        user: &str,
+        collateral: u128,
+        staked: u128,
+    ) {
+        let user = Addr::unchecked(user);
+        let lien = Lien {
+            amount: ValueRange::new_val(Uint128::new(staked)),
+            slashable: Decimal::percent(10),
+            kind: LienKind::Local,
+        };
+        contract
+            .liens
+            .save(storage, (&user, &Addr::unchecked(NATIVE_STAKING)), &lien)
+            .unwrap();
+        let mut user_info = UserInfo {
+            max_lien: ValueRange::new_val(Uint128::new(staked)),
+            total_slashable: ValueRange::new_val(Uint128::new(staked) * lien.slashable),
+            ..UserInfo::default()
+        };
+        contract
+            .set_collateral(storage, &user, &mut user_info, Uint128::new(collateral))
+            .unwrap();
+        contract.users.save(storage, &user, &user_info).unwrap();
+    }
+
+    #[test]
+    fn invariants_report() {
+        let mut deps = mock_dependencies();
+        let contract = VaultContract::new();
+        let user = Addr::unchecked("user");
+        local_stake(&contract, &mut deps.storage, user.as_str(), 300, 100);
+
+        let report = contract.invariants_report(&deps.storage, &user).unwrap();
+        assert_eq!(
+            report,
+            InvariantsReport {
+                collateral: Uint128::new(300),
+                max_lien: ValueRange::new_val(Uint128::new(100)),
+                liens_max: ValueRange::new_val(Uint128::new(100)),
+                total_slashable: ValueRange::new_val(Uint128::new(10)),
+                liens_slashable: ValueRange::new_val(Uint128::new(10)),
+                violations: vec![],
+            }
+        );
+
+        // Broken accounting is reported
+        let mut user_info = contract.users.load(&deps.storage, &user).unwrap();
+        user_info.max_lien = ValueRange::new_val(Uint128::new(50));
+        user_info.total_slashable = ValueRange::new_val(Uint128::new(12));
+        user_info.collateral = Uint128::new(40);
+        contract
+            .users
+            .save(&mut deps.storage, &user, &user_info)
+            .unwrap();
+
+        let report = contract.invariants_report(&deps.storage, &user).unwrap();
+        assert_eq!(
+            report.violations,
+            [
+                "max_lien",
+                "total_slashable",
+                "liens_max_over_collateral",
+                "used_collateral_over_collateral"
+            ]
+        );
+    }
+
+    #[test]
+    fn all_liens() {
+        let mut deps = mock_dependencies();
+        let contract = VaultContract::new();
+
+        let liens = [
+            ("user2", "cross1", 30),
+            ("user1", "cross2", 20),
+            ("user1", "cross1", 10),
+            ("user3", NATIVE_STAKING, 40),
+        ];
+        for (user, lienholder, amount) in liens {
+            let lien = Lien {
+                amount: ValueRange::new_val(Uint128::new(amount)),
+                slashable: Decimal::percent(10),
+                kind: LienKind::Cross,
+            };
+            contract
+                .liens
+                .save(
+                    &mut deps.storage,
+                    (&Addr::unchecked(user), &Addr::unchecked(lienholder)),
+                    &lien,
+                )
+                .unwrap();
+        }
+
+        let query = |start_after: Option<(&str, &str)>| {
+            let ctx = QueryCtx {
+                deps: deps.as_ref(),
+                env: mock_env(),
+            };
+            let start_after = start_after.map(|(u, l)| (u.to_owned(), l.to_owned()));
+            contract
+                .all_liens(ctx, start_after, None)
+                .unwrap()
+                .liens
+                .into_iter()
+                .map(|lien| (lien.user, lien.lienholder, lien.amount.high().u128()))
+                .collect::<Vec<_>>()
+        };
+        let expected = |liens: &[(&str, &str, u128)]| {
+            liens
+                .iter()
+                .map(|&(u, l, a)| (u.to_owned(), l.to_owned(), a))
+                .collect::<Vec<_>>()
+        };
+
+        // Ordered by user, then lienholder
+        assert_eq!(
+            query(None),
+            expected(&[
+                ("user1", "cross1", 10),
+                ("user1", "cross2", 20),
+                ("user2", "cross1", 30),
+                ("user3", NATIVE_STAKING, 40),
+            ])
+        );
+
+        // Continuation within and across users
+        assert_eq!(
+            query(Some(("user1", "cross1"))),
+            expected(&[
+                ("user1", "cross2", 20),
+                ("user2", "cross1", 30),
+                ("user3", NATIVE_STAKING, 40),
+            ])
+        );
+        assert_eq!(
+            query(Some(("user1", "cross2"))),
+            expected(&[("user2", "cross1", 30), ("user3", NATIVE_STAKING, 40)])
+        );
+        assert_eq!(query(Some(("user3", NATIVE_STAKING))), expected(&[]));
+    }
+
+    #[test]
+    fn local_staking_checksum_is_stored() {
+        let mut deps = mock_dependencies();
+        let contract = VaultContract::new();
+        let checksum = HexBinary::from([7; 32]);
+
+        let response_checksum = checksum.clone();
+        deps.querier.update_wasm(move |query| {
+            let resp = match query {
+                WasmQuery::Smart { contract_addr, .. } if contract_addr == NATIVE_STAKING => {
+                    to_binary(&MaxSlashResponse {
+                        max_slash: Decimal::percent(10),
+                    })
+                }
+                WasmQuery::ContractInfo { contract_addr } if contract_addr == NATIVE_STAKING => {
+                    let mut info = ContractInfoResponse::default();
+                    info.code_id = 3;
+                    to_binary(&info)
+                }
+                WasmQuery::CodeInfo { code_id: 3 } => to_binary(&CodeInfoResponse::new(
+                    3,
+                    "vault".to_owned(),
+                    response_checksum.clone(),
+                )),
+                _ => panic!("Unexpected query: {query:?}"),
+            };
+            SystemResult::Ok(ContractResult::Ok(resp.unwrap()))
+        });
+
+        // Protobuf encoded `MsgInstantiateContractResponse`, with the contract address only
+        let mut data = vec![0x0a, NATIVE_STAKING.len() as u8];
+        data.extend_from_slice(NATIVE_STAKING.as_bytes());
+        let reply = SubMsgResponse {
+            events: vec![],
+            data: Some(data.into()),
+        };
+        contract.reply_init_callback(deps.as_mut(), reply).unwrap();
+
+        let local_staking = contract.local_staking.load(&deps.storage).unwrap();
+        assert_eq!(local_staking.checksum, Some(checksum));
+        assert_eq!(local_staking.max_slash, Decimal::percent(10));
+    }
+
+    #[test]
+    fn local_slash_sudo() {
+        let mut deps = mock_dependencies();
+        let contract = VaultContract::new();
+        contract
+            .local_staking
+            .save(
+                &mut deps.storage,
+                &LocalStaking {
+                    contract: LocalStakingApiHelper(Addr::unchecked(NATIVE_STAKING)),
+                    max_slash: Decimal::percent(10),
+                    checksum: None,
+                },
+            )
+            .unwrap();
+        local_stake(&contract, &mut deps.storage, "user1", 300, 100);
+        local_stake(&contract, &mut deps.storage, "user2", 200, 50);
+
+        // Native staking delegations to the validator. `user2` has more delegated than its
+        // lien, `user3` has no lien
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::Smart { contract_addr, msg } if contract_addr == NATIVE_STAKING => {
+                let owners = match from_slice(msg).unwrap() {
+                    NativeStakingQueryMsg::OwnersByValidator {
+                        validator,
+                        start_after: None,
+                        ..
+                    } if validator == "val1" => vec![
+                        OwnerDelegation {
+                            owner: "user1".to_owned(),
+                            amount: Uint128::new(100),
+                        },
+                        OwnerDelegation {
+                            owner: "user2".to_owned(),
+                            amount: Uint128::new(1000),
+                        },
+                        OwnerDelegation {
+                            owner: "user3".to_owned(),
+                            amount: Uint128::new(100),
+                        },
+                    ],
+                    _ => vec![],
+                };
+                let resp = to_binary(&OwnersByValidatorResponse { owners });
+                SystemResult::Ok(ContractResult::Ok(resp.unwrap()))
+            }
+            _ => panic!("unexpected query {query:?}"),
+        });
+
+        let msg = SudoMsg::LocalSlash {
+            validator: "val1".to_owned(),
+            slash_ratio: Decimal::percent(10),
+            infraction_height: 12,
+        };
+        let resp = sudo(deps.as_mut(), mock_env(), msg).unwrap();
+        assert!(resp
+            .attributes
+            .iter()
+            .any(|attr| attr.key == "users" && attr.value == "user1, user2"));
+
+        // 10% of the delegation is slashed
+        let user1 = Addr::unchecked("user1");
+        let lien = contract
+            .liens
+            .load(&deps.storage, (&user1, &Addr::unchecked(NATIVE_STAKING)))
+            .unwrap();
+        assert_eq!(lien.amount, ValueRange::new_val(Uint128::new(90)));
+        let user_info = contract.users.load(&deps.storage, &user1).unwrap();
+        assert_eq!(user_info.collateral, Uint128::new(290));
+        assert_eq!(user_info.max_lien, ValueRange::new_val(Uint128::new(90)));
+
+        // Slashing is capped to the local lien
+        let user2 = Addr::unchecked("user2");
+        let lien = contract
+            .liens
+            .load(&deps.storage, (&user2, &Addr::unchecked(NATIVE_STAKING)))
+            .unwrap();
+        assert_eq!(lien.amount, ValueRange::new_val(Uint128::zero()));
+        let user_info = contract.users.load(&deps.storage, &user2).unwrap();
+        assert_eq!(user_info.collateral, Uint128::new(150));
+
+        assert_eq!(
+            contract.total_collateral.load(&deps.storage).unwrap(),
+            Uint128::new(440)
+        );
+    }
+
+    #[test]
+    fn local_slash_paginated() {
+        let mut deps = mock_dependencies();
+        let contract = VaultContract::new();
+        contract
+            .local_staking
+            .save(
+                &mut deps.storage,
+                &LocalStaking {
+                    contract: LocalStakingApiHelper(Addr::unchecked(NATIVE_STAKING)),
+                    max_slash: Decimal::percent(10),
+                    checksum: None,
+                },
+            )
+            .unwrap();
+        local_stake(&contract, &mut deps.storage, "user1", 300, 100);
+        local_stake(&contract, &mut deps.storage, "user2", 200, 50);
+
+        // Native staking delegations to the validator, paginated
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::Smart { contract_addr, msg } if contract_addr == NATIVE_STAKING => {
+                let owners = match from_slice(msg).unwrap() {
+                    NativeStakingQueryMsg::OwnersByValidator {
+                        start_after, limit, ..
+                    } => [("user1", 100), ("user2", 45)]
+                        .into_iter()
+                        .filter(|(owner, _)| Some(*owner) > start_after.as_deref())
+                        .take(limit.unwrap() as usize)
+                        .map(|(owner, amount)| OwnerDelegation {
+                            owner: owner.to_owned(),
+                            amount: Uint128::new(amount),
+                        })
+                        .collect(),
+                    _ => vec![],
+                };
+                let resp = to_binary(&OwnersByValidatorResponse { owners });
+                SystemResult::Ok(ContractResult::Ok(resp.unwrap()))
+            }
+            _ => panic!("unexpected query {query:?}"),
+        });
+        let lien = |deps: &OwnedDeps<_, _, _>, user: &str| {
+            contract
+                .liens
+                .load(
+                    &deps.storage,
+                    (&Addr::unchecked(user), &Addr::unchecked(NATIVE_STAKING)),
+                )
+                .unwrap()
+                .amount
+        };
+
+        // Only the first owner is slashed on notification
+        let resp = contract
+            .local_slash(
+                deps.as_mut(),
+                "val1".to_owned(),
+                Decimal::percent(10),
+                12,
+                1,
+            )
+            .unwrap();
+        assert!(resp
+            .attributes
+            .iter()
+            .any(|attr| attr.key == "pending" && attr.value == "1"));
+        assert_eq!(lien(&deps, "user1"), ValueRange::new_val(Uint128::new(90)));
+        assert_eq!(lien(&deps, "user2"), ValueRange::new_val(Uint128::new(50)));
+
+        // The others are slashed once the delegations were slashed on chain, on what they were
+        // delegating before
+        let resp = contract
+            .continue_local_slash(
+                (deps.as_mut(), mock_env(), mock_info("anyone", &[])).into(),
+                None,
+            )
+            .unwrap();
+        assert!(resp
+            .attributes
+            .iter()
+            .any(|attr| attr.key == "users" && attr.value == "user2"));
+        assert_eq!(lien(&deps, "user2"), ValueRange::new_val(Uint128::new(45)));
+        assert_eq!(contract.local_slashes.len(&deps.storage).unwrap(), 0);
+    }
+
+    // Invariants checks iterate over the liens on purpose
+    #[cfg(not(feature = "invariants"))]
+    #[test]
+    fn release_non_max_lien_skips_recalculation() {
+        let mut deps = mock_dependencies();
+        let contract = VaultContract::new();
+        let config = Config {
+            collateral: CollateralType::Native("uosmo".to_owned()),
+            tx_timeout: None,
+            bond_fee: Decimal::zero(),
+            unbond_fee: Decimal::zero(),
+            fee_recipient: None,
+            max_total_collateral: None,
+            min_release: None,
+            check_remote_stake: false,
+        };
+        contract.config.save(&mut deps.storage, &config).unwrap();
+
+        // A large set of liens, the last one being the max
+        let user = Addr::unchecked("user");
+        let slashable = Decimal::percent(10);
+        let mut user_info = UserInfo::default();
+        for i in 1..=200u128 {
+            let lien = Lien {
+                amount: ValueRange::new_val(Uint128::new(i)),
+                slashable,
+                kind: LienKind::Cross,
+            };
+            let lienholder = Addr::unchecked(format!("lienholder{i:03}"));
+            contract
+                .liens
+                .save(&mut deps.storage, (&user, &lienholder), &lien)
+                .unwrap();
+            user_info.max_lien = max_range(user_info.max_lien, lien.amount);
+            user_info
+                .total_slashable
+                .add(Uint128::new(i) * slashable, Uint128::MAX)
+                .unwrap();
+        }
+        contract
+            .set_collateral(&mut deps.storage, &user, &mut user_info, Uint128::new(200))
+            .unwrap();
+        contract
+            .users
+            .save(&mut deps.storage, &user, &user_info)
+            .unwrap();
+
+        // An unreadable lien, failing any iteration over the user's liens
+        let corrupted = contract.liens.key((&user, &Addr::unchecked("corrupted")));
+        deps.storage.set(&corrupted, b"corrupted");
+
+        let release = |deps: DepsMut, lienholder: &str, amount: u128| {
+            let info = mock_info(lienholder, &[]);
+            let ctx = ExecCtx::from((deps, mock_env(), info));
+            contract.release_cross_stake(ctx, user.to_string(), coin(amount, "uosmo"))
+        };
+
+        // Zero amounts are rejected
+        let err = release(deps.as_mut(), "lienholder050", 0).unwrap_err();
+        assert_eq!(err, ContractError::ZeroAmount);
+
+        // Releasing a lien other than the max one doesn't go through all the liens
+        release(deps.as_mut(), "lienholder050", 30).unwrap();
+        let user_info = contract.users.load(&deps.storage, &user).unwrap();
+        assert_eq!(user_info.max_lien, ValueRange::new_val(Uint128::new(200)));
+        let lien = contract
+            .liens
+            .load(&deps.storage, (&user, &Addr::unchecked("lienholder050")))
+            .unwrap();
+        assert_eq!(lien.amount, ValueRange::new_val(Uint128::new(20)));
+
+        // Releasing the max lien recalculates it
+        deps.storage.remove(&corrupted);
+        release(deps.as_mut(), "lienholder200", 30).unwrap();
+        let user_info = contract.users.load(&deps.storage, &user).unwrap();
+        assert_eq!(user_info.max_lien, ValueRange::new_val(Uint128::new(199)));
+
+        // Going through all the liens
+        deps.storage.set(&corrupted, b"corrupted");
+        release(deps.as_mut(), "lienholder199", 30).unwrap_err();
+    }
+
+    #[test]
+    fn collateral_overflow() {
+        let mut deps = mock_dependencies();
+        let contract = VaultContract::new();
+        let config = Config {
+            collateral: CollateralType::Native("osmo".to_owned()),
+            tx_timeout: None,
+            bond_fee: Decimal::zero(),
+            unbond_fee: Decimal::zero(),
+            fee_recipient: None,
+            max_total_collateral: None,
+            min_release: None,
+            check_remote_stake: false,
+        };
+        contract.config.save(&mut deps.storage, &config).unwrap();
+        let user = Addr::unchecked("user");
+        let lienholder = Addr::unchecked("lienholder");
+
+        contract
+            .bond_collateral(
+                &mut deps.storage,
+                user.clone(),
+                Uint128::MAX - Uint128::one(),
+            )
+            .unwrap();
+        contract
+            .bond_collateral(&mut deps.storage, user.clone(), Uint128::one())
+            .unwrap();
+
+        // Both the user and the total collateral are at the max
+        let err = contract
+            .bond_collateral(&mut deps.storage, user.clone(), Uint128::one())
+            .unwrap_err();
+        assert_eq!(err, ContractError::Overflow);
+        let err = contract
+            .bond_collateral(&mut deps.storage, Addr::unchecked("other"), Uint128::one())
+            .unwrap_err();
+        assert_eq!(err, ContractError::Overflow);
+
+        let mut stake = |lienholder: &Addr, amount: Uint128, remote| {
+            contract.stake(
+                &mut (deps.as_mut(), mock_env(), mock_info(user.as_str(), &[])).into(),
+                &config,
+                lienholder,
+                Decimal::percent(10),
+                coin(amount.u128(), "osmo"),
+                remote,
+            )
+        };
+        stake(&lienholder, Uint128::MAX, false).unwrap();
+        assert_eq!(
+            stake(&lienholder, Uint128::one(), false).unwrap_err(),
+            ContractError::Overflow
+        );
+        assert_eq!(
+            stake(&lienholder, Uint128::one(), true).unwrap_err(),
+            ContractError::WrongLienKind(LienKind::Cross)
+        );
+
+        let lien = contract
+            .liens
+            .load(&deps.storage, (&user, &lienholder))
+            .unwrap();
+        assert_eq!(lien.amount, ValueRange::new_val(Uint128::MAX));
+        let user_info = contract.users.load(&deps.storage, &user).unwrap();
+        assert_eq!(
+            user_info.total_slashable,
+            ValueRange::new_val(Uint128::MAX * Decimal::percent(10))
+        );
+
+        // Releasing the whole lien brings the slashable amount back to zero
+        contract
+            .unstake(
+                &mut (
+                    deps.as_mut(),
+                    mock_env(),
+                    mock_info(lienholder.as_str(), &[]),
+                )
+                    .into(),
+                &user,
+                coin(Uint128::MAX.u128(), "osmo"),
+                LienKind::Local,
+                false,
+            )
+            .unwrap();
+        let user_info = contract.users.load(&deps.storage, &user).unwrap();
+        assert_eq!(
+            user_info.total_slashable,
+            ValueRange::new_val(Uint128::zero())
+        );
+
+        // Same for a pending cross stake
+        let mut stake = |amount: Uint128| {
+            contract.stake(
+                &mut (deps.as_mut(), mock_env(), mock_info(user.as_str(), &[])).into(),
+                &config,
+                &Addr::unchecked("cross_lienholder"),
+                Decimal::percent(10),
+                coin(amount.u128(), "osmo"),
+                true,
+            )
+        };
+        stake(Uint128::MAX).unwrap();
+        assert_eq!(stake(Uint128::one()).unwrap_err(), ContractError::Overflow);
+    }
+
+    #[test]
+    fn total_collateral_is_backfilled_on_migration() {
+        let mut deps = mock_dependencies();
+        let contract = VaultContract::new();
+        let config = Config {
+            collateral: CollateralType::Native("osmo".to_owned()),
+            tx_timeout: None,
+            bond_fee: Decimal::zero(),
+            unbond_fee: Decimal::zero(),
+            fee_recipient: None,
+            max_total_collateral: None,
+            min_release: None,
+            check_remote_stake: false,
+        };
+        contract.config.save(&mut deps.storage, &config).unwrap();
+
+        // Users bonded before the total was tracked
+        for (user, collateral) in [("alice", 200), ("bob", 300)] {
+            let info = UserInfo {
+                collateral: Uint128::new(collateral),
+                ..UserInfo::default()
+            };
+            contract
+                .users
+                .save(&mut deps.storage, &Addr::unchecked(user), &info)
+                .unwrap();
+        }
+
+        // A new bond doesn't make the total trusted
+        contract
+            .bond(
+                (
+                    deps.as_mut(),
+                    mock_env(),
+                    mock_info("carol", &coins(50, "osmo")),
+                )
+                    .into(),
+            )
+            .unwrap();
+        assert_eq!(
+            contract.held_collateral(&deps.storage).unwrap_err(),
+            ContractError::CollateralTotalUntracked
+        );
+
+        contract
+            .migrate((deps.as_mut(), mock_env()).into())
+            .unwrap();
+        assert_eq!(
+            contract.total_collateral.load(&deps.storage).unwrap(),
+            Uint128::new(550)
+        );
+        assert_eq!(
+            contract.held_collateral(&deps.storage).unwrap(),
+            Uint128::new(550)
+        );
+
+        // Pre-existing users can bond and unbond
+        contract
+            .bond(
+                (
+                    deps.as_mut(),
+                    mock_env(),
+                    mock_info("alice", &coins(20, "osmo")),
+                )
+                    .into(),
+            )
+            .unwrap();
+        contract
+            .unbond(
+                (deps.as_mut(), mock_env(), mock_info("bob", &[])).into(),
+                coin(300, "osmo"),
+            )
+            .unwrap();
+        assert_eq!(
+            contract.total_collateral.load(&deps.storage).unwrap(),
+            Uint128::new(270)
+        );
+    }
 }
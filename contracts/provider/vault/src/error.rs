@@ -1,46 +1,409 @@
-use cosmwasm_std::{Addr, StdError, Uint128};
+use cosmwasm_std::{Addr, Decimal, StdError, Timestamp, Uint128};
 use cw_utils::{ParseReplyError, PaymentError};
 use mesh_sync::{RangeError, Tx, ValueRange};
+use serde::Serialize;
 use thiserror::Error;
 
+use crate::state::LienKind;
+
+/// Every error is prefixed with its stable code (eg. `[mesh-vault:E009]`), which frontends should
+/// match on rather than on the message text. Codes are never renumbered nor reused: new errors
+/// get the next free code.
 #[derive(Error, Debug, PartialEq)]
 pub enum ContractError {
-    #[error("{0}")]
+    #[error("[mesh-vault:E001] {0}")]
     Std(#[from] StdError),
 
-    #[error("{0}")]
+    #[error("[mesh-vault:E002] {0}")]
     Payment(#[from] PaymentError),
 
-    #[error("{0}")]
+    #[error("[mesh-vault:E003] {0}")]
     ParseReply(#[from] ParseReplyError),
 
-    #[error("{0}")]
+    #[error("[mesh-vault:E004] {0}")]
     Range(#[from] RangeError),
 
-    #[error("Unauthorized")]
+    #[error("[mesh-vault:E005] Unauthorized")]
     Unauthorized {},
 
-    #[error("All denoms are expected to be {0}")]
+    #[error("[mesh-vault:E006] All denoms are expected to be {0}")]
     UnexpectedDenom(String),
 
-    #[error("Claim is locked, only {0} can be unbonded")]
-    ClaimsLocked(ValueRange<Uint128>),
+    #[error(
+        "[mesh-vault:E007] Collateral is a cw20 token, it has to be sent through its contract"
+    )]
+    Cw20Collateral,
+
+    #[error(
+        "[mesh-vault:E008] Claim is locked, only {free} can be unbonded (largest lien: {}, pending txs: {pending_txs})",
+        .max_lienholder.as_ref().map_or("none", Addr::as_str)
+    )]
+    ClaimsLocked {
+        free: ValueRange<Uint128>,
+        /// Lienholder of the lien using the most collateral, the first one to unwind
+        max_lienholder: Option<Addr>,
+        /// Pending txs of the user, which may still use collateral
+        pending_txs: u32,
+    },
 
-    #[error("The address doesn't have sufficient balance for this operation")]
-    InsufficentBalance,
+    #[error("[mesh-vault:E009] The address doesn't have sufficient balance for this operation")]
+    InsufficientBalance,
 
-    #[error("The lienholder doesn't have any claims")]
+    #[error("[mesh-vault:E010] The lienholder doesn't have any claims")]
     UnknownLienholder,
 
-    #[error("The lienholder doesn't have enough claims for the action")]
+    #[error("[mesh-vault:E011] The lienholder doesn't have enough claims for the action")]
     InsufficientLien,
 
-    #[error("Invalid reply id: {0}")]
+    #[error("[mesh-vault:E012] Amount must be greater than zero")]
+    ZeroAmount,
+
+    #[error("[mesh-vault:E013] Snapshot {0} not found")]
+    SnapshotNotFound(u64),
+
+    #[error("[mesh-vault:E014] Local staking contract {0} is not compatible: {1}")]
+    LocalStakingNotCompatible(String, StdError),
+
+    #[error("[mesh-vault:E015] {addr} is not a cross staking contract: {source}")]
+    NotACrossStakingContract { addr: String, source: StdError },
+
+    #[error("[mesh-vault:E016] Invalid slash ratio {0}, must be within [0, 1]")]
+    InvalidSlashRatio(Decimal),
+
+    #[error("[mesh-vault:E017] Invalid reply id: {0}")]
     InvalidReplyId(u64),
 
-    #[error("The tx {0} exists but is of the wrong type: {1}")]
+    #[error("[mesh-vault:E018] The tx {0} exists but is of the wrong type: {1}")]
     WrongTypeTx(u64, Tx),
 
-    #[error("The tx {0} exists but comes from the wrong address: {1}")]
+    #[error("[mesh-vault:E019] The tx {0} exists but comes from the wrong address: {1}")]
     WrongContractTx(u64, Addr),
+
+    #[error("[mesh-vault:E020] A batch must have between 1 and {0} operations")]
+    InvalidBatchLength(usize),
+
+    #[error("[mesh-vault:E021] A batch can only bond once")]
+    MultipleBatchBonds,
+
+    #[error("[mesh-vault:E022] Pending txs don't expire")]
+    TxTimeoutDisabled,
+
+    #[error("[mesh-vault:E023] The tx {0} doesn't expire until {1}")]
+    TxNotExpired(u64, Timestamp),
+
+    #[error("[mesh-vault:E024] Fees can't be over {0}")]
+    FeeTooHigh(Decimal),
+
+    #[error("[mesh-vault:E025] Fees require a fee recipient")]
+    MissingFeeRecipient,
+
+    #[error("[mesh-vault:E026] Total collateral cap of {0} reached")]
+    CollateralCapReached(Uint128),
+
+    #[error("[mesh-vault:E027] Idempotency key longer than {0} characters")]
+    IdempotencyKeyTooLong(usize),
+
+    #[error("[mesh-vault:E028] The lien is not a {0:?} one")]
+    WrongLienKind(LienKind),
+
+    #[error("[mesh-vault:E029] Collateral accounting overflow")]
+    Overflow,
+
+    #[error("[mesh-vault:E030] Collateral accounting underflow")]
+    Underflow,
+
+    #[error("[mesh-vault:E031] The total collateral is not tracked")]
+    CollateralTotalUntracked,
+
+    #[error("[mesh-vault:E032] Balance {0} is below the held collateral {1}")]
+    BalanceBelowCollateral(Uint128, Uint128),
+
+    #[error("[mesh-vault:E033] Release below the minimum of {0}")]
+    ReleaseTooSmall(Uint128),
+
+    #[error("[mesh-vault:E034] Stake would push the leverage over the limit of {0}")]
+    LeverageLimitExceeded(Decimal),
+
+    #[error("[mesh-vault:E035] Stake rejected by the cross staking contract: {0}")]
+    RemoteStakeRejected(String),
+
+    #[error("[mesh-vault:E036] The account has no position in the vault")]
+    UnknownAccount,
+}
+
+impl ContractError {
+    /// Stable code of the error, as listed in [`ERROR_CODES`]
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Std(..) => "E001",
+            Self::Payment(..) => "E002",
+            Self::ParseReply(..) => "E003",
+            Self::Range(..) => "E004",
+            Self::Unauthorized {} => "E005",
+            Self::UnexpectedDenom(..) => "E006",
+            Self::Cw20Collateral => "E007",
+            Self::ClaimsLocked { .. } => "E008",
+            Self::InsufficientBalance => "E009",
+            Self::UnknownLienholder => "E010",
+            Self::InsufficientLien => "E011",
+            Self::ZeroAmount => "E012",
+            Self::SnapshotNotFound(..) => "E013",
+            Self::LocalStakingNotCompatible(..) => "E014",
+            Self::NotACrossStakingContract { .. } => "E015",
+            Self::InvalidSlashRatio(..) => "E016",
+            Self::InvalidReplyId(..) => "E017",
+            Self::WrongTypeTx(..) => "E018",
+            Self::WrongContractTx(..) => "E019",
+            Self::InvalidBatchLength(..) => "E020",
+            Self::MultipleBatchBonds => "E021",
+            Self::TxTimeoutDisabled => "E022",
+            Self::TxNotExpired(..) => "E023",
+            Self::FeeTooHigh(..) => "E024",
+            Self::MissingFeeRecipient => "E025",
+            Self::CollateralCapReached(..) => "E026",
+            Self::IdempotencyKeyTooLong(..) => "E027",
+            Self::WrongLienKind(..) => "E028",
+            Self::Overflow => "E029",
+            Self::Underflow => "E030",
+            Self::CollateralTotalUntracked => "E031",
+            Self::BalanceBelowCollateral(..) => "E032",
+            Self::ReleaseTooSmall(..) => "E033",
+            Self::LeverageLimitExceeded(..) => "E034",
+            Self::RemoteStakeRejected(..) => "E035",
+            Self::UnknownAccount => "E036",
+        }
+    }
+}
+
+/// Entry of the error codes table, published along with the schema
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorCode {
+    pub code: &'static str,
+    /// Name of the `ContractError` variant
+    pub error: &'static str,
+}
+
+/// Codes of all the errors, in order
+pub const ERROR_CODES: &[ErrorCode] = &[
+    ErrorCode {
+        code: "E001",
+        error: "Std",
+    },
+    ErrorCode {
+        code: "E002",
+        error: "Payment",
+    },
+    ErrorCode {
+        code: "E003",
+        error: "ParseReply",
+    },
+    ErrorCode {
+        code: "E004",
+        error: "Range",
+    },
+    ErrorCode {
+        code: "E005",
+        error: "Unauthorized",
+    },
+    ErrorCode {
+        code: "E006",
+        error: "UnexpectedDenom",
+    },
+    ErrorCode {
+        code: "E007",
+        error: "Cw20Collateral",
+    },
+    ErrorCode {
+        code: "E008",
+        error: "ClaimsLocked",
+    },
+    ErrorCode {
+        code: "E009",
+        error: "InsufficientBalance",
+    },
+    ErrorCode {
+        code: "E010",
+        error: "UnknownLienholder",
+    },
+    ErrorCode {
+        code: "E011",
+        error: "InsufficientLien",
+    },
+    ErrorCode {
+        code: "E012",
+        error: "ZeroAmount",
+    },
+    ErrorCode {
+        code: "E013",
+        error: "SnapshotNotFound",
+    },
+    ErrorCode {
+        code: "E014",
+        error: "LocalStakingNotCompatible",
+    },
+    ErrorCode {
+        code: "E015",
+        error: "NotACrossStakingContract",
+    },
+    ErrorCode {
+        code: "E016",
+        error: "InvalidSlashRatio",
+    },
+    ErrorCode {
+        code: "E017",
+        error: "InvalidReplyId",
+    },
+    ErrorCode {
+        code: "E018",
+        error: "WrongTypeTx",
+    },
+    ErrorCode {
+        code: "E019",
+        error: "WrongContractTx",
+    },
+    ErrorCode {
+        code: "E020",
+        error: "InvalidBatchLength",
+    },
+    ErrorCode {
+        code: "E021",
+        error: "MultipleBatchBonds",
+    },
+    ErrorCode {
+        code: "E022",
+        error: "TxTimeoutDisabled",
+    },
+    ErrorCode {
+        code: "E023",
+        error: "TxNotExpired",
+    },
+    ErrorCode {
+        code: "E024",
+        error: "FeeTooHigh",
+    },
+    ErrorCode {
+        code: "E025",
+        error: "MissingFeeRecipient",
+    },
+    ErrorCode {
+        code: "E026",
+        error: "CollateralCapReached",
+    },
+    ErrorCode {
+        code: "E027",
+        error: "IdempotencyKeyTooLong",
+    },
+    ErrorCode {
+        code: "E028",
+        error: "WrongLienKind",
+    },
+    ErrorCode {
+        code: "E029",
+        error: "Overflow",
+    },
+    ErrorCode {
+        code: "E030",
+        error: "Underflow",
+    },
+    ErrorCode {
+        code: "E031",
+        error: "CollateralTotalUntracked",
+    },
+    ErrorCode {
+        code: "E032",
+        error: "BalanceBelowCollateral",
+    },
+    ErrorCode {
+        code: "E033",
+        error: "ReleaseTooSmall",
+    },
+    ErrorCode {
+        code: "E034",
+        error: "LeverageLimitExceeded",
+    },
+    ErrorCode {
+        code: "E035",
+        error: "RemoteStakeRejected",
+    },
+    ErrorCode {
+        code: "E036",
+        error: "UnknownAccount",
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_codes_are_consistent() {
+        let errors = [
+            ContractError::Std(StdError::generic_err("err")),
+            ContractError::Payment(PaymentError::NoFunds {}),
+            ContractError::ParseReply(ParseReplyError::ParseFailure("err".to_owned())),
+            ContractError::Range(RangeError::Underflow),
+            ContractError::Unauthorized {},
+            ContractError::UnexpectedDenom("osmo".to_owned()),
+            ContractError::Cw20Collateral,
+            ContractError::ClaimsLocked {
+                free: ValueRange::new_val(Uint128::one()),
+                max_lienholder: None,
+                pending_txs: 0,
+            },
+            ContractError::InsufficientBalance,
+            ContractError::UnknownLienholder,
+            ContractError::InsufficientLien,
+            ContractError::ZeroAmount,
+            ContractError::SnapshotNotFound(1),
+            ContractError::LocalStakingNotCompatible(
+                "local".to_owned(),
+                StdError::generic_err("err"),
+            ),
+            ContractError::NotACrossStakingContract {
+                addr: "cross".to_owned(),
+                source: StdError::generic_err("err"),
+            },
+            ContractError::InvalidSlashRatio(Decimal::percent(200)),
+            ContractError::InvalidReplyId(1),
+            ContractError::WrongTypeTx(
+                1,
+                Tx::InFlightRemoteStaking {
+                    id: 1,
+                    amount: Uint128::one(),
+                    user: Addr::unchecked("user"),
+                    validator: "validator".to_owned(),
+                },
+            ),
+            ContractError::WrongContractTx(1, Addr::unchecked("cross")),
+            ContractError::InvalidBatchLength(1),
+            ContractError::MultipleBatchBonds,
+            ContractError::TxTimeoutDisabled,
+            ContractError::TxNotExpired(1, Timestamp::from_seconds(1)),
+            ContractError::FeeTooHigh(Decimal::percent(10)),
+            ContractError::MissingFeeRecipient,
+            ContractError::CollateralCapReached(Uint128::one()),
+            ContractError::IdempotencyKeyTooLong(1),
+            ContractError::WrongLienKind(LienKind::Local),
+            ContractError::Overflow,
+            ContractError::Underflow,
+            ContractError::CollateralTotalUntracked,
+            ContractError::BalanceBelowCollateral(Uint128::zero(), Uint128::one()),
+            ContractError::ReleaseTooSmall(Uint128::one()),
+            ContractError::LeverageLimitExceeded(Decimal::percent(50)),
+            ContractError::RemoteStakeRejected("reason".to_owned()),
+            ContractError::UnknownAccount,
+        ];
+        assert_eq!(errors.len(), ERROR_CODES.len());
+
+        for (i, (err, entry)) in errors.iter().zip(ERROR_CODES).enumerate() {
+            assert_eq!(entry.code, format!("E{:03}", i + 1));
+            assert_eq!(err.code(), entry.code);
+            assert!(format!("{err:?}").starts_with(entry.error), "{err:?}");
+            assert!(
+                err.to_string()
+                    .starts_with(&format!("[mesh-vault:{}] ", entry.code)),
+                "{err}"
+            );
+        }
+    }
 }
@@ -1,7 +1,12 @@
 pub mod contract;
 pub mod error;
+mod migration;
 pub mod msg;
 #[cfg(test)]
 mod multitest;
-mod state;
+#[cfg(feature = "profiling")]
+pub mod profiling;
+pub mod snapshots;
+pub mod state;
 pub mod txs;
+pub mod users;
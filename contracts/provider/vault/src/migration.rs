@@ -0,0 +1,419 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Addr, Decimal, Order, StdResult, Storage, Uint128};
+use cw_storage_plus::Item;
+
+use crate::contract::VaultContract;
+use crate::state::{CollateralType, Config, Lien, LienKind};
+use crate::txs::Txs;
+use crate::users::FreeCollateralTotal;
+
+/// Config before cw20 collateral was supported
+#[cw_serde]
+struct ConfigV1 {
+    denom: String,
+}
+
+const CONFIG_V1: Item<ConfigV1> = Item::new("config");
+
+/// Moves the native denom config to the collateral type layout. Does nothing if the config is
+/// already migrated.
+pub(crate) fn migrate_collateral(
+    storage: &mut dyn Storage,
+    contract: &VaultContract,
+) -> StdResult<()> {
+    if contract.config.load(storage).is_ok() {
+        return Ok(());
+    }
+
+    let ConfigV1 { denom } = CONFIG_V1.load(storage)?;
+    let config = Config {
+        collateral: CollateralType::Native(denom),
+        tx_timeout: None,
+        bond_fee: Decimal::zero(),
+        unbond_fee: Decimal::zero(),
+        fee_recipient: None,
+        max_total_collateral: None,
+        min_release: None,
+        check_remote_stake: false,
+    };
+    contract.config.save(storage, &config)
+}
+
+/// Sets the kind of the local staking liens. Liens stored before the kind was tracked are read as
+/// cross ones, which is right for all other lienholders.
+pub(crate) fn migrate_lien_kinds(
+    storage: &mut dyn Storage,
+    contract: &VaultContract,
+) -> StdResult<()> {
+    let local_staking = match contract.local_staking.may_load(storage)? {
+        Some(local_staking) => local_staking.contract.0,
+        None => return Ok(()),
+    };
+
+    let local_liens = contract
+        .liens
+        .range(storage, None, None, Order::Ascending)
+        .filter(|item| {
+            item.as_ref().map_or(true, |((_, lienholder), lien)| {
+                *lienholder == local_staking && lien.kind != LienKind::Local
+            })
+        })
+        .collect::<StdResult<Vec<((Addr, Addr), Lien)>>>()?;
+
+    for ((owner, lienholder), mut lien) in local_liens {
+        lien.kind = LienKind::Local;
+        contract.liens.save(storage, (&owner, &lienholder), &lien)?;
+    }
+
+    Ok(())
+}
+
+/// Moves the users index of the pending txs out of the users namespace, where its entries were
+/// ranged over as users. Safe to call again once moved.
+pub(crate) fn move_pending_txs_index(
+    storage: &mut dyn Storage,
+    contract: &VaultContract,
+) -> StdResult<()> {
+    let old = Txs::new("pending_txs", "users", "pending_txs__lienholders");
+    let txs = old
+        .txs
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for (id, tx) in txs {
+        old.txs.remove(storage, id)?;
+        contract.pending.txs.save(storage, id, &tx)?;
+    }
+    Ok(())
+}
+
+/// Keys the pending txs by their lienholder, which is required to resolve them. Txs already
+/// keyed are saved again unchanged.
+pub(crate) fn index_pending_txs_lienholders(
+    storage: &mut dyn Storage,
+    contract: &VaultContract,
+) -> StdResult<()> {
+    let txs = contract
+        .pending
+        .txs
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for (_, tx) in txs {
+        contract.pending.save(storage, &tx)?;
+    }
+    Ok(())
+}
+
+/// Builds the collateral index of the users, by saving them again. Users already indexed are
+/// left unchanged.
+///
+/// The free collateral total is recomputed first, as saving a user takes its previous free
+/// collateral out of the total.
+pub(crate) fn index_users_collateral(
+    storage: &mut dyn Storage,
+    contract: &VaultContract,
+) -> StdResult<()> {
+    let users = contract
+        .users
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    let free_collateral = users
+        .iter()
+        .map(|(_, info)| FreeCollateralTotal::free_collateral(info))
+        .sum();
+    contract
+        .users
+        .idx
+        .free_collateral
+        .save(storage, free_collateral)?;
+    for (user, info) in users {
+        contract.users.save(storage, &user, &info)?;
+    }
+    Ok(())
+}
+
+/// Recomputes the total collateral from the users, as users bonded before it was tracked are
+/// missing from it, and marks it as tracked.
+pub(crate) fn init_total_collateral(
+    storage: &mut dyn Storage,
+    contract: &VaultContract,
+) -> StdResult<()> {
+    let mut total = Uint128::zero();
+    for item in contract.users.range(storage, None, None, Order::Ascending) {
+        let (_, info) = item?;
+        total += info.collateral;
+    }
+    contract.total_collateral.save(storage, &total)?;
+    contract.total_collateral_tracked.save(storage, &true)
+}
+
+/// Initializes the tokens outstanding on the local staking contract, from the local liens. Does
+/// nothing if they are already tracked.
+pub(crate) fn init_local_outstanding(
+    storage: &mut dyn Storage,
+    contract: &VaultContract,
+) -> StdResult<()> {
+    if contract.local_outstanding.may_load(storage)?.is_some() {
+        return Ok(());
+    }
+
+    let mut outstanding = Uint128::zero();
+    for item in contract.liens.range(storage, None, None, Order::Ascending) {
+        let (_, lien) = item?;
+        if lien.kind == LienKind::Local {
+            outstanding += lien.amount.low();
+        }
+    }
+    contract.local_outstanding.save(storage, &outstanding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cosmwasm_std::testing::MockStorage;
+    use cosmwasm_std::Timestamp;
+    use cw_storage_plus::Map;
+    use mesh_apis::local_staking_api::LocalStakingApiHelper;
+    use mesh_sync::{Tx, ValueRange};
+
+    use crate::state::{LocalStaking, UserInfo};
+
+    /// Lien before its kind was tracked
+    #[cw_serde]
+    struct LienV1 {
+        amount: ValueRange<Uint128>,
+        slashable: Decimal,
+    }
+
+    #[test]
+    fn native_denom_config_is_migrated() {
+        let mut storage = MockStorage::new();
+        let contract = VaultContract::new();
+
+        CONFIG_V1
+            .save(
+                &mut storage,
+                &ConfigV1 {
+                    denom: "osmo".to_owned(),
+                },
+            )
+            .unwrap();
+
+        migrate_collateral(&mut storage, &contract).unwrap();
+
+        let config = contract.config.load(&storage).unwrap();
+        assert_eq!(config.collateral, CollateralType::Native("osmo".to_owned()));
+
+        // Migrating again is a no-op
+        migrate_collateral(&mut storage, &contract).unwrap();
+        assert_eq!(contract.config.load(&storage).unwrap(), config);
+    }
+
+    #[test]
+    fn local_liens_kind_is_migrated() {
+        let mut storage = MockStorage::new();
+        let contract = VaultContract::new();
+        let user = Addr::unchecked("user");
+        let local = Addr::unchecked("local_staking");
+        let cross = Addr::unchecked("cross_staking");
+
+        contract
+            .local_staking
+            .save(
+                &mut storage,
+                &LocalStaking {
+                    contract: LocalStakingApiHelper(local.clone()),
+                    max_slash: Decimal::percent(10),
+                    checksum: None,
+                },
+            )
+            .unwrap();
+        // Liens stored without a kind
+        let liens_v1: Map<(&Addr, &Addr), LienV1> = Map::new("liens");
+        for lienholder in [&local, &cross] {
+            let lien = LienV1 {
+                amount: ValueRange::new_val(Uint128::new(100)),
+                slashable: Decimal::percent(10),
+            };
+            liens_v1
+                .save(&mut storage, (&user, lienholder), &lien)
+                .unwrap();
+        }
+
+        migrate_lien_kinds(&mut storage, &contract).unwrap();
+
+        let lien = contract.liens.load(&storage, (&user, &local)).unwrap();
+        assert_eq!(lien.kind, LienKind::Local);
+        let lien = contract.liens.load(&storage, (&user, &cross)).unwrap();
+        assert_eq!(lien.kind, LienKind::Cross);
+    }
+
+    #[test]
+    fn pending_txs_index_is_moved() {
+        let mut storage = MockStorage::new();
+        let contract = VaultContract::new();
+
+        let user = Addr::unchecked("alice");
+        contract
+            .users
+            .save(&mut storage, &user, &UserInfo::default())
+            .unwrap();
+        let tx = Tx::InFlightStaking {
+            id: 1,
+            amount: Uint128::new(100),
+            slashable: Decimal::percent(10),
+            user: user.clone(),
+            lienholder: Addr::unchecked("cross"),
+            created_at: Timestamp::from_seconds(0),
+        };
+        let old = Txs::new("pending_txs", "users", "pending_txs__lienholders");
+        old.txs.save(&mut storage, 1, &tx).unwrap();
+        // The old index entries break ranging over the users
+        contract
+            .users
+            .range(&storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()
+            .unwrap_err();
+
+        move_pending_txs_index(&mut storage, &contract).unwrap();
+        move_pending_txs_index(&mut storage, &contract).unwrap();
+
+        let users: Vec<_> = contract
+            .users
+            .keys(&storage, None, None, Order::Ascending)
+            .collect::<StdResult<_>>()
+            .unwrap();
+        assert_eq!(users, std::slice::from_ref(&user));
+        assert_eq!(contract.pending.txs_by_user(&storage, &user).unwrap(), [tx]);
+    }
+
+    #[test]
+    fn pending_txs_are_keyed_by_lienholder() {
+        let mut storage = MockStorage::new();
+        let contract = VaultContract::new();
+
+        let cross = Addr::unchecked("cross");
+        let tx = Tx::InFlightStaking {
+            id: 1,
+            amount: Uint128::new(100),
+            slashable: Decimal::percent(10),
+            user: Addr::unchecked("alice"),
+            lienholder: cross.clone(),
+            created_at: Timestamp::from_seconds(0),
+        };
+        // Saved before the txs were keyed by lienholder
+        contract.pending.txs.save(&mut storage, 1, &tx).unwrap();
+        assert!(!contract.pending.is_lienholder(&storage, 1, &cross));
+
+        index_pending_txs_lienholders(&mut storage, &contract).unwrap();
+        index_pending_txs_lienholders(&mut storage, &contract).unwrap();
+
+        assert!(contract.pending.is_lienholder(&storage, 1, &cross));
+        assert!(!contract
+            .pending
+            .is_lienholder(&storage, 1, &Addr::unchecked("other")));
+        assert_eq!(contract.pending.txs.load(&storage, 1).unwrap(), tx);
+    }
+
+    #[test]
+    fn users_collateral_is_indexed() {
+        let mut storage = MockStorage::new();
+        let contract = VaultContract::new();
+
+        // Users stored before the index
+        let users_v1: Map<&Addr, UserInfo> = Map::new("users");
+        for (user, collateral) in [("alice", 200), ("bob", 300)] {
+            let info = UserInfo {
+                collateral: Uint128::new(collateral),
+                ..Default::default()
+            };
+            users_v1
+                .save(&mut storage, &Addr::unchecked(user), &info)
+                .unwrap();
+        }
+
+        index_users_collateral(&mut storage, &contract).unwrap();
+        // Indexing again doesn't duplicate the entries
+        index_users_collateral(&mut storage, &contract).unwrap();
+
+        let indexed: Vec<_> = contract
+            .users
+            .idx
+            .collateral
+            .keys(&storage, None, None, Order::Descending)
+            .collect::<StdResult<_>>()
+            .unwrap();
+        assert_eq!(indexed, [Addr::unchecked("bob"), Addr::unchecked("alice")]);
+
+        let free = contract.users.idx.free_collateral.load(&storage).unwrap();
+        assert_eq!(free, ValueRange::new_val(Uint128::new(500)));
+    }
+
+    #[test]
+    fn total_collateral_is_initialized() {
+        let mut storage = MockStorage::new();
+        let contract = VaultContract::new();
+
+        for (user, collateral) in [("alice", 200), ("bob", 300)] {
+            let info = UserInfo {
+                collateral: Uint128::new(collateral),
+                ..Default::default()
+            };
+            contract
+                .users
+                .save(&mut storage, &Addr::unchecked(user), &info)
+                .unwrap();
+        }
+        // Only later bonds were added to the total
+        contract
+            .total_collateral
+            .save(&mut storage, &Uint128::new(50))
+            .unwrap();
+
+        init_total_collateral(&mut storage, &contract).unwrap();
+        assert_eq!(
+            contract.total_collateral.load(&storage).unwrap(),
+            Uint128::new(500)
+        );
+        assert!(contract.total_collateral_tracked.load(&storage).unwrap());
+    }
+
+    #[test]
+    fn local_outstanding_is_initialized() {
+        let mut storage = MockStorage::new();
+        let contract = VaultContract::new();
+        let user = Addr::unchecked("user");
+
+        for (lienholder, kind) in [
+            ("local_staking", LienKind::Local),
+            ("cross", LienKind::Cross),
+        ] {
+            let lien = Lien {
+                amount: ValueRange::new_val(Uint128::new(100)),
+                slashable: Decimal::percent(10),
+                kind,
+            };
+            contract
+                .liens
+                .save(&mut storage, (&user, &Addr::unchecked(lienholder)), &lien)
+                .unwrap();
+        }
+
+        init_local_outstanding(&mut storage, &contract).unwrap();
+        assert_eq!(
+            contract.local_outstanding.load(&storage).unwrap(),
+            Uint128::new(100)
+        );
+
+        // Already tracked amounts are kept
+        contract
+            .local_outstanding
+            .save(&mut storage, &Uint128::new(70))
+            .unwrap();
+        init_local_outstanding(&mut storage, &contract).unwrap();
+        assert_eq!(
+            contract.local_outstanding.load(&storage).unwrap(),
+            Uint128::new(70)
+        );
+    }
+}
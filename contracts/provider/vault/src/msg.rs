@@ -1,6 +1,8 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Binary, Uint128};
-use mesh_sync::{Tx, ValueRange};
+use cosmwasm_std::{Binary, Coin, Decimal, HexBinary, Timestamp, Uint128};
+use mesh_sync::{Tx, TxResult, TxStatus, ValueRange};
+
+use crate::state::CollateralType;
 
 /// This is the info used to construct the native staking contract
 #[cw_serde]
@@ -31,6 +33,10 @@ pub struct AccountDetailsResponse {
     pub free: ValueRange<Uint128>,
     pub max_lien: ValueRange<Uint128>,
     pub total_slashable: ValueRange<Uint128>,
+    /// Collateral reserved with `set_reserve`, included in `free`
+    pub reserved: Uint128,
+    /// Leverage limit set with `set_max_leverage`
+    pub max_leverage: Option<Decimal>,
 }
 
 impl AccountResponse {
@@ -43,6 +49,16 @@ impl AccountResponse {
     }
 }
 
+/// Ordering of the `all_accounts` query
+#[cw_serde]
+#[derive(Default)]
+pub enum AccountsOrderBy {
+    #[default]
+    Address,
+    /// By decreasing collateral, then decreasing address
+    BondedDesc,
+}
+
 #[cw_serde]
 pub struct AllAccountsResponse {
     pub accounts: Vec<AllAccountsResponseItem>,
@@ -54,6 +70,46 @@ pub struct AllAccountsResponseItem {
     pub account: AccountResponse,
 }
 
+/// Account accounting checked against its liens
+#[cw_serde]
+pub struct InvariantsReport {
+    pub collateral: Uint128,
+    pub max_lien: ValueRange<Uint128>,
+    /// Max lien recalculated from the account liens
+    pub liens_max: ValueRange<Uint128>,
+    pub total_slashable: ValueRange<Uint128>,
+    /// Total slashable recalculated from the account liens. It may slightly differ from
+    /// `total_slashable`, which accumulates the rounding of every stake and unstake
+    pub liens_slashable: ValueRange<Uint128>,
+    /// Names of the broken invariants, empty if the account is consistent
+    pub violations: Vec<String>,
+}
+
+#[cw_serde]
+pub struct InvariantViolation {
+    /// Account breaking the invariant, none for the vault wide invariants
+    pub account: Option<String>,
+    pub invariant: String,
+}
+
+#[cw_serde]
+pub struct InvariantsResponse {
+    /// Broken invariants among the checked ones, empty if they all hold
+    pub violations: Vec<InvariantViolation>,
+    /// Last checked account, to be passed as `start_after` for the next page. None once all the
+    /// accounts are checked
+    pub cursor: Option<String>,
+}
+
+#[cw_serde]
+pub struct AccountsByFreeCollateralResponse {
+    /// Accounts with low free collateral, among the scanned ones
+    pub accounts: Vec<AllAccountsResponseItem>,
+    /// Last scanned account, to be passed as `start_after` for the next page. None once all the
+    /// accounts are scanned
+    pub cursor: Option<String>,
+}
+
 #[cw_serde]
 pub struct AccountClaimsResponse {
     pub claims: Vec<LienResponse>,
@@ -65,16 +121,169 @@ pub struct LienResponse {
     pub amount: ValueRange<Uint128>,
 }
 
+#[cw_serde]
+pub struct AllLiensResponse {
+    pub liens: Vec<AllLiensResponseItem>,
+}
+
+#[cw_serde]
+pub struct AllLiensResponseItem {
+    pub user: String,
+    pub lienholder: String,
+    pub amount: ValueRange<Uint128>,
+    pub slashable: Decimal,
+}
+
 #[cw_serde]
 pub struct ConfigResponse {
+    /// Collateral denom. For cw20 collateral, it's the token contract address
     pub denom: String,
+    pub collateral: CollateralType,
     pub local_staking: String,
+    /// Max slashing on local staking, as reported by the local staking contract at instantiation
+    pub local_staking_max_slash: Decimal,
+    /// Checksum of the local staking code, if known
+    pub local_staking_checksum: Option<HexBinary>,
+    /// Seconds after which a pending tx can be expired, if any
+    pub tx_timeout: Option<u64>,
+    pub bond_fee: Decimal,
+    pub unbond_fee: Decimal,
+    pub fee_recipient: Option<String>,
+    /// Max total collateral the vault accepts, if any
+    pub max_total_collateral: Option<Uint128>,
+    /// Collateral tokens sent to the local staking contract, less the ones sent back or slashed
+    pub local_outstanding: Uint128,
+    /// Min amount of a cross stake release, but for the final release of a lien
+    pub min_release: Option<Uint128>,
+    /// Whether remote stakes are checked with the cross staking contract before being reserved
+    #[serde(default)]
+    pub check_remote_stake: bool,
+}
+
+/// Fees collected since they were tracked, sent to the fee recipients of the time
+#[cw_serde]
+pub struct FeeStatsResponse {
+    /// Collected on bond
+    pub bond_fees: Uint128,
+    /// Collected on unbond
+    pub unbond_fees: Uint128,
+}
+
+/// Operation of a `batch` call
+#[cw_serde]
+pub enum VaultOp {
+    /// Bonds the funds attached to the batch
+    Bond {},
+    StakeLocal {
+        amount: Coin,
+        msg: Binary,
+    },
+    StakeRemote {
+        /// Contract to virtually stake on
+        contract: String,
+        amount: Coin,
+        msg: Binary,
+    },
+    Unbond {
+        amount: Coin,
+    },
+}
+
+#[cw_serde]
+pub struct EmergencyUnstakeResponse {
+    /// Next user to be processed by another call, if any
+    pub cursor: Option<String>,
 }
 
-pub type TxResponse = Tx;
-pub type AllTxsResponseItem = TxResponse;
+pub type TxResponse = TxStatus;
+pub type AllTxsResponseItem = Tx;
 
 #[cw_serde]
 pub struct AllTxsResponse {
     pub txs: Vec<AllTxsResponseItem>,
 }
+
+#[cw_serde]
+pub struct TxsHistoryResponse {
+    pub txs: Vec<TxResult>,
+}
+
+#[cw_serde]
+pub struct SnapshotAccountResponse {
+    pub snapshot_id: u64,
+    pub denom: String,
+    pub bonded: Uint128,
+}
+
+#[cw_serde]
+pub struct SnapshotAccountsResponse {
+    pub snapshot_id: u64,
+    pub height: u64,
+    pub time: Timestamp,
+    pub denom: String,
+    pub total_collateral: Uint128,
+    pub accounts: Vec<SnapshotAccountsResponseItem>,
+}
+
+#[cw_serde]
+pub struct SnapshotAccountsResponseItem {
+    pub user: String,
+    pub bonded: Uint128,
+}
+
+/// Breakdown of the user's collateral by its voting power on the native chain
+#[cw_serde]
+pub struct VotingPowerReportResponse {
+    pub denom: String,
+    /// Collateral idle in the vault, not backing any stake. It has no voting power
+    pub unstaked: Uint128,
+    /// Tokens delegated through the user's native staking proxy, which votes with them
+    pub local_staked: Uint128,
+    /// Collateral backing remote stakes (the highest remote lien), sitting idle in the vault.
+    /// It has no voting power
+    pub remote_staked: Uint128,
+    /// The user's native staking proxy, if any
+    pub proxy: Option<String>,
+}
+
+/// Native staking query for the proxy of an user. The vault doesn't depend on the native staking
+/// contract, so this mirrors its `QueryMsg`
+#[cw_serde]
+pub enum NativeStakingQueryMsg {
+    ProxyByOwner {
+        owner: String,
+    },
+    OwnersByValidator {
+        validator: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+}
+
+#[cw_serde]
+pub struct ProxyByOwnerResponse {
+    pub proxy: String,
+}
+
+#[cw_serde]
+pub struct OwnersByValidatorResponse {
+    pub owners: Vec<OwnerDelegation>,
+}
+
+#[cw_serde]
+pub struct OwnerDelegation {
+    pub owner: String,
+    pub amount: Uint128,
+}
+
+/// Messages sent by the chain
+#[cw_serde]
+pub enum SudoMsg {
+    /// A local validator is about to be slashed by `slash_ratio`, for an infraction committed at
+    /// `infraction_height`
+    LocalSlash {
+        validator: String,
+        slash_ratio: Decimal,
+        infraction_height: u64,
+    },
+}
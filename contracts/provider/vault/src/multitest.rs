@@ -1,21 +1,37 @@
-use cosmwasm_std::{coin, coins, to_binary, Addr, Decimal, Uint128, Validator};
-use cw_multi_test::{App as MtApp, StakingInfo};
+mod cross_staking_mock;
+mod cw20_mock;
+mod local_staking_mock;
+
+use cosmwasm_std::{
+    coin, coins, from_binary, to_binary, Addr, Coin, Decimal, StdError, Timestamp, Uint128,
+    Validator,
+};
+use cw_multi_test::{App as MtApp, Executor, StakingInfo};
 use mesh_apis::ibc::AddValidator;
+use mesh_apis::local_staking_api::{LocalStakingApiQueryMsg, MaxSlashResponse};
+use mesh_apis::vault_api::VaultCw20HookMsg;
 use mesh_external_staking::contract::multitest_utils::ExternalStakingContractProxy;
+use mesh_external_staking::error::ContractError as ExternalStakingError;
 use mesh_external_staking::msg::{AuthorizedEndpoint, ReceiveVirtualStake, StakeInfo};
-use mesh_external_staking::state::Stake;
+use mesh_external_staking::state::{RewardDenom, Stake};
 use mesh_external_staking::test_methods_impl::test_utils::TestMethods;
 use mesh_native_staking::contract::multitest_utils::NativeStakingContractProxy;
 use mesh_native_staking_proxy::contract::multitest_utils::NativeStakingProxyContractProxy;
 use mesh_sync::Tx::InFlightStaking;
-use mesh_sync::{Tx, ValueRange};
+use mesh_sync::{Tx, TxResult, TxStatus, ValueRange};
 use sylvia::multitest::App;
 
 use crate::contract;
 use crate::contract::multitest_utils::VaultContractProxy;
 use crate::contract::test_utils::VaultApi;
 use crate::error::ContractError;
-use crate::msg::{AccountResponse, AllAccountsResponseItem, LienResponse, StakingInitInfo};
+use crate::msg::{
+    AccountResponse, AccountsOrderBy, AllAccountsResponseItem, EmergencyUnstakeResponse,
+    LienResponse, SnapshotAccountsResponseItem, StakingInitInfo, VaultOp,
+    VotingPowerReportResponse,
+};
+use crate::snapshots::MAX_LIVE_SNAPSHOTS;
+use crate::state::{CollateralType, LienKind};
 
 const OSMO: &str = "OSMO";
 const STAR: &str = "star";
@@ -23,7 +39,7 @@ const STAR: &str = "star";
 /// 10% slashing on the remote chain
 const SLASHING_PERCENTAGE: u64 = 10;
 
-/// Test utils
+// Test utils
 
 /// App initialization
 fn init_app(users: &[&str], amounts: &[u128]) -> App<MtApp> {
@@ -83,6 +99,20 @@ fn setup<'app>(
     VaultContractProxy<'app, MtApp>,
     NativeStakingContractProxy<'app, MtApp>,
     ExternalStakingContractProxy<'app, MtApp>,
+) {
+    setup_with_tx_timeout(app, owner, slash_percent, unbond_period, None)
+}
+
+fn setup_with_tx_timeout<'app>(
+    app: &'app App<MtApp>,
+    owner: &str,
+    slash_percent: u64,
+    unbond_period: u64,
+    tx_timeout: Option<u64>,
+) -> (
+    VaultContractProxy<'app, MtApp>,
+    NativeStakingContractProxy<'app, MtApp>,
+    ExternalStakingContractProxy<'app, MtApp>,
 ) {
     let native_staking_code =
         mesh_native_staking::contract::multitest_utils::CodeId::store_code(app);
@@ -94,6 +124,7 @@ fn setup<'app>(
         denom: OSMO.to_string(),
         max_slashing: Decimal::percent(10),
         proxy_code_id: native_staking_proxy_code.code_id(),
+        default_validator: None,
     };
     let staking_init_info = StakingInitInfo {
         admin: None,
@@ -103,8 +134,13 @@ fn setup<'app>(
     };
 
     let vault = vault_code
-        .instantiate(OSMO.to_owned(), staking_init_info)
+        .instantiate(
+            CollateralType::Native(OSMO.to_owned()),
+            staking_init_info,
+            tx_timeout,
+        )
         .with_label("Vault")
+        .with_admin(owner)
         .call(owner)
         .unwrap();
 
@@ -131,7 +167,7 @@ fn setup_cross_stake<'app>(
     cross_staking_code
         .instantiate(
             OSMO.to_owned(),
-            STAR.to_owned(),
+            vec![RewardDenom::from_denom(STAR)],
             vault.contract_addr.to_string(),
             unbond_period,
             remote_contact,
@@ -178,7 +214,7 @@ fn stake_locally(
     validator: &str,
 ) -> Result<cw_multi_test::AppResponse, ContractError> {
     let msg = mesh_native_staking::msg::StakeMsg {
-        validator: validator.to_string(),
+        validator: Some(validator.to_string()),
     };
 
     vault
@@ -202,6 +238,7 @@ fn stake_remotely(
                     validator: validator.to_string(),
                 })
                 .unwrap(),
+                None,
             )
             .call(user)
             .unwrap();
@@ -242,6 +279,19 @@ fn process_staking_unbondings(app: &App<MtApp>) {
 }
 
 #[track_caller]
+/// Asserts the invariants of the vault and all its accounts hold
+fn assert_vault_invariants(vault: &VaultContractProxy<MtApp>) {
+    let mut start_after = None;
+    loop {
+        let resp = vault.invariants(None, start_after, None).unwrap();
+        assert_eq!(resp.violations, []);
+        start_after = match resp.cursor {
+            Some(cursor) => Some(cursor),
+            None => break,
+        };
+    }
+}
+
 fn get_last_vault_pending_tx_id(contract: &VaultContractProxy<MtApp>) -> Option<u64> {
     let txs = contract.all_pending_txs_desc(None, None).unwrap().txs;
     txs.first().map(Tx::id)
@@ -272,11 +322,55 @@ fn instantiation() {
 
     let config = vault.config().unwrap();
     assert_eq!(config.denom, OSMO);
+    assert_eq!(vault.denom().unwrap(), OSMO);
 
-    let users = vault.all_accounts(false, None, None).unwrap();
+    // Max slash is the one reported by the local staking contract
+    let max_slash: MaxSlashResponse = app
+        .app()
+        .wrap()
+        .query_wasm_smart(&config.local_staking, &LocalStakingApiQueryMsg::MaxSlash {})
+        .unwrap();
+    assert_eq!(config.local_staking_max_slash, max_slash.max_slash);
+    assert_eq!(config.local_staking_max_slash, Decimal::percent(10));
+
+    let users = vault.all_accounts(false, None, None, None, None).unwrap();
     assert_eq!(users.accounts, []);
 }
 
+#[test]
+fn instantiation_with_incompatible_local_staking() {
+    let owner = "owner";
+
+    let app = init_app(&[], &[]);
+
+    // Not implementing the local staking API
+    let cw20_code = cw20_mock::multitest_utils::CodeId::store_code(&app);
+    let vault_code = contract::multitest_utils::CodeId::store_code(&app);
+
+    let staking_init_info = StakingInitInfo {
+        admin: None,
+        code_id: cw20_code.code_id(),
+        msg: to_binary(&cw20_mock::InstantiateMsg {
+            initial_balances: vec![],
+        })
+        .unwrap(),
+        label: None,
+    };
+    let err = vault_code
+        .instantiate(
+            CollateralType::Native(OSMO.to_owned()),
+            staking_init_info,
+            None,
+        )
+        .with_label("Vault")
+        .call(owner)
+        .unwrap_err();
+    assert!(
+        matches!(err, ContractError::LocalStakingNotCompatible(..)),
+        "{err:?}"
+    );
+}
+
 #[test]
 fn bonding() {
     let owner = "owner";
@@ -398,273 +492,675 @@ fn bonding() {
     let err = vault.unbond(coin(100, OSMO)).call(user).unwrap_err();
     assert_eq!(
         err,
-        ContractError::ClaimsLocked(ValueRange::new_val(Uint128::new(30)))
+        ContractError::ClaimsLocked {
+            free: ValueRange::new_val(Uint128::new(30)),
+            max_lienholder: None,
+            pending_txs: 0
+        }
     );
+
+    assert_vault_invariants(&vault);
 }
 
 #[test]
-fn stake_local() {
+fn is_participant() {
     let owner = "owner";
     let user = "user1";
-    let val = "validator";
 
-    let mut app = init_app(&[user], &[300]);
-    add_local_validator(&mut app, val);
+    let app = init_app(&[user], &[300]);
 
-    let (vault, local_staking, _cross_staking1) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+    let (vault, _, cross_staking) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
 
-    bond(&vault, user, 300);
+    let validator = "validator";
+    set_active_validators(&cross_staking, &[validator]);
 
-    assert_eq!(
-        vault.account(user.to_owned()).unwrap(),
-        AccountResponse {
-            denom: OSMO.to_owned(),
-            bonded: Uint128::new(300),
-            free: ValueRange::new_val(Uint128::new(300)),
-        }
-    );
-    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
-    assert_eq!(claims.claims, []);
-    assert_eq!(
-        app.app()
-            .wrap()
-            .query_balance(&vault.contract_addr, OSMO)
-            .unwrap(),
-        coin(300, OSMO)
-    );
-    assert_eq!(
-        app.app()
-            .wrap()
-            .query_balance(&local_staking.contract_addr, OSMO)
-            .unwrap(),
-        coin(0, OSMO)
-    );
+    assert!(!vault.is_participant(user.to_owned()).unwrap());
 
-    // Staking locally
-    stake_locally(&vault, user, 100, val).unwrap();
+    bond(&vault, user, 100);
+    assert!(vault.is_participant(user.to_owned()).unwrap());
+
+    vault.unbond(coin(60, OSMO)).call(user).unwrap();
+    assert!(vault.is_participant(user.to_owned()).unwrap());
+
+    // Fully unbonded
+    vault.unbond(coin(40, OSMO)).call(user).unwrap();
+    assert!(!vault.is_participant(user.to_owned()).unwrap());
+
+    // Staked, then released and unbonded. The released lien is kept, but with no amount
+    bond(&vault, user, 100);
+    stake_remotely(&vault, &cross_staking, user, &[validator], &[100]);
+    vault
+        .vault_api_proxy()
+        .release_cross_stake(user.to_owned(), coin(100, OSMO))
+        .call(cross_staking.contract_addr.as_str())
+        .unwrap();
+    assert!(vault.is_participant(user.to_owned()).unwrap());
+
+    vault.unbond(coin(100, OSMO)).call(user).unwrap();
+    assert!(!vault.is_participant(user.to_owned()).unwrap());
+}
+
+#[test]
+fn slashable_collateral() {
+    let owner = "owner";
+    let user = "user1";
+    let local_validator = "local";
+    let validators = ["validator1", "validator2"];
+
+    let mut app = init_app(&[user], &[1000]);
+    add_local_validator(&mut app, local_validator);
+
+    let (vault, _, cross_staking) = setup(&app, owner, 10, 100);
+    set_active_validators(&cross_staking, &validators);
 
     assert_eq!(
-        vault.account(user.to_owned()).unwrap(),
-        AccountResponse {
-            denom: OSMO.to_owned(),
-            bonded: Uint128::new(300),
-            free: ValueRange::new_val(Uint128::new(200)),
-        }
-    );
-    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
-    assert_eq!(
-        claims.claims,
-        [LienResponse {
-            lienholder: local_staking.contract_addr.to_string(),
-            amount: ValueRange::new_val(Uint128::new(100))
-        }]
-    );
-    assert_eq!(
-        app.app()
-            .wrap()
-            .query_balance(&vault.contract_addr, OSMO)
-            .unwrap(),
-        coin(200, OSMO)
+        vault.slashable_collateral(user.to_owned()).unwrap(),
+        ValueRange::new_val(Uint128::zero())
     );
 
-    stake_locally(&vault, user, 150, val).unwrap();
+    bond(&vault, user, 200);
+    stake_locally(&vault, user, 190, local_validator).unwrap();
+    stake_remotely(&vault, &cross_staking, user, &validators, &[100, 50]);
 
+    // 10% of 190 locally, and 10% of 150 remotely
+    let slashable = vault.slashable_collateral(user.to_owned()).unwrap();
+    assert_eq!(slashable, ValueRange::new_val(Uint128::new(34)));
     assert_eq!(
-        vault.account(user.to_owned()).unwrap(),
-        AccountResponse {
-            denom: OSMO.to_owned(),
-            bonded: Uint128::new(300),
-            free: ValueRange::new_val(Uint128::new(50)),
-        }
+        slashable,
+        vault
+            .account_details(user.to_owned())
+            .unwrap()
+            .total_slashable
     );
-    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
+
+    // A pending remote stake widens the range
+    vault
+        .stake_remote(
+            cross_staking.contract_addr.to_string(),
+            coin(40, OSMO),
+            to_binary(&ReceiveVirtualStake {
+                validator: validators[0].to_string(),
+            })
+            .unwrap(),
+            None,
+        )
+        .call(user)
+        .unwrap();
+    let slashable = vault.slashable_collateral(user.to_owned()).unwrap();
     assert_eq!(
-        claims.claims,
-        [LienResponse {
-            lienholder: local_staking.contract_addr.to_string(),
-            amount: ValueRange::new_val(Uint128::new(250))
-        }]
+        slashable,
+        ValueRange::new(Uint128::new(34), Uint128::new(38))
     );
     assert_eq!(
-        app.app()
-            .wrap()
-            .query_balance(&vault.contract_addr, OSMO)
-            .unwrap(),
-        coin(50, OSMO)
+        slashable,
+        vault
+            .account_details(user.to_owned())
+            .unwrap()
+            .total_slashable
     );
+}
 
-    // Cannot stake over collateral
-
-    let err = stake_locally(&vault, user, 150, val).unwrap_err();
+#[test]
+fn stake_local_on() {
+    let owner = "owner";
+    let user = "user1";
+    let validator = "validator";
 
-    assert_eq!(err, ContractError::InsufficentBalance);
+    let mut app = init_app(&[user], &[300]);
+    add_local_validator(&mut app, validator);
 
-    // Cannot unbond used collateral
+    let (vault, local_staking, _) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
 
-    let err = vault.unbond(coin(100, OSMO)).call(user).unwrap_err();
-    assert_eq!(
-        err,
-        ContractError::ClaimsLocked(ValueRange::new_val(Uint128::new(50)))
-    );
+    bond(&vault, user, 300);
 
-    // Unstaking
+    vault
+        .stake_local_on(validator.to_owned(), coin(100, OSMO))
+        .call(user)
+        .unwrap();
 
     let proxy = proxy_for_user(&local_staking, user, &app);
-    proxy
-        .unstake(val.to_string(), coin(50, OSMO))
-        .call(user)
+    let delegation = app
+        .app()
+        .wrap()
+        .query_delegation(proxy.contract_addr, validator)
+        .unwrap()
         .unwrap();
-    process_staking_unbondings(&app);
-    proxy.release_unbonded().call(user).unwrap();
+    assert_eq!(delegation.amount, coin(100, OSMO));
 
-    assert_eq!(
-        vault.account(user.to_owned()).unwrap(),
-        AccountResponse {
-            denom: OSMO.to_owned(),
-            bonded: Uint128::new(300),
-            free: ValueRange::new_val(Uint128::new(100)),
-        }
-    );
     let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
     assert_eq!(
         claims.claims,
         [LienResponse {
             lienholder: local_staking.contract_addr.to_string(),
-            amount: ValueRange::new_val(Uint128::new(200))
+            amount: ValueRange::new_val(Uint128::new(100))
         }]
     );
-    assert_eq!(
-        app.app()
-            .wrap()
-            .query_balance(&vault.contract_addr, OSMO)
-            .unwrap(),
-        coin(100, OSMO)
-    );
+}
 
-    proxy
-        .unstake(val.to_string(), coin(100, OSMO))
+#[test]
+fn max_leverage() {
+    let owner = "owner";
+    let user = "user1";
+    let validator = "validator";
+
+    let mut app = init_app(&[user], &[300]);
+    add_local_validator(&mut app, validator);
+
+    let (vault, _, cross_staking) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+    set_active_validators(&cross_staking, &["remote"]);
+
+    // Accounts without a position can't set it
+    let err = vault
+        .set_max_leverage(Some(Decimal::percent(50)))
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::UnknownAccount);
+    let accounts = vault.all_accounts(false, None, None, None, None).unwrap();
+    assert_eq!(accounts.accounts, []);
+
+    bond(&vault, user, 200);
+    vault
+        .set_max_leverage(Some(Decimal::percent(50)))
         .call(user)
         .unwrap();
-    process_staking_unbondings(&app);
-    proxy.release_unbonded().call(user).unwrap();
+    let details = vault.account_details(user.to_owned()).unwrap();
+    assert_eq!(details.max_leverage, Some(Decimal::percent(50)));
 
+    // Within the limit
+    stake_locally(&vault, user, 100, validator).unwrap();
+    stake_remotely(&vault, &cross_staking, user, &["remote"], &[100]);
+
+    // Beyond it, locally or remotely
+    let err = stake_locally(&vault, user, 1, validator).unwrap_err();
     assert_eq!(
-        vault.account(user.to_owned()).unwrap(),
-        AccountResponse {
-            denom: OSMO.to_owned(),
-            bonded: Uint128::new(300),
-            free: ValueRange::new_val(Uint128::new(200)),
-        }
-    );
-    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
-    assert_eq!(
-        claims.claims,
-        [LienResponse {
-            lienholder: local_staking.contract_addr.to_string(),
-            amount: ValueRange::new_val(Uint128::new(100))
-        }]
+        err,
+        ContractError::LeverageLimitExceeded(Decimal::percent(50))
     );
-    assert_eq!(
-        app.app()
-            .wrap()
-            .query_balance(&vault.contract_addr, OSMO)
+    let err = vault
+        .stake_remote(
+            cross_staking.contract_addr.to_string(),
+            coin(1, OSMO),
+            to_binary(&ReceiveVirtualStake {
+                validator: "remote".to_string(),
+            })
             .unwrap(),
-        coin(200, OSMO)
+            None,
+        )
+        .call(user)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::LeverageLimitExceeded(Decimal::percent(50))
     );
 
-    // Cannot unstake over the lien
+    // More collateral makes room for more stake
+    bond(&vault, user, 100);
+    stake_locally(&vault, user, 50, validator).unwrap();
 
-    // TODO: catch subcall error here
-    // let err = proxy
-    //     .unstake(val.to_string(), coin(200, OSMO))
-    //     .call(user)
-    //     .unwrap_err();
-    // assert_eq!(
-    //     err,
-    //     mesh_native_staking_proxy::error::ContractError::Unauthorized {}
-    // );
+    // Removing the limit allows staking up to the collateral
+    vault.set_max_leverage(None).call(user).unwrap();
+    stake_locally(&vault, user, 150, validator).unwrap();
+    let details = vault.account_details(user.to_owned()).unwrap();
+    assert_eq!(details.max_lien, ValueRange::new_val(Uint128::new(300)));
+
+    assert_vault_invariants(&vault);
 }
 
 #[test]
-fn stake_cross() {
+fn reserve() {
     let owner = "owner";
     let user = "user1";
+    let validator = "validator";
 
-    let app = init_app(&[user], &[300]);
-
-    let unbond_period = 100;
-    let (vault, _local_staking, cross_staking) =
-        setup(&app, owner, SLASHING_PERCENTAGE, unbond_period);
+    let mut app = init_app(&[user], &[300]);
+    add_local_validator(&mut app, validator);
 
-    // Set active validator
-    let validator = "validator";
-    set_active_validators(&cross_staking, &[validator]);
+    let (vault, local_staking, _) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
 
-    // Bond some tokens
     bond(&vault, user, 300);
 
+    // The reserve must be covered by the collateral
+    let err = vault.set_reserve(Uint128::new(301)).call(user).unwrap_err();
+    assert_eq!(err, ContractError::InsufficientBalance);
+
+    vault.set_reserve(Uint128::new(100)).call(user).unwrap();
+    let details = vault.account_details(user.to_owned()).unwrap();
+    assert_eq!(details.reserved.u128(), 100);
+    assert_eq!(details.free, ValueRange::new_val(Uint128::new(300)));
+
+    // The reserve can't be staked
+    let err = stake_locally(&vault, user, 201, validator).unwrap_err();
+    assert_eq!(err, ContractError::InsufficientBalance);
+    stake_locally(&vault, user, 150, validator).unwrap();
+
+    // Nor unbonded
+    let err = vault.unbond(coin(51, OSMO)).call(user).unwrap_err();
     assert_eq!(
-        vault.account(user.to_owned()).unwrap(),
-        AccountResponse {
-            denom: OSMO.to_owned(),
-            bonded: Uint128::new(300),
-            free: ValueRange::new_val(Uint128::new(300)),
+        err,
+        ContractError::ClaimsLocked {
+            free: ValueRange::new_val(Uint128::new(50)),
+            max_lienholder: Some(local_staking.contract_addr.clone()),
+            pending_txs: 0
         }
     );
-    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
-    assert_eq!(claims.claims, []);
+
+    // The reserve can't be raised over the free collateral
+    let err = vault.set_reserve(Uint128::new(151)).call(user).unwrap_err();
+    assert_eq!(err, ContractError::InsufficientBalance);
+
+    // Clearing it frees the collateral again
+    vault.set_reserve(Uint128::zero()).call(user).unwrap();
+    vault.unbond(coin(100, OSMO)).call(user).unwrap();
+    stake_locally(&vault, user, 50, validator).unwrap();
+    assert_eq!(
+        vault.account(user.to_owned()).unwrap().free,
+        ValueRange::new_val(Uint128::zero())
+    );
+
+    assert_vault_invariants(&vault);
+}
+
+#[test]
+fn bond_unbond_fees() {
+    let owner = "owner";
+    let user = "user1";
+    let safety = "safety_module";
+
+    let app = init_app(&[user], &[2000]);
+
+    let (vault, _local_staking, _cross_staking) = setup(&app, owner, 0, 100);
+    let balance = |addr: &str| app.app().wrap().query_balance(addr, OSMO).unwrap().amount;
+
+    // No fees by default
+    bond(&vault, user, 100);
+    vault.unbond(coin(40, OSMO)).call(user).unwrap();
+    assert_eq!(vault.account(user.to_owned()).unwrap().bonded.u128(), 60);
+    assert_eq!(balance(user).u128(), 1940);
+
+    // Only the admin can set fees, within the max
+    let err = vault
+        .update_fees(
+            Decimal::percent(1),
+            Decimal::percent(2),
+            Some(safety.to_owned()),
+        )
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    let err = vault
+        .update_fees(
+            Decimal::percent(6),
+            Decimal::zero(),
+            Some(safety.to_owned()),
+        )
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(err, ContractError::FeeTooHigh(Decimal::percent(5)));
+    let err = vault
+        .update_fees(Decimal::percent(1), Decimal::zero(), None)
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(err, ContractError::MissingFeeRecipient);
+
+    vault
+        .update_fees(
+            Decimal::percent(1),
+            Decimal::percent(2),
+            Some(safety.to_owned()),
+        )
+        .call(owner)
+        .unwrap();
+    let config = vault.config().unwrap();
+    assert_eq!(config.bond_fee, Decimal::percent(1));
+    assert_eq!(config.unbond_fee, Decimal::percent(2));
+    assert_eq!(config.fee_recipient.as_deref(), Some(safety));
+
+    // Bonded collateral is net of the fee
+    bond(&vault, user, 1000);
+    assert_eq!(vault.account(user.to_owned()).unwrap().bonded.u128(), 1050);
+    assert_eq!(balance(safety).u128(), 10);
+    assert_eq!(balance(vault.contract_addr.as_str()).u128(), 1050);
+
+    // The whole unbonded amount is debited, the fee is taken from what is sent back
+    vault.unbond(coin(500, OSMO)).call(user).unwrap();
+    assert_eq!(vault.account(user.to_owned()).unwrap().bonded.u128(), 550);
+    assert_eq!(balance(user).u128(), 940 + 490);
+    assert_eq!(balance(safety).u128(), 20);
+    assert_eq!(balance(vault.contract_addr.as_str()).u128(), 550);
+
+    // Fees rounding down to zero aren't sent
+    vault.unbond(coin(49, OSMO)).call(user).unwrap();
+    assert_eq!(balance(user).u128(), 940 + 490 + 49);
+    assert_eq!(balance(safety).u128(), 20);
+
+    // Fees are rounded down in favor of the user, down to 1-unit unbonds
+    vault.unbond(coin(1, OSMO)).call(user).unwrap();
+    assert_eq!(balance(user).u128(), 940 + 490 + 49 + 1);
+    vault.unbond(coin(99, OSMO)).call(user).unwrap();
+    assert_eq!(balance(user).u128(), 940 + 490 + 49 + 1 + 98);
+    assert_eq!(balance(safety).u128(), 21);
+
+    let stats = vault.fee_stats().unwrap();
+    assert_eq!(stats.bond_fees.u128(), 10);
+    assert_eq!(stats.unbond_fees.u128(), 11);
+
+    assert_vault_invariants(&vault);
+}
+
+#[test]
+fn collateral_cap() {
+    let owner = "owner";
+    let users = ["user1", "user2"];
+
+    let app = init_app(&users, &[500, 500]);
+
+    let (vault, _local_staking, _cross_staking) = setup(&app, owner, 0, 100);
+
+    let err = vault
+        .update_max_total_collateral(Some(Uint128::new(500)))
+        .call(users[0])
+        .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    vault
+        .update_max_total_collateral(Some(Uint128::new(500)))
+        .call(owner)
+        .unwrap();
+    assert_eq!(
+        vault.config().unwrap().max_total_collateral,
+        Some(Uint128::new(500))
+    );
+
+    // Bonding up to the cap
+    bond(&vault, users[0], 300);
+    bond(&vault, users[1], 200);
+
+    let err = vault
+        .bond()
+        .with_funds(&coins(1, OSMO))
+        .call(users[1])
+        .unwrap_err();
+    assert_eq!(err, ContractError::CollateralCapReached(Uint128::new(500)));
+
+    // Unbonding makes room
+    vault.unbond(coin(100, OSMO)).call(users[0]).unwrap();
+    bond(&vault, users[1], 100);
+
+    // As raising the cap
+    vault
+        .update_max_total_collateral(Some(Uint128::new(600)))
+        .call(owner)
+        .unwrap();
+    bond(&vault, users[1], 100);
+    let err = vault
+        .bond()
+        .with_funds(&coins(1, OSMO))
+        .call(users[0])
+        .unwrap_err();
+    assert_eq!(err, ContractError::CollateralCapReached(Uint128::new(600)));
+
+    assert_vault_invariants(&vault);
+}
+
+#[test]
+fn sweep_unaccounted() {
+    let owner = "owner";
+    let users = ["user1", "user2"];
+    let recipient = "recipient";
+    let val = "validator";
+
+    let mut app = init_app(&users, &[300, 100]);
+    add_local_validator(&mut app, val);
+
+    let (vault, _local_staking, _cross_staking) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+
+    // Nothing to sweep
+    let err = vault
+        .sweep_unaccounted(recipient.to_owned())
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(err, ContractError::ZeroAmount);
+
+    bond(&vault, users[0], 300);
+    stake_locally(&vault, users[0], 100, val).unwrap();
+
+    // Tokens sent to the vault by mistake
+    app.app_mut()
+        .send_tokens(
+            Addr::unchecked(users[1]),
+            vault.contract_addr.clone(),
+            &coins(40, OSMO),
+        )
+        .unwrap();
+
+    let err = vault
+        .sweep_unaccounted(recipient.to_owned())
+        .call(users[0])
+        .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    vault
+        .sweep_unaccounted(recipient.to_owned())
+        .call(owner)
+        .unwrap();
+    assert_eq!(
+        app.app().wrap().query_balance(recipient, OSMO).unwrap(),
+        coin(40, OSMO)
+    );
     assert_eq!(
         app.app()
             .wrap()
             .query_balance(&vault.contract_addr, OSMO)
             .unwrap(),
-        coin(300, OSMO)
+        coin(200, OSMO)
+    );
+
+    // No user collateral is swept
+    let account = vault.account(users[0].to_owned()).unwrap();
+    assert_eq!(account.bonded, Uint128::new(300));
+    assert_eq!(account.free, ValueRange::new_val(Uint128::new(200)));
+    let err = vault
+        .sweep_unaccounted(recipient.to_owned())
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(err, ContractError::ZeroAmount);
+
+    vault.unbond(coin(200, OSMO)).call(users[0]).unwrap();
+    assert_eq!(
+        app.app().wrap().query_balance(users[0], OSMO).unwrap(),
+        coin(200, OSMO)
+    );
+
+    assert_vault_invariants(&vault);
+}
+
+#[test]
+fn sweep() {
+    let owner = "owner";
+    let users = ["user1", "user2"];
+    let recipient = "recipient";
+    let val = "validator";
+
+    let mut app = init_app(&users, &[300, 100]);
+    add_local_validator(&mut app, val);
+    app.app_mut().init_modules(|router, _api, storage| {
+        router
+            .bank
+            .init_balance(
+                storage,
+                &Addr::unchecked(users[1]),
+                vec![coin(100, OSMO), coin(25, STAR)],
+            )
+            .unwrap();
+    });
+
+    let (vault, _local_staking, _cross_staking) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+
+    bond(&vault, users[0], 300);
+    stake_locally(&vault, users[0], 100, val).unwrap();
+
+    // Nothing stray, the bonded collateral is never swept
+    let err = vault
+        .sweep(OSMO.to_owned(), recipient.to_owned())
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(err, ContractError::ZeroAmount);
+    let err = vault
+        .sweep(STAR.to_owned(), recipient.to_owned())
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(err, ContractError::ZeroAmount);
+
+    // Tokens sent to the vault by mistake
+    app.app_mut()
+        .send_tokens(
+            Addr::unchecked(users[1]),
+            vault.contract_addr.clone(),
+            &[coin(40, OSMO), coin(25, STAR)],
+        )
+        .unwrap();
+
+    let err = vault
+        .sweep(STAR.to_owned(), recipient.to_owned())
+        .call(users[0])
+        .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    vault
+        .sweep(STAR.to_owned(), recipient.to_owned())
+        .call(owner)
+        .unwrap();
+    vault
+        .sweep(OSMO.to_owned(), recipient.to_owned())
+        .call(owner)
+        .unwrap();
+    assert_eq!(
+        app.app().wrap().query_all_balances(recipient).unwrap(),
+        [coin(40, OSMO), coin(25, STAR)]
     );
     assert_eq!(
         app.app()
             .wrap()
-            .query_balance(&cross_staking.contract_addr, OSMO)
+            .query_all_balances(&vault.contract_addr)
             .unwrap(),
-        coin(0, OSMO)
+        [coin(200, OSMO)]
     );
 
-    // Staking remotely
+    // The user collateral is untouched
+    let account = vault.account(users[0].to_owned()).unwrap();
+    assert_eq!(account.bonded, Uint128::new(300));
+    assert_eq!(account.free, ValueRange::new_val(Uint128::new(200)));
+    vault.unbond(coin(200, OSMO)).call(users[0]).unwrap();
 
-    vault
-        .stake_remote(
-            cross_staking.contract_addr.to_string(),
-            coin(100, OSMO),
-            to_binary(&ReceiveVirtualStake {
-                validator: validator.to_string(),
-            })
-            .unwrap(),
-        )
-        .call(user)
+    assert_vault_invariants(&vault);
+}
+
+#[test]
+fn local_outstanding() {
+    let owner = "owner";
+    let users = ["user1", "user2"];
+    let val = "validator";
+
+    let mut app = init_app(&users, &[300, 200]);
+    add_local_validator(&mut app, val);
+
+    let (vault, local_staking, _cross_staking) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+
+    // The vault balance and the outstanding tokens match the users collateral at every step
+    let assert_outstanding = |outstanding: u128| {
+        assert_eq!(
+            vault.config().unwrap().local_outstanding,
+            Uint128::new(outstanding)
+        );
+        let balance = app
+            .app()
+            .wrap()
+            .query_balance(&vault.contract_addr, OSMO)
+            .unwrap()
+            .amount;
+        let collateral: Uint128 = users
+            .iter()
+            .map(|user| vault.account(user.to_string()).unwrap().bonded)
+            .sum();
+        assert_eq!(balance + Uint128::new(outstanding), collateral);
+        assert_vault_invariants(&vault);
+    };
+
+    bond(&vault, users[0], 300);
+    bond(&vault, users[1], 200);
+    assert_outstanding(0);
+
+    stake_locally(&vault, users[0], 100, val).unwrap();
+    assert_outstanding(100);
+    stake_locally(&vault, users[1], 150, val).unwrap();
+    assert_outstanding(250);
+
+    let proxy = proxy_for_user(&local_staking, users[0], &app);
+    proxy
+        .unstake(val.to_string(), coin(60, OSMO))
+        .call(users[0])
         .unwrap();
+    // Unbonding tokens are still outstanding
+    assert_outstanding(250);
+    process_staking_unbondings(&app);
+    proxy.release_unbonded().call(users[0]).unwrap();
+    assert_outstanding(190);
+
+    let proxy = proxy_for_user(&local_staking, users[1], &app);
+    proxy
+        .unstake(val.to_string(), coin(150, OSMO))
+        .call(users[1])
+        .unwrap();
+    process_staking_unbondings(&app);
+    proxy.release_unbonded().call(users[1]).unwrap();
+    assert_outstanding(40);
+
+    vault.unbond(coin(200, OSMO)).call(users[1]).unwrap();
+    assert_outstanding(40);
+}
+
+#[test]
+fn stake_local() {
+    let owner = "owner";
+    let user = "user1";
+    let val = "validator";
+
+    let mut app = init_app(&[user], &[300]);
+    add_local_validator(&mut app, val);
+
+    let (vault, local_staking, _cross_staking1) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+
+    bond(&vault, user, 300);
 
-    let acc = vault.account(user.to_owned()).unwrap();
     assert_eq!(
-        acc,
+        vault.account(user.to_owned()).unwrap(),
         AccountResponse {
             denom: OSMO.to_owned(),
             bonded: Uint128::new(300),
-            free: ValueRange::new(Uint128::new(200), Uint128::new(300)),
+            free: ValueRange::new_val(Uint128::new(300)),
         }
     );
+    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
+    assert_eq!(claims.claims, []);
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(&vault.contract_addr, OSMO)
+            .unwrap(),
+        coin(300, OSMO)
+    );
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(&local_staking.contract_addr, OSMO)
+            .unwrap(),
+        coin(0, OSMO)
+    );
 
-    // TODO: Hardcoded external-staking's commit_stake call (lack of IBC support yet).
-    // This should be through `IbcPacketAckMsg`
-    let last_external_staking_tx = get_last_external_staking_pending_tx_id(&cross_staking).unwrap();
-    println!("last_external_staking_tx: {:?}", last_external_staking_tx);
-    cross_staking
-        .test_methods_proxy()
-        .test_commit_stake(last_external_staking_tx)
-        .call("test")
-        .unwrap();
+    // Staking locally
+    stake_locally(&vault, user, 100, val).unwrap();
 
-    let acc = vault.account(user.to_owned()).unwrap();
     assert_eq!(
-        acc,
+        vault.account(user.to_owned()).unwrap(),
         AccountResponse {
             denom: OSMO.to_owned(),
             bonded: Uint128::new(300),
@@ -675,7 +1171,7 @@ fn stake_cross() {
     assert_eq!(
         claims.claims,
         [LienResponse {
-            lienholder: cross_staking.contract_addr.to_string(),
+            lienholder: local_staking.contract_addr.to_string(),
             amount: ValueRange::new_val(Uint128::new(100))
         }]
     );
@@ -684,25 +1180,277 @@ fn stake_cross() {
             .wrap()
             .query_balance(&vault.contract_addr, OSMO)
             .unwrap(),
-        coin(300, OSMO)
+        coin(200, OSMO)
     );
+
+    stake_locally(&vault, user, 150, val).unwrap();
+
     assert_eq!(
-        app.app()
+        vault.account(user.to_owned()).unwrap(),
+        AccountResponse {
+            denom: OSMO.to_owned(),
+            bonded: Uint128::new(300),
+            free: ValueRange::new_val(Uint128::new(50)),
+        }
+    );
+    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
+    assert_eq!(
+        claims.claims,
+        [LienResponse {
+            lienholder: local_staking.contract_addr.to_string(),
+            amount: ValueRange::new_val(Uint128::new(250))
+        }]
+    );
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(&vault.contract_addr, OSMO)
+            .unwrap(),
+        coin(50, OSMO)
+    );
+
+    // Cannot stake over collateral
+
+    let err = stake_locally(&vault, user, 150, val).unwrap_err();
+
+    assert_eq!(err, ContractError::InsufficientBalance);
+
+    // Cannot unbond used collateral
+
+    let err = vault.unbond(coin(100, OSMO)).call(user).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::ClaimsLocked {
+            free: ValueRange::new_val(Uint128::new(50)),
+            max_lienholder: Some(local_staking.contract_addr.clone()),
+            pending_txs: 0
+        }
+    );
+
+    // Unstaking
+
+    let proxy = proxy_for_user(&local_staking, user, &app);
+    proxy
+        .unstake(val.to_string(), coin(50, OSMO))
+        .call(user)
+        .unwrap();
+    process_staking_unbondings(&app);
+    proxy.release_unbonded().call(user).unwrap();
+
+    assert_eq!(
+        vault.account(user.to_owned()).unwrap(),
+        AccountResponse {
+            denom: OSMO.to_owned(),
+            bonded: Uint128::new(300),
+            free: ValueRange::new_val(Uint128::new(100)),
+        }
+    );
+    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
+    assert_eq!(
+        claims.claims,
+        [LienResponse {
+            lienholder: local_staking.contract_addr.to_string(),
+            amount: ValueRange::new_val(Uint128::new(200))
+        }]
+    );
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(&vault.contract_addr, OSMO)
+            .unwrap(),
+        coin(100, OSMO)
+    );
+
+    proxy
+        .unstake(val.to_string(), coin(100, OSMO))
+        .call(user)
+        .unwrap();
+    process_staking_unbondings(&app);
+    proxy.release_unbonded().call(user).unwrap();
+
+    assert_eq!(
+        vault.account(user.to_owned()).unwrap(),
+        AccountResponse {
+            denom: OSMO.to_owned(),
+            bonded: Uint128::new(300),
+            free: ValueRange::new_val(Uint128::new(200)),
+        }
+    );
+    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
+    assert_eq!(
+        claims.claims,
+        [LienResponse {
+            lienholder: local_staking.contract_addr.to_string(),
+            amount: ValueRange::new_val(Uint128::new(100))
+        }]
+    );
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(&vault.contract_addr, OSMO)
+            .unwrap(),
+        coin(200, OSMO)
+    );
+
+    // Cannot unstake over the lien
+
+    // TODO: catch subcall error here
+    // let err = proxy
+    //     .unstake(val.to_string(), coin(200, OSMO))
+    //     .call(user)
+    //     .unwrap_err();
+    // assert_eq!(
+    //     err,
+    //     mesh_native_staking_proxy::error::ContractError::Unauthorized {}
+    // );
+
+    assert_vault_invariants(&vault);
+}
+
+#[test]
+fn emergency_unstake_all_local() {
+    let owner = "owner";
+    let users = ["user1", "user2", "user3"];
+    let val = "validator";
+    let remote_val = "remote-validator";
+
+    let mut app = init_app(&users, &[300, 300, 300]);
+    add_local_validator(&mut app, val);
+
+    let (vault, local_staking, cross_staking) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+    set_active_validators(&cross_staking, &[remote_val]);
+
+    for user in users {
+        bond(&vault, user, 300);
+        stake_locally(&vault, user, 100, val).unwrap();
+    }
+    stake_remotely(&vault, &cross_staking, users[0], &[remote_val], &[50]);
+
+    // Only the admin can trigger it
+    let err = vault
+        .emergency_unstake_all_local(None)
+        .call(users[0])
+        .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    // First page
+    let resp = vault
+        .emergency_unstake_all_local(Some(2))
+        .call(owner)
+        .unwrap();
+    let page: EmergencyUnstakeResponse = from_binary(&resp.data.unwrap()).unwrap();
+    assert_eq!(page.cursor, Some(users[2].to_owned()));
+
+    let delegations = |user: &str| {
+        let proxy = proxy_for_user(&local_staking, user, &app);
+        app.app()
+            .wrap()
+            .query_all_delegations(proxy.contract_addr)
+            .unwrap()
+    };
+    assert_eq!(delegations(users[0]), []);
+    assert_eq!(delegations(users[1]), []);
+    assert_eq!(delegations(users[2]).len(), 1);
+
+    // Unbonded tokens release the local liens of the first page. Cross stakes are not touched
+    process_staking_unbondings(&app);
+    for user in &users[..2] {
+        proxy_for_user(&local_staking, user, &app)
+            .release_unbonded()
+            .call(user)
+            .unwrap();
+    }
+    let claims = vault
+        .account_claims(users[0].to_owned(), None, None)
+        .unwrap();
+    assert_eq!(
+        claims.claims,
+        [
+            LienResponse {
+                lienholder: local_staking.contract_addr.to_string(),
+                amount: ValueRange::new_val(Uint128::zero())
+            },
+            LienResponse {
+                lienholder: cross_staking.contract_addr.to_string(),
+                amount: ValueRange::new_val(Uint128::new(50))
+            }
+        ]
+    );
+    assert_eq!(
+        vault.account(users[1].to_owned()).unwrap().free,
+        ValueRange::new_val(Uint128::new(300))
+    );
+    assert_eq!(
+        vault.account(users[2].to_owned()).unwrap().free,
+        ValueRange::new_val(Uint128::new(200))
+    );
+
+    // Last page continues from the cursor
+    let resp = vault
+        .emergency_unstake_all_local(Some(2))
+        .call(owner)
+        .unwrap();
+    let page: EmergencyUnstakeResponse = from_binary(&resp.data.unwrap()).unwrap();
+    assert_eq!(page.cursor, None);
+    assert_eq!(delegations(users[2]), []);
+
+    assert_vault_invariants(&vault);
+}
+
+#[test]
+fn stake_cross() {
+    let owner = "owner";
+    let user = "user1";
+
+    let app = init_app(&[user], &[300]);
+
+    let unbond_period = 100;
+    let (vault, _local_staking, cross_staking) =
+        setup(&app, owner, SLASHING_PERCENTAGE, unbond_period);
+
+    // Set active validator
+    let validator = "validator";
+    set_active_validators(&cross_staking, &[validator]);
+
+    // Bond some tokens
+    bond(&vault, user, 300);
+
+    assert_eq!(
+        vault.account(user.to_owned()).unwrap(),
+        AccountResponse {
+            denom: OSMO.to_owned(),
+            bonded: Uint128::new(300),
+            free: ValueRange::new_val(Uint128::new(300)),
+        }
+    );
+    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
+    assert_eq!(claims.claims, []);
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(&vault.contract_addr, OSMO)
+            .unwrap(),
+        coin(300, OSMO)
+    );
+    assert_eq!(
+        app.app()
             .wrap()
             .query_balance(&cross_staking.contract_addr, OSMO)
             .unwrap(),
         coin(0, OSMO)
     );
 
-    // second stake remote
+    // Staking remotely
+
     vault
         .stake_remote(
             cross_staking.contract_addr.to_string(),
-            coin(150, OSMO),
+            coin(100, OSMO),
             to_binary(&ReceiveVirtualStake {
                 validator: validator.to_string(),
             })
             .unwrap(),
+            None,
         )
         .call(user)
         .unwrap();
@@ -713,27 +1461,33 @@ fn stake_cross() {
         AccountResponse {
             denom: OSMO.to_owned(),
             bonded: Uint128::new(300),
-            free: ValueRange::new(Uint128::new(50), Uint128::new(200)),
+            free: ValueRange::new(Uint128::new(200), Uint128::new(300)),
         }
     );
 
     // TODO: Hardcoded external-staking's commit_stake call (lack of IBC support yet).
     // This should be through `IbcPacketAckMsg`
     let last_external_staking_tx = get_last_external_staking_pending_tx_id(&cross_staking).unwrap();
-    println!("last_external_staking_tx: {:?}", last_external_staking_tx);
     cross_staking
         .test_methods_proxy()
         .test_commit_stake(last_external_staking_tx)
         .call("test")
         .unwrap();
 
+    // The commit is forwarded to the vault, so both ledgers moved together
+    assert_eq!(vault.all_pending_txs_desc(None, None).unwrap().txs, []);
+    assert_eq!(
+        cross_staking.all_pending_txs_desc(None, None).unwrap().txs,
+        []
+    );
+
     let acc = vault.account(user.to_owned()).unwrap();
     assert_eq!(
         acc,
         AccountResponse {
             denom: OSMO.to_owned(),
             bonded: Uint128::new(300),
-            free: ValueRange::new_val(Uint128::new(50)),
+            free: ValueRange::new_val(Uint128::new(200)),
         }
     );
     let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
@@ -741,7 +1495,7 @@ fn stake_cross() {
         claims.claims,
         [LienResponse {
             lienholder: cross_staking.contract_addr.to_string(),
-            amount: ValueRange::new_val(Uint128::new(250))
+            amount: ValueRange::new_val(Uint128::new(100))
         }]
     );
     assert_eq!(
@@ -759,9 +1513,8 @@ fn stake_cross() {
         coin(0, OSMO)
     );
 
-    // Cannot stake over collateral
-
-    let err = vault
+    // second stake remote
+    vault
         .stake_remote(
             cross_staking.contract_addr.to_string(),
             coin(150, OSMO),
@@ -769,9 +1522,10 @@ fn stake_cross() {
                 validator: validator.to_string(),
             })
             .unwrap(),
+            None,
         )
         .call(user)
-        .unwrap_err();
+        .unwrap();
 
     let acc = vault.account(user.to_owned()).unwrap();
     assert_eq!(
@@ -779,24 +1533,18 @@ fn stake_cross() {
         AccountResponse {
             denom: OSMO.to_owned(),
             bonded: Uint128::new(300),
-            free: ValueRange::new_val(Uint128::new(50)),
+            free: ValueRange::new(Uint128::new(50), Uint128::new(200)),
         }
     );
 
-    assert_eq!(err, ContractError::InsufficentBalance);
-
-    // Cannot unbond used collateral
-
-    let err = vault.unbond(coin(100, OSMO)).call(user).unwrap_err();
-    assert_eq!(
-        err,
-        ContractError::ClaimsLocked(ValueRange::new_val(Uint128::new(50)))
-    );
-
-    // Unstake does not free collateral on vault right away
+    // TODO: Hardcoded external-staking's commit_stake call (lack of IBC support yet).
+    // This should be through `IbcPacketAckMsg`
+    let last_external_staking_tx = get_last_external_staking_pending_tx_id(&cross_staking).unwrap();
+    println!("last_external_staking_tx: {:?}", last_external_staking_tx);
     cross_staking
-        .unstake(validator.to_owned(), coin(50, OSMO))
-        .call(user)
+        .test_methods_proxy()
+        .test_commit_stake(last_external_staking_tx)
+        .call("test")
         .unwrap();
 
     let acc = vault.account(user.to_owned()).unwrap();
@@ -831,21 +1579,98 @@ fn stake_cross() {
         coin(0, OSMO)
     );
 
-    // External staking contract will wait for `unbond_period` after receiving
-    // confirmation through the IBC channel that `unstake` was successfully executed.
-    skip_time(&app, unbond_period);
-    let tx_id = get_last_external_staking_pending_tx_id(&cross_staking).unwrap();
-    cross_staking
-        .test_methods_proxy()
-        .test_commit_unstake(tx_id)
-        .call("test")
-        .unwrap();
-
-    // No tokens is withdrawn before unbonding period is over
-    let insufficient_time = 99;
-    skip_time(&app, insufficient_time);
-
-    cross_staking.withdraw_unbonded().call(user).unwrap();
+    // Cannot stake over collateral
+
+    let err = vault
+        .stake_remote(
+            cross_staking.contract_addr.to_string(),
+            coin(150, OSMO),
+            to_binary(&ReceiveVirtualStake {
+                validator: validator.to_string(),
+            })
+            .unwrap(),
+            None,
+        )
+        .call(user)
+        .unwrap_err();
+
+    let acc = vault.account(user.to_owned()).unwrap();
+    assert_eq!(
+        acc,
+        AccountResponse {
+            denom: OSMO.to_owned(),
+            bonded: Uint128::new(300),
+            free: ValueRange::new_val(Uint128::new(50)),
+        }
+    );
+
+    assert_eq!(err, ContractError::InsufficientBalance);
+
+    // Cannot unbond used collateral
+
+    let err = vault.unbond(coin(100, OSMO)).call(user).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::ClaimsLocked {
+            free: ValueRange::new_val(Uint128::new(50)),
+            max_lienholder: Some(cross_staking.contract_addr.clone()),
+            pending_txs: 0
+        }
+    );
+
+    // Unstake does not free collateral on vault right away
+    cross_staking
+        .unstake(validator.to_owned(), coin(50, OSMO))
+        .call(user)
+        .unwrap();
+
+    let acc = vault.account(user.to_owned()).unwrap();
+    assert_eq!(
+        acc,
+        AccountResponse {
+            denom: OSMO.to_owned(),
+            bonded: Uint128::new(300),
+            free: ValueRange::new_val(Uint128::new(50)),
+        }
+    );
+    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
+    assert_eq!(
+        claims.claims,
+        [LienResponse {
+            lienholder: cross_staking.contract_addr.to_string(),
+            amount: ValueRange::new_val(Uint128::new(250))
+        }]
+    );
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(&vault.contract_addr, OSMO)
+            .unwrap(),
+        coin(300, OSMO)
+    );
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(&cross_staking.contract_addr, OSMO)
+            .unwrap(),
+        coin(0, OSMO)
+    );
+
+    // External staking contract will wait for `unbond_period` after receiving
+    // confirmation through the IBC channel that `unstake` was successfully executed.
+    skip_time(&app, unbond_period);
+    let tx_id = get_last_external_staking_pending_tx_id(&cross_staking).unwrap();
+    cross_staking
+        .test_methods_proxy()
+        .test_commit_unstake(tx_id)
+        .call("test")
+        .unwrap();
+
+    // No tokens is withdrawn before unbonding period is over
+    let insufficient_time = 99;
+    skip_time(&app, insufficient_time);
+
+    cross_staking.withdraw_unbonded(None).call(user).unwrap();
 
     let acc = vault.account(user.to_owned()).unwrap();
     assert_eq!(
@@ -861,7 +1686,7 @@ fn stake_cross() {
     let remaining_time = 1;
     skip_time(&app, remaining_time);
 
-    cross_staking.withdraw_unbonded().call(user).unwrap();
+    cross_staking.withdraw_unbonded(None).call(user).unwrap();
 
     let acc = vault.account(user.to_owned()).unwrap();
     assert_eq!(
@@ -911,7 +1736,7 @@ fn stake_cross() {
 
     skip_time(&app, unbond_period);
 
-    cross_staking.withdraw_unbonded().call(user).unwrap();
+    cross_staking.withdraw_unbonded(None).call(user).unwrap();
 
     let acc = vault.account(user.to_owned()).unwrap();
     assert_eq!(
@@ -953,149 +1778,523 @@ fn stake_cross() {
         .unstake(user.to_owned(), coin(300, OSMO))
         .call(owner)
         .unwrap_err();
+
+    assert_vault_invariants(&vault);
 }
 
 #[test]
-fn stake_cross_txs() {
+fn stake_remote_to_incompatible_contract() {
     let owner = "owner";
     let user = "user1";
-    let user2 = "user2";
 
-    let app = init_app(&[user, user2], &[300, 500]);
+    let app = init_app(&[user], &[300]);
+
+    let (vault, _, _) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+    bond(&vault, user, 300);
+
+    let stake_remote = |contract: &Addr| {
+        vault
+            .stake_remote(
+                contract.to_string(),
+                coin(100, OSMO),
+                to_binary(&ReceiveVirtualStake {
+                    validator: "validator".to_owned(),
+                })
+                .unwrap(),
+                None,
+            )
+            .call(user)
+            .unwrap_err()
+    };
+
+    // Mistyped address of a contract not implementing the cross staking API
+    let cw20 = cw20_mock::multitest_utils::CodeId::store_code(&app)
+        .instantiate(vec![])
+        .call(owner)
+        .unwrap();
+    let err = stake_remote(&cw20.contract_addr);
+    assert!(
+        matches!(
+            &err,
+            ContractError::NotACrossStakingContract { addr, .. } if *addr == cw20.contract_addr
+        ),
+        "{err:?}"
+    );
+
+    // Mistyped address of a plain account
+    let account = Addr::unchecked("user2");
+    let err = stake_remote(&account);
+    assert!(
+        matches!(
+            &err,
+            ContractError::NotACrossStakingContract { addr, .. } if *addr == account
+        ),
+        "{err:?}"
+    );
+
+    // Out of range slash ratios would corrupt the slashable collateral
+    let cross_staking = cross_staking_mock::multitest_utils::CodeId::store_code(&app)
+        .instantiate(Decimal::percent(150))
+        .call(owner)
+        .unwrap();
+    let err = stake_remote(&cross_staking.contract_addr);
+    assert_eq!(err, ContractError::InvalidSlashRatio(Decimal::percent(150)));
+
+    // Nothing was staked
+    let acc = vault.account_details(user.to_owned()).unwrap();
+    assert_eq!(acc.max_lien, ValueRange::new_val(Uint128::zero()));
+    assert_eq!(acc.total_slashable, ValueRange::new_val(Uint128::zero()));
+    assert_eq!(get_last_vault_pending_tx_id(&vault), None);
+}
+
+#[test]
+fn withdraw_unbonded_to_recipient() {
+    let owner = "owner";
+    let user = "user1";
+    let recipient = "recipient";
+
+    let app = init_app(&[user], &[300]);
 
     let unbond_period = 100;
     let (vault, _local_staking, cross_staking) =
         setup(&app, owner, SLASHING_PERCENTAGE, unbond_period);
 
-    // Set active validator
     let validator = "validator";
     set_active_validators(&cross_staking, &[validator]);
 
-    // Bond some tokens
     bond(&vault, user, 300);
+    stake_remotely(&vault, &cross_staking, user, &[validator], &[200]);
+
+    cross_staking
+        .unstake(validator.to_owned(), coin(150, OSMO))
+        .call(user)
+        .unwrap();
+    let tx_id = get_last_external_staking_pending_tx_id(&cross_staking).unwrap();
+    cross_staking
+        .test_methods_proxy()
+        .test_commit_unstake(tx_id)
+        .call("test")
+        .unwrap();
+
+    skip_time(&app, unbond_period);
+
+    cross_staking
+        .withdraw_unbonded(Some(recipient.to_owned()))
+        .call(user)
+        .unwrap();
 
+    // The lien is reduced for the owner, and the released collateral is unbonded
     assert_eq!(
         vault.account(user.to_owned()).unwrap(),
         AccountResponse {
             denom: OSMO.to_owned(),
-            bonded: Uint128::new(300),
-            free: ValueRange::new_val(Uint128::new(300)),
+            bonded: Uint128::new(150),
+            free: ValueRange::new_val(Uint128::new(100)),
         }
     );
     let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
-    assert_eq!(claims.claims, []);
+    assert_eq!(
+        claims.claims,
+        [LienResponse {
+            lienholder: cross_staking.contract_addr.to_string(),
+            amount: ValueRange::new_val(Uint128::new(50))
+        }]
+    );
 
-    bond(&vault, user2, 500);
+    // Tokens arrive at the recipient
     assert_eq!(
-        vault.account(user2.to_owned()).unwrap(),
-        AccountResponse {
-            denom: OSMO.to_owned(),
-            bonded: Uint128::new(500),
-            free: ValueRange::new_val(Uint128::new(500)),
-        }
+        app.app().wrap().query_balance(recipient, OSMO).unwrap(),
+        coin(150, OSMO)
+    );
+    assert_eq!(
+        app.app().wrap().query_balance(user, OSMO).unwrap(),
+        coin(0, OSMO)
     );
     assert_eq!(
         app.app()
             .wrap()
             .query_balance(&vault.contract_addr, OSMO)
             .unwrap(),
-        coin(800, OSMO)
+        coin(150, OSMO)
     );
     assert_eq!(
-        app.app()
-            .wrap()
-            .query_balance(&cross_staking.contract_addr, OSMO)
-            .unwrap(),
-        coin(0, OSMO)
+        vault.account(recipient.to_owned()).unwrap().bonded,
+        Uint128::zero()
     );
 
-    // No pending txs
-    assert_eq!(vault.all_pending_txs_desc(None, None).unwrap().txs, vec![]);
-    // Can query all accounts
-    let accounts = vault.all_accounts(false, None, None).unwrap();
-    assert_eq!(accounts.accounts.len(), 2);
+    assert_vault_invariants(&vault);
+}
 
-    // Staking remotely
+#[test]
+fn force_unstake_remote() {
+    let owner = "owner";
+    let user = "user1";
 
-    vault
-        .stake_remote(
-            cross_staking.contract_addr.to_string(),
-            coin(100, OSMO),
-            to_binary(&ReceiveVirtualStake {
-                validator: validator.to_string(),
-            })
-            .unwrap(),
-        )
-        .call(user)
-        .unwrap();
+    let app = init_app(&[user], &[300]);
 
-    // One pending tx
-    assert_eq!(vault.all_pending_txs_desc(None, None).unwrap().txs.len(), 1);
-    // Store for later
-    let first_tx = get_last_vault_pending_tx_id(&vault).unwrap();
+    let unbond_period = 100;
+    let (vault, _local_staking, cross_staking) =
+        setup(&app, owner, SLASHING_PERCENTAGE, unbond_period);
 
-    // Same user can stake while pending tx
-    vault
-        .stake_remote(
+    let validators = ["validator1", "validator2"];
+    set_active_validators(&cross_staking, &validators);
+
+    bond(&vault, user, 300);
+    stake_remotely(&vault, &cross_staking, user, &validators, &[50, 100]);
+
+    // Only the admin can force an exit
+    let err = vault
+        .force_unstake_remote(
+            user.to_owned(),
             cross_staking.contract_addr.to_string(),
-            coin(50, OSMO),
-            to_binary(&ReceiveVirtualStake {
-                validator: validator.to_string(),
-            })
-            .unwrap(),
+            None,
+            coin(120, OSMO),
         )
         .call(user)
-        .unwrap();
-    // Store for later
-    let second_tx = get_last_vault_pending_tx_id(&vault).unwrap();
+        .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
 
-    // Other user can as well
+    // Without a validator, the largest stake is unstaken first
     vault
-        .stake_remote(
+        .force_unstake_remote(
+            user.to_owned(),
             cross_staking.contract_addr.to_string(),
-            coin(100, OSMO),
-            to_binary(&ReceiveVirtualStake {
-                validator: validator.to_string(),
-            })
-            .unwrap(),
+            None,
+            coin(120, OSMO),
         )
-        .call(user2)
+        .call(owner)
         .unwrap();
-
-    // Three pending txs
-    assert_eq!(vault.all_pending_txs_desc(None, None).unwrap().txs.len(), 3);
-
-    // Last tx commit_tx call
-    let last_tx = get_last_vault_pending_tx_id(&vault).unwrap();
-    vault
-        .vault_api_proxy()
-        .commit_tx(last_tx)
-        .call(cross_staking.contract_addr.as_str())
+    let tx_id = get_last_external_staking_pending_tx_id(&cross_staking).unwrap();
+    cross_staking
+        .test_methods_proxy()
+        .test_commit_unstake(tx_id)
+        .call("test")
         .unwrap();
 
-    // Two pending txs now
-    assert_eq!(vault.all_pending_txs_desc(None, None).unwrap().txs.len(), 2);
+    let stakes = validators.map(|validator| {
+        cross_staking
+            .stake(user.to_owned(), validator.to_owned())
+            .unwrap()
+            .stake
+    });
+    assert_eq!(
+        stakes,
+        [
+            ValueRange::new_val(Uint128::new(30)),
+            ValueRange::new_val(Uint128::zero())
+        ]
+    );
 
-    // First tx (old one) is still pending
-    let first_id = match vault.all_pending_txs_desc(None, None).unwrap().txs[1] {
-        InFlightStaking { id, .. } => id,
-        _ => panic!("unexpected tx type"),
-    };
-    assert_eq!(first_id, first_tx);
-    // Second tx (recent one) is still pending
-    let second_id = match vault.all_pending_txs_desc(None, None).unwrap().txs[0] {
-        InFlightStaking { id, .. } => id,
-        _ => panic!("unexpected tx type"),
-    };
-    assert_eq!(second_id, second_tx);
+    // Once unbonded, the tokens are released back to the vault
+    skip_time(&app, unbond_period);
+    cross_staking.withdraw_unbonded(None).call(user).unwrap();
 
-    // Can query account while pending
     assert_eq!(
         vault.account(user.to_owned()).unwrap(),
-        AccountResponse::new(
-            OSMO,
-            Uint128::new(300),
-            ValueRange::new(Uint128::new(150), Uint128::new(300))
+        AccountResponse {
+            denom: OSMO.to_owned(),
+            bonded: Uint128::new(300),
+            free: ValueRange::new_val(Uint128::new(270)),
+        }
+    );
+    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
+    assert_eq!(
+        claims.claims,
+        [LienResponse {
+            lienholder: cross_staking.contract_addr.to_string(),
+            amount: ValueRange::new_val(Uint128::new(30))
+        }]
+    );
+
+    assert_vault_invariants(&vault);
+}
+
+#[test]
+fn consolidate_stake() {
+    let owner = "owner";
+    let user = "user1";
+
+    let app = init_app(&[user], &[300]);
+
+    let (vault, local_staking, cross_staking) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+
+    let validators = ["validator1", "validator2", "validator3"];
+    let target = "validator4";
+    set_active_validators(&cross_staking, &[validators.as_slice(), &[target]].concat());
+
+    bond(&vault, user, 300);
+    stake_remotely(&vault, &cross_staking, user, &validators, &[10, 20, 30]);
+
+    // Only an existing lienholder can be consolidated on
+    let err = vault
+        .consolidate_stake(
+            local_staking.contract_addr.to_string(),
+            validators.map(str::to_owned).to_vec(),
+            target.to_owned(),
+        )
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::UnknownLienholder);
+
+    vault
+        .consolidate_stake(
+            cross_staking.contract_addr.to_string(),
+            validators.map(str::to_owned).to_vec(),
+            target.to_owned(),
+        )
+        .call(user)
+        .unwrap();
+    let tx_id = get_last_external_staking_pending_tx_id(&cross_staking).unwrap();
+    cross_staking
+        .test_methods_proxy()
+        .test_commit_restake(tx_id)
+        .call("test")
+        .unwrap();
+
+    // The whole stake was moved to the target validator
+    for validator in validators {
+        let stake = cross_staking
+            .stake(user.to_owned(), validator.to_owned())
+            .unwrap()
+            .stake;
+        assert_eq!(stake, ValueRange::new_val(Uint128::zero()), "{validator}");
+    }
+    assert_eq!(
+        cross_staking
+            .stake(user.to_owned(), target.to_owned())
+            .unwrap()
+            .stake,
+        ValueRange::new_val(Uint128::new(60))
+    );
+
+    // The lien is unchanged
+    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
+    assert_eq!(
+        claims.claims,
+        [LienResponse {
+            lienholder: cross_staking.contract_addr.to_string(),
+            amount: ValueRange::new_val(Uint128::new(60))
+        }]
+    );
+    assert_eq!(
+        vault.account(user.to_owned()).unwrap().free,
+        ValueRange::new_val(Uint128::new(240))
+    );
+
+    assert_vault_invariants(&vault);
+}
+
+#[test]
+fn check_remote_stake() {
+    let owner = "owner";
+    let user = "user1";
+
+    let app = init_app(&[user], &[300]);
+
+    let (vault, _local_staking, cross_staking) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+    set_active_validators(&cross_staking, &["validator1"]);
+    bond(&vault, user, 300);
+
+    // Only the admin can enable the check
+    let err = vault
+        .update_check_remote_stake(true)
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    vault.update_check_remote_stake(true).call(owner).unwrap();
+    assert!(vault.config().unwrap().check_remote_stake);
+
+    // A stake the cross staking contract would reject fails right away, with no tx reserved
+    let err = vault
+        .stake_remote(
+            cross_staking.contract_addr.to_string(),
+            coin(100, OSMO),
+            to_binary(&ReceiveVirtualStake {
+                validator: "unknown".to_owned(),
+            })
+            .unwrap(),
+            None,
+        )
+        .call(user)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::RemoteStakeRejected(
+            ExternalStakingError::UnknownValidator("unknown".to_owned()).to_string()
+        )
+    );
+    assert_eq!(vault.all_pending_txs_desc(None, None).unwrap().txs, vec![]);
+    assert_eq!(
+        vault.account(user.to_owned()).unwrap().free,
+        ValueRange::new_val(Uint128::new(300))
+    );
+
+    // An accepted one goes through as usual
+    stake_remotely(&vault, &cross_staking, user, &["validator1"], &[100]);
+    assert_eq!(
+        cross_staking
+            .stake(user.to_owned(), "validator1".to_owned())
+            .unwrap()
+            .stake,
+        ValueRange::new_val(Uint128::new(100))
+    );
+
+    assert_vault_invariants(&vault);
+}
+
+#[test]
+fn stake_cross_txs() {
+    let owner = "owner";
+    let user = "user1";
+    let user2 = "user2";
+
+    let app = init_app(&[user, user2], &[300, 500]);
+
+    let unbond_period = 100;
+    let (vault, _local_staking, cross_staking) =
+        setup(&app, owner, SLASHING_PERCENTAGE, unbond_period);
+
+    // Set active validator
+    let validator = "validator";
+    set_active_validators(&cross_staking, &[validator]);
+
+    // Bond some tokens
+    bond(&vault, user, 300);
+
+    assert_eq!(
+        vault.account(user.to_owned()).unwrap(),
+        AccountResponse {
+            denom: OSMO.to_owned(),
+            bonded: Uint128::new(300),
+            free: ValueRange::new_val(Uint128::new(300)),
+        }
+    );
+    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
+    assert_eq!(claims.claims, []);
+
+    bond(&vault, user2, 500);
+    assert_eq!(
+        vault.account(user2.to_owned()).unwrap(),
+        AccountResponse {
+            denom: OSMO.to_owned(),
+            bonded: Uint128::new(500),
+            free: ValueRange::new_val(Uint128::new(500)),
+        }
+    );
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(&vault.contract_addr, OSMO)
+            .unwrap(),
+        coin(800, OSMO)
+    );
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(&cross_staking.contract_addr, OSMO)
+            .unwrap(),
+        coin(0, OSMO)
+    );
+
+    // No pending txs
+    assert_eq!(vault.all_pending_txs_desc(None, None).unwrap().txs, vec![]);
+    // Can query all accounts
+    let accounts = vault.all_accounts(false, None, None, None, None).unwrap();
+    assert_eq!(accounts.accounts.len(), 2);
+
+    // Staking remotely
+
+    vault
+        .stake_remote(
+            cross_staking.contract_addr.to_string(),
+            coin(100, OSMO),
+            to_binary(&ReceiveVirtualStake {
+                validator: validator.to_string(),
+            })
+            .unwrap(),
+            None,
+        )
+        .call(user)
+        .unwrap();
+
+    // One pending tx
+    assert_eq!(vault.all_pending_txs_desc(None, None).unwrap().txs.len(), 1);
+    // Store for later
+    let first_tx = get_last_vault_pending_tx_id(&vault).unwrap();
+
+    // Same user can stake while pending tx
+    vault
+        .stake_remote(
+            cross_staking.contract_addr.to_string(),
+            coin(50, OSMO),
+            to_binary(&ReceiveVirtualStake {
+                validator: validator.to_string(),
+            })
+            .unwrap(),
+            None,
+        )
+        .call(user)
+        .unwrap();
+    // Store for later
+    let second_tx = get_last_vault_pending_tx_id(&vault).unwrap();
+
+    // Other user can as well
+    vault
+        .stake_remote(
+            cross_staking.contract_addr.to_string(),
+            coin(100, OSMO),
+            to_binary(&ReceiveVirtualStake {
+                validator: validator.to_string(),
+            })
+            .unwrap(),
+            None,
+        )
+        .call(user2)
+        .unwrap();
+
+    // Three pending txs
+    assert_eq!(vault.all_pending_txs_desc(None, None).unwrap().txs.len(), 3);
+
+    // Last tx commit, forwarded to the vault by the external staking contract. Both
+    // contracts use the vault tx ids
+    let last_tx = get_last_vault_pending_tx_id(&vault).unwrap();
+    cross_staking
+        .test_methods_proxy()
+        .test_commit_stake(last_tx)
+        .call("test")
+        .unwrap();
+
+    // Two pending txs now, on both sides
+    assert_eq!(vault.all_pending_txs_desc(None, None).unwrap().txs.len(), 2);
+    assert_eq!(
+        cross_staking
+            .all_pending_txs_desc(None, None)
+            .unwrap()
+            .txs
+            .len(),
+        2
+    );
+
+    // First tx (old one) is still pending
+    let first_id = match vault.all_pending_txs_desc(None, None).unwrap().txs[1] {
+        InFlightStaking { id, .. } => id,
+        _ => panic!("unexpected tx type"),
+    };
+    assert_eq!(first_id, first_tx);
+    // Second tx (recent one) is still pending
+    let second_id = match vault.all_pending_txs_desc(None, None).unwrap().txs[0] {
+        InFlightStaking { id, .. } => id,
+        _ => panic!("unexpected tx type"),
+    };
+    assert_eq!(second_id, second_tx);
+
+    // Can query account while pending
+    assert_eq!(
+        vault.account(user.to_owned()).unwrap(),
+        AccountResponse::new(
+            OSMO,
+            Uint128::new(300),
+            ValueRange::new(Uint128::new(150), Uint128::new(300))
         )
     );
     // Can query claims, and value ranges are reported
@@ -1118,7 +2317,7 @@ fn stake_cross_txs() {
         coin(800, OSMO)
     );
     // Can query all accounts, and value ranges are reported
-    let accounts = vault.all_accounts(false, None, None).unwrap();
+    let accounts = vault.all_accounts(false, None, None, None, None).unwrap();
     assert_eq!(
         accounts.accounts,
         vec![
@@ -1162,10 +2361,10 @@ fn stake_cross_txs() {
     );
 
     // Commit first tx
-    vault
-        .vault_api_proxy()
-        .commit_tx(first_tx)
-        .call(cross_staking.contract_addr.as_str())
+    cross_staking
+        .test_methods_proxy()
+        .test_commit_stake(first_tx)
+        .call("test")
         .unwrap();
 
     // Can query account
@@ -1195,6 +2394,8 @@ fn stake_cross_txs() {
             .unwrap(),
         coin(800, OSMO)
     );
+
+    assert_vault_invariants(&vault);
 }
 
 #[test]
@@ -1234,6 +2435,7 @@ fn stake_cross_rollback_tx() {
                 validator: validator.to_string(),
             })
             .unwrap(),
+            None,
         )
         .call(user)
         .unwrap();
@@ -1241,12 +2443,12 @@ fn stake_cross_rollback_tx() {
     // One pending tx
     assert_eq!(vault.all_pending_txs_desc(None, None).unwrap().txs.len(), 1);
 
-    // Rollback tx
+    // Rollback tx, forwarded to the vault
     let last_tx = get_last_vault_pending_tx_id(&vault).unwrap();
-    vault
-        .vault_api_proxy()
-        .rollback_tx(last_tx)
-        .call(cross_staking.contract_addr.as_str())
+    cross_staking
+        .test_methods_proxy()
+        .test_rollback_stake(last_tx)
+        .call("test")
         .unwrap();
 
     // No pending txs
@@ -1283,6 +2485,1103 @@ fn stake_cross_rollback_tx() {
             .unwrap(),
         coin(300, OSMO)
     );
+
+    assert_vault_invariants(&vault);
+}
+
+#[test]
+fn unbond_during_cross_stake() {
+    let owner = "owner";
+    let user = "user1";
+
+    let app = init_app(&[user], &[300]);
+
+    let (vault, _local_staking, cross_staking) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+    let validator = "validator";
+    set_active_validators(&cross_staking, &[validator]);
+    bond(&vault, user, 300);
+
+    let stake_remote = |amount| {
+        vault
+            .stake_remote(
+                cross_staking.contract_addr.to_string(),
+                coin(amount, OSMO),
+                to_binary(&ReceiveVirtualStake {
+                    validator: validator.to_string(),
+                })
+                .unwrap(),
+                None,
+            )
+            .call(user)
+            .unwrap();
+        get_last_vault_pending_tx_id(&vault).unwrap()
+    };
+
+    // Only the collateral free if the stake is committed can be unbonded
+    let tx_id = stake_remote(100);
+    let free = ValueRange::new(Uint128::new(200), Uint128::new(300));
+    assert_eq!(vault.account(user.to_owned()).unwrap().free, free);
+    let err = vault.unbond(coin(201, OSMO)).call(user).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::ClaimsLocked {
+            free,
+            max_lienholder: Some(cross_staking.contract_addr.clone()),
+            pending_txs: 1
+        }
+    );
+
+    // Rolled back, it is free again
+    vault
+        .vault_api_proxy()
+        .rollback_tx(tx_id)
+        .call(cross_staking.contract_addr.as_str())
+        .unwrap();
+    vault.unbond(coin(201, OSMO)).call(user).unwrap();
+
+    let tx_id = stake_remote(50);
+    let free = ValueRange::new(Uint128::new(49), Uint128::new(99));
+    assert_eq!(vault.account(user.to_owned()).unwrap().free, free);
+    let err = vault.unbond(coin(50, OSMO)).call(user).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::ClaimsLocked {
+            free,
+            max_lienholder: Some(cross_staking.contract_addr.clone()),
+            pending_txs: 1
+        }
+    );
+
+    // Committed, the conservative amount is the actual one
+    vault
+        .vault_api_proxy()
+        .commit_tx(tx_id)
+        .call(cross_staking.contract_addr.as_str())
+        .unwrap();
+    let free = ValueRange::new_val(Uint128::new(49));
+    let err = vault.unbond(coin(50, OSMO)).call(user).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::ClaimsLocked {
+            free,
+            max_lienholder: Some(cross_staking.contract_addr.clone()),
+            pending_txs: 0
+        }
+    );
+    vault.unbond(coin(49, OSMO)).call(user).unwrap();
+    assert_eq!(
+        vault.account(user.to_owned()).unwrap().free,
+        ValueRange::new_val(Uint128::zero())
+    );
+
+    assert_vault_invariants(&vault);
+}
+
+#[test]
+fn claims_locked_breakdown() {
+    let owner = "owner";
+    let user = "user1";
+    let validator = "validator";
+
+    let mut app = init_app(&[user], &[300]);
+    add_local_validator(&mut app, validator);
+
+    let (vault, local_staking, cross_staking) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+    set_active_validators(&cross_staking, &[validator]);
+    bond(&vault, user, 300);
+
+    // The local lien is the largest one
+    stake_locally(&vault, user, 150, validator).unwrap();
+    stake_remotely(&vault, &cross_staking, user, &[validator], &[100]);
+    let free = vault.account(user.to_owned()).unwrap().free;
+    let err = vault.unbond(coin(151, OSMO)).call(user).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::ClaimsLocked {
+            free,
+            max_lienholder: Some(local_staking.contract_addr.clone()),
+            pending_txs: 0
+        }
+    );
+
+    // Until a pending cross stake makes the cross lien the largest one
+    vault
+        .stake_remote(
+            cross_staking.contract_addr.to_string(),
+            coin(100, OSMO),
+            to_binary(&ReceiveVirtualStake {
+                validator: validator.to_string(),
+            })
+            .unwrap(),
+            None,
+        )
+        .call(user)
+        .unwrap();
+    let free = vault.account(user.to_owned()).unwrap().free;
+    let err = vault.unbond(coin(101, OSMO)).call(user).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::ClaimsLocked {
+            free,
+            max_lienholder: Some(cross_staking.contract_addr.clone()),
+            pending_txs: 1
+        }
+    );
+    assert!(err
+        .to_string()
+        .contains(&format!("largest lien: {}", cross_staking.contract_addr)));
+}
+
+#[test]
+fn txs_history() {
+    let owner = "owner";
+    let user = "user1";
+
+    let app = init_app(&[user], &[300]);
+
+    let (vault, _local_staking, cross_staking) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+
+    let validator = "validator";
+    set_active_validators(&cross_staking, &[validator]);
+
+    bond(&vault, user, 300);
+
+    // Committed stake
+    stake_remotely(&vault, &cross_staking, user, &[validator], &[100]);
+    let history = vault.txs_history(None, None).unwrap().txs;
+    assert_eq!(history.len(), 1);
+    let commit_tx = history[0].clone();
+    assert!(commit_tx.committed);
+    assert_eq!(
+        vault.pending_tx(commit_tx.id).unwrap(),
+        TxStatus::Resolved(commit_tx.clone())
+    );
+
+    // Rolled back stake
+    vault
+        .stake_remote(
+            cross_staking.contract_addr.to_string(),
+            coin(50, OSMO),
+            to_binary(&ReceiveVirtualStake {
+                validator: validator.to_string(),
+            })
+            .unwrap(),
+            None,
+        )
+        .call(user)
+        .unwrap();
+    let rollback_tx = get_last_vault_pending_tx_id(&vault).unwrap();
+    assert!(matches!(
+        vault.pending_tx(rollback_tx).unwrap(),
+        TxStatus::Pending(InFlightStaking { .. })
+    ));
+    vault
+        .vault_api_proxy()
+        .rollback_tx(rollback_tx)
+        .call(cross_staking.contract_addr.as_str())
+        .unwrap();
+
+    assert_eq!(
+        vault.pending_tx(rollback_tx).unwrap(),
+        TxStatus::Resolved(TxResult {
+            id: rollback_tx,
+            committed: false,
+            resolved_at: app.block_info().time,
+        })
+    );
+
+    // Newest first, paginated
+    let history = vault.txs_history(None, None).unwrap().txs;
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].id, rollback_tx);
+    let history = vault.txs_history(Some(rollback_tx), None).unwrap().txs;
+    assert_eq!(history, [commit_tx]);
+
+    // Unknown txs are still not found
+    vault.pending_tx(rollback_tx + 1).unwrap_err();
+}
+
+#[test]
+fn stake_remote_idempotency_key() {
+    let owner = "owner";
+    let user = "user1";
+
+    let app = init_app(&[user], &[300]);
+
+    let (vault, _local_staking, cross_staking) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+
+    let validator = "validator";
+    set_active_validators(&cross_staking, &[validator]);
+
+    bond(&vault, user, 300);
+
+    let stake = |key: &str| -> u64 {
+        let resp = vault
+            .stake_remote(
+                cross_staking.contract_addr.to_string(),
+                coin(50, OSMO),
+                to_binary(&ReceiveVirtualStake {
+                    validator: validator.to_string(),
+                })
+                .unwrap(),
+                Some(key.to_owned()),
+            )
+            .call(user)
+            .unwrap();
+        from_binary(&resp.data.unwrap()).unwrap()
+    };
+
+    // Retrying returns the original tx, without staking again
+    let tx_id = stake("retry");
+    assert_eq!(stake("retry"), tx_id);
+    let txs = vault.user_pending_txs(user.to_owned()).unwrap().txs;
+    assert_eq!(txs.len(), 1);
+    let acc = vault.account_details(user.to_owned()).unwrap();
+    assert_eq!(
+        acc.max_lien,
+        ValueRange::new(Uint128::zero(), Uint128::new(50))
+    );
+
+    // Other keys are other stakes
+    let other_tx_id = stake("other");
+    assert_ne!(other_tx_id, tx_id);
+    let txs = vault.user_pending_txs(user.to_owned()).unwrap().txs;
+    assert_eq!(txs.len(), 2);
+
+    // Keys are forgotten after the TTL
+    skip_time(&app, contract::IDEMPOTENCY_KEY_TTL);
+    let new_tx_id = stake("retry");
+    assert!(new_tx_id > other_tx_id);
+    let txs = vault.user_pending_txs(user.to_owned()).unwrap().txs;
+    assert_eq!(txs.len(), 3);
+
+    let err = vault
+        .stake_remote(
+            cross_staking.contract_addr.to_string(),
+            coin(50, OSMO),
+            to_binary(&ReceiveVirtualStake {
+                validator: validator.to_string(),
+            })
+            .unwrap(),
+            Some("k".repeat(contract::MAX_IDEMPOTENCY_KEY_LEN + 1)),
+        )
+        .call(user)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::IdempotencyKeyTooLong(contract::MAX_IDEMPOTENCY_KEY_LEN)
+    );
+}
+
+#[test]
+fn user_pending_txs_created_at() {
+    let owner = "owner";
+    let user = "user1";
+
+    let app = init_app(&[user], &[300]);
+
+    let (vault, _local_staking, cross_staking) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+
+    let validator = "validator";
+    set_active_validators(&cross_staking, &[validator]);
+
+    bond(&vault, user, 300);
+
+    let stake = || {
+        vault
+            .stake_remote(
+                cross_staking.contract_addr.to_string(),
+                coin(50, OSMO),
+                to_binary(&ReceiveVirtualStake {
+                    validator: validator.to_string(),
+                })
+                .unwrap(),
+                None,
+            )
+            .call(user)
+            .unwrap();
+    };
+
+    let first_time = app.block_info().time;
+    stake();
+    let mut block_info = app.block_info();
+    block_info.height += 1;
+    block_info.time = block_info.time.plus_seconds(5);
+    app.set_block(block_info);
+    stake();
+
+    let created_at = |txs: Vec<Tx>| -> Vec<Timestamp> {
+        txs.into_iter()
+            .map(|tx| match tx {
+                InFlightStaking { created_at, .. } => created_at,
+                tx => panic!("unexpected tx {tx}"),
+            })
+            .collect()
+    };
+
+    let txs = vault.user_pending_txs(user.to_owned()).unwrap().txs;
+    assert_eq!(created_at(txs), [first_time, first_time.plus_seconds(5)]);
+
+    // Newest first
+    let txs = vault.all_pending_txs_desc(None, None).unwrap().txs;
+    assert_eq!(created_at(txs), [first_time.plus_seconds(5), first_time]);
+}
+
+#[test]
+fn batch() {
+    let owner = "owner";
+    let user = "user1";
+    let val = "validator";
+
+    let mut app = init_app(&[user], &[300]);
+    add_local_validator(&mut app, val);
+
+    let (vault, _local_staking, cross_staking) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+    set_active_validators(&cross_staking, &[val]);
+
+    let stake_local = VaultOp::StakeLocal {
+        amount: coin(100, OSMO),
+        msg: to_binary(&mesh_native_staking::msg::StakeMsg {
+            validator: Some(val.to_string()),
+        })
+        .unwrap(),
+    };
+    let stake_remote = |amount| VaultOp::StakeRemote {
+        contract: cross_staking.contract_addr.to_string(),
+        amount: coin(amount, OSMO),
+        msg: to_binary(&ReceiveVirtualStake {
+            validator: val.to_string(),
+        })
+        .unwrap(),
+    };
+
+    // Overstaking fails the whole batch
+    let err = vault
+        .batch(vec![
+            VaultOp::Bond {},
+            stake_local.clone(),
+            stake_remote(250),
+        ])
+        .with_funds(&coins(200, OSMO))
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::InsufficientBalance);
+    assert_eq!(
+        vault.account(user.to_owned()).unwrap().bonded,
+        Uint128::zero()
+    );
+    assert_eq!(
+        app.app().wrap().query_balance(user, OSMO).unwrap(),
+        coin(300, OSMO)
+    );
+    assert_eq!(get_last_vault_pending_tx_id(&vault), None);
+
+    // Only one bond, bonding the attached funds
+    let err = vault
+        .batch(vec![VaultOp::Bond {}, VaultOp::Bond {}])
+        .with_funds(&coins(200, OSMO))
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::MultipleBatchBonds);
+
+    let resp = vault
+        .batch(vec![
+            VaultOp::Bond {},
+            stake_local.clone(),
+            stake_remote(150),
+        ])
+        .with_funds(&coins(200, OSMO))
+        .call(user)
+        .unwrap();
+    let actions: Vec<_> = resp
+        .events
+        .iter()
+        .filter(|event| event.ty == "wasm-batch_op")
+        .map(|event| {
+            let attr = |key: &str| {
+                event
+                    .attributes
+                    .iter()
+                    .find(|attr| attr.key == key)
+                    .unwrap()
+                    .value
+                    .clone()
+            };
+            (attr("index"), attr("action"))
+        })
+        .collect();
+    assert_eq!(
+        actions,
+        [
+            ("0".to_owned(), "bond".to_owned()),
+            ("1".to_owned(), "stake_local".to_owned()),
+            ("2".to_owned(), "stake_remote".to_owned()),
+        ]
+    );
+
+    let account = vault.account_details(user.to_owned()).unwrap();
+    assert_eq!(account.bonded, Uint128::new(200));
+    assert_eq!(
+        account.max_lien,
+        ValueRange::new(Uint128::new(100), Uint128::new(150))
+    );
+    assert!(get_last_vault_pending_tx_id(&vault).is_some());
+
+    assert_vault_invariants(&vault);
+}
+
+#[test]
+fn expire_tx() {
+    let owner = "owner";
+    let user = "user1";
+
+    let app = init_app(&[user], &[300]);
+
+    let (vault, _local_staking, cross_staking) =
+        setup_with_tx_timeout(&app, owner, SLASHING_PERCENTAGE, 100, Some(60));
+    assert_eq!(vault.config().unwrap().tx_timeout, Some(60));
+
+    let validator = "validator";
+    set_active_validators(&cross_staking, &[validator]);
+
+    bond(&vault, user, 300);
+
+    // Never acked stake
+    vault
+        .stake_remote(
+            cross_staking.contract_addr.to_string(),
+            coin(100, OSMO),
+            to_binary(&ReceiveVirtualStake {
+                validator: validator.to_string(),
+            })
+            .unwrap(),
+            None,
+        )
+        .call(user)
+        .unwrap();
+    let tx_id = get_last_vault_pending_tx_id(&vault).unwrap();
+    assert_eq!(
+        vault.account(user.to_owned()).unwrap().free,
+        ValueRange::new(Uint128::new(200), Uint128::new(300))
+    );
+
+    // Not expired yet
+    let expires_at = app.block_info().time.plus_seconds(60);
+    let mut block_info = app.block_info();
+    block_info.time = expires_at;
+    app.set_block(block_info);
+    let err = vault.expire_tx(tx_id).call("keeper").unwrap_err();
+    assert_eq!(err, ContractError::TxNotExpired(tx_id, expires_at));
+
+    // Any keeper can expire it after the timeout, freeing the collateral
+    let mut block_info = app.block_info();
+    block_info.time = expires_at.plus_seconds(1);
+    app.set_block(block_info);
+    vault.expire_tx(tx_id).call("keeper").unwrap();
+
+    assert_eq!(get_last_vault_pending_tx_id(&vault), None);
+    assert!(matches!(
+        vault.pending_tx(tx_id).unwrap(),
+        TxStatus::Resolved(TxResult {
+            committed: false,
+            ..
+        })
+    ));
+    assert_eq!(
+        vault.account(user.to_owned()).unwrap().free,
+        ValueRange::new_val(Uint128::new(300))
+    );
+
+    // The stake is rolled back on the lienholder side as well, so it can't be acked anymore
+    assert!(cross_staking
+        .stake(user.to_owned(), validator.to_owned())
+        .unwrap()
+        .stake
+        .high()
+        .is_zero());
+    let err = cross_staking
+        .test_methods_proxy()
+        .test_commit_stake(tx_id)
+        .call("test")
+        .unwrap_err();
+    assert!(err.to_string().contains("not found"), "{err}");
+
+    assert_vault_invariants(&vault);
+}
+
+#[test]
+fn stake_cross_commit_txs() {
+    let owner = "owner";
+    let user = "user1";
+
+    let app = init_app(&[user], &[300]);
+
+    let (vault, _local_staking, cross_staking) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+
+    let validator = "validator";
+    set_active_validators(&cross_staking, &[validator]);
+
+    bond(&vault, user, 300);
+
+    // Three pending stakes
+    let mut txs = vec![];
+    for amount in [100, 50, 20] {
+        vault
+            .stake_remote(
+                cross_staking.contract_addr.to_string(),
+                coin(amount, OSMO),
+                to_binary(&ReceiveVirtualStake {
+                    validator: validator.to_string(),
+                })
+                .unwrap(),
+                None,
+            )
+            .call(user)
+            .unwrap();
+        txs.push(get_last_vault_pending_tx_id(&vault).unwrap());
+    }
+
+    // Only the lienholder can commit them
+    let err = vault
+        .vault_api_proxy()
+        .commit_txs(vec![txs[0], txs[1]])
+        .call(user)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::WrongContractTx(txs[0], Addr::unchecked(user))
+    );
+
+    // Commit the first two at once
+    vault
+        .vault_api_proxy()
+        .commit_txs(vec![txs[0], txs[1]])
+        .call(cross_staking.contract_addr.as_str())
+        .unwrap();
+
+    let pending = vault.all_pending_txs_desc(None, None).unwrap().txs;
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].id(), txs[2]);
+
+    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
+    assert_eq!(
+        claims.claims,
+        [LienResponse {
+            lienholder: cross_staking.contract_addr.to_string(),
+            amount: ValueRange::new(Uint128::new(150), Uint128::new(170))
+        }]
+    );
+
+    // A batch with an already committed tx fails as a whole
+    vault
+        .vault_api_proxy()
+        .rollback_txs(vec![txs[2], txs[0]])
+        .call(cross_staking.contract_addr.as_str())
+        .unwrap_err();
+    assert_eq!(vault.all_pending_txs_desc(None, None).unwrap().txs.len(), 1);
+
+    // Rollback the last one
+    vault
+        .vault_api_proxy()
+        .rollback_txs(vec![txs[2]])
+        .call(cross_staking.contract_addr.as_str())
+        .unwrap();
+
+    assert!(vault
+        .all_pending_txs_desc(None, None)
+        .unwrap()
+        .txs
+        .is_empty());
+    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
+    assert_eq!(
+        claims.claims,
+        [LienResponse {
+            lienholder: cross_staking.contract_addr.to_string(),
+            amount: ValueRange::new_val(Uint128::new(150))
+        }]
+    );
+
+    assert_vault_invariants(&vault);
+}
+
+#[test]
+fn txs_resolved_by_their_lienholder_only() {
+    let owner = "owner";
+    let user = "user1";
+
+    let app = init_app(&[user], &[300]);
+
+    let (vault, _local_staking, cross_staking) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+    // Another lienholder, trying to resolve the txs of the first one
+    let malicious = setup_cross_stake(&app, owner, &vault, SLASHING_PERCENTAGE, 100);
+
+    let validator = "validator";
+    set_active_validators(&cross_staking, &[validator]);
+    set_active_validators(&malicious, &[validator]);
+
+    bond(&vault, user, 300);
+
+    let mut txs = vec![];
+    for contract in [&cross_staking, &malicious] {
+        vault
+            .stake_remote(
+                contract.contract_addr.to_string(),
+                coin(100, OSMO),
+                to_binary(&ReceiveVirtualStake {
+                    validator: validator.to_string(),
+                })
+                .unwrap(),
+                None,
+            )
+            .call(user)
+            .unwrap();
+        txs.push(get_last_vault_pending_tx_id(&vault).unwrap());
+    }
+
+    // Neither the other lienholder nor the user can commit or roll back the tx
+    for sender in [malicious.contract_addr.as_str(), user] {
+        let expected = ContractError::WrongContractTx(txs[0], Addr::unchecked(sender));
+        let err = vault
+            .vault_api_proxy()
+            .commit_tx(txs[0])
+            .call(sender)
+            .unwrap_err();
+        assert_eq!(err, expected);
+        let err = vault
+            .vault_api_proxy()
+            .rollback_tx(txs[0])
+            .call(sender)
+            .unwrap_err();
+        assert_eq!(err, expected);
+        let err = vault
+            .vault_api_proxy()
+            .rollback_txs(vec![txs[0]])
+            .call(sender)
+            .unwrap_err();
+        assert_eq!(err, expected);
+    }
+
+    // Not even along with one of its own txs
+    let err = vault
+        .vault_api_proxy()
+        .commit_txs(vec![txs[1], txs[0]])
+        .call(malicious.contract_addr.as_str())
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::WrongContractTx(txs[0], malicious.contract_addr.clone())
+    );
+    assert_eq!(vault.all_pending_txs_desc(None, None).unwrap().txs.len(), 2);
+
+    // Each lienholder resolves its own tx
+    vault
+        .vault_api_proxy()
+        .commit_tx(txs[0])
+        .call(cross_staking.contract_addr.as_str())
+        .unwrap();
+    vault
+        .vault_api_proxy()
+        .rollback_tx(txs[1])
+        .call(malicious.contract_addr.as_str())
+        .unwrap();
+
+    // Resolved txs can't be resolved again
+    vault
+        .vault_api_proxy()
+        .rollback_tx(txs[0])
+        .call(cross_staking.contract_addr.as_str())
+        .unwrap_err();
+
+    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
+    assert_eq!(
+        claims.claims,
+        [
+            LienResponse {
+                lienholder: cross_staking.contract_addr.to_string(),
+                amount: ValueRange::new_val(Uint128::new(100))
+            },
+            LienResponse {
+                lienholder: malicious.contract_addr.to_string(),
+                amount: ValueRange::new_val(Uint128::zero())
+            }
+        ]
+    );
+
+    assert_vault_invariants(&vault);
+}
+
+#[test]
+fn transfer_cross_stake() {
+    let owner = "owner";
+    let user = "user1";
+    let recipient = "user2";
+    let local_validator = "local";
+
+    let mut app = init_app(&[user], &[300]);
+    add_local_validator(&mut app, local_validator);
+
+    let (vault, _, cross_staking) = setup(&app, owner, 10, 100);
+
+    let validator = "validator";
+    set_active_validators(&cross_staking, &[validator]);
+
+    bond(&vault, user, 300);
+    stake_locally(&vault, user, 200, local_validator).unwrap();
+    stake_remotely(&vault, &cross_staking, user, &[validator], &[200]);
+
+    // The remaining collateral must still cover the local stake
+    let err = vault
+        .vault_api_proxy()
+        .transfer_cross_stake(user.to_owned(), recipient.to_owned(), coin(150, OSMO))
+        .call(cross_staking.contract_addr.as_str())
+        .unwrap_err();
+    assert_eq!(err, ContractError::InsufficientBalance);
+
+    // Only the lienholder can move its lien
+    let err = vault
+        .vault_api_proxy()
+        .transfer_cross_stake(user.to_owned(), recipient.to_owned(), coin(100, OSMO))
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::UnknownLienholder);
+
+    vault
+        .vault_api_proxy()
+        .transfer_cross_stake(user.to_owned(), recipient.to_owned(), coin(100, OSMO))
+        .call(cross_staking.contract_addr.as_str())
+        .unwrap();
+
+    // Lien and collateral moved to the recipient
+    let acc = vault.account_details(user.to_owned()).unwrap();
+    assert_eq!(acc.bonded.u128(), 200);
+    assert_eq!(acc.max_lien, ValueRange::new_val(Uint128::new(200)));
+    assert_eq!(acc.total_slashable, ValueRange::new_val(Uint128::new(30)));
+    let claim = vault
+        .claim(user.to_owned(), cross_staking.contract_addr.to_string())
+        .unwrap();
+    assert_eq!(claim.amount.val().unwrap().u128(), 100);
+
+    let acc = vault.account_details(recipient.to_owned()).unwrap();
+    assert_eq!(acc.bonded.u128(), 100);
+    assert_eq!(acc.free, ValueRange::new_val(Uint128::zero()));
+    assert_eq!(acc.max_lien, ValueRange::new_val(Uint128::new(100)));
+    assert_eq!(acc.total_slashable, ValueRange::new_val(Uint128::new(10)));
+    let claim = vault
+        .claim(
+            recipient.to_owned(),
+            cross_staking.contract_addr.to_string(),
+        )
+        .unwrap();
+    assert_eq!(claim.amount.val().unwrap().u128(), 100);
+
+    assert_vault_invariants(&vault);
+}
+
+#[test]
+fn release_wrong_lien_kind() {
+    let owner = "owner";
+    let user = "user1";
+    let local_validator = "local";
+
+    let mut app = init_app(&[user], &[400]);
+    add_local_validator(&mut app, local_validator);
+
+    let (vault, native_staking, cross_staking) = setup(&app, owner, 10, 100);
+
+    let validator = "validator";
+    set_active_validators(&cross_staking, &[validator]);
+
+    bond(&vault, user, 300);
+    stake_locally(&vault, user, 100, local_validator).unwrap();
+    stake_remotely(&vault, &cross_staking, user, &[validator], &[100]);
+
+    // The local staking contract cannot release through the cross entry point
+    let err = vault
+        .vault_api_proxy()
+        .release_cross_stake(user.to_owned(), coin(50, OSMO))
+        .call(native_staking.contract_addr.as_str())
+        .unwrap_err();
+    assert_eq!(err, ContractError::WrongLienKind(LienKind::Cross));
+
+    // Nor the cross staking contract through the local one
+    app.app_mut()
+        .send_tokens(
+            Addr::unchecked(user),
+            cross_staking.contract_addr.clone(),
+            &coins(50, OSMO),
+        )
+        .unwrap();
+    let err = vault
+        .vault_api_proxy()
+        .release_local_stake(user.to_owned())
+        .with_funds(&coins(50, OSMO))
+        .call(cross_staking.contract_addr.as_str())
+        .unwrap_err();
+    assert_eq!(err, ContractError::WrongLienKind(LienKind::Local));
+
+    // Both liens are untouched
+    let claim = vault
+        .claim(user.to_owned(), native_staking.contract_addr.to_string())
+        .unwrap();
+    assert_eq!(claim.amount.val().unwrap().u128(), 100);
+    let claim = vault
+        .claim(user.to_owned(), cross_staking.contract_addr.to_string())
+        .unwrap();
+    assert_eq!(claim.amount.val().unwrap().u128(), 100);
+}
+
+#[test]
+fn release_normalizes_owner() {
+    let owner = "owner";
+    let user = "user1";
+
+    let app = init_app(&[user], &[300]);
+
+    let (vault, _, cross_staking) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+
+    let validator = "validator";
+    set_active_validators(&cross_staking, &[validator]);
+
+    bond(&vault, user, 300);
+    stake_remotely(&vault, &cross_staking, user, &[validator], &[100]);
+
+    // The lienholder passes the owner in another valid form
+    vault
+        .vault_api_proxy()
+        .release_cross_stake(user.to_uppercase(), coin(60, OSMO))
+        .call(cross_staking.contract_addr.as_str())
+        .unwrap();
+    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
+    assert_eq!(
+        claims.claims,
+        [LienResponse {
+            lienholder: cross_staking.contract_addr.to_string(),
+            amount: ValueRange::new_val(Uint128::new(40)),
+        }]
+    );
+
+    vault
+        .vault_api_proxy()
+        .release_cross_stake_to(user.to_uppercase(), user.to_owned(), coin(40, OSMO))
+        .call(cross_staking.contract_addr.as_str())
+        .unwrap();
+    assert_eq!(
+        vault.account(user.to_owned()).unwrap().bonded,
+        Uint128::new(260)
+    );
+
+    // Invalid addresses are rejected
+    vault
+        .vault_api_proxy()
+        .release_cross_stake("u".to_owned(), coin(10, OSMO))
+        .call(cross_staking.contract_addr.as_str())
+        .unwrap_err();
+
+    assert_vault_invariants(&vault);
+}
+
+#[test]
+fn can_release() {
+    let owner = "owner";
+    let user = "user1";
+    let local_validator = "local";
+
+    let mut app = init_app(&[user], &[400]);
+    add_local_validator(&mut app, local_validator);
+
+    let (vault, native_staking, cross_staking) = setup(&app, owner, 10, 100);
+
+    let validator = "validator";
+    set_active_validators(&cross_staking, &[validator]);
+
+    bond(&vault, user, 300);
+    stake_locally(&vault, user, 100, local_validator).unwrap();
+    stake_remotely(&vault, &cross_staking, user, &[validator], &[100]);
+
+    let cross = cross_staking.contract_addr.to_string();
+    let can_release = |lienholder: &str, amount: Coin| {
+        vault
+            .can_release(user.to_owned(), lienholder.to_owned(), amount)
+            .unwrap()
+    };
+
+    assert!(can_release(&cross, coin(100, OSMO)));
+    // The lien is too small
+    assert!(!can_release(&cross, coin(101, OSMO)));
+    // Wrong denom, zero amount, or no cross lien
+    assert!(!can_release(&cross, coin(50, STAR)));
+    assert!(!can_release(&cross, coin(0, OSMO)));
+    assert!(!can_release(
+        native_staking.contract_addr.as_str(),
+        coin(50, OSMO)
+    ));
+
+    // Consistent with the actual release
+    vault
+        .vault_api_proxy()
+        .release_cross_stake(user.to_owned(), coin(101, OSMO))
+        .call(&cross)
+        .unwrap_err();
+    vault
+        .vault_api_proxy()
+        .release_cross_stake(user.to_owned(), coin(60, OSMO))
+        .call(&cross)
+        .unwrap();
+
+    assert!(can_release(&cross, coin(40, OSMO)));
+    assert!(!can_release(&cross, coin(41, OSMO)));
+}
+
+#[test]
+fn min_release() {
+    let owner = "owner";
+    let user = "user1";
+
+    let app = init_app(&[user], &[300]);
+
+    let (vault, _native_staking, cross_staking) = setup(&app, owner, 10, 100);
+
+    let validator = "validator";
+    set_active_validators(&cross_staking, &[validator]);
+
+    bond(&vault, user, 300);
+    stake_remotely(&vault, &cross_staking, user, &[validator], &[100]);
+
+    let err = vault
+        .update_min_release(Some(Uint128::new(50)))
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    vault
+        .update_min_release(Some(Uint128::new(50)))
+        .call(owner)
+        .unwrap();
+    assert_eq!(vault.config().unwrap().min_release, Some(Uint128::new(50)));
+
+    let cross = cross_staking.contract_addr.to_string();
+    let can_release = |amount: u128| {
+        vault
+            .can_release(user.to_owned(), cross.clone(), coin(amount, OSMO))
+            .unwrap()
+    };
+
+    // Small releases are rejected
+    assert!(!can_release(20));
+    let err = vault
+        .vault_api_proxy()
+        .release_cross_stake(user.to_owned(), coin(20, OSMO))
+        .call(&cross)
+        .unwrap_err();
+    assert_eq!(err, ContractError::ReleaseTooSmall(Uint128::new(50)));
+
+    // Unless the released tokens are done unbonding
+    vault
+        .vault_api_proxy()
+        .release_matured_cross_stake(user.to_owned(), coin(20, OSMO), None)
+        .call(&cross)
+        .unwrap();
+
+    assert!(can_release(60));
+    vault
+        .vault_api_proxy()
+        .release_cross_stake(user.to_owned(), coin(60, OSMO))
+        .call(&cross)
+        .unwrap();
+
+    // The final release is allowed below the min
+    assert!(!can_release(10));
+    assert!(can_release(20));
+    vault
+        .vault_api_proxy()
+        .release_cross_stake(user.to_owned(), coin(20, OSMO))
+        .call(&cross)
+        .unwrap();
+    let claim = vault.claim(user.to_owned(), cross).unwrap();
+    assert_eq!(claim.amount, ValueRange::new_val(Uint128::zero()));
+
+    assert_vault_invariants(&vault);
+}
+
+#[test]
+fn total_free_collateral() {
+    let owner = "owner";
+    let users = ["user1", "user2", "user3"];
+    let local_validator = "local";
+
+    let mut app = init_app(&users, &[400, 500, 600]);
+    add_local_validator(&mut app, local_validator);
+
+    let (vault, _native_staking, cross_staking) = setup(&app, owner, 10, 100);
+
+    let validator = "validator";
+    set_active_validators(&cross_staking, &[validator]);
+
+    let assert_total_matches = || {
+        let sum: ValueRange<Uint128> = users
+            .iter()
+            .filter_map(|user| vault.account(user.to_string()).ok())
+            .map(|account| account.free)
+            .sum();
+        assert_eq!(vault.total_free_collateral().unwrap(), sum);
+        sum
+    };
+
+    assert_eq!(assert_total_matches(), ValueRange::new_val(Uint128::zero()));
+
+    bond(&vault, users[0], 400);
+    bond(&vault, users[1], 500);
+    bond(&vault, users[2], 600);
+    assert_eq!(
+        assert_total_matches(),
+        ValueRange::new_val(Uint128::new(1500))
+    );
+
+    stake_locally(&vault, users[0], 100, local_validator).unwrap();
+    stake_remotely(&vault, &cross_staking, users[1], &[validator], &[200]);
+    assert_eq!(
+        assert_total_matches(),
+        ValueRange::new_val(Uint128::new(1200))
+    );
+
+    // A pending remote stake is only taken out of the low end
+    vault
+        .stake_remote(
+            cross_staking.contract_addr.to_string(),
+            coin(300, OSMO),
+            to_binary(&ReceiveVirtualStake {
+                validator: validator.to_string(),
+            })
+            .unwrap(),
+            None,
+        )
+        .call(users[2])
+        .unwrap();
+    assert_eq!(
+        assert_total_matches(),
+        ValueRange::new(Uint128::new(900), Uint128::new(1200))
+    );
+
+    let tx_id = get_last_external_staking_pending_tx_id(&cross_staking).unwrap();
+    cross_staking
+        .test_methods_proxy()
+        .test_commit_stake(tx_id)
+        .call("test")
+        .unwrap();
+    assert_eq!(
+        assert_total_matches(),
+        ValueRange::new_val(Uint128::new(900))
+    );
+
+    vault.unbond(coin(150, OSMO)).call(users[0]).unwrap();
+    vault
+        .vault_api_proxy()
+        .release_cross_stake(users[1].to_owned(), coin(50, OSMO))
+        .call(cross_staking.contract_addr.as_str())
+        .unwrap();
+    assert_eq!(
+        assert_total_matches(),
+        ValueRange::new_val(Uint128::new(800))
+    );
 }
 
 #[test]
@@ -1397,11 +3696,272 @@ fn multiple_stakes() {
                 validator: validator.to_string(),
             })
             .unwrap(),
+            None,
+        )
+        .call(user)
+        .unwrap_err();
+
+    assert_eq!(err, ContractError::InsufficientBalance);
+
+    assert_vault_invariants(&vault);
+}
+
+#[test]
+fn voting_power_report() {
+    let owner = "owner";
+    let user = "user1";
+    let local_validator = "local";
+
+    let mut app = init_app(&[user], &[1000]);
+    add_local_validator(&mut app, local_validator);
+
+    let (vault, local_staking, cross_staking) = setup(&app, owner, 10, 100);
+
+    let validator = "validator";
+    set_active_validators(&cross_staking, &[validator]);
+
+    bond(&vault, user, 1000);
+
+    // Everything sits idle in the vault
+    assert_eq!(
+        vault.voting_power_report(user.to_owned()).unwrap(),
+        VotingPowerReportResponse {
+            denom: OSMO.to_owned(),
+            unstaked: Uint128::new(1000),
+            local_staked: Uint128::zero(),
+            remote_staked: Uint128::zero(),
+            proxy: None,
+        }
+    );
+
+    stake_locally(&vault, user, 300, local_validator).unwrap();
+    stake_remotely(&vault, &cross_staking, user, &[validator], &[200]);
+
+    let proxy = proxy_for_user(&local_staking, user, &app);
+    assert_eq!(
+        vault.voting_power_report(user.to_owned()).unwrap(),
+        VotingPowerReportResponse {
+            denom: OSMO.to_owned(),
+            unstaked: Uint128::new(500),
+            local_staked: Uint128::new(300),
+            remote_staked: Uint128::new(200),
+            proxy: Some(proxy.contract_addr.to_string()),
+        }
+    );
+
+    // Remote stakes now also use the locally staked collateral
+    stake_remotely(&vault, &cross_staking, user, &[validator], &[700]);
+
+    assert_eq!(
+        vault.voting_power_report(user.to_owned()).unwrap(),
+        VotingPowerReportResponse {
+            denom: OSMO.to_owned(),
+            unstaked: Uint128::zero(),
+            local_staked: Uint128::new(300),
+            remote_staked: Uint128::new(900),
+            proxy: Some(proxy.contract_addr.to_string()),
+        }
+    );
+}
+
+#[test]
+fn cw20_collateral() {
+    let owner = "owner";
+    let user = "user1";
+
+    let app = init_app(&[], &[]);
+
+    let cw20_code = cw20_mock::multitest_utils::CodeId::store_code(&app);
+    let local_staking_code = local_staking_mock::multitest_utils::CodeId::store_code(&app);
+    let vault_code = contract::multitest_utils::CodeId::store_code(&app);
+
+    let cw20 = cw20_code
+        .instantiate(vec![(user.to_owned(), Uint128::new(1000))])
+        .with_label("Cw20")
+        .call(owner)
+        .unwrap();
+    let cw20_addr = cw20.contract_addr.to_string();
+
+    let local_staking_inst_msg = local_staking_mock::InstantiateMsg {
+        cw20: cw20_addr.clone(),
+    };
+    let staking_init_info = StakingInitInfo {
+        admin: None,
+        code_id: local_staking_code.code_id(),
+        msg: to_binary(&local_staking_inst_msg).unwrap(),
+        label: None,
+    };
+    let vault = vault_code
+        .instantiate(
+            CollateralType::Cw20(cw20.contract_addr.clone()),
+            staking_init_info,
+            None,
+        )
+        .with_label("Vault")
+        .call(owner)
+        .unwrap();
+
+    let config = vault.config().unwrap();
+    assert_eq!(config.denom, cw20_addr);
+    assert_eq!(vault.denom().unwrap(), cw20_addr);
+    let local_staking = local_staking_mock::multitest_utils::LocalStakingMockProxy::new(
+        Addr::unchecked(config.local_staking),
+        &app,
+    );
+
+    // Native bonding is not possible
+    let err = vault.bond().call(user).unwrap_err();
+    assert_eq!(err, ContractError::Cw20Collateral);
+
+    // Bonding through the cw20 contract
+    cw20.send(
+        vault.contract_addr.to_string(),
+        Uint128::new(300),
+        to_binary(&VaultCw20HookMsg::Bond {}).unwrap(),
+    )
+    .call(user)
+    .unwrap();
+
+    let acc = vault.account(user.to_owned()).unwrap();
+    assert_eq!(acc.denom, cw20_addr);
+    assert_eq!(acc.bonded.u128(), 300);
+    assert_eq!(cw20.balance(user.to_owned()).unwrap().balance.u128(), 700);
+    assert_eq!(
+        cw20.balance(vault.contract_addr.to_string())
+            .unwrap()
+            .balance
+            .u128(),
+        300
+    );
+
+    // Only the collateral token can call the receive hook
+    let err = vault
+        .receive(
+            user.to_owned(),
+            Uint128::new(100),
+            to_binary(&VaultCw20HookMsg::Bond {}).unwrap(),
+        )
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::UnexpectedDenom(cw20_addr.clone()));
+
+    // Local staking sends the tokens to the local staking contract
+    vault
+        .stake_local(coin(100, &cw20_addr), to_binary(&()).unwrap())
+        .call(user)
+        .unwrap();
+
+    assert_eq!(
+        local_staking.stake(user.to_owned()).unwrap().stake.u128(),
+        100
+    );
+    assert_eq!(
+        cw20.balance(local_staking.contract_addr.to_string())
+            .unwrap()
+            .balance
+            .u128(),
+        100
+    );
+    let claim = vault
+        .claim(user.to_owned(), local_staking.contract_addr.to_string())
+        .unwrap();
+    assert_eq!(claim.amount.val().unwrap().u128(), 100);
+
+    // Remote staking doesn't move tokens, amounts are denominated in the cw20 address
+    let cross_staking_code =
+        mesh_external_staking::contract::multitest_utils::CodeId::store_code(&app);
+    let cross_staking = cross_staking_code
+        .instantiate(
+            cw20_addr.clone(),
+            vec![RewardDenom::from_denom(STAR)],
+            vault.contract_addr.to_string(),
+            100,
+            AuthorizedEndpoint::new("connection-2", "wasm-osmo1foobarbaz"),
+            Decimal::percent(SLASHING_PERCENTAGE),
+        )
+        .call(owner)
+        .unwrap();
+    let validator = "validator";
+    set_active_validators(&cross_staking, &[validator]);
+
+    let err = vault
+        .stake_remote(
+            cross_staking.contract_addr.to_string(),
+            coin(50, OSMO),
+            to_binary(&ReceiveVirtualStake {
+                validator: validator.to_string(),
+            })
+            .unwrap(),
+            None,
         )
         .call(user)
         .unwrap_err();
+    assert_eq!(err, ContractError::UnexpectedDenom(cw20_addr.clone()));
+
+    vault
+        .stake_remote(
+            cross_staking.contract_addr.to_string(),
+            coin(50, &cw20_addr),
+            to_binary(&ReceiveVirtualStake {
+                validator: validator.to_string(),
+            })
+            .unwrap(),
+            None,
+        )
+        .call(user)
+        .unwrap();
+    let last_external_staking_tx = get_last_external_staking_pending_tx_id(&cross_staking).unwrap();
+    cross_staking
+        .test_methods_proxy()
+        .test_commit_stake(last_external_staking_tx)
+        .call("test")
+        .unwrap();
+
+    let claim = vault
+        .claim(user.to_owned(), cross_staking.contract_addr.to_string())
+        .unwrap();
+    assert_eq!(claim.amount.val().unwrap().u128(), 50);
+    assert_eq!(
+        cw20.balance(vault.contract_addr.to_string())
+            .unwrap()
+            .balance
+            .u128(),
+        200
+    );
+
+    // Local unstaking sends the tokens back to the vault, releasing the claim
+    local_staking.unstake(Uint128::new(40)).call(user).unwrap();
+
+    let claim = vault
+        .claim(user.to_owned(), local_staking.contract_addr.to_string())
+        .unwrap();
+    assert_eq!(claim.amount.val().unwrap().u128(), 60);
+    assert_eq!(
+        cw20.balance(vault.contract_addr.to_string())
+            .unwrap()
+            .balance
+            .u128(),
+        240
+    );
+
+    // Unbonding transfers the tokens back to the user
+    let acc = vault.account(user.to_owned()).unwrap();
+    assert_eq!(acc.free, ValueRange::new_val(Uint128::new(240)));
+
+    vault.unbond(coin(240, &cw20_addr)).call(user).unwrap();
+
+    let acc = vault.account(user.to_owned()).unwrap();
+    assert_eq!(acc.bonded.u128(), 60);
+    assert_eq!(cw20.balance(user.to_owned()).unwrap().balance.u128(), 940);
+    assert_eq!(
+        cw20.balance(vault.contract_addr.to_string())
+            .unwrap()
+            .balance
+            .u128(),
+        0
+    );
 
-    assert_eq!(err, ContractError::InsufficentBalance);
+    assert_vault_invariants(&vault);
 }
 
 #[test]
@@ -1416,16 +3976,16 @@ fn all_users_fetching() {
 
     // No users should show up no matter of collateral flag
 
-    let accounts = vault.all_accounts(false, None, None).unwrap();
+    let accounts = vault.all_accounts(false, None, None, None, None).unwrap();
     assert_eq!(accounts.accounts, []);
 
-    let accounts = vault.all_accounts(true, None, None).unwrap();
+    let accounts = vault.all_accounts(true, None, None, None, None).unwrap();
     assert_eq!(accounts.accounts, []);
 
     // When user bond some collateral, he should be visible
     bond(&vault, users[0], 100);
 
-    let accounts = vault.all_accounts(false, None, None).unwrap();
+    let accounts = vault.all_accounts(false, None, None, None, None).unwrap();
     assert_eq!(
         accounts.accounts,
         [AllAccountsResponseItem {
@@ -1438,7 +3998,7 @@ fn all_users_fetching() {
         }]
     );
 
-    let accounts = vault.all_accounts(true, None, None).unwrap();
+    let accounts = vault.all_accounts(true, None, None, None, None).unwrap();
     assert_eq!(
         accounts.accounts,
         [AllAccountsResponseItem {
@@ -1454,7 +4014,7 @@ fn all_users_fetching() {
     // Second user bonds - we want to see him
     bond(&vault, users[1], 200);
 
-    let accounts = vault.all_accounts(false, None, None).unwrap();
+    let accounts = vault.all_accounts(false, None, None, None, None).unwrap();
     assert_eq!(
         accounts.accounts,
         [
@@ -1477,7 +4037,7 @@ fn all_users_fetching() {
         ]
     );
 
-    let accounts = vault.all_accounts(true, None, None).unwrap();
+    let accounts = vault.all_accounts(true, None, None, None, None).unwrap();
     assert_eq!(
         accounts.accounts,
         [
@@ -1504,7 +4064,7 @@ fn all_users_fetching() {
 
     vault.unbond(coin(50, OSMO)).call(users[0]).unwrap();
 
-    let accounts = vault.all_accounts(false, None, None).unwrap();
+    let accounts = vault.all_accounts(false, None, None, None, None).unwrap();
     assert_eq!(
         accounts.accounts,
         [
@@ -1527,7 +4087,7 @@ fn all_users_fetching() {
         ]
     );
 
-    let accounts = vault.all_accounts(true, None, None).unwrap();
+    let accounts = vault.all_accounts(true, None, None, None, None).unwrap();
     assert_eq!(
         accounts.accounts,
         [
@@ -1553,7 +4113,7 @@ fn all_users_fetching() {
     // Unbonding all the collateral hides the user when the collateral flag is set
     vault.unbond(coin(200, OSMO)).call(users[1]).unwrap();
 
-    let accounts = vault.all_accounts(false, None, None).unwrap();
+    let accounts = vault.all_accounts(false, None, None, None, None).unwrap();
     assert_eq!(
         accounts.accounts,
         [
@@ -1576,7 +4136,7 @@ fn all_users_fetching() {
         ]
     );
 
-    let accounts = vault.all_accounts(true, None, None).unwrap();
+    let accounts = vault.all_accounts(true, None, None, None, None).unwrap();
     assert_eq!(
         accounts.accounts,
         [AllAccountsResponseItem {
@@ -1590,6 +4150,259 @@ fn all_users_fetching() {
     );
 }
 
+#[test]
+fn all_accounts_by_bonded() {
+    let owner = "owner";
+    let users = ["user1", "user2", "user3"];
+    let collaterals = [300, 300, 300];
+
+    let app = init_app(&users, &collaterals);
+
+    let (vault, _, _) = setup(&app, owner, 0, 100);
+
+    bond(&vault, users[0], 200);
+    bond(&vault, users[1], 300);
+    bond(&vault, users[2], 100);
+
+    let bonded_desc = |start_after: Option<(&str, u128)>| {
+        vault
+            .all_accounts(
+                false,
+                start_after.map(|(user, _)| user.to_owned()),
+                None,
+                Some(AccountsOrderBy::BondedDesc),
+                start_after.map(|(_, bonded)| Uint128::new(bonded)),
+            )
+            .unwrap()
+            .accounts
+            .into_iter()
+            .map(|item| (item.user, item.account.bonded.u128()))
+            .collect::<Vec<_>>()
+    };
+
+    let accounts = bonded_desc(None);
+    assert_eq!(
+        accounts,
+        [
+            (users[1].to_owned(), 300),
+            (users[0].to_owned(), 200),
+            (users[2].to_owned(), 100)
+        ]
+    );
+
+    // Paging with the `(bonded, address)` cursor
+    let accounts = bonded_desc(Some((users[1], 300)));
+    assert_eq!(
+        accounts,
+        [(users[0].to_owned(), 200), (users[2].to_owned(), 100)]
+    );
+    let accounts = bonded_desc(Some((users[0], 200)));
+    assert_eq!(accounts, [(users[2].to_owned(), 100)]);
+    let accounts = bonded_desc(Some((users[2], 100)));
+    assert_eq!(accounts, []);
+
+    // The index follows the collateral changes
+    vault.unbond(coin(250, OSMO)).call(users[1]).unwrap();
+    let accounts = bonded_desc(None);
+    assert_eq!(
+        accounts,
+        [
+            (users[0].to_owned(), 200),
+            (users[2].to_owned(), 100),
+            (users[1].to_owned(), 50)
+        ]
+    );
+
+    // The cursor amount defaults to the current collateral of the account
+    let accounts = vault
+        .all_accounts(
+            false,
+            Some(users[2].to_owned()),
+            None,
+            Some(AccountsOrderBy::BondedDesc),
+            None,
+        )
+        .unwrap()
+        .accounts;
+    assert_eq!(accounts.len(), 1);
+    assert_eq!(accounts[0].user, users[1]);
+}
+
+#[test]
+fn accounts_by_free_collateral() {
+    let owner = "owner";
+    let users = ["user1", "user2", "user3"];
+    let val = "validator";
+
+    let mut app = init_app(&users, &[400, 400, 400]);
+    add_local_validator(&mut app, val);
+
+    let (vault, _, _) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+
+    let low_free = |start_after: Option<&str>, limit| {
+        let resp = vault
+            .accounts_by_free_collateral(Uint128::new(100), start_after.map(str::to_owned), limit)
+            .unwrap();
+        let accounts: Vec<_> = resp
+            .accounts
+            .into_iter()
+            .map(|item| (item.user, item.account.free.low().u128()))
+            .collect();
+        (accounts, resp.cursor)
+    };
+
+    for user in users {
+        bond(&vault, user, 300);
+    }
+    assert_eq!(low_free(None, None), (vec![], None));
+
+    // Staking reduces the free collateral below the threshold, inclusive
+    stake_locally(&vault, users[1], 250, val).unwrap();
+    stake_locally(&vault, users[2], 200, val).unwrap();
+    assert_eq!(
+        low_free(None, None),
+        (
+            vec![(users[1].to_owned(), 50), (users[2].to_owned(), 100)],
+            None
+        )
+    );
+
+    // Scanning is bounded by the limit, continuing after the cursor
+    assert_eq!(
+        low_free(None, Some(2)),
+        (vec![(users[1].to_owned(), 50)], Some(users[1].to_owned()))
+    );
+    assert_eq!(
+        low_free(Some(users[1]), Some(2)),
+        (vec![(users[2].to_owned(), 100)], None)
+    );
+
+    // Bonding more lifts the free collateral above the threshold
+    bond(&vault, users[1], 100);
+    assert_eq!(
+        low_free(None, None),
+        (vec![(users[2].to_owned(), 100)], None)
+    );
+
+    // Unbonding lowers it back
+    vault.unbond(coin(300, OSMO)).call(users[0]).unwrap();
+    assert_eq!(
+        low_free(None, None),
+        (vec![(users[2].to_owned(), 100)], None)
+    );
+    vault.unbond(coin(150, OSMO)).call(users[1]).unwrap();
+    assert_eq!(
+        low_free(None, None),
+        (
+            vec![(users[1].to_owned(), 0), (users[2].to_owned(), 100)],
+            None
+        )
+    );
+}
+
+#[test]
+fn accounts_snapshots() {
+    let owner = "owner";
+    let users = ["user1", "user2", "user3"];
+
+    let app = init_app(&users, &[300, 300, 300]);
+
+    let (vault, _, _) = setup(&app, owner, 0, 100);
+
+    bond(&vault, users[0], 100);
+    bond(&vault, users[1], 200);
+
+    // Only the admin can take snapshots
+    let err = vault.create_snapshot().call(users[0]).unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    vault.create_snapshot().call(owner).unwrap();
+    let snapshot_id = 1;
+
+    // Mutate balances after the snapshot
+    bond(&vault, users[0], 50);
+    vault.unbond(coin(150, OSMO)).call(users[1]).unwrap();
+    bond(&vault, users[2], 300);
+    vault.unbond(coin(20, OSMO)).call(users[0]).unwrap();
+
+    // Current state changed...
+    assert_eq!(
+        vault.account(users[0].to_owned()).unwrap().bonded,
+        Uint128::new(130)
+    );
+
+    // ...but the snapshot didn't
+    let account = vault
+        .snapshot_account(snapshot_id, users[0].to_owned())
+        .unwrap();
+    assert_eq!(account.bonded, Uint128::new(100));
+    let account = vault
+        .snapshot_account(snapshot_id, users[2].to_owned())
+        .unwrap();
+    assert_eq!(account.bonded, Uint128::zero());
+
+    let snapshot = vault.snapshot_accounts(snapshot_id, None, None).unwrap();
+    assert_eq!(snapshot.total_collateral, Uint128::new(300));
+    assert_eq!(
+        snapshot.accounts,
+        [
+            SnapshotAccountsResponseItem {
+                user: users[0].to_owned(),
+                bonded: Uint128::new(100),
+            },
+            SnapshotAccountsResponseItem {
+                user: users[1].to_owned(),
+                bonded: Uint128::new(200),
+            },
+        ]
+    );
+
+    // A second snapshot reflects the new state, leaving the first one untouched
+    vault.create_snapshot().call(owner).unwrap();
+    vault.unbond(coin(30, OSMO)).call(users[0]).unwrap();
+
+    let snapshot = vault
+        .snapshot_accounts(snapshot_id + 1, None, None)
+        .unwrap();
+    assert_eq!(snapshot.total_collateral, Uint128::new(480));
+    assert_eq!(
+        snapshot.accounts,
+        [
+            SnapshotAccountsResponseItem {
+                user: users[0].to_owned(),
+                bonded: Uint128::new(130),
+            },
+            SnapshotAccountsResponseItem {
+                user: users[1].to_owned(),
+                bonded: Uint128::new(50),
+            },
+            SnapshotAccountsResponseItem {
+                user: users[2].to_owned(),
+                bonded: Uint128::new(300),
+            },
+        ]
+    );
+    let account = vault
+        .snapshot_account(snapshot_id, users[0].to_owned())
+        .unwrap();
+    assert_eq!(account.bonded, Uint128::new(100));
+
+    // Only a bounded number of snapshots is kept
+    for _ in 0..MAX_LIVE_SNAPSHOTS {
+        vault.create_snapshot().call(owner).unwrap();
+    }
+    let err = vault
+        .snapshot_account(snapshot_id, users[0].to_owned())
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        ContractError::Std(StdError::GenericErr { .. })
+    ));
+    assert!(err
+        .to_string()
+        .contains(&ContractError::SnapshotNotFound(snapshot_id).to_string()));
+}
+
 /// Scenario 1:
 /// https://github.com/osmosis-labs/mesh-security/blob/main/docs/ibc/Slashing.md#scenario-1-slashed-delegator-has-free-collateral-on-the-vault
 #[test]
@@ -1714,6 +4527,13 @@ fn cross_slash_scenario_1() {
         .stake(user.to_string(), validator2.to_string())
         .unwrap();
     assert_eq!(cross_stake2.stake, ValueRange::new_val(Uint128::new(50))); // no slashing
+
+    // Accounting is still consistent with the liens
+    let report = vault.check_invariants(user.to_owned()).unwrap();
+    assert_eq!(report.violations, Vec::<String>::new());
+    assert_eq!(report.liens_max, acc_details.max_lien);
+
+    assert_vault_invariants(&vault);
 }
 
 /// Scenario 2:
@@ -1822,6 +4642,8 @@ fn cross_slash_scenario_2() {
         .stake(user.to_string(), validator1.to_string())
         .unwrap();
     assert_eq!(cross_stake1.stake, ValueRange::new_val(Uint128::new(180))); // 10% slashed
+
+    assert_vault_invariants(&vault);
 }
 
 /// Scenario 3:
@@ -1930,6 +4752,8 @@ fn cross_slash_scenario_3() {
         .stake(user.to_string(), validator1.to_string())
         .unwrap();
     assert_eq!(cross_stake1.stake, ValueRange::new_val(Uint128::new(135))); // 10% slashed
+
+    assert_vault_invariants(&vault);
 }
 
 /// Scenario 4:
@@ -2001,7 +4825,7 @@ fn cross_slash_scenario_4() {
 
     // Cross stake
     let cross_stake1 = cross_staking_1
-        .stakes(user.to_string(), None, None)
+        .stakes(user.to_string(), None, None, None, None, None)
         .unwrap();
     assert_eq!(
         cross_stake1.stakes,
@@ -2012,7 +4836,7 @@ fn cross_slash_scenario_4() {
     );
 
     let cross_stake2 = cross_staking_2
-        .stakes(user.to_string(), None, None)
+        .stakes(user.to_string(), None, None, None, None, None)
         .unwrap();
     assert_eq!(
         cross_stake2.stakes,
@@ -2064,7 +4888,7 @@ fn cross_slash_scenario_4() {
 
     // Cross stake
     let cross_stake1 = cross_staking_1
-        .stakes(user.to_string(), None, None)
+        .stakes(user.to_string(), None, None, None, None, None)
         .unwrap();
     assert_eq!(
         cross_stake1.stakes,
@@ -2076,7 +4900,7 @@ fn cross_slash_scenario_4() {
 
     // TODO: external-staking slashing propagation
     let cross_stake2 = cross_staking_2
-        .stakes(user.to_string(), None, None)
+        .stakes(user.to_string(), None, None, None, None, None)
         .unwrap();
     assert_eq!(
         cross_stake2.stakes,
@@ -2085,6 +4909,8 @@ fn cross_slash_scenario_4() {
             StakeInfo::new(user, validators_2[1], &Stake::from_amount(88u128.into()))
         ]
     );
+
+    assert_vault_invariants(&vault);
 }
 
 /// Scenario 5:
@@ -2164,7 +4990,7 @@ fn cross_slash_scenario_5() {
 
     // Cross stake
     let cross_stake1 = cross_staking_1
-        .stakes(user.to_string(), None, None)
+        .stakes(user.to_string(), None, None, None, None, None)
         .unwrap();
     assert_eq!(
         cross_stake1.stakes,
@@ -2176,7 +5002,7 @@ fn cross_slash_scenario_5() {
     );
 
     let cross_stake2 = cross_staking_2
-        .stakes(user.to_string(), None, None)
+        .stakes(user.to_string(), None, None, None, None, None)
         .unwrap();
     assert_eq!(
         cross_stake2.stakes,
@@ -2188,7 +5014,7 @@ fn cross_slash_scenario_5() {
     );
 
     let cross_stake3 = cross_staking_3
-        .stakes(user.to_string(), None, None)
+        .stakes(user.to_string(), None, None, None, None, None)
         .unwrap();
     assert_eq!(
         cross_stake3.stakes,
@@ -2246,7 +5072,7 @@ fn cross_slash_scenario_5() {
     // Cross stake
     // TODO: external-staking slashing propagation
     let cross_stake1 = cross_staking_1
-        .stakes(user.to_string(), None, None)
+        .stakes(user.to_string(), None, None, None, None, None)
         .unwrap();
     assert_eq!(
         cross_stake1.stakes,
@@ -2258,7 +5084,7 @@ fn cross_slash_scenario_5() {
     );
 
     let cross_stake2 = cross_staking_2
-        .stakes(user.to_string(), None, None)
+        .stakes(user.to_string(), None, None, None, None, None)
         .unwrap();
     assert_eq!(
         cross_stake2.stakes,
@@ -2270,7 +5096,7 @@ fn cross_slash_scenario_5() {
     );
 
     let cross_stake3 = cross_staking_3
-        .stakes(user.to_string(), None, None)
+        .stakes(user.to_string(), None, None, None, None, None)
         .unwrap();
     assert_eq!(
         cross_stake3.stakes,
@@ -2280,6 +5106,8 @@ fn cross_slash_scenario_5() {
             &Stake::from_amount(100u128.into())
         ),]
     );
+
+    assert_vault_invariants(&vault);
 }
 
 /// Scenario 6:
@@ -2374,6 +5202,8 @@ fn cross_slash_no_native_staking() {
     assert_eq!(acc_details.bonded, Uint128::new(186));
     // Free collateral
     assert_eq!(acc_details.free, ValueRange::new_val(Uint128::zero()));
+
+    assert_vault_invariants(&vault);
 }
 
 /// Checks that the slashing applies to unbonding amounts as well.
@@ -2520,4 +5350,6 @@ fn cross_slash_pending_unbonding() {
     assert_eq!(cross_stake2.stake, ValueRange::new_val(Uint128::new(50))); // no slashing
                                                                            // No pending unbondings
     assert!(cross_stake2.pending_unbonds.is_empty());
+
+    assert_vault_invariants(&vault);
 }
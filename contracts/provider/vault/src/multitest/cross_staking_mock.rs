@@ -0,0 +1,32 @@
+use cosmwasm_std::{Decimal, Response, StdResult};
+use cw_storage_plus::Item;
+use mesh_apis::cross_staking_api::MaxSlashResponse;
+use sylvia::contract;
+use sylvia::types::{InstantiateCtx, QueryCtx};
+
+/// This is a stub cross staking contract, only answering the `max_slash` query with the
+/// configured value, for test purposes only.
+pub struct CrossStakingMock<'a> {
+    max_slash: Item<'a, Decimal>,
+}
+
+#[contract]
+impl CrossStakingMock<'_> {
+    pub const fn new() -> Self {
+        Self {
+            max_slash: Item::new("max_slash"),
+        }
+    }
+
+    #[msg(instantiate)]
+    pub fn instantiate(&self, ctx: InstantiateCtx, max_slash: Decimal) -> StdResult<Response> {
+        self.max_slash.save(ctx.deps.storage, &max_slash)?;
+        Ok(Response::new())
+    }
+
+    #[msg(query)]
+    fn max_slash(&self, ctx: QueryCtx) -> StdResult<MaxSlashResponse> {
+        let max_slash = self.max_slash.load(ctx.deps.storage)?;
+        Ok(MaxSlashResponse { max_slash })
+    }
+}
@@ -0,0 +1,89 @@
+use cosmwasm_std::{Addr, Binary, Response, StdError, StdResult, Storage, Uint128};
+use cw20::{BalanceResponse, Cw20ReceiveMsg};
+use cw_storage_plus::Map;
+use sylvia::contract;
+use sylvia::types::{ExecCtx, InstantiateCtx, QueryCtx};
+
+/// This is a minimal cw20 token, for test purposes only. It supports the subset of `cw20-base`
+/// used by the vault: `Transfer`, `Send` and the `Balance` query.
+pub struct Cw20Mock<'a> {
+    balances: Map<'a, &'a Addr, Uint128>,
+}
+
+#[contract]
+impl Cw20Mock<'_> {
+    pub const fn new() -> Self {
+        Self {
+            balances: Map::new("balances"),
+        }
+    }
+
+    #[msg(instantiate)]
+    pub fn instantiate(
+        &self,
+        ctx: InstantiateCtx,
+        initial_balances: Vec<(String, Uint128)>,
+    ) -> StdResult<Response> {
+        for (address, amount) in initial_balances {
+            let address = ctx.deps.api.addr_validate(&address)?;
+            self.balances.save(ctx.deps.storage, &address, &amount)?;
+        }
+        Ok(Response::new())
+    }
+
+    #[msg(exec)]
+    fn transfer(&self, ctx: ExecCtx, recipient: String, amount: Uint128) -> StdResult<Response> {
+        let recipient = ctx.deps.api.addr_validate(&recipient)?;
+        self.move_tokens(ctx.deps.storage, &ctx.info.sender, &recipient, amount)?;
+        Ok(Response::new())
+    }
+
+    #[msg(exec)]
+    fn send(
+        &self,
+        ctx: ExecCtx,
+        contract: String,
+        amount: Uint128,
+        msg: Binary,
+    ) -> StdResult<Response> {
+        let contract = ctx.deps.api.addr_validate(&contract)?;
+        self.move_tokens(ctx.deps.storage, &ctx.info.sender, &contract, amount)?;
+
+        let msg = Cw20ReceiveMsg {
+            sender: ctx.info.sender.into_string(),
+            amount,
+            msg,
+        }
+        .into_cosmos_msg(contract)?;
+        Ok(Response::new().add_message(msg))
+    }
+
+    #[msg(query)]
+    fn balance(&self, ctx: QueryCtx, address: String) -> StdResult<BalanceResponse> {
+        let address = ctx.deps.api.addr_validate(&address)?;
+        let balance = self
+            .balances
+            .may_load(ctx.deps.storage, &address)?
+            .unwrap_or_default();
+        Ok(BalanceResponse { balance })
+    }
+
+    fn move_tokens(
+        &self,
+        storage: &mut dyn Storage,
+        from: &Addr,
+        to: &Addr,
+        amount: Uint128,
+    ) -> StdResult<()> {
+        self.balances.update(storage, from, |balance| {
+            balance
+                .unwrap_or_default()
+                .checked_sub(amount)
+                .map_err(StdError::overflow)
+        })?;
+        self.balances.update(storage, to, |balance| {
+            Ok::<_, StdError>(balance.unwrap_or_default() + amount)
+        })?;
+        Ok(())
+    }
+}
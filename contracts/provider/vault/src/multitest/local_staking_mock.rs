@@ -0,0 +1,159 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{
+    ensure_eq, from_binary, Addr, Binary, Decimal, Response, StdError, StdResult, Uint128,
+};
+use cw_storage_plus::{Item, Map};
+use mesh_apis::local_staking_api::{
+    self, LocalStakingApi, LocalStakingCw20HookMsg, MaxSlashResponse,
+};
+use mesh_apis::vault_api::VaultApiHelper;
+use sylvia::contract;
+use sylvia::types::{ExecCtx, InstantiateCtx, QueryCtx};
+
+#[cw_serde]
+pub struct Config {
+    /// The vault contract, which instantiates this one
+    pub vault: VaultApiHelper,
+    /// The cw20 collateral token
+    pub cw20: Addr,
+}
+
+/// This is a stub local staking contract accepting cw20 collateral, for test purposes only.
+/// Stakes are just kept by the contract, until the owner unstakes them.
+pub struct LocalStakingMock<'a> {
+    config: Item<'a, Config>,
+    stakes: Map<'a, &'a str, Uint128>,
+}
+
+#[contract]
+#[messages(local_staking_api as LocalStakingApi)]
+impl LocalStakingMock<'_> {
+    pub const fn new() -> Self {
+        Self {
+            config: Item::new("config"),
+            stakes: Map::new("stakes"),
+        }
+    }
+
+    #[msg(instantiate)]
+    pub fn instantiate(&self, ctx: InstantiateCtx, cw20: String) -> StdResult<Response> {
+        let config = Config {
+            vault: VaultApiHelper(ctx.info.sender),
+            cw20: ctx.deps.api.addr_validate(&cw20)?,
+        };
+        self.config.save(ctx.deps.storage, &config)?;
+        Ok(Response::new())
+    }
+
+    /// Cw20 receive hook, for stakes sent by the vault
+    #[msg(exec)]
+    fn receive(
+        &self,
+        ctx: ExecCtx,
+        sender: String,
+        amount: Uint128,
+        msg: Binary,
+    ) -> StdResult<Response> {
+        let config = self.config.load(ctx.deps.storage)?;
+        ensure_eq!(
+            ctx.info.sender,
+            config.cw20,
+            StdError::generic_err("Unexpected token")
+        );
+        ensure_eq!(
+            sender,
+            config.vault.0,
+            StdError::generic_err("Stake is only accepted from the vault")
+        );
+
+        match from_binary(&msg)? {
+            LocalStakingCw20HookMsg::ReceiveStake { owner, .. } => {
+                self.stakes
+                    .update(ctx.deps.storage, &owner, |stake| -> StdResult<_> {
+                        Ok(stake.unwrap_or_default() + amount)
+                    })?;
+            }
+        }
+
+        Ok(Response::new())
+    }
+
+    /// Sends the sender's stake back to the vault
+    #[msg(exec)]
+    fn unstake(&self, ctx: ExecCtx, amount: Uint128) -> StdResult<Response> {
+        let config = self.config.load(ctx.deps.storage)?;
+        let owner = ctx.info.sender.into_string();
+        self.stakes
+            .update(ctx.deps.storage, &owner, |stake| -> StdResult<_> {
+                Ok(stake.unwrap_or_default().checked_sub(amount)?)
+            })?;
+
+        let msg = config
+            .vault
+            .release_cw20_local_stake(&config.cw20, owner, amount)?;
+        Ok(Response::new().add_message(msg))
+    }
+
+    #[msg(query)]
+    fn stake(&self, ctx: QueryCtx, owner: String) -> StdResult<StakeResponse> {
+        let stake = self
+            .stakes
+            .may_load(ctx.deps.storage, &owner)?
+            .unwrap_or_default();
+        Ok(StakeResponse { stake })
+    }
+}
+
+#[cw_serde]
+pub struct StakeResponse {
+    pub stake: Uint128,
+}
+
+#[contract]
+#[messages(local_staking_api as LocalStakingApi)]
+impl LocalStakingApi for LocalStakingMock<'_> {
+    type Error = StdError;
+
+    /// Native stakes are not supported
+    #[msg(exec)]
+    fn receive_stake(
+        &self,
+        _ctx: ExecCtx,
+        _owner: String,
+        _msg: Binary,
+    ) -> Result<Response, Self::Error> {
+        Err(StdError::generic_err("Only cw20 stakes are supported"))
+    }
+
+    /// Sends the whole owner's stake back to the vault
+    #[msg(exec)]
+    fn emergency_unstake(&self, ctx: ExecCtx, owner: String) -> Result<Response, Self::Error> {
+        let config = self.config.load(ctx.deps.storage)?;
+        ensure_eq!(
+            ctx.info.sender,
+            config.vault.0,
+            StdError::generic_err("Unauthorized")
+        );
+
+        let amount = self
+            .stakes
+            .may_load(ctx.deps.storage, &owner)?
+            .unwrap_or_default();
+        if amount.is_zero() {
+            return Ok(Response::new());
+        }
+        self.stakes.remove(ctx.deps.storage, &owner);
+
+        let msg = config
+            .vault
+            .release_cw20_local_stake(&config.cw20, owner, amount)?;
+        Ok(Response::new().add_message(msg))
+    }
+
+    #[msg(query)]
+    fn max_slash(&self, _ctx: QueryCtx) -> Result<MaxSlashResponse, Self::Error> {
+        Ok(MaxSlashResponse {
+            max_slash: Decimal::percent(10),
+        })
+    }
+}
@@ -0,0 +1,211 @@
+//! Storage access instrumentation, for benchmarking the contract in multitest.
+//!
+//! `ProfiledContract` wraps a multitest contract so all its storage accesses go through a
+//! `CountingStorage` adapter, counting them. Accesses done by other contracts and modules, or by
+//! multitest itself, are not counted.
+use std::cell::Cell;
+use std::fmt;
+use std::rc::Rc;
+
+use cosmwasm_std::{
+    Binary, Deps, DepsMut, Empty, Env, MessageInfo, Order, Record, Reply, Response, Storage,
+};
+use sylvia::anyhow::Result as AnyResult;
+use sylvia::cw_multi_test::Contract;
+
+/// Number of storage accesses, by kind
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AccessCounts {
+    pub reads: u64,
+    pub ranges: u64,
+    pub writes: u64,
+    pub removes: u64,
+}
+
+impl fmt::Display for AccessCounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "reads: {}, ranges: {}, writes: {}, removes: {}",
+            self.reads, self.ranges, self.writes, self.removes
+        )
+    }
+}
+
+/// Storage adapter counting the accesses to the wrapped storage. Wrapping a `&dyn Storage`
+/// makes it read-only
+pub struct CountingStorage<'a, S> {
+    storage: S,
+    counts: &'a Cell<AccessCounts>,
+}
+
+impl<'a, S> CountingStorage<'a, S> {
+    pub fn new(storage: S, counts: &'a Cell<AccessCounts>) -> Self {
+        Self { storage, counts }
+    }
+
+    fn count(&self, update: impl FnOnce(&mut AccessCounts)) {
+        let mut counts = self.counts.get();
+        update(&mut counts);
+        self.counts.set(counts);
+    }
+}
+
+impl Storage for CountingStorage<'_, &mut dyn Storage> {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.count(|c| c.reads += 1);
+        self.storage.get(key)
+    }
+
+    fn range<'a>(
+        &'a self,
+        start: Option<&[u8]>,
+        end: Option<&[u8]>,
+        order: Order,
+    ) -> Box<dyn Iterator<Item = Record> + 'a> {
+        self.count(|c| c.ranges += 1);
+        self.storage.range(start, end, order)
+    }
+
+    fn set(&mut self, key: &[u8], value: &[u8]) {
+        self.count(|c| c.writes += 1);
+        self.storage.set(key, value)
+    }
+
+    fn remove(&mut self, key: &[u8]) {
+        self.count(|c| c.removes += 1);
+        self.storage.remove(key)
+    }
+}
+
+impl Storage for CountingStorage<'_, &dyn Storage> {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.count(|c| c.reads += 1);
+        self.storage.get(key)
+    }
+
+    fn range<'a>(
+        &'a self,
+        start: Option<&[u8]>,
+        end: Option<&[u8]>,
+        order: Order,
+    ) -> Box<dyn Iterator<Item = Record> + 'a> {
+        self.count(|c| c.ranges += 1);
+        self.storage.range(start, end, order)
+    }
+
+    fn set(&mut self, _key: &[u8], _value: &[u8]) {
+        panic!("Write to read-only storage");
+    }
+
+    fn remove(&mut self, _key: &[u8]) {
+        panic!("Remove from read-only storage");
+    }
+}
+
+/// Handle on the counters of a `ProfiledContract`
+#[derive(Clone)]
+pub struct AccessCounter(Rc<Cell<AccessCounts>>);
+
+impl AccessCounter {
+    /// Runs `f`, returning its result along with the storage accesses done meanwhile
+    pub fn measure<T>(&self, f: impl FnOnce() -> T) -> (T, AccessCounts) {
+        self.0.set(AccessCounts::default());
+        let res = f();
+        (res, self.0.get())
+    }
+}
+
+/// Multitest contract counting the storage accesses of the wrapped one, in all its entry points
+pub struct ProfiledContract<C> {
+    contract: C,
+    counts: Rc<Cell<AccessCounts>>,
+}
+
+impl<C> ProfiledContract<C> {
+    pub fn new(contract: C) -> Self {
+        Self {
+            contract,
+            counts: Rc::default(),
+        }
+    }
+
+    /// Returns a handle on the counters, to be kept after the contract is stored in the app
+    pub fn counter(&self) -> AccessCounter {
+        AccessCounter(self.counts.clone())
+    }
+}
+
+impl<C: Contract<Empty>> Contract<Empty> for ProfiledContract<C> {
+    fn execute(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        msg: Vec<u8>,
+    ) -> AnyResult<Response> {
+        let mut storage = CountingStorage::new(deps.storage, &self.counts);
+        let deps = DepsMut {
+            storage: &mut storage,
+            api: deps.api,
+            querier: deps.querier,
+        };
+        self.contract.execute(deps, env, info, msg)
+    }
+
+    fn instantiate(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        msg: Vec<u8>,
+    ) -> AnyResult<Response> {
+        let mut storage = CountingStorage::new(deps.storage, &self.counts);
+        let deps = DepsMut {
+            storage: &mut storage,
+            api: deps.api,
+            querier: deps.querier,
+        };
+        self.contract.instantiate(deps, env, info, msg)
+    }
+
+    fn query(&self, deps: Deps, env: Env, msg: Vec<u8>) -> AnyResult<Binary> {
+        let storage = CountingStorage::new(deps.storage, &self.counts);
+        let deps = Deps {
+            storage: &storage,
+            api: deps.api,
+            querier: deps.querier,
+        };
+        self.contract.query(deps, env, msg)
+    }
+
+    fn sudo(&self, deps: DepsMut, env: Env, msg: Vec<u8>) -> AnyResult<Response> {
+        let mut storage = CountingStorage::new(deps.storage, &self.counts);
+        let deps = DepsMut {
+            storage: &mut storage,
+            api: deps.api,
+            querier: deps.querier,
+        };
+        self.contract.sudo(deps, env, msg)
+    }
+
+    fn reply(&self, deps: DepsMut, env: Env, msg: Reply) -> AnyResult<Response> {
+        let mut storage = CountingStorage::new(deps.storage, &self.counts);
+        let deps = DepsMut {
+            storage: &mut storage,
+            api: deps.api,
+            querier: deps.querier,
+        };
+        self.contract.reply(deps, env, msg)
+    }
+
+    fn migrate(&self, deps: DepsMut, env: Env, msg: Vec<u8>) -> AnyResult<Response> {
+        let mut storage = CountingStorage::new(deps.storage, &self.counts);
+        let deps = DepsMut {
+            storage: &mut storage,
+            api: deps.api,
+            querier: deps.querier,
+        };
+        self.contract.migrate(deps, env, msg)
+    }
+}
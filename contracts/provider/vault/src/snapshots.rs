@@ -0,0 +1,172 @@
+use cosmwasm_std::{Addr, BlockInfo, Order, StdResult, Storage, Uint128};
+use cw_storage_plus::{Bound, Item, Map};
+
+use crate::state::Snapshot;
+
+/// Max number of snapshots kept queryable at the same time. Creating a new snapshot over this
+/// limit drops the oldest one.
+pub const MAX_LIVE_SNAPSHOTS: u64 = 5;
+
+/// Per-user collateral snapshots.
+///
+/// Collateral is copied lazily: on the first collateral change of an user after a snapshot was
+/// taken, the previous collateral is stored under the latest snapshot id. The collateral at
+/// snapshot `id` is then the first value stored at a snapshot id `>= id`, or the current
+/// collateral if it never changed since.
+///
+/// The values stored for snapshots which are not live anymore are removed on the next change of
+/// the user's collateral, so at most `MAX_LIVE_SNAPSHOTS` values are kept per user.
+pub struct Snapshots<'a> {
+    /// Id of the latest snapshot taken
+    pub last_id: Item<'a, u64>,
+    /// Live snapshots information
+    pub snapshots: Map<'a, u64, Snapshot>,
+    /// Users collateral before their first change after a snapshot, indexed by `(user, snapshot_id)`
+    pub collateral: Map<'a, (&'a Addr, u64), Uint128>,
+}
+
+impl<'a> Snapshots<'a> {
+    pub const fn new(
+        last_id_key: &'a str,
+        snapshots_key: &'a str,
+        collateral_key: &'a str,
+    ) -> Self {
+        Self {
+            last_id: Item::new(last_id_key),
+            snapshots: Map::new(snapshots_key),
+            collateral: Map::new(collateral_key),
+        }
+    }
+
+    /// Takes a new snapshot, returning its id. Drops the oldest snapshot if there are already
+    /// `MAX_LIVE_SNAPSHOTS` of them.
+    pub fn create(
+        &self,
+        storage: &mut dyn Storage,
+        block: &BlockInfo,
+        total_collateral: Uint128,
+    ) -> StdResult<u64> {
+        let id = self.last_id.may_load(storage)?.unwrap_or_default() + 1;
+        self.last_id.save(storage, &id)?;
+
+        let snapshot = Snapshot {
+            id,
+            height: block.height,
+            time: block.time,
+            total_collateral,
+        };
+        self.snapshots.save(storage, id, &snapshot)?;
+
+        if id > MAX_LIVE_SNAPSHOTS {
+            // Per-user values of the dropped snapshot are removed on their next checkpoint, they
+            // are never read for newer snapshots
+            self.snapshots.remove(storage, id - MAX_LIVE_SNAPSHOTS);
+        }
+
+        Ok(id)
+    }
+
+    /// Preserves the user's collateral for the latest snapshot, and removes its values stored for
+    /// the snapshots not live anymore. Has to be called before any change of the user's
+    /// collateral.
+    pub fn checkpoint(
+        &self,
+        storage: &mut dyn Storage,
+        user: &Addr,
+        collateral: Uint128,
+    ) -> StdResult<()> {
+        let id = match self.last_id.may_load(storage)? {
+            Some(id) => id,
+            None => return Ok(()),
+        };
+
+        if !self.collateral.has(storage, (user, id)) {
+            self.collateral.save(storage, (user, id), &collateral)?;
+
+            let oldest_live = (id + 1).saturating_sub(MAX_LIVE_SNAPSHOTS);
+            let stale = self
+                .collateral
+                .prefix(user)
+                .keys(
+                    storage,
+                    None,
+                    Some(Bound::exclusive(oldest_live)),
+                    Order::Ascending,
+                )
+                .collect::<StdResult<Vec<_>>>()?;
+            for stale_id in stale {
+                self.collateral.remove(storage, (user, stale_id));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the user's collateral at the given snapshot, given its current collateral
+    pub fn collateral_at(
+        &self,
+        storage: &dyn Storage,
+        snapshot_id: u64,
+        user: &Addr,
+        current: Uint128,
+    ) -> StdResult<Uint128> {
+        let collateral = self
+            .collateral
+            .prefix(user)
+            .range(
+                storage,
+                Some(Bound::inclusive(snapshot_id)),
+                None,
+                Order::Ascending,
+            )
+            .next()
+            .transpose()?
+            .map(|(_, collateral)| collateral)
+            .unwrap_or(current);
+
+        Ok(collateral)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cosmwasm_std::testing::{mock_env, MockStorage};
+
+    #[test]
+    fn stale_checkpoints_are_removed() {
+        let mut storage = MockStorage::new();
+        let snapshots = Snapshots::new("snapshot_id", "snapshots", "snapshot_collateral");
+        let user = Addr::unchecked("user");
+        let block = mock_env().block;
+
+        // A collateral change after each snapshot
+        for collateral in 0..MAX_LIVE_SNAPSHOTS + 2 {
+            snapshots
+                .create(&mut storage, &block, Uint128::zero())
+                .unwrap();
+            snapshots
+                .checkpoint(&mut storage, &user, Uint128::new(collateral.into()))
+                .unwrap();
+        }
+        let current = Uint128::new(100);
+
+        // Only the values of the live snapshots are kept
+        let kept = snapshots
+            .collateral
+            .prefix(&user)
+            .keys(&storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()
+            .unwrap();
+        assert_eq!(kept, (3..=MAX_LIVE_SNAPSHOTS + 2).collect::<Vec<_>>());
+        for id in kept {
+            assert_eq!(
+                snapshots
+                    .collateral_at(&storage, id, &user, current)
+                    .unwrap(),
+                Uint128::new((id - 1).into())
+            );
+        }
+    }
+}
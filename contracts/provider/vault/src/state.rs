@@ -1,12 +1,63 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Decimal, Uint128};
+use cosmwasm_std::{Addr, Decimal, HexBinary, Timestamp, Uint128};
 use mesh_apis::local_staking_api::LocalStakingApiHelper;
 use mesh_sync::{max_range, ValueRange};
 
 #[cw_serde]
 pub struct Config {
-    /// The denom we accept for staking (only native tokens)
-    pub denom: String,
+    /// The token we accept for staking
+    pub collateral: CollateralType,
+    /// Seconds after which a pending tx can be expired by the admin, rolling it back.
+    /// If not set, pending txs never expire
+    pub tx_timeout: Option<u64>,
+    /// Part of the bonded collateral sent to `fee_recipient`
+    #[serde(default)]
+    pub bond_fee: Decimal,
+    /// Part of the unbonded collateral sent to `fee_recipient`
+    #[serde(default)]
+    pub unbond_fee: Decimal,
+    /// Recipient of the bond and unbond fees. Always set when fees are not zero
+    #[serde(default)]
+    pub fee_recipient: Option<Addr>,
+    /// Max total collateral the vault accepts, if any
+    #[serde(default)]
+    pub max_total_collateral: Option<Uint128>,
+    /// Min amount of a cross stake release, but for the final release of a lien
+    #[serde(default)]
+    pub min_release: Option<Uint128>,
+    /// Whether `stake_remote` asks the cross staking contract if it would accept the stake,
+    /// before reserving the collateral
+    #[serde(default)]
+    pub check_remote_stake: bool,
+}
+
+/// Fees collected since they were tracked, sent to the fee recipients of the time
+#[cw_serde]
+#[derive(Default)]
+pub struct FeeStats {
+    /// Collected on bond
+    pub bond_fees: Uint128,
+    /// Collected on unbond
+    pub unbond_fees: Uint128,
+}
+
+/// Token used as collateral
+#[cw_serde]
+pub enum CollateralType {
+    /// Native token, by denom
+    Native(String),
+    /// Cw20 token, by contract address
+    Cw20(Addr),
+}
+
+impl CollateralType {
+    /// Denom of the collateral in `Coin` amounts. For cw20 tokens, it's the token contract address
+    pub fn denom(&self) -> String {
+        match self {
+            CollateralType::Native(denom) => denom.clone(),
+            CollateralType::Cw20(addr) => addr.to_string(),
+        }
+    }
 }
 
 #[cw_serde]
@@ -16,6 +67,43 @@ pub struct LocalStaking {
 
     /// Max slashing on local staking
     pub max_slash: Decimal,
+
+    /// Checksum of the local staking code, for audits. Not set if the chain doesn't support code
+    /// info queries
+    #[serde(default)]
+    pub checksum: Option<HexBinary>,
+}
+
+/// Accounts snapshot description
+#[cw_serde]
+pub struct Snapshot {
+    /// Snapshot id
+    pub id: u64,
+    /// Height at which the snapshot was taken
+    pub height: u64,
+    /// Time at which the snapshot was taken
+    pub time: Timestamp,
+    /// Total collateral of all the users at snapshot time
+    pub total_collateral: Uint128,
+}
+
+/// Local slash notified by the chain, applied to the owners delegated to the validator a page at a
+/// time
+#[cw_serde]
+pub struct LocalSlash {
+    pub validator: String,
+    pub slash_ratio: Decimal,
+    pub infraction_height: u64,
+    /// Last owner slashed
+    pub start_after: Option<String>,
+}
+
+/// Remote stake created with an idempotency key, returned again when the key is reused before it
+/// expires
+#[cw_serde]
+pub struct IdempotentStake {
+    pub tx_id: u64,
+    pub expires_at: Timestamp,
 }
 
 /// Single Lien description
@@ -25,6 +113,21 @@ pub struct Lien {
     pub amount: ValueRange<Uint128>,
     /// Slashable part - restricted to [0; 1] range
     pub slashable: Decimal,
+    /// Kind of the lienholder, restricting how the lien can be released
+    #[serde(default)]
+    pub kind: LienKind,
+}
+
+/// Kind of a lienholder. Liens stored before it was tracked are cross ones, except the local
+/// staking ones, fixed by the migration
+#[cw_serde]
+#[derive(Copy, Default)]
+pub enum LienKind {
+    /// The local staking contract
+    Local,
+    /// A cross staking contract
+    #[default]
+    Cross,
 }
 
 #[cw_serde]
@@ -36,6 +139,12 @@ pub struct UserInfo {
     pub max_lien: ValueRange<Uint128>,
     // Total slashable amount for user
     pub total_slashable: ValueRange<Uint128>,
+    // Collateral kept free on the user's request, which can be neither staked nor unbonded
+    #[serde(default)]
+    pub reserved: Uint128,
+    // Max `max_lien / collateral` ratio set by the user, over which new stakes are rejected
+    #[serde(default)]
+    pub max_leverage: Option<Decimal>,
 }
 
 impl UserInfo {
@@ -44,7 +153,8 @@ impl UserInfo {
         max_range(self.max_lien, self.total_slashable)
     }
 
-    /// Returns free collateral
+    /// Returns free collateral. Its low end is the collateral free even if all the pending stakes
+    /// are committed, and is the only part which can be unbonded
     pub fn free_collateral(&self) -> ValueRange<Uint128> {
         ValueRange::new(
             self.collateral - self.used_collateral().high(),
@@ -56,4 +166,27 @@ impl UserInfo {
     pub fn verify_collateral(&self) -> bool {
         self.collateral >= self.used_collateral().high()
     }
+
+    /// Returns the free collateral net of the reserve, which is what the user can stake or unbond
+    pub fn unreserved_collateral(&self) -> ValueRange<Uint128> {
+        let free = self.free_collateral();
+        ValueRange::new(
+            free.low().saturating_sub(self.reserved),
+            free.high().saturating_sub(self.reserved),
+        )
+    }
+
+    /// Checks if the collateral covers staked liens and the reserve. Only checked on the user's
+    /// own actions, as slashing can eat into the reserve
+    pub fn covers_reserve(&self) -> bool {
+        self.collateral >= self.used_collateral().high().saturating_add(self.reserved)
+    }
+
+    /// Checks if the max lien is within the leverage limit set by the user, if any
+    pub fn within_leverage(&self) -> bool {
+        match self.max_leverage {
+            Some(max_leverage) => self.max_lien.high() <= self.collateral * max_leverage,
+            None => true,
+        }
+    }
 }
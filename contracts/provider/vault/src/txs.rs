@@ -1,5 +1,5 @@
 use cosmwasm_std::{Addr, Order, StdResult, Storage};
-use cw_storage_plus::{Index, IndexList, IndexedMap, MultiIndex};
+use cw_storage_plus::{Index, IndexList, IndexedMap, Map, MultiIndex};
 use mesh_sync::Tx;
 use mesh_sync::Tx::InFlightStaking;
 
@@ -17,10 +17,13 @@ impl<'a> IndexList<Tx> for TxIndexes<'a> {
 
 pub struct Txs<'a> {
     pub txs: IndexedMap<'a, u64, Tx, TxIndexes<'a>>,
+    /// Txs by `(id, lienholder)`. Only the lienholder a tx was created for can resolve it, which
+    /// is checked against this key on top of the tx itself
+    pub lienholders: Map<'a, (u64, &'a Addr), ()>,
 }
 
 impl<'a> Txs<'a> {
-    pub fn new(storage_key: &'a str, user_subkey: &'a str) -> Self {
+    pub fn new(storage_key: &'a str, user_subkey: &'a str, lienholder_key: &'a str) -> Self {
         let indexes = TxIndexes {
             users: MultiIndex::new(
                 |_, tx| {
@@ -35,8 +38,32 @@ impl<'a> Txs<'a> {
             ),
         };
         let txs = IndexedMap::new(storage_key, indexes);
+        let lienholders = Map::new(lienholder_key);
 
-        Self { txs }
+        Self { txs, lienholders }
+    }
+
+    /// Saves a new tx, keyed by its lienholder too
+    pub fn save(&self, storage: &mut dyn Storage, tx: &Tx) -> StdResult<()> {
+        self.txs.save(storage, tx.id(), tx)?;
+        if let InFlightStaking { id, lienholder, .. } = tx {
+            self.lienholders.save(storage, (*id, lienholder), &())?;
+        }
+        Ok(())
+    }
+
+    /// Removes a resolved tx
+    pub fn remove(&self, storage: &mut dyn Storage, tx: &Tx) -> StdResult<()> {
+        self.txs.remove(storage, tx.id())?;
+        if let InFlightStaking { id, lienholder, .. } = tx {
+            self.lienholders.remove(storage, (*id, lienholder));
+        }
+        Ok(())
+    }
+
+    /// Whether the tx was created for `lienholder`
+    pub fn is_lienholder(&self, storage: &dyn Storage, tx_id: u64, lienholder: &Addr) -> bool {
+        self.lienholders.has(storage, (tx_id, lienholder))
     }
 
     pub fn txs_by_user(&self, storage: &dyn Storage, user: &Addr) -> StdResult<Vec<Tx>> {
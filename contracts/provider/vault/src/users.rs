@@ -0,0 +1,90 @@
+use cosmwasm_std::{Addr, StdResult, Storage, Uint128};
+use cw_storage_plus::{Index, IndexList, Item, MultiIndex};
+use mesh_sync::ValueRange;
+
+use crate::state::UserInfo;
+
+pub struct UserIndexes<'a> {
+    // Last type param defines the pk deserialization type
+    pub collateral: MultiIndex<'a, u128, UserInfo, Addr>,
+    pub free_collateral: FreeCollateralTotal<'a>,
+}
+
+impl<'a> IndexList<UserInfo> for UserIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<UserInfo>> + '_> {
+        let v: Vec<&dyn Index<UserInfo>> = vec![&self.collateral, &self.free_collateral];
+        Box::new(v.into_iter())
+    }
+}
+
+impl<'a> UserIndexes<'a> {
+    pub fn new(
+        storage_key: &'a str,
+        collateral_subkey: &'a str,
+        free_collateral_key: &'a str,
+    ) -> Self {
+        Self {
+            collateral: MultiIndex::new(
+                |_, user| user.collateral.u128(),
+                storage_key,
+                collateral_subkey,
+            ),
+            free_collateral: FreeCollateralTotal::new(free_collateral_key),
+        }
+    }
+}
+
+/// Total free collateral of all the users. Not an actual index, it is kept up to date the same
+/// way, as the users are saved.
+pub struct FreeCollateralTotal<'a> {
+    total: Item<'a, ValueRange<Uint128>>,
+}
+
+impl<'a> FreeCollateralTotal<'a> {
+    pub const fn new(storage_key: &'a str) -> Self {
+        Self {
+            total: Item::new(storage_key),
+        }
+    }
+
+    pub fn load(&self, storage: &dyn Storage) -> StdResult<ValueRange<Uint128>> {
+        Ok(self.total.may_load(storage)?.unwrap_or_default())
+    }
+
+    /// Overwrites the total, when rebuilding it from scratch
+    pub fn save(&self, storage: &mut dyn Storage, total: ValueRange<Uint128>) -> StdResult<()> {
+        self.total.save(storage, &total)
+    }
+
+    /// Free collateral of `user`, as accounted in the total. Saturates at zero rather than
+    /// failing on users temporarily over-used, before their invariants are checked
+    pub fn free_collateral(user: &UserInfo) -> ValueRange<Uint128> {
+        let used = user.used_collateral();
+        ValueRange::new(
+            user.collateral.saturating_sub(used.high()),
+            user.collateral.saturating_sub(used.low()),
+        )
+    }
+}
+
+impl<'a> Index<UserInfo> for FreeCollateralTotal<'a> {
+    fn save(&self, store: &mut dyn Storage, _pk: &[u8], data: &UserInfo) -> StdResult<()> {
+        let total = self.load(store)?;
+        let free = Self::free_collateral(data);
+        let total = ValueRange::new(
+            total.low().checked_add(free.low())?,
+            total.high().checked_add(free.high())?,
+        );
+        self.total.save(store, &total)
+    }
+
+    fn remove(&self, store: &mut dyn Storage, _pk: &[u8], old_data: &UserInfo) -> StdResult<()> {
+        let total = self.load(store)?;
+        let free = Self::free_collateral(old_data);
+        let total = ValueRange::new(
+            total.low().checked_sub(free.low())?,
+            total.high().checked_sub(free.high())?,
+        );
+        self.total.save(store, &total)
+    }
+}
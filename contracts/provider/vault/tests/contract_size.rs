@@ -0,0 +1,33 @@
+//! The optimized vault wasm must stay within the size budget, so that it can be stored and
+//! instantiated on chains with tight code size or gas limits.
+//!
+//! Checks the `artifacts/mesh_vault.wasm` built by `scripts/optimizer.sh`, and fails when it is
+//! missing. Only built with the `wasm-size` feature. The budget can be overridden with the
+//! `MESH_VAULT_WASM_BUDGET` env variable, in bytes.
+
+use std::path::Path;
+
+/// Default budget, the upload limit of most chains
+const WASM_BUDGET: u64 = 800 * 1024;
+
+#[test]
+fn check_contract_size() {
+    let wasm = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../../artifacts/mesh_vault.wasm");
+    let size = std::fs::metadata(&wasm)
+        .unwrap_or_else(|err| {
+            panic!(
+                "No optimized wasm at {}, run scripts/optimizer.sh first: {err}",
+                wasm.display()
+            )
+        })
+        .len();
+    let budget = std::env::var("MESH_VAULT_WASM_BUDGET")
+        .map(|budget| budget.parse().expect("Invalid MESH_VAULT_WASM_BUDGET"))
+        .unwrap_or(WASM_BUDGET);
+
+    println!("Optimized vault wasm is {size} bytes, budget is {budget} bytes");
+    assert!(
+        size <= budget,
+        "Optimized vault wasm is {size} bytes, over the budget of {budget} bytes"
+    );
+}
@@ -0,0 +1,179 @@
+//! Indexers and frontends rely on the JSON format of the vault messages and responses. The
+//! samples in `tests/golden` pin that format: they were generated when this test was added, and
+//! must still deserialize with the current types.
+//!
+//! Fields may be added, as long as the existing ones keep their name and encoding: the samples
+//! must be found unchanged in the serialization of what they deserialize to. Every message must
+//! have a sample, so that the new ones are covered as well. The samples are published along with
+//! the schema.
+
+use std::collections::BTreeSet;
+
+use cosmwasm_schema::generate_api;
+use cosmwasm_std::Uint128;
+use mesh_sync::{TxStatus, ValueRange};
+use mesh_vault::contract::{ContractExecMsg, ContractQueryMsg, InstantiateMsg};
+use mesh_vault::msg::{
+    AccountClaimsResponse, AccountDetailsResponse, AccountResponse,
+    AccountsByFreeCollateralResponse, AllAccountsResponse, AllLiensResponse, AllTxsResponse,
+    ConfigResponse, FeeStatsResponse, InvariantsReport, InvariantsResponse,
+    SnapshotAccountResponse, SnapshotAccountsResponse, TxsHistoryResponse,
+    VotingPowerReportResponse,
+};
+use mesh_vault::state::Lien;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+const EXECUTE: &str = include_str!("golden/execute.json");
+const QUERY: &str = include_str!("golden/query.json");
+const RESPONSES: &str = include_str!("golden/responses.json");
+
+/// Whether `current` holds all of `golden`. Objects may have more fields, anything else must be
+/// equal
+fn is_compatible(golden: &Value, current: &Value) -> bool {
+    match (golden, current) {
+        (Value::Object(golden), Value::Object(current)) => golden
+            .iter()
+            .all(|(key, value)| matches!(current.get(key), Some(v) if is_compatible(value, v))),
+        (Value::Array(golden), Value::Array(current)) => {
+            golden.len() == current.len()
+                && golden
+                    .iter()
+                    .zip(current)
+                    .all(|(golden, current)| is_compatible(golden, current))
+        }
+        _ => golden == current,
+    }
+}
+
+/// Deserializes the `golden` sample as a `T`, and checks it is serialized back compatibly
+#[track_caller]
+fn assert_round_trip<T: Serialize + DeserializeOwned>(name: &str, golden: &Value) {
+    let parsed: T = serde_json::from_value(golden.clone())
+        .unwrap_or_else(|err| panic!("Sample of {name} doesn't deserialize: {err}"));
+    let current = serde_json::to_value(parsed).unwrap();
+    assert!(
+        is_compatible(golden, &current),
+        "{name} is not compatible:\n{golden}\nserializes as\n{current}"
+    );
+}
+
+/// Name of the sample of a message, its only key
+fn msg_name(msg: &Value) -> String {
+    let msg = msg.as_object().expect("Message sample not an object");
+    assert_eq!(msg.len(), 1, "Message sample with multiple keys");
+    msg.keys().next().unwrap().clone()
+}
+
+/// Names of the messages of a `oneOf` / `anyOf` schema
+fn schema_msg_names(schema: &Value, definitions: &Value, names: &mut BTreeSet<String>) {
+    if let Some(reference) = schema["$ref"].as_str() {
+        let name = reference.rsplit('/').next().unwrap();
+        return schema_msg_names(&definitions[name], definitions, names);
+    }
+    for key in ["oneOf", "anyOf"] {
+        if let Some(variants) = schema[key].as_array() {
+            for variant in variants {
+                schema_msg_names(variant, definitions, names);
+            }
+        }
+    }
+    if let Some(required) = schema["required"].as_array() {
+        names.insert(required[0].as_str().unwrap().to_owned());
+    }
+}
+
+/// Current API, along with the names of its execute and query messages
+fn api() -> (Value, BTreeSet<String>, BTreeSet<String>) {
+    let api = generate_api! {
+        instantiate: InstantiateMsg,
+        execute: ContractExecMsg,
+        query: ContractQueryMsg,
+    }
+    .render()
+    .to_string()
+    .unwrap();
+    let api: Value = serde_json::from_str(&api).unwrap();
+
+    let mut execute = BTreeSet::new();
+    schema_msg_names(
+        &api["execute"],
+        &api["execute"]["definitions"],
+        &mut execute,
+    );
+    let mut query = BTreeSet::new();
+    schema_msg_names(&api["query"], &api["query"]["definitions"], &mut query);
+    (api, execute, query)
+}
+
+#[test]
+fn execute_msgs_are_compatible() {
+    let samples: Vec<Value> = serde_json::from_str(EXECUTE).unwrap();
+    for sample in &samples {
+        assert_round_trip::<ContractExecMsg>(&msg_name(sample), sample);
+    }
+
+    let sampled: BTreeSet<_> = samples.iter().map(msg_name).collect();
+    let (_, execute, _) = api();
+    assert_eq!(sampled, execute, "Execute messages without samples");
+}
+
+#[test]
+fn query_msgs_are_compatible() {
+    let samples: Vec<Value> = serde_json::from_str(QUERY).unwrap();
+    for sample in &samples {
+        assert_round_trip::<ContractQueryMsg>(&msg_name(sample), sample);
+    }
+
+    let sampled: BTreeSet<_> = samples.iter().map(msg_name).collect();
+    let (_, _, query) = api();
+    assert_eq!(sampled, query, "Query messages without samples");
+}
+
+#[test]
+fn responses_are_compatible() {
+    let samples: serde_json::Map<String, Value> = serde_json::from_str(RESPONSES).unwrap();
+    for (query, sample) in &samples {
+        match query.as_str() {
+            "account" => assert_round_trip::<AccountResponse>(query, sample),
+            "is_participant" | "can_release" => assert_round_trip::<bool>(query, sample),
+            "slashable_collateral" | "total_free_collateral" => {
+                assert_round_trip::<ValueRange<Uint128>>(query, sample)
+            }
+            "account_details" => assert_round_trip::<AccountDetailsResponse>(query, sample),
+            "check_invariants" => assert_round_trip::<InvariantsReport>(query, sample),
+            "invariants" => assert_round_trip::<InvariantsResponse>(query, sample),
+            "voting_power_report" => assert_round_trip::<VotingPowerReportResponse>(query, sample),
+            "config" => assert_round_trip::<ConfigResponse>(query, sample),
+            "denom" => assert_round_trip::<String>(query, sample),
+            "fee_stats" => assert_round_trip::<FeeStatsResponse>(query, sample),
+            "claim" => assert_round_trip::<Lien>(query, sample),
+            "account_claims" => assert_round_trip::<AccountClaimsResponse>(query, sample),
+            "all_liens" => assert_round_trip::<AllLiensResponse>(query, sample),
+            "all_accounts" => assert_round_trip::<AllAccountsResponse>(query, sample),
+            "accounts_by_free_collateral" => {
+                assert_round_trip::<AccountsByFreeCollateralResponse>(query, sample)
+            }
+            "snapshot_account" => assert_round_trip::<SnapshotAccountResponse>(query, sample),
+            "snapshot_accounts" => assert_round_trip::<SnapshotAccountsResponse>(query, sample),
+            "pending_tx" => assert_round_trip::<TxStatus>(query, sample),
+            "txs_history" => assert_round_trip::<TxsHistoryResponse>(query, sample),
+            "all_pending_txs_desc" | "user_pending_txs" => {
+                assert_round_trip::<AllTxsResponse>(query, sample)
+            }
+            _ => panic!("No response type for {query}"),
+        }
+    }
+
+    let sampled: BTreeSet<_> = samples.keys().cloned().collect();
+    let (api, _, query) = api();
+    assert_eq!(sampled, query, "Query responses without samples");
+    let responses: BTreeSet<_> = api["responses"]
+        .as_object()
+        .unwrap()
+        .keys()
+        .cloned()
+        .collect();
+    assert_eq!(sampled, responses);
+}
@@ -28,9 +28,61 @@ pub trait CrossStakingApi {
         msg: Binary,
     ) -> Result<Response, Self::Error>;
 
+    /// Unstakes `amount` of the owner's stake, as if the owner had called `unstake`. Can only be
+    /// called by the vault, to force an exit. Without a validator, the stake is taken from the
+    /// largest stakes first. Once unbonded, the tokens are released back to the vault as usual
+    #[msg(exec)]
+    fn vault_unstake(
+        &self,
+        ctx: ExecCtx,
+        owner: String,
+        validator: Option<String>,
+        amount: Coin,
+    ) -> Result<Response, Self::Error>;
+
+    /// Moves all the owner's stake on `from_validators` to `to_validator`. Can only be called by
+    /// the vault, on behalf of the owner. The stake stays in place, so the vault lien is unchanged.
+    ///
+    /// The source validators must be active. The moved stake is still slashed for them until the
+    /// end of its unbonding period
+    #[msg(exec)]
+    fn restake(
+        &self,
+        ctx: ExecCtx,
+        owner: String,
+        from_validators: Vec<String>,
+        to_validator: String,
+    ) -> Result<Response, Self::Error>;
+
+    /// Rolls back the pending stake `tx_id`, whose ack never arrived. Can only be called by the
+    /// vault, when it expires the tx: the vault side is already rolled back
+    #[msg(exec)]
+    fn expire_stake(&self, ctx: ExecCtx, tx_id: u64) -> Result<Response, Self::Error>;
+
     /// Returns the maximum percentage that can be slashed
     #[msg(query)]
     fn max_slash(&self, ctx: QueryCtx) -> Result<MaxSlashResponse, Self::Error>;
+
+    /// Dry run of `receive_virtual_stake`: tells whether the stake would be accepted, without
+    /// changing any state
+    #[msg(query)]
+    fn can_stake(
+        &self,
+        ctx: QueryCtx,
+        owner: String,
+        amount: Coin,
+        msg: Binary,
+    ) -> Result<CanStakeResponse, Self::Error>;
+}
+
+/// Verdict of a `can_stake` dry run
+#[cw_serde]
+pub enum CanStakeResponse {
+    Accepted {},
+    /// The stake would fail with the `reason` error
+    Rejected {
+        reason: String,
+    },
 }
 
 #[cw_serde]
@@ -63,8 +115,67 @@ impl CrossStakingApiHelper {
         Ok(wasm)
     }
 
+    pub fn vault_unstake(
+        &self,
+        owner: String,
+        validator: Option<String>,
+        amount: Coin,
+    ) -> Result<WasmMsg, StdError> {
+        let msg = CrossStakingApiExecMsg::VaultUnstake {
+            owner,
+            validator,
+            amount,
+        };
+        let wasm = WasmMsg::Execute {
+            contract_addr: self.0.to_string(),
+            msg: to_binary(&msg)?,
+            funds: vec![],
+        };
+        Ok(wasm)
+    }
+
+    pub fn expire_stake(&self, tx_id: u64) -> Result<WasmMsg, StdError> {
+        let msg = CrossStakingApiExecMsg::ExpireStake { tx_id };
+        let wasm = WasmMsg::Execute {
+            contract_addr: self.0.to_string(),
+            msg: to_binary(&msg)?,
+            funds: vec![],
+        };
+        Ok(wasm)
+    }
+
+    pub fn restake(
+        &self,
+        owner: String,
+        from_validators: Vec<String>,
+        to_validator: String,
+    ) -> Result<WasmMsg, StdError> {
+        let msg = CrossStakingApiExecMsg::Restake {
+            owner,
+            from_validators,
+            to_validator,
+        };
+        let wasm = WasmMsg::Execute {
+            contract_addr: self.0.to_string(),
+            msg: to_binary(&msg)?,
+            funds: vec![],
+        };
+        Ok(wasm)
+    }
+
     pub fn max_slash(&self, deps: Deps) -> Result<MaxSlashResponse, StdError> {
         let query = CrossStakingApiQueryMsg::MaxSlash {};
         deps.querier.query_wasm_smart(&self.0, &query)
     }
+
+    pub fn can_stake(
+        &self,
+        deps: Deps,
+        owner: String,
+        amount: Coin,
+        msg: Binary,
+    ) -> Result<CanStakeResponse, StdError> {
+        let query = CrossStakingApiQueryMsg::CanStake { owner, amount, msg };
+        deps.querier.query_wasm_smart(&self.0, &query)
+    }
 }
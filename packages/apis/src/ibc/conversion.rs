@@ -0,0 +1,102 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Coin, Decimal};
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum ConversionError {
+    #[error("Conversion rate must be positive")]
+    ZeroRate,
+    #[error("Overflow converting {0}")]
+    Overflow(Coin),
+}
+
+/// Amount of target tokens a single source token converts to, as between the provider denom
+/// carried in `ProviderPacket::Stake` and the denom staked on the consumer. Never zero.
+#[cw_serde]
+#[derive(Copy)]
+pub struct ConversionRate(Decimal);
+
+impl ConversionRate {
+    pub fn new(rate: Decimal) -> Result<Self, ConversionError> {
+        if rate.is_zero() {
+            return Err(ConversionError::ZeroRate);
+        }
+        Ok(Self(rate))
+    }
+
+    pub fn rate(&self) -> Decimal {
+        self.0
+    }
+
+    /// Converts `coin` to `target_denom`. The converted amount is rounded down, so converting
+    /// never creates tokens, and amounts worth less than a unit of the target denom convert to
+    /// zero.
+    pub fn convert(&self, coin: &Coin, target_denom: &str) -> Result<Coin, ConversionError> {
+        let amount = coin
+            .amount
+            .checked_multiply_ratio(self.0.atomics(), Decimal::one().atomics())
+            .map_err(|_| ConversionError::Overflow(coin.clone()))?;
+        Ok(Coin {
+            denom: target_denom.to_owned(),
+            amount,
+        })
+    }
+}
+
+/// Converts `coin` to `target_denom` at `rate`, rounding down. See [`ConversionRate::convert`].
+pub fn convert(coin: &Coin, rate: Decimal, target_denom: &str) -> Result<Coin, ConversionError> {
+    ConversionRate::new(rate)?.convert(coin, target_denom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cosmwasm_std::{coin, Uint128};
+
+    #[test]
+    fn exact_conversion() {
+        let converted = convert(&coin(1000, "uosmo"), Decimal::percent(250), "ujuno").unwrap();
+        assert_eq!(converted, coin(2500, "ujuno"));
+
+        let rate = ConversionRate::new(Decimal::percent(40)).unwrap();
+        assert_eq!(
+            rate.convert(&coin(1000, "uosmo"), "ujuno").unwrap(),
+            coin(400, "ujuno")
+        );
+    }
+
+    #[test]
+    fn conversion_rounds_down() {
+        let rate = ConversionRate::new(Decimal::from_ratio(1u128, 3u128)).unwrap();
+        assert_eq!(
+            rate.convert(&coin(100, "uosmo"), "ujuno").unwrap(),
+            coin(33, "ujuno")
+        );
+        // Less than a unit of the target denom
+        assert_eq!(
+            rate.convert(&coin(2, "uosmo"), "ujuno").unwrap(),
+            coin(0, "ujuno")
+        );
+
+        let converted = convert(&coin(999, "uosmo"), Decimal::permille(1500), "ujuno").unwrap();
+        assert_eq!(converted, coin(1498, "ujuno"));
+    }
+
+    #[test]
+    fn zero_rate_is_rejected() {
+        let err = ConversionRate::new(Decimal::zero()).unwrap_err();
+        assert_eq!(err, ConversionError::ZeroRate);
+        let err = convert(&coin(100, "uosmo"), Decimal::zero(), "ujuno").unwrap_err();
+        assert_eq!(err, ConversionError::ZeroRate);
+    }
+
+    #[test]
+    fn overflow_is_reported() {
+        let big = Coin {
+            denom: "uosmo".to_owned(),
+            amount: Uint128::MAX,
+        };
+        let err = convert(&big, Decimal::percent(200), "ujuno").unwrap_err();
+        assert_eq!(err, ConversionError::Overflow(big));
+    }
+}
@@ -1,5 +1,7 @@
+mod conversion;
 mod packet;
 mod version;
 
+pub use conversion::*;
 pub use packet::*;
 pub use version::*;
@@ -18,6 +18,7 @@ pub enum ProviderPacket {
         /// of the price feed.
         stake: Coin,
         /// This is local to the sending side to track the transaction, should be passed through opaquely on the consumer
+        /// and echoed in the ack
         tx_id: u64,
     },
     /// This should be called when we begin the unbonding period of some more tokens previously virtually staked
@@ -28,6 +29,23 @@ pub enum ProviderPacket {
         /// of the price feed.
         unstake: Coin,
         /// This is local to the sending side to track the transaction, should be passed through opaquely on the consumer
+        /// and echoed in the ack
+        tx_id: u64,
+    },
+    /// Like `Unstake`, from multiple validators at once. Either all of them are unstaked, or none
+    UnstakeBatch {
+        unstakes: Vec<UnstakeInfo>,
+        /// This is local to the sending side to track the transaction, should be passed through opaquely on the consumer
+        /// and echoed in the ack
+        tx_id: u64,
+    },
+    /// Moves stake from some validators to another one. The stake is unbonded from each of
+    /// `from` and bonded on `to_validator`, all or nothing
+    Restake {
+        from: Vec<UnstakeInfo>,
+        to_validator: String,
+        /// This is local to the sending side to track the transaction, should be passed through opaquely on the consumer
+        /// and echoed in the ack
         tx_id: u64,
     },
     /// This is part of the rewards protocol
@@ -39,20 +57,66 @@ pub enum ProviderPacket {
         /// This is local to the sending side to track the transaction, should be passed through opaquely on the consumer
         tx_id: u64,
     },
+    /// Asks for the whole consumer validator set, to recover from a lost `AddValidators` packet.
+    /// The validators are sent back in the ack
+    RequestValidators {},
 }
 
 /// Ack sent for ProviderPacket::Stake
 #[cw_serde]
-pub struct StakeAck {}
+pub struct StakeAck {
+    /// The `tx_id` of the packet, echoed for relayers to correlate the ack with the provider tx.
+    /// None for consumers not echoing it yet
+    #[serde(default)]
+    pub tx_id: Option<u64>,
+}
 
 /// Ack sent for ProviderPacket::Unstake
 #[cw_serde]
-pub struct UnstakeAck {}
+pub struct UnstakeAck {
+    /// The `tx_id` of the packet, echoed for relayers to correlate the ack with the provider tx.
+    /// None for consumers not echoing it yet
+    #[serde(default)]
+    pub tx_id: Option<u64>,
+}
+
+/// Single unstake of a ProviderPacket::UnstakeBatch
+#[cw_serde]
+pub struct UnstakeInfo {
+    pub validator: String,
+    /// This is the local (provider-side) denom that is held in the vault.
+    pub unstake: Coin,
+}
+
+/// Ack sent for ProviderPacket::UnstakeBatch
+#[cw_serde]
+pub struct UnstakeBatchAck {
+    /// The `tx_id` of the packet, echoed for relayers to correlate the ack with the provider tx.
+    /// None for consumers not echoing it yet
+    #[serde(default)]
+    pub tx_id: Option<u64>,
+}
+
+/// Ack sent for ProviderPacket::Restake
+#[cw_serde]
+pub struct RestakeAck {
+    /// The `tx_id` of the packet, echoed for relayers to correlate the ack with the provider tx.
+    /// None for consumers not echoing it yet
+    #[serde(default)]
+    pub tx_id: Option<u64>,
+}
 
 /// Ack sent for ProviderPacket::TransferRewards
 #[cw_serde]
 pub struct TransferRewardsAck {}
 
+/// Ack sent for ProviderPacket::RequestValidators
+#[cw_serde]
+pub struct RequestValidatorsAck {
+    /// The consumer active validators, with the current height / time as start height / time
+    pub validators: Vec<AddValidator>,
+}
+
 /// These are messages sent from consumer -> provider
 /// ibc_packet_receive in external-staking must handle them all.
 #[cw_serde]
@@ -69,6 +133,11 @@ pub enum ConsumerPacket {
     /// It contains a list of `valoper_address` to be slashed for temporary jailing, along with the
     /// jail event's block height.
     JailValidators(Vec<RemoveValidator>),
+    /// This is sent when a jailed validator is unjailed, and is available to receive
+    /// delegations again.
+    /// It contains a list of `valoper_address` to be unjailed, along with the unjail event's
+    /// block height.
+    UnjailValidators(Vec<RemoveValidator>),
     /// This is part of the rewards protocol
     Distribute {
         /// The validator whose stakers should receive these rewards
@@ -90,9 +159,9 @@ pub struct AddValidator {
     /// This is the validator operator (valoper) address used for delegations and rewards
     pub valoper: String,
 
-    // TODO: is there a better type for this? what encoding is used
-    /// This is the *Tendermint* public key, used for signing blocks.
-    /// This is needed to detect slashing conditions
+    /// This is the *Tendermint* ed25519 public key, used for signing blocks, base64 encoded.
+    /// This is needed to detect slashing conditions. Not available to consumers yet, which send a
+    /// placeholder instead: evidence can't be checked against those validators
     pub pub_key: String,
 
     /// This is the first height the validator was active.
@@ -105,11 +174,26 @@ pub struct AddValidator {
     pub start_time: u64,
 }
 
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum ValidatorError {
+    #[error("Invalid valoper address: {0}")]
+    InvalidValoper(String),
+}
+
 impl AddValidator {
+    /// Checks the valoper is a bech32 address. The pubkey is not checked, as consumers can't
+    /// query it yet
+    pub fn validate(&self) -> Result<(), ValidatorError> {
+        match bech32::decode(&self.valoper) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(ValidatorError::InvalidValoper(self.valoper.clone())),
+        }
+    }
+
     pub fn mock(valoper: &str) -> Self {
         Self {
             valoper: valoper.to_string(),
-            pub_key: "mock-pubkey".to_string(),
+            pub_key: "AQIDBAUGBwgJCgsMDQ4PEBESExQVFhcYGRobHB0eHyA=".to_string(),
             start_height: 12345,
             start_time: 1687357499,
         }
@@ -144,6 +228,10 @@ pub struct RemoveValidatorsAck {}
 #[cw_serde]
 pub struct JailValidatorsAck {}
 
+/// Ack sent for ConsumerPacket::UnjailValidators
+#[cw_serde]
+pub struct UnjailValidatorsAck {}
+
 /// Ack sent for ConsumerPacket::Distribute and ConsumerPacket::DistributeBatch
 #[cw_serde]
 pub struct DistributeAck {}
@@ -169,3 +257,59 @@ pub fn ack_fail<E: Error>(err: E) -> StdResult<Binary> {
     let res = AckWrapper::Error(err.to_string());
     to_binary(&res)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cosmwasm_std::{from_binary, from_slice};
+
+    #[test]
+    fn request_validators_serde() {
+        let packet = ProviderPacket::RequestValidators {};
+        let data = to_binary(&packet).unwrap();
+        assert_eq!(data.as_slice(), br#"{"request_validators":{}}"#);
+        assert_eq!(from_slice::<ProviderPacket>(&data).unwrap(), packet);
+
+        let ack = RequestValidatorsAck {
+            validators: vec![AddValidator::mock("alice")],
+        };
+        let data = ack_success(&ack).unwrap();
+        match from_slice(&data).unwrap() {
+            AckWrapper::Result(res) => {
+                assert_eq!(from_binary::<RequestValidatorsAck>(&res).unwrap(), ack)
+            }
+            AckWrapper::Error(err) => panic!("unexpected error ack {err}"),
+        }
+    }
+
+    #[test]
+    fn stake_ack_serde() {
+        let packet = ProviderPacket::Stake {
+            validator: "alice".to_owned(),
+            stake: cosmwasm_std::coin(100, "osmo"),
+            tx_id: 7,
+        };
+        let data = to_binary(&packet).unwrap();
+        assert_eq!(from_slice::<ProviderPacket>(&data).unwrap(), packet);
+
+        let ack = StakeAck { tx_id: Some(7) };
+        let data = to_binary(&ack).unwrap();
+        assert_eq!(data.as_slice(), br#"{"tx_id":7}"#);
+        assert_eq!(from_slice::<StakeAck>(&data).unwrap(), ack);
+
+        // Acks of consumers not echoing the tx id are still accepted
+        assert_eq!(
+            from_slice::<StakeAck>(b"{}").unwrap(),
+            StakeAck { tx_id: None }
+        );
+        assert_eq!(
+            from_slice::<UnstakeAck>(b"{}").unwrap(),
+            UnstakeAck { tx_id: None }
+        );
+        assert_eq!(
+            from_slice::<UnstakeBatchAck>(b"{}").unwrap(),
+            UnstakeBatchAck { tx_id: None }
+        );
+    }
+}
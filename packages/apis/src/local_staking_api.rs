@@ -1,5 +1,8 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{to_binary, Addr, Binary, Coin, Decimal, Deps, Response, StdError, WasmMsg};
+use cosmwasm_std::{
+    to_binary, Addr, Binary, Coin, Decimal, Deps, Response, StdError, Uint128, WasmMsg,
+};
+use cw20::Cw20ExecuteMsg;
 use sylvia::types::{ExecCtx, QueryCtx};
 use sylvia::{interface, schemars};
 
@@ -30,11 +33,33 @@ pub trait LocalStakingApi {
         msg: Binary,
     ) -> Result<Response, Self::Error>;
 
+    /// Unstakes all of the owner's stake. Can only be called by the vault, in emergencies.
+    /// The tokens are returned to the vault through `release_local_stake`, once unbonded.
+    #[msg(exec)]
+    fn emergency_unstake(&self, ctx: ExecCtx, owner: String) -> Result<Response, Self::Error>;
+
     /// Returns the maximum percentage that can be slashed
     #[msg(query)]
     fn max_slash(&self, ctx: QueryCtx) -> Result<MaxSlashResponse, Self::Error>;
 }
 
+/// The message that is binary encoded in `receive_stake(..msg)` by local staking contracts
+/// delegating the stake to a validator
+#[cw_serde]
+pub struct StakeMsg {
+    /// Validator to stake on. If omitted, the staking contract's default validator is used
+    #[serde(default)]
+    pub validator: Option<String>,
+}
+
+/// Cw20 receive hook message of local staking contracts accepting cw20 collateral. The vault
+/// sends it along with the tokens, as the `msg` of a cw20 `Send`
+#[cw_serde]
+pub enum LocalStakingCw20HookMsg {
+    /// Same as `receive_stake`, with the sent cw20 tokens as stake
+    ReceiveStake { owner: String, msg: Binary },
+}
+
 #[cw_serde]
 pub struct LocalStakingApiHelper(pub Addr);
 
@@ -61,6 +86,42 @@ impl LocalStakingApiHelper {
         Ok(wasm)
     }
 
+    /// Like `receive_stake`, for cw20 collateral. The tokens are sent through the cw20 contract
+    pub fn receive_cw20_stake(
+        &self,
+        // address of the cw20 collateral contract
+        cw20: &Addr,
+        // address of the user who originally called stake_local
+        owner: String,
+        // custom to each implementation and opaque to the vault
+        msg: Binary,
+        // amount to stake on that contract
+        amount: Uint128,
+    ) -> Result<WasmMsg, StdError> {
+        let hook = LocalStakingCw20HookMsg::ReceiveStake { owner, msg };
+        let msg = Cw20ExecuteMsg::Send {
+            contract: self.0.to_string(),
+            amount,
+            msg: to_binary(&hook)?,
+        };
+        let wasm = WasmMsg::Execute {
+            contract_addr: cw20.to_string(),
+            msg: to_binary(&msg)?,
+            funds: vec![],
+        };
+        Ok(wasm)
+    }
+
+    pub fn emergency_unstake(&self, owner: String) -> Result<WasmMsg, StdError> {
+        let msg = LocalStakingApiExecMsg::EmergencyUnstake { owner };
+        let wasm = WasmMsg::Execute {
+            contract_addr: self.0.to_string(),
+            msg: to_binary(&msg)?,
+            funds: vec![],
+        };
+        Ok(wasm)
+    }
+
     pub fn max_slash(&self, deps: Deps) -> Result<MaxSlashResponse, StdError> {
         let query = LocalStakingApiQueryMsg::MaxSlash {};
         deps.querier.query_wasm_smart(&self.0, &query)
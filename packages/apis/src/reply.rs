@@ -0,0 +1,46 @@
+/// Ids of the submessages replies handled by the mesh contracts.
+///
+/// Replies are processed in the same transaction as their submessage, so the ids are never
+/// stored and can be renumbered between releases.
+#[repr(u64)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReplyId {
+    /// Instantiation of a contract managed by the sender, eg. the local staking contract by the
+    /// vault
+    Instantiate = 1,
+    /// Withdrawal of the rewards of a validator by the virtual staking contract
+    Rewards = 2,
+}
+
+impl From<ReplyId> for u64 {
+    fn from(id: ReplyId) -> Self {
+        id as u64
+    }
+}
+
+impl TryFrom<u64> for ReplyId {
+    /// The unknown id
+    type Error = u64;
+
+    fn try_from(id: u64) -> Result<Self, Self::Error> {
+        match id {
+            1 => Ok(ReplyId::Instantiate),
+            2 => Ok(ReplyId::Rewards),
+            _ => Err(id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reply_ids_round_trip() {
+        for id in [ReplyId::Instantiate, ReplyId::Rewards] {
+            assert_eq!(ReplyId::try_from(u64::from(id)), Ok(id));
+        }
+        assert_eq!(ReplyId::try_from(0), Err(0));
+        assert_eq!(ReplyId::try_from(3), Err(3));
+    }
+}
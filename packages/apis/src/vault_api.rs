@@ -1,5 +1,6 @@
 use cosmwasm_schema::cw_serde;
 use cosmwasm_std::{to_binary, Addr, Coin, Response, StdError, Uint128, WasmMsg};
+use cw20::Cw20ExecuteMsg;
 use sylvia::types::ExecCtx;
 use sylvia::{interface, schemars};
 
@@ -20,6 +21,35 @@ pub trait VaultApi {
         amount: Coin,
     ) -> Result<Response, Self::Error>;
 
+    /// Like `release_cross_stake`, but the released collateral is also unbonded from the owner's
+    /// account and sent to the recipient (eg. when the owner migrates to another wallet).
+    #[msg(exec)]
+    fn release_cross_stake_to(
+        &self,
+        ctx: ExecCtx,
+        // address of the user who originally called stake_remote
+        owner: String,
+        // address of the user getting the released tokens
+        recipient: String,
+        // amount to unstake on that contract
+        amount: Coin,
+    ) -> Result<Response, Self::Error>;
+
+    /// Like `release_cross_stake`, or `release_cross_stake_to` with a recipient, for tokens whose
+    /// unbonding period is over. They are released whatever the vault min release, so that they
+    /// are never stuck in the lien.
+    #[msg(exec)]
+    fn release_matured_cross_stake(
+        &self,
+        ctx: ExecCtx,
+        // address of the user who originally called stake_remote
+        owner: String,
+        // amount to unstake on that contract
+        amount: Coin,
+        // address of the user getting the released tokens, if they are to be unbonded
+        recipient: Option<String>,
+    ) -> Result<Response, Self::Error>;
+
     /// This must be called by the local staking contract to release this claim
     /// Amount of tokens unstaked are those included in ctx.info.funds
     #[msg(exec)]
@@ -30,6 +60,33 @@ pub trait VaultApi {
         owner: String,
     ) -> Result<Response, Self::Error>;
 
+    /// This must be called by the local staking contract when rewards are staked back on behalf
+    /// of the owner. The staked tokens become collateral of the owner, under the local claim,
+    /// so that they can be released like the rest of the stake.
+    #[msg(exec)]
+    fn add_local_stake(
+        &self,
+        ctx: ExecCtx,
+        // address of the user who originally called stake_local
+        owner: String,
+        // amount staked back
+        amount: Coin,
+    ) -> Result<Response, Self::Error>;
+
+    /// This must be called by the remote staking contract to move part of the owner's claim to
+    /// the recipient, along with the collateral it covers (eg. when a pending unbond is sold).
+    #[msg(exec)]
+    fn transfer_cross_stake(
+        &self,
+        ctx: ExecCtx,
+        // address of the user who originally called stake_remote
+        owner: String,
+        // address of the user getting the claim
+        recipient: String,
+        // amount of the claim to move
+        amount: Coin,
+    ) -> Result<Response, Self::Error>;
+
     /// This must be called by the remote staking contract to commit the remote staking call on success.
     /// Transaction ID is used to identify the original (vault contract originated) transaction.
     #[msg(exec)]
@@ -40,10 +97,43 @@ pub trait VaultApi {
     #[msg(exec)]
     fn rollback_tx(&self, ctx: ExecCtx, tx_id: u64) -> Result<Response, Self::Error>;
 
+    /// Like `commit_tx`, for a batch of transactions. Either all of them are committed, or none.
+    #[msg(exec)]
+    fn commit_txs(&self, ctx: ExecCtx, tx_ids: Vec<u64>) -> Result<Response, Self::Error>;
+
+    /// Like `rollback_tx`, for a batch of transactions. Either all of them are rolled back, or none.
+    #[msg(exec)]
+    fn rollback_txs(&self, ctx: ExecCtx, tx_ids: Vec<u64>) -> Result<Response, Self::Error>;
+
     /// This must be called by the external staking contract to process a slashing event
     /// because of a misbehaviour on the Consumer chain
     #[msg(exec)]
     fn cross_slash(&self, ctx: ExecCtx, slashes: Vec<SlashInfo>) -> Result<Response, Self::Error>;
+
+    /// Like `cross_slash`, also paying a bounty out of the slashed collateral to the recipient
+    /// (eg. the submitter of the misbehaviour evidence). The bounty is capped to the vault
+    /// available balance.
+    #[msg(exec)]
+    fn cross_slash_with_bounty(
+        &self,
+        ctx: ExecCtx,
+        slashes: Vec<SlashInfo>,
+        // address of the bounty recipient
+        recipient: String,
+        // bounty amount, in the collateral denom
+        bounty: Uint128,
+    ) -> Result<Response, Self::Error>;
+
+    /// Like `cross_slash`, also making each of the payouts out of the slashed collateral, in
+    /// order, as far as it and the vault available balance allow. This is how the lienholder gets
+    /// back the part of the slash it redistributes to its stakers.
+    #[msg(exec)]
+    fn cross_slash_with_payouts(
+        &self,
+        ctx: ExecCtx,
+        slashes: Vec<SlashInfo>,
+        payouts: Vec<SlashPayout>,
+    ) -> Result<Response, Self::Error>;
 }
 
 #[cw_serde]
@@ -52,6 +142,23 @@ pub struct SlashInfo {
     pub slash: Uint128,
 }
 
+#[cw_serde]
+pub struct SlashPayout {
+    pub recipient: String,
+    /// Amount in the collateral denom
+    pub amount: Uint128,
+}
+
+/// Cw20 receive hook message of the vault, when its collateral is a cw20 token
+#[cw_serde]
+pub enum VaultCw20HookMsg {
+    /// Bonds the sent tokens as collateral of the sender
+    Bond {},
+    /// Same as `release_local_stake`, with the sent tokens as released stake.
+    /// Has to be sent by the local staking contract
+    ReleaseLocalStake { owner: String },
+}
+
 #[cw_serde]
 pub struct VaultApiHelper(pub Addr);
 
@@ -77,6 +184,50 @@ impl VaultApiHelper {
         Ok(wasm)
     }
 
+    pub fn release_matured_cross_stake(
+        &self,
+        // address of the user who originally called stake_remote
+        owner: String,
+        // amount to unstake on that contract
+        amount: Coin,
+        // address of the user getting the released tokens, if they are to be unbonded
+        recipient: Option<String>,
+    ) -> Result<WasmMsg, StdError> {
+        let msg = VaultApiExecMsg::ReleaseMaturedCrossStake {
+            owner,
+            amount,
+            recipient,
+        };
+        let wasm = WasmMsg::Execute {
+            contract_addr: self.0.to_string(),
+            msg: to_binary(&msg)?,
+            funds: vec![],
+        };
+        Ok(wasm)
+    }
+
+    pub fn release_cross_stake_to(
+        &self,
+        // address of the user who originally called stake_remote
+        owner: String,
+        // address of the user getting the released tokens
+        recipient: String,
+        // amount to unstake on that contract
+        amount: Coin,
+    ) -> Result<WasmMsg, StdError> {
+        let msg = VaultApiExecMsg::ReleaseCrossStakeTo {
+            owner,
+            recipient,
+            amount,
+        };
+        let wasm = WasmMsg::Execute {
+            contract_addr: self.0.to_string(),
+            msg: to_binary(&msg)?,
+            funds: vec![],
+        };
+        Ok(wasm)
+    }
+
     pub fn release_local_stake(
         &self,
         // address of the user who originally called stake_remote
@@ -93,6 +244,69 @@ impl VaultApiHelper {
         Ok(wasm)
     }
 
+    /// Like `release_local_stake`, for cw20 collateral. The tokens are sent through the cw20
+    /// contract
+    pub fn release_cw20_local_stake(
+        &self,
+        // address of the cw20 collateral contract
+        cw20: &Addr,
+        // address of the user who originally called stake_remote
+        owner: String,
+        // tokens to send along with this
+        amount: Uint128,
+    ) -> Result<WasmMsg, StdError> {
+        let hook = VaultCw20HookMsg::ReleaseLocalStake { owner };
+        let msg = Cw20ExecuteMsg::Send {
+            contract: self.0.to_string(),
+            amount,
+            msg: to_binary(&hook)?,
+        };
+        let wasm = WasmMsg::Execute {
+            contract_addr: cw20.to_string(),
+            msg: to_binary(&msg)?,
+            funds: vec![],
+        };
+        Ok(wasm)
+    }
+
+    pub fn add_local_stake(
+        &self,
+        // address of the user who originally called stake_local
+        owner: String,
+        // amount staked back
+        amount: Coin,
+    ) -> Result<WasmMsg, StdError> {
+        let msg = VaultApiExecMsg::AddLocalStake { owner, amount };
+        let wasm = WasmMsg::Execute {
+            contract_addr: self.0.to_string(),
+            msg: to_binary(&msg)?,
+            funds: vec![],
+        };
+        Ok(wasm)
+    }
+
+    pub fn transfer_cross_stake(
+        &self,
+        // address of the user who originally called stake_remote
+        owner: String,
+        // address of the user getting the claim
+        recipient: String,
+        // amount of the claim to move
+        amount: Coin,
+    ) -> Result<WasmMsg, StdError> {
+        let msg = VaultApiExecMsg::TransferCrossStake {
+            owner,
+            recipient,
+            amount,
+        };
+        let wasm = WasmMsg::Execute {
+            contract_addr: self.0.to_string(),
+            msg: to_binary(&msg)?,
+            funds: vec![],
+        };
+        Ok(wasm)
+    }
+
     pub fn process_cross_slashing(&self, slashes: Vec<SlashInfo>) -> Result<WasmMsg, StdError> {
         let msg = VaultApiExecMsg::CrossSlash { slashes };
         let wasm = WasmMsg::Execute {
@@ -103,6 +317,39 @@ impl VaultApiHelper {
         Ok(wasm)
     }
 
+    pub fn process_cross_slashing_with_bounty(
+        &self,
+        slashes: Vec<SlashInfo>,
+        recipient: String,
+        bounty: Uint128,
+    ) -> Result<WasmMsg, StdError> {
+        let msg = VaultApiExecMsg::CrossSlashWithBounty {
+            slashes,
+            recipient,
+            bounty,
+        };
+        let wasm = WasmMsg::Execute {
+            contract_addr: self.0.to_string(),
+            msg: to_binary(&msg)?,
+            funds: vec![],
+        };
+        Ok(wasm)
+    }
+
+    pub fn process_cross_slashing_with_payouts(
+        &self,
+        slashes: Vec<SlashInfo>,
+        payouts: Vec<SlashPayout>,
+    ) -> Result<WasmMsg, StdError> {
+        let msg = VaultApiExecMsg::CrossSlashWithPayouts { slashes, payouts };
+        let wasm = WasmMsg::Execute {
+            contract_addr: self.0.to_string(),
+            msg: to_binary(&msg)?,
+            funds: vec![],
+        };
+        Ok(wasm)
+    }
+
     pub fn commit_tx(&self, tx_id: u64) -> Result<WasmMsg, StdError> {
         let msg = VaultApiExecMsg::CommitTx { tx_id };
         let wasm = WasmMsg::Execute {
@@ -122,4 +369,24 @@ impl VaultApiHelper {
         };
         Ok(wasm)
     }
+
+    pub fn commit_txs(&self, tx_ids: Vec<u64>) -> Result<WasmMsg, StdError> {
+        let msg = VaultApiExecMsg::CommitTxs { tx_ids };
+        let wasm = WasmMsg::Execute {
+            contract_addr: self.0.to_string(),
+            msg: to_binary(&msg)?,
+            funds: vec![],
+        };
+        Ok(wasm)
+    }
+
+    pub fn rollback_txs(&self, tx_ids: Vec<u64>) -> Result<WasmMsg, StdError> {
+        let msg = VaultApiExecMsg::RollbackTxs { tx_ids };
+        let wasm = WasmMsg::Execute {
+            contract_addr: self.0.to_string(),
+            msg: to_binary(&msg)?,
+            funds: vec![],
+        };
+        Ok(wasm)
+    }
 }
@@ -0,0 +1,134 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Order, StdResult, Storage, Timestamp};
+use cw_storage_plus::{Bound, Deque, Map};
+
+use crate::Tx;
+
+/// Default number of resolved txs kept in the history
+pub const DEFAULT_TX_HISTORY_LEN: u32 = 500;
+
+/// Outcome of a resolved tx
+#[cw_serde]
+pub struct TxResult {
+    /// Transaction id
+    pub id: u64,
+    /// Whether the tx was committed or rolled back
+    pub committed: bool,
+    /// Block time of the resolution
+    pub resolved_at: Timestamp,
+}
+
+/// State of a tx, pending or recently resolved
+#[cw_serde]
+pub enum TxStatus {
+    Pending(Tx),
+    Resolved(TxResult),
+}
+
+/// Bounded history of the resolved txs. Once full, the earliest resolved txs are pruned first.
+pub struct TxHistory<'a> {
+    results: Map<'a, u64, TxResult>,
+    /// Resolved tx ids, in resolution order
+    order: Deque<'a, u64>,
+    max_len: u32,
+}
+
+impl<'a> TxHistory<'a> {
+    pub const fn new(results_key: &'a str, order_key: &'a str, max_len: u32) -> Self {
+        Self {
+            results: Map::new(results_key),
+            order: Deque::new(order_key),
+            max_len,
+        }
+    }
+
+    /// Records the outcome of a tx, pruning the earliest resolved one if the history is full
+    pub fn record(
+        &self,
+        storage: &mut dyn Storage,
+        id: u64,
+        committed: bool,
+        resolved_at: Timestamp,
+    ) -> StdResult<()> {
+        let result = TxResult {
+            id,
+            committed,
+            resolved_at,
+        };
+        self.results.save(storage, id, &result)?;
+        self.order.push_back(storage, &id)?;
+
+        if self.order.len(storage)? > self.max_len {
+            if let Some(pruned) = self.order.pop_front(storage)? {
+                self.results.remove(storage, pruned);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn may_load(&self, storage: &dyn Storage, id: u64) -> StdResult<Option<TxResult>> {
+        self.results.may_load(storage, id)
+    }
+
+    /// Paginates over the resolved txs, most recent ids first.
+    ///
+    /// `start_after` is the last tx id included in previous page
+    pub fn range_desc(
+        &self,
+        storage: &dyn Storage,
+        start_after: Option<u64>,
+        limit: usize,
+    ) -> StdResult<Vec<TxResult>> {
+        let bound = start_after.map(Bound::exclusive);
+        self.results
+            .range(storage, None, bound, Order::Descending)
+            .map(|item| item.map(|(_, result)| result))
+            .take(limit)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cosmwasm_std::testing::MockStorage;
+
+    #[test]
+    fn history_is_pruned() {
+        let mut storage = MockStorage::new();
+        let history = TxHistory::new("results", "order", 3);
+        let time = Timestamp::from_seconds(100);
+
+        // Resolved out of order
+        for (id, committed) in [(2, true), (1, false), (4, true)] {
+            history.record(&mut storage, id, committed, time).unwrap();
+        }
+        let results = history.range_desc(&storage, None, 10).unwrap();
+        let ids: Vec<_> = results.iter().map(|result| result.id).collect();
+        assert_eq!(ids, [4, 2, 1]);
+        assert!(!history.may_load(&storage, 1).unwrap().unwrap().committed);
+
+        // Earliest resolved tx is pruned first
+        history.record(&mut storage, 3, true, time).unwrap();
+        let ids: Vec<_> = history
+            .range_desc(&storage, None, 10)
+            .unwrap()
+            .iter()
+            .map(|result| result.id)
+            .collect();
+        assert_eq!(ids, [4, 3, 1]);
+        assert_eq!(history.may_load(&storage, 2).unwrap(), None);
+
+        // Pagination
+        let page = history.range_desc(&storage, Some(4), 1).unwrap();
+        assert_eq!(
+            page,
+            [TxResult {
+                id: 3,
+                committed: true,
+                resolved_at: time,
+            }]
+        );
+    }
+}
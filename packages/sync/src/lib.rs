@@ -1,9 +1,12 @@
+mod history;
 mod locks;
 mod range;
 mod txs;
 
+pub use history::{TxHistory, TxResult, TxStatus, DEFAULT_TX_HISTORY_LEN};
 pub use locks::{LockError, LockState, Lockable};
 pub use range::{
-    max_range, min_range, reduce_max_range, reduce_min_range, spread, RangeError, ValueRange,
+    max_range, min_range, reduce_max_range, reduce_min_range, spread, spread_checked, RangeError,
+    ValueRange,
 };
 pub use txs::Tx;
@@ -6,17 +6,39 @@ use std::{
 use thiserror::Error;
 
 use cosmwasm_schema::cw_serde;
+use serde::Deserialize;
 
 /// This is designed to work with two numeric primitives that can be added, subtracted, and compared.
+///
+/// Serializes as `{"low": .., "high": ..}`. The former `{"l": .., "h": ..}` and `[low, high]`
+/// representations are still accepted when deserializing, so existing state keeps loading.
 #[cw_serde]
 #[derive(Default, Copy)]
+#[serde(from = "ValueRangeRepr<T>")]
 pub struct ValueRange<T> {
-    #[serde(rename = "l")]
     low: T,
-    #[serde(rename = "h")]
     high: T,
 }
 
+/// All the accepted serialized forms of a `ValueRange`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ValueRangeRepr<T> {
+    Named { low: T, high: T },
+    Short { l: T, h: T },
+    Tuple(T, T),
+}
+
+impl<T> From<ValueRangeRepr<T>> for ValueRange<T> {
+    fn from(repr: ValueRangeRepr<T>) -> Self {
+        match repr {
+            ValueRangeRepr::Named { low, high }
+            | ValueRangeRepr::Short { l: low, h: high }
+            | ValueRangeRepr::Tuple(low, high) => Self { low, high },
+        }
+    }
+}
+
 #[derive(Debug, Error, PartialEq, Eq)]
 pub enum RangeError {
     #[error("Underflow minimum value")]
@@ -111,18 +133,28 @@ where
     iter.copied().reduce(min_range).unwrap_or_default()
 }
 
-/// Captures the spread from the lowest low to the highest high
+/// Captures the spread from the lowest low to the highest high.
+///
+/// An empty iterator gives `ValueRange::default()`, which can't be told apart from ranges all
+/// at the default value. Use `spread_checked` when that matters.
 pub fn spread<'a, I, T>(iter: I) -> ValueRange<T>
 where
     I: Iterator<Item = &'a ValueRange<T>> + 'a,
     T: Ord + Copy + Default + 'a,
 {
-    iter.copied()
-        .reduce(|a, b| ValueRange {
-            low: std::cmp::min(a.low(), b.low()),
-            high: std::cmp::max(a.high(), b.high()),
-        })
-        .unwrap_or_default()
+    spread_checked(iter).unwrap_or_default()
+}
+
+/// Captures the spread from the lowest low to the highest high, or `None` for an empty iterator
+pub fn spread_checked<'a, I, T>(iter: I) -> Option<ValueRange<T>>
+where
+    I: Iterator<Item = &'a ValueRange<T>> + 'a,
+    T: Ord + Copy + 'a,
+{
+    iter.copied().reduce(|a, b| ValueRange {
+        low: std::cmp::min(a.low(), b.low()),
+        high: std::cmp::max(a.high(), b.high()),
+    })
 }
 
 impl<T, U> Mul<U> for ValueRange<T>
@@ -179,6 +211,14 @@ where
         self.low >= min
     }
 
+    /// Returns true iff the range is >= value whatever the outcome of the pending changes, that
+    /// is if its guaranteed minimum is. This is the rule for spending a range, like unbonding
+    /// free collateral
+    #[inline]
+    pub fn guaranteed_ge(&self, value: T) -> bool {
+        self.low >= value
+    }
+
     /// This is to be called at the beginning of a transaction, to reserve the ability to commit (or rollback) an addition.
     /// If the last value is set, it enforces that the new maximum will remain under that limit.
     /// Usage: `range.prepare_add(20, None)?;` or `range.prepare_add(20, 100)?;`
@@ -327,10 +367,32 @@ where
 
 #[cfg(test)]
 mod tests {
-    use cosmwasm_std::{Decimal, Uint128};
+    use cosmwasm_std::{from_slice, to_vec, Decimal, Uint128};
 
     use super::*;
 
+    #[test]
+    fn serialization() {
+        let range = ValueRange::new(Uint128::new(50), Uint128::new(80));
+
+        let serialized = to_vec(&range).unwrap();
+        assert_eq!(serialized, br#"{"low":"50","high":"80"}"#);
+        let deserialized: ValueRange<Uint128> = from_slice(&serialized).unwrap();
+        assert_eq!(deserialized, range);
+
+        // Former representations
+        let short: ValueRange<Uint128> = from_slice(br#"{"l":"50","h":"80"}"#).unwrap();
+        assert_eq!(short, range);
+        let tuple: ValueRange<Uint128> = from_slice(br#"["50","80"]"#).unwrap();
+        assert_eq!(tuple, range);
+
+        let range = ValueRange::new(Decimal::percent(10), Decimal::percent(25));
+        let serialized = to_vec(&range).unwrap();
+        assert_eq!(serialized, br#"{"low":"0.1","high":"0.25"}"#);
+        let tuple: ValueRange<Decimal> = from_slice(br#"["0.1","0.25"]"#).unwrap();
+        assert_eq!(tuple, range);
+    }
+
     #[test]
     fn comparisons() {
         // check for one point - it behaves like an integer
@@ -355,6 +417,11 @@ mod tests {
         // all comparisons inside the range lead to false
         assert!(!range.is_under_max(60));
         assert!(!range.is_over_min(60));
+
+        // only the low end is guaranteed
+        assert!(range.guaranteed_ge(50));
+        assert!(!range.guaranteed_ge(51));
+        assert!(!range.guaranteed_ge(80));
     }
 
     #[test]
@@ -436,6 +503,27 @@ mod tests {
         assert_eq!(all, ValueRange::new(40, 380));
     }
 
+    #[test]
+    fn spread_checked_tells_empty_input() {
+        let empty: Vec<ValueRange<u32>> = vec![];
+        assert_eq!(spread_checked(empty.iter()), None);
+        assert_eq!(spread(empty.iter()), ValueRange::new_val(0));
+
+        let single = [ValueRange::new(0, 0)];
+        assert_eq!(spread_checked(single.iter()), Some(ValueRange::new_val(0)));
+        let single = [ValueRange::new(20, 50)];
+        assert_eq!(spread_checked(single.iter()), Some(ValueRange::new(20, 50)));
+        assert_eq!(spread(single.iter()), ValueRange::new(20, 50));
+
+        let many = [
+            ValueRange::new(20, 50),
+            ValueRange::new(10, 30),
+            ValueRange::new(40, 70),
+        ];
+        assert_eq!(spread_checked(many.iter()), Some(ValueRange::new(10, 70)));
+        assert_eq!(spread(many.iter()), ValueRange::new(10, 70));
+    }
+
     // most tests will use i32 for simplicity - just ensure APIs work properly with Uint128
     #[test]
     fn works_with_uint128() {
@@ -500,7 +588,7 @@ mod tests {
     #[test]
     fn invariants_over_set_of_liens() {
         // some existing outstanding liens
-        let liens = vec![
+        let liens = [
             ValueRange::new(Uint128::new(5000), Uint128::new(7000)),
             ValueRange::new(Uint128::new(2000), Uint128::new(8000)),
             ValueRange::new(Uint128::new(3000), Uint128::new(12000)),
@@ -1,5 +1,5 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Addr, Decimal, Uint128};
+use cosmwasm_std::{Addr, Decimal, Timestamp, Uint128};
 use std::fmt::Formatter;
 
 #[cw_serde]
@@ -15,6 +15,9 @@ pub enum Tx {
         user: Addr,
         /// Remote staking contract
         lienholder: Addr,
+        /// Block time the tx was created at. Zero for txs created before it was tracked
+        #[serde(default)]
+        created_at: Timestamp,
     },
     InFlightRemoteStaking {
         /// Transaction id
@@ -35,12 +38,43 @@ pub enum Tx {
         user: Addr,
         /// Remote validator
         validator: String,
+        /// Block time the tx was created at. Zero for txs created before it was tracked
+        #[serde(default)]
+        created_at: Timestamp,
+    },
+    InFlightRemoteUnstakingBatch {
+        /// Transaction id
+        id: u64,
+        /// Associated owner
+        user: Addr,
+        /// Remote validators, with the amount unstaked from each of them
+        unstakes: Vec<(String, Uint128)>,
+        /// Block time the tx was created at. Zero for txs created before it was tracked
+        #[serde(default)]
+        created_at: Timestamp,
+    },
+    /// Stake moved from some remote validators to another one, without leaving the stake
+    InFlightRemoteRestaking {
+        /// Transaction id
+        id: u64,
+        /// Associated owner
+        user: Addr,
+        /// Remote validators, with the amount moved from each of them
+        from: Vec<(String, Uint128)>,
+        /// Remote validator the stake is moved to
+        to: String,
+        /// Block time the tx was created at
+        created_at: Timestamp,
     },
     /// This is stored on the provider side when releasing funds
     InFlightTransferFunds {
         id: u64,
         /// Amount of rewards being withdrawn
         amount: Uint128,
+        /// Denom of rewards being withdrawn. Empty for txs created before multiple rewards denoms
+        /// were supported
+        #[serde(default)]
+        denom: String,
         /// The staker sending the funds
         staker: Addr,
         /// The validator whose rewards they come from (to revert)
@@ -54,6 +88,8 @@ impl Tx {
             Tx::InFlightStaking { id, .. } => *id,
             Tx::InFlightRemoteStaking { id, .. } => *id,
             Tx::InFlightRemoteUnstaking { id, .. } => *id,
+            Tx::InFlightRemoteUnstakingBatch { id, .. } => *id,
+            Tx::InFlightRemoteRestaking { id, .. } => *id,
             Tx::InFlightTransferFunds { id, .. } => *id,
         }
     }